@@ -0,0 +1,314 @@
+// Alerts of the alerts plugin, given as a list in the `alerts` attribute:
+//
+//     - name: overheat
+//       when: "motor/temperature > 80"
+//       severity: critical
+//       message: Motor is too hot
+//       debounce_ms: 2000
+//       actions:
+//         - set: {key: robot/alarm, value: true}
+//         - webhook: http://ops.local:9000/hooks/rtime
+//         - email: ops@example.com
+//
+// An alert fires once its condition, see `interfaces::predicate`, held for `debounce_ms`, and
+// runs its actions. It stays firing, or acknowledged, until the condition does not hold anymore.
+use interfaces::alerts::{AlertState, AlertStatus};
+use interfaces::blackboard::{BlackboardValue, TypedBlackboardValue};
+use interfaces::predicate::Predicate;
+use serde::Deserialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ActionSpec {
+    Set { key: String, value: BlackboardValue },
+    Webhook(String), // url the status is posted to as json
+    Email(String),   // recipient
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AlertSpec {
+    name: String,
+    when: String,
+    #[serde(default = "default_severity")]
+    severity: String,
+    message: Option<String>, // the condition by default
+    #[serde(default)]
+    debounce_ms: u64,
+    #[serde(default)]
+    actions: Vec<ActionSpec>,
+}
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Spec(Vec<AlertSpec>);
+
+impl Spec {
+    /// Reads the alerts from yaml or json.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // serde_yml only reads enums from yaml tags, json takes the maps with one key
+        serde_yml::from_str(text)
+            .map_err(|e| e.to_string())
+            .and_then(Spec::from_json)
+    }
+
+    pub fn from_json(alerts: serde_json::Value) -> Result<Self, String> {
+        serde_json::from_value(alerts).map_err(|e| format!("Invalid alerts: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Set {
+        key: String,
+        value: TypedBlackboardValue,
+    },
+    Webhook(String),
+    Email(String),
+}
+
+/// What an update changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    None,
+    State, // the state changed without firing
+    Fired,
+}
+
+pub struct Alert {
+    name: String,
+    when: Predicate,
+    severity: String,
+    message: String,
+    debounce: Duration,
+    actions: Vec<Action>,
+    state: AlertState,
+    holding_since: Option<Instant>, // of the condition
+    since: u64,
+    fired: u64,
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+/// Fails for invalid conditions or values and for names given twice.
+pub fn alerts(spec: Spec) -> Result<Vec<Alert>, String> {
+    let mut alerts: Vec<Alert> = Vec::new();
+    for alert in spec.0 {
+        if alerts.iter().any(|other| other.name == alert.name) {
+            return Err(format!("Alert {} is given twice", alert.name));
+        }
+        alerts.push(Alert::new(alert)?);
+    }
+    Ok(alerts)
+}
+
+impl Alert {
+    fn new(spec: AlertSpec) -> Result<Self, String> {
+        if spec.name.is_empty() || spec.name.contains('/') {
+            return Err(format!("'{}' is no valid alert name", spec.name));
+        }
+        let when = Predicate::parse(&spec.when)?;
+        let actions = spec
+            .actions
+            .into_iter()
+            .map(|action| {
+                Ok(match action {
+                    ActionSpec::Set { key, value } => Action::Set {
+                        key,
+                        value: value.try_into()?,
+                    },
+                    ActionSpec::Webhook(url) => Action::Webhook(url),
+                    ActionSpec::Email(to) => Action::Email(to),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Alert {
+            name: spec.name,
+            message: spec.message.unwrap_or_else(|| when.to_string()),
+            when,
+            severity: spec.severity,
+            debounce: Duration::from_millis(spec.debounce_ms),
+            actions,
+            state: AlertState::Ok,
+            holding_since: None,
+            since: unix_millis(),
+            fired: 0,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The key the condition reads.
+    pub fn key(&self) -> &str {
+        self.when.key()
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    pub fn state(&self) -> AlertState {
+        self.state
+    }
+
+    fn enter(&mut self, state: AlertState) {
+        self.state = state;
+        self.since = unix_millis();
+    }
+
+    /// Checks the condition against the `value` of its key at `now`.
+    pub fn update(&mut self, value: Option<&TypedBlackboardValue>, now: Instant) -> Change {
+        if !self.when.holds(value) {
+            self.holding_since = None;
+            if self.state == AlertState::Ok {
+                return Change::None;
+            }
+            self.enter(AlertState::Ok);
+            return Change::State;
+        }
+        let holding_since = *self.holding_since.get_or_insert(now);
+        match self.state {
+            AlertState::Ok | AlertState::Pending if now >= holding_since + self.debounce => {
+                self.fired += 1;
+                self.enter(AlertState::Firing);
+                Change::Fired
+            }
+            AlertState::Ok => {
+                self.enter(AlertState::Pending);
+                Change::State
+            }
+            _ => Change::None,
+        }
+    }
+
+    /// When a pending alert fires if its condition still holds.
+    pub fn due(&self) -> Option<Instant> {
+        match (self.state, self.holding_since) {
+            (AlertState::Pending, Some(since)) => Some(since + self.debounce),
+            _ => None,
+        }
+    }
+
+    /// Acknowledges a firing alert, false if it does not fire.
+    pub fn acknowledge(&mut self) -> bool {
+        if self.state != AlertState::Firing {
+            return false;
+        }
+        self.enter(AlertState::Acknowledged);
+        true
+    }
+
+    pub fn status(&self) -> AlertStatus {
+        AlertStatus {
+            name: self.name.clone(),
+            state: self.state,
+            severity: self.severity.clone(),
+            message: self.message.clone(),
+            since: self.since,
+            fired: self.fired,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALERTS: &str = "
+        - name: overheat
+          when: 'motor/temperature > 80'
+          severity: critical
+          debounce_ms: 2000
+          actions:
+            - set: {key: robot/alarm, value: true}
+            - webhook: http://localhost:9000/hooks
+            - email: ops@example.com
+        - name: stopped
+          when: emergency_stop";
+
+    fn temperature(value: f64) -> Option<TypedBlackboardValue> {
+        Some(TypedBlackboardValue::Double(value))
+    }
+
+    #[test]
+    fn test_parse() {
+        let alerts = alerts(Spec::parse(ALERTS).unwrap()).unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].key(), "motor/temperature");
+        assert_eq!(
+            alerts[0].actions(),
+            &[
+                Action::Set {
+                    key: "robot/alarm".to_string(),
+                    value: TypedBlackboardValue::Bool(true)
+                },
+                Action::Webhook("http://localhost:9000/hooks".to_string()),
+                Action::Email("ops@example.com".to_string()),
+            ]
+        );
+        let status = alerts[1].status();
+        assert_eq!(status.severity, "warning");
+        assert_eq!(status.message, "emergency_stop");
+        assert_eq!(status.state, AlertState::Ok);
+
+        let invalid = |text: &str| Spec::parse(text).and_then(super::alerts).is_err();
+        assert!(invalid("[{name: a, when: '<'}]"));
+        assert!(invalid("[{name: a/b, when: x}]"));
+        assert!(invalid("[{name: a, when: x}, {name: a, when: y}]"));
+        assert!(invalid("[{name: a, when: x, actions: [{page: ops}]}]"));
+    }
+
+    #[test]
+    fn test_debounce() {
+        let mut alert = alerts(Spec::parse(ALERTS).unwrap()).unwrap().remove(0);
+        let start = Instant::now();
+        assert_eq!(alert.update(temperature(70.0).as_ref(), start), Change::None);
+        assert_eq!(alert.update(temperature(85.0).as_ref(), start), Change::State);
+        assert_eq!(alert.state(), AlertState::Pending);
+        assert_eq!(alert.due(), Some(start + Duration::from_secs(2)));
+
+        // dropping below resets the debounce
+        let later = start + Duration::from_secs(1);
+        assert_eq!(alert.update(temperature(75.0).as_ref(), later), Change::State);
+        assert_eq!(alert.state(), AlertState::Ok);
+        assert_eq!(alert.update(temperature(85.0).as_ref(), later), Change::State);
+        let almost = later + Duration::from_millis(1999);
+        assert_eq!(alert.update(temperature(90.0).as_ref(), almost), Change::None);
+        let due = alert.due().unwrap();
+        assert_eq!(alert.update(temperature(90.0).as_ref(), due), Change::Fired);
+        assert_eq!(alert.state(), AlertState::Firing);
+        assert_eq!(alert.due(), None);
+        assert_eq!(alert.update(temperature(95.0).as_ref(), due), Change::None);
+        assert_eq!(alert.status().fired, 1);
+    }
+
+    #[test]
+    fn test_acknowledge() {
+        let mut alert = alerts(Spec::parse(ALERTS).unwrap()).unwrap().remove(1);
+        assert!(!alert.acknowledge());
+        let now = Instant::now();
+        let stop = Some(TypedBlackboardValue::Bool(true));
+        // without debounce it fires at once
+        assert_eq!(alert.update(stop.as_ref(), now), Change::Fired);
+        assert!(alert.acknowledge());
+        assert_eq!(alert.state(), AlertState::Acknowledged);
+        assert!(!alert.acknowledge());
+        assert_eq!(alert.update(stop.as_ref(), now), Change::None);
+
+        // resolved, the next firing needs a new acknowledgment
+        assert_eq!(alert.update(None, now), Change::State);
+        assert_eq!(alert.state(), AlertState::Ok);
+        assert_eq!(alert.update(stop.as_ref(), now), Change::Fired);
+        assert_eq!(alert.status().fired, 2);
+    }
+}
@@ -0,0 +1,355 @@
+// Raises alerts on conditions of blackboard keys, see `alert` for their description in the
+// `alerts` attribute. The conditions are checked again whenever one of their keys changes and
+// when a pending alert is due. Fired alerts run their actions, the status of every alert is
+// published to `alerts/<name>`, see `interfaces::alerts`.
+mod alert;
+mod notify;
+
+use alert::{Action, Alert, Change, Spec};
+use interfaces::alerts::{ack_key, acknowledged_alert, alert_key, AlertState};
+use interfaces::blackboard::{
+    BlackboardEntry, BlackboardValue, TypedBlackboardValue, parse_attributes,
+};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::capabilities::Capabilities;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{error, info, warn};
+use notify::Notifier;
+use serde_json::json;
+use std::os::raw::{c_char, c_int};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// how long the worker waits for changes without pending alerts
+const IDLE: Duration = Duration::from_secs(60);
+
+struct Config {
+    alerts: Option<Spec>,
+    smtp_server: String,
+    email_from: String,
+    timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            alerts: None,
+            smtp_server: "localhost:25".to_string(),
+            email_from: "rtime@localhost".to_string(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("alerts", BlackboardValue::String(text)) => {
+                    config.alerts = Some(Spec::parse(text).map_err(invalid)?)
+                }
+                // written as yaml right in the attributes
+                ("alerts", BlackboardValue::Json(alerts)) => {
+                    config.alerts = Some(Spec::from_json(alerts.clone()).map_err(invalid)?)
+                }
+                ("smtp_server", BlackboardValue::String(server)) => {
+                    config.smtp_server = server.clone()
+                }
+                ("email_from", BlackboardValue::String(from)) => config.email_from = from.clone(),
+                ("timeout_ms", BlackboardValue::Int(timeout)) if *timeout > 0 => {
+                    config.timeout = Duration::from_millis(*timeout as u64)
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+struct Watcher {
+    client: BlackboardClient,
+    alerts: Mutex<Vec<Alert>>,
+    notifier: Arc<Notifier>,
+}
+
+impl Watcher {
+    fn publish(&self, alert: &Alert) {
+        let status = serde_json::to_value(alert.status()).unwrap_or_default();
+        let key = alert_key(alert.name());
+        if let Err(e) = self.client.set_value(&key, &TypedBlackboardValue::Json(status)) {
+            warn!("Can not publish {}: {}", key, e);
+        }
+    }
+
+    // runs the actions of a fired alert, notifications on their own thread so a slow server does
+    // not hold up the other alerts
+    fn fire(&self, alert: &Alert) {
+        warn!("Alert {} fired", alert.name());
+        let status = alert.status();
+        for action in alert.actions() {
+            let notification = match action {
+                Action::Set { key, value } => {
+                    if let Err(e) = self.client.set_value(key, value) {
+                        warn!("Alert {} can not set {}: {}", alert.name(), key, e);
+                    }
+                    continue;
+                }
+                action => action.clone(),
+            };
+            let (notifier, status) = (self.notifier.clone(), status.clone());
+            std::thread::spawn(move || {
+                let result = match &notification {
+                    Action::Webhook(url) => notifier.webhook(url, &status),
+                    Action::Email(to) => notifier.email(to, &status),
+                    Action::Set { .. } => Ok(()),
+                };
+                if let Err(e) = result {
+                    warn!("Alert {} can not be sent: {}", status.name, e);
+                }
+            });
+        }
+    }
+
+    // checks every alert and returns when the next pending one is due
+    fn check(&self) -> Option<Instant> {
+        let mut alerts = self.alerts.lock().unwrap();
+        let now = Instant::now();
+        for alert in alerts.iter_mut() {
+            let value = self.client.get_value(alert.key()).ok();
+            match alert.update(value.as_ref(), now) {
+                Change::None => continue,
+                Change::Fired => self.fire(alert),
+                Change::State => info!("Alert {} is {:?}", alert.name(), alert.state()),
+            }
+            self.publish(alert);
+        }
+        alerts.iter().filter_map(Alert::due).min()
+    }
+
+    fn acknowledge(&self, name: &str) {
+        let acknowledged = matches!(
+            self.client.get_value(&ack_key(name)),
+            Ok(TypedBlackboardValue::Bool(true))
+        );
+        if !acknowledged {
+            return;
+        }
+        // consumed, so the next firing needs a new acknowledgment
+        let reset = TypedBlackboardValue::Bool(false);
+        if let Err(e) = self.client.set_value(&ack_key(name), &reset) {
+            warn!("Can not reset {}: {}", ack_key(name), e);
+        }
+        let mut alerts = self.alerts.lock().unwrap();
+        let Some(alert) = alerts.iter_mut().find(|alert| alert.name() == name) else {
+            return;
+        };
+        if alert.acknowledge() {
+            info!("Alert {} is acknowledged", name);
+            self.publish(alert);
+        }
+    }
+
+    // checks at the start, on every change and when pending alerts are due until the
+    // subscriptions are dropped
+    fn run(&self, changes: mpsc::Receiver<String>) {
+        // every alert is listed from the start, also the ones that never fire
+        for alert in self.alerts.lock().unwrap().iter() {
+            self.publish(alert);
+        }
+        let mut due = self.check();
+        loop {
+            let wait = due.map_or(IDLE, |due| due.saturating_duration_since(Instant::now()));
+            let changed = match changes.recv_timeout(wait) {
+                Ok(key) => Some(key),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            // one check covers all conditions, only acknowledgments are handled one by one
+            for key in changed.into_iter().chain(changes.try_iter()) {
+                if let Some(name) = acknowledged_alert(&key) {
+                    self.acknowledge(name);
+                }
+            }
+            due = self.check();
+        }
+    }
+
+    fn count(&self, state: AlertState) -> usize {
+        let alerts = self.alerts.lock().unwrap();
+        alerts.iter().filter(|alert| alert.state() == state).count()
+    }
+}
+
+struct WatcherState {
+    watcher: Arc<Watcher>,
+    subscriptions: Vec<Subscription>,
+    worker: JoinHandle<()>,
+}
+
+static WATCHER_STATE: Mutex<Option<WatcherState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "alerts",
+    summary = "raises alerts on blackboard conditions and notifies about them",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        alerts_start = start: "i32(caps,cstr)",
+        alerts_stop = stop: "i32()",
+        alerts_health = health: "i32()",
+        alerts_state = state: "i32()",
+        alerts_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_watcher(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = WATCHER_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Alerts are already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    let spec = config
+        .alerts
+        .ok_or_else(|| RtError::new(RtStatus::InvalidArgument, "No alerts are given"))?;
+    let alerts =
+        alert::alerts(spec).map_err(|e| RtError::new(RtStatus::InvalidArgument, e))?;
+    let mut keys: Vec<String> = alerts
+        .iter()
+        .flat_map(|alert| [alert.key().to_string(), ack_key(alert.name())])
+        .collect();
+    keys.sort();
+    keys.dedup();
+    let watcher = Arc::new(Watcher {
+        client: BlackboardClient::new(Capabilities::from_raw(caps)),
+        alerts: Mutex::new(alerts),
+        notifier: Arc::new(Notifier {
+            smtp_server: config.smtp_server,
+            email_from: config.email_from,
+            timeout: config.timeout,
+        }),
+    });
+
+    let (subscriptions, changes) = watcher.client.subscribe_forwarding(&keys, "alerts")?;
+
+    let running = watcher.clone();
+    *state = Some(WatcherState {
+        watcher,
+        subscriptions,
+        worker: std::thread::spawn(move || running.run(changes)),
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "alerts");
+    match LIFECYCLE.started(catch_panic(|| start_watcher(caps, attributes))) {
+        Ok(()) => {
+            info!("Alerts started");
+            0
+        }
+        Err(e) => {
+            error!("Error starting alerts: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_watcher() -> Result<(), RtError> {
+    let state = WATCHER_STATE.lock().unwrap().take();
+    let state =
+        state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Alerts are not running"))?;
+    // the worker ends once no subscription can send anymore
+    drop(state.subscriptions);
+    let _ = state.worker.join();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_watcher)) {
+        Ok(()) => {
+            info!("Alerts stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping alerts: {}", e);
+            e.record()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match WATCHER_STATE.lock().unwrap().as_ref() {
+        Some(state) if state.worker.is_finished() => {
+            RtError::new(RtStatus::Error, "Watcher ended unexpectedly").record()
+        }
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the alerts, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the number of pending, firing and acknowledged alerts as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match WATCHER_STATE.lock().unwrap().as_ref() {
+        Some(state) => json!({
+            "pending": state.watcher.count(AlertState::Pending),
+            "firing": state.watcher.count(AlertState::Firing),
+            "acknowledged": state.watcher.count(AlertState::Acknowledged),
+        }),
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let entries: Vec<BlackboardEntry> = serde_yml::from_str(
+            "[{key: alerts, value: '[{name: stop, when: emergency_stop}]'},
+              {key: smtp_server, value: 'mail.local:2525'},
+              {key: timeout_ms, value: 500}]",
+        )
+        .unwrap();
+        let config = Config::new(&entries).unwrap();
+        assert_eq!(alert::alerts(config.alerts.unwrap()).unwrap().len(), 1);
+        assert_eq!(config.smtp_server, "mail.local:2525");
+        assert_eq!(config.email_from, "rtime@localhost");
+        assert_eq!(config.timeout, Duration::from_millis(500));
+
+        let entries: Vec<BlackboardEntry> =
+            serde_yml::from_str("[{key: alerts, value: '[{when: x}]'}]").unwrap();
+        assert!(Config::new(&entries).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+// Notifications of fired alerts. Webhooks get the status of the alert posted as json over plain
+// http, emails go to an smtp relay without authentication, like the one of the site network.
+// Https and authenticated mail servers are reached through such a relay.
+use interfaces::alerts::AlertStatus;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub struct Notifier {
+    pub smtp_server: String, // host:port
+    pub email_from: String,
+    pub timeout: Duration, // of connecting and of every read or write
+}
+
+fn connect(address: &str, timeout: Duration) -> Result<TcpStream, String> {
+    let failed = |e: std::io::Error| format!("Can not connect to {}: {}", address, e);
+    let socket = address
+        .to_socket_addrs()
+        .map_err(failed)?
+        .next()
+        .ok_or_else(|| format!("Can not resolve {}", address))?;
+    let stream = TcpStream::connect_timeout(&socket, timeout).map_err(failed)?;
+    stream.set_read_timeout(Some(timeout)).map_err(failed)?;
+    stream.set_write_timeout(Some(timeout)).map_err(failed)?;
+    Ok(stream)
+}
+
+// host:port and path of an http url
+fn split_url(url: &str) -> Result<(String, &str), String> {
+    let rest = match url.split_once("://") {
+        Some(("http", rest)) => rest,
+        Some((scheme, _)) => return Err(format!("Unsupported scheme '{}', use http", scheme)),
+        None => url,
+    };
+    let (address, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    if address.is_empty() {
+        return Err(format!("No host in url '{}'", url));
+    }
+    match address.contains(':') {
+        true => Ok((address.to_string(), path)),
+        false => Ok((format!("{}:80", address), path)),
+    }
+}
+
+impl Notifier {
+    /// Posts `status` to `url`, fails unless it answers with a 2xx status.
+    pub fn webhook(&self, url: &str, status: &AlertStatus) -> Result<(), String> {
+        let (address, path) = split_url(url)?;
+        let body = serde_json::to_string(status).map_err(|e| e.to_string())?;
+        let mut stream = connect(&address, self.timeout)?;
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            address,
+            body.len(),
+            body
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("Can not send to {}: {}", url, e))?;
+        let mut status_line = String::new();
+        BufReader::new(stream)
+            .read_line(&mut status_line)
+            .map_err(|e| format!("No answer from {}: {}", url, e))?;
+        match status_line.split(' ').nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(format!("{} answered '{}'", url, status_line.trim_end())),
+        }
+    }
+
+    /// Mails `status` to `to` through the smtp server.
+    pub fn email(&self, to: &str, status: &AlertStatus) -> Result<(), String> {
+        let stream = connect(&self.smtp_server, self.timeout)?;
+        let mut writer = stream
+            .try_clone()
+            .map_err(|e| format!("Can not talk to {}: {}", self.smtp_server, e))?;
+        let mut reader = BufReader::new(stream);
+        let mut expect = |code: &str| -> Result<(), String> {
+            // the last line of a reply has a space after the code
+            loop {
+                let mut line = String::new();
+                reader
+                    .read_line(&mut line)
+                    .map_err(|e| format!("No reply from {}: {}", self.smtp_server, e))?;
+                if !line.starts_with(code) {
+                    return Err(format!("{} replied '{}'", self.smtp_server, line.trim_end()));
+                }
+                if line.as_bytes().get(3) != Some(&b'-') {
+                    return Ok(());
+                }
+            }
+        };
+        let mut send = |line: String| {
+            writer
+                .write_all(line.as_bytes())
+                .map_err(|e| format!("Can not send to {}: {}", self.smtp_server, e))
+        };
+        let state = format!("{:?}", status.state).to_lowercase();
+        let subject = format!("[{}] {} is {}", status.severity, status.name, state);
+        // a line of the body starting with a dot is escaped by another one
+        let body = status
+            .message
+            .lines()
+            .map(|line| match line.starts_with('.') {
+                true => format!(".{}\r\n", line),
+                false => format!("{}\r\n", line),
+            })
+            .collect::<String>();
+
+        expect("220")?;
+        send("HELO rtime\r\n".to_string())?;
+        expect("250")?;
+        send(format!("MAIL FROM:<{}>\r\n", self.email_from))?;
+        expect("250")?;
+        send(format!("RCPT TO:<{}>\r\n", to))?;
+        expect("250")?;
+        send("DATA\r\n".to_string())?;
+        expect("354")?;
+        send(format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\n\r\n{}.\r\n",
+            self.email_from, to, subject, body
+        ))?;
+        expect("250")?;
+        send("QUIT\r\n".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interfaces::alerts::AlertState;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    fn status() -> AlertStatus {
+        AlertStatus {
+            name: "overheat".to_string(),
+            state: AlertState::Firing,
+            severity: "critical".to_string(),
+            message: "Motor is too hot\n.".to_string(),
+            since: 0,
+            fired: 1,
+        }
+    }
+
+    fn notifier(smtp_server: String) -> Notifier {
+        Notifier {
+            smtp_server,
+            email_from: "rtime@robot".to_string(),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    // answers one connection with the greeting and then `replies`, one for every line received,
+    // and returns what it received
+    fn server(greeting: &str, replies: Vec<&'static str>) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let greeting = greeting.to_string();
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(greeting.as_bytes()).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut received = String::new();
+            for reply in replies {
+                // the message after DATA is answered once it ends with a line holding a dot
+                let message = received.ends_with("DATA\r\n");
+                loop {
+                    let mut line = String::new();
+                    let size = reader.read_line(&mut line).unwrap();
+                    received += &line;
+                    if !message || size == 0 || line == ".\r\n" {
+                        break;
+                    }
+                }
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+            let _ = reader.read_to_string(&mut received);
+            received
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn test_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/rtime", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let size = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+            String::from_utf8_lossy(&request[..size]).to_string()
+        });
+        notifier(String::new()).webhook(&url, &status()).unwrap();
+        let request = handle.join().unwrap();
+        assert!(request.starts_with("POST /hooks/rtime HTTP/1.1\r\n"));
+        assert!(request.ends_with(&serde_json::to_string(&status()).unwrap()));
+
+        let (address, handle) = server("HTTP/1.1 500 Internal Server Error\r\n\r\n", vec![]);
+        let result = notifier(String::new()).webhook(&format!("http://{}", address), &status());
+        assert!(result.unwrap_err().ends_with("answered 'HTTP/1.1 500 Internal Server Error'"));
+        handle.join().unwrap();
+        assert!(notifier(String::new()).webhook("https://ops/hooks", &status()).is_err());
+    }
+
+    #[test]
+    fn test_email() {
+        let replies = vec!["250 hi\r\n", "250 ok\r\n", "250 ok\r\n", "354 go\r\n", "250 sent\r\n"];
+        let (address, handle) = server("220-relay\r\n220 ready\r\n", replies);
+        notifier(address).email("ops@example.com", &status()).unwrap();
+        let received = handle.join().unwrap();
+        assert!(received.starts_with(
+            "HELO rtime\r\nMAIL FROM:<rtime@robot>\r\nRCPT TO:<ops@example.com>\r\nDATA\r\n"
+        ));
+        assert!(received.contains("Subject: [critical] overheat is firing\r\n"));
+        assert!(received.ends_with("Motor is too hot\r\n..\r\n.\r\nQUIT\r\n"));
+
+        let (address, handle) = server("220 ready\r\n", vec!["250 hi\r\n", "550 unknown\r\n"]);
+        let result = notifier(address).email("ops@example.com", &status());
+        assert!(result.unwrap_err().ends_with("replied '550 unknown'"));
+        handle.join().unwrap();
+    }
+}
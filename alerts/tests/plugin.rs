@@ -0,0 +1,47 @@
+use interfaces::alerts::{ack_key, alert_key};
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue, TypedBlackboardValue};
+use interfaces::testing::Host;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_alerts() {
+    let alerts = "[{name: overheat, when: 'motor/temperature > 80', debounce_ms: 100, \
+                  actions: [{set: {key: robot/alarm, value: true}}]}]";
+    let attributes = vec![BlackboardEntry {
+        key: "alerts".to_string(),
+        value: BlackboardValue::String(alerts.to_string()),
+    }];
+    let host = Host::start(&[("alerts", attributes)]).unwrap();
+    let client = host.client();
+    let state = || match client.get_value(&alert_key("overheat")) {
+        Ok(TypedBlackboardValue::Json(status)) => {
+            status["state"].as_str().unwrap_or_default().to_string()
+        }
+        _ => String::new(),
+    };
+    let wait_for = |expected: &str| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while state() != expected {
+            assert!(Instant::now() < deadline, "alert is not {}", expected);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+    wait_for("ok");
+
+    let temperature = |value: f64| {
+        let value = TypedBlackboardValue::Double(value);
+        client.set_value("motor/temperature", &value).unwrap();
+    };
+    temperature(85.0);
+    wait_for("firing");
+    assert_eq!(
+        client.get_value("robot/alarm").unwrap(),
+        TypedBlackboardValue::Bool(true)
+    );
+    client.set_value(&ack_key("overheat"), &TypedBlackboardValue::Bool(true)).unwrap();
+    wait_for("acknowledged");
+    temperature(70.0);
+    wait_for("ok");
+
+    assert!(host.stop().is_empty());
+}
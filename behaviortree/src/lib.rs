@@ -0,0 +1,335 @@
+// Runs a behavior tree, see `spec` for its description. The tree comes from the `tree` attribute
+// or the blackboard key `tree_key`, writing the key replaces the running tree. Its status is
+// published to `status_key` as `running`, `success` or `failure`, `idle` without a tree and
+// `invalid` for a description that can not be read.
+mod spec;
+mod tree;
+
+use interfaces::blackboard::{
+    BlackboardEntry, BlackboardValue, TypedBlackboardValue, parse_attributes,
+};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::capabilities::Capabilities;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{error, info, warn};
+use serde_json::json;
+use spec::Spec;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tree::{Context, Status, Tree};
+
+struct Config {
+    tree: Option<Spec>,
+    tree_key: Option<String>,
+    tick: Duration,
+    status_key: String,
+    repeat: bool, // ticks a finished tree again, otherwise it waits for a new one
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            tree: None,
+            tree_key: None,
+            tick: Duration::from_millis(100),
+            status_key: "behaviortree/status".to_string(),
+            repeat: false,
+        }
+    }
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("tree", BlackboardValue::String(text)) => {
+                    config.tree = Some(Spec::parse(text).map_err(invalid)?)
+                }
+                // written as yaml right in the attributes
+                ("tree", BlackboardValue::Json(tree)) => {
+                    config.tree = Some(
+                        serde_json::from_value(tree.clone())
+                            .map_err(|e| invalid(format!("Invalid tree: {}", e)))?,
+                    )
+                }
+                ("tree_key", BlackboardValue::String(key)) => config.tree_key = Some(key.clone()),
+                ("tick_ms", BlackboardValue::Int(value)) if *value > 0 => {
+                    config.tick = Duration::from_millis(*value as u64)
+                }
+                ("tick_ms", value) => return Err(invalid(format!("Invalid tick_ms {:?}", value))),
+                ("status_key", BlackboardValue::String(key)) => config.status_key = key.clone(),
+                ("repeat", BlackboardValue::Bool(value)) => config.repeat = *value,
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+// the blackboard and the skills of the loader
+struct Runtime {
+    client: BlackboardClient,
+    caps: Capabilities,
+}
+
+impl Context for Runtime {
+    fn read(&self, key: &str) -> Option<TypedBlackboardValue> {
+        self.client.get_value(key).ok()
+    }
+
+    fn write(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), String> {
+        self.client.set_value(key, value).map_err(|e| e.to_string())
+    }
+
+    fn run_skill(&self, name: &str) -> Result<i32, String> {
+        interfaces::runtime::run_skill(&self.caps, name).map_err(|e| e.to_string())
+    }
+}
+
+struct Executor {
+    runtime: Arc<Runtime>,
+    tick: Duration,
+    tree_key: Option<String>,
+    status_key: String,
+    repeat: bool,
+    status: Mutex<String>,
+    ticks: AtomicU64,
+}
+
+impl Executor {
+    fn publish(&self, status: &str) {
+        let mut published = self.status.lock().unwrap();
+        if *published == status {
+            return;
+        }
+        *published = status.to_string();
+        if let Err(e) = self.runtime.client.set_string(&self.status_key, status) {
+            warn!("Can not publish the status: {}", e);
+        }
+    }
+
+    // the tree of `tree_key`, none if the key is missing
+    fn load(&self, key: &str) -> Option<Tree> {
+        let text = self.runtime.client.get_string(key).ok()?;
+        match Spec::parse(&text).and_then(|spec| Tree::new(spec, self.runtime.clone())) {
+            Ok(tree) => {
+                info!("Loaded the tree of {}", key);
+                Some(tree)
+            }
+            Err(e) => {
+                error!("{}", e);
+                self.publish("invalid");
+                None
+            }
+        }
+    }
+
+    // ticks until the wake channel is closed, a message reloads the tree from its key
+    fn run(&self, mut tree: Option<Tree>, wake: mpsc::Receiver<()>) {
+        let mut next = Instant::now();
+        let mut done = false;
+        loop {
+            match wake.recv_timeout(next.saturating_duration_since(Instant::now())) {
+                Ok(()) => {
+                    // halted first, so skills of the old tree end before new ones start
+                    drop(tree.take());
+                    tree = self.tree_key.as_deref().and_then(|key| self.load(key));
+                    done = false;
+                    next = Instant::now();
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            next += self.tick;
+            // a slow tick skips the missed ones
+            next = next.max(Instant::now());
+            match tree.as_mut() {
+                Some(_) if done => {}
+                Some(tree) => {
+                    let status = tree.tick();
+                    self.ticks.fetch_add(1, Ordering::SeqCst);
+                    self.publish(status.name());
+                    done = status != Status::Running && !self.repeat;
+                }
+                None => {
+                    if *self.status.lock().unwrap() != "invalid" {
+                        self.publish("idle");
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct ExecutorState {
+    executor: Arc<Executor>,
+    subscription: Option<Subscription>,
+    wake: mpsc::Sender<()>,
+    ticker: JoinHandle<()>,
+}
+
+static EXECUTOR_STATE: Mutex<Option<ExecutorState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "behaviortree",
+    summary = "ticks a behavior tree of skills and blackboard conditions",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        behaviortree_start = start: "i32(caps,cstr)",
+        behaviortree_stop = stop: "i32()",
+        behaviortree_health = health: "i32()",
+        behaviortree_state = state: "i32()",
+        behaviortree_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_executor(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = EXECUTOR_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Behavior tree is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    if config.tree.is_none() && config.tree_key.is_none() {
+        return Err(RtError::new(
+            RtStatus::InvalidArgument,
+            "Neither tree nor tree_key is given",
+        ));
+    }
+    let runtime = Arc::new(Runtime {
+        client: BlackboardClient::new(Capabilities::from_raw(caps)),
+        caps: Capabilities::from_raw(caps),
+    });
+    let executor = Arc::new(Executor {
+        runtime: runtime.clone(),
+        tick: config.tick,
+        tree_key: config.tree_key.clone(),
+        status_key: config.status_key,
+        repeat: config.repeat,
+        status: Mutex::new(String::new()),
+        ticks: AtomicU64::new(0),
+    });
+    let tree = match (config.tree, &config.tree_key) {
+        (Some(spec), _) => {
+            Some(Tree::new(spec, runtime).map_err(|e| RtError::new(RtStatus::InvalidArgument, e))?)
+        }
+        (None, Some(key)) => executor.load(key),
+        (None, None) => None,
+    };
+
+    let (wake, woken) = mpsc::channel();
+    let subscription = match &config.tree_key {
+        Some(key) => {
+            let wake = wake.clone();
+            Some(
+                executor
+                    .runtime
+                    .client
+                    .subscribe(key, "behaviortree", move |_| {
+                        let _ = wake.send(());
+                    })?,
+            )
+        }
+        None => None,
+    };
+    let ticking = executor.clone();
+    *state = Some(ExecutorState {
+        executor,
+        subscription,
+        wake,
+        ticker: std::thread::spawn(move || ticking.run(tree, woken)),
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "behaviortree");
+    match LIFECYCLE.started(catch_panic(|| start_executor(caps, attributes))) {
+        Ok(()) => {
+            info!("Behavior tree started");
+            0
+        }
+        Err(e) => {
+            error!("Error starting behavior tree: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_executor() -> Result<(), RtError> {
+    let state = EXECUTOR_STATE.lock().unwrap().take();
+    let state =
+        state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Behavior tree is not running"))?;
+    // closes the wake channel, the ticker ends once the running skills returned
+    drop(state.subscription);
+    drop(state.wake);
+    let _ = state.ticker.join();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_executor)) {
+        Ok(()) => {
+            info!("Behavior tree stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping behavior tree: {}", e);
+            e.record()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match EXECUTOR_STATE.lock().unwrap().as_ref() {
+        Some(state) if state.ticker.is_finished() => {
+            RtError::new(RtStatus::Error, "Ticker ended unexpectedly").record()
+        }
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the behavior tree, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the status of the tree and the number of ticks as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match EXECUTOR_STATE.lock().unwrap().as_ref() {
+        Some(state) => json!({
+            "status": *state.executor.status.lock().unwrap(),
+            "ticks": state.executor.ticks.load(Ordering::SeqCst),
+        }),
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
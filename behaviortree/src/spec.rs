@@ -0,0 +1,121 @@
+// Descriptions of behavior trees. In yaml or json every node is a map with one key naming it:
+//
+//     sequence:
+//       - condition: "battery/level < 20"
+//       - fallback:
+//           - skill: dock
+//           - set: {key: robot/state, value: stuck}
+//
+// The xml of BehaviorTree.CPP is read with the same nodes, named `Sequence`, `Fallback`,
+// `Parallel`, `Inverter`, `RetryUntilSuccessful`, `Repeat`, `Skill`, `Condition` and
+// `SetBlackboard`.
+use interfaces::blackboard::BlackboardValue;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Spec {
+    Sequence(Vec<Spec>), // succeeds once all children succeeded in turn
+    Fallback(Vec<Spec>), // succeeds once a child succeeded, tried in turn
+    Parallel(Vec<Spec>), // ticks all children, fails as soon as one fails
+    Inverter(Box<Spec>),
+    Retry { attempts: u32, node: Box<Spec> }, // runs a failing node again
+    Repeat { cycles: u32, node: Box<Spec> },  // runs a succeeding node again
+    Skill(String),
+    Condition(String), // see `interfaces::predicate`
+    Set { key: String, value: BlackboardValue },
+}
+
+impl Spec {
+    /// Reads xml if the text starts with `<`, yaml or json otherwise.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.trim_start().starts_with('<') {
+            from_xml(text)
+        } else {
+            // serde_yml only reads enums from yaml tags, json takes the maps with one key
+            serde_yml::from_str(text)
+                .and_then(|tree: serde_json::Value| {
+                    serde_json::from_value(tree).map_err(serde::de::Error::custom)
+                })
+                .map_err(|e| format!("Invalid tree: {}", e))
+        }
+    }
+}
+
+fn from_xml(text: &str) -> Result<Spec, String> {
+    let document = roxmltree::Document::parse(text).map_err(|e| format!("Invalid tree: {}", e))?;
+    let root = document.root_element();
+    let tree = match root.tag_name().name() {
+        "root" => {
+            let main = root.attribute("main_tree_to_execute");
+            root.children()
+                .filter(|node| node.has_tag_name("BehaviorTree"))
+                .find(|node| main.is_none() || node.attribute("ID") == main)
+                .ok_or("The xml has no BehaviorTree")?
+        }
+        _ => root,
+    };
+    let node = match tree.tag_name().name() {
+        "BehaviorTree" => only_child(tree)?,
+        _ => tree,
+    };
+    xml_node(node)
+}
+
+fn only_child<'a>(node: roxmltree::Node<'a, 'a>) -> Result<roxmltree::Node<'a, 'a>, String> {
+    let mut children = node.children().filter(roxmltree::Node::is_element);
+    match (children.next(), children.next()) {
+        (Some(child), None) => Ok(child),
+        _ => Err(format!(
+            "{} needs exactly one child",
+            node.tag_name().name()
+        )),
+    }
+}
+
+fn xml_node(node: roxmltree::Node) -> Result<Spec, String> {
+    let name = node.tag_name().name();
+    let attribute = |attribute: &str| {
+        node.attribute(attribute)
+            .ok_or_else(|| format!("{} needs the attribute {}", name, attribute))
+    };
+    let count = |attribute: &str| {
+        node.attribute(attribute)
+            .ok_or_else(|| format!("{} needs the attribute {}", name, attribute))?
+            .parse::<u32>()
+            .map_err(|e| format!("Invalid {} of {}: {}", attribute, name, e))
+    };
+    let children = || {
+        node.children()
+            .filter(roxmltree::Node::is_element)
+            .map(xml_node)
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let child = || only_child(node).and_then(xml_node).map(Box::new);
+    Ok(match name {
+        "Sequence" => Spec::Sequence(children()?),
+        "Fallback" => Spec::Fallback(children()?),
+        "Parallel" => Spec::Parallel(children()?),
+        "Inverter" => Spec::Inverter(child()?),
+        "RetryUntilSuccessful" => Spec::Retry {
+            attempts: count("num_attempts")?,
+            node: child()?,
+        },
+        "Repeat" => Spec::Repeat {
+            cycles: count("num_cycles")?,
+            node: child()?,
+        },
+        "Skill" => Spec::Skill(attribute("name")?.to_string()),
+        "Condition" => Spec::Condition(attribute("check")?.to_string()),
+        "SetBlackboard" => {
+            let value = attribute("value")?;
+            Spec::Set {
+                key: attribute("output_key")?.to_string(),
+                // typed like json, anything else is a string
+                value: serde_json::from_str(value)
+                    .unwrap_or_else(|_| BlackboardValue::String(value.to_string())),
+            }
+        }
+        _ => return Err(format!("Unknown node {}", name)),
+    })
+}
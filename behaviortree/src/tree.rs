@@ -0,0 +1,470 @@
+// Ticking of behavior trees. Skills run on their own thread, their node is running until the
+// skill returns. Composites remember the running child, so a sequence does not check its
+// conditions again while a skill runs. Retry and repeat continue with the next tick.
+use crate::spec::Spec;
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::predicate::Predicate;
+use log::warn;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Running,
+    Success,
+    Failure,
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Running => "running",
+            Status::Success => "success",
+            Status::Failure => "failure",
+        }
+    }
+}
+
+/// What the leaves act on, the blackboard and the skills of the loader.
+pub trait Context: Send + Sync {
+    fn read(&self, key: &str) -> Option<TypedBlackboardValue>;
+    fn write(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), String>;
+    fn run_skill(&self, name: &str) -> Result<i32, String>;
+}
+
+type SkillRun = JoinHandle<Result<i32, String>>;
+
+enum Node {
+    Sequence {
+        children: Vec<Node>,
+        current: usize,
+    },
+    Fallback {
+        children: Vec<Node>,
+        current: usize,
+    },
+    Parallel {
+        children: Vec<Node>,
+        done: Vec<bool>,
+    },
+    Inverter(Box<Node>),
+    Retry {
+        attempts: u32,
+        failed: u32,
+        node: Box<Node>,
+    },
+    Repeat {
+        cycles: u32,
+        done: u32,
+        node: Box<Node>,
+    },
+    Skill {
+        name: String,
+        run: Option<SkillRun>,
+    },
+    Condition(Predicate),
+    Set {
+        key: String,
+        value: TypedBlackboardValue,
+    },
+}
+
+impl Node {
+    fn new(spec: Spec) -> Result<Self, String> {
+        let nodes = |specs: Vec<Spec>| {
+            specs
+                .into_iter()
+                .map(Node::new)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let node = |spec: Box<Spec>| Node::new(*spec).map(Box::new);
+        Ok(match spec {
+            Spec::Sequence(children) => Node::Sequence {
+                children: nodes(children)?,
+                current: 0,
+            },
+            Spec::Fallback(children) => Node::Fallback {
+                children: nodes(children)?,
+                current: 0,
+            },
+            Spec::Parallel(children) => Node::Parallel {
+                done: vec![false; children.len()],
+                children: nodes(children)?,
+            },
+            Spec::Inverter(child) => Node::Inverter(node(child)?),
+            Spec::Retry {
+                attempts,
+                node: child,
+            } => Node::Retry {
+                attempts,
+                failed: 0,
+                node: node(child)?,
+            },
+            Spec::Repeat {
+                cycles,
+                node: child,
+            } => Node::Repeat {
+                cycles,
+                done: 0,
+                node: node(child)?,
+            },
+            Spec::Skill(name) => Node::Skill { name, run: None },
+            Spec::Condition(condition) => Node::Condition(Predicate::parse(&condition)?),
+            Spec::Set { key, value } => Node::Set {
+                key,
+                value: value.try_into()?,
+            },
+        })
+    }
+
+    fn tick(&mut self, context: &Arc<dyn Context>, halted: &mut Vec<SkillRun>) -> Status {
+        match self {
+            // a sequence goes on with its children while they succeed, a fallback while they fail
+            Node::Sequence { children, current } => {
+                tick_in_turn(children, current, Status::Success, context, halted)
+            }
+            Node::Fallback { children, current } => {
+                tick_in_turn(children, current, Status::Failure, context, halted)
+            }
+            Node::Parallel { children, done } => {
+                let mut failed = false;
+                for (child, done) in children.iter_mut().zip(done.iter_mut()) {
+                    if *done {
+                        continue;
+                    }
+                    match child.tick(context, halted) {
+                        Status::Running => {}
+                        Status::Success => *done = true,
+                        Status::Failure => {
+                            failed = true;
+                            break;
+                        }
+                    }
+                }
+                if failed {
+                    self.halt(halted);
+                    Status::Failure
+                } else if done.iter().all(|done| *done) {
+                    done.fill(false);
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Inverter(node) => match node.tick(context, halted) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Retry {
+                attempts,
+                failed,
+                node,
+            } => match node.tick(context, halted) {
+                Status::Failure if *failed + 1 < *attempts => {
+                    *failed += 1;
+                    Status::Running
+                }
+                status => {
+                    if status != Status::Running {
+                        *failed = 0;
+                    }
+                    status
+                }
+            },
+            Node::Repeat { cycles, done, node } => match node.tick(context, halted) {
+                Status::Success if *done + 1 < *cycles => {
+                    *done += 1;
+                    Status::Running
+                }
+                status => {
+                    if status != Status::Running {
+                        *done = 0;
+                    }
+                    status
+                }
+            },
+            Node::Skill { name, run } => {
+                let Some(running) = run.take() else {
+                    let context = context.clone();
+                    let name = name.clone();
+                    *run = Some(std::thread::spawn(move || context.run_skill(&name)));
+                    return Status::Running;
+                };
+                if !running.is_finished() {
+                    *run = Some(running);
+                    return Status::Running;
+                }
+                match running.join() {
+                    Ok(Ok(_)) => Status::Success,
+                    Ok(Err(e)) => {
+                        warn!("{}", e);
+                        Status::Failure
+                    }
+                    Err(_) => {
+                        warn!("Skill '{}' panicked", name);
+                        Status::Failure
+                    }
+                }
+            }
+            Node::Condition(predicate) => {
+                match predicate.holds(context.read(predicate.key()).as_ref()) {
+                    true => Status::Success,
+                    false => Status::Failure,
+                }
+            }
+            Node::Set { key, value } => match context.write(key, value) {
+                Ok(()) => Status::Success,
+                Err(e) => {
+                    warn!("Can not set {}: {}", key, e);
+                    Status::Failure
+                }
+            },
+        }
+    }
+
+    // resets the node, running skills are handed to `halted`, they can not be interrupted
+    fn halt(&mut self, halted: &mut Vec<SkillRun>) {
+        match self {
+            Node::Sequence { children, current } | Node::Fallback { children, current } => {
+                *current = 0;
+                children.iter_mut().for_each(|child| child.halt(halted));
+            }
+            Node::Parallel { children, done } => {
+                done.fill(false);
+                children.iter_mut().for_each(|child| child.halt(halted));
+            }
+            Node::Inverter(node) => node.halt(halted),
+            Node::Retry { failed, node, .. } => {
+                *failed = 0;
+                node.halt(halted);
+            }
+            Node::Repeat { done, node, .. } => {
+                *done = 0;
+                node.halt(halted);
+            }
+            Node::Skill { run, .. } => halted.extend(run.take()),
+            Node::Condition(_) | Node::Set { .. } => {}
+        }
+    }
+}
+
+// ticks the children from `current` on while they return `going_on`
+fn tick_in_turn(
+    children: &mut [Node],
+    current: &mut usize,
+    going_on: Status,
+    context: &Arc<dyn Context>,
+    halted: &mut Vec<SkillRun>,
+) -> Status {
+    while let Some(child) = children.get_mut(*current) {
+        match child.tick(context, halted) {
+            Status::Running => return Status::Running,
+            status if status == going_on => *current += 1,
+            status => {
+                *current = 0;
+                return status;
+            }
+        }
+    }
+    *current = 0;
+    going_on
+}
+
+pub struct Tree {
+    root: Node,
+    context: Arc<dyn Context>,
+    halted: Vec<SkillRun>,
+}
+
+impl Tree {
+    pub fn new(spec: Spec, context: Arc<dyn Context>) -> Result<Self, String> {
+        Ok(Tree {
+            root: Node::new(spec)?,
+            context,
+            halted: Vec::new(),
+        })
+    }
+
+    /// Ticks the tree once, a finished tree starts over with the next tick.
+    pub fn tick(&mut self) -> Status {
+        self.halted.retain(|run| !run.is_finished());
+        self.root.tick(&self.context, &mut self.halted)
+    }
+
+    /// Resets the tree and waits for the skills still running.
+    pub fn halt(&mut self) {
+        self.root.halt(&mut self.halted);
+        for run in self.halted.drain(..) {
+            let _ = run.join();
+        }
+    }
+}
+
+impl Drop for Tree {
+    fn drop(&mut self) {
+        self.halt();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    // skills succeed if their name is on the blackboard as true
+    #[derive(Default)]
+    struct Board(Mutex<HashMap<String, TypedBlackboardValue>>);
+
+    impl Context for Board {
+        fn read(&self, key: &str) -> Option<TypedBlackboardValue> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn write(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), String> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.clone());
+            Ok(())
+        }
+
+        fn run_skill(&self, name: &str) -> Result<i32, String> {
+            match self.read(name) {
+                Some(TypedBlackboardValue::Bool(true)) => Ok(0),
+                _ => Err(format!("Skill '{}' failed", name)),
+            }
+        }
+    }
+
+    fn tree(text: &str) -> (Tree, Arc<Board>) {
+        let board = Arc::new(Board::default());
+        let tree = Tree::new(Spec::parse(text).unwrap(), board.clone()).unwrap();
+        (tree, board)
+    }
+
+    // ticks until the tree is done
+    fn run(tree: &mut Tree) -> Status {
+        for _ in 0..1000 {
+            match tree.tick() {
+                Status::Running => std::thread::sleep(std::time::Duration::from_millis(1)),
+                status => return status,
+            }
+        }
+        panic!("The tree did not finish");
+    }
+
+    #[test]
+    fn test_tick() {
+        let (mut tree, board) = tree(
+            "sequence:
+               - condition: 'battery/level < 20'
+               - fallback:
+                   - skill: dock
+                   - set: {key: robot/state, value: stuck}",
+        );
+        assert_eq!(tree.tick(), Status::Failure);
+        board
+            .write("battery/level", &TypedBlackboardValue::Double(12.5))
+            .unwrap();
+        assert_eq!(tree.tick(), Status::Running);
+        assert_eq!(run(&mut tree), Status::Success);
+        assert_eq!(
+            board.read("robot/state"),
+            Some(TypedBlackboardValue::String("stuck".to_string()))
+        );
+
+        board
+            .write("dock", &TypedBlackboardValue::Bool(true))
+            .unwrap();
+        board
+            .write("robot/state", &TypedBlackboardValue::Int(0))
+            .unwrap();
+        assert_eq!(run(&mut tree), Status::Success);
+        assert_eq!(
+            board.read("robot/state"),
+            Some(TypedBlackboardValue::Int(0))
+        );
+    }
+
+    #[test]
+    fn test_decorators() {
+        let (mut tree, board) = tree(
+            "sequence:
+               - repeat: {cycles: 3, node: {set: {key: moved, value: true}}}
+               - retry: {attempts: 2, node: {inverter: {condition: blocked}}}",
+        );
+        board
+            .write("blocked", &TypedBlackboardValue::Bool(true))
+            .unwrap();
+        assert_eq!(tree.tick(), Status::Running);
+        assert_eq!(tree.tick(), Status::Running);
+        assert_eq!(tree.tick(), Status::Running);
+        assert_eq!(tree.tick(), Status::Failure);
+        board
+            .write("blocked", &TypedBlackboardValue::Int(0))
+            .unwrap();
+        assert_eq!(run(&mut tree), Status::Success);
+    }
+
+    #[test]
+    fn test_parallel() {
+        let (mut tree, board) = tree(
+            r#"{"parallel": [{"skill": "left"}, {"skill": "right"}, {"condition": "!blocked"}]}"#,
+        );
+        board
+            .write("left", &TypedBlackboardValue::Bool(true))
+            .unwrap();
+        board
+            .write("right", &TypedBlackboardValue::Bool(true))
+            .unwrap();
+        board
+            .write("blocked", &TypedBlackboardValue::Bool(false))
+            .unwrap();
+        assert_eq!(run(&mut tree), Status::Success);
+        board
+            .write("right", &TypedBlackboardValue::Bool(false))
+            .unwrap();
+        assert_eq!(run(&mut tree), Status::Failure);
+    }
+
+    #[test]
+    fn test_xml() {
+        let (mut tree, board) = tree(
+            r#"<root main_tree_to_execute="Main">
+                 <BehaviorTree ID="Other"><Skill name="other"/></BehaviorTree>
+                 <BehaviorTree ID="Main">
+                   <Fallback>
+                     <Condition check="robot/state == &quot;docked&quot;"/>
+                     <RetryUntilSuccessful num_attempts="2">
+                       <SetBlackboard output_key="robot/state" value="docked"/>
+                     </RetryUntilSuccessful>
+                   </Fallback>
+                 </BehaviorTree>
+               </root>"#,
+        );
+        assert_eq!(tree.tick(), Status::Success);
+        assert_eq!(
+            board.read("robot/state"),
+            Some(TypedBlackboardValue::String("docked".to_string()))
+        );
+
+        for invalid in [
+            "<root/>",
+            "<Sequence><Unknown/></Sequence>",
+            "<Inverter/>",
+            "<Repeat><Skill name='a'/></Repeat>",
+            "skill: [a]",
+            "condition: 'a <'",
+        ] {
+            let spec = Spec::parse(invalid);
+            let board: Arc<dyn Context> = Arc::new(Board::default());
+            assert!(
+                spec.and_then(|spec| Tree::new(spec, board)).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
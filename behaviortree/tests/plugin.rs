@@ -0,0 +1,59 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::capabilities::Capability;
+use interfaces::runtime::{RUNTIME_RUN_SKILL_CAPABILITY, RUNTIME_RUN_SKILL_SIGNATURE};
+use interfaces::status::RtStatus;
+use interfaces::testing::Host;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::time::{Duration, Instant};
+
+// the skills of the loader, `broken` fails and no other one is loaded
+extern "C" fn run_skill(name: *const c_char) -> c_int {
+    match unsafe { CStr::from_ptr(name) }.to_str() {
+        Ok("broken") => RtStatus::Error.code(),
+        _ => RtStatus::KeyNotFound.code(),
+    }
+}
+
+#[test]
+fn test_behaviortree() {
+    let attributes = vec![
+        BlackboardEntry {
+            key: "tree".to_string(),
+            value: BlackboardValue::String(
+                "fallback: [{skill: broken}, {set: {key: robot/state, value: stuck}}]".into(),
+            ),
+        },
+        BlackboardEntry {
+            key: "tree_key".to_string(),
+            value: BlackboardValue::String("robot/tree".into()),
+        },
+        BlackboardEntry {
+            key: "tick_ms".to_string(),
+            value: BlackboardValue::Int(10),
+        },
+    ];
+    let runtime = Capability::with_signature(
+        RUNTIME_RUN_SKILL_CAPABILITY,
+        run_skill as *mut c_void,
+        RUNTIME_RUN_SKILL_SIGNATURE,
+    );
+    let host = Host::start_with(&[("behaviortree", attributes)], vec![runtime]).unwrap();
+    let client = host.client();
+    let wait_for = |key: &str, value: &str| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.get_string(key).ok().as_deref() != Some(value) {
+            assert!(Instant::now() < deadline, "{} is not {}", key, value);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+    wait_for("robot/state", "stuck");
+    wait_for("behaviortree/status", "success");
+
+    // the key replaces the tree
+    client.set_string("robot/tree", "<Condition check=\"robot/state == docked\"/>").unwrap();
+    wait_for("behaviortree/status", "failure");
+    client.set_string("robot/tree", "sequence: [{unknown: a}]").unwrap();
+    wait_for("behaviortree/status", "invalid");
+
+    assert!(host.stop().is_empty());
+}
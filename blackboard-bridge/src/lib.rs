@@ -0,0 +1,499 @@
+// Mirrors keys of the blackboard between two rtime instances, e.g. a robot and its operator
+// station, over tcp. Every change is sent to the peers with a timestamp, the latest change of
+// a key wins on all sides.
+mod protocol;
+
+use interfaces::blackboard::{
+    BlackboardEntry, BlackboardValue, TypedBlackboardValue, key_matches, parse_attributes,
+};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{debug, error, info, warn};
+use protocol::{read_update, write_update, Update};
+use std::collections::HashMap;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// between two attempts to reach the peer
+const RECONNECT: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct Config {
+    listen: Option<String>,  // address peers connect to, e.g. `0.0.0.0:7447`
+    connect: Option<String>, // address of the peer, reconnected while the bridge runs
+    keys: Vec<String>,       // mirrored keys, or prefixes ending with `*`
+    node: Option<String>,    // name of this side, orders changes with equal timestamps
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Self {
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("listen", BlackboardValue::String(value)) => config.listen = Some(value.clone()),
+                ("connect", BlackboardValue::String(value)) => config.connect = Some(value.clone()),
+                ("node", BlackboardValue::String(value)) => config.node = Some(value.clone()),
+                ("keys", BlackboardValue::String(key)) => config.keys = vec![key.clone()],
+                ("keys", BlackboardValue::Array(keys)) => {
+                    config.keys = keys
+                        .iter()
+                        .filter_map(|key| match key {
+                            BlackboardValue::String(key) => Some(key.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        config
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+// the last change of a mirrored key, made here or by a peer
+struct Known {
+    stamp: u64,
+    node: String,
+    value: Option<TypedBlackboardValue>,
+}
+
+struct Peer {
+    id: usize,
+    stream: TcpStream,
+}
+
+struct Bridge {
+    client: BlackboardClient,
+    keys: Vec<String>,
+    node: String,
+    known: Mutex<HashMap<String, Known>>,
+    peers: Mutex<Vec<Peer>>,
+    next_peer: AtomicUsize,
+    running: AtomicBool,
+}
+
+impl Bridge {
+    fn mirrors(&self, key: &str) -> bool {
+        self.keys.iter().any(|filter| key_matches(filter, key))
+    }
+
+    // current value of a key, None if it does not exist
+    fn read(&self, key: &str) -> Result<Option<TypedBlackboardValue>, RtError> {
+        match self.client.get_value(key) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.status == RtStatus::KeyNotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // sends a key written on this side to the peers
+    fn local_change(&self, key: &str) {
+        let update = {
+            let mut known = self.known.lock().unwrap();
+            let value = match self.read(key) {
+                Ok(value) => value,
+                Err(e) => return warn!("Can not read changed key {}: {}", key, e),
+            };
+            let stamp = match known.get(key) {
+                // written by `remote_change`
+                Some(last) if last.value == value => return,
+                // the peer's clock may be ahead
+                Some(last) => now().max(last.stamp + 1),
+                None => now(),
+            };
+            known.insert(
+                key.to_string(),
+                Known {
+                    stamp,
+                    node: self.node.clone(),
+                    value: value.clone(),
+                },
+            );
+            Update {
+                key: key.to_string(),
+                value,
+                stamp,
+                node: self.node.clone(),
+            }
+        };
+        self.send(&update, None);
+    }
+
+    // applies a change of the peer `from`, unless a later one is known, and passes it on
+    fn remote_change(&self, update: Update, from: usize) {
+        if !self.mirrors(&update.key) {
+            return debug!(
+                "Ignoring {} of {}, it is not mirrored",
+                update.key, update.node
+            );
+        }
+        {
+            let mut known = self.known.lock().unwrap();
+            if let Some(last) = known.get(&update.key) {
+                if !update.wins_over(last.stamp, &last.node) {
+                    return;
+                }
+            }
+            known.insert(
+                update.key.clone(),
+                Known {
+                    stamp: update.stamp,
+                    node: update.node.clone(),
+                    value: update.value.clone(),
+                },
+            );
+            // written while locked, so `local_change` finds the value known
+            let written = match &update.value {
+                Some(value) => self.client.set_value(&update.key, value),
+                None => match self.client.delete(&update.key) {
+                    Err(e) if e.status == RtStatus::KeyNotFound => Ok(()),
+                    result => result,
+                },
+            };
+            if let Err(e) = written {
+                warn!("Can not write {} of {}: {}", update.key, update.node, e);
+            }
+        }
+        self.send(&update, Some(from));
+    }
+
+    fn send(&self, update: &Update, except: Option<usize>) {
+        self.peers.lock().unwrap().retain(|peer| {
+            if Some(peer.id) == except {
+                return true;
+            }
+            match write_update(&mut &peer.stream, update) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Lost peer {:?}: {}", peer.stream.peer_addr().ok(), e);
+                    let _ = peer.stream.shutdown(Shutdown::Both);
+                    false
+                }
+            }
+        });
+    }
+
+    // sends all mirrored keys to a new peer, then the changes after
+    fn add_peer(&self, stream: &TcpStream) -> std::io::Result<usize> {
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+        let peer = Peer {
+            id: self.next_peer.fetch_add(1, Ordering::SeqCst),
+            stream: stream.try_clone()?,
+        };
+
+        // keys not changed since the bridge started are unstamped, any change wins over them
+        let keys = self
+            .client
+            .keys()
+            .map_err(|e| std::io::Error::other(e.message))?;
+        let unstamped: Vec<(String, TypedBlackboardValue)> = keys
+            .into_iter()
+            .filter(|info| self.mirrors(&info.key))
+            .filter_map(|info| match self.read(&info.key) {
+                Ok(Some(value)) => Some((info.key, value)),
+                _ => None,
+            })
+            .collect();
+        let id = peer.id;
+        let updates: Vec<Update> = {
+            let mut known = self.known.lock().unwrap();
+            for (key, value) in unstamped {
+                known.entry(key).or_insert_with(|| Known {
+                    stamp: 0,
+                    node: self.node.clone(),
+                    value: Some(value),
+                });
+            }
+            // added while locked, so the changes after the snapshot are sent to the peer as well
+            self.peers.lock().unwrap().push(peer);
+            known
+                .iter()
+                .map(|(key, last)| Update {
+                    key: key.clone(),
+                    value: last.value.clone(),
+                    stamp: last.stamp,
+                    node: last.node.clone(),
+                })
+                .collect()
+        };
+        let mut writer = stream;
+        if let Err(e) = updates
+            .iter()
+            .try_for_each(|update| write_update(&mut writer, update))
+        {
+            self.peers.lock().unwrap().retain(|peer| peer.id != id);
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    // reads the changes of a peer until it disconnects
+    fn serve(&self, stream: TcpStream) {
+        let address = stream.peer_addr().ok();
+        let id = match self.add_peer(&stream) {
+            Ok(id) => id,
+            Err(e) => return warn!("Can not add peer {:?}: {}", address, e),
+        };
+        info!("Connected to peer {:?}", address);
+        loop {
+            match read_update(&mut &stream) {
+                Ok(update) => self.remote_change(update, id),
+                Err(e) => {
+                    if self.running.load(Ordering::SeqCst) {
+                        warn!("Lost peer {:?}: {}", address, e);
+                    }
+                    break;
+                }
+            }
+        }
+        self.peers.lock().unwrap().retain(|peer| peer.id != id);
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+
+    // accepts peers until the bridge stops
+    fn listen(self: Arc<Self>, listener: TcpListener) {
+        let mut connections = Vec::new();
+        while self.running.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(false) {
+                        warn!("Can not accept peer: {}", e);
+                        continue;
+                    }
+                    let bridge = self.clone();
+                    connections.push(std::thread::spawn(move || bridge.serve(stream)));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => warn!("Can not accept peer: {}", e),
+            }
+            connections.retain(|connection: &JoinHandle<()>| !connection.is_finished());
+        }
+        for connection in connections {
+            let _ = connection.join();
+        }
+    }
+
+    // connects to the peer again whenever the connection is lost
+    fn connect(self: Arc<Self>, address: String) {
+        while self.running.load(Ordering::SeqCst) {
+            let stream = address
+                .to_socket_addrs()
+                .map_err(|e| e.to_string())
+                .and_then(|mut addresses| addresses.next().ok_or("No address".to_string()))
+                .and_then(|peer| {
+                    TcpStream::connect_timeout(&peer, RECONNECT).map_err(|e| e.to_string())
+                });
+            match stream {
+                Ok(stream) => self.serve(stream),
+                Err(e) => debug!("Can not connect to {}: {}", address, e),
+            }
+            let retry = std::time::Instant::now() + RECONNECT;
+            while self.running.load(Ordering::SeqCst) && std::time::Instant::now() < retry {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+    }
+
+    // closes all connections, their threads see the error
+    fn disconnect(&self) {
+        for peer in self.peers.lock().unwrap().drain(..) {
+            let _ = peer.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+struct BridgeState {
+    bridge: Arc<Bridge>,
+    listening: Option<SocketAddr>,
+    subscriptions: Vec<Subscription>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+static BRIDGE_STATE: Mutex<Option<BridgeState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "blackboard_bridge",
+    summary = "mirrors blackboard keys to other rtime instances",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        blackboard_bridge_start = start: "i32(caps,cstr)",
+        blackboard_bridge_stop = stop: "i32()",
+        blackboard_bridge_health = health: "i32()",
+        blackboard_bridge_state = state: "i32()",
+        blackboard_bridge_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_bridge(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = BRIDGE_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Bridge is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?);
+    if config.keys.is_empty() {
+        return Err(RtError::new(RtStatus::InvalidArgument, "No keys to mirror"));
+    }
+    if config.listen.is_none() && config.connect.is_none() {
+        return Err(RtError::new(
+            RtStatus::InvalidArgument,
+            "Neither listen nor connect is set",
+        ));
+    }
+    let listener = match &config.listen {
+        Some(address) => {
+            let listener = TcpListener::bind(address)
+                .map_err(|e| format!("Can not listen on {}: {}", address, e))?;
+            // polled, so the bridge can stop
+            listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+            Some(listener)
+        }
+        None => None,
+    };
+
+    let bridge = Arc::new(Bridge {
+        client: BlackboardClient::new(interfaces::capabilities::Capabilities::from_raw(caps)),
+        node: config
+            .node
+            .unwrap_or_else(|| format!("{}-{}", std::process::id(), now())),
+        keys: config.keys,
+        known: Mutex::new(HashMap::new()),
+        peers: Mutex::new(Vec::new()),
+        next_peer: AtomicUsize::new(0),
+        running: AtomicBool::new(true),
+    });
+
+    let (subscriptions, changes) =
+        bridge.client.subscribe_forwarding(&bridge.keys, "blackboard_bridge")?;
+
+    let mut threads = Vec::new();
+    let worker = bridge.clone();
+    // ends once the subscriptions are dropped
+    threads.push(std::thread::spawn(move || {
+        for key in changes {
+            worker.local_change(&key);
+        }
+    }));
+    let listening = listener
+        .as_ref()
+        .and_then(|listener| listener.local_addr().ok());
+    if let Some(listener) = listener {
+        let listening = bridge.clone();
+        threads.push(std::thread::spawn(move || listening.listen(listener)));
+    }
+    if let Some(address) = config.connect {
+        let connecting = bridge.clone();
+        threads.push(std::thread::spawn(move || connecting.connect(address)));
+    }
+
+    info!(
+        "Mirroring {:?} as {} on {:?}",
+        bridge.keys, bridge.node, listening
+    );
+    *state = Some(BridgeState {
+        bridge,
+        listening,
+        subscriptions,
+        threads,
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "blackboard_bridge");
+    match LIFECYCLE.started(catch_panic(|| start_bridge(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting bridge: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_bridge() -> Result<(), RtError> {
+    let state = BRIDGE_STATE.lock().unwrap().take();
+    let state = state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Bridge is not running"))?;
+    state.bridge.running.store(false, Ordering::SeqCst);
+    drop(state.subscriptions);
+    state.bridge.disconnect();
+    for thread in state.threads {
+        let _ = thread.join();
+    }
+    // a connection accepted while stopping
+    state.bridge.disconnect();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_bridge)) {
+        Ok(()) => {
+            info!("Bridge stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping bridge: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_OK` while the bridge runs, `RT_NOT_RUNNING` once it is stopped. A missing peer is not an
+/// error, it is connected again.
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match BRIDGE_STATE.lock().unwrap().as_ref() {
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the bridge, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the node name, the listening address and the number of connected peers as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match BRIDGE_STATE.lock().unwrap().as_ref() {
+        Some(state) => serde_json::json!({
+            "node": state.bridge.node,
+            "listen": state.listening.map(|address| address.to_string()),
+            "peers": state.bridge.peers.lock().unwrap().len(),
+        }),
+        None => serde_json::json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
@@ -0,0 +1,99 @@
+// Wire format between two bridges: every frame is the length of a json `Update` as big endian
+// u32, followed by the json.
+use interfaces::blackboard::TypedBlackboardValue;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+// larger frames are refused, a peer sending one is dropped
+const MAX_FRAME: u32 = 16 * 1024 * 1024;
+
+/// A key changed on one of the bridges. The change with the later `stamp` wins on both sides,
+/// equal stamps are ordered by `node`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Update {
+    pub key: String,
+    pub value: Option<TypedBlackboardValue>, // None once the key is removed
+    pub stamp: u64,                          // milliseconds since the unix epoch
+    pub node: String,                        // the bridge the change happened on
+}
+
+impl Update {
+    /// True if the change is newer than the one stamped with `stamp` by `node`.
+    pub fn wins_over(&self, stamp: u64, node: &str) -> bool {
+        (self.stamp, self.node.as_str()) > (stamp, node)
+    }
+}
+
+pub fn write_update(stream: &mut impl Write, update: &Update) -> std::io::Result<()> {
+    let json = serde_json::to_vec(update)?;
+    let len = u32::try_from(json.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Update too large"))?;
+    // one write, so updates of several threads do not interleave
+    let mut frame = Vec::with_capacity(json.len() + 4);
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(&json);
+    stream.write_all(&frame)
+}
+
+/// Reads the next update, an error once the peer closed the connection.
+pub fn read_update(stream: &mut impl Read) -> std::io::Result<Update> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Frame of {} bytes", len),
+        ));
+    }
+    let mut json = vec![0u8; len as usize];
+    stream.read_exact(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let update = Update {
+            key: "robot/speed".to_string(),
+            value: Some(TypedBlackboardValue::Int(3)),
+            stamp: 1000,
+            node: "robot".to_string(),
+        };
+        let removed = Update {
+            value: None,
+            ..update.clone()
+        };
+        let mut frames = Vec::new();
+        write_update(&mut frames, &update).unwrap();
+        write_update(&mut frames, &removed).unwrap();
+
+        let mut reader = frames.as_slice();
+        assert_eq!(read_update(&mut reader).unwrap(), update);
+        assert_eq!(read_update(&mut reader).unwrap(), removed);
+        assert!(read_update(&mut reader).is_err());
+
+        let mut oversized = (MAX_FRAME + 1).to_be_bytes().to_vec();
+        oversized.extend_from_slice(b"{}");
+        assert!(read_update(&mut oversized.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_wins_over() {
+        let update = Update {
+            key: "answer".to_string(),
+            value: None,
+            stamp: 1000,
+            node: "b".to_string(),
+        };
+        assert!(update.wins_over(999, "z"));
+        assert!(update.wins_over(1000, "a"));
+        assert!(!update.wins_over(1000, "b"));
+        assert!(!update.wins_over(1001, "a"));
+    }
+}
@@ -0,0 +1,106 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::testing::Host;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+// the next update of a bridge, a length prefixed json
+fn read_update(stream: &mut TcpStream) -> serde_json::Value {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).unwrap();
+    let mut json = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut json).unwrap();
+    serde_json::from_slice(&json).unwrap()
+}
+
+fn write_update(stream: &mut TcpStream, update: serde_json::Value) {
+    let json = update.to_string();
+    stream.write_all(&(json.len() as u32).to_be_bytes()).unwrap();
+    stream.write_all(json.as_bytes()).unwrap();
+}
+
+#[test]
+fn test_blackboard_bridge() {
+    let attributes = vec![
+        BlackboardEntry {
+            key: "listen".to_string(),
+            value: BlackboardValue::String("127.0.0.1:18800".to_string()),
+        },
+        BlackboardEntry {
+            key: "keys".to_string(),
+            value: BlackboardValue::Array(vec![BlackboardValue::String("robot/*".to_string())]),
+        },
+        BlackboardEntry {
+            key: "node".to_string(),
+            value: BlackboardValue::String("robot".to_string()),
+        },
+    ];
+    // written before the bridge started
+    let initial = vec![
+        BlackboardEntry {
+            key: "robot/speed".to_string(),
+            value: BlackboardValue::Int(3),
+        },
+        BlackboardEntry {
+            key: "other".to_string(),
+            value: BlackboardValue::Int(1),
+        },
+    ];
+    let host = Host::start(&[("blackboard", initial), ("blackboard_bridge", attributes)]).unwrap();
+    let client = host.client();
+
+    // a new peer gets the mirrored keys first, unstamped if they did not change since
+    let mut peer = TcpStream::connect(("127.0.0.1", 18800)).unwrap();
+    peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let snapshot = read_update(&mut peer);
+    assert_eq!(snapshot["key"], "robot/speed");
+    assert_eq!(snapshot["value"], serde_json::json!({"type": "int", "value": 3}));
+    assert_eq!(snapshot["node"], "robot");
+    assert_eq!(snapshot["stamp"], 0);
+    let wait_for = |key: &str, value: i32| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.get_i32(key) != Ok(value) {
+            assert!(Instant::now() < deadline, "{} is not {}", key, value);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+    write_update(
+        &mut peer,
+        serde_json::json!({"key": "robot/speed", "value": {"type": "int", "value": 2}, "stamp": 1, "node": "station"}),
+    );
+    wait_for("robot/speed", 2);
+
+    // changes here are sent, not the ones of the peer written back
+    client.set_i32("robot/speed", 4).unwrap();
+    client.set_i32("other", 2).unwrap();
+    let update = read_update(&mut peer);
+    assert_eq!(update["key"], "robot/speed");
+    assert_eq!(update["value"]["value"], 4);
+    let stamp = update["stamp"].as_u64().unwrap();
+    assert!(stamp > 1);
+
+    // older changes of the peer lose
+    write_update(
+        &mut peer,
+        serde_json::json!({"key": "robot/speed", "value": {"type": "int", "value": 1}, "stamp": stamp - 1, "node": "station"}),
+    );
+    write_update(
+        &mut peer,
+        serde_json::json!({"key": "robot/target", "value": {"type": "string", "value": "dock"}, "stamp": stamp + 1, "node": "station"}),
+    );
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while client.get_string("robot/target").is_err() {
+        assert!(Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(client.get_string("robot/target").unwrap(), "dock");
+    assert_eq!(client.get_i32("robot/speed").unwrap(), 4);
+
+    client.delete("robot/speed").unwrap();
+    let removed = read_update(&mut peer);
+    assert_eq!(removed["key"], "robot/speed");
+    assert!(removed["value"].is_null());
+
+    // connected peers do not keep the bridge from stopping
+    assert!(host.stop().is_empty());
+}
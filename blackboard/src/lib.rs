@@ -1,1262 +1,6855 @@
-use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
-use log::{debug, error, info, trace};
+use interfaces::blackboard::{
+    AuditRecord, BlackboardEntry, BlackboardEvent, BlackboardKeyInfo, BlackboardKeyStats,
+    BlackboardOperation, BlackboardOperationResult, BlackboardValue, HistorySample, NotifyReason,
+    SubscribeOptions, Timestamp, TypedBlackboardEntry, TypedBlackboardValue,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use dashmap::mapref::one::MappedRef;
+use dashmap::DashMap;
+use interfaces::lifecycle::{Lifecycle, PluginState};
+#[cfg(feature = "shm")]
+use interfaces::shared_memory::SharedBlackboard;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{debug, error, info, trace, warn};
 use once_cell::sync::OnceCell;
+use regex::Regex;
 use std::any::Any;
-use std::collections::HashMap;
-use std::ffi::CStr;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int, c_void};
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::cell::Cell;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::vec::Vec;
 
-static SUMMARY_MESSAGE: &str = "{
-    \"name\": \"blackboard\",
-    \"version\": \"0.1.0\",
-    \"library_type\": \"Service\",
-    \"provides\": [
-        {
-            \"capability\": \"blackboard_start\",
-            \"entry\": \"start\"
-        },
-        {
-            \"capability\": \"blackboard_stop\",
-            \"entry\": \"stop\"
-        },
-        {
-            \"capability\": \"blackboard_reset\",
-            \"entry\": \"reset\"
-        },
-        {
-            \"capability\": \"blackboard_size\",
-            \"entry\": \"size\"
-        },
-        {
-            \"capability\": \"blackboard_get_string\",
-            \"entry\": \"get_string\"
-        },
-        {
-            \"capability\": \"blackboard_set_string\",
-            \"entry\": \"set_string\"
-        },
-        {
-            \"capability\": \"blackboard_get_int\",
-            \"entry\": \"get_int\"
-        },
-        {
-            \"capability\": \"blackboard_set_int\",
-            \"entry\": \"set_int\"
-        },
-        {
-            \"capability\": \"blackboard_get_bool\",
-            \"entry\": \"get_bool\"
-        },
-        {
-            \"capability\": \"blackboard_set_bool\",
-            \"entry\": \"set_bool\"
-        },
-        {
-            \"capability\": \"blackboard_get_float\",
-            \"entry\": \"get_float\"
-        },
-        {
-            \"capability\": \"blackboard_set_float\",
-            \"entry\": \"set_float\"
-        },
-        {
-            \"capability\": \"blackboard_get_double\",
-            \"entry\": \"get_double\"
-        },
-        {
-            \"capability\": \"blackboard_set_double\",
-            \"entry\": \"set_double\"
-        },
-        {
-            \"capability\": \"blackboard_as_json_schema\",
-            \"entry\": \"as_json_schema\"
-        },
-        {
-            \"capability\": \"blackboard_subscribe\",
-            \"entry\": \"subscribe\"
-        },
-        { 
-            \"capability\": \"blackboard_unsubscribe\",
-            \"entry\": \"unsubscribe\"
-        }
-    ]
-}\0";
+/// Returned by the compare_and_set_* capabilities if the current value differs from the
+/// expected one and nothing was written.
+pub const VALUE_MISMATCH: c_int = RtStatus::ValueMismatch as c_int;
+
+/// Returned by the set_* capabilities in strict mode if the key already holds a value of a
+/// different type. Nothing is written.
+pub const TYPE_MISMATCH: c_int = RtStatus::TypeMismatch as c_int;
+
+/// Returned by `wait` if the key was not written before the timeout elapsed.
+pub const TIMEOUT: c_int = RtStatus::Timeout as c_int;
+
+/// Returned if the access rules forbid the caller to read or write the key, and by the set_*
+/// capabilities if a writer of higher priority holds the key, see `set_double_tagged`.
+pub const ACCESS_DENIED: c_int = RtStatus::AccessDenied as c_int;
+
+/// Returned by the set_* capabilities if the value violates the constraint declared for the key.
+/// Nothing is written.
+pub const CONSTRAINT_VIOLATION: c_int = RtStatus::ConstraintViolation as c_int;
+
+// rejected writes are published here as a json array, the latest last
+const VALIDATION_ERRORS_KEY: &str = "blackboard/validation_errors";
+const MAX_VALIDATION_ERRORS: usize = 20;
+
+// start attributes configuring the blackboard itself instead of becoming entries
+const CONFIG_KEYS: [&str; 8] = [
+    "persist_path",
+    "strict",
+    "history",
+    "audit",
+    "access",
+    "shared_memory",
+    "shared_memory_slots",
+    "priority_window_ms",
+];
+
+// how long a tagged write keeps writers of lower priority from overwriting the key, unless
+// `priority_window_ms` is given
+const PRIORITY_WINDOW: Duration = Duration::from_millis(1000);
+
+// keys the shared memory region holds unless `shared_memory_slots` is given
+const SHARED_MEMORY_SLOTS: usize = 1024;
+
+/// Namespaces a component may read and write, e.g. `{read: [nav, robot], write: [nav]}`. A
+/// namespace covers the key of its name and all keys below it, `*` covers every key. Without a
+/// list the component is not restricted.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccessRule {
+    read: Option<Vec<String>>,
+    write: Option<Vec<String>>,
+}
 
-#[derive(Debug)]
-struct BlackBoardData {
-    data: HashMap<String, Box<dyn Any + Send>>,
-    listener: interfaces::capabilities::Capabilities,
-    user_data: HashMap<String, *mut c_void>,
-    key_to_listener: HashMap<String, Vec<String>>, // blackboard key
+fn in_namespace(key: &str, namespaces: &[String]) -> bool {
+    namespaces.iter().any(|namespace| {
+        namespace == "*"
+            || key
+                .strip_prefix(namespace.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
 }
 
-unsafe impl Send for BlackBoardData {}
-unsafe impl Sync for BlackBoardData {}
+/// Values a key accepts, declared next to its initial value in the start attributes, e.g.
+/// `{key: speed, value: 1.0, min: 0, max: 2.5}`. `min` and `max` bound numbers and the items of
+/// numeric arrays, strings have to match `pattern` as a whole and `enum` lists the accepted
+/// values.
+#[derive(Debug, Default, serde::Deserialize)]
+struct Constraint {
+    min: Option<f64>,
+    max: Option<f64>,
+    pattern: Option<Pattern>,
+    #[serde(rename = "enum")]
+    allowed: Option<Vec<serde_json::Value>>,
+}
 
-impl BlackBoardData {
-    fn new() -> Self {
-        Self {
-            data: HashMap::new(),
-            listener: interfaces::capabilities::Capabilities::new(),
-            user_data: HashMap::new(),
-            key_to_listener: HashMap::new(),
-        }
+#[derive(Debug, serde::Deserialize)]
+#[serde(try_from = "String")]
+struct Pattern {
+    source: String,
+    regex: Regex, // anchored at both ends
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&format!("^(?:{})$", source))?;
+        Ok(Pattern { source, regex })
     }
+}
 
-    fn subscribe(&mut self, key: &str, component: &str, callback: *mut c_void, user_data: *mut c_void) {
-        let listener_key = format!("{}_{}", key, component);
+// an entry of the start attributes, read for its constraint only
+#[derive(serde::Deserialize)]
+struct ConstrainedEntry {
+    key: String,
+    #[serde(flatten)]
+    constraint: Constraint,
+}
 
-        if callback.is_null() {
-            error!("Provided callback is null");
-            return;
-        }
+impl Constraint {
+    /// Constraints declared in the start attributes per key.
+    fn from_attributes(attributes: &str) -> Result<HashMap<String, Constraint>, String> {
+        let entries = serde_yml::from_str::<Option<Vec<ConstrainedEntry>>>(attributes)
+            .map_err(|e| format!("Failed to parse constraints: {}", e))?;
+        Ok(entries
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| !entry.constraint.is_empty())
+            .map(|entry| (entry.key, entry.constraint))
+            .collect())
+    }
 
-        if !self.key_to_listener.contains_key(key) {
-            self.key_to_listener
-                .insert(key.to_string(), vec![listener_key.clone()]);
+    fn is_empty(&self) -> bool {
+        self.min.is_none() && self.max.is_none() && self.pattern.is_none() && self.allowed.is_none()
+    }
+
+    /// Adds the constraint to the JSON schema `property` of its key, see `as_json_schema`.
+    fn annotate(&self, property: &mut serde_json::Value) {
+        if let Some(pattern) = &self.pattern {
+            property["pattern"] = pattern.regex.as_str().into();
+        }
+        if let Some(allowed) = &self.allowed {
+            property["enum"] = allowed.clone().into();
+        }
+        let numbers = if property["type"] == "array" {
+            &mut property["items"]
         } else {
-            if self
-                .key_to_listener
-                .get_mut(key)
-                .unwrap()
-                .contains(&listener_key)
-            {
-                debug!("Already subscribed");
-                return;
-            }
-            self.key_to_listener
-                .get_mut(key)
-                .unwrap()
-                .push(listener_key.clone());
+            property
+        };
+        if let Some(min) = self.min {
+            numbers["minimum"] = min.into();
         }
-
-        let cap = interfaces::capabilities::Capability::new(&listener_key, callback);
-        self.listener.add(cap);
-
-        if !user_data.is_null() {
-            self.user_data.insert(listener_key, user_data);
+        if let Some(max) = self.max {
+            numbers["maximum"] = max.into();
         }
+    }
 
-        debug!("Subscribing to key: {}", key);
+    /// Why `value` violates the constraint, None if it does not.
+    fn check(&self, value: &TypedBlackboardValue) -> Option<String> {
+        use TypedBlackboardValue::*;
+        let numbers: Vec<f64> = match value {
+            Int(v) => vec![*v as f64],
+            Int64(v) => vec![*v as f64],
+            Timestamp(v) => vec![*v as f64],
+            Float(v) => vec![*v as f64],
+            Double(v) => vec![*v],
+            IntArray(v) => v.iter().map(|v| *v as f64).collect(),
+            DoubleArray(v) => v.clone(),
+            _ => Vec::new(),
+        };
+        if let Some(min) = self.min {
+            if let Some(number) = numbers.iter().find(|number| **number < min) {
+                return Some(format!("{} is below the minimum {}", number, min));
+            }
+        }
+        if let Some(max) = self.max {
+            if let Some(number) = numbers.iter().find(|number| **number > max) {
+                return Some(format!("{} is above the maximum {}", number, max));
+            }
+        }
+        if let (Some(pattern), String(v)) = (&self.pattern, value) {
+            if !pattern.regex.is_match(v) {
+                return Some(format!("'{}' does not match the pattern '{}'", v, pattern.source));
+            }
+        }
+        if let Some(allowed) = &self.allowed {
+            let json = serde_json::to_value(value)
+                .ok()
+                .and_then(|typed| typed.get("value").cloned())
+                .unwrap_or_default();
+            // 1 and 1.0 are the same value
+            let equal = |a: &serde_json::Value| {
+                *a == json || a.as_f64().is_some_and(|a| Some(a) == json.as_f64())
+            };
+            if !allowed.iter().any(equal) {
+                return Some(format!(
+                    "{} is not one of {}",
+                    json,
+                    serde_json::Value::Array(allowed.clone())
+                ));
+            }
+        }
+        None
     }
+}
 
-    fn unsubscribe(&mut self, key: &str, component: &str) {
-        let listener_key = format!("{}_{}", key, component);
+#[derive(Debug, Default)]
+struct Config {
+    persist_path: Option<PathBuf>,  // snapshot loaded at start and written at stop
+    strict: bool,                   // lock the type of a key after its first write
+    history: HashMap<String, usize>, // number of values kept per key, e.g. `{pid/output: 50}`
+    audit: usize,                    // writes kept in the audit log, none by default
+    access: HashMap<String, AccessRule>, // per component, declared by the loader
+    shared_memory: Option<String>, // region the numeric values are mirrored to, created at start
+    shared_memory_slots: usize,
+    constraints: HashMap<String, Constraint>, // declared next to the initial values
+    priority_window: Duration, // see `set_double_tagged`
+}
 
-        if !self.key_to_listener.contains_key(key) {
-            debug!("No subscribers for key: {}", key);
-            return;
+impl Config {
+    fn new(key_values: &[BlackboardEntry]) -> Self {
+        let mut config = Self {
+            shared_memory_slots: SHARED_MEMORY_SLOTS,
+            priority_window: PRIORITY_WINDOW,
+            ..Self::default()
+        };
+        for entry in key_values {
+            match entry.key.as_str() {
+                "persist_path" => {
+                    if let BlackboardValue::String(value) = &entry.value {
+                        config.persist_path = Some(PathBuf::from(value));
+                    }
+                }
+                "strict" => {
+                    if let BlackboardValue::Bool(value) = &entry.value {
+                        config.strict = *value;
+                    }
+                }
+                "history" => {
+                    if let BlackboardValue::Json(serde_json::Value::Object(depths)) = &entry.value {
+                        for (key, depth) in depths {
+                            match depth.as_u64() {
+                                Some(depth) if depth > 0 => {
+                                    config.history.insert(key.clone(), depth as usize);
+                                }
+                                _ => warn!("Invalid history depth for key {}: {}", key, depth),
+                            }
+                        }
+                    }
+                }
+                "audit" => match &entry.value {
+                    BlackboardValue::Int(size) if *size >= 0 => config.audit = *size as usize,
+                    value => warn!("Invalid size of the audit log: {:?}", value),
+                },
+                "access" => {
+                    if let BlackboardValue::Json(rules) = &entry.value {
+                        match serde_json::from_value(rules.clone()) {
+                            Ok(rules) => config.access = rules,
+                            Err(e) => warn!("Invalid access rules: {}", e),
+                        }
+                    }
+                }
+                "shared_memory" => {
+                    if let BlackboardValue::String(name) = &entry.value {
+                        config.shared_memory = Some(name.clone());
+                    }
+                }
+                "shared_memory_slots" => match &entry.value {
+                    BlackboardValue::Int(slots) if *slots > 0 => {
+                        config.shared_memory_slots = *slots as usize
+                    }
+                    value => warn!("Invalid number of shared memory slots: {:?}", value),
+                },
+                "priority_window_ms" => match &entry.value {
+                    BlackboardValue::Int(ms) if *ms >= 0 => {
+                        config.priority_window = Duration::from_millis(*ms as u64)
+                    }
+                    value => warn!("Invalid priority window: {:?}", value),
+                },
+                _ => {}
+            }
         }
+        config
+    }
+}
 
-        let listeners = self.key_to_listener.get_mut(key).unwrap();
-        listeners.retain(|x| x != &listener_key);
-
-        // we need to remove the capability, too. but we do it later
+// notifications waiting for the dispatcher thread, further ones are coalesced by key
+const NOTIFY_QUEUE_SIZE: usize = 1024;
+// failing calls in a row after which a subscriber is dropped
+const MAX_CALLBACK_FAILURES: u32 = 10;
+
+type KeyCallback = interfaces::capabilities::Function<
+    unsafe extern "C" fn(key: *const c_char, user_data: *mut c_void) -> c_int,
+>;
+type EventCallback = interfaces::capabilities::Function<
+    unsafe extern "C" fn(key: *const c_char, event: *const c_char, user_data: *mut c_void) -> c_int,
+>;
+
+enum Callback {
+    Key(KeyCallback),
+    Event(EventCallback),
+}
 
-        if self.key_to_listener.get(key).unwrap().len() == 0 {
-            self.key_to_listener.remove(key);
-        }
+// a subscriber resolved while the change is applied, called later by the dispatcher
+struct Delivery {
+    listener: String,
+    callback: Callback,
+    user_data: *mut c_void,
+    limit: Option<RateLimit>,
+}
 
-        if self.user_data.contains_key(&listener_key) {
-            self.user_data.remove(&listener_key);
-        }
+/// Rate limit of a subscription, see `subscribe_with_options`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RateLimit {
+    interval: Duration, // between two notifications of a key
+    coalesce: bool,     // deliver the latest skipped change once due, drop it otherwise
+}
 
-        info!("Unsubscribing from key: {}", key);
-    }
+type Held = (Instant, Option<CString>, Delivery); // due time, event
 
-    fn notify(&self, key: &str) {
-        if !self.key_to_listener.contains_key(key) {
-            debug!("No subscribers for key: {}", key);
-            return;
-        }
+// notifications of rate limited subscribers per listener and key, shared by the blackboard and
+// the dispatcher thread
+#[derive(Default)]
+struct Limiter {
+    delivered: HashMap<(String, CString), Instant>,
+    held: HashMap<(String, CString), Held>, // latest skipped change of coalescing subscribers
+}
 
-        trace!("Notifying subscribers for key: {}", key);
-        let listeners = self.key_to_listener.get(key).unwrap();
+// user_data is owned by the subscriber, see `Dispatch`
+unsafe impl Send for Limiter {}
 
-        for listener in listeners {
-            trace!("Notifying listener: {}", listener);
-            let cap = self.listener.get(listener).unwrap();
-            
-            unsafe {
-                let f: interfaces::capabilities::Function<
-                    unsafe extern "C" fn(key: *const c_char, user_data: *mut c_void) -> c_int,
-                > = cap.get().unwrap();
-                trace!("Calling listener: {}", listener);
-                if self.user_data.contains_key(listener) && !self.user_data.get(listener).unwrap().is_null() {
-                    let user_data = self.user_data.get(listener).unwrap().clone();
-                    f(key.as_ptr() as *const c_char, user_data);
-                } else {
-                    f(key.as_ptr() as *const c_char, std::ptr::null_mut());
-                }
-                trace!("Listener called: {}", listener);
-            }
-        }
+impl std::fmt::Debug for Limiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Limiter")
+            .field("held", &self.held.len())
+            .finish()
     }
+}
 
-    fn is_key_valid(&self, key: &str) -> bool {
-        self.data.contains_key(key)
+impl Limiter {
+    // the deliveries to make now, changes arriving too early are held back or dropped
+    fn admit(
+        &mut self,
+        key: &CString,
+        event: Option<&CString>,
+        deliveries: Vec<Delivery>,
+        now: Instant,
+    ) -> Vec<Delivery> {
+        deliveries
+            .into_iter()
+            .filter_map(|delivery| {
+                let Some(limit) = delivery.limit else {
+                    return Some(delivery);
+                };
+                let id = (delivery.listener.clone(), key.clone());
+                match self.delivered.get(&id) {
+                    Some(last) if now < *last + limit.interval => {
+                        if limit.coalesce {
+                            let due = *last + limit.interval;
+                            self.held.insert(id, (due, event.cloned(), delivery));
+                        }
+                        None
+                    }
+                    _ => {
+                        self.held.remove(&id);
+                        self.delivered.insert(id, now);
+                        Some(delivery)
+                    }
+                }
+            })
+            .collect()
     }
 
-    fn set<T: 'static + std::marker::Send>(&mut self, key: &str, value: T) {
-        if !self.data.contains_key(key) {
-            self.data.insert(key.to_string(), Box::<T>::new(value));
-        } else {
-            let data = self.data.get_mut(key).unwrap();
-            *data = Box::<T>::new(value);
-        }
-        self.notify(key);
+    fn next_due(&self) -> Option<Instant> {
+        self.held.values().map(|(due, _, _)| *due).min()
     }
 
-    fn get<T: 'static>(&self, key: &str) -> Result<&T, String> {
-        let p_value = self.data.get(key);
-        match p_value {
-            Some(v) => match v.downcast_ref::<T>() {
-                Some(v) => Ok(v),
-                None => Err(format!("Failed to downcast value for key: {}", key)),
-            },
-            None => Err(format!("Key not found: {}", key)),
-        }
+    // held changes whose interval elapsed, with their key
+    fn take_due(&mut self, now: Instant) -> Vec<(CString, Option<CString>, Delivery)> {
+        let due: Vec<_> = self
+            .held
+            .iter()
+            .filter(|(_, (due, _, _))| *due <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        due.into_iter()
+            .map(|id| {
+                let (_, event, delivery) = self.held.remove(&id).unwrap();
+                self.delivered.insert(id.clone(), now);
+                (id.1, event, delivery)
+            })
+            .collect()
     }
 
-    fn reset(&mut self) {
-        self.data.clear();
+    // an unsubscribed listener may release its user data after the next flush
+    fn forget(&mut self, listener: &str) {
+        self.delivered.retain(|(held, _), _| held != listener);
+        self.held.retain(|(held, _), _| held != listener);
     }
 }
 
-static SINGLETON: OnceCell<Mutex<Option<BlackBoardData>>> = OnceCell::new();
-
-fn get_singleton() -> &'static Mutex<Option<BlackBoardData>> {
-    SINGLETON.get_or_init(|| {
-        trace!("Creating singleton");
-        Mutex::new(None)
-    })
+/// Return codes of the subscriber callbacks: 0 keeps the subscription, 1 ends it and a negative
+/// code is a failure. Subscribers failing `MAX_CALLBACK_FAILURES` times in a row are dropped.
+/// Other codes count as 0.
+#[derive(Debug, Default)]
+struct Outcomes {
+    failures: HashMap<String, u32>, // failing calls in a row per listener
+    warned: HashSet<String>,        // listeners whose failures were logged once
+    dropped: HashSet<String>,       // not called anymore until they subscribe again
+    unsubscribe: Vec<String>,       // dropped listeners the blackboard still has to remove
 }
 
-fn start_server(
-    _caps: &interfaces::bindings::Capabilities,
-    attributes: *const c_char,
-) -> Result<(), String> {
-    let mut blackboard_data = get_singleton().lock().unwrap();
-    if blackboard_data.is_some() {
-        return Err("Server is already running".to_string());
+impl Outcomes {
+    fn record(&mut self, listener: &str, code: c_int) {
+        match code {
+            1 => {
+                debug!("Listener {} unsubscribed itself", listener);
+                self.drop_listener(listener);
+            }
+            code if code < 0 => {
+                let failures = self.failures.entry(listener.to_string()).or_default();
+                *failures += 1;
+                let failures = *failures;
+                if self.warned.insert(listener.to_string()) {
+                    warn!(
+                        "Listener {} failed with {}, it is dropped after {} failures in a row",
+                        listener, code, MAX_CALLBACK_FAILURES
+                    );
+                }
+                if failures >= MAX_CALLBACK_FAILURES {
+                    warn!("Dropping listener {} after {} failures in a row", listener, failures);
+                    self.drop_listener(listener);
+                }
+            }
+            _ => {
+                self.failures.remove(listener);
+            }
+        }
     }
 
-    *blackboard_data = Some(BlackBoardData::new());
+    fn drop_listener(&mut self, listener: &str) {
+        self.failures.remove(listener);
+        if self.dropped.insert(listener.to_string()) {
+            self.unsubscribe.push(listener.to_string());
+        }
+    }
 
-    if !attributes.is_null() {
-        let attributes = unsafe { CStr::from_ptr(attributes).to_str().unwrap() };
-        trace!("Attributes: {}", attributes);
-        serde_yml::from_str(attributes)
-            .map_err(|e| format!("Failed to parse attributes: {}", e))
-            .and_then(|entries: Vec<BlackboardEntry>| {
-                // String(String),
-                // Int(i32),
-                // Float(f32),
-                // Double(f64),
-                // Bool(bool),
-                for entry in entries {
-                    match entry.value {
-                        BlackboardValue::String(v) => {
-                            &blackboard_data.as_mut().unwrap().set(entry.key.as_str(), v)
-                        }
-                        BlackboardValue::Int(v) => {
-                            &blackboard_data.as_mut().unwrap().set(entry.key.as_str(), v)
-                        }
-                        BlackboardValue::Float(v) => {
-                            &blackboard_data.as_mut().unwrap().set(entry.key.as_str(), v)
-                        }
-                        BlackboardValue::Double(v) => {
-                            &blackboard_data.as_mut().unwrap().set(entry.key.as_str(), v)
-                        }
-                        BlackboardValue::Bool(v) => {
-                            &blackboard_data.as_mut().unwrap().set(entry.key.as_str(), v)
-                        }
-                    };
-                }
-                Ok(())
-            })?;
+    // a listener subscribing again starts over
+    fn resume(&mut self, listener: &str) {
+        self.failures.remove(listener);
+        self.warned.remove(listener);
+        self.dropped.remove(listener);
+        self.unsubscribe.retain(|dropped| dropped != listener);
     }
-    info!("Blackboard is up and running");
-    Ok(())
-}
 
-#[no_mangle]
-pub extern "C" fn start(
-    caps: &interfaces::bindings::Capabilities,
-    attributes: *const c_char,
-) -> c_int {
-    env_logger::init();
-    debug!("Starting server");
-    match start_server(caps, attributes) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to start server: {}", e);
-            -1
-        }
+    // removed by the blackboard already, deliveries still queued are skipped
+    fn revoke(&mut self, listener: &str) {
+        self.failures.remove(listener);
+        self.dropped.insert(listener.to_string());
+        self.unsubscribe.retain(|dropped| dropped != listener);
     }
 }
 
-#[no_mangle]
-pub extern "C" fn stop() -> c_int {
-    debug!("Stopping server");
-    let mut blackboard_data = get_singleton().lock().unwrap();
-    *blackboard_data = None;
-    info!("Blackboard is stopped");
-    0
+enum Dispatch {
+    Notify {
+        key: CString,
+        event: Option<CString>,
+        deliveries: Vec<Delivery>,
+    },
+    Flush(mpsc::Sender<()>), // answered once everything queued before it is delivered
 }
 
-#[no_mangle]
-pub extern "C" fn summary() -> *const c_char {
-    // summary message + null terminator
-    SUMMARY_MESSAGE.as_ptr() as *const c_char
+// user_data is owned by the subscriber, which has to keep it alive while subscribed
+unsafe impl Send for Dispatch {}
+
+// notifications which did not fit into the queue, only the latest one per key, in the order their
+// keys overflowed. Delivered once the queue is empty, so they never overtake queued ones.
+#[derive(Default)]
+struct Overflow {
+    pending: Vec<(CString, Option<CString>, Vec<Delivery>)>,
+    coalesced: u64, // notifications replaced by a later one of their key, never delivered
 }
 
-fn reset_intern() -> Result<(), String> {
-    let mut blackboard_data = get_singleton().lock().unwrap();
-    if blackboard_data.is_none() {
-        return Err("Server is not running".to_string());
+// user_data is owned by the subscriber, see `Dispatch`
+unsafe impl Send for Overflow {}
+
+impl std::fmt::Debug for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Overflow")
+            .field("pending", &self.pending.len())
+            .field("coalesced", &self.coalesced)
+            .finish()
     }
-    blackboard_data.as_mut().unwrap().reset();
-    Ok(())
 }
 
-#[no_mangle]
-pub extern "C" fn reset() -> c_int {
-    match reset_intern() {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to reset server: {}", e);
-            -1
+impl Overflow {
+    fn push(&mut self, key: CString, event: Option<CString>, deliveries: Vec<Delivery>) {
+        match self.pending.iter_mut().find(|(pending, _, _)| *pending == key) {
+            Some(pending) => {
+                *pending = (key, event, deliveries);
+                self.coalesced += 1;
+            }
+            None => self.pending.push((key, event, deliveries)),
         }
     }
 }
 
-fn size_intern() -> Result<usize, String> {
-    let blackboard_data = get_singleton().lock().unwrap();
-    if blackboard_data.is_none() {
-        return Err("Server is not running".to_string());
-    }
-
-    Ok(blackboard_data.as_ref().unwrap().data.len())
+/// Calls subscriber callbacks on a dedicated thread, so callbacks never run while the
+/// blackboard is locked and may call back into it.
+#[derive(Debug)]
+struct Dispatcher {
+    sender: Option<mpsc::SyncSender<Dispatch>>,
+    thread: Option<JoinHandle<()>>,
+    limiter: Arc<Mutex<Limiter>>,
+    outcomes: Arc<Mutex<Outcomes>>,
+    overflow: Arc<Mutex<Overflow>>,
 }
 
-#[no_mangle]
-pub extern "C" fn size() -> c_int {
-    match size_intern() {
-        Ok(size) => size as c_int,
-        Err(e) => {
-            error!("Failed to get size: {}", e);
-            -1
+impl Dispatcher {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Dispatch>(NOTIFY_QUEUE_SIZE);
+        let limiter = Arc::new(Mutex::new(Limiter::default()));
+        let thread_limiter = limiter.clone();
+        let outcomes = Arc::new(Mutex::new(Outcomes::default()));
+        let thread_outcomes = outcomes.clone();
+        let overflow = Arc::new(Mutex::new(Overflow::default()));
+        let thread_overflow = overflow.clone();
+        let thread = std::thread::Builder::new()
+            .name("blackboard-notify".to_string())
+            .spawn(move || {
+                let notify = |key: CString, event: Option<CString>, deliveries| {
+                    let deliveries = thread_limiter.lock().unwrap().admit(
+                        &key,
+                        event.as_ref(),
+                        deliveries,
+                        Instant::now(),
+                    );
+                    Self::deliver(&key, event.as_ref(), deliveries, &thread_outcomes)
+                };
+                // not locked while delivering, callbacks may notify again
+                let drain = || {
+                    let pending = std::mem::take(&mut thread_overflow.lock().unwrap().pending);
+                    for (key, event, deliveries) in pending {
+                        notify(key, event, deliveries);
+                    }
+                };
+                loop {
+                    let dispatch = match receiver.try_recv() {
+                        Ok(dispatch) => Ok(dispatch),
+                        Err(_) => {
+                            drain();
+                            // wakes up for held changes falling due
+                            let next_due = thread_limiter.lock().unwrap().next_due();
+                            match next_due {
+                                Some(due) => receiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+                                None => receiver.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                            }
+                        }
+                    };
+                    match dispatch {
+                        Ok(Dispatch::Notify {
+                            key,
+                            event,
+                            deliveries,
+                        }) => notify(key, event, deliveries),
+                        Ok(Dispatch::Flush(done)) => {
+                            drain();
+                            let _ = done.send(());
+                        }
+                        Err(mpsc::RecvTimeoutError::Timeout) => {}
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    }
+                    let due = thread_limiter.lock().unwrap().take_due(Instant::now());
+                    for (key, event, delivery) in due {
+                        Self::deliver(&key, event.as_ref(), vec![delivery], &thread_outcomes);
+                    }
+                }
+                debug!("Notification dispatcher stopped");
+            })
+            .expect("Failed to spawn notification dispatcher");
+        Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            limiter,
+            outcomes,
+            overflow,
         }
     }
-}
 
-fn set_string_intern(ckey: *const c_char, cvalue: *const c_char) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+    // notifications replaced by a later one of their key while the queue was full
+    fn coalesced(&self) -> u64 {
+        self.overflow.lock().unwrap().coalesced
     }
 
-    if cvalue.is_null() {
-        return Err("Input value is null pointer".to_string());
+    fn forget(&self, listener: &str) {
+        self.limiter.lock().unwrap().forget(listener);
     }
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
-    let value = unsafe { CStr::from_ptr(cvalue).to_str().unwrap() };
+    fn resume(&self, listener: &str) {
+        self.outcomes.lock().unwrap().resume(listener);
+    }
 
-    {
-        let mut blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+    fn revoke(&self, listener: &str) {
+        self.forget(listener);
+        self.outcomes.lock().unwrap().revoke(listener);
+    }
+
+    // listeners dropped since the last call, to be unsubscribed by the blackboard
+    fn dropped(&self) -> Vec<String> {
+        std::mem::take(&mut self.outcomes.lock().unwrap().unsubscribe)
+    }
+
+    fn deliver(
+        key: &CString,
+        event: Option<&CString>,
+        deliveries: Vec<Delivery>,
+        outcomes: &Mutex<Outcomes>,
+    ) {
+        for delivery in deliveries {
+            if outcomes.lock().unwrap().dropped.contains(&delivery.listener) {
+                continue;
+            }
+            trace!("Calling listener: {}", delivery.listener);
+            let code = unsafe {
+                match delivery.callback {
+                    Callback::Key(f) => f(key.as_ptr(), delivery.user_data),
+                    Callback::Event(f) => {
+                        let Some(event) = event else {
+                            continue;
+                        };
+                        f(key.as_ptr(), event.as_ptr(), delivery.user_data)
+                    }
+                }
+            };
+            trace!("Listener called: {} ({})", delivery.listener, code);
+            outcomes.lock().unwrap().record(&delivery.listener, code);
         }
-        blackboard_data
-            .as_mut()
-            .unwrap()
-            .set(key, value.to_string());
     }
 
-    Ok(())
-}
+    fn is_dispatcher_thread(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|t| t.thread().id() == std::thread::current().id())
+    }
 
-#[no_mangle]
-pub extern "C" fn set_string(ckey: *const c_char, cvalue: *const c_char) -> c_int {
-    match set_string_intern(ckey, cvalue) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to set string: {}", e);
-            -1
+    fn notify(&self, key: CString, event: Option<CString>, deliveries: Vec<Delivery>) {
+        let Some(sender) = self.sender.as_ref() else {
+            return;
+        };
+        // may run on the dispatcher thread, which would wait for itself on a full queue
+        let mut overflow = self.overflow.lock().unwrap();
+        // once notifications overflowed, later ones follow them to keep their order
+        if overflow.pending.is_empty() {
+            match sender.try_send(Dispatch::Notify { key, event, deliveries }) {
+                Ok(_) => {}
+                Err(mpsc::TrySendError::Full(Dispatch::Notify { key, event, deliveries })) => {
+                    warn!("Notification queue is full, coalescing notifications by key");
+                    overflow.push(key, event, deliveries);
+                }
+                Err(mpsc::TrySendError::Full(_)) => {}
+                Err(mpsc::TrySendError::Disconnected(_)) => {
+                    error!("Notification dispatcher is not running")
+                }
+            }
+        } else {
+            overflow.push(key, event, deliveries);
         }
     }
 }
 
-fn get_string_intern(ckey: *const c_char, cvalue: *mut c_char) -> Result<i32, String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+impl Drop for Dispatcher {
+    // delivers what is still queued; a callback stopping the blackboard cannot wait for itself
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != std::thread::current().id() {
+                let _ = thread.join();
+            }
+        }
     }
+}
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+// usage counters of a key, kept until the key is deleted so they survive expiry
+#[derive(Debug)]
+struct KeyStats {
+    writes: u64,
+    revision: u64,     // blackboard revision of the last write
+    reads: AtomicU64,  // getters only read lock the key
+    last_write: SystemTime,
+}
 
-    {
-        let blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
-        }
-        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
-            return Err(format!("Key not found: {}", key));
-        }
+// a key and everything kept about it, locked on its own so calls for different keys do not
+// wait for each other. An expired key keeps its slot without a value.
+#[derive(Debug)]
+struct Slot {
+    value: Option<Box<dyn Any + Send + Sync>>,
+    ttl: Option<Duration>, // time-to-live, renewed by every write
+    expires_at: Option<Instant>,
+    stats: KeyStats,
+    locked_type: Option<&'static str>, // type name in strict mode
+    history: VecDeque<(SystemTime, TypedBlackboardValue)>, // newest value first
+    tag: Option<(WriteTag, Instant)>, // of the last write if it was tagged, and when
+}
 
-        let v = blackboard_data.as_ref().unwrap().get::<String>(key);
+// the writer of a tagged write, untagged writes have priority 0
+#[derive(Debug, Clone)]
+struct WriteTag {
+    source: String,
+    priority: i32,
+}
 
-        match v {
-            Ok(v) => {
-                if !cvalue.is_null() {
-                    let tmp_value = v.as_bytes();
-                    unsafe {
-                        std::ptr::copy_nonoverlapping(
-                            tmp_value.as_ptr(),
-                            cvalue as *mut u8,
-                            tmp_value.len(),
-                        );
-                    }
-                }
-                return Ok(v.len() as i32 + 1);
-            }
-            Err(e) => {
-                return Err(format!("Error: {}", e));
-            }
+impl Slot {
+    fn new() -> Self {
+        Slot {
+            value: None,
+            ttl: None,
+            expires_at: None,
+            stats: KeyStats {
+                writes: 0,
+                revision: 0,
+                reads: AtomicU64::new(0),
+                last_write: SystemTime::now(),
+            },
+            locked_type: None,
+            history: VecDeque::new(),
+            tag: None,
         }
     }
+
+    fn expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= Instant::now())
+    }
 }
 
-#[no_mangle]
-pub extern "C" fn get_string(ckey: *const c_char, cvalue: *mut c_char) -> c_int {
-    match get_string_intern(ckey, cvalue) {
-        Ok(size) => size,
-        Err(e) => {
-            error!("Failed to get string: {}", e);
-            -1
+// wakes up the threads in `wait` after every write
+#[derive(Debug, Default)]
+struct Written {
+    revision: AtomicU64, // counts all writes, lets `wait` tell old values from new ones
+    waiters: AtomicUsize,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl Written {
+    fn notify(&self) {
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            self.wake();
         }
     }
+
+    fn wake(&self) {
+        let _lock = self.lock.lock().unwrap();
+        self.condvar.notify_all();
+    }
 }
 
-fn get_int_intern(ckey: *const c_char, value: *mut c_int) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+// counts a thread in `wait` until it returns, writers only wake up waiting threads
+struct Waiting<'a>(&'a Written);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.waiters.fetch_sub(1, Ordering::SeqCst);
     }
+}
 
-    if value.is_null() {
-        return Err("Output value is null pointer".to_string());
+// the subscriptions, resolved for every change of a key
+#[derive(Debug)]
+struct Listeners {
+    listener: interfaces::capabilities::Capabilities,
+    user_data: HashMap<String, *mut c_void>,
+    key_to_listener: HashMap<String, Vec<String>>, // blackboard key
+    event_listener: HashSet<String>,                // listeners expecting the v2 event payload
+    rate_limits: HashMap<String, RateLimit>,        // per listener
+    wildcards: HashSet<String>,                     // subscribed keys ending with '*'
+    libraries: HashMap<String, usize>,              // per listener, see `library_of`
+}
+
+impl Listeners {
+    fn new() -> Self {
+        Self {
+            listener: interfaces::capabilities::Capabilities::new(),
+            user_data: HashMap::new(),
+            key_to_listener: HashMap::new(),
+            event_listener: HashSet::new(),
+            rate_limits: HashMap::new(),
+            wildcards: HashSet::new(),
+            libraries: HashMap::new(),
+        }
     }
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    fn remove(&mut self, key: &str, listener_key: &str) {
+        if !self.key_to_listener.contains_key(key) {
+            debug!("No subscribers for key: {}", key);
+            return;
+        }
 
-    {
-        let blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+        let listeners = self.key_to_listener.get_mut(key).unwrap();
+        listeners.retain(|x| x != listener_key);
+        self.listener.remove(listener_key);
+
+        if self.key_to_listener.get(key).unwrap().is_empty() {
+            self.key_to_listener.remove(key);
+            self.wildcards.remove(key);
         }
-        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
-            return Err(format!("Key not found: {}", key));
+
+        if self.user_data.contains_key(listener_key) {
+            self.user_data.remove(listener_key);
         }
+        self.event_listener.remove(listener_key);
+        self.rate_limits.remove(listener_key);
+        self.libraries.remove(listener_key);
 
-        let v = blackboard_data.as_ref().unwrap().get::<i32>(key);
+        info!("Unsubscribing from key: {}", key);
+    }
 
-        match v {
-            Ok(v) => {
-                unsafe {
-                    *value = *v as c_int;
-                }
-                return Ok(());
-            }
-            Err(e) => {
-                return Err(format!("Error: {}", e));
+    fn remove_key(&mut self, key: &str) {
+        if let Some(listeners) = self.key_to_listener.remove(key) {
+            for listener in listeners {
+                self.listener.remove(&listener);
+                self.user_data.remove(&listener);
+                self.event_listener.remove(&listener);
+                self.rate_limits.remove(&listener);
+                self.libraries.remove(&listener);
             }
         }
     }
-}
 
-#[no_mangle]
-pub extern "C" fn get_int(ckey: *const c_char, value: *mut c_int) -> c_int {
-    match get_int_intern(ckey, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to get int: {}", e);
-            -1
+    // listeners with their callback in the library loaded at `base`, with their keys
+    fn owned_by(&self, base: usize) -> Vec<(String, String)> {
+        self.key_to_listener
+            .iter()
+            .flat_map(|(key, listeners)| listeners.iter().map(move |listener| (key, listener)))
+            .filter(|(_, listener)| self.libraries.get(*listener) == Some(&base))
+            .map(|(key, listener)| (key.clone(), listener.clone()))
+            .collect()
+    }
+
+    // listeners of the exact key followed by those of matching wildcard subscriptions,
+    // e.g. "robot/pose/*" matches "robot/pose/x"
+    fn listeners_for(&self, key: &str) -> Vec<&String> {
+        let mut listeners: Vec<&String> = self
+            .key_to_listener
+            .get(key)
+            .map(|l| l.iter().collect())
+            .unwrap_or_default();
+        for pattern in self.wildcards.iter() {
+            if key.starts_with(&pattern[..pattern.len() - 1]) {
+                listeners.extend(self.key_to_listener.get(pattern).into_iter().flatten());
+            }
         }
+        listeners
     }
-}
 
-fn set_int_intern(ckey: *const c_char, value: c_int) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+    fn has_listeners(&self, key: &str) -> bool {
+        self.key_to_listener.contains_key(key)
+            || self
+                .wildcards
+                .iter()
+                .any(|pattern| key.starts_with(&pattern[..pattern.len() - 1]))
     }
+}
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+#[derive(Debug)]
+struct BlackBoardData {
+    data: DashMap<String, Slot>,
+    listeners: RwLock<Listeners>,
+    pending: Mutex<Option<Vec<BlackboardEvent>>>, // events held back while a batch is applied
+    written: Arc<Written>,
+    rejecting: Mutex<()>, // keeps concurrent rejections from overwriting each other
+    dispatcher: Dispatcher,
+    config: Config,
+    sessions: RwLock<HashMap<c_int, String>>, // component per session token
+    audit: Mutex<VecDeque<AuditRecord>>, // oldest write first, see `Config::audit`
+    #[cfg(feature = "shm")]
+    shared: Option<Mutex<SharedBlackboard>>, // the region has one writer at a time
+}
 
-    {
-        let mut blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+unsafe impl Send for BlackBoardData {}
+unsafe impl Sync for BlackBoardData {}
+
+impl BlackBoardData {
+    fn new() -> Self {
+        Self {
+            data: DashMap::new(),
+            listeners: RwLock::new(Listeners::new()),
+            pending: Mutex::new(None),
+            written: Arc::new(Written::default()),
+            rejecting: Mutex::new(()),
+            dispatcher: Dispatcher::new(),
+            config: Config::default(),
+            sessions: RwLock::new(HashMap::new()),
+            audit: Mutex::new(VecDeque::new()),
+            #[cfg(feature = "shm")]
+            shared: None,
         }
-        blackboard_data.as_mut().unwrap().set(key, value);
     }
 
-    Ok(())
-}
-
-#[no_mangle]
-pub extern "C" fn set_int(ckey: *const c_char, value: c_int) -> c_int {
-    match set_int_intern(ckey, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to set int: {}", e);
-            -1
+    /// Creates the shared memory region configured in `shared_memory`, see
+    /// `interfaces::shared_memory`.
+    fn open_shared_memory(&mut self) -> Result<(), String> {
+        let Some(name) = self.config.shared_memory.clone() else {
+            return Ok(());
+        };
+        #[cfg(feature = "shm")]
+        {
+            let slots = self.config.shared_memory_slots;
+            self.shared = Some(Mutex::new(SharedBlackboard::create(&name, slots)?));
+            info!("Numeric values are mirrored to shared memory {}", name);
         }
+        #[cfg(not(feature = "shm"))]
+        warn!("Shared memory {} is ignored, the blackboard is built without `shm`", name);
+        Ok(())
     }
-}
 
-fn get_float_intern(ckey: *const c_char, value: *mut f32) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+    // writes the value of `key` to the shared memory region, numbers only
+    #[cfg(feature = "shm")]
+    fn mirror(&self, key: &str, value: Option<&dyn Any>) {
+        let Some(shared) = &self.shared else {
+            return;
+        };
+        let shared = shared.lock().unwrap();
+        match value.and_then(shared_value) {
+            Some(value) => {
+                if !shared.write(key, &value) {
+                    trace!("Key {} is not mirrored to shared memory", key);
+                }
+            }
+            None => shared.remove(key),
+        }
     }
 
-    if value.is_null() {
-        return Err("Output value is null pointer".to_string());
+    /// Number of keys holding a value.
+    fn len(&self) -> usize {
+        self.data.iter().filter(|slot| slot.value.is_some()).count()
     }
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    fn subscribe(
+        &self,
+        key: &str,
+        component: &str,
+        callback: *mut c_void,
+        user_data: *mut c_void,
+        with_event: bool,
+        limit: Option<RateLimit>,
+    ) -> Result<(), RtError> {
+        let listener_key = format!("{}_{}", key, component);
 
-    {
-        let blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
-        }
-        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
-            return Err(format!("Key not found: {}", key));
+        if callback.is_null() {
+            return Err(RtError::new(RtStatus::NullArgument, "Provided callback is null"));
         }
 
-        let v = blackboard_data.as_ref().unwrap().get::<f32>(key);
+        self.drop_listeners();
+        self.dispatcher.resume(&listener_key);
 
-        match v {
-            Ok(v) => {
-                unsafe {
-                    *value = *v;
-                }
-                return Ok(());
-            }
-            Err(e) => {
-                return Err(format!("Error: {}", e));
-            }
+        let mut listeners = self.listeners.write().unwrap();
+        if listeners
+            .key_to_listener
+            .get(key)
+            .is_some_and(|listeners| listeners.contains(&listener_key))
+        {
+            debug!("Already subscribed");
+            return Ok(());
         }
-    }
-}
 
-#[no_mangle]
-pub extern "C" fn get_float(key: *const c_char, value: *mut f32) -> c_int {
-    match get_float_intern(key, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to get float: {}", e);
-            -1
-        }
-    }
-}
+        let cap = interfaces::capabilities::Capability::new(&listener_key, callback);
+        listeners.listener.add(cap)?;
 
-fn set_float_intern(ckey: *const c_char, value: f32) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
-    }
+        listeners
+            .key_to_listener
+            .entry(key.to_string())
+            .or_default()
+            .push(listener_key.clone());
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+        if with_event {
+            listeners.event_listener.insert(listener_key.clone());
+        }
 
-    {
-        let mut blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+        if let Some(limit) = limit {
+            listeners.rate_limits.insert(listener_key.clone(), limit);
         }
-        blackboard_data.as_mut().unwrap().set(key, value);
-    }
 
-    Ok(())
-}
+        if key.ends_with('*') {
+            listeners.wildcards.insert(key.to_string());
+        }
 
-#[no_mangle]
-pub extern "C" fn set_float(key: *const c_char, value: f32) -> c_int {
-    match set_float_intern(key, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to set float: {}", e);
-            -1
+        if !user_data.is_null() {
+            listeners.user_data.insert(listener_key.clone(), user_data);
+        }
+        match library_of(callback) {
+            Some(library) => {
+                listeners.libraries.insert(listener_key, library.dli_fbase as usize);
+            }
+            None => warn!("Callback of {} is in no loaded library", listener_key),
         }
-    }
-}
 
-fn get_bool_intern(ckey: *const c_char, value: *mut bool) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+        debug!("Subscribing to key: {}", key);
+        Ok(())
     }
 
-    if value.is_null() {
-        return Err("Output value is null pointer".to_string());
+    fn unsubscribe(&self, key: &str, component: &str) {
+        let listener_key = format!("{}_{}", key, component);
+        self.dispatcher.forget(&listener_key);
+        self.listeners.write().unwrap().remove(key, &listener_key);
     }
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    // unsubscribes every listener with its callback in the library loaded at `base` before the
+    // library is unloaded, queued notifications do not call them anymore
+    fn revoke(&self, base: usize) -> usize {
+        let mut listeners = self.listeners.write().unwrap();
+        let revoked = listeners.owned_by(base);
+        for (key, listener) in revoked.iter() {
+            self.dispatcher.revoke(listener);
+            listeners.remove(key, listener);
+        }
+        revoked.len()
+    }
 
-    {
-        let blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+    // unsubscribes the listeners dropped by the dispatcher, see `Outcomes`
+    fn drop_listeners(&self) {
+        let dropped = self.dispatcher.dropped();
+        if dropped.is_empty() {
+            return;
         }
-        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
-            return Err(format!("Key not found: {}", key));
+        let mut listeners = self.listeners.write().unwrap();
+        for listener in dropped {
+            self.dispatcher.forget(&listener);
+            let keys: Vec<String> = listeners
+                .key_to_listener
+                .iter()
+                .filter(|(_, listeners)| listeners.contains(&listener))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in keys {
+                listeners.remove(&key, &listener);
+            }
         }
+    }
 
-        let v = blackboard_data.as_ref().unwrap().get::<bool>(key);
+    fn drop_key_listeners(&self, key: &str) {
+        self.listeners.write().unwrap().remove_key(key);
+    }
 
-        match v {
-            Ok(v) => {
-                unsafe {
-                    *value = *v;
-                }
-                return Ok(());
-            }
-            Err(e) => {
-                return Err(format!("Error: {}", e));
+    // resolves the subscribers of the event now and leaves calling them to the dispatcher
+    fn notify(&self, event: BlackboardEvent) {
+        self.drop_listeners();
+        let key = event.key.as_str();
+        let deliveries: Vec<Delivery> = {
+            let state = self.listeners.read().unwrap();
+            let listeners = state.listeners_for(key);
+            if listeners.is_empty() {
+                debug!("No subscribers for key: {}", key);
+                return;
             }
-        }
+            listeners
+                .into_iter()
+                .map(|listener| {
+                    let cap = state.listener.get(listener).unwrap();
+                    let callback = unsafe {
+                        if state.event_listener.contains(listener.as_str()) {
+                            Callback::Event(cap.get().unwrap())
+                        } else {
+                            Callback::Key(cap.get().unwrap())
+                        }
+                    };
+                    Delivery {
+                        listener: listener.clone(),
+                        callback,
+                        user_data: state
+                            .user_data
+                            .get(listener)
+                            .copied()
+                            .unwrap_or(std::ptr::null_mut()),
+                        limit: state.rate_limits.get(listener).copied(),
+                    }
+                })
+                .collect()
+        };
+
+        trace!("Notifying subscribers for key: {} ({:?})", key, event.reason);
+        // keys may come from owned strings (e.g. expiry), so hand out a null terminated copy
+        let ckey = CString::new(key).unwrap();
+        let cevent = if deliveries.iter().any(|d| matches!(d.callback, Callback::Event(_))) {
+            serde_json::to_string(&event)
+                .map_err(|e| error!("Failed to serialize event for key {}: {}", key, e))
+                .ok()
+                .map(|e| CString::new(e).unwrap())
+        } else {
+            None
+        };
+
+        self.dispatcher.notify(ckey, cevent, deliveries);
     }
-}
 
-#[no_mangle]
-pub extern "C" fn get_bool(key: *const c_char, value: *mut bool) -> c_int {
-    match get_bool_intern(key, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to get bool: {}", e);
-            -1
+    // captures the value of a slot for an event, only if somebody listens
+    fn event_value(&self, key: &str, slot: &Slot) -> Option<BlackboardValue> {
+        if !self.listeners.read().unwrap().has_listeners(key) {
+            return None;
         }
+        slot.value
+            .as_deref()
+            .and_then(|v| BlackboardValue::from_any(v))
     }
-}
 
-fn set_bool_intern(ckey: *const c_char, value: bool) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+    // notifies right away, or merges the event into the pending ones during a batch so every
+    // key is reported once with its value from before and after the batch
+    fn publish(&self, event: BlackboardEvent) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            match pending.iter_mut().find(|e| e.key == event.key) {
+                Some(existing) => {
+                    existing.reason = event.reason;
+                    existing.new = event.new;
+                }
+                None => pending.push(event),
+            }
+            return;
+        }
+        self.notify(event);
     }
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    /// Applies all entries or none of them. Subscribers are notified once per key after the
+    /// last entry is written. Numbers keep the numeric type of the key they update.
+    fn set_batch(&mut self, entries: Vec<BlackboardEntry>) -> Result<(), String> {
+        let values = entries
+            .into_iter()
+            .map(|entry| {
+                let value = TypedBlackboardValue::try_from(entry.value)
+                    .map_err(|e| format!("Unsupported value for key {}: {}", entry.key, e))?;
+                let value = match self.peek(&entry.key, TypedBlackboardValue::from_any).flatten() {
+                    Some(current) => value.coerce_to(&current),
+                    None => value,
+                };
+                if self.type_locked(&entry.key, value.type_name()) {
+                    return Err(format!(
+                        "Type of key {} is locked, cannot store {}",
+                        entry.key,
+                        value.type_name()
+                    ));
+                }
+                self.validate(&entry.key, &value)?;
+                Ok((entry.key, value))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        *self.pending.lock().unwrap() = Some(Vec::new());
+        let result = values
+            .into_iter()
+            .try_for_each(|(key, value)| self.set_typed(&key, value).map_err(|e| e.message));
+        self.end_batch();
+        result
+    }
 
-    {
-        let mut blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+    // notifies the events held back during a batch, deleted keys drop their subscribers after
+    // the last notification
+    fn end_batch(&self) {
+        let events = self.pending.lock().unwrap().take().unwrap_or_default();
+        for event in events {
+            let deleted = event.new.is_none() && self.peek(&event.key, |_| ()).is_none();
+            let key = event.key.clone();
+            self.notify(event);
+            if deleted {
+                self.drop_key_listeners(&key);
+            }
         }
-        blackboard_data.as_mut().unwrap().set(key, value);
     }
 
-    Ok(())
-}
-
-#[no_mangle]
-pub extern "C" fn set_bool(key: *const c_char, value: bool) -> c_int {
-    match set_bool_intern(key, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to set bool: {}", e);
-            -1
+    /// Applies the operations in order, all of them or none if one of them fails. Subscribers
+    /// are notified once per key after the last operation.
+    fn transaction(
+        &mut self,
+        operations: Vec<BlackboardOperation>,
+    ) -> Vec<BlackboardOperationResult> {
+        let errors = self.check_operations(&operations);
+        let valid = errors.iter().all(Option::is_none);
+        if valid {
+            *self.pending.lock().unwrap() = Some(Vec::new());
+            for operation in &operations {
+                let result = match operation {
+                    BlackboardOperation::Set { key, value } => self.set_typed(key, value.clone()),
+                    BlackboardOperation::Delete { key } => self.delete(key),
+                };
+                // checked before, so only a bug gets here
+                if let Err(e) = result {
+                    error!("Transaction failed after checking it: {}", e);
+                }
+            }
+            self.end_batch();
         }
+        operation_results(&operations, errors, valid)
     }
-}
 
-fn get_double_intern(ckey: *const c_char, value: *mut f64) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+    // why each operation of a transaction would fail, given the ones in front of it
+    fn check_operations(&self, operations: &[BlackboardOperation]) -> Vec<Option<String>> {
+        // type of the keys written so far, None once deleted
+        let mut written: HashMap<&str, Option<&'static str>> = HashMap::new();
+        operations
+            .iter()
+            .map(|operation| {
+                let key = operation.key();
+                if let Err(e) = self.allowed(key, true) {
+                    return Some(e.message);
+                }
+                match operation {
+                    BlackboardOperation::Set { value, .. } => {
+                        let locked = match written.get(key) {
+                            Some(Some(value_type)) => {
+                                self.config.strict && *value_type != value.type_name()
+                            }
+                            Some(None) => false,
+                            None => self.type_locked(key, value.type_name()),
+                        };
+                        if locked {
+                            return Some(format!(
+                                "Type of key {} is locked, cannot store {}",
+                                key,
+                                value.type_name()
+                            ));
+                        }
+                        if let Err(e) = self.validate(key, value) {
+                            return Some(e.message);
+                        }
+                        written.insert(key, Some(value.type_name()));
+                    }
+                    BlackboardOperation::Delete { .. } => {
+                        let exists = match written.get(key) {
+                            Some(value_type) => value_type.is_some(),
+                            None => self.is_key_valid(key),
+                        };
+                        if !exists {
+                            return Some(format!("Key not found: {}", key));
+                        }
+                        written.insert(key, None);
+                    }
+                }
+                None
+            })
+            .collect()
     }
 
-    if value.is_null() {
-        return Err("Output value is null pointer".to_string());
+    fn is_key_valid(&self, key: &str) -> bool {
+        self.expire_key(key);
+        self.peek(key, |_| ()).is_some()
     }
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    // in strict mode a key keeps the type of its first write until it is deleted
+    fn type_locked(&self, key: &str, type_name: &str) -> bool {
+        self.config.strict
+            && self
+                .data
+                .get(key)
+                .is_some_and(|slot| slot.locked_type.is_some_and(|locked| locked != type_name))
+    }
 
-    {
-        let blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+    /// Checks the access rules of the component whose session the calling thread entered.
+    /// Keys in a namespace some component may write are only writable by components allowed
+    /// to, components without rules can not write them. Callers without a session only reach
+    /// the keys outside of every namespace of the rules.
+    fn allowed(&self, key: &str, write: bool) -> Result<(), RtError> {
+        if self.config.access.is_empty() {
+            return Ok(());
         }
-        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
-            return Err(format!("Key not found: {}", key));
+        let sessions = self.sessions.read().unwrap();
+        let component = sessions.get(&SESSION.with(Cell::get));
+        let rule = component.and_then(|component| self.config.access.get(component));
+        let permitted = match (component, rule, write) {
+            (None, _, _) => !self.declared(key, true),
+            (_, Some(AccessRule { read: Some(read), .. }), false) => in_namespace(key, read),
+            (_, _, false) => true,
+            (_, Some(AccessRule { write: Some(write), .. }), true) => in_namespace(key, write),
+            (_, _, true) => !self.declared(key, false),
+        };
+        if permitted {
+            return Ok(());
         }
+        Err(RtError::new(
+            RtStatus::AccessDenied,
+            format!(
+                "{} may not {} key {}",
+                component.map_or("Caller without session", |c| c.as_str()),
+                if write { "write" } else { "read" },
+                key
+            ),
+        ))
+    }
 
-        let v = blackboard_data.as_ref().unwrap().get::<f64>(key);
+    // whether `key` is in a namespace the rules let a component write, or read as well
+    fn declared(&self, key: &str, read: bool) -> bool {
+        self.config
+            .access
+            .values()
+            .flat_map(|rule| rule.write.iter().chain(rule.read.iter().filter(|_| read)))
+            .flatten()
+            .any(|namespace| {
+                namespace != "*" && in_namespace(key, std::slice::from_ref(namespace))
+            })
+    }
 
-        match v {
-            Ok(v) => {
-                unsafe {
-                    *value = *v;
-                }
-                return Ok(());
-            }
-            Err(e) => {
-                return Err(format!("Error: {}", e));
-            }
+    /// Like `set`, but refuses to change the type of a locked key and values violating the
+    /// constraint of the key. Returns whether the value was written, false for a locked type.
+    fn set_checked<T: 'static + Send + Sync>(&self, key: &str, value: T) -> Result<bool, RtError> {
+        if value_type_name(&value).is_some_and(|type_name| self.type_locked(key, type_name)) {
+            return Ok(false);
         }
+        self.validate_any(key, &value)?;
+        self.set(key, value)
     }
-}
 
-#[no_mangle]
-pub extern "C" fn get_double(key: *const c_char, value: *mut f64) -> c_int {
-    match get_double_intern(key, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to get double: {}", e);
-            -1
-        }
+    // why `value` violates the constraint of `key`, see `Constraint`
+    fn violation(&self, key: &str, value: &TypedBlackboardValue) -> Option<String> {
+        self.config.constraints.get(key).and_then(|c| c.check(value))
     }
-}
 
-fn set_double_intern(ckey: *const c_char, value: f64) -> Result<(), String> {
-    if ckey.is_null() {
-        return Err("Input key is null pointer".to_string());
+    // `violation` for the values of the typed capabilities
+    fn violation_any<T: 'static>(&self, key: &str, value: &T) -> Option<String> {
+        if !self.config.constraints.contains_key(key) {
+            return None;
+        }
+        TypedBlackboardValue::from_any(value).and_then(|value| self.violation(key, &value))
     }
 
-    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    // appends the violation to `VALIDATION_ERRORS_KEY`, no slot may be locked by the caller
+    fn reject(&self, key: &str, reason: String) -> RtError {
+        let _rejecting = self.rejecting.lock().unwrap();
+        let errors = self.peek(VALIDATION_ERRORS_KEY, |errors| {
+            errors.downcast_ref::<serde_json::Value>().cloned()
+        });
+        let mut errors = match errors.flatten() {
+            Some(serde_json::Value::Array(errors)) => errors,
+            _ => Vec::new(),
+        };
+        errors.push(serde_json::json!({
+            "key": key,
+            "error": reason,
+            "time": SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+        }));
+        let excess = errors.len().saturating_sub(MAX_VALIDATION_ERRORS);
+        errors.drain(..excess);
+        // the errors are not held by the writer of the rejected value
+        let tag = TAG.take();
+        let _ = self.set(VALIDATION_ERRORS_KEY, serde_json::Value::Array(errors));
+        TAG.set(tag);
+        RtError::new(
+            RtStatus::ConstraintViolation,
+            format!("Value of key {} rejected: {}", key, reason),
+        )
+    }
 
-    {
-        let mut blackboard_data = get_singleton().lock().unwrap();
-        if blackboard_data.is_none() {
-            return Err("Server is not running".to_string());
+    /// Fails with `RtStatus::ConstraintViolation` if `value` violates the constraint of `key`,
+    /// see `Constraint`. The violation is appended to `VALIDATION_ERRORS_KEY`.
+    fn validate(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), RtError> {
+        match self.violation(key, value) {
+            Some(reason) => Err(self.reject(key, reason)),
+            None => Ok(()),
         }
-        blackboard_data.as_mut().unwrap().set(key, value);
     }
 
+    // `validate` for the values of the typed capabilities
+    fn validate_any<T: 'static>(&self, key: &str, value: &T) -> Result<(), RtError> {
+        match self.violation_any(key, value) {
+            Some(reason) => Err(self.reject(key, reason)),
+            None => Ok(()),
+        }
+    }
+
+    fn set<T: 'static + Send + Sync>(&self, key: &str, value: T) -> Result<bool, RtError> {
+        self.set_at(key, value, None)
+    }
+
+    /// Like `set`, the event tells subscribers the JSON pointer `path` of the changed field.
+    fn set_at<T: 'static + Send + Sync>(
+        &self,
+        key: &str,
+        value: T,
+        path: Option<&str>,
+    ) -> Result<bool, RtError> {
+        let written = match self.data.get_mut(key) {
+            Some(mut slot) => self.write_slot(key, &mut slot, Box::new(value), path),
+            None => {
+                let mut slot = self.data.entry(key.to_string()).or_insert_with(Slot::new);
+                self.write_slot(key, &mut slot, Box::new(value), path)
+            }
+        };
+        if matches!(written, Ok(true)) {
+            self.written.notify();
+        }
+        written
+    }
+
+    // fails with `RtStatus::AccessDenied` if a writer of higher priority than the one of the
+    // calling thread wrote the key within the priority window
+    fn check_priority(
+        &self,
+        key: &str,
+        slot: &Slot,
+        tag: Option<&WriteTag>,
+    ) -> Result<(), RtError> {
+        let Some((held, at)) = &slot.tag else {
+            return Ok(());
+        };
+        let priority = tag.map_or(0, |tag| tag.priority);
+        if held.priority <= priority || at.elapsed() >= self.config.priority_window {
+            return Ok(());
+        }
+        Err(RtError::new(
+            RtStatus::AccessDenied,
+            format!(
+                "Key {} is held by {} with priority {}, a write of priority {} is rejected",
+                key, held.source, held.priority, priority
+            ),
+        ))
+    }
+
+    // writes the value while the slot is locked, so subscribers get the changes of a key in the
+    // order they were made. Returns false without writing if the type of the key is locked.
+    fn write_slot(
+        &self,
+        key: &str,
+        slot: &mut Slot,
+        value: Box<dyn Any + Send + Sync>,
+        path: Option<&str>,
+    ) -> Result<bool, RtError> {
+        let tag = TAG.take();
+        TAG.set(tag.clone());
+        self.check_priority(key, slot, tag.as_ref())?;
+        if self.config.strict {
+            if let Some(type_name) = value_type_name(value.as_ref()) {
+                match slot.locked_type {
+                    Some(locked) if locked != type_name => return Ok(false),
+                    Some(_) => {}
+                    None => slot.locked_type = Some(type_name),
+                }
+            }
+        }
+        slot.tag = tag.map(|tag| (tag, Instant::now()));
+        let old = self.event_value(key, slot);
+        let audited = (self.config.audit > 0)
+            .then(|| slot.value.as_deref().and_then(|v| TypedBlackboardValue::from_any(v)));
+        slot.value = Some(value);
+        #[cfg(feature = "shm")]
+        self.mirror(key, slot.value.as_deref().map(|v| v as &dyn Any));
+        if let Some(ttl) = slot.ttl {
+            slot.expires_at = Some(Instant::now() + ttl);
+        }
+        slot.stats.writes += 1;
+        // the revisions of the audit log are in the order of the records
+        let audit = audited.as_ref().map(|_| self.audit.lock().unwrap());
+        slot.stats.revision = self.written.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        slot.stats.last_write = SystemTime::now();
+        if let (Some(mut audit), Some(old)) = (audit, audited) {
+            let new = slot.value.as_deref().and_then(|v| TypedBlackboardValue::from_any(v));
+            if let Some(new) = new {
+                audit.push_back(AuditRecord {
+                    revision: slot.stats.revision,
+                    time: slot
+                        .stats
+                        .last_write
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_millis() as u64)
+                        .unwrap_or_default(),
+                    key: key.to_string(),
+                    old,
+                    new,
+                    component: self.session_component(),
+                });
+                let excess = audit.len().saturating_sub(self.config.audit);
+                audit.drain(..excess);
+            }
+        }
+        if let Some(&depth) = self.config.history.get(key) {
+            let value = slot.value.as_deref().and_then(|v| TypedBlackboardValue::from_any(v));
+            if let Some(value) = value {
+                slot.history.push_front((slot.stats.last_write, value));
+                slot.history.truncate(depth);
+            }
+        }
+        self.publish(BlackboardEvent {
+            key: key.to_string(),
+            reason: NotifyReason::Changed,
+            old,
+            new: self.event_value(key, slot),
+            path: path.map(str::to_string),
+        });
+        Ok(true)
+    }
+
+    // whether key holds a value written after the blackboard was at `revision`
+    fn written_since(&self, key: &str, revision: u64) -> bool {
+        self.is_key_valid(key)
+            && self
+                .data
+                .get(key)
+                .is_some_and(|slot| slot.stats.revision > revision)
+    }
+
+    /// Value of `key` from `index` writes ago, 0 being the latest write.
+    fn get_history(&self, key: &str, index: usize) -> Result<TypedBlackboardValue, RtError> {
+        if !self.config.history.contains_key(key) {
+            return Err(RtError::new(
+                RtStatus::InvalidArgument,
+                format!("No history configured for key: {}", key),
+            ));
+        }
+        let slot = self
+            .data
+            .get(key)
+            .filter(|slot| !slot.history.is_empty())
+            .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)))?;
+        slot.history.get(index).map(|(_, value)| value.clone()).ok_or_else(|| {
+            RtError::new(
+                RtStatus::InvalidArgument,
+                format!(
+                    "History index {} out of range for key {} ({} values)",
+                    index,
+                    key,
+                    slot.history.len()
+                ),
+            )
+        })
+    }
+
+    /// Values of `key` written after `since`, in milliseconds since the unix epoch, oldest first.
+    fn history_since(&self, key: &str, since: u64) -> Result<Vec<HistorySample>, RtError> {
+        if !self.config.history.contains_key(key) {
+            return Err(RtError::new(
+                RtStatus::InvalidArgument,
+                format!("No history configured for key: {}", key),
+            ));
+        }
+        let slot = self
+            .data
+            .get(key)
+            .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)))?;
+        Ok(slot
+            .history
+            .iter()
+            .rev()
+            .map(|(time, value)| HistorySample {
+                time: time
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or_default(),
+                value: value.clone(),
+            })
+            .filter(|sample| sample.time > since)
+            .collect())
+    }
+
+    // component of the session the calling thread entered
+    fn session_component(&self) -> Option<String> {
+        let sessions = self.sessions.read().unwrap();
+        sessions.get(&SESSION.with(Cell::get)).cloned()
+    }
+
+    /// Writes recorded by the audit log after `revision`, oldest first, of the keys the caller may
+    /// read.
+    fn audit_log(&self, revision: u64) -> Result<Vec<AuditRecord>, RtError> {
+        if self.config.audit == 0 {
+            return Err(RtError::new(
+                RtStatus::InvalidArgument,
+                "No audit log configured",
+            ));
+        }
+        let audit = self.audit.lock().unwrap();
+        Ok(audit
+            .iter()
+            .filter(|record| record.revision > revision)
+            .filter(|record| self.allowed(&record.key, false).is_ok())
+            .cloned()
+            .collect())
+    }
+
+    /// Writes `value` only if the current value of `key` equals `expected`. Returns whether the
+    /// value was written.
+    fn compare_and_set<T: 'static + Send + Sync + PartialEq>(
+        &self,
+        key: &str,
+        expected: &T,
+        value: T,
+    ) -> Result<bool, RtError> {
+        if !self.is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+        // the key stays locked from the comparison to the write
+        let mut slot = self
+            .data
+            .get_mut(key)
+            .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)))?;
+        slot.stats.reads.fetch_add(1, Ordering::Relaxed);
+        match slot.value.as_ref().and_then(|v| v.downcast_ref::<T>()) {
+            Some(current) if current == expected => {}
+            Some(_) => return Ok(false),
+            None => {
+                return Err(RtError::new(
+                    RtStatus::TypeMismatch,
+                    format!("Failed to downcast value for key: {}", key),
+                ))
+            }
+        }
+        if let Some(reason) = self.violation_any(key, &value) {
+            drop(slot);
+            return Err(self.reject(key, reason));
+        }
+        let written = self.write_slot(key, &mut slot, Box::new(value), None);
+        drop(slot);
+        self.written.notify();
+        written
+    }
+
+    /// Replaces the field at the JSON pointer `pointer` of the json document of `key`, see
+    /// `patch_json`. Concurrent patches of the same document do not lose each other's fields.
+    fn patch(&self, key: &str, pointer: &str, value: serde_json::Value) -> Result<(), RtError> {
+        if !self.is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+        let mut slot = self
+            .data
+            .get_mut(key)
+            .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)))?;
+        let mut document = slot
+            .value
+            .as_ref()
+            .and_then(|v| v.downcast_ref::<serde_json::Value>())
+            .ok_or_else(|| {
+                RtError::new(
+                    RtStatus::TypeMismatch,
+                    format!("Failed to downcast value for key: {}", key),
+                )
+            })?
+            .clone();
+        patch_json(&mut document, pointer, value)?;
+        if let Some(reason) = self.violation_any(key, &document) {
+            drop(slot);
+            return Err(self.reject(key, reason));
+        }
+        let written = self.write_slot(key, &mut slot, Box::new(document), Some(pointer));
+        drop(slot);
+        self.written.notify();
+        written.map(|_| ())
+    }
+
+    /// Every following write of `key` keeps the value alive for `ttl`. A zero ttl makes the
+    /// key persistent again.
+    fn set_ttl(&self, key: &str, ttl: Duration) -> Result<(), RtError> {
+        let slot = match self.is_key_valid(key) {
+            true => self.data.get_mut(key),
+            false => None,
+        };
+        let Some(mut slot) = slot else {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        };
+
+        if ttl.is_zero() {
+            slot.ttl = None;
+            slot.expires_at = None;
+        } else {
+            slot.ttl = Some(ttl);
+            slot.expires_at = Some(Instant::now() + ttl);
+        }
+        Ok(())
+    }
+
+    // removes the value of key if its ttl elapsed, returns true if it did
+    fn expire_key(&self, key: &str) -> bool {
+        // getters only take the write lock of a key once it expired
+        if !self
+            .data
+            .get(key)
+            .is_some_and(|slot| slot.value.is_some() && slot.expired())
+        {
+            return false;
+        }
+        let Some(mut slot) = self.data.get_mut(key) else {
+            return false;
+        };
+        if slot.value.is_none() || !slot.expired() {
+            return false;
+        }
+        let old = self.event_value(key, &slot);
+        slot.value = None;
+        slot.expires_at = None;
+        #[cfg(feature = "shm")]
+        self.mirror(key, None);
+        debug!("Key expired: {}", key);
+        self.publish(BlackboardEvent {
+            key: key.to_string(),
+            reason: NotifyReason::Expired,
+            old,
+            new: None,
+            path: None,
+        });
+        true
+    }
+
+    fn purge_expired(&self) {
+        let expired: Vec<String> = self
+            .data
+            .iter()
+            .filter(|slot| slot.value.is_some() && slot.expired())
+            .map(|slot| slot.key().clone())
+            .collect();
+        for key in expired {
+            self.expire_key(&key);
+        }
+    }
+
+    fn set_value(&self, key: &str, value: BlackboardValue) -> Result<(), String> {
+        let value = TypedBlackboardValue::try_from(value)
+            .map_err(|e| format!("Unsupported value for key {}: {}", key, e))?;
+        Ok(self.set_typed(key, value)?)
+    }
+
+    fn set_typed(&self, key: &str, value: TypedBlackboardValue) -> Result<(), RtError> {
+        let locked = || {
+            RtError::new(
+                RtStatus::TypeMismatch,
+                format!("Type of key {} is locked, cannot store {}", key, value.type_name()),
+            )
+        };
+        if self.type_locked(key, value.type_name()) {
+            return Err(locked());
+        }
+        self.validate(key, &value)?;
+        let error = locked();
+        let written = match value {
+            TypedBlackboardValue::String(v) => self.set(key, v),
+            TypedBlackboardValue::Int(v) => self.set(key, v),
+            TypedBlackboardValue::Int64(v) => self.set(key, v),
+            TypedBlackboardValue::Timestamp(v) => self.set(key, Timestamp(v)),
+            TypedBlackboardValue::Float(v) => self.set(key, v),
+            TypedBlackboardValue::Double(v) => self.set(key, v),
+            TypedBlackboardValue::Bool(v) => self.set(key, v),
+            TypedBlackboardValue::IntArray(v) => self.set(key, v),
+            TypedBlackboardValue::DoubleArray(v) => self.set(key, v),
+            TypedBlackboardValue::Json(v) => self.set(key, v),
+            TypedBlackboardValue::Bytes(v) => self.set(key, v),
+        };
+        // another thread locked the type in between
+        match written? {
+            true => Ok(()),
+            false => Err(error),
+        }
+    }
+
+    // all entries sorted by key, so snapshots are stable
+    fn snapshot(&self) -> Result<Vec<TypedBlackboardEntry>, String> {
+        let mut entries = self
+            .data
+            .iter()
+            .filter_map(|slot| {
+                let value = TypedBlackboardValue::from_any(slot.value.as_deref()?);
+                Some((slot.key().clone(), value))
+            })
+            .map(|(key, value)| {
+                value
+                    .map(|value| TypedBlackboardEntry { key: key.clone(), value })
+                    .ok_or(format!("Unsupported type for key: {}", key))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(entries)
+    }
+
+    /// Writes all entries to `path`, as json if the extension is `.json` and as yaml otherwise.
+    fn save(&self, path: &Path) -> Result<(), String> {
+        self.purge_expired();
+        let entries = self.snapshot()?;
+        let content = if is_json_path(path) {
+            serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?
+        } else {
+            serde_yml::to_string(&entries).map_err(|e| e.to_string())?
+        };
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write snapshot {}: {}", path.display(), e))?;
+        info!("Saved {} entries to {}", entries.len(), path.display());
+        Ok(())
+    }
+
+    /// Restores the entries of a snapshot written by `save`. Keys which are not part of the
+    /// snapshot are left untouched.
+    fn load(&mut self, path: &Path) -> Result<(), String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read snapshot {}: {}", path.display(), e))?;
+        let entries: Vec<TypedBlackboardEntry> = if is_json_path(path) {
+            serde_json::from_str(&content).map_err(|e| e.to_string())?
+        } else {
+            serde_yml::from_str(&content).map_err(|e| e.to_string())?
+        };
+        info!("Loading {} entries from {}", entries.len(), path.display());
+        for entry in entries {
+            self.set_typed(&entry.key, entry.value)?;
+        }
+        Ok(())
+    }
+
+    // calls `f` with the value of `key` without counting it as a read
+    fn peek<R>(&self, key: &str, f: impl FnOnce(&dyn Any) -> R) -> Option<R> {
+        let slot = self.data.get(key)?;
+        slot.value.as_deref().map(|value| f(value))
+    }
+
+    // the value stays read locked until the returned reference is dropped
+    fn get<T: 'static>(&self, key: &str) -> Result<MappedRef<'_, String, Slot, T>, RtError> {
+        let slot = self
+            .data
+            .get(key)
+            .filter(|slot| slot.value.is_some())
+            .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)))?;
+        slot.stats.reads.fetch_add(1, Ordering::Relaxed);
+        slot.try_map(|slot| slot.value.as_ref()?.downcast_ref::<T>())
+            .map_err(|_| {
+                RtError::new(
+                    RtStatus::TypeMismatch,
+                    format!("Failed to downcast value for key: {}", key),
+                )
+            })
+    }
+
+    /// Removes `key` together with its ttl. Subscribers are notified one last time and then
+    /// dropped, a new subscription is needed once the key is written again.
+    fn delete(&self, key: &str) -> Result<(), RtError> {
+        let slot = match self.is_key_valid(key) {
+            true => self.data.get_mut(key),
+            false => None,
+        };
+        let Some(mut slot) = slot else {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        };
+
+        let old = self.event_value(key, &slot);
+        *slot = Slot::new();
+        #[cfg(feature = "shm")]
+        self.mirror(key, None);
+        self.publish(BlackboardEvent {
+            key: key.to_string(),
+            reason: NotifyReason::Deleted,
+            old,
+            new: None,
+            path: None,
+        });
+        drop(slot);
+        // unless it was written again in the meantime
+        self.data.remove_if(key, |_, slot| slot.value.is_none());
+
+        // a batch drops them once it notified them
+        if self.pending.lock().unwrap().is_none() {
+            self.drop_key_listeners(key);
+        }
+        debug!("Deleted key: {}", key);
+        Ok(())
+    }
+
+    /// Lists all live keys with their type, sorted by key.
+    fn keys(&self) -> Result<Vec<BlackboardKeyInfo>, String> {
+        self.purge_expired();
+        let mut keys = self
+            .data
+            .iter()
+            .filter_map(|slot| Some((slot.key().clone(), value_type_name(slot.value.as_deref()?))))
+            .map(|(key, value_type)| {
+                value_type
+                    .map(|value_type| BlackboardKeyInfo {
+                        key: key.clone(),
+                        value_type: value_type.to_string(),
+                    })
+                    .ok_or_else(|| format!("Unsupported type for key: {}", key))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        keys.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(keys)
+    }
+
+    fn key_stats(&self, key: &str) -> Result<BlackboardKeyStats, RtError> {
+        let mut stats = {
+            let slot = self.data.get(key).ok_or_else(|| {
+                RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key))
+            })?;
+            BlackboardKeyStats {
+                key: key.to_string(),
+                writes: slot.stats.writes,
+                reads: slot.stats.reads.load(Ordering::Relaxed),
+                last_write: slot
+                    .stats
+                    .last_write
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or_default(),
+                subscribers: 0,
+            }
+        };
+        stats.subscribers = self.listeners.read().unwrap().listeners_for(key).len();
+        Ok(stats)
+    }
+
+    fn reset(&mut self) {
+        self.data.clear();
+        #[cfg(feature = "shm")]
+        if let Some(shared) = &self.shared {
+            shared.lock().unwrap().clear();
+        }
+    }
+}
+
+// same clock as clock_gettime(CLOCK_MONOTONIC) in C components
+fn monotonic_now() -> Timestamp {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now);
+    }
+    Timestamp(now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64)
+}
+
+fn is_json_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// Type name of a stored value, matching the tags of `TypedBlackboardValue`.
+fn value_type_name(value: &dyn Any) -> Option<&'static str> {
+    if value.is::<String>() {
+        Some("string")
+    } else if value.is::<i32>() {
+        Some("int")
+    } else if value.is::<i64>() {
+        Some("int64")
+    } else if value.is::<Timestamp>() {
+        Some("timestamp")
+    } else if value.is::<f32>() {
+        Some("float")
+    } else if value.is::<f64>() {
+        Some("double")
+    } else if value.is::<bool>() {
+        Some("bool")
+    } else if value.is::<Vec<i32>>() {
+        Some("int_array")
+    } else if value.is::<Vec<f64>>() {
+        Some("double_array")
+    } else if value.is::<serde_json::Value>() {
+        Some("json")
+    } else if value.is::<Vec<u8>>() {
+        Some("bytes")
+    } else {
+        None
+    }
+}
+
+// a value the shared memory region holds
+#[cfg(feature = "shm")]
+fn shared_value(value: &dyn Any) -> Option<TypedBlackboardValue> {
+    if let Some(value) = value.downcast_ref::<i32>() {
+        Some(TypedBlackboardValue::Int(*value))
+    } else if let Some(value) = value.downcast_ref::<i64>() {
+        Some(TypedBlackboardValue::Int64(*value))
+    } else if let Some(value) = value.downcast_ref::<Timestamp>() {
+        Some(TypedBlackboardValue::Timestamp(value.0))
+    } else if let Some(value) = value.downcast_ref::<f32>() {
+        Some(TypedBlackboardValue::Float(*value))
+    } else if let Some(value) = value.downcast_ref::<f64>() {
+        Some(TypedBlackboardValue::Double(*value))
+    } else {
+        value
+            .downcast_ref::<bool>()
+            .map(|value| TypedBlackboardValue::Bool(*value))
+    }
+}
+
+/// Derives a json schema describing `value`, recursing into objects and arrays.
+fn json_value_schema(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Null => serde_json::json!({"type": "null"}),
+        serde_json::Value::Bool(_) => serde_json::json!({"type": "boolean"}),
+        serde_json::Value::Number(n) => {
+            if n.is_f64() {
+                serde_json::json!({"type": "number"})
+            } else {
+                serde_json::json!({"type": "integer"})
+            }
+        }
+        serde_json::Value::String(_) => serde_json::json!({"type": "string"}),
+        serde_json::Value::Array(items) => {
+            let mut schemas: Vec<serde_json::Value> = Vec::new();
+            for item in items {
+                let schema = json_value_schema(item);
+                if !schemas.contains(&schema) {
+                    schemas.push(schema);
+                }
+            }
+            match schemas.len() {
+                0 => serde_json::json!({"type": "array"}),
+                1 => serde_json::json!({"type": "array", "items": schemas.pop().unwrap()}),
+                _ => serde_json::json!({"type": "array", "items": {"anyOf": schemas}}),
+            }
+        }
+        serde_json::Value::Object(map) => {
+            let properties: serde_json::Map<String, serde_json::Value> = map
+                .iter()
+                .map(|(k, v)| (k.clone(), json_value_schema(v)))
+                .collect();
+            serde_json::json!({"type": "object", "properties": properties})
+        }
+    }
+}
+
+thread_local! {
+    // session entered by the calling thread, 0 if none
+    static SESSION: Cell<c_int> = const { Cell::new(0) };
+    // writer of the tagged write the calling thread makes, see `set_double_tagged`
+    static TAG: Cell<Option<WriteTag>> = const { Cell::new(None) };
+}
+
+// calls on single keys share the read lock and only lock their key, see `Slot`. Starting,
+// stopping, reconfiguring and batches of writes take the write lock.
+static SINGLETON: OnceCell<RwLock<Option<BlackBoardData>>> = OnceCell::new();
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+// whether the singleton holds a blackboard, read by `health` without locking it
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+fn get_singleton() -> &'static RwLock<Option<BlackBoardData>> {
+    SINGLETON.get_or_init(|| {
+        trace!("Creating singleton");
+        RwLock::new(None)
+    })
+}
+
+fn start_server(
+    _caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut blackboard_data = get_singleton().write().unwrap();
+    if blackboard_data.is_some() {
+        return Err(RtError::new(RtStatus::AlreadyRunning, "Server is already running"));
+    }
+
+    let mut data = BlackBoardData::new();
+
+    if !attributes.is_null() {
+        let attributes = unsafe { CStr::from_ptr(attributes).to_str().unwrap() };
+        trace!("Attributes: {}", attributes);
+        serde_yml::from_str(attributes)
+            .map_err(|e| format!("Failed to parse attributes: {}", e))
+            .and_then(|entries: Vec<BlackboardEntry>| {
+                data.config = Config::new(&entries);
+                data.config.constraints = Constraint::from_attributes(attributes)?;
+                // the initial values are mirrored as well
+                data.open_shared_memory()?;
+                for entry in entries {
+                    if CONFIG_KEYS.contains(&entry.key.as_str()) {
+                        continue;
+                    }
+                    data.set_value(entry.key.as_str(), entry.value)?;
+                }
+                Ok(())
+            })?;
+    }
+
+    // a snapshot is newer than the initial attributes, so it wins
+    if let Some(path) = data.config.persist_path.clone() {
+        if path.exists() {
+            data.load(&path)?;
+        } else {
+            info!("No snapshot found at {}, starting empty", path.display());
+        }
+    }
+
+    *blackboard_data = Some(data);
+    RUNNING.store(true, Ordering::SeqCst);
+    info!("Blackboard is up and running");
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> c_int {
+    interfaces::logging::init_with_fallback(caps, "blackboard");
+    debug!("Starting server");
+    match LIFECYCLE.started(catch_panic(|| start_server(caps, attributes))) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to start server: {}", e);
+            e.record()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> c_int {
+    debug!("Stopping server");
+    let mut blackboard_data = get_singleton().write().unwrap();
+    if let Some(data) = blackboard_data.as_ref() {
+        if let Some(path) = data.config.persist_path.clone() {
+            data.save(&path)
+                .unwrap_or_else(|e| error!("Failed to persist blackboard: {}", e));
+        }
+    }
+    let data = blackboard_data.take();
+    RUNNING.store(false, Ordering::SeqCst);
+    if let Some(data) = data.as_ref() {
+        // waiters return with an error
+        data.written.revision.fetch_add(1, Ordering::SeqCst);
+        data.written.wake();
+        let _ = LIFECYCLE.change(PluginState::Stopped);
+    }
+    // callbacks still being delivered may need the lock
+    drop(blackboard_data);
+    drop(data);
+    info!("Blackboard is stopped");
+    0
+}
+
+// writes, reads and deletes a key of the running blackboard
+fn self_test_intern() -> Result<(), RtError> {
+    let key = c"rtime/self_test";
+    if !set_int_intern(key.as_ptr(), 42)? {
+        return Err(RtError::new(RtStatus::TypeMismatch, "Type of the test key is locked"));
+    }
+    let mut value = 0;
+    get_int_intern(key.as_ptr(), &mut value)?;
+    if value != 42 {
+        let message = format!("Read {} instead of the written 42", value);
+        return Err(RtError::new(RtStatus::ValueMismatch, message));
+    }
+    delete_intern(key.as_ptr())
+}
+
+/// Self test of `rtime run --self-test`, which starts a blackboard of its own for it.
+#[no_mangle]
+pub extern "C" fn self_test(
+    _caps: &interfaces::bindings::Capabilities,
+    _attributes: *const c_char,
+) -> c_int {
+    match catch_panic(self_test_intern) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Self test failed: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn reconfigure_server(attributes: *const c_char) -> Result<(), RtError> {
+    let attributes = if attributes.is_null() {
+        ""
+    } else {
+        unsafe { CStr::from_ptr(attributes) }
+            .to_str()
+            .map_err(|e| format!("Failed to read attributes: {}", e))?
+    };
+    let entries: Vec<BlackboardEntry> =
+        serde_yml::from_str::<Option<Vec<BlackboardEntry>>>(attributes)
+            .map_err(|e| format!("Failed to parse attributes: {}", e))?
+            .unwrap_or_default();
+    let constraints = Constraint::from_attributes(attributes)?;
+    let mut blackboard_data = get_singleton().write().unwrap();
+    let data = blackboard_data
+        .as_mut()
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "Server is not running"))?;
+    data.config = Config::new(&entries);
+    data.config.constraints = constraints;
+    Ok(())
+}
+
+/// Applies `strict`, `history`, `audit`, `access`, `persist_path` and the constraints of new
+/// attributes without a restart. The keys in the attributes are only written at start.
+#[no_mangle]
+pub extern "C" fn reconfigure(attributes: *const c_char) -> c_int {
+    match catch_panic(|| reconfigure_server(attributes)) {
+        Ok(()) => {
+            info!("Blackboard is reconfigured");
+            0
+        }
+        Err(e) => {
+            error!("Failed to reconfigure: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_OK` while the blackboard runs, `RT_NOT_RUNNING` otherwise. Polled by the supervisor of
+/// the loader.
+#[no_mangle]
+pub extern "C" fn health() -> c_int {
+    match RUNNING.load(Ordering::SeqCst) {
+        true => RtStatus::Ok.code(),
+        false => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the blackboard, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> c_int {
+    LIFECYCLE.code()
+}
+
+/// Writes the number of keys, subscribed keys and notifications coalesced on a full queue as
+/// json, like `get_last_error`.
+/// Read by the supervisor of the loader.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match get_singleton().read().unwrap().as_ref() {
+        Some(blackboard_data) => serde_json::json!({
+            "keys": blackboard_data.len(),
+            "subscribed_keys": blackboard_data.listeners.read().unwrap().key_to_listener.len(),
+            "coalesced_notifications": blackboard_data.dispatcher.coalesced(),
+        }),
+        None => serde_json::json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+#[rt_plugin(
+    name = "blackboard",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        blackboard_start = start: "i32(caps,cstr)",
+        blackboard_stop = stop: "i32()",
+        blackboard_health = health: "i32()",
+        blackboard_state = state: "i32()",
+        blackboard_health_status = health_status: "i32(*mut char,i32)",
+        blackboard_reconfigure = reconfigure: "i32(cstr)",
+        blackboard_reset = reset: "i32()",
+        blackboard_delete = delete: "i32(cstr)",
+        blackboard_save = save: "i32(cstr)",
+        blackboard_load = load: "i32(cstr)",
+        blackboard_export_msgpack = export_msgpack: "i32(*mut u8,i32)",
+        blackboard_import_msgpack = import_msgpack: "i32(*const u8,i32)",
+        blackboard_size = size: "i32()",
+        blackboard_keys = keys: "i32(*mut char)",
+        blackboard_stats = stats: "i32(cstr,*mut char)",
+        blackboard_get_string = get_string: "i32(cstr,*mut char)",
+        blackboard_get_string_v2 = get_string_v2: "i32(cstr,*mut char,i32,*mut i32)",
+        blackboard_set_string = set_string: "i32(cstr,cstr)",
+        blackboard_set_batch = set_batch: "i32(cstr)",
+        blackboard_transaction = transaction: "i32(cstr,*mut char,i32)",
+        blackboard_get_int = get_int: "i32(cstr,*mut i32)",
+        blackboard_set_int = set_int: "i32(cstr,i32)",
+        blackboard_get_int64 = get_int64: "i32(cstr,*mut i64)",
+        blackboard_set_int64 = set_int64: "i32(cstr,i64)",
+        blackboard_get_timestamp = get_timestamp: "i32(cstr,*mut u64)",
+        blackboard_set_timestamp = set_timestamp: "i32(cstr,u64)",
+        blackboard_stamp = stamp: "i32(cstr)",
+        blackboard_compare_and_set_int = compare_and_set_int: "i32(cstr,i32,i32)",
+        blackboard_compare_and_set_string = compare_and_set_string: "i32(cstr,cstr,cstr)",
+        blackboard_get_bool = get_bool: "i32(cstr,*mut bool)",
+        blackboard_set_bool = set_bool: "i32(cstr,bool)",
+        blackboard_get_float = get_float: "i32(cstr,*mut f32)",
+        blackboard_set_float = set_float: "i32(cstr,f32)",
+        blackboard_get_double = get_double: "i32(cstr,*mut f64)",
+        blackboard_set_double = set_double: "i32(cstr,f64)",
+        blackboard_set_double_tagged = set_double_tagged: "i32(cstr,f64,cstr,i32)",
+        blackboard_get_int_array = get_int_array: "i32(cstr,*mut i32)",
+        blackboard_set_int_array = set_int_array: "i32(cstr,*const i32,i32)",
+        blackboard_get_double_array = get_double_array: "i32(cstr,*mut f64)",
+        blackboard_set_double_array = set_double_array: "i32(cstr,*const f64,i32)",
+        blackboard_get_json = get_json: "i32(cstr,*mut char)",
+        blackboard_set_json = set_json: "i32(cstr,cstr)",
+        blackboard_get_json_path = get_json_path: "i32(cstr,cstr,*mut char)",
+        blackboard_set_json_path = set_json_path: "i32(cstr,cstr,cstr)",
+        blackboard_get_bytes = get_bytes: "i32(cstr,*mut u8,i32)",
+        blackboard_set_bytes = set_bytes: "i32(cstr,*const u8,i32)",
+        blackboard_get_history = get_history: "i32(cstr,i32,*mut char)",
+        blackboard_get_history_since = get_history_since: "i32(cstr,u64,*mut char,i32)",
+        blackboard_get_audit_log = get_audit_log: "i32(u64,*mut char,i32)",
+        blackboard_get_value = get_value: "i32(cstr,*mut char)",
+        blackboard_set_value = set_value: "i32(cstr,cstr)",
+        blackboard_set_value_tagged = set_value_tagged: "i32(cstr,cstr,cstr,i32)",
+        blackboard_set_ttl = set_ttl: "i32(cstr,i32)",
+        blackboard_as_json_schema = as_json_schema: "i32(*mut char)",
+        blackboard_subscribe = subscribe: "i32(cstr,cstr,*mut void,*mut void)",
+        blackboard_subscribe_v2 = subscribe_v2: "i32(cstr,cstr,*mut void,*mut void)",
+        blackboard_subscribe_with_options = subscribe_with_options: "i32(cstr,cstr,*mut void,*mut void,cstr)",
+        blackboard_wait = wait: "i32(cstr,i32)",
+        blackboard_flush = flush: "i32()",
+        blackboard_unsubscribe = unsubscribe: "i32(cstr,cstr)",
+        blackboard_revoke_subscriptions = revoke_subscriptions: "i32(*const void)",
+        blackboard_open_session = open_session: "i32(cstr)",
+        blackboard_close_session = close_session: "i32(i32)",
+        blackboard_enter_session = enter_session: "i32(i32)",
+    ),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+// the string a plugin passes, which may not be UTF-8
+unsafe fn utf8<'a>(cstr: *const c_char, what: &str) -> Result<&'a str, RtError> {
+    CStr::from_ptr(cstr)
+        .to_str()
+        .map_err(|e| RtError::new(RtStatus::InvalidArgument, format!("Invalid {}: {}", what, e)))
+}
+
+fn open_session_intern(ccomponent: *const c_char) -> Result<c_int, RtError> {
+    if ccomponent.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input component is null pointer"));
+    }
+
+    let component = unsafe { utf8(ccomponent, "component")? };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    let Some(data) = blackboard_data.as_ref() else {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    };
+    let mut sessions = data.sessions.write().unwrap();
+    let token = loop {
+        let token = session_token()?;
+        if !sessions.contains_key(&token) {
+            break token;
+        }
+    };
+    sessions.insert(token, component.to_string());
+    debug!("Opened a session for {}", component);
+    Ok(token)
+}
+
+// a random positive token, so no caller guesses the session of another component
+fn session_token() -> Result<c_int, RtError> {
+    let mut bytes = [0u8; 4];
+    let read = unsafe { libc::getrandom(bytes.as_mut_ptr() as *mut c_void, bytes.len(), 0) };
+    if read != bytes.len() as isize {
+        return Err(RtError::new(
+            RtStatus::Error,
+            format!("No random session token: {}", std::io::Error::last_os_error()),
+        ));
+    }
+    Ok((u32::from_ne_bytes(bytes) >> 1).max(1) as c_int)
+}
+
+/// Opens a session of the component `ccomponent` and returns its token, a positive number.
+/// Calls of a thread which entered the session are checked against the access rules of the
+/// component, see `enter_session`. The loader opens the sessions and enters them around the
+/// calls of the components, which do not get this capability.
+#[no_mangle]
+pub extern "C" fn open_session(ccomponent: *const c_char) -> c_int {
+    match catch_panic(|| open_session_intern(ccomponent)) {
+        Ok(token) => token,
+        Err(e) => {
+            error!("Failed to open session: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn close_session_intern(token: c_int) -> Result<(), RtError> {
+    let blackboard_data = get_singleton().read().unwrap();
+    let Some(data) = blackboard_data.as_ref() else {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    };
+    let removed = data.sessions.write().unwrap().remove(&token);
+    removed
+        .map(|_| ())
+        .ok_or_else(|| RtError::new(RtStatus::InvalidArgument, format!("Unknown session: {}", token)))
+}
+
+#[no_mangle]
+pub extern "C" fn close_session(token: c_int) -> c_int {
+    match catch_panic(|| close_session_intern(token)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to close session: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn enter_session_intern(token: c_int) -> Result<(), RtError> {
+    if token != 0 {
+        let blackboard_data = get_singleton().read().unwrap();
+        let Some(data) = blackboard_data.as_ref() else {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        };
+        if !data.sessions.read().unwrap().contains_key(&token) {
+            return Err(RtError::new(RtStatus::InvalidArgument, format!("Unknown session: {}", token)));
+        }
+    }
+    SESSION.with(|session| session.set(token));
     Ok(())
 }
 
-#[no_mangle]
-pub extern "C" fn set_double(key: *const c_char, value: f64) -> c_int {
-    match set_double_intern(key, value) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to set double: {}", e);
+/// The following calls of the calling thread are made on behalf of the session `token`, until
+/// it enters another one. 0 leaves the session.
+#[no_mangle]
+pub extern "C" fn enter_session(token: c_int) -> c_int {
+    match catch_panic(|| enter_session_intern(token)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to enter session: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn reset_intern() -> Result<(), RtError> {
+    let mut blackboard_data = get_singleton().write().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_mut().unwrap().reset();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn reset() -> c_int {
+    match catch_panic(reset_intern) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to reset server: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn delete_intern(ckey: *const c_char) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().allowed(key, true)?;
+    blackboard_data.as_ref().unwrap().delete(key)
+}
+
+#[no_mangle]
+pub extern "C" fn delete(ckey: *const c_char) -> c_int {
+    match catch_panic(|| delete_intern(ckey)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to delete key: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn save_intern(cpath: *const c_char) -> Result<(), RtError> {
+    if cpath.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input path is null pointer"));
+    }
+
+    let path = unsafe { CStr::from_ptr(cpath).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().save(Path::new(path)).map_err(RtError::from)
+}
+
+/// Writes a snapshot of all entries to `cpath`. Files ending with `.json` are written as json,
+/// everything else as yaml.
+#[no_mangle]
+pub extern "C" fn save(cpath: *const c_char) -> c_int {
+    match catch_panic(|| save_intern(cpath)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to save blackboard: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn load_intern(cpath: *const c_char) -> Result<(), RtError> {
+    if cpath.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input path is null pointer"));
+    }
+
+    let path = unsafe { CStr::from_ptr(cpath).to_str().unwrap() };
+
+    let mut blackboard_data = get_singleton().write().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_mut().unwrap().load(Path::new(path)).map_err(RtError::from)
+}
+
+/// Restores a snapshot written by `save`, overwriting (and notifying) the contained keys.
+#[no_mangle]
+pub extern "C" fn load(cpath: *const c_char) -> c_int {
+    match catch_panic(|| load_intern(cpath)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to load blackboard: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn export_msgpack_intern(buffer: *mut u8, capacity: c_int) -> Result<i32, RtError> {
+    let blackboard_data = get_singleton().read().unwrap();
+    let data = blackboard_data
+        .as_ref()
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "Server is not running"))?;
+    data.purge_expired();
+    // keys the caller may not read are left out
+    let entries: Vec<TypedBlackboardEntry> = data
+        .snapshot()?
+        .into_iter()
+        .filter(|entry| data.allowed(&entry.key, false).is_ok())
+        .collect();
+    let packed = rmp_serde::to_vec_named(&entries)
+        .map_err(|e| format!("Failed to encode entries: {}", e))?;
+    if !buffer.is_null() {
+        if capacity < 0 || (capacity as usize) < packed.len() {
+            return Err(RtError::new(
+                RtStatus::BufferTooSmall,
+                format!("Buffer too small for the export: {} < {}", capacity, packed.len()),
+            ));
+        }
+        unsafe { std::ptr::copy_nonoverlapping(packed.as_ptr(), buffer, packed.len()) };
+    }
+    Ok(packed.len() as i32)
+}
+
+/// Writes all entries as MessagePack, a list of `{key, value: {type, value}}` maps keeping the
+/// exact type of every value. Returns the size, a null `buffer` only asks for it.
+#[no_mangle]
+pub extern "C" fn export_msgpack(buffer: *mut u8, capacity: c_int) -> c_int {
+    match catch_panic(|| export_msgpack_intern(buffer, capacity)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to export blackboard: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn import_msgpack_intern(buffer: *const u8, len: c_int) -> Result<(), RtError> {
+    if buffer.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input buffer is null pointer"));
+    }
+    if len < 0 {
+        return Err(RtError::new(RtStatus::InvalidArgument, "Negative buffer length"));
+    }
+    let packed = unsafe { std::slice::from_raw_parts(buffer, len as usize) };
+    let entries: Vec<TypedBlackboardEntry> = rmp_serde::from_slice(packed).map_err(|e| {
+        RtError::new(RtStatus::InvalidArgument, format!("Failed to decode entries: {}", e))
+    })?;
+
+    let mut blackboard_data = get_singleton().write().unwrap();
+    let data = blackboard_data
+        .as_mut()
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "Server is not running"))?;
+    for entry in &entries {
+        data.allowed(&entry.key, true)?;
+    }
+    info!("Importing {} entries", entries.len());
+    for entry in entries {
+        data.set_typed(&entry.key, entry.value)?;
+    }
+    Ok(())
+}
+
+/// Writes the entries of an `export_msgpack`, overwriting (and notifying) the contained keys
+/// like `load`.
+#[no_mangle]
+pub extern "C" fn import_msgpack(buffer: *const u8, len: c_int) -> c_int {
+    match catch_panic(|| import_msgpack_intern(buffer, len)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to import blackboard: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn size_intern() -> Result<usize, RtError> {
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().purge_expired();
+
+    Ok(blackboard_data.as_ref().unwrap().len())
+}
+
+#[no_mangle]
+pub extern "C" fn size() -> c_int {
+    match catch_panic(size_intern) {
+        Ok(size) => size as c_int,
+        Err(e) => {
+            error!("Failed to get size: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn keys_intern(cvalue: *mut c_char) -> Result<i32, RtError> {
+    let keys = {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().keys()?
+    };
+
+    let keys_str = serde_json::to_string(&keys).map_err(|e| e.to_string())? + "\0";
+
+    if !cvalue.is_null() {
+        let tmp_value = keys_str.as_bytes();
+        unsafe {
+            std::ptr::copy_nonoverlapping(tmp_value.as_ptr(), cvalue as *mut u8, tmp_value.len());
+        }
+    }
+    Ok(keys_str.len() as i32)
+}
+
+/// Writes a json array of `{"key": ..., "type": ...}` objects for all keys into `cvalue` and
+/// returns the buffer size needed including the null terminator. Pass a null pointer to query
+/// the size first.
+#[no_mangle]
+pub extern "C" fn keys(cvalue: *mut c_char) -> c_int {
+    match catch_panic(|| keys_intern(cvalue)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to list keys: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stats_intern(ckey: *const c_char, cvalue: *mut c_char) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    let stats = {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().key_stats(key)?
+    };
+
+    let stats_str = serde_json::to_string(&stats).map_err(|e| e.to_string())? + "\0";
+
+    if !cvalue.is_null() {
+        let tmp_value = stats_str.as_bytes();
+        unsafe {
+            std::ptr::copy_nonoverlapping(tmp_value.as_ptr(), cvalue as *mut u8, tmp_value.len());
+        }
+    }
+    Ok(stats_str.len() as i32)
+}
+
+/// Writes the write and read counters, the last write time and the number of subscribers of
+/// `ckey` as json into `cvalue`. Returns the buffer size needed including the null terminator,
+/// pass a null pointer to query the size first.
+#[no_mangle]
+pub extern "C" fn stats(ckey: *const c_char, cvalue: *mut c_char) -> c_int {
+    match catch_panic(|| stats_intern(ckey, cvalue)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get stats: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_string_intern(ckey: *const c_char, cvalue: *const c_char) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cvalue.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let value = unsafe { CStr::from_ptr(cvalue).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value.to_string())? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_string(ckey: *const c_char, cvalue: *const c_char) -> c_int {
+    match catch_panic(|| set_string_intern(ckey, cvalue)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set string: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set string: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_string_intern(ckey: *const c_char, cvalue: *mut c_char) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<String>(key)?;
+        if !cvalue.is_null() {
+            let tmp_value = v.as_bytes();
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    tmp_value.as_ptr(),
+                    cvalue as *mut u8,
+                    tmp_value.len(),
+                );
+            }
+        }
+        Ok(v.len() as i32 + 1)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_string(ckey: *const c_char, cvalue: *mut c_char) -> c_int {
+    match catch_panic(|| get_string_intern(ckey, cvalue)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get string: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_string_v2_intern(
+    ckey: *const c_char,
+    cvalue: *mut c_char,
+    capacity: c_int,
+    crequired: *mut c_int,
+) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+    if capacity < 0 {
+        return Err(RtError::new(RtStatus::InvalidArgument, "Buffer length is negative"));
+    }
+
+    let key = unsafe { utf8(ckey, "key")? };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    let Some(data) = blackboard_data.as_ref() else {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    };
+    data.allowed(key, false)?;
+    if !data.is_key_valid(key) {
+        return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+    }
+    let value = data.get::<String>(key)?;
+
+    // size and copy are taken from the same read, so a concurrent write can not overflow
+    if !crequired.is_null() {
+        unsafe { *crequired = value.len() as c_int + 1 };
+    }
+    if cvalue.is_null() {
+        return Ok(0);
+    }
+    let written = unsafe { interfaces::status::copy_to_buffer(&value, cvalue, capacity) };
+    if written > capacity {
+        return Err(RtError::new(
+            RtStatus::BufferTooSmall,
+            format!("Buffer too small for key {}: {} < {}", key, capacity, written),
+        ));
+    }
+    Ok(0)
+}
+
+/// Copies the string stored under `ckey` into `cvalue`, never writing more than `capacity`
+/// bytes, and sets `crequired` to its size including the null terminator. A value that does
+/// not fit is truncated, still null terminated, and `RT_BUFFER_TOO_SMALL` is returned. Both
+/// `cvalue` and `crequired` may be null, e.g. to query the size only.
+#[no_mangle]
+pub extern "C" fn get_string_v2(
+    ckey: *const c_char,
+    cvalue: *mut c_char,
+    capacity: c_int,
+    crequired: *mut c_int,
+) -> c_int {
+    match catch_panic(|| get_string_v2_intern(ckey, cvalue, capacity, crequired)) {
+        Ok(status) => status,
+        // the caller retries with a larger buffer, nothing failed
+        Err(e) if e.status == RtStatus::BufferTooSmall => e.record(),
+        Err(e) => {
+            error!("Failed to get string: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_batch_intern(centries: *const c_char) -> Result<(), RtError> {
+    if centries.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input entries are null pointer"));
+    }
+
+    let entries = unsafe { CStr::from_ptr(centries).to_str().unwrap() };
+    let entries: Vec<BlackboardEntry> =
+        serde_yml::from_str(entries).map_err(|e| format!("Failed to parse entries: {}", e))?;
+
+    let mut blackboard_data = get_singleton().write().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    for entry in &entries {
+        blackboard_data.as_ref().unwrap().allowed(&entry.key, true)?;
+    }
+    blackboard_data.as_mut().unwrap().set_batch(entries).map_err(RtError::from)
+}
+
+/// Atomically writes a yaml or json list of `{key, value}` entries, the same format as the
+/// start attributes. Either all entries are written or none, and subscribers are notified once
+/// per key after the whole batch has been applied.
+#[no_mangle]
+pub extern "C" fn set_batch(centries: *const c_char) -> c_int {
+    match catch_panic(|| set_batch_intern(centries)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to set batch: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn operation_results(
+    operations: &[BlackboardOperation],
+    errors: Vec<Option<String>>,
+    applied: bool,
+) -> Vec<BlackboardOperationResult> {
+    operations
+        .iter()
+        .zip(errors)
+        .map(|(operation, error)| BlackboardOperationResult {
+            key: operation.key().to_string(),
+            applied,
+            error,
+        })
+        .collect()
+}
+
+fn transaction_intern(
+    coperations: *const c_char,
+    buffer: *mut c_char,
+    len: c_int,
+) -> Result<c_int, RtError> {
+    if coperations.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input operations are null pointer"));
+    }
+
+    let operations = unsafe { CStr::from_ptr(coperations).to_str().unwrap() };
+    let operations: Vec<BlackboardOperation> = serde_json::from_str(operations).map_err(|e| {
+        RtError::new(RtStatus::InvalidArgument, format!("Failed to parse operations: {}", e))
+    })?;
+
+    let mut blackboard_data = get_singleton().write().unwrap();
+    let Some(blackboard_data) = blackboard_data.as_mut() else {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    };
+    // results of applied operations are never longer than those of checked ones
+    let errors = blackboard_data.check_operations(&operations);
+    let checked = operation_results(&operations, errors, false);
+    let size = serde_json::to_string(&checked).map_err(|e| e.to_string())?.len() + 1;
+    if buffer.is_null() || (len as usize) < size {
+        return Ok(size as c_int);
+    }
+    let results = blackboard_data.transaction(operations);
+    let results = serde_json::to_string(&results).map_err(|e| e.to_string())?;
+    Ok(unsafe { interfaces::status::copy_to_buffer(&results, buffer, len) })
+}
+
+/// Applies a json array of `BlackboardOperation`s in order, all of them or none if one fails,
+/// and writes the `BlackboardOperationResult` of every operation into `buffer`. Subscribers are
+/// notified once per key after the last operation. Returns the buffer size needed including
+/// the null terminator; nothing is applied while `buffer` is null or shorter than that.
+#[no_mangle]
+pub extern "C" fn transaction(
+    coperations: *const c_char,
+    buffer: *mut c_char,
+    len: c_int,
+) -> c_int {
+    match catch_panic(|| transaction_intern(coperations, buffer, len)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to apply transaction: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_int_intern(ckey: *const c_char, value: *mut c_int) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if value.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Output value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<i32>(key)?;
+        unsafe {
+            *value = *v as c_int;
+        }
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_int(ckey: *const c_char, value: *mut c_int) -> c_int {
+    match catch_panic(|| get_int_intern(ckey, value)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to get int: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_int_intern(ckey: *const c_char, value: c_int) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_int(ckey: *const c_char, value: c_int) -> c_int {
+    match catch_panic(|| set_int_intern(ckey, value)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set int: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set int: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_int64_intern(ckey: *const c_char, value: *mut i64) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if value.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Output value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<i64>(key);
+
+        match v {
+            Ok(v) => {
+                unsafe {
+                    *value = *v;
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_int64(ckey: *const c_char, value: *mut i64) -> c_int {
+    match catch_panic(|| get_int64_intern(ckey, value)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to get int64: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_int64_intern(ckey: *const c_char, value: i64) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_int64(ckey: *const c_char, value: i64) -> c_int {
+    match catch_panic(|| set_int64_intern(ckey, value)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set int64: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set int64: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_timestamp_intern(ckey: *const c_char, value: *mut u64) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if value.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Output value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<Timestamp>(key);
+
+        match v {
+            Ok(v) => {
+                unsafe {
+                    *value = v.0;
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Reads a timestamp written by `set_timestamp` or `stamp`, in nanoseconds of the monotonic
+/// clock.
+#[no_mangle]
+pub extern "C" fn get_timestamp(ckey: *const c_char, value: *mut u64) -> c_int {
+    match catch_panic(|| get_timestamp_intern(ckey, value)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to get timestamp: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_timestamp_intern(ckey: *const c_char, value: u64) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, Timestamp(value))? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Stores `value` as a timestamp in nanoseconds of the monotonic clock (`CLOCK_MONOTONIC`).
+#[no_mangle]
+pub extern "C" fn set_timestamp(ckey: *const c_char, value: u64) -> c_int {
+    match catch_panic(|| set_timestamp_intern(ckey, value)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set timestamp: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set timestamp: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stamp_intern(ckey: *const c_char) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let now = monotonic_now();
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, now)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Sets `ckey` to the current time of the monotonic clock.
+#[no_mangle]
+pub extern "C" fn stamp(ckey: *const c_char) -> c_int {
+    match catch_panic(|| stamp_intern(ckey)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to stamp: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to stamp: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn compare_and_set_int_intern(ckey: *const c_char, expected: c_int, value: c_int) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().allowed(key, true)?;
+    blackboard_data
+        .as_ref()
+        .unwrap()
+        .compare_and_set(key, &expected, value)
+}
+
+/// Sets `ckey` to `value` if it currently holds `expected`. Returns `VALUE_MISMATCH` without
+/// writing if it doesn't.
+#[no_mangle]
+pub extern "C" fn compare_and_set_int(ckey: *const c_char, expected: c_int, value: c_int) -> c_int {
+    match catch_panic(|| compare_and_set_int_intern(ckey, expected, value)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            debug!("Compare and set int: value mismatch");
+            VALUE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to compare and set int: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn compare_and_set_string_intern(
+    ckey: *const c_char,
+    cexpected: *const c_char,
+    cvalue: *const c_char,
+) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cexpected.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input expected value is null pointer"));
+    }
+
+    if cvalue.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let expected = unsafe { CStr::from_ptr(cexpected).to_str().unwrap() };
+    let value = unsafe { CStr::from_ptr(cvalue).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().allowed(key, true)?;
+    blackboard_data
+        .as_ref()
+        .unwrap()
+        .compare_and_set(key, &expected.to_string(), value.to_string())
+}
+
+/// String variant of `compare_and_set_int`.
+#[no_mangle]
+pub extern "C" fn compare_and_set_string(
+    ckey: *const c_char,
+    cexpected: *const c_char,
+    cvalue: *const c_char,
+) -> c_int {
+    match catch_panic(|| compare_and_set_string_intern(ckey, cexpected, cvalue)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            debug!("Compare and set string: value mismatch");
+            VALUE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to compare and set string: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_float_intern(ckey: *const c_char, value: *mut f32) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if value.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Output value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<f32>(key)?;
+        unsafe {
+            *value = *v;
+        }
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_float(key: *const c_char, value: *mut f32) -> c_int {
+    match catch_panic(|| get_float_intern(key, value)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to get float: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_float_intern(ckey: *const c_char, value: f32) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_float(key: *const c_char, value: f32) -> c_int {
+    match catch_panic(|| set_float_intern(key, value)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set float: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set float: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_bool_intern(ckey: *const c_char, value: *mut bool) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if value.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Output value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<bool>(key)?;
+        unsafe {
+            *value = *v;
+        }
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_bool(key: *const c_char, value: *mut bool) -> c_int {
+    match catch_panic(|| get_bool_intern(key, value)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to get bool: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_bool_intern(ckey: *const c_char, value: bool) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_bool(key: *const c_char, value: bool) -> c_int {
+    match catch_panic(|| set_bool_intern(key, value)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set bool: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set bool: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_double_intern(ckey: *const c_char, value: *mut f64) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if value.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Output value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<f64>(key)?;
+        unsafe {
+            *value = *v;
+        }
+        Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_double(key: *const c_char, value: *mut f64) -> c_int {
+    match catch_panic(|| get_double_intern(key, value)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to get double: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_double_intern(ckey: *const c_char, value: f64) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_double(key: *const c_char, value: f64) -> c_int {
+    match catch_panic(|| set_double_intern(key, value)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set double: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set double: {}", e);
+            e.record()
+        }
+    }
+}
+
+// makes the writes of `write` on behalf of `source` with `priority`
+fn tagged<T>(
+    csource: *const c_char,
+    priority: c_int,
+    write: impl FnOnce() -> Result<T, RtError>,
+) -> Result<T, RtError> {
+    if csource.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input source is null pointer"));
+    }
+    let source = unsafe { CStr::from_ptr(csource) }.to_string_lossy().into_owned();
+    // the tag is dropped even if `write` panics
+    struct Untag;
+    impl Drop for Untag {
+        fn drop(&mut self) {
+            TAG.set(None);
+        }
+    }
+    let _untag = Untag;
+    TAG.set(Some(WriteTag { source, priority }));
+    write()
+}
+
+/// Like `set_double`, written by `source` with `priority`. For `priority_window_ms` after, 1000
+/// by default, writes of a lower priority to the key are rejected with `ACCESS_DENIED`, e.g. the
+/// planner writing a command the safety monitor just wrote with a higher priority. Untagged
+/// writes have priority 0 and end the hold of the key, writes of the same or a higher priority
+/// take it over.
+#[no_mangle]
+pub extern "C" fn set_double_tagged(
+    key: *const c_char,
+    value: f64,
+    source: *const c_char,
+    priority: c_int,
+) -> c_int {
+    match catch_panic(|| tagged(source, priority, || set_double_intern(key, value))) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set double: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            debug!("Failed to set tagged double: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_int_array_intern(ckey: *const c_char, cvalues: *mut c_int) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<Vec<i32>>(key);
+
+        match v {
+            Ok(v) => {
+                if !cvalues.is_null() {
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(v.as_ptr(), cvalues, v.len());
+                    }
+                }
+                Ok(v.len() as i32)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Returns the number of elements stored under `ckey`. If `cvalues` is not null, the elements
+/// are copied into it, so the caller has to query the length first and allocate accordingly.
+#[no_mangle]
+pub extern "C" fn get_int_array(ckey: *const c_char, cvalues: *mut c_int) -> c_int {
+    match catch_panic(|| get_int_array_intern(ckey, cvalues)) {
+        Ok(len) => len,
+        Err(e) => {
+            error!("Failed to get int array: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_int_array_intern(ckey: *const c_char, cvalues: *const c_int, len: c_int) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cvalues.is_null() && len > 0 {
+        return Err(RtError::new(RtStatus::NullArgument, "Input values are null pointer"));
+    }
+
+    if len < 0 {
+        return Err(RtError::new(RtStatus::InvalidArgument, format!("Invalid array length: {}", len)));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let values = if len == 0 {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(cvalues, len as usize).to_vec() }
+    };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, values)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_int_array(ckey: *const c_char, cvalues: *const c_int, len: c_int) -> c_int {
+    match catch_panic(|| set_int_array_intern(ckey, cvalues, len)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set int array: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set int array: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_double_array_intern(ckey: *const c_char, cvalues: *mut f64) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<Vec<f64>>(key);
+
+        match v {
+            Ok(v) => {
+                if !cvalues.is_null() {
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(v.as_ptr(), cvalues, v.len());
+                    }
+                }
+                Ok(v.len() as i32)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Same length-query plus fill semantics as `get_int_array`.
+#[no_mangle]
+pub extern "C" fn get_double_array(ckey: *const c_char, cvalues: *mut f64) -> c_int {
+    match catch_panic(|| get_double_array_intern(ckey, cvalues)) {
+        Ok(len) => len,
+        Err(e) => {
+            error!("Failed to get double array: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_double_array_intern(ckey: *const c_char, cvalues: *const f64, len: c_int) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cvalues.is_null() && len > 0 {
+        return Err(RtError::new(RtStatus::NullArgument, "Input values are null pointer"));
+    }
+
+    if len < 0 {
+        return Err(RtError::new(RtStatus::InvalidArgument, format!("Invalid array length: {}", len)));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let values = if len == 0 {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(cvalues, len as usize).to_vec() }
+    };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, values)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[no_mangle]
+pub extern "C" fn set_double_array(ckey: *const c_char, cvalues: *const f64, len: c_int) -> c_int {
+    match catch_panic(|| set_double_array_intern(ckey, cvalues, len)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set double array: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set double array: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_json_intern(ckey: *const c_char, cvalue: *const c_char) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cvalue.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let value = unsafe { CStr::from_ptr(cvalue).to_str().unwrap() };
+    let value: serde_json::Value = serde_json::from_str(value)
+        .map_err(|e| format!("Failed to parse json for key {}: {}", key, e))?;
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Stores the json document `cvalue` under `ckey`. The document is parsed once and kept
+/// structured, so invalid json is rejected.
+#[no_mangle]
+pub extern "C" fn set_json(ckey: *const c_char, cvalue: *const c_char) -> c_int {
+    match catch_panic(|| set_json_intern(ckey, cvalue)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set json: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set json: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_json_intern(ckey: *const c_char, cvalue: *mut c_char) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<serde_json::Value>(key);
+
+        match v {
+            Ok(v) => {
+                let json_str = v.to_string() + "\0";
+                if !cvalue.is_null() {
+                    let tmp_value = json_str.as_bytes();
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(
+                            tmp_value.as_ptr(),
+                            cvalue as *mut u8,
+                            tmp_value.len(),
+                        );
+                    }
+                }
+                Ok(json_str.len() as i32)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Serializes the json document stored under `ckey`, using the same size query plus fill
+/// convention as `get_string`. The returned size includes the null terminator.
+#[no_mangle]
+pub extern "C" fn get_json(ckey: *const c_char, cvalue: *mut c_char) -> c_int {
+    match catch_panic(|| get_json_intern(ckey, cvalue)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get json: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_json_path_intern(
+    ckey: *const c_char,
+    cpointer: *const c_char,
+    cvalue: *mut c_char,
+) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cpointer.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input pointer is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let pointer = unsafe { CStr::from_ptr(cpointer).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    let Some(data) = blackboard_data.as_ref() else {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    };
+    data.allowed(key, false)?;
+    if !data.is_key_valid(key) {
+        return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+    }
+    let document = data.get::<serde_json::Value>(key)?;
+    let value = document.pointer(pointer).ok_or_else(|| {
+        RtError::new(
+            RtStatus::KeyNotFound,
+            format!("Path {} not found in key {}", pointer, key),
+        )
+    })?;
+
+    let json_str = value.to_string() + "\0";
+    if !cvalue.is_null() {
+        let tmp_value = json_str.as_bytes();
+        unsafe {
+            std::ptr::copy_nonoverlapping(tmp_value.as_ptr(), cvalue as *mut u8, tmp_value.len());
+        }
+    }
+    Ok(json_str.len() as i32)
+}
+
+/// Serializes the field at the JSON pointer `cpointer`, e.g. `/pose/x`, of the json document
+/// stored under `ckey`, like `get_json`. A missing field is reported as `RT_KEY_NOT_FOUND`.
+#[no_mangle]
+pub extern "C" fn get_json_path(
+    ckey: *const c_char,
+    cpointer: *const c_char,
+    cvalue: *mut c_char,
+) -> c_int {
+    match catch_panic(|| get_json_path_intern(ckey, cpointer, cvalue)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get json path: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// Replaces the field at the JSON pointer `pointer` of `document` by `value`. The parent of the
+/// field has to exist: a member is added to an object, an array takes an index up to its length
+/// or `-` to append. The empty pointer replaces the whole document.
+fn patch_json(
+    document: &mut serde_json::Value,
+    pointer: &str,
+    value: serde_json::Value,
+) -> Result<(), RtError> {
+    if pointer.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let invalid = |reason: String| RtError::new(RtStatus::InvalidArgument, reason);
+    let (parent, token) = pointer
+        .rsplit_once('/')
+        .ok_or_else(|| invalid(format!("Invalid JSON pointer {}", pointer)))?;
+    let token = token.replace("~1", "/").replace("~0", "~");
+    match document.pointer_mut(parent) {
+        Some(serde_json::Value::Object(members)) => {
+            members.insert(token, value);
+            Ok(())
+        }
+        Some(serde_json::Value::Array(items)) => {
+            let index = match token.as_str() {
+                "-" => items.len(),
+                index => index
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| *index <= items.len())
+                    .ok_or_else(|| invalid(format!("Invalid array index in {}", pointer)))?,
+            };
+            if index == items.len() {
+                items.push(value);
+            } else {
+                items[index] = value;
+            }
+            Ok(())
+        }
+        Some(_) => Err(invalid(format!("{} is neither an object nor an array", parent))),
+        None => Err(RtError::new(RtStatus::KeyNotFound, format!("Path {} not found", parent))),
+    }
+}
+
+fn set_json_path_intern(
+    ckey: *const c_char,
+    cpointer: *const c_char,
+    cvalue: *const c_char,
+) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cpointer.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input pointer is null pointer"));
+    }
+
+    if cvalue.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let pointer = unsafe { CStr::from_ptr(cpointer).to_str().unwrap() };
+    let value = unsafe { CStr::from_ptr(cvalue).to_str().unwrap() };
+    let value: serde_json::Value = serde_json::from_str(value)
+        .map_err(|e| format!("Failed to parse json for key {}: {}", key, e))?;
+
+    let blackboard_data = get_singleton().read().unwrap();
+    let Some(data) = blackboard_data.as_ref() else {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    };
+    data.allowed(key, true)?;
+    data.patch(key, pointer, value)
+}
+
+/// Writes the json document `cvalue` to the field at the JSON pointer `cpointer` of the json
+/// document stored under `ckey`, see `patch_json`. Subscribers get the pointer as `path` of the
+/// event.
+#[no_mangle]
+pub extern "C" fn set_json_path(
+    ckey: *const c_char,
+    cpointer: *const c_char,
+    cvalue: *const c_char,
+) -> c_int {
+    match catch_panic(|| set_json_path_intern(ckey, cpointer, cvalue)) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Failed to set json path: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_bytes_intern(ckey: *const c_char, cvalue: *const u8, len: c_int) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cvalue.is_null() && len > 0 {
+        return Err(RtError::new(RtStatus::NullArgument, "Input value is null pointer"));
+    }
+
+    if len < 0 {
+        return Err(RtError::new(RtStatus::InvalidArgument, format!("Invalid length: {}", len)));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let value = if len == 0 {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(cvalue, len as usize).to_vec() }
+    };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, true)?;
+        if !blackboard_data.as_ref().unwrap().set_checked(key, value)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Stores a copy of the `len` bytes at `cvalue` under `ckey`.
+#[no_mangle]
+pub extern "C" fn set_bytes(ckey: *const c_char, cvalue: *const u8, len: c_int) -> c_int {
+    match catch_panic(|| set_bytes_intern(ckey, cvalue, len)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            error!("Failed to set bytes: type of key is locked");
+            TYPE_MISMATCH
+        }
+        Err(e) => {
+            error!("Failed to set bytes: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_bytes_intern(ckey: *const c_char, cvalue: *mut u8, capacity: c_int) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+
+        let v = blackboard_data.as_ref().unwrap().get::<Vec<u8>>(key);
+
+        match v {
+            Ok(v) => {
+                if !cvalue.is_null() {
+                    if (capacity as usize) < v.len() || capacity < 0 {
+                        return Err(RtError::new(RtStatus::BufferTooSmall, format!(
+                            "Buffer too small for key {}: {} < {}",
+                            key,
+                            capacity,
+                            v.len()
+                        )));
+                    }
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(v.as_ptr(), cvalue, v.len());
+                    }
+                }
+                Ok(v.len() as i32)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Returns the number of bytes stored under `ckey`. If `cvalue` is not null the bytes are
+/// copied into it, failing without writing anything if `capacity` is too small.
+#[no_mangle]
+pub extern "C" fn get_bytes(ckey: *const c_char, cvalue: *mut u8, capacity: c_int) -> c_int {
+    match catch_panic(|| get_bytes_intern(ckey, cvalue, capacity)) {
+        Ok(len) => len,
+        Err(e) => {
+            error!("Failed to get bytes: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_history_intern(ckey: *const c_char, index: c_int, cvalue: *mut c_char) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if index < 0 {
+        return Err(RtError::new(RtStatus::InvalidArgument, format!("Invalid history index: {}", index)));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    let value_str = {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        let value = blackboard_data
+            .as_ref()
+            .unwrap()
+            .get_history(key, index as usize)?;
+        serde_json::to_string(&value).map_err(|e| e.to_string())? + "\0"
+    };
+
+    if !cvalue.is_null() {
+        let tmp_value = value_str.as_bytes();
+        unsafe {
+            std::ptr::copy_nonoverlapping(tmp_value.as_ptr(), cvalue as *mut u8, tmp_value.len());
+        }
+    }
+    Ok(value_str.len() as i32)
+}
+
+/// Writes the value of `ckey` from `index` writes ago as json `{"type": ..., "value": ...}`
+/// into `cvalue`, 0 being the latest write. Only keys listed in the `history` start attribute
+/// keep older values. Returns the buffer size needed including the null terminator, pass a null
+/// pointer to query the size first.
+#[no_mangle]
+pub extern "C" fn get_history(ckey: *const c_char, index: c_int, cvalue: *mut c_char) -> c_int {
+    match catch_panic(|| get_history_intern(ckey, index, cvalue)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get history: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_history_since_intern(
+    ckey: *const c_char,
+    since: u64,
+    buffer: *mut c_char,
+    len: c_int,
+) -> Result<c_int, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    let samples = {
+        let blackboard_data = get_singleton().read().unwrap();
+        let Some(blackboard_data) = blackboard_data.as_ref() else {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        };
+        blackboard_data.allowed(key, false)?;
+        blackboard_data.history_since(key, since)?
+    };
+    let samples = serde_json::to_string(&samples).map_err(|e| e.to_string())?;
+    Ok(unsafe { interfaces::status::copy_to_buffer(&samples, buffer, len) })
+}
+
+/// Writes the values of `ckey` written after `since`, in milliseconds since the unix epoch, as
+/// a json array of `HistorySample`s into `buffer`, oldest first. Only keys listed in the
+/// `history` start attribute keep older values. Returns the buffer size needed including the
+/// null terminator, the samples are cut off if `buffer` is shorter.
+#[no_mangle]
+pub extern "C" fn get_history_since(
+    ckey: *const c_char,
+    since: u64,
+    buffer: *mut c_char,
+    len: c_int,
+) -> c_int {
+    match catch_panic(|| get_history_since_intern(ckey, since, buffer, len)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get history: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_audit_log_intern(revision: u64, buffer: *mut c_char, len: c_int) -> Result<c_int, RtError> {
+    let records = {
+        let blackboard_data = get_singleton().read().unwrap();
+        let Some(blackboard_data) = blackboard_data.as_ref() else {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        };
+        blackboard_data.audit_log(revision)?
+    };
+    let records = serde_json::to_string(&records).map_err(|e| e.to_string())?;
+    Ok(unsafe { interfaces::status::copy_to_buffer(&records, buffer, len) })
+}
+
+/// Writes the writes recorded after `revision` as a json array of `AuditRecord`s into `buffer`,
+/// oldest first, 0 returns all of them. Only the latest `audit` writes are kept, the start
+/// attribute enables the log. Returns the buffer size needed including the null terminator, the
+/// records are cut off if `buffer` is shorter.
+#[no_mangle]
+pub extern "C" fn get_audit_log(revision: u64, buffer: *mut c_char, len: c_int) -> c_int {
+    match catch_panic(|| get_audit_log_intern(revision, buffer, len)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get audit log: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn get_value_intern(ckey: *const c_char, cvalue: *mut c_char) -> Result<i32, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    let value_str = {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        blackboard_data.as_ref().unwrap().allowed(key, false)?;
+        if !blackboard_data.as_ref().unwrap().is_key_valid(key) {
+            return Err(RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key)));
+        }
+        let value = blackboard_data
+            .as_ref()
+            .unwrap()
+            .peek(key, TypedBlackboardValue::from_any)
+            .flatten()
+            .ok_or_else(|| {
+                RtError::new(RtStatus::TypeMismatch, format!("Unsupported type of key: {}", key))
+            })?;
+        serde_json::to_string(&value).map_err(|e| e.to_string())? + "\0"
+    };
+
+    if !cvalue.is_null() {
+        let tmp_value = value_str.as_bytes();
+        unsafe {
+            std::ptr::copy_nonoverlapping(tmp_value.as_ptr(), cvalue as *mut u8, tmp_value.len());
+        }
+    }
+    Ok(value_str.len() as i32)
+}
+
+fn set_value_intern(ckey: *const c_char, cvalue: *const c_char) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if cvalue.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input value is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let value = unsafe { CStr::from_ptr(cvalue).to_str().unwrap() };
+    let value: TypedBlackboardValue = serde_json::from_str(value).map_err(|e| {
+        RtError::new(
+            RtStatus::InvalidArgument,
+            format!("Invalid value for key {}: {}", key, e),
+        )
+    })?;
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().allowed(key, true)?;
+    blackboard_data.as_ref().unwrap().set_typed(key, value)
+}
+
+/// Stores the json `{"type": ..., "value": ...}` written by `get_value` under `ckey`, keeping
+/// the given type.
+#[no_mangle]
+pub extern "C" fn set_value(ckey: *const c_char, cvalue: *const c_char) -> c_int {
+    match catch_panic(|| set_value_intern(ckey, cvalue)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to set value: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// Like `set_value`, written by `source` with `priority`, see `set_double_tagged`.
+#[no_mangle]
+pub extern "C" fn set_value_tagged(
+    ckey: *const c_char,
+    cvalue: *const c_char,
+    source: *const c_char,
+    priority: c_int,
+) -> c_int {
+    match catch_panic(|| tagged(source, priority, || set_value_intern(ckey, cvalue))) {
+        Ok(_) => 0,
+        Err(e) => {
+            debug!("Failed to set tagged value: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// Writes the value of `ckey` of any type as json `{"type": ..., "value": ...}` into `cvalue`.
+/// Returns the buffer size needed including the null terminator, pass a null pointer to query
+/// the size first.
+#[no_mangle]
+pub extern "C" fn get_value(ckey: *const c_char, cvalue: *mut c_char) -> c_int {
+    match catch_panic(|| get_value_intern(ckey, cvalue)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get value: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn set_ttl_intern(ckey: *const c_char, millis: c_int) -> Result<(), RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    if millis < 0 {
+        return Err(RtError::new(RtStatus::InvalidArgument, format!("Invalid ttl: {} ms", millis)));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().allowed(key, true)?;
+    blackboard_data
+        .as_ref()
+        .unwrap()
+        .set_ttl(key, Duration::from_millis(millis as u64))
+}
+
+/// Lets the existing key `ckey` expire `millis` milliseconds after its latest write. Expired
+/// keys are removed on the next access and their subscribers are notified. Passing 0 removes
+/// the ttl again.
+#[no_mangle]
+pub extern "C" fn set_ttl(ckey: *const c_char, millis: c_int) -> c_int {
+    match catch_panic(|| set_ttl_intern(ckey, millis)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to set ttl: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn as_json_schema_intern(cvalue: *mut c_char) -> Result<i32, RtError> {
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+    blackboard_data.as_ref().unwrap().purge_expired();
+
+    let mut schema = serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "type": "object",
+        "properties": {}
+    });
+
+    for slot in blackboard_data.as_ref().unwrap().data.iter() {
+        let (key, Some(value)) = (slot.key(), slot.value.as_deref()) else {
+            continue;
+        };
+        let mut property = serde_json::json!({});
+        if let Some(v) = value.downcast_ref::<String>() {
+            property["type"] = "string".into();
+            property["value"] = v.clone().into();
+        } else if let Some(v) = value.downcast_ref::<i32>() {
+            property["type"] = "integer".into();
+            property["value"] = v.clone().into();
+        } else if let Some(v) = value.downcast_ref::<i64>() {
+            property["type"] = "integer".into();
+            property["value"] = (*v).into();
+        } else if let Some(v) = value.downcast_ref::<Timestamp>() {
+            property["type"] = "integer".into();
+            property["format"] = "monotonic-ns".into();
+            property["value"] = v.0.into();
+        } else if let Some(v) = value.downcast_ref::<f32>() {
+            property["type"] = "number".into();
+            property["value"] = v.clone().into();
+        } else if let Some(v) = value.downcast_ref::<f64>() {
+            property["type"] = "number".into();
+            property["value"] = v.clone().into();
+        } else if let Some(v) = value.downcast_ref::<bool>() {
+            property["type"] = "boolean".into();
+            property["value"] = v.clone().into();
+        } else if let Some(v) = value.downcast_ref::<Vec<i32>>() {
+            property["type"] = "array".into();
+            property["items"] = serde_json::json!({"type": "integer"});
+            property["value"] = v.clone().into();
+        } else if let Some(v) = value.downcast_ref::<Vec<f64>>() {
+            property["type"] = "array".into();
+            property["items"] = serde_json::json!({"type": "number"});
+            property["value"] = v.clone().into();
+        } else if let Some(v) = value.downcast_ref::<serde_json::Value>() {
+            property = json_value_schema(v);
+            property["value"] = v.clone();
+        } else if let Some(v) = value.downcast_ref::<Vec<u8>>() {
+            property["type"] = "string".into();
+            property["contentEncoding"] = "base64".into();
+            property["value"] = STANDARD.encode(v).into();
+        } else {
+            return Err(format!("Unsupported type for key: {}", key).into());
+        }
+        if let Some(constraint) = blackboard_data.as_ref().unwrap().config.constraints.get(key) {
+            constraint.annotate(&mut property);
+        }
+        schema["properties"][key] = property;
+    }
+
+    let schema_str = schema.to_string() + "\0";
+
+    if !cvalue.is_null() {
+        let tmp_value = schema_str.as_bytes();
+        unsafe {
+            std::ptr::copy_nonoverlapping(tmp_value.as_ptr(), cvalue as *mut u8, tmp_value.len());
+        }
+    }
+    return Ok(schema_str.len() as i32);
+}
+
+#[no_mangle]
+pub extern "C" fn as_json_schema(value: *mut c_char) -> c_int {
+    match catch_panic(|| as_json_schema_intern(value)) {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to get json schema: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn wait_intern(ckey: *const c_char, timeout_ms: c_int) -> Result<bool, RtError> {
+    if ckey.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input key is null pointer"));
+    }
+
+    let key = unsafe { CStr::from_ptr(ckey).to_str().unwrap() };
+    let deadline =
+        (timeout_ms >= 0).then(|| Instant::now() + Duration::from_millis(timeout_ms as u64));
+
+    let (written, revision) = match get_singleton().read().unwrap().as_ref() {
+        Some(data) => (data.written.clone(), data.written.revision.load(Ordering::SeqCst)),
+        None => return Err(RtError::new(RtStatus::NotRunning, "Server is not running")),
+    };
+    written.waiters.fetch_add(1, Ordering::SeqCst);
+    let _waiting = Waiting(&written);
+
+    loop {
+        // a write after this is noticed below, even if it is not written_since yet
+        let seen = written.revision.load(Ordering::SeqCst);
+        match get_singleton().read().unwrap().as_ref() {
+            Some(data) if Arc::ptr_eq(&data.written, &written) => {
+                if data.written_since(key, revision) {
+                    return Ok(true);
+                }
+            }
+            Some(_) => {
+                return Err(RtError::new(
+                    RtStatus::NotRunning,
+                    "Blackboard was restarted while waiting",
+                ))
+            }
+            None => return Err(RtError::new(RtStatus::NotRunning, "Server is not running")),
+        }
+
+        let lock = written.lock.lock().unwrap();
+        if written.revision.load(Ordering::SeqCst) != seen {
+            continue;
+        }
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(false);
+                }
+                let _ = written.condvar.wait_timeout(lock, deadline - now).unwrap();
+            }
+            None => drop(written.condvar.wait(lock).unwrap()),
+        }
+    }
+}
+
+/// Blocks until `ckey` is written after this call, which includes its creation. Returns
+/// `TIMEOUT` if that does not happen within `timeout_ms` milliseconds, a negative timeout waits
+/// forever. Must not be called from a subscriber callback for a key written by the caller.
+#[no_mangle]
+pub extern "C" fn wait(ckey: *const c_char, timeout_ms: c_int) -> c_int {
+    match catch_panic(|| wait_intern(ckey, timeout_ms)) {
+        Ok(true) => 0,
+        Ok(false) => {
+            debug!("Wait: timeout");
+            TIMEOUT
+        }
+        Err(e) => {
+            error!("Failed to wait: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn flush_intern() -> Result<(), RtError> {
+    let sender = {
+        let blackboard_data = get_singleton().read().unwrap();
+        if blackboard_data.is_none() {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        }
+        let dispatcher = &blackboard_data.as_ref().unwrap().dispatcher;
+        if dispatcher.is_dispatcher_thread() {
+            return Err("Cannot flush from within a subscriber callback".to_string().into());
+        }
+        dispatcher.sender.clone().unwrap()
+    };
+
+    let (done_sender, done) = mpsc::channel();
+    sender
+        .send(Dispatch::Flush(done_sender))
+        .map_err(|_| RtError::new(RtStatus::NotRunning, "Notification dispatcher is not running"))?;
+    done.recv().map_err(|_| {
+        RtError::new(RtStatus::NotRunning, "Notification dispatcher stopped while flushing")
+    })
+}
+
+/// Blocks until all notifications queued so far are delivered. Subscribers are called from a
+/// dispatcher thread, so flush after `unsubscribe` before releasing the user data. Changes
+/// held back by a rate limit are not waited for, `unsubscribe` discards them.
+#[no_mangle]
+pub extern "C" fn flush() -> c_int {
+    match catch_panic(flush_intern) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to flush notifications: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn subscribe_intern(
+    key: *const c_char,
+    component: *const c_char,
+    callback: *mut c_void,
+    user_data: *mut c_void,
+    with_event: bool,
+    limit: Option<RateLimit>,
+) -> Result<(), RtError> {
+    let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
+    let component = unsafe { CStr::from_ptr(component).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+
+    blackboard_data
+        .as_ref()
+        .unwrap()
+        .subscribe(key, component, callback, user_data, with_event, limit)
+}
+
+/// Calls `callback` whenever `key` changes. A key ending with `*` subscribes to every key
+/// starting with the part in front of it, e.g. `robot/pose/*`. The callback returns 0 to stay
+/// subscribed, 1 to unsubscribe and a negative code on failure; after 10 failures in a row it
+/// is unsubscribed as well.
+#[no_mangle]
+pub extern "C" fn subscribe(
+    key: *const c_char,
+    component: *const c_char,
+    callback: *mut c_void,
+    user_data: *mut c_void,
+) -> c_int {
+    match catch_panic(|| subscribe_intern(key, component, callback, user_data, false, None)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to subscribe: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// Like `subscribe`, but `callback` has the signature
+/// `int callback(const char* key, const char* event, void* user_data)` where `event` is a json
+/// serialized `BlackboardEvent` holding the reason plus the old and new value of the key. Both
+/// values are captured while the change is applied, so they are consistent even if the key is
+/// written again before the callback reads them.
+#[no_mangle]
+pub extern "C" fn subscribe_v2(
+    key: *const c_char,
+    component: *const c_char,
+    callback: *mut c_void,
+    user_data: *mut c_void,
+) -> c_int {
+    match catch_panic(|| subscribe_intern(key, component, callback, user_data, true, None)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to subscribe: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn subscribe_with_options_intern(
+    key: *const c_char,
+    component: *const c_char,
+    callback: *mut c_void,
+    user_data: *mut c_void,
+    coptions: *const c_char,
+) -> Result<(), RtError> {
+    if coptions.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input options are null pointer"));
+    }
+
+    let options = unsafe { CStr::from_ptr(coptions).to_str().unwrap() };
+    let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+    let options: SubscribeOptions = serde_json::from_str(options)
+        .map_err(|e| invalid(format!("Invalid subscribe options: {}", e)))?;
+    let limit = match options.max_rate {
+        Some(rate) if rate.is_finite() && rate > 0.0 => Some(RateLimit {
+            interval: Duration::from_secs_f64(1.0 / rate),
+            coalesce: options.coalesce,
+        }),
+        Some(rate) => return Err(invalid(format!("Invalid max_rate: {}", rate))),
+        None if options.coalesce => return Err(invalid("coalesce needs a max_rate".to_string())),
+        None => None,
+    };
+    subscribe_intern(key, component, callback, user_data, options.event, limit)
+}
+
+/// Like `subscribe`, configured by the json `SubscribeOptions` in `coptions`. With `max_rate`
+/// the subscriber is notified of every key at most that often per second. Changes in between
+/// are dropped, with `coalesce` the latest of them is delivered once the interval elapsed. With
+/// `event` the callback takes the event like with `subscribe_v2`.
+#[no_mangle]
+pub extern "C" fn subscribe_with_options(
+    key: *const c_char,
+    component: *const c_char,
+    callback: *mut c_void,
+    user_data: *mut c_void,
+    coptions: *const c_char,
+) -> c_int {
+    let subscribe = || subscribe_with_options_intern(key, component, callback, user_data, coptions);
+    match catch_panic(subscribe) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to subscribe: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn unsubscribe_intern(key: *const c_char, component: *const c_char) -> Result<(), RtError> {
+    let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
+    let component = unsafe { CStr::from_ptr(component).to_str().unwrap() };
+
+    let blackboard_data = get_singleton().read().unwrap();
+    if blackboard_data.is_none() {
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+    }
+
+    blackboard_data.as_ref().unwrap().unsubscribe(key, component);
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn unsubscribe(key: *const c_char, component: *const c_char) -> c_int {
+    match catch_panic(|| unsubscribe_intern(key, component)) {
+        Ok(_) => 0,
+        Err(e) => {
+            error!("Failed to unsubscribe: {}", e);
+            e.record()
+        }
+    }
+}
+
+// the loaded library `address` is in, the base identifies it as long as it stays loaded
+fn library_of(address: *const c_void) -> Option<libc::Dl_info> {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    match unsafe { libc::dladdr(address, &mut info) } {
+        0 => None,
+        _ => Some(info),
+    }
+}
+
+fn revoke_subscriptions_intern(address: *const c_void) -> Result<c_int, RtError> {
+    if address.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Input address is null pointer"));
+    }
+    let Some(library) = library_of(address) else {
+        return Err(RtError::new(
+            RtStatus::InvalidArgument,
+            format!("Address {:?} is in no loaded library", address),
+        ));
+    };
+    let name = if library.dli_fname.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(library.dli_fname) }.to_string_lossy().into_owned()
+    };
+
+    let (revoked, in_callback) = {
+        let blackboard_data = get_singleton().read().unwrap();
+        let Some(data) = blackboard_data.as_ref() else {
+            return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
+        };
+        (data.revoke(library.dli_fbase as usize), data.dispatcher.is_dispatcher_thread())
+    };
+    if revoked > 0 {
+        info!("Revoked {} subscriptions of {}", revoked, name);
+        // a callback of the component may still run
+        if !in_callback {
+            flush_intern()?;
+        }
+    }
+    Ok(revoked as c_int)
+}
+
+/// Unsubscribes every listener whose callback is in the library containing `address`, whatever
+/// component it subscribed as, and waits for their callbacks still running. The loader calls
+/// it with a symbol of a library before unloading it, so no callback points into unloaded code.
+/// Returns the number of subscriptions revoked.
+#[no_mangle]
+pub extern "C" fn revoke_subscriptions(address: *const c_void) -> c_int {
+    match catch_panic(|| revoke_subscriptions_intern(address)) {
+        Ok(revoked) => revoked,
+        Err(e) => {
+            error!("Failed to revoke subscriptions: {}", e);
+            e.record()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_void;
+    use std::time::Duration;
+    use super::*;
+    use assert_float_eq::assert_f32_near;
+    use rstest::fixture;
+    use rstest::rstest;
+    use serial_test::serial;
+    use interfaces::callback::CallbackChannel;
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_start() {
+        let key_values: Vec<BlackboardEntry> = vec![
+            BlackboardEntry {
+                key: "StringValue".to_string(),
+                value: BlackboardValue::String("Hello, World!".to_string()),
+            },
+            BlackboardEntry {
+                key: "IntValue".to_string(),
+                value: BlackboardValue::Int(42),
+            },
+        ];
+
+        let attributes = serde_yml::to_string(&key_values).unwrap() + "\0";
+
+        debug!("Attributes: {}", attributes);
+
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert_eq!(result.is_ok(), true);
+
+        {
+            let singleton = get_singleton().read().unwrap();
+            assert!(singleton.is_some());
+            let singleton = singleton.as_ref().unwrap();
+            assert_eq!(singleton.len(), 2);
+        }
+
+        {
+            let singleton = get_singleton().read().unwrap();
+            assert!(singleton.is_some());
+            let singleton = singleton.as_ref().unwrap();
+            assert_eq!(singleton.len(), 2);
+        }
+
+        let mut int_value: i32 = 0;
+        let result = get_int("IntValue\0".as_ptr() as *const c_char, &mut int_value);
+        assert_eq!(result, 0);
+        assert_eq!(int_value, 42);
+
+        let string_value_length = get_string(
+            "StringValue\0".as_ptr() as *const c_char,
+            std::ptr::null_mut(),
+        );
+        assert!(string_value_length > 0);
+
+        let mut buffer = vec![0u8; string_value_length as usize];
+        let string_value_length = get_string(
+            "StringValue\0".as_ptr() as *const c_char,
+            buffer.as_mut_ptr() as *mut c_char,
+        );
+
+        assert!(string_value_length > 0);
+
+        let string_value = unsafe { std::str::from_utf8_unchecked(&buffer) };
+        assert_eq!(string_value, "Hello, World!\0");
+
+        let result = stop();
+        assert_eq!(result, 0);
+
+        {
+            let singleton = get_singleton().read().unwrap();
+            assert!(singleton.is_none());
+        }
+    }
+
+    #[fixture]
+    fn startup() -> c_int {
+        let _result = stop();
+        let caps = interfaces::capabilities::Capabilities::new();
+        let result = start_server(caps.inner(), std::ptr::null());
+        return if result.is_ok() { 0 } else { -1 };
+    }
+
+    #[serial]
+    #[test]
+    fn test_string() {
+        let key = "int_key_4\0";
+        let ckey = key.as_ptr() as *const c_char;
+
+        unsafe {
+            let y = CStr::from_ptr(ckey).to_str().unwrap();
+
+            assert_eq!(&key[0..key.len() - 1], y);
+        }
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_int(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "int_key_4\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value = 42;
+
+        let result = set_int(key_c, value);
+        assert_eq!(result, 0);
+
+        let mut return_value = 0;
+
+        let result = get_int(key_c, &mut return_value);
+        assert_eq!(result, 0);
+        assert_eq!(value, return_value);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_int_not_found(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "int_key_not_found\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let mut return_value = 0;
+        let result = get_int(key_c, &mut return_value);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_float(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "float_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value = 42.0;
+
+        let result = set_float(key_c, value);
+        assert_eq!(result, 0);
+
+        let mut return_value = 0.0;
+
+        let result = get_float(key_c, &mut return_value);
+        assert_eq!(result, 0);
+        assert_f32_near!(value, return_value);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_float_not_found(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "float_key_not_found\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let mut return_value = 0.0;
+
+        let result = get_float(key_c, &mut return_value);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_bool(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "bool_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value = true;
+
+        let result = set_bool(key_c, value);
+        assert_eq!(result, 0);
+
+        let mut result_value = false;
+
+        let result = get_bool(key_c, &mut result_value);
+        assert_eq!(result, 0);
+        assert_eq!(result_value, value);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_bool_not_found(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "bool_key_not_found\0";
+        let key_c = key.as_ptr() as *const c_char;
+
+        let mut result_value = false;
+        let result = get_bool(key_c, &mut result_value);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_double(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key = "double_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value = 42.0;
+
+        let result = set_double(key_c, value);
+        assert_eq!(result, 0);
+
+        let mut result_value = 0.0;
+        let result = get_double(key_c, &mut result_value);
+        assert_eq!(result, 0);
+        assert_eq!(result_value, value);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_double_not_found(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "double_key_not_found\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let mut result_value = 0.0;
+        let result = get_double(key_c, &mut result_value);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+    }
+
+    #[serial]
+    #[test_log::test]
+    fn test_summary() {
+        // memory leak
+        let result = &String::from(SUMMARY_MESSAGE)[0..SUMMARY_MESSAGE.len() - 1]; // remove null terminator
+        let summary_result_c = summary();
+        let summary_result = unsafe { CStr::from_ptr(summary_result_c).to_str().unwrap() };
+        assert_eq!(result, summary_result);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_string(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key = "key\0";
+        let value = "value\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value_c = value.as_ptr() as *const c_char;
+
+        let result = set_string(key_c, value_c);
+        assert_eq!(result, 0);
+
+        let size = get_string(key_c, std::ptr::null_mut());
+        assert_eq!(size, value.len() as i32);
+
+        let mut buffer = vec![0u8; value.len()];
+
+        let result = get_string(key_c, buffer.as_mut_ptr() as *mut c_char);
+        assert_eq!(result, value.len() as i32);
+
+        let result_str = unsafe { std::str::from_utf8_unchecked(&buffer) };
+        assert_eq!(result_str, value);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_string_v2(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key_c = c"key".as_ptr();
+        assert_eq!(set_string(key_c, c"value".as_ptr()), 0);
+
+        let mut required = 0;
+        assert_eq!(get_string_v2(key_c, std::ptr::null_mut(), 0, &mut required), 0);
+        assert_eq!(required, 6);
+
+        // truncated and null terminated, the byte after the buffer stays untouched
+        let mut buffer = [0xffu8; 5];
+        let result = get_string_v2(key_c, buffer.as_mut_ptr() as *mut c_char, 4, &mut required);
+        assert_eq!(result, RtStatus::BufferTooSmall.code());
+        assert_eq!(required, 6);
+        assert_eq!(&buffer, b"val\0\xff");
+
+        let mut buffer = [0xffu8; 6];
+        let buffer_c = buffer.as_mut_ptr() as *mut c_char;
+        assert_eq!(get_string_v2(key_c, buffer_c, 6, std::ptr::null_mut()), 0);
+        assert_eq!(&buffer, b"value\0");
+
+        assert_eq!(
+            get_string_v2(key_c, buffer.as_mut_ptr() as *mut c_char, -1, &mut required),
+            RtStatus::InvalidArgument.code()
+        );
+        assert_eq!(
+            get_string_v2(c"missing".as_ptr(), std::ptr::null_mut(), 0, &mut required),
+            RtStatus::KeyNotFound.code()
+        );
+        let invalid = c"\xff\xfe";
+        assert_eq!(
+            get_string_v2(invalid.as_ptr(), std::ptr::null_mut(), 0, &mut required),
+            RtStatus::InvalidArgument.code()
+        );
+        assert_eq!(open_session(invalid.as_ptr()), RtStatus::InvalidArgument.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_string_not_found(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "key_not_found\0";
+        let key_c = key.as_ptr() as *const c_char;
+
+        let result = get_string(key_c, std::ptr::null_mut());
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_reset(startup: c_int) {
+        assert_eq!(startup, 0);
+        assert_eq!(size(), 0);
+        let key = "int_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value = 42;
+
+        let result = set_int(key_c, value);
+        assert_eq!(result, 0);
+        let mut result_value = 0;
+        let result = get_int(key_c, &mut result_value);
+        assert_eq!(result, 0);
+        assert_eq!(result_value, value);
+        assert_eq!(size(), 1);
+
+        reset();
+        assert_eq!(size(), 0);
+        let mut result_value = 0;
+        let result = get_int(key_c, &mut result_value);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+    }
+
+    
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_subscribe(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        static mut CALLBACK_CALLED: bool = false;
+
+        extern "C" fn callback(key: *const c_char, _user_data: *mut c_void) -> c_int {
+            let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
+            debug!("Callback called for key: {}", key);
+            unsafe {
+                CALLBACK_CALLED = true;
+            }
+            0
+        }
+        
+        let key = "int_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
+
+        let result = subscribe_intern(key_c, component_c, callback as *mut c_void, std::ptr::null_mut(), false, None);
+        assert_eq!(result.is_ok(), true);
+        let callback_called = unsafe { CALLBACK_CALLED };
+        assert_eq!(callback_called, false);
+        let set_value = 42;
+        let result = set_int(key_c, set_value);
+        assert_eq!(result, 0);
+        assert_eq!(flush(), 0);
+        let callback_called = unsafe { CALLBACK_CALLED };
+        assert_eq!(callback_called, true);
+
+        let result = unsubscribe_intern(key_c, component_c);
+        assert_eq!(result.is_ok(), true);
+
+        let result = subscribe(key_c, component_c, std::ptr::null_mut(), std::ptr::null_mut());
+        assert_eq!(result, RtStatus::NullArgument.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_subscribe_with_user_data(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let channel = CallbackChannel::<String>::new();
+        let callback = CallbackChannel::<String>::key_callback();
+
+        let key = "int_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
+
+        let result = subscribe_intern(key_c, component_c, callback, channel.user_data(), false, None);
+        assert!(result.is_ok());
+
+        let set_value = 42;
+        let result = set_int(key_c, set_value);
+        assert_eq!(result, 0);
+
+        assert!(channel.recv_timeout(Duration::from_secs(1)).is_ok());
+
+        let set_value = 43;
+        let result = set_int(key_c, set_value);
+        assert_eq!(result, 0);
+
+        assert!(channel.recv_timeout(Duration::from_secs(1)).is_ok());
+
+        let set_value = 60;
+        let result = set_int(key_c, set_value);
+        assert_eq!(result, 0);
+
+        assert!(channel.recv_timeout(Duration::from_secs(1)).is_ok());
+        
+        let result = unsubscribe_intern(key_c, component_c);
+        assert!(result.is_ok());
+        assert_eq!(flush(), 0);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_subscribe_with_options(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let (coalesced, dropped) = (CallbackChannel::new(), CallbackChannel::new());
+        let callback = CallbackChannel::<BlackboardEvent>::event_callback();
+        let key = "controller/output\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let (coalescing, other) = ("coalescing\0", "dropping\0");
+        let coalescing = coalescing.as_ptr() as *const c_char;
+        let other = other.as_ptr() as *const c_char;
+
+        let options = "{\"max_rate\": 10, \"coalesce\": true, \"event\": true}\0";
+        let result = subscribe_with_options(key_c, coalescing, callback, coalesced.user_data(), options.as_ptr() as *const c_char);
+        assert_eq!(result, 0);
+        let options = "{\"max_rate\": 10, \"event\": true}\0";
+        let result = subscribe_with_options(key_c, other, callback, dropped.user_data(), options.as_ptr() as *const c_char);
+        assert_eq!(result, 0);
+
+        for value in 0..10 {
+            assert_eq!(set_int(key_c, value), 0);
+        }
+        assert_eq!(flush(), 0);
+        // the first change is delivered right away, the others fall into the interval
+        let value = |event: BlackboardEvent| serde_json::to_value(event.new).unwrap();
+        let first: Vec<_> = coalesced.try_iter().chain(dropped.try_iter()).map(value).collect();
+        assert_eq!(first, vec![serde_json::json!(0), serde_json::json!(0)]);
+        let event = coalesced.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(value(event), serde_json::json!(9));
+        assert!(coalesced.recv_timeout(Duration::from_millis(200)).is_err());
+        assert!(dropped.try_recv().is_err());
+
+        let invalid = |options: &str| {
+            let options = CString::new(options).unwrap();
+            subscribe_with_options(key_c, other, callback, dropped.user_data(), options.as_ptr())
+        };
+        assert_eq!(invalid("{\"coalesce\": true}"), RtStatus::InvalidArgument.code());
+        assert_eq!(invalid("{\"max_rate\": 0}"), RtStatus::InvalidArgument.code());
+        assert_eq!(invalid("{\"rate\": 1}"), RtStatus::InvalidArgument.code());
+
+        assert_eq!(unsubscribe(key_c, coalescing), 0);
+        assert_eq!(unsubscribe(key_c, other), 0);
+        assert_eq!(flush(), 0);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_int_array(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key = "int_array_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let values = vec![1, 2, 3, 42];
+
+        let result = set_int_array(key_c, values.as_ptr(), values.len() as c_int);
+        assert_eq!(result, 0);
+
+        let len = get_int_array(key_c, std::ptr::null_mut());
+        assert_eq!(len, values.len() as c_int);
+
+        let mut buffer = vec![0; len as usize];
+        let len = get_int_array(key_c, buffer.as_mut_ptr());
+        assert_eq!(len, values.len() as c_int);
+        assert_eq!(buffer, values);
+
+        let mut value = 0;
+        let result = get_int(key_c, &mut value);
+        assert_eq!(result, RtStatus::TypeMismatch.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_double_array(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key = "double_array_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let values = vec![0.5, -1.25, 42.0];
+
+        let result = set_double_array(key_c, values.as_ptr(), values.len() as c_int);
+        assert_eq!(result, 0);
+
+        let len = get_double_array(key_c, std::ptr::null_mut());
+        assert_eq!(len, values.len() as c_int);
+
+        let mut buffer = vec![0.0; len as usize];
+        let len = get_double_array(key_c, buffer.as_mut_ptr());
+        assert_eq!(len, values.len() as c_int);
+        assert_eq!(buffer, values);
+
+        let result = get_int_array(key_c, std::ptr::null_mut());
+        assert_eq!(result, RtStatus::TypeMismatch.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_start_with_arrays() {
+        let attributes = "- key: joints\n  value: [1, 2, 3]\n- key: waypoints\n  value: [0.5, 1, 2.5]\n\0";
+
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert!(result.is_ok());
+
+        let key = "joints\0";
+        let mut joints = vec![0; 3];
+        let len = get_int_array(key.as_ptr() as *const c_char, joints.as_mut_ptr());
+        assert_eq!(len, 3);
+        assert_eq!(joints, vec![1, 2, 3]);
+
+        let key = "waypoints\0";
+        let mut waypoints = vec![0.0; 3];
+        let len = get_double_array(key.as_ptr() as *const c_char, waypoints.as_mut_ptr());
+        assert_eq!(len, 3);
+        assert_eq!(waypoints, vec![0.5, 1.0, 2.5]);
+    }
+
+    #[test_log::test]
+    #[serial]
+    fn test_strict_mode() {
+        let attributes = "- key: strict\n  value: true\n- key: speed\n  value: 1\n\0";
+
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert!(result.is_ok());
+
+        // "strict" configures the blackboard and is not stored
+        let key = "strict\0";
+        let mut strict = false;
+        assert_eq!(get_bool(key.as_ptr() as *const c_char, &mut strict), RtStatus::KeyNotFound.code());
+
+        let speed_key = "speed\0";
+        let speed_c = speed_key.as_ptr() as *const c_char;
+        assert_eq!(set_float(speed_c, 2.5), TYPE_MISMATCH);
+        assert_eq!(set_int(speed_c, 2), 0);
+        let mut speed = 0;
+        assert_eq!(get_int(speed_c, &mut speed), 0);
+        assert_eq!(speed, 2);
+
+        let key = "mode\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value = "auto\0";
+        assert_eq!(set_string(key_c, value.as_ptr() as *const c_char), 0);
+        assert_eq!(set_bool(key_c, true), TYPE_MISMATCH);
+
+        // a batch touching a locked key is rejected as a whole
+        let batch = "- key: speed\n  value: 3\n- key: mode\n  value: 1\n\0";
+        assert_eq!(set_batch(batch.as_ptr() as *const c_char), -1);
+        assert_eq!(get_int(speed_c, &mut speed), 0);
+        assert_eq!(speed, 2);
+
+        // deleting a key releases its type
+        assert_eq!(delete(key_c), 0);
+        assert_eq!(set_bool(key_c, true), 0);
+
+        assert_eq!(stop(), 0);
+    }
+
+    #[test_log::test]
+    #[serial]
+    fn test_tagged_writes() {
+        let attributes = "- key: priority_window_ms\n  value: 200\n\0";
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        assert!(start_server(caps.inner(), attributes.as_ptr() as *const c_char).is_ok());
+
+        let key = c"cmd/speed".as_ptr();
+        let mut speed = 0.0;
+        assert_eq!(set_double_tagged(key, 0.0, c"safety".as_ptr(), 10), 0);
+        // the planner may not overwrite the stop of the safety monitor
+        assert_eq!(set_double_tagged(key, 1.5, c"planner".as_ptr(), 1), ACCESS_DENIED);
+        assert_eq!(set_double(key, 1.5), ACCESS_DENIED);
+        let value = cr#"{"type": "double", "value": 1.5}"#.as_ptr();
+        assert_eq!(set_value(key, value), ACCESS_DENIED);
+        assert_eq!(get_double(key, &mut speed), 0);
+        assert_eq!(speed, 0.0);
+        // the same or a higher priority takes the key over
+        assert_eq!(set_value_tagged(key, value, c"safety".as_ptr(), 10), 0);
+        assert_eq!(set_double_tagged(key, 0.5, c"operator".as_ptr(), 20), 0);
+        assert_eq!(set_double_tagged(key, 0.7, c"safety".as_ptr(), 10), ACCESS_DENIED);
+
+        // once the window passed, the key is written by anybody again
+        std::thread::sleep(Duration::from_millis(250));
+        assert_eq!(set_double(key, 1.5), 0);
+        assert_eq!(set_double_tagged(key, 1.0, c"planner".as_ptr(), 1), 0);
+        assert_eq!(get_double(key, &mut speed), 0);
+        assert_eq!(speed, 1.0);
+        let unknown = set_double_tagged(key, 1.0, std::ptr::null(), 1);
+        assert_eq!(unknown, RtStatus::NullArgument.code());
+
+        assert_eq!(stop(), 0);
+    }
+
+    #[test_log::test]
+    #[serial]
+    fn test_constraints() {
+        let attributes = "- key: speed\n  value: 1.0\n  min: 0\n  max: 2.5\n\
+                          - key: name\n  value: robot\n  pattern: '[a-z]+'\n\
+                          - key: mode\n  value: auto\n  enum: [auto, manual]\n\
+                          - key: gains\n  value: [1, 2]\n  max: 10\n\0";
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        assert!(start_server(caps.inner(), attributes.as_ptr() as *const c_char).is_ok());
+
+        let speed = c"speed".as_ptr();
+        assert_eq!(set_double(speed, 2.0), 0);
+        assert_eq!(set_double(speed, 3.0), CONSTRAINT_VIOLATION);
+        assert_eq!(set_double(speed, -0.5), CONSTRAINT_VIOLATION);
+        let mut value = 0.0;
+        assert_eq!(get_double(speed, &mut value), 0);
+        assert_eq!(value, 2.0);
+
+        let name = c"name".as_ptr();
+        assert_eq!(set_string(name, c"walle".as_ptr()), 0);
+        // the pattern has to match the whole string
+        assert_eq!(set_string(name, c"walle 2".as_ptr()), CONSTRAINT_VIOLATION);
+
+        let mode = c"mode".as_ptr();
+        assert_eq!(set_string(mode, c"manual".as_ptr()), 0);
+        assert_eq!(set_string(mode, c"off".as_ptr()), CONSTRAINT_VIOLATION);
+        let value = cr#"{"type": "string", "value": "off"}"#.as_ptr();
+        assert_eq!(set_value(mode, value), CONSTRAINT_VIOLATION);
+        assert_eq!(
+            compare_and_set_string(mode, c"manual".as_ptr(), c"off".as_ptr()),
+            CONSTRAINT_VIOLATION
+        );
+
+        let gains = c"gains".as_ptr();
+        let values = [1, 11];
+        assert_eq!(set_int_array(gains, values.as_ptr(), 2), CONSTRAINT_VIOLATION);
+
+        // a batch with one rejected value writes nothing
+        let batch = "- key: speed\n  value: 1.5\n- key: mode\n  value: off\n\0";
+        assert_eq!(set_batch(batch.as_ptr() as *const c_char), -1);
+        let mut value = 0.0;
+        assert_eq!(get_double(speed, &mut value), 0);
+        assert_eq!(value, 2.0);
+
+        // the violations are published, the latest last
+        let key = c"blackboard/validation_errors".as_ptr();
+        let mut buffer = vec![0u8; get_json(key, std::ptr::null_mut()) as usize];
+        assert!(get_json(key, buffer.as_mut_ptr() as *mut c_char) > 0);
+        let errors: serde_json::Value =
+            serde_json::from_str(CStr::from_bytes_until_nul(&buffer).unwrap().to_str().unwrap())
+                .unwrap();
+        let errors = errors.as_array().unwrap();
+        assert_eq!(errors.len(), 8);
+        assert_eq!(errors[0]["key"], "speed");
+        assert_eq!(errors[0]["error"], "3 is above the maximum 2.5");
+        assert_eq!(errors[7]["key"], "mode");
+
+        let mut buffer = vec![0u8; as_json_schema(std::ptr::null_mut()) as usize];
+        as_json_schema(buffer.as_mut_ptr() as *mut c_char);
+        let schema: serde_json::Value =
+            serde_json::from_str(CStr::from_bytes_until_nul(&buffer).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(schema["properties"]["speed"]["maximum"], 2.5);
+        assert_eq!(schema["properties"]["gains"]["items"]["maximum"], 10.0);
+        assert_eq!(schema["properties"]["mode"]["enum"], serde_json::json!(["auto", "manual"]));
+        assert_eq!(stop(), 0);
+
+        // initial values have to meet their constraints, invalid patterns are refused
+        let attributes = "- key: speed\n  value: 5\n  max: 2.5\n\0";
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert!(result.is_err());
+        let attributes = "- key: name\n  value: a\n  pattern: '['\n\0";
+        assert!(start_server(caps.inner(), attributes.as_ptr() as *const c_char).is_err());
+        let _ = stop();
+    }
+
+    #[test_log::test]
+    #[serial]
+    fn test_history() {
+        let attributes = "- key: history\n  value:\n    pid/output: 3\n\0";
+
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert!(result.is_ok());
+        assert_eq!(size(), 0); // history configures the blackboard and is no entry
+
+        let key = "pid/output\0";
+        let key_c = key.as_ptr() as *const c_char;
+        for value in [1.0, 2.0, 3.0, 4.0] {
+            assert_eq!(set_double(key_c, value), 0);
+        }
+
+        let history_value = |index: c_int| -> Option<TypedBlackboardValue> {
+            let size = get_history(key_c, index, std::ptr::null_mut());
+            if size < 0 {
+                return None;
+            }
+            let mut buffer = vec![0u8; size as usize];
+            assert_eq!(get_history(key_c, index, buffer.as_mut_ptr() as *mut c_char), size);
+            let value = CStr::from_bytes_with_nul(&buffer).unwrap().to_str().unwrap();
+            Some(serde_json::from_str(value).unwrap())
+        };
+
+        // newest first, only the configured depth is kept
+        assert_eq!(history_value(0), Some(TypedBlackboardValue::Double(4.0)));
+        assert_eq!(history_value(1), Some(TypedBlackboardValue::Double(3.0)));
+        assert_eq!(history_value(2), Some(TypedBlackboardValue::Double(2.0)));
+        assert_eq!(history_value(3), None);
+        assert_eq!(history_value(-1), None);
+
+        // the kept values with their write times, oldest first
+        let history_since = |since: u64| -> Vec<HistorySample> {
+            let size = get_history_since(key_c, since, std::ptr::null_mut(), 0);
+            let mut buffer = vec![0u8; size as usize];
+            let written = get_history_since(key_c, since, buffer.as_mut_ptr() as *mut c_char, size);
+            assert_eq!(written, size);
+            let samples = CStr::from_bytes_with_nul(&buffer).unwrap().to_str().unwrap();
+            serde_json::from_str(samples).unwrap()
+        };
+        let samples = history_since(0);
+        let values: Vec<_> = samples.iter().map(|sample| sample.value.clone()).collect();
+        assert_eq!(values, [2.0, 3.0, 4.0].map(TypedBlackboardValue::Double));
+        assert!(samples.windows(2).all(|pair| pair[0].time <= pair[1].time));
+        let latest = samples[2].time;
+        assert!(history_since(latest).is_empty());
+        let latest_writes = samples.iter().filter(|sample| sample.time == latest).count();
+        assert_eq!(history_since(latest - 1).len(), latest_writes);
+
+        let key = "other\0";
+        assert_eq!(set_int(key.as_ptr() as *const c_char, 1), 0);
+        assert_eq!(get_history(key.as_ptr() as *const c_char, 0, std::ptr::null_mut()), RtStatus::InvalidArgument.code());
+        let size = get_history_since(key.as_ptr() as *const c_char, 0, std::ptr::null_mut(), 0);
+        assert_eq!(size, RtStatus::InvalidArgument.code());
+
+        assert_eq!(stop(), 0);
+    }
+
+    #[test_log::test]
+    #[serial]
+    fn test_audit_log() {
+        let attributes = "- key: audit\n  value: 3\n- key: speed\n  value: 1\n\0";
+
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert!(result.is_ok());
+        assert_eq!(size(), 1);
+
+        let audit_log = |revision: u64| -> Vec<AuditRecord> {
+            let size = get_audit_log(revision, std::ptr::null_mut(), 0);
+            let mut buffer = vec![0u8; size as usize];
+            let written = get_audit_log(revision, buffer.as_mut_ptr() as *mut c_char, size);
+            assert_eq!(written, size);
+            let records = CStr::from_bytes_with_nul(&buffer).unwrap().to_str().unwrap();
+            serde_json::from_str(records).unwrap()
+        };
+        // the initial values are written as well
+        let records = audit_log(0);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].old, None);
+        assert_eq!(records[0].new, TypedBlackboardValue::Int(1));
+
+        let speed = c"speed".as_ptr();
+        let nav = open_session(c"nav".as_ptr());
+        assert_eq!(enter_session(nav), 0);
+        assert_eq!(set_int(speed, 2), 0);
+        assert_eq!(enter_session(0), 0);
+        assert_eq!(set_double(c"other".as_ptr(), 0.5), 0);
+        assert_eq!(set_int(speed, 3), 0);
+
+        // only the latest writes are kept, oldest first
+        let records = audit_log(0);
+        let keys: Vec<_> = records.iter().map(|record| record.key.as_str()).collect();
+        assert_eq!(keys, ["speed", "other", "speed"]);
+        assert_eq!(records[0].old, Some(TypedBlackboardValue::Int(1)));
+        assert_eq!(records[0].new, TypedBlackboardValue::Int(2));
+        assert_eq!(records[0].component.as_deref(), Some("nav"));
+        assert_eq!(records[1].component, None);
+        assert_eq!(records[2].old, Some(TypedBlackboardValue::Int(2)));
+        assert!(records.windows(2).all(|pair| pair[0].revision < pair[1].revision));
+        assert_eq!(audit_log(records[1].revision), records[2..]);
+        assert_eq!(close_session(nav), 0);
+        assert_eq!(stop(), 0);
+
+        // the log is off by default
+        let result = start_server(caps.inner(), std::ptr::null());
+        assert!(result.is_ok());
+        let size = get_audit_log(0, std::ptr::null_mut(), 0);
+        assert_eq!(size, RtStatus::InvalidArgument.code());
+        assert_eq!(stop(), 0);
+    }
+
+    #[test_log::test]
+    #[serial]
+    fn test_access() {
+        let attributes =
+            "- key: access\n  value:\n    nav: {read: [nav], write: [nav]}\n    logger: {read: ['*']}\n\0";
+
+        let caps = interfaces::capabilities::Capabilities::new();
+        let _result = stop();
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert!(result.is_ok());
+        assert_eq!(size(), 0);
+
+        let (goal, state, other) = ("nav/goal\0", "arm/state\0", "navigation\0");
+        let goal = goal.as_ptr() as *const c_char;
+        let state = state.as_ptr() as *const c_char;
+        let value = "{\"type\": \"int\", \"value\": 1}\0";
+        let value = value.as_ptr() as *const c_char;
+        // callers without session can not write a namespace a component writes
+        assert_eq!(set_int(goal, 1), ACCESS_DENIED);
+        assert_eq!(set_int(state, 1), 0);
+
+        let (nav, logger) = ("nav\0", "logger\0");
+        let nav = open_session(nav.as_ptr() as *const c_char);
+        assert!(nav > 0);
+        assert_eq!(enter_session(nav), 0);
+        assert_eq!(set_value(goal, value), 0);
+        assert_eq!(set_int(other.as_ptr() as *const c_char, 1), ACCESS_DENIED);
+        let mut number = 0;
+        assert_eq!(get_int(state, &mut number), ACCESS_DENIED);
+        assert_eq!(delete(state), ACCESS_DENIED);
+        assert_eq!(enter_session(0), 0);
+
+        let logger = open_session(logger.as_ptr() as *const c_char);
+        assert_eq!(enter_session(logger), 0);
+        assert_eq!(get_int(goal, &mut number), 0);
+        assert_eq!(number, 1);
+        assert_eq!(set_int(goal, 2), ACCESS_DENIED);
+        let batch = "[{key: arm/state, value: 2}, {key: nav/goal, value: 2}]\0";
+        assert_eq!(set_batch(batch.as_ptr() as *const c_char), ACCESS_DENIED);
+        assert_eq!(enter_session(0), 0);
+        assert_eq!(get_int(state, &mut number), 0);
+        assert_eq!(number, 1); // nothing of the batch was written
+        // callers without session can not read the namespaces of the rules either
+        assert_eq!(get_int(goal, &mut number), ACCESS_DENIED);
+
+        assert_eq!(close_session(logger), 0);
+        assert_eq!(enter_session(logger), RtStatus::InvalidArgument.code());
+        assert_eq!(close_session(nav), 0);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_json(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key = "json_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let value = "{\"pose\": {\"x\": 1.5, \"y\": -2}, \"frames\": [1, 2, 3]}\0";
+
+        let result = set_json(key_c, value.as_ptr() as *const c_char);
+        assert_eq!(result, 0);
+
+        let size = get_json(key_c, std::ptr::null_mut());
+        assert!(size > 0);
+
+        let mut buffer = vec![0u8; size as usize];
+        let result = get_json(key_c, buffer.as_mut_ptr() as *mut c_char);
+        assert_eq!(result, size);
+
+        let json = unsafe { CStr::from_ptr(buffer.as_ptr() as *const c_char).to_str().unwrap() };
+        let json: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(json["pose"]["x"], 1.5);
+        assert_eq!(json["frames"][2], 3);
+
+        let invalid = "{not json\0";
+        let result = set_json(key_c, invalid.as_ptr() as *const c_char);
+        assert_eq!(result, -1);
+
+        let size = get_string(key_c, std::ptr::null_mut());
+        assert_eq!(size, RtStatus::TypeMismatch.code());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_set_json_path(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let channel = CallbackChannel::<BlackboardEvent>::new();
+        let callback = CallbackChannel::<BlackboardEvent>::event_callback();
+
+        let key = CString::new("json_key").unwrap();
+        let value = CString::new(r#"{"pose": {"x": 1.5, "y": -2}, "frames": [1, 2]}"#).unwrap();
+        assert_eq!(set_json(key.as_ptr(), value.as_ptr()), 0);
+        let component = CString::new("component").unwrap();
+        let result = subscribe_v2(key.as_ptr(), component.as_ptr(), callback, channel.user_data());
+        assert_eq!(result, 0);
+
+        let get = |pointer: &str| -> Result<serde_json::Value, c_int> {
+            let pointer = CString::new(pointer).unwrap();
+            let size = get_json_path(key.as_ptr(), pointer.as_ptr(), std::ptr::null_mut());
+            if size < 0 {
+                return Err(size);
+            }
+            let mut buffer = vec![0u8; size as usize];
+            get_json_path(key.as_ptr(), pointer.as_ptr(), buffer.as_mut_ptr() as *mut c_char);
+            let json = CStr::from_bytes_until_nul(&buffer).unwrap().to_str().unwrap();
+            Ok(serde_json::from_str(json).unwrap())
+        };
+        let set = |pointer: &str, value: &str| {
+            let (pointer, value) = (CString::new(pointer).unwrap(), CString::new(value).unwrap());
+            set_json_path(key.as_ptr(), pointer.as_ptr(), value.as_ptr())
+        };
+
+        assert_eq!(get("/pose/x"), Ok(serde_json::json!(1.5)));
+        assert_eq!(get("/frames/1"), Ok(serde_json::json!(2)));
+        assert_eq!(get("").unwrap()["pose"]["y"], -2);
+        assert_eq!(get("/pose/z"), Err(RtStatus::KeyNotFound.code()));
+
+        assert_eq!(set("/pose/x", "2.5"), 0);
+        let event = channel.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(event.path.as_deref(), Some("/pose/x"));
+        assert_eq!(get("/pose"), Ok(serde_json::json!({"x": 2.5, "y": -2})));
+
+        assert_eq!(set("/pose/theta", "0.5"), 0);
+        assert_eq!(set("/frames/-", "3"), 0);
+        assert_eq!(set("/frames/0", "0"), 0);
+        assert_eq!(get("/frames"), Ok(serde_json::json!([0, 2, 3])));
+        assert_eq!(get("/pose/theta"), Ok(serde_json::json!(0.5)));
+
+        assert_eq!(set("/missing/x", "1"), RtStatus::KeyNotFound.code());
+        assert_eq!(set("/frames/7", "1"), RtStatus::InvalidArgument.code());
+        assert_eq!(set("/pose/x/y", "1"), RtStatus::InvalidArgument.code());
+        assert_eq!(set("pose", "1"), RtStatus::InvalidArgument.code());
+        assert_eq!(set("/pose/x", "{not json"), -1);
+
+        let other = CString::new("int_key").unwrap();
+        assert_eq!(set_int(other.as_ptr(), 1), 0);
+        let pointer = CString::new("/x").unwrap();
+        let result = get_json_path(other.as_ptr(), pointer.as_ptr(), std::ptr::null_mut());
+        assert_eq!(result, RtStatus::TypeMismatch.code());
+
+        assert_eq!(unsubscribe(key.as_ptr(), component.as_ptr()), 0);
+        assert_eq!(flush(), 0);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_json_schema_for_json_values(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key = "json_key\0";
+        let value = "{\"pose\": {\"x\": 1.5}, \"tags\": [\"a\", \"b\"]}\0";
+        let result = set_json(key.as_ptr() as *const c_char, value.as_ptr() as *const c_char);
+        assert_eq!(result, 0);
+
+        let mut buffer = vec![0u8; as_json_schema(std::ptr::null_mut()) as usize];
+        as_json_schema(buffer.as_mut_ptr() as *mut c_char);
+        let schema = unsafe { CStr::from_ptr(buffer.as_ptr() as *const c_char).to_str().unwrap() };
+        let schema: serde_json::Value = serde_json::from_str(schema).unwrap();
+
+        let property = &schema["properties"]["json_key"];
+        assert_eq!(property["type"], "object");
+        assert_eq!(property["properties"]["pose"]["properties"]["x"]["type"], "number");
+        assert_eq!(property["properties"]["tags"]["items"]["type"], "string");
+        assert_eq!(property["value"]["tags"][1], "b");
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_ttl(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let key = "ttl_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+
+        let result = set_ttl(key_c, 50);
+        assert_eq!(result, RtStatus::KeyNotFound.code()); // key does not exist yet
+
+        let result = set_int(key_c, 42);
+        assert_eq!(result, 0);
+        let result = set_ttl(key_c, 50);
+        assert_eq!(result, 0);
+
+        let mut value = 0;
+        let result = get_int(key_c, &mut value);
+        assert_eq!(result, 0);
+        assert_eq!(value, 42);
+
+        std::thread::sleep(Duration::from_millis(80));
+        let result = get_int(key_c, &mut value);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+        assert_eq!(size(), 0);
+
+        // the ttl stays attached to the key and is refreshed by every write
+        let result = set_int(key_c, 43);
+        assert_eq!(result, 0);
+        std::thread::sleep(Duration::from_millis(30));
+        let result = set_int(key_c, 44);
+        assert_eq!(result, 0);
+        std::thread::sleep(Duration::from_millis(30));
+        let result = get_int(key_c, &mut value);
+        assert_eq!(result, 0);
+        assert_eq!(value, 44);
+
+        let result = set_ttl(key_c, 0);
+        assert_eq!(result, 0);
+        std::thread::sleep(Duration::from_millis(80));
+        assert_eq!(size(), 1);
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_ttl_expiry_notifies_subscribers(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        static NOTIFICATIONS: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+        extern "C" fn callback(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            NOTIFICATIONS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            0
+        }
+
+        let key = "expiring_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
+
+        let result = set_bool(key_c, true);
+        assert_eq!(result, 0);
+        let result = set_ttl(key_c, 10);
+        assert_eq!(result, 0);
+
+        let result = subscribe_intern(key_c, component_c, callback as *mut c_void, std::ptr::null_mut(), false, None);
+        assert!(result.is_ok());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(size(), 0);
+        assert_eq!(flush(), 0);
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let result = unsubscribe_intern(key_c, component_c);
+        assert!(result.is_ok());
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_callback_return_codes(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        static ONCE: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+        static FAILING: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+        extern "C" fn once(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            ONCE.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            1
+        }
+
+        extern "C" fn failing(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            FAILING.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
             -1
         }
-    }
-}
 
-fn as_json_schema_intern(cvalue: *mut c_char) -> Result<i32, String> {
-    let blackboard_data = get_singleton().lock().unwrap();
-    if blackboard_data.is_none() {
-        return Err("Server is not running".to_string());
+        let key = "callback_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let once_component = "once\0";
+        let once_c = once_component.as_ptr() as *const c_char;
+        let failing_component = "failing\0";
+        let failing_c = failing_component.as_ptr() as *const c_char;
+
+        let result = subscribe_intern(key_c, once_c, once as *mut c_void, std::ptr::null_mut(), false, None);
+        assert!(result.is_ok());
+        let result = subscribe_intern(key_c, failing_c, failing as *mut c_void, std::ptr::null_mut(), false, None);
+        assert!(result.is_ok());
+
+        for value in 0..15 {
+            assert_eq!(set_int(key_c, value), 0);
+            assert_eq!(flush(), 0);
+        }
+        assert_eq!(ONCE.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(FAILING.load(std::sync::atomic::Ordering::SeqCst), MAX_CALLBACK_FAILURES as i32);
+        {
+            let singleton = get_singleton().read().unwrap();
+            let stats = singleton.as_ref().unwrap().key_stats("callback_key").unwrap();
+            assert_eq!(stats.subscribers, 0);
+        }
+
+        // subscribing again starts over
+        let result = subscribe_intern(key_c, once_c, once as *mut c_void, std::ptr::null_mut(), false, None);
+        assert!(result.is_ok());
+        assert_eq!(set_int(key_c, 42), 0);
+        assert_eq!(flush(), 0);
+        assert_eq!(ONCE.load(std::sync::atomic::Ordering::SeqCst), 2);
     }
 
-    let mut schema = serde_json::json!({
-        "$schema": "http://json-schema.org/draft-07/schema#",
-        "type": "object",
-        "properties": {}
-    });
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_full_notify_queue(startup: c_int) {
+        assert_eq!(startup, 0);
 
-    for (key, value) in blackboard_data.as_ref().unwrap().data.iter() {
-        let mut property = serde_json::json!({});
-        if let Some(v) = value.downcast_ref::<String>() {
-            property["type"] = "string".into();
-            property["value"] = v.clone().into();
-        } else if let Some(v) = value.downcast_ref::<i32>() {
-            property["type"] = "integer".into();
-            property["value"] = v.clone().into();
-        } else if let Some(v) = value.downcast_ref::<f32>() {
-            property["type"] = "number".into();
-            property["value"] = v.clone().into();
-        } else if let Some(v) = value.downcast_ref::<f64>() {
-            property["type"] = "number".into();
-            property["value"] = v.clone().into();
-        } else if let Some(v) = value.downcast_ref::<bool>() {
-            property["type"] = "boolean".into();
-            property["value"] = v.clone().into();
-        } else {
-            return Err(format!("Unsupported type for key: {}", key));
+        static GATE: Mutex<()> = Mutex::new(());
+        static ENTERED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+        extern "C" fn blocking(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            ENTERED.store(true, std::sync::atomic::Ordering::SeqCst);
+            drop(GATE.lock());
+            0
         }
-        schema["properties"][key] = property;
+
+        let gate_key = CString::new("overflow/gate").unwrap();
+        let key = CString::new("overflow/value").unwrap();
+        let (gate_component, component) = (CString::new("gate").unwrap(), CString::new("values").unwrap());
+        let events = CallbackChannel::<BlackboardEvent>::new();
+        let callback = CallbackChannel::<BlackboardEvent>::event_callback();
+        let result = subscribe_intern(gate_key.as_ptr(), gate_component.as_ptr(), blocking as *mut c_void, std::ptr::null_mut(), false, None);
+        assert!(result.is_ok());
+        let result = subscribe_intern(key.as_ptr(), component.as_ptr(), callback, events.user_data(), true, None);
+        assert!(result.is_ok());
+
+        // the dispatcher is held in the callback while the queue fills up
+        let guard = GATE.lock().unwrap();
+        assert_eq!(set_int(gate_key.as_ptr(), 1), 0);
+        while !ENTERED.load(std::sync::atomic::Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let last = NOTIFY_QUEUE_SIZE as c_int + 100;
+        for value in 0..=last {
+            assert_eq!(set_int(key.as_ptr(), value), 0);
+        }
+        drop(guard);
+        assert_eq!(flush(), 0);
+
+        // the queued ones in order, the last value of the key is delivered
+        let values: Vec<_> = events.try_iter().map(|event| serde_json::to_value(event.new).unwrap()).collect();
+        assert_eq!(values.len(), NOTIFY_QUEUE_SIZE + 1);
+        assert_eq!(values[0], serde_json::json!(0));
+        assert_eq!(values.last(), Some(&serde_json::json!(last)));
+        {
+            let singleton = get_singleton().read().unwrap();
+            assert_eq!(singleton.as_ref().unwrap().dispatcher.coalesced(), 100);
+        }
+
+        assert_eq!(unsubscribe(gate_key.as_ptr(), gate_component.as_ptr()), 0);
+        assert_eq!(unsubscribe(key.as_ptr(), component.as_ptr()), 0);
+        assert_eq!(flush(), 0);
     }
 
-    let schema_str = schema.to_string() + "\0";
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_delete(startup: c_int) {
+        assert_eq!(startup, 0);
 
-    if !cvalue.is_null() {
-        let tmp_value = schema_str.as_bytes();
-        unsafe {
-            std::ptr::copy_nonoverlapping(tmp_value.as_ptr(), cvalue as *mut u8, tmp_value.len());
+        static NOTIFICATIONS: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+        extern "C" fn callback(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            NOTIFICATIONS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            0
         }
-    }
-    return Ok(schema_str.len() as i32);
-}
 
-#[no_mangle]
-pub extern "C" fn as_json_schema(value: *mut c_char) -> c_int {
-    match as_json_schema_intern(value) {
-        Ok(size) => size,
-        Err(e) => {
-            error!("Failed to get json schema: {}", e);
-            -1
+        let key = "delete_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
+
+        let result = delete(key_c);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+
+        let result = set_double(key_c, 1.0);
+        assert_eq!(result, 0);
+        let other_key = "other_key\0";
+        let result = set_int(other_key.as_ptr() as *const c_char, 1);
+        assert_eq!(result, 0);
+        assert_eq!(size(), 2);
+
+        let result = subscribe_intern(key_c, component_c, callback as *mut c_void, std::ptr::null_mut(), false, None);
+        assert!(result.is_ok());
+
+        let result = delete(key_c);
+        assert_eq!(result, 0);
+        assert_eq!(size(), 1);
+        assert_eq!(flush(), 0);
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let mut value = 0.0;
+        let result = get_double(key_c, &mut value);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
+
+        {
+            let singleton = get_singleton().read().unwrap();
+            let listeners = singleton.as_ref().unwrap().listeners.read().unwrap();
+            assert!(!listeners.key_to_listener.contains_key("delete_key"));
         }
+
+        // the subscription is gone together with the key
+        let result = set_double(key_c, 2.0);
+        assert_eq!(result, 0);
+        assert_eq!(flush(), 0);
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), 1);
     }
-}
 
-fn subscribe_intern(
-    key: *const c_char,
-    component: *const c_char,
-    callback: *mut c_void,
-    user_data: *mut c_void,
-) -> Result<(), String> {
-    let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
-    let component = unsafe { CStr::from_ptr(component).to_str().unwrap() };
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_subscribe_v2_old_and_new_value(startup: c_int) {
+        assert_eq!(startup, 0);
 
-    let mut blackboard_data = get_singleton().lock().unwrap();
-    if blackboard_data.is_none() {
-        return Err("Server is not running".to_string());
-    }
+        let channel = CallbackChannel::<BlackboardEvent>::new();
+        let callback = CallbackChannel::<BlackboardEvent>::event_callback();
 
-    blackboard_data
-        .as_mut()
-        .unwrap()
-        .subscribe(key, component, callback, user_data);
-    Ok(())
-}
+        let key = "v2_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
 
-#[no_mangle]
-pub extern "C" fn subscribe(
-    key: *const c_char,
-    component: *const c_char,
-    callback: *mut c_void,
-    user_data: *mut c_void,
-) -> c_int {
-    match subscribe_intern(key, component, callback, user_data) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to subscribe: {}", e);
-            -1
-        }
+        let result = subscribe_v2(key_c, component_c, callback, channel.user_data());
+        assert_eq!(result, 0);
+
+        let receive = || channel.recv_timeout(Duration::from_secs(1)).unwrap();
+
+        assert_eq!(set_int(key_c, 1), 0);
+        let event = receive();
+        assert_eq!(event.key, "v2_key");
+        assert_eq!(event.reason, NotifyReason::Changed);
+        assert!(event.old.is_none());
+        assert!(matches!(event.new, Some(BlackboardValue::Int(1))));
+
+        assert_eq!(set_int(key_c, 2), 0);
+        let event = receive();
+        assert!(matches!(event.old, Some(BlackboardValue::Int(1))));
+        assert!(matches!(event.new, Some(BlackboardValue::Int(2))));
+
+        assert_eq!(delete(key_c), 0);
+        let event = receive();
+        assert_eq!(event.reason, NotifyReason::Deleted);
+        assert!(matches!(event.old, Some(BlackboardValue::Int(2))));
+        assert!(event.new.is_none());
+
+        assert_eq!(unsubscribe(key_c, component_c), 0);
+        assert_eq!(flush(), 0);
     }
-}
 
-fn unsubscribe_intern(key: *const c_char, component: *const c_char) -> Result<(), String> {
-    let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
-    let component = unsafe { CStr::from_ptr(component).to_str().unwrap() };
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_subscribe_wildcard(startup: c_int) {
+        assert_eq!(startup, 0);
 
-    let mut blackboard_data = get_singleton().lock().unwrap();
-    if blackboard_data.is_none() {
-        return Err("Server is not running".to_string());
+        let channel = CallbackChannel::<String>::new();
+        let callback = CallbackChannel::<String>::key_callback();
+
+        let pattern = "robot/pose/*\0";
+        let pattern_c = pattern.as_ptr() as *const c_char;
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
+
+        let result = subscribe(pattern_c, component_c, callback, channel.user_data());
+        assert_eq!(result, 0);
+
+        let key = "robot/pose/x\0";
+        assert_eq!(set_double(key.as_ptr() as *const c_char, 1.0), 0);
+        assert_eq!(channel.recv_timeout(Duration::from_secs(1)).unwrap(), "robot/pose/x");
+
+        let key = "robot/pose/y\0";
+        assert_eq!(set_double(key.as_ptr() as *const c_char, 2.0), 0);
+        assert_eq!(channel.recv_timeout(Duration::from_secs(1)).unwrap(), "robot/pose/y");
+
+        let key = "robot/twist/x\0";
+        assert_eq!(set_double(key.as_ptr() as *const c_char, 3.0), 0);
+        assert_eq!(flush(), 0);
+        assert!(channel.try_recv().is_err());
+
+        let result = unsubscribe(pattern_c, component_c);
+        assert_eq!(result, 0);
+
+        let key = "robot/pose/x\0";
+        assert_eq!(set_double(key.as_ptr() as *const c_char, 4.0), 0);
+        assert_eq!(flush(), 0);
+        assert!(channel.try_recv().is_err());
     }
 
-    blackboard_data.as_mut().unwrap().unsubscribe(key, component);
-    Ok(())
-}
+    fn snapshot_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("blackboard_{}_{}", std::process::id(), name))
+    }
 
-#[no_mangle]
-pub extern "C" fn unsubscribe(key: *const c_char, component: *const c_char) -> c_int {
-    match unsubscribe_intern(key, component) {
-        Ok(_) => 0,
-        Err(e) => {
-            error!("Failed to unsubscribe: {}", e);
-            -1
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_save_load(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        let (double_key, float_key, array_key) = ("double_key\0", "float_key\0", "array_key\0");
+        let double_key = double_key.as_ptr() as *const c_char;
+        let float_key = float_key.as_ptr() as *const c_char;
+        let array_key = array_key.as_ptr() as *const c_char;
+
+        for name in ["snapshot.json", "snapshot.yaml"] {
+            let path = snapshot_path(name);
+            let cpath = CString::new(path.to_str().unwrap()).unwrap();
+
+            assert_eq!(set_double(double_key, 0.1), 0);
+            assert_eq!(set_float(float_key, 0.5), 0);
+            let values = [1, 2, 3];
+            assert_eq!(set_int_array(array_key, values.as_ptr(), 3), 0);
+
+            assert_eq!(save(cpath.as_ptr()), 0);
+            reset();
+            assert_eq!(size(), 0);
+
+            assert_eq!(load(cpath.as_ptr()), 0);
+            assert_eq!(size(), 3);
+
+            // values come back with their exact type
+            let mut double_value = 0.0;
+            assert_eq!(get_double(double_key, &mut double_value), 0);
+            assert_eq!(double_value, 0.1);
+            let mut float_value = 0.0;
+            assert_eq!(get_float(float_key, &mut float_value), 0);
+            assert_f32_near!(float_value, 0.5);
+            assert_eq!(get_int_array(array_key, std::ptr::null_mut()), 3);
+
+            std::fs::remove_file(&path).unwrap();
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::ffi::c_void;
-    use std::time::Duration;
-    use super::*;
-    use assert_float_eq::assert_f32_near;
-    use rstest::fixture;
-    use rstest::rstest;
-    use serial_test::serial;
-    use std::sync::mpsc;
+        let missing = "/nonexistent/snapshot.json\0";
+        assert_eq!(load(missing.as_ptr() as *const c_char), -1);
+    }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_start() {
-        let key_values: Vec<BlackboardEntry> = vec![
-            BlackboardEntry {
-                key: "StringValue".to_string(),
-                value: BlackboardValue::String("Hello, World!".to_string()),
-            },
-            BlackboardEntry {
-                key: "IntValue".to_string(),
-                value: BlackboardValue::Int(42),
-            },
-        ];
+    fn test_msgpack(startup: c_int) {
+        assert_eq!(startup, 0);
 
-        let attributes = serde_yml::to_string(&key_values).unwrap() + "\0";
+        let (int64_key, float_key, bytes_key) = ("int64_key\0", "float_key\0", "bytes_key\0");
+        let int64_key = int64_key.as_ptr() as *const c_char;
+        let float_key = float_key.as_ptr() as *const c_char;
+        let bytes_key = bytes_key.as_ptr() as *const c_char;
+        assert_eq!(set_int64(int64_key, i64::MAX), 0);
+        assert_eq!(set_float(float_key, 0.1), 0);
+        let bytes = [0u8, 1, 255];
+        assert_eq!(set_bytes(bytes_key, bytes.as_ptr(), 3), 0);
+
+        let len = export_msgpack(std::ptr::null_mut(), 0);
+        assert!(len > 0);
+        let mut buffer = vec![0u8; len as usize];
+        assert_eq!(
+            export_msgpack(buffer.as_mut_ptr(), 1),
+            RtStatus::BufferTooSmall.code()
+        );
+        assert_eq!(export_msgpack(buffer.as_mut_ptr(), len), len);
 
-        debug!("Attributes: {}", attributes);
+        reset();
+        assert_eq!(import_msgpack(buffer.as_ptr(), len), 0);
+        assert_eq!(size(), 3);
+
+        // values come back with their exact type
+        let mut int64_value = 0;
+        assert_eq!(get_int64(int64_key, &mut int64_value), 0);
+        assert_eq!(int64_value, i64::MAX);
+        let mut float_value = 0.0;
+        assert_eq!(get_float(float_key, &mut float_value), 0);
+        assert_eq!(float_value, 0.1);
+        let mut bytes_value = [0u8; 3];
+        assert_eq!(get_bytes(bytes_key, bytes_value.as_mut_ptr(), 3), 3);
+        assert_eq!(bytes_value, bytes);
+
+        assert_eq!(
+            import_msgpack(buffer.as_ptr(), 2),
+            RtStatus::InvalidArgument.code()
+        );
+    }
+
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_persist_path() {
+        let path = snapshot_path("persist.yaml");
+        let _ = std::fs::remove_file(&path);
+        let attributes = format!(
+            "- key: persist_path\n  value: {}\n- key: counter\n  value: 1\n\0",
+            path.display()
+        );
 
         let caps = interfaces::capabilities::Capabilities::new();
         let _result = stop();
         let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
+        assert_eq!(size(), 1); // persist_path configures the blackboard and is no entry
 
-        {
-            let singleton = get_singleton().lock().unwrap();
-            assert!(singleton.is_some());
-            let singleton = singleton.as_ref().unwrap();
-            assert_eq!(singleton.data.len(), 2);
-        }
+        let key = "counter\0";
+        assert_eq!(set_int(key.as_ptr() as *const c_char, 5), 0);
+        assert_eq!(stop(), 0);
+        assert!(path.exists());
 
-        {
-            let singleton = get_singleton().lock().unwrap();
-            assert!(singleton.is_some());
-            let singleton = singleton.as_ref().unwrap();
-            assert_eq!(singleton.data.len(), 2);
-        }
+        let result = start_server(caps.inner(), attributes.as_ptr() as *const c_char);
+        assert!(result.is_ok());
+        let mut value = 0;
+        assert_eq!(get_int(key.as_ptr() as *const c_char, &mut value), 0);
+        assert_eq!(value, 5);
 
-        let mut int_value: i32 = 0;
-        let result = get_int("IntValue\0".as_ptr() as *const c_char, &mut int_value);
-        assert_eq!(result, 0);
-        assert_eq!(int_value, 42);
+        assert_eq!(stop(), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        let string_value_length = get_string(
-            "StringValue\0".as_ptr() as *const c_char,
-            std::ptr::null_mut(),
-        );
-        assert!(string_value_length > 0);
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_compare_and_set_int(startup: c_int) {
+        assert_eq!(startup, 0);
 
-        let mut buffer = vec![0u8; string_value_length as usize];
-        let string_value_length = get_string(
-            "StringValue\0".as_ptr() as *const c_char,
-            buffer.as_mut_ptr() as *mut c_char,
-        );
+        let key = "cas_int_key\0";
+        let key_c = key.as_ptr() as *const c_char;
 
-        assert!(string_value_length > 0);
+        let result = compare_and_set_int(key_c, 0, 1);
+        assert_eq!(result, RtStatus::KeyNotFound.code());
 
-        let string_value = unsafe { std::str::from_utf8_unchecked(&buffer) };
-        assert_eq!(string_value, "Hello, World!\0");
+        assert_eq!(set_int(key_c, 1), 0);
 
-        let result = stop();
+        let result = compare_and_set_int(key_c, 1, 2);
         assert_eq!(result, 0);
 
-        {
-            let singleton = get_singleton().lock().unwrap();
-            assert!(singleton.is_none());
-        }
-    }
+        let result = compare_and_set_int(key_c, 1, 3);
+        assert_eq!(result, VALUE_MISMATCH);
 
-    #[fixture]
-    fn startup() -> c_int {
-        let _result = stop();
-        let caps = interfaces::capabilities::Capabilities::new();
-        let result = start_server(caps.inner(), std::ptr::null());
-        return if result.is_ok() { 0 } else { -1 };
+        let mut value = 0;
+        assert_eq!(get_int(key_c, &mut value), 0);
+        assert_eq!(value, 2);
+
+        let string_key = "cas_string_key\0";
+        let string_key_c = string_key.as_ptr() as *const c_char;
+        let value = "value\0";
+        assert_eq!(set_string(string_key_c, value.as_ptr() as *const c_char), 0);
+        let result = compare_and_set_int(string_key_c, 0, 1);
+        assert_eq!(result, RtStatus::TypeMismatch.code());
     }
 
+    #[rstest]
     #[serial]
-    #[test]
-    fn test_string() {
-        let key = "int_key_4\0";
-        let ckey = key.as_ptr() as *const c_char;
+    #[test_log::test]
+    fn test_compare_and_set_string(startup: c_int) {
+        assert_eq!(startup, 0);
 
-        unsafe {
-            let y = CStr::from_ptr(ckey).to_str().unwrap();
+        let key = "cas_string_key\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let (idle, busy, done) = ("idle\0", "busy\0", "done\0");
+        let idle = idle.as_ptr() as *const c_char;
+        let busy = busy.as_ptr() as *const c_char;
+        let done = done.as_ptr() as *const c_char;
+
+        assert_eq!(set_string(key_c, idle), 0);
+
+        let result = compare_and_set_string(key_c, idle, busy);
+        assert_eq!(result, 0);
+
+        // a second worker loses the race
+        let result = compare_and_set_string(key_c, idle, busy);
+        assert_eq!(result, VALUE_MISMATCH);
+
+        let result = compare_and_set_string(key_c, busy, done);
+        assert_eq!(result, 0);
 
-            assert_eq!(&key[0..key.len() - 1], y);
-        }
+        let mut buffer = vec![0u8; 5];
+        assert_eq!(get_string(key_c, buffer.as_mut_ptr() as *mut c_char), 5);
+        assert_eq!(&buffer[..4], b"done");
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_set_int(startup: c_int) {
+    fn test_set_batch(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "int_key_4\0";
-        let key_c = key.as_ptr() as *const c_char;
-        let value = 42;
 
-        let result = set_int(key_c, value);
+        let channel = CallbackChannel::<BlackboardEvent>::new();
+
+        let pattern = "pose/*\0";
+        let component = "component\0";
+        let result = subscribe_v2(
+            pattern.as_ptr() as *const c_char,
+            component.as_ptr() as *const c_char,
+            CallbackChannel::<BlackboardEvent>::event_callback(),
+            channel.user_data(),
+        );
         assert_eq!(result, 0);
 
-        let mut return_value = 0;
+        let key = "pose/x\0";
+        assert_eq!(set_double(key.as_ptr() as *const c_char, 0.5), 0);
+        channel.recv_timeout(Duration::from_secs(1)).unwrap();
 
-        let result = get_int(key_c, &mut return_value);
+        let batch = "[{\"key\": \"pose/x\", \"value\": 1.5}, {\"key\": \"pose/valid\", \"value\": true}, {\"key\": \"pose/x\", \"value\": 2.5}]\0";
+        let result = set_batch(batch.as_ptr() as *const c_char);
         assert_eq!(result, 0);
-        assert_eq!(value, return_value);
+        assert_eq!(flush(), 0);
+
+        // one event per key, spanning the whole batch
+        let mut events: Vec<BlackboardEvent> = channel.try_iter().collect();
+        events.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].key, "pose/valid");
+        assert!(events[0].old.is_none());
+        assert_eq!(events[1].key, "pose/x");
+        let number = |value: &Option<BlackboardValue>| match value {
+            Some(BlackboardValue::Float(v)) => *v as f64,
+            Some(BlackboardValue::Double(v)) => *v,
+            _ => panic!("Not a number: {:?}", value),
+        };
+        assert_eq!(number(&events[1].old), 0.5);
+        assert_eq!(number(&events[1].new), 2.5);
+
+        // pose/x was a double and stays one
+        let mut value = 0.0;
+        assert_eq!(get_double(key.as_ptr() as *const c_char, &mut value), 0);
+        assert_eq!(value, 2.5);
+
+        let invalid = "[{\"key\": \"pose/y\"}]\0";
+        let result = set_batch(invalid.as_ptr() as *const c_char);
+        assert_eq!(result, -1);
+        assert_eq!(size(), 2);
+        assert_eq!(flush(), 0);
+        assert!(channel.try_recv().is_err());
+
+        let result = unsubscribe(pattern.as_ptr() as *const c_char, component.as_ptr() as *const c_char);
+        assert_eq!(result, 0);
+        assert_eq!(flush(), 0);
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_int_not_found(startup: c_int) {
+    fn test_transaction(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "int_key_not_found\0";
-        let key_c = key.as_ptr() as *const c_char;
-        let mut return_value = 0;
-        let result = get_int(key_c, &mut return_value);
-        assert_eq!(result, -1);
+
+        let channel = CallbackChannel::<BlackboardEvent>::new();
+
+        let key = "form/speed\0";
+        assert_eq!(set_int(key.as_ptr() as *const c_char, 1), 0);
+        let pattern = "form/*\0";
+        let component = "component\0";
+        let result = subscribe_v2(
+            pattern.as_ptr() as *const c_char,
+            component.as_ptr() as *const c_char,
+            CallbackChannel::<BlackboardEvent>::event_callback(),
+            channel.user_data(),
+        );
+        assert_eq!(result, 0);
+
+        let run = |operations: &str| -> (c_int, Vec<BlackboardOperationResult>) {
+            let operations = CString::new(operations).unwrap();
+            let size = transaction(operations.as_ptr(), std::ptr::null_mut(), 0);
+            assert!(size > 0);
+            let mut buffer = vec![0u8; size as usize];
+            let written = transaction(operations.as_ptr(), buffer.as_mut_ptr() as *mut c_char, size);
+            let results = CStr::from_bytes_until_nul(&buffer).unwrap().to_str().unwrap();
+            (written, serde_json::from_str(results).unwrap())
+        };
+
+        // one event per key, the deleted key gets its last one
+        let operations = r#"[
+            {"op": "set", "key": "form/speed", "value": {"type": "int", "value": 2}},
+            {"op": "set", "key": "form/mode", "value": {"type": "string", "value": "auto"}},
+            {"op": "set", "key": "form/speed", "value": {"type": "int", "value": 3}},
+            {"op": "delete", "key": "form/mode"}
+        ]"#;
+        let (written, results) = run(operations);
+        assert!(written > 0);
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|result| result.applied && result.error.is_none()));
+        assert_eq!(flush(), 0);
+        let mut events: Vec<BlackboardEvent> = channel.try_iter().collect();
+        events.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].key, "form/mode");
+        assert_eq!(events[0].reason, NotifyReason::Deleted);
+        assert_eq!(events[1].key, "form/speed");
+        assert!(matches!(events[1].new, Some(BlackboardValue::Int(3))));
+        let mut value = 0;
+        assert_eq!(get_int(key.as_ptr() as *const c_char, &mut value), 0);
+        assert_eq!(value, 3);
+
+        // nothing is applied if one operation fails
+        let operations = r#"[
+            {"op": "set", "key": "form/speed", "value": {"type": "int", "value": 4}},
+            {"op": "delete", "key": "form/mode"}
+        ]"#;
+        let (_, results) = run(operations);
+        assert!(results.iter().all(|result| !result.applied));
+        assert_eq!(results[0].error, None);
+        assert_eq!(results[1].error.as_deref(), Some("Key not found: form/mode"));
+        assert_eq!(flush(), 0);
+        assert!(channel.try_recv().is_err());
+        assert_eq!(get_int(key.as_ptr() as *const c_char, &mut value), 0);
+        assert_eq!(value, 3);
+
+        // a short buffer only reports the size
+        let operations = CString::new(r#"[{"op": "delete", "key": "form/speed"}]"#).unwrap();
+        let mut buffer = vec![0u8; 4];
+        let needed = transaction(operations.as_ptr(), buffer.as_mut_ptr() as *mut c_char, 4);
+        assert!(needed > 4);
+        assert_eq!(size(), 1);
+
+        let invalid = "[{\"op\": \"rename\", \"key\": \"form/speed\"}]\0";
+        let result = transaction(invalid.as_ptr() as *const c_char, std::ptr::null_mut(), 0);
+        assert_eq!(result, RtStatus::InvalidArgument.code());
+
+        let result = unsubscribe(pattern.as_ptr() as *const c_char, component.as_ptr() as *const c_char);
+        assert_eq!(result, 0);
+        assert_eq!(flush(), 0);
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_set_float(startup: c_int) {
+    fn test_get_set_bytes(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "float_key\0";
+
+        let key = "bytes_key\0";
         let key_c = key.as_ptr() as *const c_char;
-        let value = 42.0;
+        let value: Vec<u8> = vec![0, 1, 2, 255, 0, 42];
 
-        let result = set_float(key_c, value);
+        let result = set_bytes(key_c, value.as_ptr(), value.len() as c_int);
         assert_eq!(result, 0);
 
-        let mut return_value = 0.0;
+        let len = get_bytes(key_c, std::ptr::null_mut(), 0);
+        assert_eq!(len, value.len() as c_int);
 
-        let result = get_float(key_c, &mut return_value);
-        assert_eq!(result, 0);
-        assert_f32_near!(value, return_value);
+        let mut small = vec![0u8; 2];
+        let result = get_bytes(key_c, small.as_mut_ptr(), small.len() as c_int);
+        assert_eq!(result, RtStatus::BufferTooSmall.code());
+        assert_eq!(small, vec![0, 0]);
+
+        let mut buffer = vec![0u8; 16];
+        let len = get_bytes(key_c, buffer.as_mut_ptr(), buffer.len() as c_int);
+        assert_eq!(len, value.len() as c_int);
+        assert_eq!(&buffer[..len as usize], value.as_slice());
+
+        // blobs survive a snapshot round trip
+        let path = snapshot_path("bytes.yaml");
+        let cpath = CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(save(cpath.as_ptr()), 0);
+        reset();
+        assert_eq!(load(cpath.as_ptr()), 0);
+        let mut buffer = vec![0u8; value.len()];
+        assert_eq!(get_bytes(key_c, buffer.as_mut_ptr(), buffer.len() as c_int), value.len() as c_int);
+        assert_eq!(buffer, value);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_float_not_found(startup: c_int) {
+    fn test_keys(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "float_key_not_found\0";
-        let key_c = key.as_ptr() as *const c_char;
-        let mut return_value = 0.0;
 
-        let result = get_float(key_c, &mut return_value);
-        assert_eq!(result, -1);
+        let key = "b_int\0";
+        assert_eq!(set_int(key.as_ptr() as *const c_char, 1), 0);
+        let key = "a_string\0";
+        let value = "value\0";
+        assert_eq!(
+            set_string(key.as_ptr() as *const c_char, value.as_ptr() as *const c_char),
+            0
+        );
+        let key = "c_bytes\0";
+        let value: Vec<u8> = vec![1, 2, 3];
+        assert_eq!(
+            set_bytes(key.as_ptr() as *const c_char, value.as_ptr(), value.len() as c_int),
+            0
+        );
+
+        let buffer_size = keys(std::ptr::null_mut());
+        assert!(buffer_size > 0);
+        let mut buffer = vec![0u8; buffer_size as usize];
+        assert_eq!(keys(buffer.as_mut_ptr() as *mut c_char), buffer_size);
+
+        let listing = CStr::from_bytes_with_nul(&buffer).unwrap().to_str().unwrap();
+        let listing: Vec<BlackboardKeyInfo> = serde_json::from_str(listing).unwrap();
+        let expected: Vec<(&str, &str)> =
+            vec![("a_string", "string"), ("b_int", "int"), ("c_bytes", "bytes")];
+        assert_eq!(
+            listing
+                .iter()
+                .map(|k| (k.key.as_str(), k.value_type.as_str()))
+                .collect::<Vec<_>>(),
+            expected
+        );
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_set_bool(startup: c_int) {
+    fn test_get_value(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "bool_key\0";
-        let key_c = key.as_ptr() as *const c_char;
-        let value = true;
-
-        let result = set_bool(key_c, value);
-        assert_eq!(result, 0);
-
-        let mut result_value = false;
 
-        let result = get_bool(key_c, &mut result_value);
-        assert_eq!(result, 0);
-        assert_eq!(result_value, value);
+        let key = c"an_int64".as_ptr();
+        assert_eq!(set_int64(key, 1 << 40), 0);
+        let size = get_value(key, std::ptr::null_mut());
+        let mut buffer = vec![0u8; size as usize];
+        assert_eq!(get_value(key, buffer.as_mut_ptr() as *mut c_char), size);
+        let value = CStr::from_bytes_with_nul(&buffer).unwrap().to_str().unwrap();
+        let value: TypedBlackboardValue = serde_json::from_str(value).unwrap();
+        assert_eq!(value, TypedBlackboardValue::Int64(1 << 40));
+
+        let missing = c"missing".as_ptr();
+        assert_eq!(get_value(missing, std::ptr::null_mut()), RtStatus::KeyNotFound.code());
+
+        // the type is kept, even if the value fits a smaller one
+        let key = c"small_int64".as_ptr();
+        assert_eq!(set_value(key, cr#"{"type": "int64", "value": 1}"#.as_ptr()), 0);
+        let mut value = 0;
+        assert_eq!(get_int64(key, &mut value), 0);
+        assert_eq!(value, 1);
+        assert_eq!(set_value(key, c"42".as_ptr()), RtStatus::InvalidArgument.code());
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_bool_not_found(startup: c_int) {
+    fn test_stats(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "bool_key_not_found\0";
+
+        extern "C" fn callback(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            0
+        }
+
+        let key = "stats_key\0";
         let key_c = key.as_ptr() as *const c_char;
+        let component = "stats_component\0";
+
+        assert_eq!(stats(key_c, std::ptr::null_mut()), RtStatus::KeyNotFound.code());
+
+        assert_eq!(set_int(key_c, 1), 0);
+        assert_eq!(set_int(key_c, 2), 0);
+        let mut value: c_int = 0;
+        assert_eq!(get_int(key_c, &mut value), 0);
+        assert_eq!(
+            subscribe(
+                key_c,
+                component.as_ptr() as *const c_char,
+                callback as *mut c_void,
+                std::ptr::null_mut()
+            ),
+            0
+        );
 
-        let mut result_value = false;
-        let result = get_bool(key_c, &mut result_value);
-        assert_eq!(result, -1);
+        let buffer_size = stats(key_c, std::ptr::null_mut());
+        assert!(buffer_size > 0);
+        let mut buffer = vec![0u8; buffer_size as usize];
+        assert_eq!(stats(key_c, buffer.as_mut_ptr() as *mut c_char), buffer_size);
+
+        let stats_json = CStr::from_bytes_with_nul(&buffer).unwrap().to_str().unwrap();
+        let key_stats: BlackboardKeyStats = serde_json::from_str(stats_json).unwrap();
+        assert_eq!(key_stats.key, "stats_key");
+        assert_eq!(key_stats.writes, 2);
+        assert_eq!(key_stats.reads, 1);
+        assert_eq!(key_stats.subscribers, 1);
+        assert!(key_stats.last_write > 0);
+
+        assert_eq!(delete(key_c), 0);
+        assert_eq!(stats(key_c, std::ptr::null_mut()), RtStatus::KeyNotFound.code());
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_set_double(startup: c_int) {
+    fn test_callback_reads_blackboard(startup: c_int) {
         assert_eq!(startup, 0);
 
-        let key = "double_key\0";
+        static SEEN: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+        // reading the board from a callback used to deadlock on the blackboard lock
+        extern "C" fn callback(key: *const c_char, _user_data: *mut c_void) -> c_int {
+            let mut value: c_int = 0;
+            if get_int(key, &mut value) == 0 {
+                SEEN.store(value, std::sync::atomic::Ordering::SeqCst);
+            }
+            assert_eq!(flush(), -1);
+            0
+        }
+
+        let key = "reentrant_key\0";
         let key_c = key.as_ptr() as *const c_char;
-        let value = 42.0;
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
 
-        let result = set_double(key_c, value);
+        let result = subscribe(key_c, component_c, callback as *mut c_void, std::ptr::null_mut());
         assert_eq!(result, 0);
 
-        let mut result_value = 0.0;
-        let result = get_double(key_c, &mut result_value);
+        assert_eq!(set_int(key_c, 7), 0);
+        assert_eq!(flush(), 0);
+        assert_eq!(SEEN.load(std::sync::atomic::Ordering::SeqCst), 7);
+
+        let result = unsubscribe(key_c, component_c);
         assert_eq!(result, 0);
-        assert_eq!(result_value, value);
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_double_not_found(startup: c_int) {
+    fn test_unsubscribe_releases_capability(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "double_key_not_found\0";
+
+        static NOTIFICATIONS: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+        extern "C" fn callback(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            NOTIFICATIONS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            0
+        }
+
+        let key = "churn_key\0";
         let key_c = key.as_ptr() as *const c_char;
-        let mut result_value = 0.0;
-        let result = get_double(key_c, &mut result_value);
-        assert_eq!(result, -1);
+        let component = "component\0";
+        let component_c = component.as_ptr() as *const c_char;
+
+        // more rounds than the capability table has slots
+        let rounds = interfaces::bindings::CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES as i32 + 10;
+        for i in 0..rounds {
+            assert_eq!(subscribe(key_c, component_c, callback as *mut c_void, std::ptr::null_mut()), 0);
+            assert_eq!(set_int(key_c, i), 0);
+            assert_eq!(unsubscribe(key_c, component_c), 0);
+        }
+        assert_eq!(flush(), 0);
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), rounds);
+
+        {
+            let singleton = get_singleton().read().unwrap();
+            assert_eq!(singleton.as_ref().unwrap().listeners.read().unwrap().listener.len(), 0);
+        }
     }
 
+    #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_summary() {
-        // memory leak
-        let result = &String::from(SUMMARY_MESSAGE)[0..SUMMARY_MESSAGE.len() - 1]; // remove null terminator
-        let summary_result_c = summary();
-        let summary_result = unsafe { CStr::from_ptr(summary_result_c).to_str().unwrap() };
-        assert_eq!(result, summary_result);
+    fn test_revoke_subscriptions(startup: c_int) {
+        assert_eq!(startup, 0);
+
+        static NOTIFICATIONS: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+        extern "C" fn callback(_key: *const c_char, _user_data: *mut c_void) -> c_int {
+            NOTIFICATIONS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            0
+        }
+        let callback = callback as *mut c_void;
+        // a callback in another library, its key is never set
+        let foreign = libc::getpid as *mut c_void;
+
+        let key_c = c"revoke_key".as_ptr();
+        let plugin = c"plugin".as_ptr();
+        assert_eq!(subscribe(key_c, plugin, callback, std::ptr::null_mut()), 0);
+        let events = c"plugin/events".as_ptr();
+        assert_eq!(subscribe(key_c, events, callback, std::ptr::null_mut()), 0);
+        // the component name does not matter, the library of the callback does
+        let unused = c"revoke_unused".as_ptr();
+        assert_eq!(subscribe(unused, plugin, foreign, std::ptr::null_mut()), 0);
+
+        assert_eq!(revoke_subscriptions(libc::getpid as *const c_void), 1);
+        assert_eq!(set_int(key_c, 1), 0);
+        assert_eq!(flush(), 0);
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        assert_eq!(revoke_subscriptions(callback), 2);
+        assert_eq!(revoke_subscriptions(callback), 0);
+        assert_eq!(set_int(key_c, 2), 0);
+        assert_eq!(flush(), 0);
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), 2);
+        {
+            let singleton = get_singleton().read().unwrap();
+            let listeners = singleton.as_ref().unwrap().listeners.read().unwrap();
+            assert_eq!(listeners.listener.len(), 0);
+            assert_eq!(listeners.libraries.len(), 0);
+        }
+
+        // a reloaded plugin subscribes again
+        assert_eq!(subscribe(key_c, plugin, callback, std::ptr::null_mut()), 0);
+        assert_eq!(set_int(key_c, 3), 0);
+        assert_eq!(flush(), 0);
+        assert_eq!(NOTIFICATIONS.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(revoke_subscriptions(std::ptr::null()), RtStatus::NullArgument.code());
+        let nowhere = 16 as *const c_void;
+        assert_eq!(revoke_subscriptions(nowhere), RtStatus::InvalidArgument.code());
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_set_string(startup: c_int) {
+    fn test_get_set_int64(startup: c_int) {
         assert_eq!(startup, 0);
 
-        let key = "key\0";
-        let value = "value\0";
+        let key = "frame_counter\0";
         let key_c = key.as_ptr() as *const c_char;
-        let value_c = value.as_ptr() as *const c_char;
-
-        let result = set_string(key_c, value_c);
-        assert_eq!(result, 0);
+        let value: i64 = i32::MAX as i64 * 4;
 
-        let size = get_string(key_c, std::ptr::null_mut());
-        assert_eq!(size, value.len() as i32);
+        assert_eq!(set_int64(key_c, value), 0);
 
-        let mut buffer = vec![0u8; value.len()];
+        let mut result: i64 = 0;
+        assert_eq!(get_int64(key_c, &mut result), 0);
+        assert_eq!(result, value);
 
-        let result = get_string(key_c, buffer.as_mut_ptr() as *mut c_char);
-        assert_eq!(result, value.len() as i32);
+        let mut small: c_int = 0;
+        assert_eq!(get_int(key_c, &mut small), RtStatus::TypeMismatch.code());
 
-        let result_str = unsafe { std::str::from_utf8_unchecked(&buffer) };
-        assert_eq!(result_str, value);
+        // values beyond i32 in the start attributes become int64
+        let entry: BlackboardEntry = serde_yml::from_str("key: big\nvalue: 8589934592\n").unwrap();
+        assert!(matches!(entry.value, BlackboardValue::Int64(8589934592)));
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_get_string_not_found(startup: c_int) {
+    fn test_timestamp(startup: c_int) {
         assert_eq!(startup, 0);
-        let key = "key_not_found\0";
+
+        let key = "camera/stamp\0";
         let key_c = key.as_ptr() as *const c_char;
 
-        let result = get_string(key_c, std::ptr::null_mut());
-        assert_eq!(result, -1);
+        assert_eq!(set_timestamp(key_c, 42), 0);
+        let mut value: u64 = 0;
+        assert_eq!(get_timestamp(key_c, &mut value), 0);
+        assert_eq!(value, 42);
+
+        let before = monotonic_now();
+        assert_eq!(stamp(key_c), 0);
+        assert_eq!(get_timestamp(key_c, &mut value), 0);
+        assert!(value >= before.0);
+        assert!(value <= monotonic_now().0);
+
+        // a timestamp is no plain integer
+        let mut plain: i64 = 0;
+        assert_eq!(get_int64(key_c, &mut plain), RtStatus::TypeMismatch.code());
+
+        let size = keys(std::ptr::null_mut());
+        let mut buffer = vec![0u8; size as usize];
+        assert_eq!(keys(buffer.as_mut_ptr() as *mut c_char), size);
+        let listing = CStr::from_bytes_with_nul(&buffer).unwrap().to_str().unwrap();
+        let listing: Vec<BlackboardKeyInfo> = serde_json::from_str(listing).unwrap();
+        assert_eq!(listing[0].value_type, "timestamp");
     }
 
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_reset(startup: c_int) {
+    fn test_wait(startup: c_int) {
         assert_eq!(startup, 0);
-        assert_eq!(size(), 0);
-        let key = "int_key\0";
-        let key_c = key.as_ptr() as *const c_char;
-        let value = 42;
 
-        let result = set_int(key_c, value);
-        assert_eq!(result, 0);
-        let mut result_value = 0;
-        let result = get_int(key_c, &mut result_value);
-        assert_eq!(result, 0);
-        assert_eq!(result_value, value);
-        assert_eq!(size(), 1);
+        let key = "ready\0";
+        let key_c = key.as_ptr() as *const c_char;
 
-        reset();
-        assert_eq!(size(), 0);
-        let mut result_value = 0;
-        let result = get_int(key_c, &mut result_value);
-        assert_eq!(result, -1);
+        // nothing writes the key
+        assert_eq!(wait(key_c, 20), TIMEOUT);
+
+        let writer = std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            let key = "ready\0";
+            set_bool(key.as_ptr() as *const c_char, true)
+        });
+        assert_eq!(wait(key_c, 5000), 0);
+        assert_eq!(writer.join().unwrap(), 0);
+
+        // an existing value does not count, only a new write
+        assert_eq!(wait(key_c, 20), TIMEOUT);
+
+        let writer = std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            let key = "ready\0";
+            set_bool(key.as_ptr() as *const c_char, false)
+        });
+        assert_eq!(wait(key_c, -1), 0);
+        assert_eq!(writer.join().unwrap(), 0);
+
+        // waiters are released when the blackboard stops
+        let waiter = std::thread::spawn(|| {
+            let key = "never\0";
+            wait(key.as_ptr() as *const c_char, -1)
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(stop(), 0);
+        assert_eq!(waiter.join().unwrap(), RtStatus::NotRunning.code());
     }
 
-    
-
     #[rstest]
     #[serial]
     #[test_log::test]
-    fn test_subscribe(startup: c_int) {
+    fn test_concurrent_keys(startup: c_int) {
         assert_eq!(startup, 0);
-
-        static mut CALLBACK_CALLED: bool = false;
-
-        extern "C" fn callback(key: *const c_char, user_data: *mut c_void) -> c_int {
-            let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
-            debug!("Callback called for key: {}", key);
-            unsafe {
-                CALLBACK_CALLED = true;
-            }
-            0
+        assert_eq!(set_int(c"counter".as_ptr(), 0), 0);
+
+        // every thread writes its own key and increments the shared counter
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                std::thread::spawn(move || {
+                    let key = CString::new(format!("thread/{}", thread)).unwrap();
+                    for i in 0..200 {
+                        assert_eq!(set_int(key.as_ptr(), i), 0);
+                        let mut value = 0;
+                        assert_eq!(get_int(key.as_ptr(), &mut value), 0);
+                        assert_eq!(value, i);
+                        loop {
+                            let mut count = 0;
+                            assert_eq!(get_int(c"counter".as_ptr(), &mut count), 0);
+                            match compare_and_set_int(c"counter".as_ptr(), count, count + 1) {
+                                0 => break,
+                                result => assert_eq!(result, VALUE_MISMATCH),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
         }
-        
-        let key = "int_key\0";
-        let key_c = key.as_ptr() as *const c_char;
-        let component = "component\0";
-        let component_c = component.as_ptr() as *const c_char;
-
-        let result = subscribe_intern(key_c, component_c, callback as *mut c_void, std::ptr::null_mut());
-        assert_eq!(result.is_ok(), true);
-        let callback_called = unsafe { CALLBACK_CALLED };
-        assert_eq!(callback_called, false);
-        let set_value = 42;
-        let result = set_int(key_c, set_value);
-        assert_eq!(result, 0);
-        let callback_called = unsafe { CALLBACK_CALLED };
-        assert_eq!(callback_called, true);
-
-        let result = unsubscribe_intern(key_c, component_c);
-        assert_eq!(result.is_ok(), true);
 
+        let mut count = 0;
+        assert_eq!(get_int(c"counter".as_ptr(), &mut count), 0);
+        assert_eq!(count, 8 * 200);
+        assert_eq!(size(), 9);
     }
 
+    // cargo test -p blackboard --release -- --ignored --nocapture bench_get_set
     #[rstest]
     #[serial]
-    #[test_log::test]
-    fn test_subscribe_with_user_data(startup: c_int) {
+    #[ignore]
+    fn bench_get_set(startup: c_int) {
         assert_eq!(startup, 0);
-
-        
-        let (sender, receiver): (mpsc::Sender<String>, mpsc::Receiver<String>) = mpsc::channel();
-        let sender_ptr = Box::into_raw(Box::new(sender));
-
-        extern "C" fn callback(key: *const c_char, user_data: *mut c_void) -> c_int {
-            let key = unsafe { CStr::from_ptr(key).to_str().unwrap() };
-            debug!("Callback called for key: {}", key);
-
-            if user_data.is_null() {
-                error!("User data is null");
-                return -1;
+        const CALLS: u32 = 200_000;
+        for key in 0..1000 {
+            let key = CString::new(format!("bench/{}", key)).unwrap();
+            assert_eq!(set_double(key.as_ptr(), 1.0), 0);
+        }
+        let measure = |threads: u32| {
+            let started = Instant::now();
+            let readers: Vec<_> = (0..threads)
+                .map(|thread| {
+                    std::thread::spawn(move || {
+                        let key = CString::new(format!("bench/{}", thread)).unwrap();
+                        let mut value = 0.0;
+                        for i in 0..CALLS {
+                            assert_eq!(set_double(key.as_ptr(), i as f64), 0);
+                            assert_eq!(get_double(key.as_ptr(), &mut value), 0);
+                        }
+                    })
+                })
+                .collect();
+            for reader in readers {
+                reader.join().unwrap();
             }
+            started.elapsed().as_nanos() / (2 * CALLS as u128)
+        };
+        println!("1 thread: {} ns per get or set", measure(1));
+        println!("8 threads: {} ns per get or set", measure(8));
 
-            let sender = unsafe { &*(user_data as *mut mpsc::Sender<String>) };
-
-            sender.send(key.to_string()).unwrap_or_else(|e| {
-                error!("Failed to send key: {}", key);
+        // a schema of all keys does not stop the others
+        let schema = std::thread::spawn(|| {
+            for _ in 0..50 {
+                assert!(as_json_schema(std::ptr::null_mut()) > 0);
             }
-            );
-            0
-        }
-        
-        let key = "int_key\0";
-        let key_c = key.as_ptr() as *const c_char;
-        let component = "component\0";
-        let component_c = component.as_ptr() as *const c_char;
-
-        let result = subscribe_intern(key_c, component_c, callback as *mut c_void, sender_ptr as *mut c_void);
-        assert_eq!(result.is_ok(), true);
-
-        let set_value = 42;
-        let result = set_int(key_c, set_value);
-        assert_eq!(result, 0);
-
-        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).is_ok(), true);
-
-        let set_value = 43;
-        let result = set_int(key_c, set_value);
-        assert_eq!(result, 0);
-
-        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).is_ok(), true);
-
-        let set_value = 60;
-        let result = set_int(key_c, set_value);
-        assert_eq!(result, 0);
+        });
+        println!("8 threads with as_json_schema: {} ns per get or set", measure(8));
+        schema.join().unwrap();
+    }
 
-        assert_eq!(receiver.recv_timeout(Duration::from_secs(1)).is_ok(), true);
-        
-        let result = unsubscribe_intern(key_c, component_c);
-        assert_eq!(result.is_ok(), true);
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_get_last_error(startup: c_int) {
+        assert_eq!(startup, 0);
+        let key = "last_error_missing\0";
+        let key_c = key.as_ptr() as *const c_char;
+        let mut value = 0;
+        assert_eq!(get_int(key_c, &mut value), RtStatus::KeyNotFound.code());
+
+        let size = get_last_error(std::ptr::null_mut(), 0);
+        let mut buffer = vec![0u8; size as usize];
+        assert_eq!(get_last_error(buffer.as_mut_ptr() as *mut c_char, size), size);
+        let message = CStr::from_bytes_until_nul(&buffer).unwrap().to_str().unwrap();
+        assert_eq!(message, "Key not found: last_error_missing");
+
+        // a too small buffer receives a truncated, null terminated message
+        let mut small = [1u8; 4];
+        get_last_error(small.as_mut_ptr() as *mut c_char, small.len() as c_int);
+        assert_eq!(&small, b"Key\0");
     }
 
     #[rstest]
@@ -1338,8 +6931,39 @@ mod tests {
         let mut value =0;
         let result = get_int(key_c, &mut value);
 
-        assert_eq!(result, -1);
+        assert_eq!(result, RtStatus::TypeMismatch.code());
 
     }
 
+    #[cfg(feature = "shm")]
+    #[serial]
+    #[test_log::test]
+    fn test_shared_memory() {
+        use interfaces::shared_memory::SharedBlackboard;
+        let name = format!("/rtime-blackboard-test-{}", std::process::id());
+        let attributes = format!(
+            "[{{key: shared_memory, value: {}}}, {{key: speed, value: 2.5}}]\0",
+            name
+        );
+        let _ = stop();
+        let caps = interfaces::capabilities::Capabilities::new();
+        start_server(caps.inner(), attributes.as_ptr() as *const c_char).unwrap();
+
+        let shared = SharedBlackboard::open(&name).unwrap();
+        assert_eq!(shared.slots(), SHARED_MEMORY_SLOTS);
+        assert_eq!(shared.read("speed").unwrap().0, TypedBlackboardValue::Float(2.5));
+        let key = c"count";
+        assert_eq!(set_int(key.as_ptr(), 3), 0);
+        assert_eq!(shared.read("count"), Some((TypedBlackboardValue::Int(3), 1)));
+        let text = c"three";
+        assert_eq!(set_string(key.as_ptr(), text.as_ptr()), 0);
+        assert!(shared.read("count").is_none());
+        assert_eq!(delete(c"speed".as_ptr()), 0);
+        assert!(shared.read("speed").is_none());
+
+        // the region is removed at stop
+        assert_eq!(stop(), 0);
+        drop(shared);
+        assert!(SharedBlackboard::open(&name).is_err());
+    }
 }
@@ -0,0 +1,61 @@
+// `cargo rtime`, creates plugin crates for rtime. `cargo rtime new-skill <name>` writes a skill
+// and `cargo rtime new-service <name>` a service, both ready to build, load and test.
+mod template;
+
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use template::Kind;
+
+// cargo runs `cargo-rtime rtime <args>` for `cargo rtime <args>`
+#[derive(Parser, Debug)]
+#[command(name = "cargo", bin_name = "cargo")]
+enum Cargo {
+    /// Creates plugin crates for rtime
+    #[command(subcommand)]
+    Rtime(Command),
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a skill, run by the loader on demand
+    NewSkill(New),
+    /// Create a service, started by the loader and running until shutdown
+    NewService(New),
+}
+
+#[derive(Args, Debug)]
+struct New {
+    /// Name of the library, lowercase letters, digits and underscores
+    name: String,
+    /// Directory of the crate, `./<name>` by default
+    #[arg(long)]
+    path: Option<PathBuf>,
+    /// Checkout of rtime the crate depends on, the one this tool was built from by default
+    #[arg(long)]
+    rtime: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let Cargo::Rtime(command) = Cargo::parse();
+    let (kind, new) = match command {
+        Command::NewSkill(new) => (Kind::Skill, new),
+        Command::NewService(new) => (Kind::Service, new),
+    };
+    let path = new.path.unwrap_or_else(|| PathBuf::from(&new.name));
+    let rtime = new.rtime.unwrap_or_else(template::default_rtime);
+    match template::create(kind, &new.name, &path, &rtime) {
+        Ok(()) => {
+            println!("Created {} '{}' in {}", kind, new.name, path.display());
+            println!(
+                "Build it with `cargo build` and load it with `plugin_dirs: [{}]`",
+                path.join("target/debug").display()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// The files of a new plugin crate, the templates with `{{name}}` and `{{rtime}}` replaced.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const CARGO_TOML: &str = include_str!("../templates/Cargo.toml.tmpl");
+const GITIGNORE: &str = include_str!("../templates/gitignore.tmpl");
+const SKILL: &str = include_str!("../templates/skill.rs.tmpl");
+const SERVICE: &str = include_str!("../templates/service.rs.tmpl");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Skill,
+    Service,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Skill => write!(f, "skill"),
+            Kind::Service => write!(f, "service"),
+        }
+    }
+}
+
+/// The checkout of rtime this tool was built from.
+pub fn default_rtime() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
+// the name is used for the crate, the library file and the capabilities, so it has to be an
+// identifier the loader finds the library by
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        return Err(format!(
+            "Invalid name '{}', use lowercase letters, digits and underscores",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// The files of the crate by their path in it.
+pub fn files(kind: Kind, name: &str, rtime: &Path) -> Result<Vec<(&'static str, String)>, String> {
+    check_name(name)?;
+    // cargo reads the path of the dependencies with forward slashes on every platform
+    let rtime = rtime.display().to_string().replace('\\', "/");
+    let fill = |template: &str| template.replace("{{name}}", name).replace("{{rtime}}", &rtime);
+    let lib = match kind {
+        Kind::Skill => SKILL,
+        Kind::Service => SERVICE,
+    };
+    Ok(vec![
+        ("Cargo.toml", fill(CARGO_TOML)),
+        (".gitignore", fill(GITIGNORE)),
+        ("src/lib.rs", fill(lib)),
+    ])
+}
+
+/// Writes the crate to `path`, which may not exist yet or has to be empty.
+pub fn create(kind: Kind, name: &str, path: &Path, rtime: &Path) -> Result<(), String> {
+    let rtime = rtime
+        .canonicalize()
+        .map_err(|e| format!("rtime is not found at {}: {}", rtime.display(), e))?;
+    if !rtime.join("interfaces").is_dir() {
+        return Err(format!("{} is no checkout of rtime", rtime.display()));
+    }
+    let files = files(kind, name, &rtime)?;
+    if path.read_dir().is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!("{} is not empty", path.display()));
+    }
+    for (file, content) in files {
+        let file = path.join(file);
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Can not create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&file, content)
+            .map_err(|e| format!("Can not write {}: {}", file.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files() {
+        for kind in [Kind::Skill, Kind::Service] {
+            let files = files(kind, "gripper", Path::new("/opt/rtime")).unwrap();
+            for (_, content) in &files {
+                assert!(!content.contains("{{"));
+            }
+            let (_, manifest) = &files[0];
+            assert!(manifest.contains("name = \"gripper\""));
+            assert!(manifest.contains("path = \"/opt/rtime/interfaces\""));
+            let (_, lib) = &files[2];
+            assert!(lib.contains(&format!("library_type = \"{:?}\"", kind)));
+            assert!(lib.contains("gripper_health") == (kind == Kind::Service));
+        }
+    }
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("gripper_2").is_ok());
+        for name in ["", "Gripper", "2gripper", "gripper-2", "../gripper"] {
+            assert!(check_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_create() {
+        let dir = std::env::temp_dir().join(format!("cargo-rtime-{}", std::process::id()));
+        let path = dir.join("gripper");
+        create(Kind::Skill, "gripper", &path, &default_rtime()).unwrap();
+        let lib = std::fs::read_to_string(path.join("src/lib.rs")).unwrap();
+        assert!(lib.contains("pub extern \"C\" fn run("));
+
+        // nothing is overwritten
+        let result = create(Kind::Service, "gripper", &path, &default_rtime());
+        assert_eq!(result, Err(format!("{} is not empty", path.display())));
+        assert!(create(Kind::Skill, "gripper", &dir.join("other"), &dir).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
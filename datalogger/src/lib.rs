@@ -0,0 +1,293 @@
+// Appends the changes of blackboard keys to a csv, jsonl or sqlite log, so runs can be analyzed
+// offline. The log is rotated by size or age, see `writer`.
+mod writer;
+
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue, parse_attributes};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{error, info, warn};
+use serde_json::json;
+use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use writer::{Format, Log, Record, Rotation};
+
+struct Config {
+    file: PathBuf,
+    format: Option<Format>, // from the extension of `file` if not given
+    keys: Vec<String>,      // logged keys, or prefixes ending with `*`
+    rotation: Rotation,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            file: PathBuf::from("datalogger.csv"),
+            format: None,
+            keys: Vec::new(),
+            rotation: Rotation {
+                max_bytes: 0,
+                max_age: None,
+                keep: 5,
+            },
+        }
+    }
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("file", BlackboardValue::String(value)) => config.file = PathBuf::from(value),
+                ("format", BlackboardValue::String(value)) => {
+                    config.format = Some(Format::parse(value).ok_or_else(|| {
+                        invalid(format!(
+                            "Unknown format {}, use csv, jsonl or sqlite",
+                            value
+                        ))
+                    })?)
+                }
+                ("keys", BlackboardValue::String(key)) => config.keys = vec![key.clone()],
+                ("keys", BlackboardValue::Array(keys)) => {
+                    config.keys = keys
+                        .iter()
+                        .filter_map(|key| match key {
+                            BlackboardValue::String(key) => Some(key.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                }
+                ("rotate_bytes", BlackboardValue::Int(value)) => {
+                    config.rotation.max_bytes = u64::try_from(*value)
+                        .map_err(|_| invalid(format!("Invalid rotate_bytes {}", value)))?
+                }
+                ("rotate_bytes", BlackboardValue::Int64(value)) => {
+                    config.rotation.max_bytes = u64::try_from(*value)
+                        .map_err(|_| invalid(format!("Invalid rotate_bytes {}", value)))?
+                }
+                ("rotate_seconds", BlackboardValue::Int(value)) => {
+                    config.rotation.max_age = match u64::try_from(*value) {
+                        Ok(0) => None,
+                        Ok(seconds) => Some(Duration::from_secs(seconds)),
+                        Err(_) => return Err(invalid(format!("Invalid rotate_seconds {}", value))),
+                    }
+                }
+                ("keep", BlackboardValue::Int(value)) => {
+                    config.rotation.keep = usize::try_from(*value)
+                        .map_err(|_| invalid(format!("Invalid keep {}", value)))?
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    fn format(&self) -> Format {
+        self.format.unwrap_or_else(|| Format::of_path(&self.file))
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+struct Logger {
+    file: PathBuf,
+    records: AtomicU64,
+    failing: AtomicBool, // the last write failed
+}
+
+impl Logger {
+    // writes the queued records in batches until the subscriptions are dropped
+    fn run(&self, mut log: Log, changes: mpsc::Receiver<Record>) {
+        while let Ok(record) = changes.recv() {
+            let mut batch = vec![record];
+            batch.extend(changes.try_iter());
+            match log.write(&batch) {
+                Ok(()) => {
+                    self.records.fetch_add(batch.len() as u64, Ordering::SeqCst);
+                    self.failing.store(false, Ordering::SeqCst);
+                }
+                Err(e) => {
+                    // the records are lost, a full disk should not stop the robot
+                    if !self.failing.swap(true, Ordering::SeqCst) {
+                        warn!("{}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct LoggerState {
+    logger: Arc<Logger>,
+    subscriptions: Vec<Subscription>,
+    writer: JoinHandle<()>,
+}
+
+static LOGGER_STATE: Mutex<Option<LoggerState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "datalogger",
+    summary = "logs blackboard changes to csv, jsonl or sqlite files",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        datalogger_start = start: "i32(caps,cstr)",
+        datalogger_stop = stop: "i32()",
+        datalogger_health = health: "i32()",
+        datalogger_state = state: "i32()",
+        datalogger_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_logger(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = LOGGER_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Datalogger is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    if config.keys.is_empty() {
+        return Err(RtError::new(RtStatus::InvalidArgument, "No keys to log"));
+    }
+    let log = Log::open(&config.file, config.format(), config.rotation.clone())?;
+    let client = Arc::new(BlackboardClient::new(
+        interfaces::capabilities::Capabilities::from_raw(caps),
+    ));
+
+    // values are read in the notification, later changes would overwrite them
+    let (sender, changes) = mpsc::channel::<Record>();
+    let mut keys = config.keys.clone();
+    keys.sort();
+    keys.dedup();
+    let subscriptions = keys
+        .iter()
+        .map(|key| {
+            let sender = sender.clone();
+            let reader = client.clone();
+            client.subscribe(key, "datalogger", move |changed| {
+                let value = match reader.get_value(changed) {
+                    Ok(value) => Some(value),
+                    Err(e) if e.status == RtStatus::KeyNotFound => None,
+                    Err(e) => {
+                        warn!("Can not read changed key {}: {}", changed, e);
+                        return;
+                    }
+                };
+                let _ = sender.send(Record {
+                    timestamp: unix_millis(),
+                    key: changed.to_string(),
+                    value,
+                });
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    drop(sender);
+
+    let logger = Arc::new(Logger {
+        file: log.path().to_path_buf(),
+        records: AtomicU64::new(0),
+        failing: AtomicBool::new(false),
+    });
+    let writing = logger.clone();
+    info!("Logging {:?} to {}", keys, logger.file.display());
+    *state = Some(LoggerState {
+        logger,
+        subscriptions,
+        writer: std::thread::spawn(move || writing.run(log, changes)),
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "datalogger");
+    match LIFECYCLE.started(catch_panic(|| start_logger(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting datalogger: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_logger() -> Result<(), RtError> {
+    let state = LOGGER_STATE.lock().unwrap().take();
+    let state =
+        state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Datalogger is not running"))?;
+    // the writer ends after the queued records
+    drop(state.subscriptions);
+    let _ = state.writer.join();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_logger)) {
+        Ok(()) => {
+            info!("Datalogger stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping datalogger: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_ERROR` while the records can not be written, like on a full disk.
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match LOGGER_STATE.lock().unwrap().as_ref() {
+        Some(state) if state.logger.failing.load(Ordering::SeqCst) => RtError::new(
+            RtStatus::Error,
+            format!("Can not write {}", state.logger.file.display()),
+        )
+        .record(),
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the datalogger, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the log file and the number of records written as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match LOGGER_STATE.lock().unwrap().as_ref() {
+        Some(state) => json!({
+            "file": state.logger.file.display().to_string(),
+            "records": state.logger.records.load(Ordering::SeqCst),
+            "failing": state.logger.failing.load(Ordering::SeqCst),
+        }),
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
@@ -0,0 +1,356 @@
+// Log files of the datalogger. Every record has a timestamp in milliseconds since the unix epoch,
+// the key, the type and the value as json, a removed key has neither type nor value. A full log
+// is renamed to `<name>.1.<extension>`, older logs move up a number until `keep` is reached.
+use interfaces::blackboard::TypedBlackboardValue;
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Jsonl,
+    Sqlite,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(Format::Csv),
+            "jsonl" => Some(Format::Jsonl),
+            "sqlite" => Some(Format::Sqlite),
+            _ => None,
+        }
+    }
+
+    /// The format a file name suggests, csv if it suggests none.
+    pub fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("jsonl") => Format::Jsonl,
+            Some("db" | "sqlite" | "sqlite3") => Format::Sqlite,
+            _ => Format::Csv,
+        }
+    }
+}
+
+pub struct Record {
+    pub timestamp: u64,
+    pub key: String,
+    pub value: Option<TypedBlackboardValue>,
+}
+
+impl Record {
+    // the type and the value of the typed json representation
+    fn typed(&self) -> (Value, Value) {
+        match self
+            .value
+            .as_ref()
+            .and_then(|value| serde_json::to_value(value).ok())
+        {
+            Some(Value::Object(mut typed)) => (
+                typed.remove("type").unwrap_or(Value::Null),
+                typed.remove("value").unwrap_or(Value::Null),
+            ),
+            _ => (Value::Null, Value::Null),
+        }
+    }
+}
+
+/// When a log is rotated, a zero limit never rotates.
+#[derive(Debug, Clone, Default)]
+pub struct Rotation {
+    pub max_bytes: u64,
+    pub max_age: Option<Duration>,
+    pub keep: usize, // rotated logs kept, older ones are removed
+}
+
+enum Output {
+    Csv(Box<csv::Writer<File>>),
+    Jsonl(BufWriter<File>),
+    Sqlite(rusqlite::Connection),
+}
+
+impl Output {
+    fn open(path: &Path, format: Format) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| format!("Can not open {}: {}", path.display(), e);
+        if let Some(directory) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(directory).map_err(|e| error(&e))?;
+        }
+        if format == Format::Sqlite {
+            let connection = rusqlite::Connection::open(path).map_err(|e| error(&e))?;
+            connection
+                .execute_batch(
+                    "CREATE TABLE IF NOT EXISTS samples (
+                        timestamp INTEGER NOT NULL, key TEXT NOT NULL, type TEXT, value TEXT
+                    );
+                    CREATE INDEX IF NOT EXISTS samples_key ON samples (key, timestamp);",
+                )
+                .map_err(|e| error(&e))?;
+            return Ok(Output::Sqlite(connection));
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| error(&e))?;
+        let empty = file.metadata().map_err(|e| error(&e))?.len() == 0;
+        Ok(match format {
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(file);
+                if empty {
+                    writer
+                        .write_record(["timestamp", "key", "type", "value"])
+                        .map_err(|e| error(&e))?;
+                }
+                Output::Csv(Box::new(writer))
+            }
+            _ => Output::Jsonl(BufWriter::new(file)),
+        })
+    }
+
+    fn write(&mut self, records: &[Record]) -> Result<(), String> {
+        match self {
+            Output::Csv(writer) => {
+                for record in records {
+                    let (type_name, value) = record.typed();
+                    let type_name = type_name.as_str().unwrap_or_default().to_string();
+                    let value = match value {
+                        Value::Null => String::new(),
+                        value => value.to_string(),
+                    };
+                    let timestamp = record.timestamp.to_string();
+                    writer
+                        .write_record([&timestamp, &record.key, &type_name, &value])
+                        .map_err(|e| e.to_string())?;
+                }
+                writer.flush().map_err(|e| e.to_string())
+            }
+            Output::Jsonl(writer) => {
+                for record in records {
+                    let (type_name, value) = record.typed();
+                    let line = json!({
+                        "timestamp": record.timestamp,
+                        "key": record.key,
+                        "type": type_name,
+                        "value": value,
+                    });
+                    writeln!(writer, "{}", line).map_err(|e| e.to_string())?;
+                }
+                writer.flush().map_err(|e| e.to_string())
+            }
+            Output::Sqlite(connection) => {
+                // one transaction per batch, sqlite syncs on every commit
+                let transaction = connection.transaction().map_err(|e| e.to_string())?;
+                {
+                    let mut insert = transaction
+                        .prepare_cached(
+                            "INSERT INTO samples (timestamp, key, type, value) VALUES (?1, ?2, ?3, ?4)",
+                        )
+                        .map_err(|e| e.to_string())?;
+                    for record in records {
+                        let (type_name, value) = record.typed();
+                        let value = (!value.is_null()).then(|| value.to_string());
+                        insert
+                            .execute(rusqlite::params![
+                                record.timestamp as i64,
+                                record.key,
+                                type_name.as_str(),
+                                value
+                            ])
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+                transaction.commit().map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+pub struct Log {
+    path: PathBuf,
+    format: Format,
+    rotation: Rotation,
+    output: Option<Output>,
+    opened: Instant,
+}
+
+impl Log {
+    pub fn open(path: &Path, format: Format, rotation: Rotation) -> Result<Self, String> {
+        Ok(Log {
+            path: path.to_path_buf(),
+            format,
+            rotation,
+            output: Some(Output::open(path, format)?),
+            opened: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the records and rotates the log once it is full.
+    pub fn write(&mut self, records: &[Record]) -> Result<(), String> {
+        let output = match self.output.as_mut() {
+            Some(output) => output,
+            // a failed rotation is retried with the next records
+            None => self.output.insert(Output::open(&self.path, self.format)?),
+        };
+        output
+            .write(records)
+            .map_err(|e| format!("Can not write {}: {}", self.path.display(), e))?;
+        if self.full() {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn full(&self) -> bool {
+        let too_large = self.rotation.max_bytes > 0
+            && fs::metadata(&self.path).is_ok_and(|file| file.len() >= self.rotation.max_bytes);
+        let too_old = self
+            .rotation
+            .max_age
+            .is_some_and(|max_age| self.opened.elapsed() >= max_age);
+        too_large || too_old
+    }
+
+    /// The path of the log rotated `number` times, `run.csv` becomes `run.2.csv`.
+    pub fn rotated(&self, number: usize) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, number, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, number),
+        };
+        self.path.with_file_name(name)
+    }
+
+    fn rotate(&mut self) -> Result<(), String> {
+        // closed before renaming, sqlite keeps its journal next to the file
+        drop(self.output.take());
+        let rename = |from: &Path, to: &Path| {
+            fs::rename(from, to).map_err(|e| format!("Can not rotate {}: {}", from.display(), e))
+        };
+        if self.rotation.keep == 0 {
+            fs::remove_file(&self.path)
+                .map_err(|e| format!("Can not remove {}: {}", self.path.display(), e))?;
+        } else {
+            let _ = fs::remove_file(self.rotated(self.rotation.keep));
+            for number in (1..self.rotation.keep).rev() {
+                let from = self.rotated(number);
+                if from.exists() {
+                    rename(&from, &self.rotated(number + 1))?;
+                }
+            }
+            rename(&self.path, &self.rotated(1))?;
+        }
+        self.output = Some(Output::open(&self.path, self.format)?);
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("datalogger_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn record(timestamp: u64, key: &str, value: Option<TypedBlackboardValue>) -> Record {
+        Record {
+            timestamp,
+            key: key.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        let directory = directory("formats");
+        let records = [
+            record(1, "speed", Some(TypedBlackboardValue::Double(0.5))),
+            record(
+                2,
+                "name",
+                Some(TypedBlackboardValue::String("a,b".to_string())),
+            ),
+            record(3, "speed", None),
+        ];
+        for name in ["run.csv", "run.jsonl", "run.db"] {
+            let path = directory.join(name);
+            let mut log = Log::open(&path, Format::of_path(&path), Rotation::default()).unwrap();
+            log.write(&records).unwrap();
+        }
+
+        assert_eq!(
+            fs::read_to_string(directory.join("run.csv")).unwrap(),
+            "timestamp,key,type,value\n1,speed,double,0.5\n2,name,string,\"\"\"a,b\"\"\"\n3,speed,,\n"
+        );
+        let lines: Vec<Value> = fs::read_to_string(directory.join("run.jsonl"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines[1],
+            json!({"timestamp": 2, "key": "name", "type": "string", "value": "a,b"})
+        );
+        assert_eq!(
+            lines[2],
+            json!({"timestamp": 3, "key": "speed", "type": null, "value": null})
+        );
+        let connection = rusqlite::Connection::open(directory.join("run.db")).unwrap();
+        let rows: Vec<(i64, Option<String>, Option<String>)> = connection
+            .prepare("SELECT timestamp, type, value FROM samples WHERE key = 'speed'")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (1, Some("double".to_string()), Some("0.5".to_string())),
+                (3, None, None)
+            ]
+        );
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let directory = directory("rotation");
+        let path = directory.join("run.jsonl");
+        let rotation = Rotation {
+            max_bytes: 1,
+            max_age: None,
+            keep: 2,
+        };
+        let mut log = Log::open(&path, Format::Jsonl, rotation).unwrap();
+        for timestamp in 1..=4 {
+            log.write(&[record(timestamp, "tick", None)]).unwrap();
+        }
+        assert_eq!(log.rotated(2), directory.join("run.2.jsonl"));
+
+        // the current log is empty, the oldest rotated log was removed
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        let first = |path: PathBuf| {
+            let log = fs::read_to_string(path).unwrap();
+            serde_json::from_str::<Value>(log.lines().next().unwrap()).unwrap()["timestamp"].clone()
+        };
+        assert_eq!(first(directory.join("run.1.jsonl")), json!(4));
+        assert_eq!(first(directory.join("run.2.jsonl")), json!(3));
+        assert!(!directory.join("run.3.jsonl").exists());
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,45 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::testing::Host;
+
+#[test]
+fn test_datalogger() {
+    let dir = std::env::temp_dir().join(format!("rtime-datalogger-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let file = dir.join("run.jsonl");
+    let attributes = vec![
+        BlackboardEntry {
+            key: "file".to_string(),
+            value: BlackboardValue::String(file.display().to_string()),
+        },
+        BlackboardEntry {
+            key: "keys".to_string(),
+            value: BlackboardValue::Array(vec![BlackboardValue::String("robot/*".into())]),
+        },
+    ];
+    let host = Host::start(&[("datalogger", attributes)]).unwrap();
+    let client = host.client();
+    client.set_i32("robot/speed", 3).unwrap();
+    client.set_i32("other", 1).unwrap();
+    client.set_string("robot/state", "docking").unwrap();
+
+    // stopping writes the queued records
+    assert!(host.stop().is_empty());
+    let records: Vec<serde_json::Value> = std::fs::read_to_string(&file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let logged: Vec<(&str, &serde_json::Value)> = records
+        .iter()
+        .map(|record| (record["key"].as_str().unwrap(), &record["value"]))
+        .collect();
+    assert_eq!(
+        logged,
+        vec![
+            ("robot/speed", &serde_json::json!(3)),
+            ("robot/state", &serde_json::json!("docking"))
+        ]
+    );
+    assert!(records[0]["timestamp"].as_u64().unwrap() > 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
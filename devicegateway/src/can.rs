@@ -0,0 +1,144 @@
+// Raw SocketCAN sockets. Ids above 0x7ff are sent as extended frames, remote and error frames
+// are ignored.
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+/// A CAN id given as hex `0x120` or decimal.
+pub fn parse_id(text: &str) -> Option<u32> {
+    let id = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    (id <= libc::CAN_EFF_MASK).then_some(id)
+}
+
+/// Opens a raw socket bound to a CAN interface like `can0`.
+pub fn open(interface: &str) -> io::Result<OwnedFd> {
+    let name =
+        CString::new(interface).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe {
+        libc::socket(
+            libc::PF_CAN,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::CAN_RAW,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut address: libc::sockaddr_can = unsafe { std::mem::zeroed() };
+    address.can_family = libc::AF_CAN as libc::sa_family_t;
+    address.can_ifindex = index as libc::c_int;
+    let bound = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &address as *const libc::sockaddr_can as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+        )
+    };
+    if bound != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fd)
+}
+
+/// The frame of an id and up to 8 bytes of data.
+pub fn frame(id: u32, data: &[u8]) -> libc::can_frame {
+    // the padding of `can_frame` is private
+    let mut frame: libc::can_frame = unsafe { std::mem::zeroed() };
+    frame.can_id = match id > libc::CAN_SFF_MASK {
+        true => id | libc::CAN_EFF_FLAG,
+        false => id,
+    };
+    let len = data.len().min(frame.data.len());
+    frame.can_dlc = len as u8;
+    frame.data[..len].copy_from_slice(&data[..len]);
+    frame
+}
+
+/// Id and data of a received data frame.
+pub fn parse_frame(frame: &libc::can_frame) -> Option<(u32, &[u8])> {
+    if frame.can_id & (libc::CAN_RTR_FLAG | libc::CAN_ERR_FLAG) != 0 {
+        return None;
+    }
+    let id = match frame.can_id & libc::CAN_EFF_FLAG != 0 {
+        true => frame.can_id & libc::CAN_EFF_MASK,
+        false => frame.can_id & libc::CAN_SFF_MASK,
+    };
+    let len = (frame.can_dlc as usize).min(frame.data.len());
+    Some((id, &frame.data[..len]))
+}
+
+/// Reads one frame, blocks until there is one.
+pub fn read(fd: &OwnedFd) -> io::Result<libc::can_frame> {
+    let mut frame: libc::can_frame = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::can_frame>();
+    let read = unsafe {
+        libc::read(
+            fd.as_raw_fd(),
+            &mut frame as *mut libc::can_frame as *mut libc::c_void,
+            size,
+        )
+    };
+    match read {
+        read if read < 0 => Err(io::Error::last_os_error()),
+        read if read as usize != size => {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "Incomplete CAN frame"))
+        }
+        _ => Ok(frame),
+    }
+}
+
+pub fn write(fd: &OwnedFd, frame: &libc::can_frame) -> io::Result<()> {
+    let size = std::mem::size_of::<libc::can_frame>();
+    let written = unsafe {
+        libc::write(
+            fd.as_raw_fd(),
+            frame as *const libc::can_frame as *const libc::c_void,
+            size,
+        )
+    };
+    match written {
+        written if written < 0 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_id() {
+        assert_eq!(parse_id("0x120"), Some(0x120));
+        assert_eq!(parse_id("288"), Some(0x120));
+        assert_eq!(parse_id("0x20000000"), None);
+        assert_eq!(parse_id("speed"), None);
+    }
+
+    #[test]
+    fn test_frame() {
+        let sent = frame(0x120, &[1, 2, 3]);
+        assert_eq!(sent.can_dlc, 3);
+        assert_eq!(parse_frame(&sent), Some((0x120, &[1u8, 2, 3][..])));
+
+        let extended = frame(0x18ff50e5, &[0; 10]);
+        assert_eq!(extended.can_id, 0x18ff50e5 | libc::CAN_EFF_FLAG);
+        assert_eq!(parse_frame(&extended), Some((0x18ff50e5, &[0u8; 8][..])));
+
+        let mut remote = frame(0x120, &[]);
+        remote.can_id |= libc::CAN_RTR_FLAG;
+        assert_eq!(parse_frame(&remote), None);
+    }
+
+    #[test]
+    fn test_open() {
+        assert!(open("does-not-exist").is_err());
+    }
+}
@@ -0,0 +1,606 @@
+// Connects serial ports and SocketCAN interfaces to blackboard keys, so a device is integrated by
+// configuration instead of a plugin of its own. `devices` names the ports, `mappings` map fields
+// of CAN frames or lines `<name>=<value>` of serial ports to keys, see `mapping`. Lost devices
+// are opened again.
+mod can;
+mod mapping;
+mod serial;
+
+use interfaces::blackboard::{
+    BlackboardEntry, BlackboardValue, TypedBlackboardValue, parse_attributes,
+};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{debug, error, info, warn};
+use mapping::{Field, Mapping};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// between two attempts to open a device
+const REOPEN: Duration = Duration::from_secs(1);
+// how long a read waits, bounds how long stopping waits for the devices
+const POLL: Duration = Duration::from_millis(100);
+
+/// A device as configured, either `serial` with `baud` or `can`.
+#[derive(Debug, Clone, Deserialize)]
+struct DeviceConfig {
+    name: String,
+    serial: Option<String>, // path of the port
+    baud: Option<u32>,
+    can: Option<String>, // the interface
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Port {
+    Serial { path: String, baud: u32 },
+    Can { interface: String },
+}
+
+impl DeviceConfig {
+    fn port(&self) -> Result<Port, String> {
+        match (&self.serial, &self.can) {
+            (Some(path), None) => Ok(Port::Serial {
+                path: path.clone(),
+                baud: self.baud.unwrap_or(115200),
+            }),
+            (None, Some(interface)) => Ok(Port::Can {
+                interface: interface.clone(),
+            }),
+            _ => Err(format!("{} needs either serial or can", self.name)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Config {
+    devices: Vec<(String, Port)>,
+    mappings: Vec<Mapping>,
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("devices", value) => {
+                    let devices: Vec<DeviceConfig> = serde_json::to_value(value)
+                        .and_then(serde_json::from_value)
+                        .map_err(|e| invalid(format!("Invalid devices: {}", e)))?;
+                    config.devices = devices
+                        .iter()
+                        .map(|device| Ok((device.name.clone(), device.port()?)))
+                        .collect::<Result<_, String>>()
+                        .map_err(invalid)?;
+                }
+                ("mappings", BlackboardValue::String(text)) => {
+                    config.mappings = vec![Mapping::parse(text).map_err(invalid)?]
+                }
+                ("mappings", BlackboardValue::Array(texts)) => {
+                    config.mappings = texts
+                        .iter()
+                        .map(|text| match text {
+                            BlackboardValue::String(text) => Mapping::parse(text),
+                            _ => Err(format!("Mapping {:?} is no string", text)),
+                        })
+                        .collect::<Result<_, String>>()
+                        .map_err(invalid)?;
+                }
+                _ => {}
+            }
+        }
+        for mapping in &config.mappings {
+            config.check(mapping).map_err(invalid)?;
+        }
+        Ok(config)
+    }
+
+    // whether the device of a mapping is configured and can carry its field
+    fn check(&self, mapping: &Mapping) -> Result<(), String> {
+        let port = self
+            .devices
+            .iter()
+            .find(|(name, _)| *name == mapping.device)
+            .map(|(_, port)| port)
+            .ok_or_else(|| format!("Unknown device {} of {}", mapping.device, mapping.key))?;
+        if let Port::Can { .. } = port {
+            if can::parse_id(&mapping.address).is_none() {
+                return Err(format!("Invalid CAN id {} of {}", mapping.address, mapping.key));
+            }
+            if mapping.field == Field::Str || mapping.offset + mapping.field.size() > 8 {
+                return Err(format!("{} does not fit into a CAN frame", mapping.key));
+            }
+        }
+        Ok(())
+    }
+}
+
+// waits until a device has something to read
+fn readable(fd: &OwnedFd, timeout: Duration) -> io::Result<bool> {
+    let mut poll = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as c_int) } {
+        ready if ready < 0 => match io::Error::last_os_error() {
+            e if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+            e => Err(e),
+        },
+        ready => Ok(ready > 0),
+    }
+}
+
+struct Device {
+    name: String,
+    port: Port,
+    mappings: Vec<Mapping>,
+    link: Mutex<Option<Arc<OwnedFd>>>, // None while the device is not open
+    // data last sent per CAN id, a write only changes the bytes of its field
+    frames: Mutex<HashMap<u32, Vec<u8>>>,
+    received: AtomicU64,
+}
+
+impl Device {
+    fn new(name: String, port: Port, mappings: Vec<Mapping>) -> Self {
+        let mut frames: HashMap<u32, Vec<u8>> = HashMap::new();
+        if let Port::Can { .. } = port {
+            for mapping in mappings.iter().filter(|mapping| mapping.writes()) {
+                if let Some(id) = can::parse_id(&mapping.address) {
+                    let data = frames.entry(id).or_default();
+                    let len = data.len().max(mapping.offset + mapping.field.size());
+                    data.resize(len, 0);
+                }
+            }
+        }
+        Device {
+            name,
+            port,
+            mappings,
+            link: Mutex::new(None),
+            frames: Mutex::new(frames),
+            received: AtomicU64::new(0),
+        }
+    }
+
+    fn open(&self) -> io::Result<Arc<OwnedFd>> {
+        let fd = match &self.port {
+            Port::Serial { path, baud } => serial::open(path, *baud)?,
+            Port::Can { interface } => can::open(interface)?,
+        };
+        let fd = Arc::new(fd);
+        *self.link.lock().unwrap() = Some(fd.clone());
+        Ok(fd)
+    }
+
+    fn close(&self) {
+        *self.link.lock().unwrap() = None;
+    }
+
+    // sends a value of a key to the device
+    fn send(&self, mapping: &Mapping, value: &TypedBlackboardValue) -> Result<(), String> {
+        let link = self.link.lock().unwrap().clone();
+        let fd = link.ok_or_else(|| format!("{} is not open", self.name))?;
+        let sent = match &self.port {
+            Port::Serial { .. } => {
+                let line = format!("{}={}\n", mapping.address, mapping.format_text(value)?);
+                serial::write(&fd, line.as_bytes())
+            }
+            Port::Can { .. } => {
+                let id = can::parse_id(&mapping.address)
+                    .ok_or_else(|| format!("Invalid CAN id {}", mapping.address))?;
+                let mut frames = self.frames.lock().unwrap();
+                let data = frames.entry(id).or_default();
+                mapping.encode(value, data)?;
+                can::write(&fd, &can::frame(id, data))
+            }
+        };
+        sent.map_err(|e| e.to_string())
+    }
+}
+
+struct Gateway {
+    client: BlackboardClient,
+    devices: Vec<Device>,
+    // values read from devices to keys also written to them, not sent back
+    written: Mutex<HashMap<String, TypedBlackboardValue>>,
+    running: AtomicBool,
+}
+
+impl Gateway {
+    fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .devices
+            .iter()
+            .flat_map(|device| device.mappings.iter())
+            .filter(|mapping| mapping.writes())
+            .map(|mapping| mapping.key.clone())
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    // writes a value read from a device to its key
+    fn receive(&self, mapping: &Mapping, value: TypedBlackboardValue) {
+        if mapping.writes() {
+            self.written
+                .lock()
+                .unwrap()
+                .insert(mapping.key.clone(), value.clone());
+        }
+        if let Err(e) = self.client.set_value(&mapping.key, &value) {
+            warn!("Can not write {} from {}: {}", mapping.key, mapping.device, e);
+        }
+    }
+
+    fn receive_line(&self, device: &Device, line: &str) {
+        let Some((name, text)) = serial::split_line(line) else {
+            return debug!("Ignoring line {:?} of {}", line, device.name);
+        };
+        for mapping in device.mappings.iter().filter(|mapping| mapping.reads()) {
+            if mapping.address != name {
+                continue;
+            }
+            match mapping.parse_text(text) {
+                Some(value) => self.receive(mapping, value),
+                None => warn!("Invalid value {:?} of {} on {}", text, name, device.name),
+            }
+        }
+    }
+
+    fn receive_frame(&self, device: &Device, id: u32, data: &[u8]) {
+        for mapping in device.mappings.iter().filter(|mapping| mapping.reads()) {
+            if can::parse_id(&mapping.address) != Some(id) {
+                continue;
+            }
+            match mapping.decode(data) {
+                Some(value) => self.receive(mapping, value),
+                None => warn!(
+                    "Frame {:#x} on {} is too short for {}",
+                    id, device.name, mapping.key
+                ),
+            }
+        }
+    }
+
+    // sends the current value of a changed key to the devices
+    fn send(&self, key: &str) {
+        let value = match self.client.get_value(key) {
+            Ok(value) => value,
+            // devices keep their last value
+            Err(e) if e.status == RtStatus::KeyNotFound => return,
+            Err(e) => return warn!("Can not read changed key {}: {}", key, e),
+        };
+        if self.written.lock().unwrap().remove(key).as_ref() == Some(&value) {
+            return;
+        }
+        for device in &self.devices {
+            for mapping in device.mappings.iter().filter(|m| m.writes() && m.key == key) {
+                if let Err(e) = device.send(mapping, &value) {
+                    warn!("Can not send {} to {}: {}", key, device.name, e);
+                }
+            }
+        }
+    }
+
+    // reads the next frames or lines of an open device
+    fn read(&self, device: &Device, fd: &OwnedFd, lines: &mut serial::Lines) -> io::Result<()> {
+        if !readable(fd, POLL)? {
+            return Ok(());
+        }
+        match &device.port {
+            Port::Serial { .. } => {
+                let mut buffer = [0u8; 1024];
+                let read = serial::read(fd, &mut buffer)?;
+                if read == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Device is gone"));
+                }
+                for line in lines.push(&buffer[..read]) {
+                    device.received.fetch_add(1, Ordering::Relaxed);
+                    self.receive_line(device, &line);
+                }
+            }
+            Port::Can { .. } => {
+                let frame = can::read(fd)?;
+                if let Some((id, data)) = can::parse_frame(&frame) {
+                    device.received.fetch_add(1, Ordering::Relaxed);
+                    self.receive_frame(device, id, data);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // reads a device until the gateway stops, it is opened again after errors
+    fn run(&self, device: &Device) {
+        let mut lost = false;
+        while self.running.load(Ordering::SeqCst) {
+            let fd = match device.open() {
+                Ok(fd) => {
+                    info!("Opened {} ({:?})", device.name, device.port);
+                    fd
+                }
+                Err(e) => {
+                    if !lost {
+                        warn!("Can not open {}: {}", device.name, e);
+                        lost = true;
+                    }
+                    let retry = Instant::now() + REOPEN;
+                    while self.running.load(Ordering::SeqCst) && Instant::now() < retry {
+                        std::thread::sleep(Duration::from_millis(50));
+                    }
+                    continue;
+                }
+            };
+            lost = false;
+            let mut lines = serial::Lines::default();
+            while self.running.load(Ordering::SeqCst) {
+                if let Err(e) = self.read(device, &fd, &mut lines) {
+                    warn!("Lost {}: {}", device.name, e);
+                    lost = true;
+                    break;
+                }
+            }
+            device.close();
+        }
+    }
+}
+
+struct GatewayState {
+    gateway: Arc<Gateway>,
+    subscriptions: Vec<Subscription>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+static GATEWAY_STATE: Mutex<Option<GatewayState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "devicegateway",
+    summary = "connects serial and CAN devices to blackboard keys",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        devicegateway_start = start: "i32(caps,cstr)",
+        devicegateway_stop = stop: "i32()",
+        devicegateway_health = health: "i32()",
+        devicegateway_state = state: "i32()",
+        devicegateway_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_gateway(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = GATEWAY_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Gateway is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    if config.mappings.is_empty() {
+        return Err(RtError::new(
+            RtStatus::InvalidArgument,
+            "No mappings to connect",
+        ));
+    }
+    let devices = config
+        .devices
+        .into_iter()
+        .map(|(name, port)| {
+            let mappings = config
+                .mappings
+                .iter()
+                .filter(|mapping| mapping.device == name)
+                .cloned()
+                .collect();
+            Device::new(name, port, mappings)
+        })
+        .collect();
+    let gateway = Arc::new(Gateway {
+        client: BlackboardClient::new(interfaces::capabilities::Capabilities::from_raw(caps)),
+        devices,
+        written: Mutex::new(HashMap::new()),
+        running: AtomicBool::new(true),
+    });
+
+    let (subscriptions, changes) =
+        gateway.client.subscribe_forwarding(gateway.keys(), "devicegateway")?;
+
+    let sending = gateway.clone();
+    // ends once the subscriptions are dropped
+    let mut threads = vec![std::thread::spawn(move || {
+        for key in changes {
+            sending.send(&key);
+        }
+    })];
+    for index in 0..gateway.devices.len() {
+        let reading = gateway.clone();
+        threads.push(std::thread::spawn(move || {
+            reading.run(&reading.devices[index])
+        }));
+    }
+
+    info!(
+        "Connecting {} mappings of {} devices",
+        config.mappings.len(),
+        gateway.devices.len()
+    );
+    *state = Some(GatewayState {
+        gateway,
+        subscriptions,
+        threads,
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "devicegateway");
+    match LIFECYCLE.started(catch_panic(|| start_gateway(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting gateway: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_gateway() -> Result<(), RtError> {
+    let state = GATEWAY_STATE.lock().unwrap().take();
+    let state =
+        state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Gateway is not running"))?;
+    state.gateway.running.store(false, Ordering::SeqCst);
+    drop(state.subscriptions);
+    for thread in state.threads {
+        let _ = thread.join();
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_gateway)) {
+        Ok(()) => {
+            info!("Gateway stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping gateway: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_OK` while the gateway runs, `RT_NOT_RUNNING` once it is stopped. A device that is not
+/// open is not an error, it is opened again.
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match GATEWAY_STATE.lock().unwrap().as_ref() {
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the gateway, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes per device whether it is open and the number of frames or lines it received as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match GATEWAY_STATE.lock().unwrap().as_ref() {
+        Some(state) => {
+            let devices: serde_json::Map<String, serde_json::Value> = state
+                .gateway
+                .devices
+                .iter()
+                .map(|device| {
+                    let status = serde_json::json!({
+                        "open": device.link.lock().unwrap().is_some(),
+                        "received": device.received.load(Ordering::Relaxed),
+                    });
+                    (device.name.clone(), status)
+                })
+                .collect();
+            serde_json::json!({ "devices": devices })
+        }
+        None => serde_json::json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> Result<Config, RtError> {
+        let entries: Vec<BlackboardEntry> = serde_yml::from_str(yaml).unwrap();
+        Config::new(&entries)
+    }
+
+    #[test]
+    fn test_config() {
+        let config = config(
+            "[{key: devices, value: [{name: bus, can: can0}, {name: arm, serial: /dev/ttyUSB0}]},
+              {key: mappings, value: ['bus:0x120 u16@2 -> robot/speed',
+                                      'arm:speed f32 <- arm/speed']}]",
+        )
+        .unwrap();
+        assert_eq!(
+            config.devices,
+            vec![
+                (
+                    "bus".to_string(),
+                    Port::Can {
+                        interface: "can0".to_string()
+                    }
+                ),
+                (
+                    "arm".to_string(),
+                    Port::Serial {
+                        path: "/dev/ttyUSB0".to_string(),
+                        baud: 115200
+                    }
+                ),
+            ]
+        );
+        assert_eq!(config.mappings.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let devices =
+            "{key: devices, value: [{name: bus, can: can0}, {name: arm, serial: /dev/tty0}]}";
+        let mapped = |mapping: &str| {
+            config(&format!("[{}, {{key: mappings, value: '{}'}}]", devices, mapping))
+        };
+        let invalid = |mapping: &str| mapped(mapping).unwrap_err().status;
+        assert_eq!(invalid("motor:1 u8 -> speed"), RtStatus::InvalidArgument);
+        assert_eq!(invalid("bus:speed u8 -> speed"), RtStatus::InvalidArgument);
+        assert_eq!(invalid("bus:1 u32@6 -> speed"), RtStatus::InvalidArgument);
+        assert_eq!(invalid("bus:1 str -> name"), RtStatus::InvalidArgument);
+        assert!(mapped("arm:name str -> name").is_ok());
+        let both = "[{key: devices, value: [{name: both, can: can0, serial: /dev/tty0}]}]";
+        assert!(config(both).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let mappings = vec![
+            Mapping::parse("bus:0x120 u8 <- robot/mode").unwrap(),
+            Mapping::parse("bus:0x120 i16@2 <- robot/speed").unwrap(),
+            Mapping::parse("bus:0x121 u8 -> robot/state").unwrap(),
+        ];
+        let port = Port::Can {
+            interface: "can0".to_string(),
+        };
+        let device = Device::new("bus".to_string(), port, mappings);
+        assert_eq!(*device.frames.lock().unwrap(), HashMap::from([(0x120, vec![0; 4])]));
+        assert!(device
+            .send(&device.mappings[0], &TypedBlackboardValue::Int(1))
+            .is_err());
+    }
+}
@@ -0,0 +1,308 @@
+// The mapping DSL of the gateway, one mapping per string:
+//
+//     <device>:<address> <type>[@<offset>] [* <scale>] <direction> <key>
+//
+// `bus:0x120 u16@2 * 0.01 -> robot/speed` writes bytes 2 and 3 of CAN frame 0x120 as little
+// endian u16 times 0.01 to `robot/speed`, `arm:speed f32 <- arm/speed` sends `arm/speed` as
+// line `speed=<value>` to the serial device `arm`. `->` reads from the device, `<-` writes to
+// it and `<->` does both.
+use interfaces::blackboard::TypedBlackboardValue;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    Bool,
+    Str, // the whole value of a line, serial devices only
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => Field::U8,
+            "i8" => Field::I8,
+            "u16" => Field::U16,
+            "i16" => Field::I16,
+            "u32" => Field::U32,
+            "i32" => Field::I32,
+            "f32" => Field::F32,
+            "bool" => Field::Bool,
+            "str" => Field::Str,
+            _ => return None,
+        })
+    }
+
+    /// Bytes in a CAN frame.
+    pub fn size(&self) -> usize {
+        match self {
+            Field::U8 | Field::I8 | Field::Bool | Field::Str => 1,
+            Field::U16 | Field::I16 => 2,
+            Field::U32 | Field::I32 | Field::F32 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Read,  // `->`, from the device to the key
+    Write, // `<-`, from the key to the device
+    Both,  // `<->`
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub device: String,
+    pub address: String, // the CAN id or the name of a serial line
+    pub field: Field,
+    pub big_endian: bool,
+    pub offset: usize, // of the field in a CAN frame
+    pub scale: Option<f64>,
+    pub direction: Direction,
+    pub key: String,
+}
+
+impl Mapping {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("Invalid mapping '{}': {}", text, reason);
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let (source, field, scale, direction, key) = match tokens.as_slice() {
+            [source, field, direction, key] => (source, field, None, direction, key),
+            [source, field, "*", scale, direction, key] => {
+                let scale: f64 = scale.parse().map_err(|_| invalid("scale is no number"))?;
+                (source, field, Some(scale), direction, key)
+            }
+            _ => return Err(invalid("expected '<device>:<address> <type> [* <scale>] -> <key>'")),
+        };
+        let (device, address) = source
+            .split_once(':')
+            .filter(|(device, address)| !device.is_empty() && !address.is_empty())
+            .ok_or_else(|| invalid("expected '<device>:<address>'"))?;
+        let (name, offset) = match field.split_once('@') {
+            Some((name, offset)) => {
+                let offset = offset.parse().map_err(|_| invalid("offset is no number"))?;
+                (name, offset)
+            }
+            None => (*field, 0),
+        };
+        let (name, big_endian) = match name.strip_suffix("be") {
+            Some(name) => (name, true),
+            None => (name.strip_suffix("le").unwrap_or(name), false),
+        };
+        let field = Field::parse(name).ok_or_else(|| invalid("unknown type"))?;
+        if field == Field::Str && scale.is_some() {
+            return Err(invalid("str can not be scaled"));
+        }
+        let direction = match *direction {
+            "->" => Direction::Read,
+            "<-" => Direction::Write,
+            "<->" => Direction::Both,
+            _ => return Err(invalid("direction is not ->, <- or <->")),
+        };
+        Ok(Mapping {
+            device: device.to_string(),
+            address: address.to_string(),
+            field,
+            big_endian,
+            offset,
+            scale,
+            direction,
+            key: key.to_string(),
+        })
+    }
+
+    pub fn reads(&self) -> bool {
+        self.direction != Direction::Write
+    }
+
+    pub fn writes(&self) -> bool {
+        self.direction != Direction::Read
+    }
+
+    // the value of a number of the device
+    fn typed(&self, raw: f64) -> TypedBlackboardValue {
+        match (self.scale, self.field) {
+            (Some(scale), _) => TypedBlackboardValue::Double(raw * scale),
+            (None, Field::Bool) => TypedBlackboardValue::Bool(raw != 0.0),
+            (None, Field::F32) => TypedBlackboardValue::Double(raw),
+            (None, Field::U32) => TypedBlackboardValue::Int64(raw as i64),
+            (None, _) => TypedBlackboardValue::Int(raw as i32),
+        }
+    }
+
+    // the number sent to the device for a value
+    fn raw(&self, value: &TypedBlackboardValue) -> Result<f64, String> {
+        let number = match value {
+            TypedBlackboardValue::Int(value) => *value as f64,
+            TypedBlackboardValue::Int64(value) => *value as f64,
+            TypedBlackboardValue::Float(value) => *value as f64,
+            TypedBlackboardValue::Double(value) => *value,
+            TypedBlackboardValue::Bool(value) => *value as u8 as f64,
+            _ => return Err(format!("{} is no number", self.key)),
+        };
+        Ok(match self.scale {
+            Some(scale) => number / scale,
+            None => number,
+        })
+    }
+
+    /// The value of the field in the data of a CAN frame, None if the frame is too short.
+    pub fn decode(&self, data: &[u8]) -> Option<TypedBlackboardValue> {
+        let bytes = data.get(self.offset..self.offset + self.field.size())?;
+        let mut word = [0u8; 4];
+        word[..bytes.len()].copy_from_slice(bytes);
+        if self.big_endian {
+            word[..bytes.len()].reverse();
+        }
+        let raw = match self.field {
+            Field::U8 | Field::Bool | Field::Str => word[0] as f64,
+            Field::I8 => word[0] as i8 as f64,
+            Field::U16 => u16::from_le_bytes([word[0], word[1]]) as f64,
+            Field::I16 => i16::from_le_bytes([word[0], word[1]]) as f64,
+            Field::U32 => u32::from_le_bytes(word) as f64,
+            Field::I32 => i32::from_le_bytes(word) as f64,
+            Field::F32 => f32::from_le_bytes(word) as f64,
+        };
+        Some(self.typed(raw))
+    }
+
+    /// Writes a value into the data of a CAN frame, integers saturate at the limits of the field.
+    pub fn encode(&self, value: &TypedBlackboardValue, data: &mut [u8]) -> Result<(), String> {
+        let raw = self.raw(value)?;
+        let rounded = raw.round();
+        let mut bytes = match self.field {
+            Field::U8 | Field::Bool | Field::Str => vec![rounded as u8],
+            Field::I8 => (rounded as i8).to_le_bytes().to_vec(),
+            Field::U16 => (rounded as u16).to_le_bytes().to_vec(),
+            Field::I16 => (rounded as i16).to_le_bytes().to_vec(),
+            Field::U32 => (rounded as u32).to_le_bytes().to_vec(),
+            Field::I32 => (rounded as i32).to_le_bytes().to_vec(),
+            Field::F32 => (raw as f32).to_le_bytes().to_vec(),
+        };
+        if self.big_endian {
+            bytes.reverse();
+        }
+        let target = data
+            .get_mut(self.offset..self.offset + bytes.len())
+            .ok_or_else(|| format!("{} does not fit into the frame", self.key))?;
+        target.copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// The value of a serial line, None if it is no value of the type.
+    pub fn parse_text(&self, text: &str) -> Option<TypedBlackboardValue> {
+        let text = text.trim();
+        match self.field {
+            Field::Str => Some(TypedBlackboardValue::String(text.to_string())),
+            Field::Bool => match text {
+                "1" | "true" => Some(self.typed(1.0)),
+                "0" | "false" => Some(self.typed(0.0)),
+                _ => None,
+            },
+            _ => text.parse().ok().map(|raw| self.typed(raw)),
+        }
+    }
+
+    /// The text of a value on a serial line.
+    pub fn format_text(&self, value: &TypedBlackboardValue) -> Result<String, String> {
+        match (self.field, value) {
+            (Field::Str, TypedBlackboardValue::String(value)) => Ok(value.clone()),
+            (Field::Str, _) => Err(format!("{} is no string", self.key)),
+            (Field::F32, _) => self.raw(value).map(|raw| raw.to_string()),
+            _ => self.raw(value).map(|raw| (raw.round() as i64).to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let mapping = Mapping::parse("bus:0x120 u16be@2 * 0.01 <-> robot/speed").unwrap();
+        assert_eq!(mapping.device, "bus");
+        assert_eq!(mapping.address, "0x120");
+        assert_eq!(mapping.field, Field::U16);
+        assert!(mapping.big_endian);
+        assert_eq!(mapping.offset, 2);
+        assert_eq!(mapping.scale, Some(0.01));
+        assert_eq!(mapping.direction, Direction::Both);
+        assert_eq!(mapping.key, "robot/speed");
+
+        let mapping = Mapping::parse("arm:pos:x f32 -> arm/x").unwrap();
+        assert_eq!(mapping.address, "pos:x");
+        assert!(mapping.reads() && !mapping.writes());
+
+        assert!(Mapping::parse("bus:0x120 u16 => robot/speed").is_err());
+        assert!(Mapping::parse("bus u16 -> robot/speed").is_err());
+        assert!(Mapping::parse("bus:1 u12 -> robot/speed").is_err());
+        assert!(Mapping::parse("arm:name str * 2 -> arm/name").is_err());
+        assert!(Mapping::parse("bus:1 u16@x -> robot/speed").is_err());
+    }
+
+    #[test]
+    fn test_frame() {
+        let mapping = Mapping::parse("bus:0x120 u16be@2 * 0.01 -> robot/speed").unwrap();
+        let mut data = [0u8; 4];
+        mapping
+            .encode(&TypedBlackboardValue::Double(2.5), &mut data)
+            .unwrap();
+        assert_eq!(data, [0, 0, 0x00, 0xfa]);
+        assert_eq!(
+            mapping.decode(&data),
+            Some(TypedBlackboardValue::Double(2.5))
+        );
+        assert_eq!(mapping.decode(&data[..3]), None);
+
+        let mapping = Mapping::parse("bus:0x120 i8 -> robot/turn").unwrap();
+        mapping
+            .encode(&TypedBlackboardValue::Int(-300), &mut data)
+            .unwrap();
+        assert_eq!(data[0], 0x80);
+        assert_eq!(mapping.decode(&data), Some(TypedBlackboardValue::Int(-128)));
+
+        let mapping = Mapping::parse("bus:0x120 u32@2 -> robot/odometer").unwrap();
+        assert!(mapping
+            .encode(&TypedBlackboardValue::Int(1), &mut data)
+            .is_err());
+        assert!(mapping
+            .encode(&TypedBlackboardValue::String("1".to_string()), &mut [0u8; 8])
+            .is_err());
+    }
+
+    #[test]
+    fn test_text() {
+        let mapping = Mapping::parse("arm:speed f32 <-> arm/speed").unwrap();
+        assert_eq!(
+            mapping.parse_text("1.5\r"),
+            Some(TypedBlackboardValue::Double(1.5))
+        );
+        assert_eq!(mapping.parse_text("fast"), None);
+        assert_eq!(
+            mapping.format_text(&TypedBlackboardValue::Int(2)).unwrap(),
+            "2"
+        );
+
+        let mapping = Mapping::parse("arm:enabled bool -> arm/enabled").unwrap();
+        assert_eq!(
+            mapping.parse_text("true"),
+            Some(TypedBlackboardValue::Bool(true))
+        );
+        assert_eq!(
+            mapping.format_text(&TypedBlackboardValue::Bool(true)).unwrap(),
+            "1"
+        );
+
+        let mapping = Mapping::parse("arm:name str -> arm/name").unwrap();
+        assert_eq!(
+            mapping.parse_text("gripper"),
+            Some(TypedBlackboardValue::String("gripper".to_string()))
+        );
+        assert!(mapping.format_text(&TypedBlackboardValue::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+// Serial ports in raw mode, the gateway exchanges lines `<name>=<value>` with them.
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+fn speed(baud: u32) -> io::Result<libc::speed_t> {
+    Ok(match baud {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        921600 => libc::B921600,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported baud rate {}", baud),
+            ))
+        }
+    })
+}
+
+/// Opens a serial port with 8N1 at `baud`, without echo or line editing.
+pub fn open(path: &str, baud: u32) -> io::Result<OwnedFd> {
+    let speed = speed(baud)?;
+    let path = CString::new(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    unsafe {
+        if libc::tcgetattr(fd.as_raw_fd(), &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut termios);
+        termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+        if libc::cfsetspeed(&mut termios, speed) != 0
+            || libc::tcsetattr(fd.as_raw_fd(), libc::TCSANOW, &termios) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(fd)
+}
+
+/// Reads what the port received, 0 once the device is gone.
+pub fn read(fd: &OwnedFd, buffer: &mut [u8]) -> io::Result<usize> {
+    let read = unsafe {
+        libc::read(
+            fd.as_raw_fd(),
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            buffer.len(),
+        )
+    };
+    match read {
+        read if read < 0 => Err(io::Error::last_os_error()),
+        read => Ok(read as usize),
+    }
+}
+
+pub fn write(fd: &OwnedFd, mut bytes: &[u8]) -> io::Result<()> {
+    while !bytes.is_empty() {
+        let written = unsafe {
+            libc::write(
+                fd.as_raw_fd(),
+                bytes.as_ptr() as *const libc::c_void,
+                bytes.len(),
+            )
+        };
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        bytes = &bytes[written as usize..];
+    }
+    Ok(())
+}
+
+/// Collects the bytes read from a port into lines.
+#[derive(Default)]
+pub struct Lines {
+    pending: Vec<u8>,
+}
+
+// a device sending no newline does not grow the buffer forever
+const MAX_LINE: usize = 4096;
+
+impl Lines {
+    /// The complete lines of the bytes read so far, without `\r\n`.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            lines.push(line.trim_end_matches(['\r', '\n']).to_string());
+        }
+        if self.pending.len() > MAX_LINE {
+            self.pending.clear();
+        }
+        lines
+    }
+}
+
+/// Name and value of a line `<name>=<value>`.
+pub fn split_line(line: &str) -> Option<(&str, &str)> {
+    line.split_once('=')
+        .map(|(name, value)| (name.trim(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    // a pseudo terminal standing in for the device, and the path of its port
+    fn pseudo_terminal() -> (std::fs::File, String) {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let mut name = [0 as libc::c_char; 128];
+            assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+            let path = std::ffi::CStr::from_ptr(name.as_ptr());
+            (
+                std::fs::File::from_raw_fd(master),
+                path.to_string_lossy().into_owned(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_port() {
+        let (mut device, path) = pseudo_terminal();
+        let port = open(&path, 115200).unwrap();
+        assert!(open(&path, 1234).is_err());
+        assert!(open("/dev/does-not-exist", 9600).is_err());
+
+        device.write_all(b"speed=1.5\r\ntemp").unwrap();
+        let mut lines = Lines::default();
+        let mut buffer = [0u8; 64];
+        let read = super::read(&port, &mut buffer).unwrap();
+        assert_eq!(lines.push(&buffer[..read]), vec!["speed=1.5"]);
+        assert_eq!(lines.push(b"=21\n"), vec!["temp=21"]);
+
+        write(&port, b"enabled=1\n").unwrap();
+        let read = device.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..read], b"enabled=1\n");
+    }
+
+    #[test]
+    fn test_split_line() {
+        assert_eq!(split_line("speed = 1.5"), Some(("speed", "1.5")));
+        assert_eq!(split_line("ready"), None);
+    }
+}
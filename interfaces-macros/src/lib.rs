@@ -0,0 +1,306 @@
+//! Generates the `summary()` export of a plugin from a declaration of what it provides.
+//!
+//! ```ignore
+//! #[rt_plugin(
+//!     name = "blackboard",
+//!     version = "0.1.0",
+//!     library_type = "Service",
+//!     capabilities_abi = 2,
+//!     provides(
+//!         blackboard_get_int = get_int: "i32(cstr,*mut i32)",
+//!     ),
+//!     requires("other >= 0.1"),
+//! )]
+//! pub extern "C" fn summary() -> *const c_char;
+//! ```
+//!
+//! Every entry has to name a function in scope whose type matches the declared signature,
+//! otherwise the plugin does not compile. The macro also exports `get_last_error` and provides
+//! it as `<name>_get_last_error`, and exports `abi_version`, which the loader checks before it
+//! loads the plugin.
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    parenthesized, parse_macro_input, Attribute, Block, Ident, LitInt, LitStr, Signature, Token,
+    Visibility,
+};
+
+// the annotated `summary` function, its body is optional and replaced anyway
+struct SummaryFn {
+    attrs: Vec<Attribute>,
+    sig: Signature,
+}
+
+impl Parse for SummaryFn {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        input.parse::<Visibility>()?;
+        let sig = input.parse()?;
+        if input.peek(Token![;]) {
+            input.parse::<Token![;]>()?;
+        } else {
+            input.parse::<Block>()?;
+        }
+        Ok(SummaryFn { attrs, sig })
+    }
+}
+
+struct Provide {
+    capability: Ident,
+    entry: Ident,
+    signature: LitStr,
+}
+
+impl Parse for Provide {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let capability = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let entry = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let signature = input.parse()?;
+        Ok(Provide {
+            capability,
+            entry,
+            signature,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Plugin {
+    name: Option<LitStr>,
+    version: Option<LitStr>,
+    library_type: Option<LitStr>,
+    summary: Option<LitStr>,
+    capabilities_abi: Option<LitInt>,
+    provides: Vec<Provide>,
+    requires: Vec<LitStr>,
+}
+
+impl Parse for Plugin {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut plugin = Plugin::default();
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            match key.to_string().as_str() {
+                "name" | "version" | "library_type" | "summary" => {
+                    input.parse::<Token![=]>()?;
+                    let value: LitStr = input.parse()?;
+                    match key.to_string().as_str() {
+                        "name" => plugin.name = Some(value),
+                        "version" => plugin.version = Some(value),
+                        "library_type" => plugin.library_type = Some(value),
+                        _ => plugin.summary = Some(value),
+                    }
+                }
+                "capabilities_abi" => {
+                    input.parse::<Token![=]>()?;
+                    plugin.capabilities_abi = Some(input.parse()?);
+                }
+                "provides" => {
+                    let content;
+                    parenthesized!(content in input);
+                    let provides = Punctuated::<Provide, Token![,]>::parse_terminated(&content)?;
+                    plugin.provides.extend(provides);
+                }
+                "requires" => {
+                    let content;
+                    parenthesized!(content in input);
+                    let requires = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                    plugin.requires.extend(requires);
+                }
+                _ => return Err(syn::Error::new(key.span(), "unknown rt_plugin attribute")),
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(plugin)
+    }
+}
+
+fn required(value: &Option<LitStr>, key: &str) -> syn::Result<String> {
+    value
+        .as_ref()
+        .map(|v| v.value())
+        .ok_or_else(|| syn::Error::new(Span::call_site(), format!("rt_plugin requires `{}`", key)))
+}
+
+// Rust type of a type name used in capability signatures, see `interfaces::signature`
+fn rust_type(name: &str) -> Option<TokenStream2> {
+    let c_char = quote!(::std::os::raw::c_char);
+    let c_void = quote!(::std::os::raw::c_void);
+    let name = name.trim();
+    if let Some(pointee) = name.strip_prefix("*const ") {
+        let pointee = if pointee.trim() == "void" { c_void } else { rust_type(pointee)? };
+        return Some(quote!(*const #pointee));
+    }
+    if let Some(pointee) = name.strip_prefix("*mut ") {
+        let pointee = if pointee.trim() == "void" { c_void } else { rust_type(pointee)? };
+        return Some(quote!(*mut #pointee));
+    }
+    Some(match name {
+        "void" => quote!(()),
+        "char" => c_char,
+        "cstr" => quote!(*const #c_char),
+        "caps" => quote!(&::interfaces::bindings::Capabilities),
+        "legacy_caps" => quote!(&::interfaces::bindings::LegacyCapabilities),
+        "ctx" => quote!(*mut ::interfaces::bindings::rt_context),
+        "bool" | "u8" | "i32" | "u32" | "i64" | "u64" | "f32" | "f64" | "usize" => {
+            let ident = Ident::new(name, Span::call_site());
+            quote!(#ident)
+        }
+        _ => return None,
+    })
+}
+
+/// Function pointer type described by a signature like `i32(cstr,*mut i32)`.
+fn signature_type(signature: &str) -> Result<TokenStream2, String> {
+    let (ret, args) = signature
+        .trim()
+        .strip_suffix(')')
+        .and_then(|s| s.split_once('('))
+        .ok_or_else(|| format!("invalid signature `{}`", signature))?;
+    let unknown = |name: &str| format!("unknown type `{}` in signature `{}`", name, signature);
+    let ret = rust_type(ret).ok_or_else(|| unknown(ret))?;
+    let args = args
+        .split(',')
+        .filter(|arg| !arg.trim().is_empty())
+        .map(|arg| rust_type(arg).ok_or_else(|| unknown(arg)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(quote!(unsafe extern "C" fn(#(#args),*) -> #ret))
+}
+
+const GET_LAST_ERROR_SIGNATURE: &str = "i32(*mut char,i32)";
+
+fn summary_json(plugin: &Plugin) -> syn::Result<String> {
+    let string = |s: String| serde_json::to_string(&s).unwrap();
+    let name = required(&plugin.name, "name")?;
+    let version = required(&plugin.version, "version")?;
+
+    let mut json = String::from("{\n");
+    json += &format!("    \"name\": {},\n", string(name.clone()));
+    if let Some(summary) = &plugin.summary {
+        json += &format!("    \"summary\": {},\n", string(summary.value()));
+    }
+    json += &format!(
+        "    \"library_type\": {},\n",
+        string(required(&plugin.library_type, "library_type")?)
+    );
+    json += &format!("    \"version\": {},\n", string(version.clone()));
+    if let Some(abi) = &plugin.capabilities_abi {
+        json += &format!("    \"capabilities_abi\": {},\n", abi.base10_parse::<u32>()?);
+    }
+
+    let mut entries: Vec<(String, String, String)> = plugin
+        .provides
+        .iter()
+        .map(|provide| {
+            (
+                provide.capability.to_string(),
+                provide.entry.to_string(),
+                provide.signature.value(),
+            )
+        })
+        .collect();
+    entries.push((
+        format!("{}_get_last_error", name),
+        "get_last_error".to_string(),
+        GET_LAST_ERROR_SIGNATURE.to_string(),
+    ));
+
+    let provides: Vec<String> = entries
+        .into_iter()
+        .map(|(capability, entry, signature)| {
+            format!(
+                "        {{\n            \"capability\": {},\n            \"entry\": {},\n            \"signature\": {},\n            \"version\": {}\n        }}",
+                string(capability),
+                string(entry),
+                string(signature),
+                string(version.clone())
+            )
+        })
+        .collect();
+    json += &format!("    \"provides\": [\n{}\n    ]", provides.join(",\n"));
+
+    if !plugin.requires.is_empty() {
+        let requires: Vec<String> = plugin.requires.iter().map(|r| string(r.value())).collect();
+        json += &format!(",\n    \"requires\": [{}]", requires.join(", "));
+    }
+    json += "\n}";
+    Ok(json)
+}
+
+fn expand(plugin: Plugin, function: SummaryFn) -> syn::Result<TokenStream2> {
+    let json = summary_json(&plugin)? + "\0";
+
+    let mut checks = Vec::new();
+    for provide in &plugin.provides {
+        let fn_type = signature_type(&provide.signature.value())
+            .map_err(|e| syn::Error::new(provide.signature.span(), e))?;
+        let entry = &provide.entry;
+        checks.push(quote_spanned! {entry.span()=>
+            const _: #fn_type = #entry;
+        });
+    }
+
+    let attrs = function
+        .attrs
+        .iter()
+        .filter(|attr| !attr.path().is_ident("no_mangle"));
+    let name = &function.sig.ident;
+
+    Ok(quote! {
+        static SUMMARY_MESSAGE: &str = #json;
+
+        #(#attrs)*
+        #[no_mangle]
+        pub extern "C" fn #name() -> *const ::std::os::raw::c_char {
+            // summary message + null terminator
+            SUMMARY_MESSAGE.as_ptr() as *const ::std::os::raw::c_char
+        }
+
+        ::interfaces::export_last_error!();
+
+        // the layout of caps.h the plugin is built with
+        #[no_mangle]
+        pub extern "C" fn abi_version() -> ::std::os::raw::c_int {
+            ::interfaces::bindings::RT_ABI_VERSION as ::std::os::raw::c_int
+        }
+
+        // every entry exists and has the declared signature
+        const _: () = {
+            #(#checks)*
+        };
+    })
+}
+
+/// Exports the summary of a plugin, see the crate documentation.
+#[proc_macro_attribute]
+pub fn rt_plugin(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let plugin = parse_macro_input!(attr as Plugin);
+    let function = parse_macro_input!(item as SummaryFn);
+    expand(plugin, function)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_type() {
+        let fn_type = signature_type("i32(cstr, *mut i32)").unwrap();
+        assert_eq!(
+            fn_type.to_string(),
+            quote!(unsafe extern "C" fn(*const ::std::os::raw::c_char, *mut i32) -> i32)
+                .to_string()
+        );
+        assert!(signature_type("i32(string)").is_err());
+        assert!(signature_type("i32").is_err());
+    }
+}
@@ -1,8 +1,51 @@
 // bindgen path_to_header.h -o bindings.rs
 use std::path::PathBuf;
 
+// items of src/ffi.rs published in rtime_plugin.h
+const PLUGIN_HEADER_ITEMS: [&str; 14] = [
+    "RtStatus",
+    "Capability",
+    "Capabilities",
+    "rt_context",
+    "rt_completion",
+    "rt_summary_fn",
+    "rt_abi_version_fn",
+    "rt_start_fn",
+    "rt_run_fn",
+    "rt_tick_fn",
+    "rt_self_test_fn",
+    "rt_entry_fn",
+    "rt_health_status_fn",
+    "rt_context_start_fn",
+];
+
+// the header for C and C++ plugins, see src/ffi.rs
+fn write_plugin_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    let mut builder = cbindgen::Builder::new()
+        .with_src("src/ffi.rs")
+        .with_language(cbindgen::Language::C)
+        .with_style(cbindgen::Style::Type)
+        .with_cpp_compat(true)
+        .with_no_includes()
+        .with_include_guard("RTIME_PLUGIN_H")
+        .with_header(format!(
+            "/* Plugin ABI of rtime {}, generated by cbindgen from interfaces/src/ffi.rs. */",
+            env!("CARGO_PKG_VERSION")
+        ));
+    for item in PLUGIN_HEADER_ITEMS {
+        builder = builder.include_item(item);
+    }
+    builder
+        .generate()
+        .expect("Unable to generate rtime_plugin.h")
+        .write_to_file("rtime_plugin.h");
+}
 
 fn main(){
+    write_plugin_header();
+
     println!("cargo:rerun-if-changed=caps.h");
     
     let bindings = bindgen::Builder::default()
@@ -16,4 +59,4 @@ fn main(){
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
 
-}
\ No newline at end of file
+}
@@ -1,15 +1,83 @@
 #define CAPABILITY_FUNCTION_NAME_LEN        256
-#define CAPABILITY_NUMBER_OF_CAPABILITIES   20
+#define CAPABILITY_SIGNATURE_LEN            128
+#define CAPABILITY_VERSION_LEN              32
+#define CAPABILITIES_ABI_VERSION            3   // declared as "capabilities_abi" in the summary
+#define CAPABILITIES_ABI_TABLE              2   // first version passed a Capabilities table
+#define CAPABILITIES_ABI_CONTEXT            3   // first version passed an rt_context
+#define CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES   64
+#define RT_ABI_VERSION                      1   // returned by `abi_version` of every plugin
+
+// Status returned by capabilities. Non negative values mean success, some capabilities return a
+// size or count instead of RT_OK.
+typedef enum rt_status
+{
+    RT_OK = 0,
+    RT_ERROR = -1, // failure without a more specific status
+    RT_VALUE_MISMATCH = -2, // compare and set found a different value, nothing written
+    RT_TYPE_MISMATCH = -3, // key holds a value of a different type, nothing written
+    RT_TIMEOUT = -4,
+    RT_NOT_RUNNING = -5, // service is not started
+    RT_ALREADY_RUNNING = -6,
+    RT_KEY_NOT_FOUND = -7,
+    RT_BUFFER_TOO_SMALL = -8,
+    RT_NULL_ARGUMENT = -9,
+    RT_INVALID_ARGUMENT = -10,
+    RT_ACCESS_DENIED = -11, // the access rules of the blackboard forbid the call
+    RT_CONSTRAINT_VIOLATION = -12, // value violates the constraint of the key, nothing written
+} RtStatus;
 
 typedef struct capability
 {
    char name[CAPABILITY_FUNCTION_NAME_LEN]; // name of the capability
+   char signature[CAPABILITY_SIGNATURE_LEN]; // e.g. "i32(cstr,cstr)", empty if not declared
+   char version[CAPABILITY_VERSION_LEN]; // semver of the capability, e.g. "0.1.0", empty if not declared
    void* function; // function pointer
 } Capability;
 
 
+// The table is owned by the loader and only valid during the call it is passed to,
+// copy the capabilities you want to keep.
 typedef struct capabilities_
 {
-    Capability capability[CAPABILITY_NUMBER_OF_CAPABILITIES]; // array of capabilities
+    Capability* capability; // array of n_capabilities capabilities
     int n_capabilities; // number of capabilities
-} Capabilities;
\ No newline at end of file
+} Capabilities;
+
+
+// Capability and table passed to plugins which do not declare "capabilities_abi" in their summary.
+typedef struct legacy_capability
+{
+   char name[CAPABILITY_FUNCTION_NAME_LEN]; // name of the capability
+   void* function; // function pointer
+} LegacyCapability;
+
+typedef struct legacy_capabilities_
+{
+    LegacyCapability capability[CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES]; // array of capabilities
+    int n_capabilities; // number of capabilities
+} LegacyCapabilities;
+
+
+// Asynchronous calls. A long running capability `X` may also be provided as `X_async`, taking
+// the arguments of `X` followed by a completion and its user data, e.g. "i32(cstr,*mut void,
+// *mut void)" next to "i32(cstr)". It returns a positive handle once the call started or an
+// error if it did not, and calls the completion exactly once with the handle, the result of `X`
+// and the user data, from any thread and possibly before it returned. An optional `X_cancel`
+// "i32(i32)" asks the call of a handle to finish early, it still completes.
+// Skills may export `run_async` and `run_cancel` next to `run` in the same way.
+typedef void (*rt_completion)(int handle, int result, void* user_data);
+
+
+// Context of a loaded plugin instance, owned by the runtime. Plugins declaring a
+// "capabilities_abi" of CAPABILITIES_ABI_CONTEXT or higher get it as the first argument of their
+// entries start, stop, health, state, health_status, reconfigure, run, run_async and run_cancel,
+// e.g. "i32(ctx,caps,cstr)" for start. Every call into the same instance gets the same context,
+// it lives until the instance is unloaded. Capabilities keep their signatures.
+// A plugin keeps its state in `state` instead of statics, so several instances of it do not
+// share it. The runtime calls `release` with the state before it unloads the instance.
+typedef struct rt_context
+{
+    void* state; // owned by the plugin, null until it stores its state
+    void (*release)(void* state); // set by the plugin together with `state`, may be null
+    const char* instance; // name of the component, the instance name for instances
+} rt_context;
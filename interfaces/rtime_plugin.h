@@ -0,0 +1,181 @@
+/* Plugin ABI of rtime 0.1.0, generated by cbindgen from interfaces/src/ffi.rs. */
+
+#ifndef RTIME_PLUGIN_H
+#define RTIME_PLUGIN_H
+
+/**
+ * Version of this header, the highest `capabilities_abi` a plugin may declare in its summary.
+ */
+#define RTIME_PLUGIN_ABI_VERSION 3
+
+/**
+ * First `capabilities_abi` passing a `Capabilities` table to `start` and `run`.
+ */
+#define CAPABILITIES_ABI_TABLE 2
+
+/**
+ * First `capabilities_abi` passing an `rt_context` as the first argument of the entries.
+ */
+#define CAPABILITIES_ABI_CONTEXT 3
+
+/**
+ * Layout of the structs and entries of this header, returned by `abi_version`. The loader
+ * refuses plugins built for another one.
+ */
+#define RT_ABI_VERSION 1
+
+#define CAPABILITY_FUNCTION_NAME_LEN 256
+
+#define CAPABILITY_SIGNATURE_LEN 128
+
+#define CAPABILITY_VERSION_LEN 32
+
+/**
+ * Status returned by capabilities and entries. Non negative values mean success, some
+ * capabilities return a size or count instead of `RT_OK`.
+ */
+typedef enum {
+  RT_OK = 0,
+  /**
+   * Failure without a more specific status.
+   */
+  RT_ERROR = -1,
+  /**
+   * Compare and set found a different value, nothing written.
+   */
+  RT_VALUE_MISMATCH = -2,
+  /**
+   * The key holds a value of a different type, nothing written.
+   */
+  RT_TYPE_MISMATCH = -3,
+  RT_TIMEOUT = -4,
+  /**
+   * The service is not started.
+   */
+  RT_NOT_RUNNING = -5,
+  RT_ALREADY_RUNNING = -6,
+  RT_KEY_NOT_FOUND = -7,
+  RT_BUFFER_TOO_SMALL = -8,
+  RT_NULL_ARGUMENT = -9,
+  RT_INVALID_ARGUMENT = -10,
+  /**
+   * The access rules of the blackboard forbid the call.
+   */
+  RT_ACCESS_DENIED = -11,
+  /**
+   * The value violates the constraint of the key, nothing written.
+   */
+  RT_CONSTRAINT_VIOLATION = -12,
+} RtStatus;
+
+/**
+ * A function provided by a component, null terminated strings in fixed size arrays.
+ */
+typedef struct {
+  /**
+   * Name of the capability, e.g. "blackboard_get_int".
+   */
+  char name[CAPABILITY_FUNCTION_NAME_LEN];
+  /**
+   * e.g. "i32(cstr,*mut i32)", empty if not declared.
+   */
+  char signature[CAPABILITY_SIGNATURE_LEN];
+  /**
+   * Semver of the capability, e.g. "0.1.0", empty if not declared.
+   */
+  char version[CAPABILITY_VERSION_LEN];
+  /**
+   * The function, cast to the type of its signature before calling it.
+   */
+  void *function;
+} Capability;
+
+/**
+ * The capabilities passed to `start` and `run`. The table is owned by the loader and only valid
+ * during the call it is passed to, copy the capabilities you want to keep.
+ */
+typedef struct {
+  Capability *capability;
+  int n_capabilities;
+} Capabilities;
+
+/**
+ * Context of a loaded plugin instance, owned by the runtime and passed as the first argument
+ * of the entries with `capabilities_abi` 3. Every call into the same instance gets the same
+ * context, it lives until the instance is unloaded.
+ */
+typedef struct {
+  /**
+   * Owned by the plugin, null until it stores its state.
+   */
+  void *state;
+  /**
+   * Set by the plugin together with `state`, called with it before the instance is unloaded.
+   */
+  void (*release)(void *state);
+  /**
+   * Name of the component, the instance name for instances.
+   */
+  const char *instance;
+} rt_context;
+
+/**
+ * Completion of an asynchronous call `X_async`, called exactly once with the handle `X_async`
+ * returned, the result of `X` and the user data, from any thread.
+ */
+typedef void (*rt_completion)(int handle, int result, void *user_data);
+
+/**
+ * `summary`, exported by every plugin. Returns its description as a static null terminated
+ * json (or yaml) document:
+ *
+ * ```json
+ * {"name": "planner", "version": "0.1.0", "library_type": "Skill",
+ *  "capabilities_abi": 3,
+ *  "provides": [{"capability": "planner_plan", "entry": "plan",
+ *                "signature": "i32(cstr,*mut char,i32)", "version": "0.1.0"}],
+ *  "requires": ["blackboard >= 0.1"]}
+ * ```
+ *
+ * `library_type` is "Service" or "Skill". `provides` lists the exported functions the loader
+ * hands to other components as capabilities, `requires` the components or capabilities needed
+ * with optional semver requirements. Without `capabilities_abi` the plugin gets the legacy
+ * table of caps.h.
+ */
+typedef const char *(*rt_summary_fn)(void);
+
+/**
+ * `abi_version`, exported by every plugin next to `summary`. Returns `RT_ABI_VERSION`.
+ */
+typedef int (*rt_abi_version_fn)(void);
+
+/**
+ * `start` of a service: the capabilities it may call and its attributes as yaml, or null.
+ */
+typedef int (*rt_start_fn)(const Capabilities *caps, const char *attributes);
+
+/**
+ * `run` of a skill, with the same arguments as `start`. Returns `RT_OK`, a non negative result
+ * or an `RtStatus`.
+ */
+typedef int (*rt_run_fn)(const Capabilities *caps, const char *attributes);
+
+/**
+ * `stop` and `health` of a service, returning an `RtStatus`.
+ */
+typedef int (*rt_entry_fn)(void);
+
+/**
+ * `health_status` of a service, writing json details of its health into the buffer.
+ */
+typedef int (*rt_health_status_fn)(char *buffer, int len);
+
+/**
+ * `start` of a plugin with `capabilities_abi` 3, the other entries take the context first in
+ * the same way.
+ */
+typedef int (*rt_context_start_fn)(rt_context *context,
+                                   const Capabilities *caps,
+                                   const char *attributes);
+
+#endif  /* RTIME_PLUGIN_H */
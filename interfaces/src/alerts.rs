@@ -0,0 +1,69 @@
+// Alerts of the `alerts` plugin, shared with the components showing them. Every alert publishes
+// its status as json to `alerts/<name>`, writing `true` to `alerts/<name>/ack` acknowledges it.
+use serde::{Deserialize, Serialize};
+
+/// Prefix of the keys holding the status of every alert, e.g. `alerts/overheat`.
+pub const ALERT_PREFIX: &str = "alerts/";
+/// Suffix of the key acknowledging an alert, e.g. `alerts/overheat/ack`.
+pub const ACK_SUFFIX: &str = "/ack";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Ok,
+    Pending, // the condition holds, but not for the debounce time yet
+    Firing,
+    Acknowledged, // firing, but someone takes care of it
+}
+
+/// Status of an alert as published to its key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertStatus {
+    pub name: String,
+    pub state: AlertState,
+    pub severity: String,
+    pub message: String,
+    pub since: u64, // of the state, in milliseconds since the unix epoch
+    pub fired: u64, // times the alert fired since the start of the plugin
+}
+
+/// Key of the status of the alert `name`.
+pub fn alert_key(name: &str) -> String {
+    format!("{}{}", ALERT_PREFIX, name)
+}
+
+/// Key acknowledging the alert `name`.
+pub fn ack_key(name: &str) -> String {
+    format!("{}{}{}", ALERT_PREFIX, name, ACK_SUFFIX)
+}
+
+/// The alert acknowledged by writing `key`, if it is an ack key.
+pub fn acknowledged_alert(key: &str) -> Option<&str> {
+    key.strip_prefix(ALERT_PREFIX)?.strip_suffix(ACK_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(alert_key("overheat"), "alerts/overheat");
+        assert_eq!(ack_key("overheat"), "alerts/overheat/ack");
+        assert_eq!(acknowledged_alert("alerts/overheat/ack"), Some("overheat"));
+        assert_eq!(acknowledged_alert("alerts/overheat"), None);
+        assert_eq!(acknowledged_alert("robot/ack"), None);
+
+        let status = AlertStatus {
+            name: "overheat".to_string(),
+            state: AlertState::Firing,
+            severity: "critical".to_string(),
+            message: "motor/temperature > 80".to_string(),
+            since: 1714557600000,
+            fired: 1,
+        };
+        let json = serde_json::to_value(&status).unwrap();
+        assert_eq!(json["state"], "firing");
+        assert_eq!(serde_json::from_value::<AlertStatus>(json).unwrap(), status);
+    }
+}
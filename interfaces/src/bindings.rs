@@ -1,35 +1,118 @@
 /* automatically generated by rust-bindgen 0.71.1 */
 
 pub const CAPABILITY_FUNCTION_NAME_LEN: u32 = 256;
-pub const CAPABILITY_NUMBER_OF_CAPABILITIES: u32 = 20;
+pub const CAPABILITY_SIGNATURE_LEN: u32 = 128;
+pub const CAPABILITY_VERSION_LEN: u32 = 32;
+pub const CAPABILITIES_ABI_VERSION: u32 = 3;
+pub const CAPABILITIES_ABI_TABLE: u32 = 2;
+pub const CAPABILITIES_ABI_CONTEXT: u32 = 3;
+pub const CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES: u32 = 64;
+pub const RT_ABI_VERSION: u32 = 1;
+pub const rt_status_RT_OK: rt_status = 0;
+pub const rt_status_RT_ERROR: rt_status = -1;
+pub const rt_status_RT_VALUE_MISMATCH: rt_status = -2;
+pub const rt_status_RT_TYPE_MISMATCH: rt_status = -3;
+pub const rt_status_RT_TIMEOUT: rt_status = -4;
+pub const rt_status_RT_NOT_RUNNING: rt_status = -5;
+pub const rt_status_RT_ALREADY_RUNNING: rt_status = -6;
+pub const rt_status_RT_KEY_NOT_FOUND: rt_status = -7;
+pub const rt_status_RT_BUFFER_TOO_SMALL: rt_status = -8;
+pub const rt_status_RT_NULL_ARGUMENT: rt_status = -9;
+pub const rt_status_RT_INVALID_ARGUMENT: rt_status = -10;
+pub const rt_status_RT_ACCESS_DENIED: rt_status = -11;
+pub const rt_status_RT_CONSTRAINT_VIOLATION: rt_status = -12;
+pub type rt_status = ::std::os::raw::c_int;
+pub use self::rt_status as RtStatus;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct capability {
     pub name: [::std::os::raw::c_char; 256usize],
+    pub signature: [::std::os::raw::c_char; 128usize],
+    pub version: [::std::os::raw::c_char; 32usize],
     pub function: *mut ::std::os::raw::c_void,
 }
 #[allow(clippy::unnecessary_operation, clippy::identity_op)]
 const _: () = {
-    ["Size of capability"][::std::mem::size_of::<capability>() - 264usize];
+    ["Size of capability"][::std::mem::size_of::<capability>() - 424usize];
     ["Alignment of capability"][::std::mem::align_of::<capability>() - 8usize];
     ["Offset of field: capability::name"][::std::mem::offset_of!(capability, name) - 0usize];
+    ["Offset of field: capability::signature"]
+        [::std::mem::offset_of!(capability, signature) - 256usize];
+    ["Offset of field: capability::version"]
+        [::std::mem::offset_of!(capability, version) - 384usize];
     ["Offset of field: capability::function"]
-        [::std::mem::offset_of!(capability, function) - 256usize];
+        [::std::mem::offset_of!(capability, function) - 416usize];
 };
 pub type Capability = capability;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct capabilities_ {
-    pub capability: [Capability; 20usize],
+    pub capability: *mut Capability,
     pub n_capabilities: ::std::os::raw::c_int,
 }
 #[allow(clippy::unnecessary_operation, clippy::identity_op)]
 const _: () = {
-    ["Size of capabilities_"][::std::mem::size_of::<capabilities_>() - 5288usize];
+    ["Size of capabilities_"][::std::mem::size_of::<capabilities_>() - 16usize];
     ["Alignment of capabilities_"][::std::mem::align_of::<capabilities_>() - 8usize];
     ["Offset of field: capabilities_::capability"]
         [::std::mem::offset_of!(capabilities_, capability) - 0usize];
     ["Offset of field: capabilities_::n_capabilities"]
-        [::std::mem::offset_of!(capabilities_, n_capabilities) - 5280usize];
+        [::std::mem::offset_of!(capabilities_, n_capabilities) - 8usize];
 };
 pub type Capabilities = capabilities_;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct legacy_capability {
+    pub name: [::std::os::raw::c_char; 256usize],
+    pub function: *mut ::std::os::raw::c_void,
+}
+#[allow(clippy::unnecessary_operation, clippy::identity_op)]
+const _: () = {
+    ["Size of legacy_capability"][::std::mem::size_of::<legacy_capability>() - 264usize];
+    ["Alignment of legacy_capability"][::std::mem::align_of::<legacy_capability>() - 8usize];
+    ["Offset of field: legacy_capability::name"]
+        [::std::mem::offset_of!(legacy_capability, name) - 0usize];
+    ["Offset of field: legacy_capability::function"]
+        [::std::mem::offset_of!(legacy_capability, function) - 256usize];
+};
+pub type LegacyCapability = legacy_capability;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct legacy_capabilities_ {
+    pub capability: [LegacyCapability; 64usize],
+    pub n_capabilities: ::std::os::raw::c_int,
+}
+#[allow(clippy::unnecessary_operation, clippy::identity_op)]
+const _: () = {
+    ["Size of legacy_capabilities_"][::std::mem::size_of::<legacy_capabilities_>() - 16904usize];
+    ["Alignment of legacy_capabilities_"][::std::mem::align_of::<legacy_capabilities_>() - 8usize];
+    ["Offset of field: legacy_capabilities_::capability"]
+        [::std::mem::offset_of!(legacy_capabilities_, capability) - 0usize];
+    ["Offset of field: legacy_capabilities_::n_capabilities"]
+        [::std::mem::offset_of!(legacy_capabilities_, n_capabilities) - 16896usize];
+};
+pub type LegacyCapabilities = legacy_capabilities_;
+pub type rt_completion = ::std::option::Option<
+    unsafe extern "C" fn(
+        handle: ::std::os::raw::c_int,
+        result: ::std::os::raw::c_int,
+        user_data: *mut ::std::os::raw::c_void,
+    ),
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rt_context {
+    pub state: *mut ::std::os::raw::c_void,
+    pub release: ::std::option::Option<unsafe extern "C" fn(state: *mut ::std::os::raw::c_void)>,
+    pub instance: *const ::std::os::raw::c_char,
+}
+#[allow(clippy::unnecessary_operation, clippy::identity_op)]
+const _: () = {
+    ["Size of rt_context"][::std::mem::size_of::<rt_context>() - 24usize];
+    ["Alignment of rt_context"][::std::mem::align_of::<rt_context>() - 8usize];
+    ["Offset of field: rt_context::state"][::std::mem::offset_of!(rt_context, state) - 0usize];
+    ["Offset of field: rt_context::release"]
+        [::std::mem::offset_of!(rt_context, release) - 8usize];
+    ["Offset of field: rt_context::instance"]
+        [::std::mem::offset_of!(rt_context, instance) - 16usize];
+};
@@ -1,20 +1,29 @@
+use crate::status::{RtError, RtStatus};
 use serde::{Deserialize, Serialize};
 use std::any::Any;
+use std::ffi::{c_char, CStr};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
 pub enum BlackboardValue {
     String(String),
     Int(i32),
+    Int64(i64), // integers not fitting into i32, must stay in front of the floats
     Float(f32),
     Double(f64),
     Bool(bool),
+    Array(Vec<BlackboardValue>),
+    Json(serde_json::Value), // must stay last, untagged matching would catch everything otherwise
 }
 
 impl BlackboardValue {
     pub fn from_any(value: &dyn Any) -> Option<Self> {
         if let Some(&v) = value.downcast_ref::<i32>() {
             Some(BlackboardValue::Int(v))
+        } else if let Some(&v) = value.downcast_ref::<i64>() {
+            Some(BlackboardValue::Int64(v))
+        } else if let Some(&v) = value.downcast_ref::<Timestamp>() {
+            Some(BlackboardValue::Int64(v.0 as i64))
         } else if let Some(&v) = value.downcast_ref::<f32>() {
             Some(BlackboardValue::Float(v))
         } else if let Some(&v) = value.downcast_ref::<f64>() {
@@ -23,16 +32,324 @@ impl BlackboardValue {
             Some(BlackboardValue::String(v.clone()))
         } else if let Some(&v) = value.downcast_ref::<bool>() {
             Some(BlackboardValue::Bool(v))
+        } else if let Some(v) = value.downcast_ref::<Vec<i32>>() {
+            Some(BlackboardValue::Array(
+                v.iter().map(|&v| BlackboardValue::Int(v)).collect(),
+            ))
+        } else if let Some(v) = value.downcast_ref::<Vec<f64>>() {
+            Some(BlackboardValue::Array(
+                v.iter().map(|&v| BlackboardValue::Double(v)).collect(),
+            ))
         } else {
-            None // Unsupported type
+            // None for unsupported types
+            value.downcast_ref::<serde_json::Value>().map(|v| BlackboardValue::Json(v.clone()))
         }
     }
 }
 
+/// Point in time as nanoseconds of the monotonic clock (`CLOCK_MONOTONIC`), stored apart from
+/// plain 64 bit integers so consumers know how to interpret it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Timestamp(pub u64);
+
+/// Type preserving counterpart of `BlackboardValue`, used where values have to survive a
+/// round trip unchanged (snapshots, bulk transfer). Serialized as `{"type": ..., "value": ...}`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum TypedBlackboardValue {
+    String(String),
+    Int(i32),
+    Int64(i64),
+    Timestamp(u64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    IntArray(Vec<i32>),
+    DoubleArray(Vec<f64>),
+    Json(serde_json::Value),
+    Bytes(#[serde(with = "base64_bytes")] Vec<u8>),
+}
+
+// binary values are written as base64 strings to keep text snapshots compact
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+impl TypedBlackboardValue {
+    pub fn from_any(value: &dyn Any) -> Option<Self> {
+        if let Some(&v) = value.downcast_ref::<i32>() {
+            Some(TypedBlackboardValue::Int(v))
+        } else if let Some(&v) = value.downcast_ref::<i64>() {
+            Some(TypedBlackboardValue::Int64(v))
+        } else if let Some(&v) = value.downcast_ref::<Timestamp>() {
+            Some(TypedBlackboardValue::Timestamp(v.0))
+        } else if let Some(&v) = value.downcast_ref::<f32>() {
+            Some(TypedBlackboardValue::Float(v))
+        } else if let Some(&v) = value.downcast_ref::<f64>() {
+            Some(TypedBlackboardValue::Double(v))
+        } else if let Some(v) = value.downcast_ref::<String>() {
+            Some(TypedBlackboardValue::String(v.clone()))
+        } else if let Some(&v) = value.downcast_ref::<bool>() {
+            Some(TypedBlackboardValue::Bool(v))
+        } else if let Some(v) = value.downcast_ref::<Vec<i32>>() {
+            Some(TypedBlackboardValue::IntArray(v.clone()))
+        } else if let Some(v) = value.downcast_ref::<Vec<f64>>() {
+            Some(TypedBlackboardValue::DoubleArray(v.clone()))
+        } else if let Some(v) = value.downcast_ref::<serde_json::Value>() {
+            Some(TypedBlackboardValue::Json(v.clone()))
+        } else {
+            value
+                .downcast_ref::<Vec<u8>>()
+                .map(|v| TypedBlackboardValue::Bytes(v.clone()))
+        }
+    }
+}
+
+impl TypedBlackboardValue {
+    /// Name of the variant as written to the `type` field.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TypedBlackboardValue::String(_) => "string",
+            TypedBlackboardValue::Int(_) => "int",
+            TypedBlackboardValue::Int64(_) => "int64",
+            TypedBlackboardValue::Timestamp(_) => "timestamp",
+            TypedBlackboardValue::Float(_) => "float",
+            TypedBlackboardValue::Double(_) => "double",
+            TypedBlackboardValue::Bool(_) => "bool",
+            TypedBlackboardValue::IntArray(_) => "int_array",
+            TypedBlackboardValue::DoubleArray(_) => "double_array",
+            TypedBlackboardValue::Json(_) => "json",
+            TypedBlackboardValue::Bytes(_) => "bytes",
+        }
+    }
+
+    /// Converts numeric values to the numeric type of `like`, e.g. an untyped `1.5` parsed as
+    /// `Float` becomes a `Double` when it updates a double. Other values are returned unchanged.
+    pub fn coerce_to(self, like: &TypedBlackboardValue) -> TypedBlackboardValue {
+        use TypedBlackboardValue::*;
+        match (self, like) {
+            (Int(v), Int64(_)) => Int64(v as i64),
+            (Int(v), Timestamp(_)) if v >= 0 => Timestamp(v as u64),
+            (Int64(v), Timestamp(_)) if v >= 0 => Timestamp(v as u64),
+            (Int(v), Float(_)) => Float(v as f32),
+            (Int(v), Double(_)) => Double(v as f64),
+            (Float(v), Double(_)) => Double(v as f64),
+            (IntArray(v), DoubleArray(_)) => DoubleArray(v.into_iter().map(|v| v as f64).collect()),
+            (value, _) => value,
+        }
+    }
+}
+
+impl TryFrom<BlackboardValue> for TypedBlackboardValue {
+    type Error = String;
+
+    /// Arrays are stored homogeneously: integers stay integers, any float promotes the array to
+    /// doubles and mixed or nested arrays are kept as json.
+    fn try_from(value: BlackboardValue) -> Result<Self, Self::Error> {
+        Ok(match value {
+            BlackboardValue::String(v) => TypedBlackboardValue::String(v),
+            BlackboardValue::Int(v) => TypedBlackboardValue::Int(v),
+            BlackboardValue::Int64(v) => TypedBlackboardValue::Int64(v),
+            BlackboardValue::Float(v) => TypedBlackboardValue::Float(v),
+            BlackboardValue::Double(v) => TypedBlackboardValue::Double(v),
+            BlackboardValue::Bool(v) => TypedBlackboardValue::Bool(v),
+            BlackboardValue::Array(values) => {
+                if values.iter().all(|v| matches!(v, BlackboardValue::Int(_))) {
+                    TypedBlackboardValue::IntArray(
+                        values
+                            .iter()
+                            .map(|v| match v {
+                                BlackboardValue::Int(v) => *v,
+                                _ => unreachable!(),
+                            })
+                            .collect(),
+                    )
+                } else if values.iter().all(|v| {
+                    matches!(
+                        v,
+                        BlackboardValue::Int(_) | BlackboardValue::Float(_) | BlackboardValue::Double(_)
+                    )
+                }) {
+                    TypedBlackboardValue::DoubleArray(
+                        values
+                            .iter()
+                            .map(|v| match v {
+                                BlackboardValue::Int(v) => *v as f64,
+                                BlackboardValue::Float(v) => *v as f64,
+                                BlackboardValue::Double(v) => *v,
+                                _ => unreachable!(),
+                            })
+                            .collect(),
+                    )
+                } else {
+                    TypedBlackboardValue::Json(
+                        serde_json::to_value(BlackboardValue::Array(values))
+                            .map_err(|e| e.to_string())?,
+                    )
+                }
+            }
+            BlackboardValue::Json(v) => TypedBlackboardValue::Json(v),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TypedBlackboardEntry {
+    pub key: String,
+    #[serde(flatten)]
+    pub value: TypedBlackboardValue,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BlackboardEntry {
     pub key: String,
     pub value: BlackboardValue,
 }
 
-pub type BlackboardEntries = Vec<BlackboardEntry>;
\ No newline at end of file
+pub type BlackboardEntries = Vec<BlackboardEntry>;
+
+/// The entries of the start attributes the loader passes to a plugin as yaml, e.g.
+/// `[{key: port, value: 8080}]`. None are no entries.
+///
+/// # Safety
+///
+/// `attributes` must be null or a null terminated string, like the one passed to `start`.
+pub unsafe fn parse_attributes(attributes: *const c_char) -> Result<BlackboardEntries, RtError> {
+    if attributes.is_null() {
+        return Ok(Vec::new());
+    }
+    let attributes = CStr::from_ptr(attributes)
+        .to_str()
+        .map_err(|e| format!("Cannot convert incoming attributes to string: {}", e))?;
+    serde_yml::from_str(attributes)
+        .map_err(|e| RtError::new(RtStatus::InvalidArgument, e.to_string()))
+}
+
+/// Whether `key` is selected by `filter`, a filter ending with `*` matches every key starting
+/// with the part before.
+pub fn key_matches(filter: &str, key: &str) -> bool {
+    match filter.strip_suffix('*') {
+        Some(prefix) => key.starts_with(prefix),
+        None => filter == key,
+    }
+}
+
+/// Key listing returned by `blackboard_keys`, `value_type` uses the type names of
+/// `TypedBlackboardValue`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BlackboardKeyInfo {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub value_type: String,
+}
+
+/// Usage counters of a key returned by `blackboard_stats`. `last_write` is given in
+/// milliseconds since the unix epoch.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BlackboardKeyStats {
+    pub key: String,
+    pub writes: u64,
+    pub reads: u64,
+    pub last_write: u64,
+    pub subscribers: usize,
+}
+
+/// Value of a key kept by the `history` of the blackboard, returned by
+/// `blackboard_get_history_since`. `time` of the write is given in milliseconds since the unix
+/// epoch.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistorySample {
+    pub time: u64,
+    pub value: TypedBlackboardValue,
+}
+
+/// Write recorded by the `audit` log of the blackboard, returned by `blackboard_get_audit_log`.
+/// `revision` counts the writes of the blackboard, `time` is given in milliseconds since the
+/// unix epoch and `component` is the writer, if it opened a session.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub revision: u64,
+    pub time: u64,
+    pub key: String,
+    pub old: Option<TypedBlackboardValue>,
+    pub new: TypedBlackboardValue,
+    pub component: Option<String>,
+}
+
+/// Operation of `blackboard_transaction`, e.g.
+/// `{"op": "set", "key": "speed", "value": {"type": "double", "value": 2.5}}` or
+/// `{"op": "delete", "key": "speed"}`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BlackboardOperation {
+    Set {
+        key: String,
+        value: TypedBlackboardValue,
+    },
+    Delete {
+        key: String,
+    },
+}
+
+impl BlackboardOperation {
+    pub fn key(&self) -> &str {
+        match self {
+            BlackboardOperation::Set { key, .. } | BlackboardOperation::Delete { key } => key,
+        }
+    }
+}
+
+/// Result of an operation of `blackboard_transaction`. Operations are applied all or none,
+/// `error` tells why one could not be, the others are not applied without an error of their own.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BlackboardOperationResult {
+    pub key: String,
+    pub applied: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Options of `blackboard_subscribe_with_options`, passed as json, e.g.
+/// `{"max_rate": 10, "coalesce": true}`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rate: Option<f64>, // notifications per second and key at most
+    #[serde(default)]
+    pub coalesce: bool, // the latest change skipped by `max_rate` follows once it is due
+    #[serde(default)]
+    pub event: bool, // the callback takes the event like with `subscribe_v2`
+}
+
+/// Why subscribers of a key are notified.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyReason {
+    Changed,
+    Expired,
+    Deleted,
+}
+
+/// Payload handed to `subscribe_v2` callbacks as json, captured while the change is applied.
+/// Values without a `BlackboardValue` representation (binary blobs) are reported as `null`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlackboardEvent {
+    pub key: String,
+    pub reason: NotifyReason,
+    pub old: Option<BlackboardValue>,
+    pub new: Option<BlackboardValue>,
+    /// JSON pointer of the field changed by `set_json_path`, e.g. `/pose/x`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
\ No newline at end of file
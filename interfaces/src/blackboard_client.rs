@@ -0,0 +1,591 @@
+use crate::blackboard::{
+    AuditRecord, BlackboardEntries, BlackboardKeyInfo, BlackboardOperation,
+    BlackboardOperationResult, HistorySample, SubscribeOptions, TypedBlackboardValue,
+};
+use crate::capabilities::{Capabilities, Function, SharedCapabilities};
+use crate::messages::{self, Encoding, Message};
+use crate::signature::Signature;
+use crate::status::{RtError, RtStatus};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+type GetStringFn = unsafe extern "C" fn(*const c_char, *mut c_char) -> c_int;
+type GetStringV2Fn = unsafe extern "C" fn(*const c_char, *mut c_char, c_int, *mut c_int) -> c_int;
+type SetStringFn = unsafe extern "C" fn(*const c_char, *const c_char) -> c_int;
+type GetIntFn = unsafe extern "C" fn(*const c_char, *mut i32) -> c_int;
+type SetIntFn = unsafe extern "C" fn(*const c_char, i32) -> c_int;
+type SetDoubleTaggedFn = unsafe extern "C" fn(*const c_char, f64, *const c_char, c_int) -> c_int;
+type SetValueTaggedFn =
+    unsafe extern "C" fn(*const c_char, *const c_char, *const c_char, c_int) -> c_int;
+type GetJsonPathFn = unsafe extern "C" fn(*const c_char, *const c_char, *mut c_char) -> c_int;
+type SetJsonPathFn = unsafe extern "C" fn(*const c_char, *const c_char, *const c_char) -> c_int;
+type SubscribeFn =
+    unsafe extern "C" fn(*const c_char, *const c_char, *mut c_void, *mut c_void) -> c_int;
+type SubscribeWithOptionsFn = unsafe extern "C" fn(
+    *const c_char,
+    *const c_char,
+    *mut c_void,
+    *mut c_void,
+    *const c_char,
+) -> c_int;
+type UnsubscribeFn = unsafe extern "C" fn(*const c_char, *const c_char) -> c_int;
+type FlushFn = unsafe extern "C" fn() -> c_int;
+type RevokeSubscriptionsFn = unsafe extern "C" fn(*const c_void) -> c_int;
+type SetBatchFn = unsafe extern "C" fn(*const c_char) -> c_int;
+type DeleteFn = unsafe extern "C" fn(*const c_char) -> c_int;
+type KeysFn = unsafe extern "C" fn(*mut c_char) -> c_int;
+type AsJsonSchemaFn = unsafe extern "C" fn(*mut c_char) -> c_int;
+type ExportFn = unsafe extern "C" fn(*mut u8, c_int) -> c_int;
+type ImportFn = unsafe extern "C" fn(*const u8, c_int) -> c_int;
+type TransactionFn = unsafe extern "C" fn(*const c_char, *mut c_char, c_int) -> c_int;
+type GetHistorySinceFn = unsafe extern "C" fn(*const c_char, u64, *mut c_char, c_int) -> c_int;
+type GetAuditLogFn = unsafe extern "C" fn(u64, *mut c_char, c_int) -> c_int;
+type GetLastErrorFn = unsafe extern "C" fn(*mut c_char, c_int) -> c_int;
+
+type SubscriberFn = Box<dyn FnMut(&str) + Send>;
+
+/// Called with the name and duration of every blackboard call, e.g. to export latencies.
+pub type CallTimer = Arc<dyn Fn(&str, Duration) + Send + Sync>;
+
+fn c_string(s: &str) -> Result<CString, String> {
+    CString::new(s).map_err(|e| format!("Invalid string '{}': {}", s, e))
+}
+
+// passed to the blackboard as callback, `user_data` is the boxed closure of the subscription
+extern "C" fn notify_subscriber(key: *const c_char, user_data: *mut c_void) -> c_int {
+    if key.is_null() || user_data.is_null() {
+        return -1;
+    }
+    let callback = unsafe { &mut *(user_data as *mut SubscriberFn) };
+    let key = unsafe { CStr::from_ptr(key) }.to_string_lossy();
+    callback(&key);
+    0
+}
+
+/// Safe access to the blackboard through the capabilities it provides.
+pub struct BlackboardClient {
+    caps: SharedCapabilities, // every call looks up the current table
+    timer: Option<CallTimer>,
+}
+
+impl BlackboardClient {
+    /// A client of `caps`, shared ones let the owner replace the blackboard capabilities later.
+    pub fn new(caps: impl Into<SharedCapabilities>) -> Self {
+        BlackboardClient {
+            caps: caps.into(),
+            timer: None,
+        }
+    }
+
+    /// Reports the duration of every call to the blackboard to `timer`.
+    pub fn with_timer(mut self, timer: CallTimer) -> Self {
+        self.timer = Some(timer);
+        self
+    }
+
+    /// The current capabilities of the client.
+    pub fn caps(&self) -> Arc<Capabilities> {
+        self.caps.snapshot()
+    }
+
+    /// The capabilities of the client, e.g. to replace them once the blackboard is reloaded.
+    pub fn shared_caps(&self) -> &SharedCapabilities {
+        &self.caps
+    }
+
+    // turns an error status into a message, preferring the reason reported by the blackboard
+    fn check(&self, name: &str, key: &str, result: c_int) -> Result<c_int, RtError> {
+        if result >= 0 {
+            return Ok(result);
+        }
+        let status = RtStatus::from_code(result);
+        let reason = self.last_error().unwrap_or_else(|| status.to_string());
+        Err(RtError::new(
+            status,
+            format!("{} failed for key '{}' ({}): {}", name, key, result, reason),
+        ))
+    }
+
+    // runs one call into the blackboard, timed if there is a timer
+    fn call<F: FnOnce() -> c_int>(&self, name: &str, key: &str, call: F) -> Result<c_int, RtError> {
+        let start = Instant::now();
+        let result = call();
+        if let Some(timer) = &self.timer {
+            timer(name, start.elapsed());
+        }
+        self.check(name, key, result)
+    }
+
+    /// Message of the last failed blackboard call on this thread.
+    pub fn last_error(&self) -> Option<String> {
+        let f: Function<GetLastErrorFn> = self.function("blackboard_get_last_error").ok()?;
+        let size = unsafe { f(std::ptr::null_mut(), 0) };
+        if size <= 1 {
+            return None;
+        }
+        let mut buffer = vec![0u8; size as usize];
+        unsafe { f(buffer.as_mut_ptr() as *mut c_char, size) };
+        CStr::from_bytes_until_nul(&buffer)
+            .ok()
+            .map(|message| message.to_string_lossy().into_owned())
+    }
+
+    fn function<T: Signature>(&self, name: &str) -> Result<Function<T>, String> {
+        let cap = self
+            .caps
+            .snapshot()
+            .get(name)
+            .ok_or_else(|| format!("Blackboard capability '{}' is not available", name))?;
+        unsafe { cap.get() }
+    }
+
+    /// Value of a string key, read with `blackboard_get_string_v2`, which never writes past the
+    /// buffer. Blackboards without it are read with `blackboard_get_string`.
+    pub fn get_string(&self, key: &str) -> Result<String, String> {
+        if self.caps.snapshot().get("blackboard_get_string_v2").is_none() {
+            return Ok(self.read_text("blackboard_get_string", "get_string", key)?);
+        }
+        Ok(self.read_string(key)?)
+    }
+
+    // a value grown since the buffer was sized is truncated, so it is read again with the size
+    // the blackboard reported
+    fn read_string(&self, key: &str) -> Result<String, RtError> {
+        let f: Function<GetStringV2Fn> = self.function("blackboard_get_string_v2")?;
+        let ckey = c_string(key)?;
+        let mut buffer = vec![0u8; 64];
+        loop {
+            let mut required = 0;
+            let result = self.call("get_string", key, || unsafe {
+                let len = buffer.len() as c_int;
+                f(ckey.as_ptr(), buffer.as_mut_ptr() as *mut c_char, len, &mut required)
+            });
+            match result {
+                Ok(_) => {
+                    buffer.truncate((required as usize).saturating_sub(1));
+                    return String::from_utf8(buffer).map_err(|e| RtError::from(e.to_string()));
+                }
+                Err(e) if e.status == RtStatus::BufferTooSmall => {
+                    let len = (required as usize).max(buffer.len() * 2);
+                    buffer.resize(len, 0);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Value of `key` whatever its type. The status tells a missing key from other errors.
+    pub fn get_value(&self, key: &str) -> Result<TypedBlackboardValue, RtError> {
+        let value = self.read_text("blackboard_get_value", "get_value", key)?;
+        serde_json::from_str(&value)
+            .map_err(|e| RtError::from(format!("Invalid value of key '{}': {}", key, e)))
+    }
+
+    /// Writes `value` keeping its type.
+    pub fn set_value(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), RtError> {
+        let f: Function<SetStringFn> = self.function("blackboard_set_value")?;
+        let value = serde_json::to_string(value).map_err(|e| e.to_string())?;
+        let (ckey, cvalue) = (c_string(key)?, c_string(&value)?);
+        self.call("set_value", key, || unsafe { f(ckey.as_ptr(), cvalue.as_ptr()) })?;
+        Ok(())
+    }
+
+    /// The typed message of `key`, see `messages`.
+    pub fn get_message<T: Message>(&self, key: &str) -> Result<T, RtError> {
+        messages::from_value(&self.get_value(key)?)
+    }
+
+    /// Writes `message` to `key` as json or MessagePack, see `messages`.
+    pub fn set_message<T: Message>(
+        &self,
+        key: &str,
+        message: &T,
+        encoding: Encoding,
+    ) -> Result<(), RtError> {
+        self.set_value(key, &messages::to_value(message, encoding)?)
+    }
+
+    /// Writes `value` on behalf of `source` with `priority`. While the key is held by a writer of
+    /// higher priority, the write fails with `RtStatus::AccessDenied`, see `set_double_tagged`
+    /// of the blackboard.
+    pub fn set_value_tagged(
+        &self,
+        key: &str,
+        value: &TypedBlackboardValue,
+        source: &str,
+        priority: i32,
+    ) -> Result<(), RtError> {
+        let f: Function<SetValueTaggedFn> = self.function("blackboard_set_value_tagged")?;
+        let value = serde_json::to_string(value).map_err(|e| e.to_string())?;
+        let (ckey, cvalue, csource) = (c_string(key)?, c_string(&value)?, c_string(source)?);
+        self.call("set_value_tagged", key, || unsafe {
+            f(ckey.as_ptr(), cvalue.as_ptr(), csource.as_ptr(), priority)
+        })?;
+        Ok(())
+    }
+
+    /// Field at the JSON pointer `pointer`, e.g. `/pose/x`, of the json document under `key`.
+    pub fn get_json_path(&self, key: &str, pointer: &str) -> Result<serde_json::Value, RtError> {
+        let f: Function<GetJsonPathFn> = self.function("blackboard_get_json_path")?;
+        let (ckey, cpointer) = (c_string(key)?, c_string(pointer)?);
+        let size = self.call("get_json_path", key, || unsafe {
+            f(ckey.as_ptr(), cpointer.as_ptr(), std::ptr::null_mut())
+        })?;
+        let mut buffer = vec![0u8; size as usize];
+        let size = self.call("get_json_path", key, || unsafe {
+            f(ckey.as_ptr(), cpointer.as_ptr(), buffer.as_mut_ptr() as *mut c_char)
+        })?;
+        buffer.truncate((size as usize).saturating_sub(1));
+        serde_json::from_slice(&buffer)
+            .map_err(|e| RtError::from(format!("Invalid json of key '{}': {}", key, e)))
+    }
+
+    /// Values of `key` kept by the `history` of the blackboard and written after `since`, in
+    /// milliseconds since the unix epoch, oldest first.
+    pub fn history(&self, key: &str, since: u64) -> Result<Vec<HistorySample>, RtError> {
+        let f: Function<GetHistorySinceFn> = self.function("blackboard_get_history_since")?;
+        let ckey = c_string(key)?;
+        loop {
+            let size = self.call("get_history_since", key, || unsafe {
+                f(ckey.as_ptr(), since, std::ptr::null_mut(), 0)
+            })?;
+            let mut buffer = vec![0u8; size as usize];
+            // values written between both calls may need a larger buffer
+            let written = self.call("get_history_since", key, || unsafe {
+                f(ckey.as_ptr(), since, buffer.as_mut_ptr() as *mut c_char, size)
+            })?;
+            if written <= size {
+                buffer.truncate((written as usize).saturating_sub(1));
+                return serde_json::from_slice(&buffer)
+                    .map_err(|e| RtError::from(format!("Invalid history of key '{}': {}", key, e)));
+            }
+        }
+    }
+
+    /// Writes recorded by the `audit` log of the blackboard after `revision`, oldest first.
+    pub fn audit_log(&self, revision: u64) -> Result<Vec<AuditRecord>, RtError> {
+        let f: Function<GetAuditLogFn> = self.function("blackboard_get_audit_log")?;
+        loop {
+            let size = self.call("get_audit_log", "", || unsafe {
+                f(revision, std::ptr::null_mut(), 0)
+            })?;
+            let mut buffer = vec![0u8; size as usize];
+            // writes between both calls may need a larger buffer
+            let written = self.call("get_audit_log", "", || unsafe {
+                f(revision, buffer.as_mut_ptr() as *mut c_char, size)
+            })?;
+            if written <= size {
+                buffer.truncate((written as usize).saturating_sub(1));
+                return serde_json::from_slice(&buffer)
+                    .map_err(|e| RtError::from(format!("Invalid audit log: {}", e)));
+            }
+        }
+    }
+
+    /// Writes `value` to the field at the JSON pointer `pointer` of the json document under
+    /// `key`, without sending the whole document.
+    pub fn set_json_path(
+        &self,
+        key: &str,
+        pointer: &str,
+        value: &serde_json::Value,
+    ) -> Result<(), RtError> {
+        let f: Function<SetJsonPathFn> = self.function("blackboard_set_json_path")?;
+        let (ckey, cpointer) = (c_string(key)?, c_string(pointer)?);
+        let cvalue = c_string(&value.to_string())?;
+        self.call("set_json_path", key, || unsafe {
+            f(ckey.as_ptr(), cpointer.as_ptr(), cvalue.as_ptr())
+        })?;
+        Ok(())
+    }
+
+    pub fn delete(&self, key: &str) -> Result<(), RtError> {
+        let f: Function<DeleteFn> = self.function("blackboard_delete")?;
+        let ckey = c_string(key)?;
+        self.call("delete", key, || unsafe { f(ckey.as_ptr()) })?;
+        Ok(())
+    }
+
+    /// All keys with the type of their value, sorted by key.
+    pub fn keys(&self) -> Result<Vec<BlackboardKeyInfo>, RtError> {
+        let f: Function<KeysFn> = self.function("blackboard_keys")?;
+        let size = self.call("keys", "", || unsafe { f(std::ptr::null_mut()) })?;
+        let mut buffer = vec![0u8; size as usize];
+        let size = self.call("keys", "", || unsafe { f(buffer.as_mut_ptr() as *mut c_char) })?;
+        buffer.truncate((size as usize).saturating_sub(1));
+        serde_json::from_slice(&buffer).map_err(|e| RtError::from(format!("Invalid keys: {}", e)))
+    }
+
+    /// JSON schema of the blackboard, every key is a property holding its type and value.
+    pub fn schema(&self) -> Result<serde_json::Value, RtError> {
+        let f: Function<AsJsonSchemaFn> = self.function("blackboard_as_json_schema")?;
+        let size = self.call("as_json_schema", "", || unsafe { f(std::ptr::null_mut()) })?;
+        let mut buffer = vec![0u8; size as usize];
+        let size = self.call("as_json_schema", "", || unsafe { f(buffer.as_mut_ptr() as *mut c_char) })?;
+        buffer.truncate((size as usize).saturating_sub(1));
+        serde_json::from_slice(&buffer).map_err(|e| RtError::from(format!("Invalid schema: {}", e)))
+    }
+
+    /// All entries as MessagePack, see `export_msgpack` of the blackboard.
+    pub fn export_msgpack(&self) -> Result<Vec<u8>, RtError> {
+        let f: Function<ExportFn> = self.function("blackboard_export_msgpack")?;
+        loop {
+            let size = self.call("export_msgpack", "", || unsafe { f(std::ptr::null_mut(), 0) })?;
+            let mut buffer = vec![0u8; size as usize];
+            // keys written between both calls may need a larger buffer
+            match self.call("export_msgpack", "", || unsafe { f(buffer.as_mut_ptr(), size) }) {
+                Err(e) if e.status == RtStatus::BufferTooSmall => continue,
+                result => {
+                    buffer.truncate(result? as usize);
+                    return Ok(buffer);
+                }
+            }
+        }
+    }
+
+    /// Writes the entries of an `export_msgpack`.
+    pub fn import_msgpack(&self, packed: &[u8]) -> Result<(), RtError> {
+        let f: Function<ImportFn> = self.function("blackboard_import_msgpack")?;
+        let len = c_int::try_from(packed.len()).map_err(|e| e.to_string())?;
+        self.call("import_msgpack", "", || unsafe { f(packed.as_ptr(), len) })?;
+        Ok(())
+    }
+
+    /// Writes all entries at once, see `set_batch` of the blackboard. Numbers keep the numeric
+    /// type of the key they update.
+    pub fn set_batch(&self, entries: &BlackboardEntries) -> Result<(), String> {
+        let f: Function<SetBatchFn> = self.function("blackboard_set_batch")?;
+        let keys: Vec<&str> = entries.iter().map(|entry| entry.key.as_str()).collect();
+        let entries = serde_json::to_string(entries).map_err(|e| e.to_string())?;
+        let centries = c_string(&entries)?;
+        self.call("set_batch", &keys.join(", "), || unsafe { f(centries.as_ptr()) })?;
+        Ok(())
+    }
+
+    /// Applies the operations all or none, see `transaction` of the blackboard. Returns the
+    /// result of every operation, also when they were not applied.
+    pub fn transaction(
+        &self,
+        operations: &[BlackboardOperation],
+    ) -> Result<Vec<BlackboardOperationResult>, RtError> {
+        let f: Function<TransactionFn> = self.function("blackboard_transaction")?;
+        let keys: Vec<&str> = operations.iter().map(BlackboardOperation::key).collect();
+        let keys = keys.join(", ");
+        let operations = serde_json::to_string(operations).map_err(|e| e.to_string())?;
+        let coperations = c_string(&operations)?;
+        loop {
+            let size = self.call("transaction", &keys, || unsafe {
+                f(coperations.as_ptr(), std::ptr::null_mut(), 0)
+            })?;
+            let mut buffer = vec![0u8; size as usize];
+            // nothing is applied if keys written in between make the results longer
+            let written = self.call("transaction", &keys, || unsafe {
+                f(coperations.as_ptr(), buffer.as_mut_ptr() as *mut c_char, size)
+            })?;
+            if written <= size {
+                buffer.truncate((written as usize).saturating_sub(1));
+                return serde_json::from_slice(&buffer)
+                    .map_err(|e| RtError::from(format!("Invalid transaction results: {}", e)));
+            }
+        }
+    }
+
+    // calls a capability `fn(key, buffer) -> size` filling a null terminated text
+    fn read_text(&self, capability: &str, name: &str, key: &str) -> Result<String, RtError> {
+        let f: Function<GetStringFn> = self.function(capability)?;
+        let ckey = c_string(key)?;
+
+        // the first call only reports the size including the null terminator
+        let size = self.call(name, key, || unsafe {
+            f(ckey.as_ptr(), std::ptr::null_mut())
+        })?;
+        let mut buffer = vec![0u8; size as usize];
+        let size = self.call(name, key, || unsafe {
+            f(ckey.as_ptr(), buffer.as_mut_ptr() as *mut c_char)
+        })?;
+        buffer.truncate((size as usize).saturating_sub(1));
+        String::from_utf8(buffer).map_err(|e| RtError::from(e.to_string()))
+    }
+
+    pub fn set_string(&self, key: &str, value: &str) -> Result<(), String> {
+        let f: Function<SetStringFn> = self.function("blackboard_set_string")?;
+        let (ckey, cvalue) = (c_string(key)?, c_string(value)?);
+        self.call("set_string", key, || unsafe { f(ckey.as_ptr(), cvalue.as_ptr()) })?;
+        Ok(())
+    }
+
+    pub fn get_i32(&self, key: &str) -> Result<i32, String> {
+        let f: Function<GetIntFn> = self.function("blackboard_get_int")?;
+        let ckey = c_string(key)?;
+        let mut value = 0;
+        self.call("get_int", key, || unsafe { f(ckey.as_ptr(), &mut value) })?;
+        Ok(value)
+    }
+
+    pub fn set_i32(&self, key: &str, value: i32) -> Result<(), String> {
+        let f: Function<SetIntFn> = self.function("blackboard_set_int")?;
+        let ckey = c_string(key)?;
+        self.call("set_int", key, || unsafe { f(ckey.as_ptr(), value) })?;
+        Ok(())
+    }
+
+    /// Writes the double `value` on behalf of `source` with `priority`, see `set_value_tagged`.
+    pub fn set_f64_tagged(
+        &self,
+        key: &str,
+        value: f64,
+        source: &str,
+        priority: i32,
+    ) -> Result<(), RtError> {
+        let f: Function<SetDoubleTaggedFn> = self.function("blackboard_set_double_tagged")?;
+        let (ckey, csource) = (c_string(key)?, c_string(source)?);
+        self.call("set_double_tagged", key, || unsafe {
+            f(ckey.as_ptr(), value, csource.as_ptr(), priority)
+        })?;
+        Ok(())
+    }
+
+    /// Calls `callback` with the changed key whenever `key` is written. The callback runs on the
+    /// notification thread of the blackboard until the returned `Subscription` is dropped.
+    pub fn subscribe<F>(&self, key: &str, component: &str, callback: F) -> Result<Subscription, String>
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        self.subscribe_to(key, component, None, callback)
+    }
+
+    /// Subscribes to every key of `keys` and queues the changed keys for the returned receiver,
+    /// so handling them does not hold up the notification thread of the blackboard. The receiver
+    /// ends once the subscriptions are dropped.
+    pub fn subscribe_forwarding<K: AsRef<str>>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+        component: &str,
+    ) -> Result<(Vec<Subscription>, mpsc::Receiver<String>), String> {
+        let (sender, changes) = mpsc::channel::<String>();
+        let subscriptions = keys
+            .into_iter()
+            .map(|key| {
+                let sender = sender.clone();
+                self.subscribe(key.as_ref(), component, move |changed| {
+                    let _ = sender.send(changed.to_string());
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok((subscriptions, changes))
+    }
+
+    /// Like `subscribe`, limited by `options`, e.g. to the latest change every 100 ms with
+    /// `max_rate: Some(10.0)` and `coalesce: true`. The callback always takes the key only.
+    pub fn subscribe_with_options<F>(
+        &self,
+        key: &str,
+        component: &str,
+        options: &SubscribeOptions,
+        callback: F,
+    ) -> Result<Subscription, String>
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        let options = SubscribeOptions {
+            event: false,
+            ..options.clone()
+        };
+        let options = serde_json::to_string(&options).map_err(|e| e.to_string())?;
+        self.subscribe_to(key, component, Some(&options), callback)
+    }
+
+    /// Ends every subscription with its callback in the library containing `address`, e.g. a
+    /// symbol of it, and waits for callbacks running. Returns the number of subscriptions ended.
+    pub fn revoke_subscriptions(&self, address: *const c_void) -> Result<usize, RtError> {
+        let f: Function<RevokeSubscriptionsFn> =
+            self.function("blackboard_revoke_subscriptions")?;
+        let library = format!("{:?}", address);
+        let revoked =
+            self.call("revoke_subscriptions", &library, || unsafe { f(address) })?;
+        Ok(revoked as usize)
+    }
+
+    fn subscribe_to<F>(
+        &self,
+        key: &str,
+        component: &str,
+        options: Option<&str>,
+        callback: F,
+    ) -> Result<Subscription, String>
+    where
+        F: FnMut(&str) + Send + 'static,
+    {
+        let subscribe: Function<SubscribeFn> = self.function("blackboard_subscribe")?;
+        let with_options = match options {
+            Some(options) => Some((
+                self.function::<SubscribeWithOptionsFn>("blackboard_subscribe_with_options")?,
+                c_string(options)?,
+            )),
+            None => None,
+        };
+        let unsubscribe: Function<UnsubscribeFn> = self.function("blackboard_unsubscribe")?;
+        let flush: Option<Function<FlushFn>> = self.function("blackboard_flush").ok();
+
+        let (ckey, ccomponent) = (c_string(key)?, c_string(component)?);
+        let user_data = Box::into_raw(Box::new(Box::new(callback) as SubscriberFn));
+
+        let result = match with_options {
+            Some((subscribe, coptions)) => unsafe {
+                subscribe(
+                    ckey.as_ptr(),
+                    ccomponent.as_ptr(),
+                    notify_subscriber as *mut c_void,
+                    user_data as *mut c_void,
+                    coptions.as_ptr(),
+                )
+            },
+            None => unsafe {
+                subscribe(
+                    ckey.as_ptr(),
+                    ccomponent.as_ptr(),
+                    notify_subscriber as *mut c_void,
+                    user_data as *mut c_void,
+                )
+            },
+        };
+        if let Err(e) = self.check("subscribe", key, result) {
+            drop(unsafe { Box::from_raw(user_data) });
+            return Err(e.into());
+        }
+
+        Ok(Subscription {
+            unsubscribe,
+            flush,
+            key: ckey,
+            component: ccomponent,
+            user_data,
+        })
+    }
+}
+
+/// Active subscription created by `BlackboardClient::subscribe`, unsubscribes when dropped.
+pub struct Subscription {
+    unsubscribe: Function<UnsubscribeFn>,
+    flush: Option<Function<FlushFn>>,
+    key: CString,
+    component: CString,
+    user_data: *mut SubscriberFn,
+}
+
+unsafe impl Send for Subscription {}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        unsafe {
+            (self.unsubscribe)(self.key.as_ptr(), self.component.as_ptr());
+            // notifications queued before unsubscribing may still use the closure
+            if let Some(flush) = &self.flush {
+                flush();
+            }
+            drop(Box::from_raw(self.user_data));
+        }
+    }
+}
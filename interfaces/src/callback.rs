@@ -0,0 +1,143 @@
+// Channels fed by the C callbacks of services. A subscription passes an `extern "C" fn` and a
+// `user_data` pointer; the `CallbackChannel` owns what `user_data` points to and its trampolines
+// forward every call into an mpsc channel:
+//
+//     let channel = CallbackChannel::<String>::new();
+//     subscribe(key, component, CallbackChannel::<String>::key_callback(), channel.user_data());
+//     let changed = channel.recv_timeout(Duration::from_secs(1));
+//     unsubscribe(key, component);
+//     flush();
+//     drop(channel);
+//
+// The callback may run until the service is done with `user_data`, so the channel is dropped
+// after unsubscribing and flushing the queued notifications, never before.
+use crate::blackboard::BlackboardEvent;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::mpsc;
+
+/// Receiving end of the calls of a C callback, see `user_data`.
+pub struct CallbackChannel<T> {
+    sender: NonNull<mpsc::Sender<T>>, // handed out as `user_data`, released on drop
+    receiver: mpsc::Receiver<T>,
+}
+
+// the sender is only shared with the callbacks, which run on any thread
+unsafe impl<T: Send> Send for CallbackChannel<T> {}
+
+impl<T: Send> CallbackChannel<T> {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        CallbackChannel {
+            sender: NonNull::from(Box::leak(Box::new(sender))),
+            receiver,
+        }
+    }
+
+    /// Pointer passed along with the callback, valid until the channel is dropped.
+    pub fn user_data(&self) -> *mut c_void {
+        self.sender.as_ptr() as *mut c_void
+    }
+
+    /// Forwards `value` from a callback to the channel of `user_data`. Returns 0, or -1 for a null
+    /// `user_data`, like a failed callback.
+    ///
+    /// # Safety
+    /// `user_data` has to be null or the `user_data` of a `CallbackChannel<T>` not dropped yet.
+    pub unsafe fn send(user_data: *mut c_void, value: T) -> c_int {
+        let Some(sender) = (user_data as *const mpsc::Sender<T>).as_ref() else {
+            return -1;
+        };
+        // nobody waiting for the value is no failure of the callback
+        let _ = sender.send(value);
+        0
+    }
+}
+
+impl CallbackChannel<String> {
+    /// Callback of `subscribe`, `i32(cstr,*mut void)`, sending the changed key.
+    pub fn key_callback() -> *mut c_void {
+        forward_key as *mut c_void
+    }
+}
+
+impl CallbackChannel<BlackboardEvent> {
+    /// Callback of `subscribe_v2` and of `subscribe_with_options` with `event: true`,
+    /// `i32(cstr,cstr,*mut void)`, sending the event.
+    pub fn event_callback() -> *mut c_void {
+        forward_event as *mut c_void
+    }
+}
+
+impl<T: Send> Default for CallbackChannel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Deref for CallbackChannel<T> {
+    type Target = mpsc::Receiver<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+impl<T> Drop for CallbackChannel<T> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.sender.as_ptr()) });
+    }
+}
+
+extern "C" fn forward_key(key: *const c_char, user_data: *mut c_void) -> c_int {
+    if key.is_null() {
+        return -1;
+    }
+    let key = unsafe { CStr::from_ptr(key) }.to_string_lossy().into_owned();
+    unsafe { CallbackChannel::send(user_data, key) }
+}
+
+extern "C" fn forward_event(
+    _key: *const c_char,
+    event: *const c_char,
+    user_data: *mut c_void,
+) -> c_int {
+    if event.is_null() {
+        return -1;
+    }
+    let event = unsafe { CStr::from_ptr(event) }.to_string_lossy();
+    match serde_json::from_str::<BlackboardEvent>(&event) {
+        Ok(event) => unsafe { CallbackChannel::send(user_data, event) },
+        Err(_) => -1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_forward() {
+        type KeyCallback = extern "C" fn(*const c_char, *mut c_void) -> c_int;
+        type EventCallback = extern "C" fn(*const c_char, *const c_char, *mut c_void) -> c_int;
+
+        let keys = CallbackChannel::<String>::new();
+        let callback: KeyCallback =
+            unsafe { std::mem::transmute(CallbackChannel::<String>::key_callback()) };
+        assert_eq!(callback(c"answer".as_ptr(), keys.user_data()), 0);
+        assert_eq!(keys.recv_timeout(Duration::from_secs(1)).unwrap(), "answer");
+        assert_eq!(callback(c"answer".as_ptr(), std::ptr::null_mut()), -1);
+        assert_eq!(callback(std::ptr::null(), keys.user_data()), -1);
+
+        let events = CallbackChannel::<BlackboardEvent>::new();
+        let callback: EventCallback =
+            unsafe { std::mem::transmute(CallbackChannel::<BlackboardEvent>::event_callback()) };
+        let event = cr#"{"key": "answer", "reason": "changed", "old": null, "new": 42}"#;
+        assert_eq!(callback(c"answer".as_ptr(), event.as_ptr(), events.user_data()), 0);
+        let event = events.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(event.key, "answer");
+        assert_eq!(callback(c"answer".as_ptr(), c"{".as_ptr(), events.user_data()), -1);
+    }
+}
@@ -1,5 +1,14 @@
-use std::{os::raw::c_void, marker, iter};
-use crate::bindings::{self, CAPABILITY_FUNCTION_NAME_LEN};
+use std::{os::raw::{c_char, c_int, c_void}, marker, iter};
+use std::ffi::CString;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::Duration;
+use crate::bindings::{
+    self, CAPABILITY_FUNCTION_NAME_LEN, CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES,
+    CAPABILITY_SIGNATURE_LEN, CAPABILITY_VERSION_LEN,
+};
+use crate::signature::{self, Signature};
+use crate::status::{RtError, RtStatus};
 
 // reimplementation of libloading::Function to allow custom getter
 pub struct Function<T> { // we admit here that the lifetime of the function is less than the lifetime of the library
@@ -30,41 +39,55 @@ impl <T> Clone for Function<T> {
 }
 
 unsafe impl Send for bindings::Capability {}
+unsafe impl Send for bindings::LegacyCapabilities {}
 unsafe impl Sync for bindings::Capability {}
 
 pub struct Capability (bindings::Capability);
 
 
-fn capability_name(cap: &bindings::Capability) -> String {
-    let mut name = String::new();
-    for i in 0..CAPABILITY_FUNCTION_NAME_LEN as usize {
-        if cap.name[i] == 0 {
+fn c_string(chars: &[i8]) -> String {
+    let mut s = String::new();
+    for c in chars {
+        if *c == 0 {
             break;
         }
-        name.push(cap.name[i] as u8 as char);
+        s.push(*c as u8 as char);
     }
-    name
+    s
+}
+
+// copies `s` into `chars`, truncated so the null terminator still fits
+fn copy_c_string(chars: &mut [i8], s: &str) {
+    let bytes = s.as_bytes();
+    let len = bytes.len().min(chars.len() - 1);
+    for i in 0..len {
+        chars[i] = bytes[i] as i8;
+    }
+}
+
+fn capability_name(cap: &bindings::Capability) -> String {
+    c_string(&cap.name)
 }
 
 unsafe impl Send for Capability {}
 unsafe impl Sync for Capability {}
 
 impl Capability {
+    /// Capability without a declared signature, `get` does not check the requested type.
     pub fn new(name: &str, function: *mut c_void) -> Self {
+        Self::with_signature(name, function, "")
+    }
+
+    /// Capability whose function has the given signature, e.g. `"i32(cstr,cstr)"`.
+    pub fn with_signature(name: &str, function: *mut c_void, signature: &str) -> Self {
         let mut cap = bindings::Capability {
             name: [0; CAPABILITY_FUNCTION_NAME_LEN as usize],
+            signature: [0; CAPABILITY_SIGNATURE_LEN as usize],
+            version: [0; CAPABILITY_VERSION_LEN as usize],
             function: function,
         };
-        let name_bytes = name.as_bytes();
-
-        let name_len = if name_bytes.len() + 1 > CAPABILITY_FUNCTION_NAME_LEN as usize {
-            CAPABILITY_FUNCTION_NAME_LEN as usize - 1 // leave space for null terminator
-        } else {
-            name_bytes.len()
-        };
-        for i in 0..name_len {
-            cap.name[i] = name_bytes[i] as i8;
-        }
+        copy_c_string(&mut cap.name, name);
+        copy_c_string(&mut cap.signature, &signature::normalize(signature));
 
         Capability(cap)
     }
@@ -77,11 +100,41 @@ impl Capability {
         capability_name(&self.0)
     }
 
-    pub unsafe fn get<T>(&self) -> Result<Function<T>, String> {
+    pub fn signature(&self) -> String {
+        c_string(&self.0.signature)
+    }
+
+    pub fn version(&self) -> String {
+        c_string(&self.0.version)
+    }
+
+    pub fn set_version(&mut self, version: &str) {
+        self.0.version = [0; CAPABILITY_VERSION_LEN as usize];
+        copy_c_string(&mut self.0.version, version.trim());
+    }
+
+    /// Fails if the function pointer is null or the declared signature differs from `T`.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the `extern "C"` function pointer type the capability was registered with.
+    /// Without a declared signature nothing is checked, and calling a pointer of another type is
+    /// undefined behaviour. The returned function must not be called after its library is
+    /// unloaded.
+    pub unsafe fn get<T: Signature>(&self) -> Result<Function<T>, String> {
         let function = self.0.function;
         if function.is_null() {
             return Err("Function pointer is null".to_string());
         }
+        let declared = self.signature();
+        if !declared.is_empty() && declared != signature::normalize(&T::signature()) {
+            return Err(format!(
+                "Capability {} has signature {}, requested {}",
+                self.name(),
+                declared,
+                T::signature()
+            ));
+        }
         Ok(Function {
             pointer: function,
             pd: marker::PhantomData,
@@ -94,31 +147,183 @@ impl Capability {
 
 }
 
+/// A reference on the library providing capabilities, held by every table containing some of
+/// them. The loader unloads a library only once no lease on it is held anymore; tables a plugin
+/// copies with `from_raw` hold none.
+#[derive(Debug, Clone, Default)]
+pub struct Lease(Arc<()>);
+
+impl Lease {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of the other leases on the same library.
+    pub fn holders(&self) -> usize {
+        Arc::strong_count(&self.0) - 1
+    }
+
+    /// Whether both are leases on the same library.
+    pub fn same(&self, other: &Lease) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Owns a growable capability table and keeps the C view (`inner`) pointing at it.
 #[derive(Debug)]
-pub struct Capabilities (bindings::Capabilities);
+pub struct Capabilities {
+    table: Vec<bindings::Capability>,
+    raw: bindings::Capabilities,
+    leases: Vec<Lease>, // on the libraries providing the table, kept by clones
+}
 
 impl Capabilities {
     pub fn new() -> Self {
-        Capabilities(bindings::Capabilities {
-            capability: [Capability::new("", std::ptr::null_mut()).inner().clone(); 20],
-            n_capabilities: 0,
-        })
+        Self::from_table(Vec::new())
+    }
+
+    fn from_table(table: Vec<bindings::Capability>) -> Self {
+        let mut caps = Capabilities {
+            table,
+            raw: bindings::Capabilities {
+                capability: std::ptr::null_mut(),
+                n_capabilities: 0,
+            },
+            leases: Vec::new(),
+        };
+        caps.sync();
+        caps
     }
 
+    // the table may have moved or changed its length
+    fn sync(&mut self) {
+        self.raw.capability = self.table.as_mut_ptr();
+        self.raw.n_capabilities = self.table.len() as c_int;
+    }
+
+    /// Copies the table handed over by the loader, which is only valid during the call.
     pub fn from_raw(cap: &bindings::Capabilities) -> Self {
-        Capabilities(cap.clone())
+        let table = if cap.capability.is_null() || cap.n_capabilities <= 0 {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(cap.capability, cap.n_capabilities as usize) }
+                .to_vec()
+        };
+        Self::from_table(table)
     }
 
-    pub fn add(&mut self, cap: Capability) {
-        if self.0.n_capabilities < 20 {
-            self.0.capability[self.0.n_capabilities as usize] = cap.inner().clone();
-            self.0.n_capabilities += 1;
+    pub fn from_legacy(cap: &bindings::LegacyCapabilities) -> Self {
+        let n = (cap.n_capabilities.max(0) as usize).min(cap.capability.len());
+        let table = cap.capability[..n]
+            .iter()
+            .map(|legacy| *Capability::new(&c_string(&legacy.name), legacy.function).inner())
+            .collect();
+        Self::from_table(table)
+    }
+
+    /// Fixed size table for plugins built against the previous layout of `Capabilities`. Legacy
+    /// capabilities carry no signature.
+    pub fn to_legacy(&self) -> Result<Box<bindings::LegacyCapabilities>, String> {
+        if self.table.len() > CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES as usize {
+            return Err(format!(
+                "{} capabilities do not fit into the legacy table of {}",
+                self.table.len(),
+                CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES
+            ));
+        }
+        let mut legacy = Box::new(bindings::LegacyCapabilities {
+            capability: [bindings::LegacyCapability {
+                name: [0; CAPABILITY_FUNCTION_NAME_LEN as usize],
+                function: std::ptr::null_mut(),
+            }; CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES as usize],
+            n_capabilities: self.table.len() as c_int,
+        });
+        for (legacy, cap) in legacy.capability.iter_mut().zip(self.table.iter()) {
+            legacy.name = cap.name;
+            legacy.function = cap.function;
         }
+        Ok(legacy)
     }
 
+    /// Appends `cap`. Fails if a capability with the same name is already present or the table
+    /// cannot be described by the C struct anymore.
+    pub fn add(&mut self, cap: Capability) -> Result<(), String> {
+        let name = cap.name();
+        if self.table.iter().any(|existing| capability_name(existing) == name) {
+            return Err(format!("Duplicate capability: {}", name));
+        }
+        if self.table.len() >= c_int::MAX as usize {
+            return Err(format!("Capability table is full, cannot add {}", name));
+        }
+        self.table.push(*cap.inner());
+        self.sync();
+        Ok(())
+    }
+
+    /// Removes the capability called `name`, keeping the order of the others. Returns whether
+    /// it was found.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let Some(index) = self.table.iter().position(|cap| capability_name(cap) == name) else {
+            return false;
+        };
+        self.table.remove(index);
+        self.sync();
+        true
+    }
+
+    /// Appends the capabilities of `other`. Fails without adding any if one of them is already
+    /// present.
+    pub fn merge(&mut self, other: &Capabilities) -> Result<(), String> {
+        if let Some(duplicate) = other.iter().find(|cap| self.find(&cap.name()).is_some()) {
+            return Err(format!("Duplicate capability: {}", duplicate.name()));
+        }
+        for cap in other.iter() {
+            self.add(cap)?;
+        }
+        self.hold_leases(other);
+        Ok(())
+    }
+
+    /// Holds `lease` as long as the table or a clone of it lives.
+    pub fn hold(&mut self, lease: Lease) {
+        if !self.leases.iter().any(|held| held.same(&lease)) {
+            self.leases.push(lease);
+        }
+    }
+
+    /// Holds the leases of `other`, e.g. for a table wrapping its capabilities.
+    pub fn hold_leases(&mut self, other: &Capabilities) {
+        for lease in &other.leases {
+            self.hold(lease.clone());
+        }
+    }
+
+    pub fn leases(&self) -> &[Lease] {
+        &self.leases
+    }
+
+    /// The capabilities whose name starts with `prefix`, e.g. `blackboard_get_` for the getters
+    /// of the blackboard.
+    pub fn filtered(&self, prefix: &str) -> Capabilities {
+        let table = self
+            .table
+            .iter()
+            .filter(|cap| capability_name(cap).starts_with(prefix))
+            .copied()
+            .collect();
+        let mut filtered = Self::from_table(table);
+        filtered.hold_leases(self);
+        filtered
+    }
+
+    /// The capability called `name`. One missing in the table is resolved by the groups of the
+    /// table, see `GROUP_SUFFIX`.
     pub fn get(&self, name: &str) -> Option<Capability> {
-        for i in 0..self.0.n_capabilities {
-            let cap = &self.0.capability[i as usize];
+        self.find(name).or_else(|| self.resolve(name))
+    }
+
+    fn find(&self, name: &str) -> Option<Capability> {
+        for cap in self.table.iter() {
             let cap_name = capability_name(cap);
             if cap_name.len() != name.len() {
                 continue;
@@ -130,12 +335,28 @@ impl Capabilities {
         None
     }
 
+    // asks the groups of the table for `name`, the first providing it wins
+    fn resolve(&self, name: &str) -> Option<Capability> {
+        let name = CString::new(name).ok()?;
+        self.table
+            .iter()
+            .filter(|cap| group_library(&capability_name(cap)).is_some() && !cap.function.is_null())
+            .find_map(|group| {
+                let resolve: ResolveGroup = unsafe { std::mem::transmute(group.function) };
+                let mut cap = *Capability::new("", std::ptr::null_mut()).inner();
+                let result = unsafe {
+                    resolve(group.name.as_ptr(), name.as_ptr(), &mut cap as *mut _ as *mut c_void)
+                };
+                (result == RtStatus::Ok.code()).then_some(Capability(cap))
+            })
+    }
+
     pub fn inner(&self) -> &bindings::Capabilities {
-        &self.0
+        &self.raw
     }
 
     pub fn len(&self) -> usize {
-        self.0.n_capabilities as usize
+        self.table.len()
     }
 
     pub fn iter(&self) -> CapabilitiesIterator {
@@ -147,6 +368,225 @@ impl Capabilities {
 
 }
 
+impl Clone for Capabilities {
+    // the copy points its C view at its own table
+    fn clone(&self) -> Self {
+        let mut caps = Self::from_table(self.table.clone());
+        caps.hold_leases(self);
+        caps
+    }
+}
+
+/// Capabilities shared between threads and replaced while they run, e.g. once the library
+/// providing them is reloaded. Readers take a `snapshot`, which stays valid and unchanged while
+/// they use it, updates only reach the snapshots taken after them. Clones share the table.
+#[derive(Debug, Clone)]
+pub struct SharedCapabilities(Arc<RwLock<Arc<Capabilities>>>);
+
+impl SharedCapabilities {
+    pub fn new(caps: Capabilities) -> Self {
+        SharedCapabilities(Arc::new(RwLock::new(Arc::new(caps))))
+    }
+
+    /// The current table, cheap to take, the lock is only held to clone the `Arc`.
+    pub fn snapshot(&self) -> Arc<Capabilities> {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Replaces the table, returning the one before.
+    pub fn replace(&self, caps: Capabilities) -> Arc<Capabilities> {
+        std::mem::replace(&mut *self.0.write().unwrap(), Arc::new(caps))
+    }
+
+    /// Changes a copy of the table with `change` and replaces the table with it, unless `change`
+    /// fails. Snapshots taken before keep the table they got.
+    pub fn update<T>(
+        &self,
+        change: impl FnOnce(&mut Capabilities) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut current = self.0.write().unwrap();
+        let mut caps = Capabilities::clone(&current);
+        let result = change(&mut caps)?;
+        *current = Arc::new(caps);
+        Ok(result)
+    }
+}
+
+impl Default for SharedCapabilities {
+    fn default() -> Self {
+        Self::new(Capabilities::new())
+    }
+}
+
+impl From<Capabilities> for SharedCapabilities {
+    fn from(caps: Capabilities) -> Self {
+        Self::new(caps)
+    }
+}
+
+// a function of `caps`, the copy a component got in `start`
+pub(crate) fn function<T: Signature>(caps: &Capabilities, name: &str) -> Result<Function<T>, RtError> {
+    let capability = caps
+        .get(name)
+        .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("Capability {} is missing", name)))?;
+    Ok(unsafe { capability.get::<T>()? })
+}
+
+/// Suffix of a group in `requires`: `datalogger.*` or `datalogger.* >= 0.1` stands for the
+/// capabilities of the datalogger, resolved on their first lookup instead of at start, so a
+/// component works with and without the datalogger. The table holds an entry per group, named
+/// like the requirement, whose function of `GROUP_SIGNATURE` resolves a capability of the group.
+pub const GROUP_SUFFIX: &str = ".*";
+/// Signature of the entry of a group, called with the name of the group entry, the capability
+/// looked up and a `Capability` it fills. Returns `RT_OK`, or `RT_KEY_NOT_FOUND` if the group
+/// does not provide the capability (yet).
+pub const GROUP_SIGNATURE: &str = "i32(cstr,cstr,*mut void)";
+
+pub type ResolveGroup = unsafe extern "C" fn(*const c_char, *const c_char, *mut c_void) -> c_int;
+
+/// The library of a group like `datalogger.* >= 0.1`, None for other requirements.
+pub fn group_library(require: &str) -> Option<&str> {
+    let name = require
+        .trim()
+        .split(|c: char| c.is_whitespace() || "<>=~^".contains(c))
+        .next()?;
+    name.strip_suffix(GROUP_SUFFIX).filter(|library| !library.is_empty())
+}
+
+/// Suffix of the asynchronous variant of a capability, see `caps.h`.
+pub const ASYNC_SUFFIX: &str = "_async";
+/// Suffix of the capability cancelling an asynchronous call.
+pub const CANCEL_SUFFIX: &str = "_cancel";
+
+/// `X_async` of the capability `X`.
+pub fn async_name(name: &str) -> String {
+    format!("{}{}", name, ASYNC_SUFFIX)
+}
+
+/// `X_cancel` of the capability `X`.
+pub fn cancel_name(name: &str) -> String {
+    format!("{}{}", name, CANCEL_SUFFIX)
+}
+
+/// `rt_completion(handle, result, user_data)` of an asynchronous call.
+pub type Completion = unsafe extern "C" fn(c_int, c_int, *mut c_void);
+/// `X_cancel(handle)` of an asynchronous call.
+pub type Cancel = unsafe extern "C" fn(c_int) -> c_int;
+
+// the result, set once the call completed
+type Completed = (Mutex<Option<c_int>>, Condvar);
+
+unsafe extern "C" fn complete(_handle: c_int, result: c_int, user_data: *mut c_void) {
+    let completed = Arc::from_raw(user_data as *const Completed);
+    *completed.0.lock().unwrap() = Some(result);
+    completed.1.notify_all();
+}
+
+/// Caller side of an asynchronous call, its result can be polled or waited for.
+pub struct PendingCall {
+    handle: c_int,
+    completed: Arc<Completed>,
+}
+
+impl PendingCall {
+    /// Starts a call with `start(completion, user_data)`, which passes both on to an `X_async`
+    /// function and returns what it returns. Fails with the status of a call that did not start.
+    pub fn start<F>(start: F) -> Result<Self, RtError>
+    where
+        F: FnOnce(*mut c_void, *mut c_void) -> c_int,
+    {
+        let completed: Arc<Completed> = Arc::new((Mutex::new(None), Condvar::new()));
+        // owned by the completion until it is called
+        let user_data = Arc::into_raw(completed.clone()) as *mut c_void;
+        let handle = start(complete as *mut c_void, user_data);
+        if handle <= 0 {
+            unsafe { drop(Arc::from_raw(user_data as *const Completed)) };
+            let status = match RtStatus::from_code(handle) {
+                RtStatus::Ok => RtStatus::Error,
+                status => status,
+            };
+            return Err(RtError::new(status, format!("Call did not start, it returned {}", handle)));
+        }
+        Ok(PendingCall { handle, completed })
+    }
+
+    pub fn handle(&self) -> c_int {
+        self.handle
+    }
+
+    /// The result once the call completed.
+    pub fn poll(&self) -> Option<c_int> {
+        *self.completed.0.lock().unwrap()
+    }
+
+    pub fn wait(&self) -> c_int {
+        let result = self.completed.0.lock().unwrap();
+        let result = self.completed.1.wait_while(result, |result| result.is_none()).unwrap();
+        result.unwrap()
+    }
+
+    /// The result if the call completes within `timeout`.
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<c_int> {
+        let result = self.completed.0.lock().unwrap();
+        let (result, _) = self
+            .completed
+            .1
+            .wait_timeout_while(result, timeout, |result| result.is_none())
+            .unwrap();
+        *result
+    }
+}
+
+/// Provider side of an asynchronous call, completes it once. Dropping it without completing
+/// completes the call with `RT_ERROR`, so the caller never waits for nothing.
+pub struct Completer {
+    handle: c_int,
+    completion: Option<Completion>,
+    user_data: *mut c_void,
+}
+
+unsafe impl Send for Completer {}
+
+impl Completer {
+    /// Takes the completion of an `X_async` call and gives the call a new handle, which the
+    /// function returns.
+    ///
+    /// # Safety
+    ///
+    /// `completion` and `user_data` must be the arguments an `X_async` function was called with.
+    pub unsafe fn new(completion: *mut c_void, user_data: *mut c_void) -> Result<Self, RtError> {
+        static HANDLES: AtomicI32 = AtomicI32::new(1);
+        if completion.is_null() {
+            return Err(RtError::new(RtStatus::NullArgument, "Completion is null pointer"));
+        }
+        // positive, also after wrapping around
+        let handle = HANDLES.fetch_add(1, Ordering::SeqCst).max(1);
+        Ok(Completer {
+            handle,
+            completion: Some(std::mem::transmute::<*mut c_void, Completion>(completion)),
+            user_data,
+        })
+    }
+
+    pub fn handle(&self) -> c_int {
+        self.handle
+    }
+
+    pub fn complete(mut self, result: c_int) {
+        if let Some(completion) = self.completion.take() {
+            unsafe { completion(self.handle, result, self.user_data) };
+        }
+    }
+}
+
+impl Drop for Completer {
+    fn drop(&mut self) {
+        if let Some(completion) = self.completion.take() {
+            unsafe { completion(self.handle, RtStatus::Error.code(), self.user_data) };
+        }
+    }
+}
+
 pub struct CapabilitiesIterator<'a> {
     capabilities: &'a Capabilities,
     index: usize,
@@ -157,7 +597,7 @@ impl<'a> iter::Iterator for CapabilitiesIterator<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.index < self.capabilities.len() {
-            let cap = &self.capabilities.0.capability[self.index];
+            let cap = &self.capabilities.table[self.index];
             self.index += 1;
             Some(Capability::from_raw(cap))
         } else {
@@ -166,4 +606,7 @@ impl<'a> iter::Iterator for CapabilitiesIterator<'a> {
     }
 }
 
-unsafe impl Send for Capabilities {}
\ No newline at end of file
+// `raw` only points into the own `table`, which is changed through `&mut self` alone, and the
+// functions of the table are called by the threads of all components anyway
+unsafe impl Send for Capabilities {}
+unsafe impl Sync for Capabilities {}
@@ -0,0 +1,43 @@
+// Clock of the loader, given to every component next to `log_write`. Its time starts with the
+// loader. With `rtime run --sim-time` it is simulated and only moves when it is advanced, e.g.
+// with `clock advance <ms>` on the control socket, so components reading and sleeping on it step
+// the same way on every run.
+use crate::capabilities::{function, Capabilities};
+use crate::status::{RtError, RtStatus};
+use std::os::raw::c_int;
+use std::time::Duration;
+
+pub const CLOCK_NOW_CAPABILITY: &str = "clock_now_ns";
+pub const CLOCK_NOW_SIGNATURE: &str = "u64()";
+pub const CLOCK_SLEEP_UNTIL_CAPABILITY: &str = "clock_sleep_until";
+pub const CLOCK_SLEEP_UNTIL_SIGNATURE: &str = "i32(u64)";
+
+/// `clock_now_ns()` returns the nanoseconds since the loader started.
+pub type ClockNow = unsafe extern "C" fn() -> u64;
+/// `clock_sleep_until(time)` blocks until `clock_now_ns()` reaches `time`, returns immediately
+/// for a time in the past.
+pub type ClockSleepUntil = unsafe extern "C" fn(u64) -> c_int;
+
+/// Time of the loader clock.
+pub fn now(caps: &Capabilities) -> Result<Duration, RtError> {
+    let now = function::<ClockNow>(caps, CLOCK_NOW_CAPABILITY)?;
+    Ok(Duration::from_nanos(unsafe { now() }))
+}
+
+/// Blocks until the loader clock reaches `time`.
+pub fn sleep_until(caps: &Capabilities, time: Duration) -> Result<(), RtError> {
+    let sleep_until = function::<ClockSleepUntil>(caps, CLOCK_SLEEP_UNTIL_CAPABILITY)?;
+    let time = u64::try_from(time.as_nanos()).unwrap_or(u64::MAX);
+    match unsafe { sleep_until(time) } {
+        0 => Ok(()),
+        code => {
+            let status = RtStatus::from_code(code);
+            Err(RtError::new(status, format!("Sleeping failed: {}", status)))
+        }
+    }
+}
+
+/// Blocks for `duration` of the loader clock.
+pub fn sleep(caps: &Capabilities, duration: Duration) -> Result<(), RtError> {
+    sleep_until(caps, now(caps)? + duration)
+}
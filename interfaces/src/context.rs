@@ -0,0 +1,194 @@
+// Per instance state of plugins declaring a "capabilities_abi" of 3 or higher, see `rt_context`
+// in caps.h. The runtime owns an `OwnedContext` for every loaded instance and passes it to the
+// lifecycle entries, which read their state with `Context::state` instead of keeping it in
+// statics:
+//
+//     #[no_mangle]
+//     pub extern "C" fn health(context: *mut rt_context) -> i32 {
+//         match unsafe { Context::from_raw(context) }.and_then(|c| c.state::<Instance>()) { .. }
+//     }
+use crate::bindings::rt_context;
+use crate::status::{RtError, RtStatus};
+use std::any::Any;
+use std::cell::UnsafeCell;
+use std::ffi::{c_void, CStr, CString};
+use std::marker::PhantomData;
+use std::ptr::{addr_of_mut, NonNull};
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+// what `state` points to, boxed twice for a thin pointer
+type State = Box<dyn Any + Send + Sync>;
+
+unsafe extern "C" fn release_state(state: *mut c_void) {
+    drop(Box::from_raw(state as *mut State));
+}
+
+// the pointer sized fields of a context are only accessed atomically, entries run on any thread
+unsafe fn atomic<'a>(field: *mut *mut c_void) -> &'a AtomicPtr<c_void> {
+    AtomicPtr::from_ptr(field)
+}
+
+/// Context passed by the runtime, valid for the whole call of the entry it is passed to.
+pub struct Context<'a> {
+    inner: NonNull<rt_context>,
+    _context: PhantomData<&'a rt_context>,
+}
+
+impl<'a> Context<'a> {
+    /// Fails with `RtStatus::NullArgument` for a null `context`.
+    ///
+    /// # Safety
+    /// `context` has to be null or the context the runtime passed to the calling entry.
+    pub unsafe fn from_raw(context: *mut rt_context) -> Result<Self, RtError> {
+        NonNull::new(context)
+            .map(|inner| Context {
+                inner,
+                _context: PhantomData,
+            })
+            .ok_or_else(|| RtError::new(RtStatus::NullArgument, "No context passed"))
+    }
+
+    /// Name of the component, the instance name for instances of a library.
+    pub fn instance(&self) -> &'a str {
+        let instance = unsafe { self.inner.as_ref().instance };
+        if instance.is_null() {
+            return "";
+        }
+        unsafe { CStr::from_ptr(instance) }.to_str().unwrap_or_default()
+    }
+
+    /// State of the instance, created with `T::default()` on first use. It lives until the
+    /// runtime unloads the instance, keep what changes behind a `Mutex` or atomics inside `T`.
+    /// Fails if the instance keeps a state of another type.
+    pub fn state<T: Default + Send + Sync + 'static>(&self) -> Result<&'a T, RtError> {
+        let context = self.inner.as_ptr();
+        let slot = unsafe { atomic(addr_of_mut!((*context).state)) };
+        let release = unsafe { atomic(addr_of_mut!((*context).release) as *mut *mut c_void) };
+        let mut state = slot.load(Ordering::Acquire);
+        if state.is_null() {
+            let created: *mut State = Box::into_raw(Box::new(Box::new(T::default())));
+            release.store(release_state as *mut c_void, Ordering::Release);
+            state = match slot.compare_exchange(
+                std::ptr::null_mut(),
+                created as *mut c_void,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => created as *mut c_void,
+                Err(current) => {
+                    // another call was faster
+                    drop(unsafe { Box::from_raw(created) });
+                    current
+                }
+            };
+        }
+        unsafe { &*(state as *const State) }
+            .downcast_ref::<T>()
+            .ok_or_else(|| RtError::new(RtStatus::Error, "The context holds a state of another type"))
+    }
+}
+
+/// Context of an instance as owned by the runtime. Dropping it releases the state of the plugin,
+/// so it has to be dropped before the library is unloaded.
+#[derive(Debug)]
+pub struct OwnedContext {
+    context: Box<UnsafeCell<rt_context>>,
+    _instance: CString,
+}
+
+// the plugin synchronizes access to its state, see `Context::state`
+unsafe impl Send for OwnedContext {}
+unsafe impl Sync for OwnedContext {}
+
+impl OwnedContext {
+    pub fn new(instance: &str) -> Self {
+        let instance = CString::new(instance.replace('\0', "")).unwrap();
+        OwnedContext {
+            context: Box::new(UnsafeCell::new(rt_context {
+                state: std::ptr::null_mut(),
+                release: None,
+                instance: instance.as_ptr(),
+            })),
+            _instance: instance,
+        }
+    }
+
+    /// Pointer passed to the entries of the plugin.
+    pub fn as_ptr(&self) -> *mut rt_context {
+        self.context.get()
+    }
+
+    /// Calls `release` of the plugin with its state, if it stored one.
+    pub fn release(&self) {
+        let context = self.as_ptr();
+        let state = unsafe { atomic(addr_of_mut!((*context).state)) }
+            .swap(std::ptr::null_mut(), Ordering::AcqRel);
+        let release = unsafe { atomic(addr_of_mut!((*context).release) as *mut *mut c_void) }
+            .load(Ordering::Acquire);
+        if !state.is_null() && !release.is_null() {
+            let release: unsafe extern "C" fn(*mut c_void) = unsafe { std::mem::transmute(release) };
+            unsafe { release(state) };
+        }
+    }
+}
+
+impl Drop for OwnedContext {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    #[test]
+    fn test_state() {
+        let owned = OwnedContext::new("left");
+        let context = unsafe { Context::from_raw(owned.as_ptr()) }.unwrap();
+        assert_eq!(context.instance(), "left");
+
+        context.state::<Counter>().unwrap().0.fetch_add(1, Ordering::SeqCst);
+        let again = unsafe { Context::from_raw(owned.as_ptr()) }.unwrap();
+        assert_eq!(again.state::<Counter>().unwrap().0.load(Ordering::SeqCst), 1);
+        assert!(again.state::<String>().is_err());
+
+        // instances do not share their state
+        let other = OwnedContext::new("right");
+        let context = unsafe { Context::from_raw(other.as_ptr()) }.unwrap();
+        assert_eq!(context.state::<Counter>().unwrap().0.load(Ordering::SeqCst), 0);
+
+        let status = unsafe { Context::from_raw(std::ptr::null_mut()) }.err().unwrap().status;
+        assert_eq!(status, RtStatus::NullArgument);
+    }
+
+    #[test]
+    fn test_release() {
+        struct Tracked(Arc<AtomicUsize>);
+        impl Drop for Tracked {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        static DROPPED: std::sync::OnceLock<Arc<AtomicUsize>> = std::sync::OnceLock::new();
+        impl Default for Tracked {
+            fn default() -> Self {
+                Tracked(DROPPED.get_or_init(Default::default).clone())
+            }
+        }
+
+        let owned = OwnedContext::new("left");
+        // released without a state
+        owned.release();
+        unsafe { Context::from_raw(owned.as_ptr()) }.unwrap().state::<Tracked>().unwrap();
+        let dropped = DROPPED.get().unwrap().clone();
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        drop(owned);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+}
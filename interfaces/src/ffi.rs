@@ -0,0 +1,180 @@
+// The C ABI of the plugins as it is published in `rtime_plugin.h`. build.rs generates the header
+// from this file with cbindgen, so C and C++ plugins implement against the definitions the
+// loader is built with. The loader itself uses the `bindings` of caps.h, the assertions at the
+// end keep both in step.
+#![allow(non_camel_case_types)]
+use std::ffi::{c_char, c_int, c_void};
+
+/// Version of this header, the highest `capabilities_abi` a plugin may declare in its summary.
+pub const RTIME_PLUGIN_ABI_VERSION: u32 = 3;
+/// First `capabilities_abi` passing a `Capabilities` table to `start` and `run`.
+pub const CAPABILITIES_ABI_TABLE: u32 = 2;
+/// First `capabilities_abi` passing an `rt_context` as the first argument of the entries.
+pub const CAPABILITIES_ABI_CONTEXT: u32 = 3;
+/// Layout of the structs and entries of this header, returned by `abi_version`. The loader
+/// refuses plugins built for another one.
+pub const RT_ABI_VERSION: u32 = 1;
+pub const CAPABILITY_FUNCTION_NAME_LEN: usize = 256;
+pub const CAPABILITY_SIGNATURE_LEN: usize = 128;
+pub const CAPABILITY_VERSION_LEN: usize = 32;
+
+/// Status returned by capabilities and entries. Non negative values mean success, some
+/// capabilities return a size or count instead of `RT_OK`.
+#[repr(C)]
+pub enum RtStatus {
+    RT_OK = 0,
+    /// Failure without a more specific status.
+    RT_ERROR = -1,
+    /// Compare and set found a different value, nothing written.
+    RT_VALUE_MISMATCH = -2,
+    /// The key holds a value of a different type, nothing written.
+    RT_TYPE_MISMATCH = -3,
+    RT_TIMEOUT = -4,
+    /// The service is not started.
+    RT_NOT_RUNNING = -5,
+    RT_ALREADY_RUNNING = -6,
+    RT_KEY_NOT_FOUND = -7,
+    RT_BUFFER_TOO_SMALL = -8,
+    RT_NULL_ARGUMENT = -9,
+    RT_INVALID_ARGUMENT = -10,
+    /// The access rules of the blackboard forbid the call.
+    RT_ACCESS_DENIED = -11,
+    /// The value violates the constraint of the key, nothing written.
+    RT_CONSTRAINT_VIOLATION = -12,
+}
+
+/// A function provided by a component, null terminated strings in fixed size arrays.
+#[repr(C)]
+pub struct Capability {
+    /// Name of the capability, e.g. "blackboard_get_int".
+    pub name: [c_char; CAPABILITY_FUNCTION_NAME_LEN],
+    /// e.g. "i32(cstr,*mut i32)", empty if not declared.
+    pub signature: [c_char; CAPABILITY_SIGNATURE_LEN],
+    /// Semver of the capability, e.g. "0.1.0", empty if not declared.
+    pub version: [c_char; CAPABILITY_VERSION_LEN],
+    /// The function, cast to the type of its signature before calling it.
+    pub function: *mut c_void,
+}
+
+/// The capabilities passed to `start` and `run`. The table is owned by the loader and only valid
+/// during the call it is passed to, copy the capabilities you want to keep.
+#[repr(C)]
+pub struct Capabilities {
+    pub capability: *mut Capability,
+    pub n_capabilities: c_int,
+}
+
+/// Context of a loaded plugin instance, owned by the runtime and passed as the first argument
+/// of the entries with `capabilities_abi` 3. Every call into the same instance gets the same
+/// context, it lives until the instance is unloaded.
+#[repr(C)]
+pub struct rt_context {
+    /// Owned by the plugin, null until it stores its state.
+    pub state: *mut c_void,
+    /// Set by the plugin together with `state`, called with it before the instance is unloaded.
+    pub release: Option<unsafe extern "C" fn(state: *mut c_void)>,
+    /// Name of the component, the instance name for instances.
+    pub instance: *const c_char,
+}
+
+/// Completion of an asynchronous call `X_async`, called exactly once with the handle `X_async`
+/// returned, the result of `X` and the user data, from any thread.
+pub type rt_completion =
+    Option<unsafe extern "C" fn(handle: c_int, result: c_int, user_data: *mut c_void)>;
+
+/// `summary`, exported by every plugin. Returns its description as a static null terminated
+/// json (or yaml) document:
+///
+/// ```json
+/// {"name": "planner", "version": "0.1.0", "library_type": "Skill",
+///  "capabilities_abi": 3,
+///  "provides": [{"capability": "planner_plan", "entry": "plan",
+///                "signature": "i32(cstr,*mut char,i32)", "version": "0.1.0"}],
+///  "requires": ["blackboard >= 0.1"]}
+/// ```
+///
+/// `library_type` is "Service" or "Skill". `provides` lists the exported functions the loader
+/// hands to other components as capabilities, `requires` the components or capabilities needed
+/// with optional semver requirements. Without `capabilities_abi` the plugin gets the legacy
+/// table of caps.h.
+pub type rt_summary_fn = unsafe extern "C" fn() -> *const c_char;
+
+/// `abi_version`, exported by every plugin next to `summary`. Returns `RT_ABI_VERSION`.
+pub type rt_abi_version_fn = unsafe extern "C" fn() -> c_int;
+
+/// `start` of a service: the capabilities it may call and its attributes as yaml, or null.
+pub type rt_start_fn =
+    unsafe extern "C" fn(caps: *const Capabilities, attributes: *const c_char) -> c_int;
+
+/// `run` of a skill, with the same arguments as `start`. Returns `RT_OK`, a non negative result
+/// or an `RtStatus`.
+pub type rt_run_fn =
+    unsafe extern "C" fn(caps: *const Capabilities, attributes: *const c_char) -> c_int;
+
+/// `tick` of a skill with a tick rate, with the same arguments as `run`. Called at that rate
+/// by the loader, returns `RT_OK` to be ticked again, 1 to end ticking or an `RtStatus`.
+pub type rt_tick_fn =
+    unsafe extern "C" fn(caps: *const Capabilities, attributes: *const c_char) -> c_int;
+
+/// Optional `self_test` of a plugin, with the same arguments as `start`. `rtime run --self-test`
+/// calls it on a blackboard of its own without starting anything else. Returns `RT_OK` if it
+/// passes, else an `RtStatus` with the reason as last error.
+pub type rt_self_test_fn =
+    unsafe extern "C" fn(caps: *const Capabilities, attributes: *const c_char) -> c_int;
+
+/// `stop` and `health` of a service, returning an `RtStatus`.
+pub type rt_entry_fn = unsafe extern "C" fn() -> c_int;
+
+/// `health_status` of a service, writing json details of its health into the buffer.
+pub type rt_health_status_fn = unsafe extern "C" fn(buffer: *mut c_char, len: c_int) -> c_int;
+
+/// `start` of a plugin with `capabilities_abi` 3, the other entries take the context first in
+/// the same way.
+pub type rt_context_start_fn = unsafe extern "C" fn(
+    context: *mut rt_context,
+    caps: *const Capabilities,
+    attributes: *const c_char,
+) -> c_int;
+
+// the published ABI is the one of caps.h
+const _: () = {
+    use crate::bindings;
+    use std::mem::{align_of, offset_of, size_of};
+    assert!(RTIME_PLUGIN_ABI_VERSION == bindings::CAPABILITIES_ABI_VERSION);
+    assert!(CAPABILITIES_ABI_TABLE == bindings::CAPABILITIES_ABI_TABLE);
+    assert!(CAPABILITIES_ABI_CONTEXT == bindings::CAPABILITIES_ABI_CONTEXT);
+    assert!(RT_ABI_VERSION == bindings::RT_ABI_VERSION);
+    assert!(CAPABILITY_FUNCTION_NAME_LEN == bindings::CAPABILITY_FUNCTION_NAME_LEN as usize);
+    assert!(CAPABILITY_SIGNATURE_LEN == bindings::CAPABILITY_SIGNATURE_LEN as usize);
+    assert!(CAPABILITY_VERSION_LEN == bindings::CAPABILITY_VERSION_LEN as usize);
+
+    assert!(RtStatus::RT_OK as c_int == bindings::rt_status_RT_OK);
+    assert!(RtStatus::RT_ERROR as c_int == bindings::rt_status_RT_ERROR);
+    assert!(RtStatus::RT_VALUE_MISMATCH as c_int == bindings::rt_status_RT_VALUE_MISMATCH);
+    assert!(RtStatus::RT_TYPE_MISMATCH as c_int == bindings::rt_status_RT_TYPE_MISMATCH);
+    assert!(RtStatus::RT_TIMEOUT as c_int == bindings::rt_status_RT_TIMEOUT);
+    assert!(RtStatus::RT_NOT_RUNNING as c_int == bindings::rt_status_RT_NOT_RUNNING);
+    assert!(RtStatus::RT_ALREADY_RUNNING as c_int == bindings::rt_status_RT_ALREADY_RUNNING);
+    assert!(RtStatus::RT_KEY_NOT_FOUND as c_int == bindings::rt_status_RT_KEY_NOT_FOUND);
+    assert!(RtStatus::RT_BUFFER_TOO_SMALL as c_int == bindings::rt_status_RT_BUFFER_TOO_SMALL);
+    assert!(RtStatus::RT_NULL_ARGUMENT as c_int == bindings::rt_status_RT_NULL_ARGUMENT);
+    assert!(RtStatus::RT_INVALID_ARGUMENT as c_int == bindings::rt_status_RT_INVALID_ARGUMENT);
+    assert!(RtStatus::RT_ACCESS_DENIED as c_int == bindings::rt_status_RT_ACCESS_DENIED);
+    assert!(
+        RtStatus::RT_CONSTRAINT_VIOLATION as c_int == bindings::rt_status_RT_CONSTRAINT_VIOLATION
+    );
+
+    assert!(size_of::<Capability>() == size_of::<bindings::Capability>());
+    assert!(align_of::<Capability>() == align_of::<bindings::Capability>());
+    assert!(offset_of!(Capability, signature) == offset_of!(bindings::Capability, signature));
+    assert!(offset_of!(Capability, version) == offset_of!(bindings::Capability, version));
+    assert!(offset_of!(Capability, function) == offset_of!(bindings::Capability, function));
+    assert!(size_of::<Capabilities>() == size_of::<bindings::Capabilities>());
+    assert!(
+        offset_of!(Capabilities, n_capabilities)
+            == offset_of!(bindings::Capabilities, n_capabilities)
+    );
+    assert!(size_of::<rt_context>() == size_of::<bindings::rt_context>());
+    assert!(offset_of!(rt_context, release) == offset_of!(bindings::rt_context, release));
+    assert!(offset_of!(rt_context, instance) == offset_of!(bindings::rt_context, instance));
+};
@@ -0,0 +1,277 @@
+// Hooks around the calls of a capability, e.g. to log, measure, validate or refuse them.
+// `intercept` returns a capability with the same name, signature and version whose function is
+// a shim: it calls `Interceptor::before`, then the wrapped function unless `before` refused the
+// call, and `Interceptor::after` with the result. A component gets the shim in place of the
+// capability and calls it like any other.
+//
+// The shims are compiled in, a fixed number per signature of `shims!`. Capabilities of other
+// signatures, like the ones taking callbacks, can not be intercepted.
+use crate::capabilities::Capability;
+use crate::signature::{self, Signature};
+use crate::status::RtStatus;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+// shims per signature, a pair of owner and capability keeps its shim once assigned
+const SLOTS: usize = 64;
+
+/// An argument of an intercepted call. Buffers and out parameters are only passed as pointers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arg {
+    Str(*const c_char),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    Pointer(*const c_void),
+}
+
+impl Arg {
+    /// The text of a `Str` argument, None for other arguments, null pointers and invalid utf-8.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Arg::Str(text) if !text.is_null() => unsafe { CStr::from_ptr(*text) }.to_str().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// A call of an intercepted capability.
+#[derive(Debug)]
+pub struct Call<'a> {
+    pub capability: &'a str,
+    pub args: &'a [Arg],
+}
+
+/// Hooks of an intercepted capability. They run on the thread of the caller and must not
+/// panic, a panicking `before` refuses the call with `RT_ERROR`.
+pub trait Interceptor: Send + Sync {
+    /// Called in front of the capability. An error is returned to the caller instead of calling
+    /// it, e.g. `RtStatus::AccessDenied.code()`.
+    fn before(&self, _call: &Call) -> Result<(), c_int> {
+        Ok(())
+    }
+
+    /// Called with the result of the capability and how long it took.
+    fn after(&self, _call: &Call, _result: c_int, _elapsed: Duration) {}
+}
+
+// what a shim calls
+#[derive(Clone)]
+struct Target {
+    function: usize,
+    capability: Arc<str>,
+    interceptor: Arc<dyn Interceptor>,
+}
+
+type Targets = [RwLock<Option<Target>>; SLOTS];
+
+// calls `target` between the hooks of its interceptor
+fn call_through(target: &Target, args: &[Arg], call: impl FnOnce(usize) -> c_int) -> c_int {
+    let intercepted = Call {
+        capability: &target.capability,
+        args,
+    };
+    let interceptor = &target.interceptor;
+    match catch_unwind(AssertUnwindSafe(|| interceptor.before(&intercepted))) {
+        Ok(Ok(())) => {}
+        Ok(Err(code)) => return code,
+        Err(_) => return RtStatus::Error.code(),
+    }
+    let start = Instant::now();
+    let result = call(target.function);
+    let elapsed = start.elapsed();
+    let _ = catch_unwind(AssertUnwindSafe(|| interceptor.after(&intercepted, result, elapsed)));
+    result
+}
+
+trait ToArg {
+    fn to_arg(&self) -> Arg;
+}
+
+macro_rules! to_arg {
+    ($($t:ty => $variant:ident as $as:ty),* $(,)?) => {
+        $(impl ToArg for $t {
+            fn to_arg(&self) -> Arg {
+                Arg::$variant(*self as $as)
+            }
+        })*
+    };
+}
+
+to_arg! {
+    *const c_char => Str as *const c_char,
+    c_int => Int as i64,
+    i64 => Int as i64,
+    u64 => UInt as u64,
+    f32 => Float as f64,
+    f64 => Float as f64,
+    bool => Bool as bool,
+    *mut c_char => Pointer as *const c_void,
+    *mut c_int => Pointer as *const c_void,
+    *mut i64 => Pointer as *const c_void,
+    *mut u64 => Pointer as *const c_void,
+    *mut f32 => Pointer as *const c_void,
+    *mut f64 => Pointer as *const c_void,
+    *mut bool => Pointer as *const c_void,
+    *mut u8 => Pointer as *const c_void,
+    *const u8 => Pointer as *const c_void,
+    *const c_int => Pointer as *const c_void,
+    *const f64 => Pointer as *const c_void,
+}
+
+struct Shims {
+    signature: String, // normalized
+    slots: [usize; SLOTS],
+    targets: &'static Targets,
+}
+
+macro_rules! slots {
+    ($name:ident $types:tt) => {
+        slots!(@ $name $types 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25
+            26 27 28 29 30 31 32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48 49 50 51 52 53
+            54 55 56 57 58 59 60 61 62 63)
+    };
+    (@ $name:ident $types:tt $($slot:literal)*) => {
+        [$($name::shim::<$slot> as extern "C" fn $types -> c_int as usize),*]
+    };
+}
+
+// a shim function per signature and slot, calling the target of the slot
+macro_rules! shims {
+    ($($name:ident($($arg:ident: $t:ty),*);)*) => {
+        $(
+            mod $name {
+                use super::*;
+
+                pub static TARGETS: Targets = [const { RwLock::new(None) }; SLOTS];
+
+                pub extern "C" fn shim<const SLOT: usize>($($arg: $t),*) -> c_int {
+                    // cloned, so the slot may be reassigned during the call
+                    let Some(target) = TARGETS[SLOT].read().unwrap().clone() else {
+                        return RtStatus::NotRunning.code();
+                    };
+                    call_through(&target, &[$($arg.to_arg()),*], |function| unsafe {
+                        std::mem::transmute::<usize, unsafe extern "C" fn($($t),*) -> c_int>(
+                            function,
+                        )($($arg),*)
+                    })
+                }
+            }
+        )*
+
+        fn shims() -> Vec<Shims> {
+            vec![$(
+                Shims {
+                    signature: signature::normalize(
+                        &<unsafe extern "C" fn($($t),*) -> c_int as Signature>::signature(),
+                    ),
+                    slots: slots!($name ($($t),*)),
+                    targets: &$name::TARGETS,
+                },
+            )*]
+        }
+    };
+}
+
+shims! {
+    shim_none();
+    shim_str(a: *const c_char);
+    shim_str_str(a: *const c_char, b: *const c_char);
+    shim_str_str_str(a: *const c_char, b: *const c_char, c: *const c_char);
+    shim_str_i32(a: *const c_char, b: c_int);
+    shim_str_i32_i32(a: *const c_char, b: c_int, c: c_int);
+    shim_str_i64(a: *const c_char, b: i64);
+    shim_str_u64(a: *const c_char, b: u64);
+    shim_str_f32(a: *const c_char, b: f32);
+    shim_str_f64(a: *const c_char, b: f64);
+    shim_str_bool(a: *const c_char, b: bool);
+    shim_u64(a: u64);
+    shim_text(a: *mut c_char);
+    shim_text_len(a: *mut c_char, b: c_int);
+    shim_str_text(a: *const c_char, b: *mut c_char);
+    shim_str_i32_text(a: *const c_char, b: c_int, c: *mut c_char);
+    shim_str_out_i32(a: *const c_char, b: *mut c_int);
+    shim_str_out_i64(a: *const c_char, b: *mut i64);
+    shim_str_out_u64(a: *const c_char, b: *mut u64);
+    shim_str_out_f32(a: *const c_char, b: *mut f32);
+    shim_str_out_f64(a: *const c_char, b: *mut f64);
+    shim_str_out_bool(a: *const c_char, b: *mut bool);
+    shim_str_out_u8_len(a: *const c_char, b: *mut u8, c: c_int);
+    shim_str_in_u8_len(a: *const c_char, b: *const u8, c: c_int);
+    shim_str_in_i32_len(a: *const c_char, b: *const c_int, c: c_int);
+    shim_str_in_f64_len(a: *const c_char, b: *const f64, c: c_int);
+    shim_str_i32_str_str(a: *const c_char, b: c_int, c: *const c_char, d: *const c_char);
+}
+
+#[derive(Default)]
+struct Assignments {
+    slots: HashMap<(String, String), (usize, usize)>, // (owner, capability) -> (shims, slot)
+    used: HashMap<usize, usize>,                      // shims -> slots used
+}
+
+static ASSIGNMENTS: Mutex<Option<(Vec<Shims>, Assignments)>> = Mutex::new(None);
+
+/// Whether capabilities of `signature` can be intercepted.
+pub fn supported(signature: &str) -> bool {
+    let signature = signature::normalize(signature);
+    shims().iter().any(|shims| shims.signature == signature)
+}
+
+/// `cap` with `interceptor` around its calls. The shim is kept for `owner` and the name of the
+/// capability: intercepting it again, like for a restarted component, reuses the shim with the
+/// new function and interceptor. Interceptors stacked on one capability need different owners.
+/// Fails for capabilities without a supported signature and once all shims of the signature
+/// are used.
+pub fn intercept(
+    owner: &str,
+    cap: &Capability,
+    interceptor: Arc<dyn Interceptor>,
+) -> Result<Capability, String> {
+    let signature = cap.signature();
+    let name = cap.name();
+    let mut assignments = ASSIGNMENTS.lock().unwrap();
+    let (shims, assignments) = assignments.get_or_insert_with(|| (shims(), Assignments::default()));
+    let key = (owner.to_string(), name.clone());
+    let (index, slot) = match assignments.slots.get(&key) {
+        Some(assigned) => *assigned,
+        None => {
+            let index = shims
+                .iter()
+                .position(|shims| !signature.is_empty() && shims.signature == signature)
+                .ok_or_else(|| {
+                    format!(
+                        "Capability '{}' of signature '{}' can not be intercepted",
+                        name, signature
+                    )
+                })?;
+            let used = assignments.used.entry(index).or_default();
+            if *used == SLOTS {
+                return Err(format!(
+                    "Capability '{}' can not be intercepted, all shims of '{}' are used",
+                    name, signature
+                ));
+            }
+            *used += 1;
+            assignments.slots.insert(key, (index, *used - 1));
+            (index, *used - 1)
+        }
+    };
+    let shim = shims[index].slots[slot];
+    let function = cap.inner().function as usize;
+    if function == shim {
+        return Err(format!("Capability '{}' is already intercepted by '{}'", name, owner));
+    }
+    // a reloaded library provides the capability at another address
+    *shims[index].targets[slot].write().unwrap() = Some(Target {
+        function,
+        capability: name.as_str().into(),
+        interceptor,
+    });
+    let mut intercepted = Capability::with_signature(&name, shim as *mut c_void, &signature);
+    intercepted.set_version(&cap.version());
+    Ok(intercepted)
+}
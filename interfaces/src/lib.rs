@@ -1,3 +1,24 @@
+pub mod alerts;
+#[allow(non_upper_case_globals, non_camel_case_types)]
 pub mod bindings;
+pub mod callback;
 pub mod capabilities;
-pub mod blackboard;
\ No newline at end of file
+pub mod clock;
+pub mod context;
+pub mod ffi;
+pub mod intercept;
+pub mod blackboard;
+pub mod blackboard_client;
+pub mod lifecycle;
+pub mod logging;
+pub mod messages;
+pub mod predicate;
+pub mod project;
+pub mod runtime;
+pub mod scheduler;
+#[cfg(all(unix, feature = "shm"))]
+pub mod shared_memory;
+pub mod signature;
+pub mod status;
+#[cfg(feature = "testing")]
+pub mod testing;
\ No newline at end of file
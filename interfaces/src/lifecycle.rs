@@ -0,0 +1,143 @@
+// Lifecycle state of a plugin, reported by its `state` entry (`i32()`) as the code of a
+// `PluginState`:
+//
+//     Created --start--> Started --stop--> Stopped --start--> Started
+//        |                  |                 |
+//        +------------------+-----------------+--> Failed --start--> Started
+//
+// A plugin keeps its state in a `Lifecycle`, static or in the state of its context (see
+// `interfaces::context`), and passes the results of its `start` and `stop` through
+// `Lifecycle::started` and `Lifecycle::stopped`.
+use crate::status::{RtError, RtStatus};
+use std::fmt;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginState {
+    Created = 0,
+    Started = 1,
+    Stopped = 2,
+    Failed = 3, // failed to start or to stop
+}
+
+impl PluginState {
+    const ALL: [PluginState; 4] = [
+        PluginState::Created,
+        PluginState::Started,
+        PluginState::Stopped,
+        PluginState::Failed,
+    ];
+
+    pub fn code(self) -> c_int {
+        self as c_int
+    }
+
+    /// State of a code returned by `state`, `None` for unknown codes.
+    pub fn from_code(code: c_int) -> Option<Self> {
+        PluginState::ALL
+            .into_iter()
+            .find(|state| state.code() == code)
+    }
+
+    /// Whether a plugin may change from this state to `next`.
+    pub fn can_change_to(self, next: PluginState) -> bool {
+        matches!(
+            (self, next),
+            (PluginState::Created, PluginState::Started)
+                | (PluginState::Started, PluginState::Stopped)
+                | (PluginState::Stopped, PluginState::Started)
+                | (PluginState::Failed, PluginState::Started)
+                | (PluginState::Failed, PluginState::Stopped)
+                | (_, PluginState::Failed)
+        ) && self != next
+    }
+}
+
+impl fmt::Display for PluginState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            PluginState::Created => "created",
+            PluginState::Started => "started",
+            PluginState::Stopped => "stopped",
+            PluginState::Failed => "failed",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// The state of a plugin, shared by its entries.
+pub struct Lifecycle {
+    state: AtomicI32,
+}
+
+impl Lifecycle {
+    pub const fn new() -> Self {
+        Lifecycle {
+            state: AtomicI32::new(PluginState::Created as i32),
+        }
+    }
+
+    pub fn state(&self) -> PluginState {
+        PluginState::from_code(self.state.load(Ordering::SeqCst)).unwrap_or(PluginState::Failed)
+    }
+
+    /// Code of the state, returned by the `state` entry.
+    pub fn code(&self) -> c_int {
+        self.state().code()
+    }
+
+    /// Changes to `next`, fails and keeps the state if the change is not allowed.
+    pub fn change(&self, next: PluginState) -> Result<(), RtError> {
+        self.state
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |code| {
+                let current = PluginState::from_code(code).unwrap_or(PluginState::Failed);
+                current.can_change_to(next).then_some(next.code())
+            })
+            .map(|_| ())
+            .map_err(|code| {
+                let current = PluginState::from_code(code).unwrap_or(PluginState::Failed);
+                RtError::new(
+                    RtStatus::Error,
+                    format!("Can not change from {} to {}", current, next),
+                )
+            })
+    }
+
+    /// Records the result of `start`: `Started` on success, `Failed` on an error. Starting a
+    /// started plugin again keeps its state.
+    pub fn started<T>(&self, result: Result<T, RtError>) -> Result<T, RtError> {
+        match &result {
+            Ok(_) => {
+                let _ = self.change(PluginState::Started);
+            }
+            Err(e) if e.status == RtStatus::AlreadyRunning => {}
+            Err(_) => {
+                let _ = self.change(PluginState::Failed);
+            }
+        }
+        result
+    }
+
+    /// Records the result of `stop`: `Stopped` on success, `Failed` on an error. Stopping a
+    /// plugin that does not run keeps its state.
+    pub fn stopped<T>(&self, result: Result<T, RtError>) -> Result<T, RtError> {
+        match &result {
+            Ok(_) => {
+                let _ = self.change(PluginState::Stopped);
+            }
+            Err(e) if e.status == RtStatus::NotRunning => {}
+            Err(_) => {
+                let _ = self.change(PluginState::Failed);
+            }
+        }
+        result
+    }
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
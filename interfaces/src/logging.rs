@@ -0,0 +1,96 @@
+// Logging of plugins through the loader. Every plugin has its own copy of the `log` crate, the
+// logger set here forwards its records to the `log_write` capability so all of them end up in
+// the one sink configured by the loader, tagged with the component name.
+use crate::bindings;
+use crate::capabilities::{Capabilities, Function};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// Name of the capability every component gets from the loader.
+pub const LOG_WRITE_CAPABILITY: &str = "log_write";
+pub const LOG_WRITE_SIGNATURE: &str = "i32(cstr,i32,cstr,cstr)";
+
+/// `log_write(component, level, target, message)`, `level` is 1 (error) to 5 (trace). Returns the
+/// most verbose level enabled for the component in the same numbering, 0 if it is off. Level 0
+/// only queries it.
+pub type LogWrite =
+    unsafe extern "C" fn(*const c_char, i32, *const c_char, *const c_char) -> i32;
+
+struct PluginLogger {
+    sink: RwLock<Option<(CString, Function<LogWrite>)>>,
+}
+
+static LOGGER: PluginLogger = PluginLogger {
+    sink: RwLock::new(None),
+};
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+impl Log for PluginLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let sink = self.sink.read().unwrap();
+        let Some((component, write)) = sink.as_ref() else {
+            return;
+        };
+        let target = CString::new(record.target()).unwrap_or_default();
+        let message = CString::new(record.args().to_string().replace('\0', ""))
+            .unwrap_or_default();
+        let enabled = unsafe {
+            write(
+                component.as_ptr(),
+                record.level() as i32,
+                target.as_ptr(),
+                message.as_ptr(),
+            )
+        };
+        log::set_max_level(level_filter(enabled));
+    }
+
+    fn flush(&self) {}
+}
+
+fn level_filter(level: i32) -> LevelFilter {
+    LevelFilter::iter()
+        .find(|filter| *filter as i32 == level)
+        .unwrap_or(LevelFilter::Trace)
+}
+
+/// Sends the log records of the calling plugin to the `log_write` capability, tagged with
+/// `component`. Call it in `start` again after a restart, the capability may have moved. Fails
+/// if `caps` has no `log_write` or the plugin installed another logger, the plugin may fall
+/// back to its own logger then.
+pub fn init(caps: &Capabilities, component: &str) -> Result<(), String> {
+    let capability = caps
+        .get(LOG_WRITE_CAPABILITY)
+        .ok_or_else(|| format!("Capability {} is missing", LOG_WRITE_CAPABILITY))?;
+    let write = unsafe { capability.get::<LogWrite>()? };
+    let component = CString::new(component).map_err(|e| e.to_string())?;
+    let enabled = unsafe { write(component.as_ptr(), 0, std::ptr::null(), std::ptr::null()) };
+    *LOGGER.sink.write().unwrap() = Some((component, write));
+
+    // a restarted plugin has set the logger before
+    if log::set_logger(&LOGGER).is_ok() {
+        INSTALLED.store(true, Ordering::SeqCst);
+    } else if !INSTALLED.load(Ordering::SeqCst) {
+        return Err("Another logger is installed".to_string());
+    }
+    log::set_max_level(level_filter(enabled));
+    Ok(())
+}
+
+/// `init`, falling back to `env_logger` without `log_write`, e.g. for a plugin started outside
+/// the loader. A reloaded or restarted plugin finds its logger installed already.
+pub fn init_with_fallback(caps: &bindings::Capabilities, component: &str) {
+    if init(&Capabilities::from_raw(caps), component).is_err() {
+        let _ = env_logger::try_init();
+    }
+}
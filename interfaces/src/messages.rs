@@ -0,0 +1,289 @@
+// Typed messages for common robotics data, shared by the plugins instead of ad-hoc encodings.
+// A message is written to the blackboard as json, which the webinterface and the bridges show
+// as it is, or as MessagePack bytes where size matters, e.g. for high rate poses. Both carry the
+// version of their layout as `v` next to the fields, e.g.
+// `{"v": 1, "position": {"x": 1.5, "y": 0, "z": 0}, "orientation": {...}}`. Reading fails for a
+// newer version than the reader knows, so a plugin notices a peer writing a layout it does not
+// understand instead of reading defaults.
+//
+// The fields follow the ROS 2 messages where there is one, e.g. `Pose` is `geometry_msgs/Pose`
+// with the frame of `std_msgs/Header`. Fields left out when reading get their defaults.
+use crate::blackboard::TypedBlackboardValue;
+use crate::status::{RtError, RtStatus};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A message type. `VERSION` is incremented whenever a change of the layout breaks readers.
+pub trait Message: Serialize + DeserializeOwned {
+    const NAME: &'static str;
+    const VERSION: u32;
+}
+
+/// How a message is written to the blackboard.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MsgPack, // a bytes value
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Vector3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quaternion {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+// no rotation
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0,
+        }
+    }
+}
+
+/// Position and orientation in `frame_id`, e.g. `map`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pose {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub frame_id: String,
+    pub position: Vector3,
+    pub orientation: Quaternion,
+}
+
+impl Message for Pose {
+    const NAME: &'static str = "pose";
+    const VERSION: u32 = 1;
+}
+
+/// Linear velocity in m/s and angular velocity in rad/s.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Twist {
+    pub linear: Vector3,
+    pub angular: Vector3,
+}
+
+impl Message for Twist {
+    const NAME: &'static str = "twist";
+    const VERSION: u32 = 1;
+}
+
+/// Level of a `Status`, the levels of `diagnostic_msgs/DiagnosticStatus`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusLevel {
+    #[default]
+    Ok,
+    Warn,
+    Error,
+    Stale, // not updated anymore
+}
+
+/// Status of a part of the robot, e.g. `{"name": "battery", "level": "warn", "message": "Low"}`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Status {
+    pub name: String,
+    pub level: StatusLevel,
+    pub message: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, String>, // details, e.g. `{"voltage": "11.2"}`
+}
+
+impl Message for Status {
+    const NAME: &'static str = "status";
+    const VERSION: u32 = 1;
+}
+
+/// A task handed to the component executing it, answered with a `TaskResult` of the same `id`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskRequest {
+    pub id: String,
+    pub task: String, // e.g. `navigate`
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub params: serde_json::Value, // e.g. `{"goal": "dock"}`
+}
+
+impl Message for TaskRequest {
+    const NAME: &'static str = "task_request";
+    const VERSION: u32 = 1;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    #[default]
+    Accepted,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskResult {
+    pub id: String, // of the request
+    pub state: TaskState,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub result: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // why the task failed
+}
+
+impl Message for TaskResult {
+    const NAME: &'static str = "task_result";
+    const VERSION: u32 = 1;
+}
+
+// a message with its version, written as one map
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    v: u32,
+    #[serde(flatten)]
+    message: &'a T,
+}
+
+#[derive(Deserialize)]
+struct Version {
+    v: Option<u32>,
+}
+
+fn invalid<T: Message>(reason: impl std::fmt::Display) -> RtError {
+    RtError::new(RtStatus::InvalidArgument, format!("Invalid {} message: {}", T::NAME, reason))
+}
+
+/// The blackboard value holding `message` in `encoding`.
+pub fn to_value<T: Message>(
+    message: &T,
+    encoding: Encoding,
+) -> Result<TypedBlackboardValue, RtError> {
+    let versioned = Versioned {
+        v: T::VERSION,
+        message,
+    };
+    match encoding {
+        Encoding::Json => serde_json::to_value(&versioned)
+            .map(TypedBlackboardValue::Json)
+            .map_err(invalid::<T>),
+        Encoding::MsgPack => rmp_serde::to_vec_named(&versioned)
+            .map(TypedBlackboardValue::Bytes)
+            .map_err(invalid::<T>),
+    }
+}
+
+/// The message held by a json or bytes `value`. Fails for other types, layouts of a newer
+/// version and values without version.
+pub fn from_value<T: Message>(value: &TypedBlackboardValue) -> Result<T, RtError> {
+    fn decode<U: DeserializeOwned>(value: &TypedBlackboardValue) -> Result<U, String> {
+        match value {
+            TypedBlackboardValue::Json(json) => U::deserialize(json).map_err(|e| e.to_string()),
+            TypedBlackboardValue::Bytes(bytes) => {
+                rmp_serde::from_slice(bytes).map_err(|e| e.to_string())
+            }
+            other => Err(format!("a {} value is no message", other.type_name())),
+        }
+    }
+
+    let version = decode::<Version>(value).map_err(invalid::<T>)?.v;
+    match version {
+        None => return Err(invalid::<T>("it has no version 'v'")),
+        Some(v) if v > T::VERSION => {
+            return Err(invalid::<T>(format!(
+                "version {} is newer than {}",
+                v,
+                T::VERSION
+            )))
+        }
+        Some(_) => {}
+    }
+    decode(value).map_err(invalid::<T>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pose() -> Pose {
+        Pose {
+            frame_id: "map".to_string(),
+            position: Vector3 {
+                x: 1.5,
+                y: -2.0,
+                z: 0.0,
+            },
+            orientation: Quaternion::default(),
+        }
+    }
+
+    #[test]
+    fn test_encodings() {
+        let value = to_value(&pose(), Encoding::Json).unwrap();
+        let TypedBlackboardValue::Json(json) = &value else {
+            panic!("json is written as json");
+        };
+        assert_eq!(json["v"], 1);
+        assert_eq!(json["position"]["x"], 1.5);
+        assert_eq!(json["orientation"]["w"], 1.0);
+        assert_eq!(from_value::<Pose>(&value).unwrap(), pose());
+
+        let value = to_value(&pose(), Encoding::MsgPack).unwrap();
+        assert!(matches!(value, TypedBlackboardValue::Bytes(_)));
+        assert_eq!(from_value::<Pose>(&value).unwrap(), pose());
+
+        let result = TaskResult {
+            id: "7".to_string(),
+            state: TaskState::Failed,
+            result: json!({"reached": [1, 2]}),
+            error: Some("blocked".to_string()),
+        };
+        for encoding in [Encoding::Json, Encoding::MsgPack] {
+            let value = to_value(&result, encoding).unwrap();
+            assert_eq!(from_value::<TaskResult>(&value).unwrap(), result);
+        }
+    }
+
+    #[test]
+    fn test_defaults_and_versions() {
+        let twist = json!({"v": 1, "linear": {"x": 0.5}});
+        let twist = from_value::<Twist>(&TypedBlackboardValue::Json(twist)).unwrap();
+        assert_eq!(twist.linear.x, 0.5);
+        assert_eq!(twist.angular, Vector3::default());
+        let status = json!({"v": 1, "name": "battery", "level": "warn"});
+        let status = from_value::<Status>(&TypedBlackboardValue::Json(status)).unwrap();
+        assert_eq!(status.level, StatusLevel::Warn);
+        // empty details are left out
+        let expected = json!({"v": 1, "name": "battery", "level": "warn", "message": ""});
+        let value = to_value(&status, Encoding::Json).unwrap();
+        assert_eq!(value, TypedBlackboardValue::Json(expected));
+
+        let newer = TypedBlackboardValue::Json(json!({"v": 2, "task": "dock"}));
+        let error = from_value::<TaskRequest>(&newer).unwrap_err();
+        assert_eq!(error.status, RtStatus::InvalidArgument);
+        assert!(error.message.ends_with("version 2 is newer than 1"));
+        let unversioned = TypedBlackboardValue::Json(json!({"task": "dock"}));
+        assert!(from_value::<TaskRequest>(&unversioned).is_err());
+        assert!(from_value::<Pose>(&TypedBlackboardValue::Double(1.0)).is_err());
+        let wrong = TypedBlackboardValue::Json(json!({"v": 1, "position": "here"}));
+        assert!(from_value::<Pose>(&wrong).is_err());
+    }
+}
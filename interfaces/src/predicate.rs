@@ -0,0 +1,151 @@
+// Conditions on a blackboard key like `battery/level < 20`, used by the orchestration plugins.
+// The right side is a number, `true`, `false`, a quoted or a plain string. A key alone holds if
+// its value is true, not zero or not empty, `!key` if it does not.
+use crate::blackboard::{BlackboardValue, TypedBlackboardValue};
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+// longer operators first, `<=` must not be read as `<`
+const OPERATORS: [(&str, Operator); 6] = [
+    ("==", Operator::Equal),
+    ("!=", Operator::NotEqual),
+    ("<=", Operator::LessEqual),
+    (">=", Operator::GreaterEqual),
+    ("<", Operator::Less),
+    (">", Operator::Greater),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(f64),
+    Bool(bool),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Test {
+    Truthy(bool), // false for `!key`
+    Compare(Operator, Literal),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    key: String,
+    test: Test,
+    text: String,
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn literal(text: &str) -> Literal {
+    match serde_json::from_str::<BlackboardValue>(text) {
+        Ok(BlackboardValue::Int(value)) => Literal::Number(value as f64),
+        Ok(BlackboardValue::Int64(value)) => Literal::Number(value as f64),
+        Ok(BlackboardValue::Float(value)) => Literal::Number(value as f64),
+        Ok(BlackboardValue::Double(value)) => Literal::Number(value),
+        Ok(BlackboardValue::Bool(value)) => Literal::Bool(value),
+        Ok(BlackboardValue::String(value)) => Literal::String(value),
+        _ => Literal::String(text.to_string()),
+    }
+}
+
+fn number(value: &TypedBlackboardValue) -> Option<f64> {
+    match value {
+        TypedBlackboardValue::Int(value) => Some(*value as f64),
+        TypedBlackboardValue::Int64(value) => Some(*value as f64),
+        TypedBlackboardValue::Timestamp(value) => Some(*value as f64),
+        TypedBlackboardValue::Float(value) => Some(*value as f64),
+        TypedBlackboardValue::Double(value) => Some(*value),
+        _ => None,
+    }
+}
+
+impl Predicate {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let operator = OPERATORS
+            .iter()
+            .filter_map(|(token, operator)| text.find(token).map(|at| (at, *token, *operator)))
+            .min_by_key(|(at, token, _)| (*at, usize::MAX - token.len()));
+        let (key, test) = match operator {
+            Some((at, token, operator)) => {
+                let right = text[at + token.len()..].trim();
+                if right.is_empty() {
+                    return Err(format!("'{}' has nothing to compare with", text));
+                }
+                (text[..at].trim(), Test::Compare(operator, literal(right)))
+            }
+            None => match text.strip_prefix('!') {
+                Some(key) => (key.trim(), Test::Truthy(false)),
+                None => (text, Test::Truthy(true)),
+            },
+        };
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("'{}' is no condition on a key", text));
+        }
+        Ok(Predicate {
+            key: key.to_string(),
+            test,
+            text: text.to_string(),
+        })
+    }
+
+    /// The key the predicate reads.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Whether the value of the key satisfies the predicate, a missing key never does.
+    pub fn holds(&self, value: Option<&TypedBlackboardValue>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        let (operator, literal) = match &self.test {
+            Test::Truthy(expected) => {
+                let truthy = match value {
+                    TypedBlackboardValue::Bool(value) => *value,
+                    TypedBlackboardValue::String(value) => !value.is_empty(),
+                    value => number(value).is_none_or(|value| value != 0.0),
+                };
+                return truthy == *expected;
+            }
+            Test::Compare(operator, literal) => (*operator, literal),
+        };
+        let ordering = match (value, literal) {
+            (value, Literal::Number(expected)) => {
+                number(value).and_then(|value| value.partial_cmp(expected))
+            }
+            (TypedBlackboardValue::Bool(value), Literal::Bool(expected)) => {
+                Some(value.cmp(expected))
+            }
+            (TypedBlackboardValue::String(value), Literal::String(expected)) => {
+                Some(value.as_str().cmp(expected.as_str()))
+            }
+            _ => None,
+        };
+        match (operator, ordering) {
+            (Operator::NotEqual, None) => true,
+            (_, None) => false,
+            (Operator::Equal, Some(ordering)) => ordering == Ordering::Equal,
+            (Operator::NotEqual, Some(ordering)) => ordering != Ordering::Equal,
+            (Operator::Less, Some(ordering)) => ordering == Ordering::Less,
+            (Operator::LessEqual, Some(ordering)) => ordering != Ordering::Greater,
+            (Operator::Greater, Some(ordering)) => ordering == Ordering::Greater,
+            (Operator::GreaterEqual, Some(ordering)) => ordering != Ordering::Less,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Projects run by the skill runner of the loader, shared with the components managing them.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Blackboard key of the project description, e.g. `{"name": "demo", "skills": ["greet"]}`.
+pub const START_PROJECT_KEY: &str = "start_project";
+/// Set to `true` to stop the running project before its next skill.
+pub const STOP_PROJECT_KEY: &str = "stop_project";
+/// `running`, `finished`, `failed` or `stopped`.
+pub const PROJECT_STATUS_KEY: &str = "project/status";
+/// Number of skills of the project that have finished.
+pub const PROJECT_PROGRESS_KEY: &str = "project/progress";
+/// Reason why the project failed.
+pub const PROJECT_ERROR_KEY: &str = "project/error";
+/// Prefix of the keys holding the result of `run` of every skill, e.g. `project/result/greet`.
+pub const PROJECT_RESULT_PREFIX: &str = "project/result/";
+/// Prefix of the keys holding stored project descriptions, e.g. `projects/demo`.
+pub const PROJECT_DEFINITION_PREFIX: &str = "projects/";
+/// Name of the stored project started next.
+pub const PROJECT_SELECTED_KEY: &str = "project/selected";
+/// The assets of the running project as json, e.g. `{"map": "9f86..."}`, see `Project::assets`.
+pub const PROJECT_ASSETS_KEY: &str = "project/assets";
+
+/// Project description written to `start_project`, JSON or YAML.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Project {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub skills: Vec<String>, // run one after the other
+    /// Files uploaded to the webinterface the skills use, by name, e.g. `{"map": "9f86..."}`.
+    /// Skills get the path of an asset id from the capability `webinterface_asset_path`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<String, String>,
+}
+
+impl Project {
+    pub fn parse(description: &str) -> Result<Self, String> {
+        serde_yml::from_str(description)
+            .map_err(|e| format!("Invalid project: {}. Reason: {}", description, e))
+    }
+}
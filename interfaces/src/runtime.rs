@@ -0,0 +1,199 @@
+// Capabilities of the loader itself, given to every component next to `log_write`. They let a
+// component report on and restart the other components, e.g. for a web frontend, or run skills.
+use crate::capabilities::{function, Capabilities, Capability};
+use crate::status::{RtError, RtStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+
+pub const RUNTIME_STATUS_CAPABILITY: &str = "runtime_status";
+pub const RUNTIME_STATUS_SIGNATURE: &str = "i32(*mut char,i32)";
+pub const RUNTIME_COMPONENTS_CAPABILITY: &str = "runtime_components";
+pub const RUNTIME_COMPONENTS_SIGNATURE: &str = "i32(*mut char,i32)";
+pub const RUNTIME_RESTART_CAPABILITY: &str = "runtime_restart";
+pub const RUNTIME_RESTART_SIGNATURE: &str = "i32(cstr)";
+pub const RUNTIME_RUN_SKILL_CAPABILITY: &str = "runtime_run_skill";
+pub const RUNTIME_RUN_SKILL_SIGNATURE: &str = "i32(cstr)";
+pub const RUNTIME_READY_CAPABILITY: &str = "runtime_ready";
+pub const RUNTIME_READY_SIGNATURE: &str = "i32(cstr,i32)";
+pub const RUNTIME_METRICS_CAPABILITY: &str = "runtime_metrics";
+pub const RUNTIME_METRICS_SIGNATURE: &str = "i32(*mut char,i32)";
+pub const RUNTIME_CAPABILITY_CAPABILITY: &str = "runtime_capability";
+pub const RUNTIME_CAPABILITY_SIGNATURE: &str = "i32(cstr,*mut void)";
+
+/// `runtime_status(buffer, len)` writes the state of all components as json, like
+/// `get_last_error`, see `Components::states` of the loader. `RT_TIMEOUT` if the loader is busy.
+pub type RuntimeStatus = unsafe extern "C" fn(*mut c_char, c_int) -> c_int;
+/// `runtime_components(buffer, len)` writes every loaded library with its version, type,
+/// provided capabilities, requirements and state as json, see `Components::inventory` of the
+/// loader.
+pub type RuntimeComponents = unsafe extern "C" fn(*mut c_char, c_int) -> c_int;
+/// `runtime_restart(name)` restarts the running service `name` and the services requiring it.
+/// Returns once the restart is scheduled, it happens on a thread of the loader, so a service may
+/// restart itself. `RT_KEY_NOT_FOUND` for unknown services, `RT_NOT_RUNNING` for stopped ones,
+/// `RT_ACCESS_DENIED` for the blackboard, which the loader uses.
+pub type RuntimeRestart = unsafe extern "C" fn(*const c_char) -> c_int;
+/// `runtime_run_skill(name)` runs the loaded skill `name` with its requirements and returns what
+/// its `run` returns. `RT_KEY_NOT_FOUND` for unknown skills, `RT_TIMEOUT` if the loader is busy,
+/// like while a project runs.
+pub type RuntimeRunSkill = unsafe extern "C" fn(*const c_char) -> c_int;
+/// `runtime_ready(name, timeout_ms)` waits until the services and keys in the `wait_for` of the
+/// skill `name` are started and written, for its `wait_timeout_ms` if `timeout_ms` is negative.
+/// `RT_TIMEOUT` if they are not ready in time, `RT_KEY_NOT_FOUND` for unknown skills.
+pub type RuntimeReady = unsafe extern "C" fn(*const c_char, c_int) -> c_int;
+/// `runtime_metrics(buffer, len)` writes the `RuntimeMetrics` of the loader as json, like
+/// `runtime_status`.
+pub type RuntimeMetrics = unsafe extern "C" fn(*mut c_char, c_int) -> c_int;
+/// `runtime_capability(name, capability)` fills the `Capability` `name` provided by a running
+/// service or a loaded skill, with its function, signature and version. `RT_KEY_NOT_FOUND` if no
+/// library provides it, `RT_NOT_RUNNING` if its service is stopped.
+pub type RuntimeCapability = unsafe extern "C" fn(*const c_char, *mut c_void) -> c_int;
+
+/// Metrics the loader samples about itself and its components.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    pub process: ProcessMetrics,
+    pub threads: Vec<ThreadMetrics>,
+    pub starts: BTreeMap<String, f64>, // seconds the last `start` of a service took
+    pub skills: BTreeMap<String, SkillMetrics>,
+    pub blackboard: BTreeMap<String, u64>, // calls per blackboard capability
+}
+
+/// CPU and memory of the loader process, zero where `/proc` is not available.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessMetrics {
+    pub cpu_percent: f64, // of one core, since the sample before
+    pub cpu_seconds: f64,
+    pub resident_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThreadMetrics {
+    pub id: u32,
+    pub name: String,
+    pub cpu_percent: f64,
+    pub cpu_seconds: f64,
+}
+
+/// The runs of a skill, failed ones returned an error or a negative result.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SkillMetrics {
+    pub runs: u64,
+    pub failures: u64,
+    pub total_seconds: f64,
+    pub max_seconds: f64,
+}
+
+// the json a report capability like `runtime_status` writes
+fn report(caps: &Capabilities, capability: &str) -> Result<serde_json::Value, RtError> {
+    let report = function::<RuntimeStatus>(caps, capability)?;
+    let mut buffer = vec![0u8; 4096];
+    // the report may grow between two calls, so the size is only known once it fits
+    loop {
+        let size = unsafe { report(buffer.as_mut_ptr() as *mut c_char, buffer.len() as c_int) };
+        if size < 0 {
+            return Err(RtError::new(
+                RtStatus::from_code(size),
+                format!("{} failed", capability),
+            ));
+        }
+        if size as usize <= buffer.len() {
+            buffer.truncate(size as usize - 1);
+            break;
+        }
+        buffer.resize(size as usize, 0);
+    }
+    serde_json::from_slice(&buffer)
+        .map_err(|e| RtError::from(format!("Invalid {}: {}", capability, e)))
+}
+
+/// State of all components, reported by the loader.
+pub fn status(caps: &Capabilities) -> Result<serde_json::Value, RtError> {
+    report(caps, RUNTIME_STATUS_CAPABILITY)
+}
+
+/// The loaded libraries with what they provide and require, reported by the loader.
+pub fn components(caps: &Capabilities) -> Result<serde_json::Value, RtError> {
+    report(caps, RUNTIME_COMPONENTS_CAPABILITY)
+}
+
+/// Metrics of the loader, see `RuntimeMetrics`.
+pub fn metrics(caps: &Capabilities) -> Result<Metrics, RtError> {
+    serde_json::from_value(report(caps, RUNTIME_METRICS_CAPABILITY)?)
+        .map_err(|e| RtError::from(format!("Invalid {}: {}", RUNTIME_METRICS_CAPABILITY, e)))
+}
+
+/// Asks the loader to restart the service `name`.
+pub fn restart(caps: &Capabilities, name: &str) -> Result<(), RtError> {
+    let restart = function::<RuntimeRestart>(caps, RUNTIME_RESTART_CAPABILITY)?;
+    let cname = CString::new(name).map_err(|e| e.to_string())?;
+    match unsafe { restart(cname.as_ptr()) } {
+        0 => Ok(()),
+        code => {
+            let status = RtStatus::from_code(code);
+            Err(RtError::new(
+                status,
+                format!("Service '{}' can not be restarted: {}", name, status),
+            ))
+        }
+    }
+}
+
+/// The capability `name` of any running library, looked up by the loader.
+pub fn capability(caps: &Capabilities, name: &str) -> Result<Capability, RtError> {
+    let lookup = function::<RuntimeCapability>(caps, RUNTIME_CAPABILITY_CAPABILITY)?;
+    let cname = CString::new(name).map_err(|e| e.to_string())?;
+    let mut capability = *Capability::new("", std::ptr::null_mut()).inner();
+    let result = unsafe { lookup(cname.as_ptr(), &mut capability as *mut _ as *mut c_void) };
+    match result {
+        0 => Ok(Capability::from_raw(&capability)),
+        code => {
+            let status = RtStatus::from_code(code);
+            Err(RtError::new(
+                status,
+                format!("Capability '{}' is not available: {}", name, status),
+            ))
+        }
+    }
+}
+
+/// Runs the skill `name` through the loader, the result of its `run` if it is not an error.
+pub fn run_skill(caps: &Capabilities, name: &str) -> Result<i32, RtError> {
+    let run = function::<RuntimeRunSkill>(caps, RUNTIME_RUN_SKILL_CAPABILITY)?;
+    let cname = CString::new(name).map_err(|e| e.to_string())?;
+    match unsafe { run(cname.as_ptr()) } {
+        result if result >= 0 => Ok(result),
+        code => {
+            let status = RtStatus::from_code(code);
+            Err(RtError::new(
+                status,
+                format!("Skill '{}' failed: {}", name, status),
+            ))
+        }
+    }
+}
+
+/// Waits until the skill `name` is ready to run, see `RuntimeReady`. None waits for the
+/// `wait_timeout_ms` of the skill.
+pub fn ready(
+    caps: &Capabilities,
+    name: &str,
+    timeout: Option<std::time::Duration>,
+) -> Result<(), RtError> {
+    let ready = function::<RuntimeReady>(caps, RUNTIME_READY_CAPABILITY)?;
+    let cname = CString::new(name).map_err(|e| e.to_string())?;
+    let timeout_ms = timeout.map_or(-1, |timeout| {
+        timeout.as_millis().min(c_int::MAX as u128) as c_int
+    });
+    match unsafe { ready(cname.as_ptr(), timeout_ms) } {
+        0 => Ok(()),
+        code => {
+            let status = RtStatus::from_code(code);
+            Err(RtError::new(
+                status,
+                format!("Skill '{}' is not ready: {}", name, status),
+            ))
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Capabilities of the `scheduler` plugin. A schedule writes the number of times it fired to its
+// key, so skills subscribe to the key instead of running their own timer thread.
+use crate::capabilities::{function, Capabilities};
+use crate::status::{RtError, RtStatus};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::time::Duration;
+
+pub const SCHEDULE_PERIODIC_CAPABILITY: &str = "schedule_periodic";
+pub const SCHEDULE_CRON_CAPABILITY: &str = "schedule_cron";
+pub const SCHEDULE_CANCEL_CAPABILITY: &str = "schedule_cancel";
+
+/// `schedule_periodic(key, period_ms)` fires `key` every `period_ms` milliseconds, replacing an
+/// earlier schedule of the key.
+pub type SchedulePeriodic = unsafe extern "C" fn(*const c_char, c_int) -> c_int;
+/// `schedule_cron(key, expression)` fires `key` at the minutes matching the cron expression
+/// `minute hour day-of-month month day-of-week` in local time. `RT_INVALID_ARGUMENT` if it never
+/// matches.
+pub type ScheduleCron = unsafe extern "C" fn(*const c_char, *const c_char) -> c_int;
+/// `schedule_cancel(key)` stops firing `key`, `RT_KEY_NOT_FOUND` if it is not scheduled.
+pub type ScheduleCancel = unsafe extern "C" fn(*const c_char) -> c_int;
+
+fn c_string(value: &str) -> Result<CString, RtError> {
+    CString::new(value).map_err(|e| RtError::new(RtStatus::InvalidArgument, e.to_string()))
+}
+
+fn check(code: c_int, what: &str) -> Result<(), RtError> {
+    match code {
+        0 => Ok(()),
+        code => {
+            let status = RtStatus::from_code(code);
+            Err(RtError::new(status, format!("{} failed: {}", what, status)))
+        }
+    }
+}
+
+pub fn schedule_periodic(caps: &Capabilities, key: &str, period: Duration) -> Result<(), RtError> {
+    let schedule = function::<SchedulePeriodic>(caps, SCHEDULE_PERIODIC_CAPABILITY)?;
+    let period = c_int::try_from(period.as_millis())
+        .map_err(|_| RtError::new(RtStatus::InvalidArgument, "Period is too long"))?;
+    let ckey = c_string(key)?;
+    check(
+        unsafe { schedule(ckey.as_ptr(), period) },
+        &format!("Scheduling {}", key),
+    )
+}
+
+pub fn schedule_cron(caps: &Capabilities, key: &str, expression: &str) -> Result<(), RtError> {
+    let schedule = function::<ScheduleCron>(caps, SCHEDULE_CRON_CAPABILITY)?;
+    let (ckey, cexpression) = (c_string(key)?, c_string(expression)?);
+    check(
+        unsafe { schedule(ckey.as_ptr(), cexpression.as_ptr()) },
+        &format!("Scheduling {} at '{}'", key, expression),
+    )
+}
+
+pub fn cancel(caps: &Capabilities, key: &str) -> Result<(), RtError> {
+    let cancel = function::<ScheduleCancel>(caps, SCHEDULE_CANCEL_CAPABILITY)?;
+    let ckey = c_string(key)?;
+    check(
+        unsafe { cancel(ckey.as_ptr()) },
+        &format!("Cancelling {}", key),
+    )
+}
@@ -0,0 +1,354 @@
+// Numeric blackboard values mirrored to a POSIX shared memory region, so other processes read
+// them without a call into the blackboard. The blackboard is the only writer, readers open the
+// region by name and never block it.
+//
+// The layout is native endian and starts with a header of 64 bytes:
+//
+//     offset  0  u32  magic, `MAGIC`
+//     offset  4  u32  layout version, `LAYOUT_VERSION`
+//     offset  8  u32  number of slots
+//     offset 12  u32  size of a slot, 64
+//
+// followed by the slots, a hash table with linear probing. A key is placed at the first free
+// slot from `fnv1a(key) % slots` on and keeps it until the region is removed:
+//
+//     offset  0  u32  state, 0 empty, 1 claimed by the writer, 2 ready
+//     offset  4  u32  type tag of the value, `Tag`, 0 while the key holds no numeric value
+//     offset  8  u64  sequence, odd while the value is written, counts two per write
+//     offset 16  u64  value, integers sign extended, floats as their bits
+//     offset 24  40B  key, utf-8 padded with zeros, so keys have at most `MAX_KEY_LEN` bytes
+//
+// A reader probes from the hash until the key or an empty slot is found, then reads the
+// sequence, tag and value and the sequence again and retries while the sequence was odd or
+// changed.
+use crate::blackboard::TypedBlackboardValue;
+use std::ffi::CString;
+use std::sync::atomic::{fence, AtomicU32, AtomicU64, Ordering};
+
+pub const MAGIC: u32 = u32::from_le_bytes(*b"RTBB");
+pub const LAYOUT_VERSION: u32 = 1;
+pub const MAX_KEY_LEN: usize = 39;
+
+const EMPTY: u32 = 0;
+const CLAIMED: u32 = 1;
+const READY: u32 = 2;
+
+/// Type of the value in a slot.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    None = 0,
+    Int = 1,
+    Int64 = 2,
+    Timestamp = 3,
+    Float = 4,
+    Double = 5,
+    Bool = 6,
+}
+
+#[repr(C)]
+struct Header {
+    magic: AtomicU32, // written last, once the header is complete
+    layout: u32,
+    slots: u32,
+    slot_size: u32,
+    reserved: [u8; 48],
+}
+
+#[repr(C)]
+struct Slot {
+    state: AtomicU32,
+    tag: AtomicU32,
+    sequence: AtomicU64,
+    value: AtomicU64,
+    key: [u8; MAX_KEY_LEN + 1],
+}
+
+// FNV-1a, 64 bit
+fn fnv1a(key: &[u8]) -> u64 {
+    key.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn encode(value: &TypedBlackboardValue) -> Option<(Tag, u64)> {
+    Some(match value {
+        TypedBlackboardValue::Int(v) => (Tag::Int, *v as i64 as u64),
+        TypedBlackboardValue::Int64(v) => (Tag::Int64, *v as u64),
+        TypedBlackboardValue::Timestamp(v) => (Tag::Timestamp, *v),
+        TypedBlackboardValue::Float(v) => (Tag::Float, v.to_bits() as u64),
+        TypedBlackboardValue::Double(v) => (Tag::Double, v.to_bits()),
+        TypedBlackboardValue::Bool(v) => (Tag::Bool, *v as u64),
+        _ => return None,
+    })
+}
+
+fn decode(tag: u32, bits: u64) -> Option<TypedBlackboardValue> {
+    Some(match tag {
+        t if t == Tag::Int as u32 => TypedBlackboardValue::Int(bits as i64 as i32),
+        t if t == Tag::Int64 as u32 => TypedBlackboardValue::Int64(bits as i64),
+        t if t == Tag::Timestamp as u32 => TypedBlackboardValue::Timestamp(bits),
+        t if t == Tag::Float as u32 => TypedBlackboardValue::Float(f32::from_bits(bits as u32)),
+        t if t == Tag::Double as u32 => TypedBlackboardValue::Double(f64::from_bits(bits)),
+        t if t == Tag::Bool as u32 => TypedBlackboardValue::Bool(bits != 0),
+        _ => return None,
+    })
+}
+
+/// A mapped region, created by the blackboard or opened read only by a reader.
+#[derive(Debug)]
+pub struct SharedBlackboard {
+    name: CString,
+    base: *mut u8,
+    size: usize,
+    slots: usize,
+    owner: bool, // removes the region when dropped
+}
+
+unsafe impl Send for SharedBlackboard {}
+unsafe impl Sync for SharedBlackboard {}
+
+impl SharedBlackboard {
+    /// Creates the region `name`, e.g. `/rtime-blackboard`, with room for `slots` keys. A region
+    /// left behind under the name is cleared.
+    pub fn create(name: &str, slots: usize) -> Result<Self, String> {
+        if slots == 0 || slots > u32::MAX as usize {
+            return Err(format!("Invalid number of slots: {}", slots));
+        }
+        let cname = CString::new(name).map_err(|e| e.to_string())?;
+        let size = std::mem::size_of::<Header>() + slots * std::mem::size_of::<Slot>();
+        let base = unsafe {
+            let fd = libc::shm_open(cname.as_ptr(), libc::O_CREAT | libc::O_RDWR, 0o600);
+            if fd < 0 {
+                return Err(format!(
+                    "Can not create shared memory {}: {}",
+                    name,
+                    std::io::Error::last_os_error()
+                ));
+            }
+            // truncating to zero first clears a left over region
+            let resized =
+                libc::ftruncate(fd, 0) == 0 && libc::ftruncate(fd, size as libc::off_t) == 0;
+            let base = resized.then(|| map(fd, size, libc::PROT_READ | libc::PROT_WRITE));
+            let error = std::io::Error::last_os_error();
+            libc::close(fd);
+            match base.flatten() {
+                Some(base) => base,
+                None => {
+                    libc::shm_unlink(cname.as_ptr());
+                    return Err(format!("Can not map shared memory {}: {}", name, error));
+                }
+            }
+        };
+        let header = unsafe { &mut *(base as *mut Header) };
+        header.layout = LAYOUT_VERSION;
+        header.slots = slots as u32;
+        header.slot_size = std::mem::size_of::<Slot>() as u32;
+        header.magic.store(MAGIC, Ordering::Release);
+        Ok(SharedBlackboard {
+            name: cname,
+            base,
+            size,
+            slots,
+            owner: true,
+        })
+    }
+
+    /// Opens the region `name` for reading. Fails unless it was written with this layout.
+    pub fn open(name: &str) -> Result<Self, String> {
+        let cname = CString::new(name).map_err(|e| e.to_string())?;
+        let (base, size) = unsafe {
+            let fd = libc::shm_open(cname.as_ptr(), libc::O_RDONLY, 0);
+            if fd < 0 {
+                return Err(format!(
+                    "Can not open shared memory {}: {}",
+                    name,
+                    std::io::Error::last_os_error()
+                ));
+            }
+            let mut stat: libc::stat = std::mem::zeroed();
+            let size = if libc::fstat(fd, &mut stat) == 0 {
+                stat.st_size as usize
+            } else {
+                0
+            };
+            let base = (size >= std::mem::size_of::<Header>())
+                .then(|| map(fd, size, libc::PROT_READ))
+                .flatten();
+            libc::close(fd);
+            (
+                base.ok_or_else(|| format!("Can not map shared memory {}", name))?,
+                size,
+            )
+        };
+        let mut region = SharedBlackboard {
+            name: cname,
+            base,
+            size,
+            slots: 0,
+            owner: false,
+        };
+        let header = region.header();
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(format!("{} is no blackboard region", name));
+        }
+        if header.layout != LAYOUT_VERSION || header.slot_size != std::mem::size_of::<Slot>() as u32
+        {
+            return Err(format!(
+                "{} has layout {}, expected {}",
+                name, header.layout, LAYOUT_VERSION
+            ));
+        }
+        let slots = header.slots as usize;
+        if std::mem::size_of::<Header>() + slots * std::mem::size_of::<Slot>() > size {
+            return Err(format!("{} is truncated", name));
+        }
+        region.slots = slots;
+        Ok(region)
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &*(self.base as *const Header) }
+    }
+
+    fn slot(&self, index: usize) -> *mut Slot {
+        unsafe { (self.base.add(std::mem::size_of::<Header>()) as *mut Slot).add(index) }
+    }
+
+    // index of the ready slot of `key`
+    fn find(&self, key: &[u8]) -> Option<usize> {
+        let start = (fnv1a(key) % self.slots as u64) as usize;
+        (0..self.slots)
+            .map(|probe| (start + probe) % self.slots)
+            .map_while(|index| {
+                let slot = unsafe { &*self.slot(index) };
+                match slot.state.load(Ordering::Acquire) {
+                    EMPTY => None,
+                    READY if slot_key(slot) == key => Some(Some(index)),
+                    _ => Some(None),
+                }
+            })
+            .flatten()
+            .next()
+    }
+
+    // index of the slot of `key`, claiming a free one for a new key
+    fn find_or_claim(&self, key: &[u8]) -> Option<usize> {
+        if let Some(index) = self.find(key) {
+            return Some(index);
+        }
+        let start = (fnv1a(key) % self.slots as u64) as usize;
+        let index = (0..self.slots)
+            .map(|probe| (start + probe) % self.slots)
+            .find(|index| unsafe { &*self.slot(*index) }.state.load(Ordering::Acquire) == EMPTY)?;
+        unsafe {
+            let slot = self.slot(index);
+            (*slot).state.store(CLAIMED, Ordering::Relaxed);
+            (&mut (*slot).key)[..key.len()].copy_from_slice(key);
+            (*slot).state.store(READY, Ordering::Release);
+        }
+        Some(index)
+    }
+
+    fn store(&self, index: usize, tag: Tag, bits: u64) {
+        let slot = unsafe { &*self.slot(index) };
+        let sequence = slot.sequence.load(Ordering::Relaxed);
+        slot.sequence.store(sequence + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.tag.store(tag as u32, Ordering::Relaxed);
+        slot.value.store(bits, Ordering::Relaxed);
+        slot.sequence.store(sequence + 2, Ordering::Release);
+    }
+
+    /// Writes a numeric value of `key`, other values clear it. Returns false if the key is too
+    /// long or all slots are taken. Only the creator of the region writes.
+    pub fn write(&self, key: &str, value: &TypedBlackboardValue) -> bool {
+        if !self.owner || key.len() > MAX_KEY_LEN {
+            return false;
+        }
+        let Some(index) = self.find_or_claim(key.as_bytes()) else {
+            return false;
+        };
+        let (tag, bits) = encode(value).unwrap_or((Tag::None, 0));
+        self.store(index, tag, bits);
+        true
+    }
+
+    /// Clears the value of `key`, the key keeps its slot.
+    pub fn remove(&self, key: &str) {
+        if !self.owner {
+            return;
+        }
+        if let Some(index) = self.find(key.as_bytes()) {
+            self.store(index, Tag::None, 0);
+        }
+    }
+
+    /// Clears the values of all keys.
+    pub fn clear(&self) {
+        if !self.owner {
+            return;
+        }
+        for index in 0..self.slots {
+            if unsafe { &*self.slot(index) }.state.load(Ordering::Acquire) == READY {
+                self.store(index, Tag::None, 0);
+            }
+        }
+    }
+
+    /// The value of `key` and its version, which grows with every write. `None` while the key
+    /// holds no numeric value.
+    pub fn read(&self, key: &str) -> Option<(TypedBlackboardValue, u64)> {
+        let slot = unsafe { &*self.slot(self.find(key.as_bytes())?) };
+        loop {
+            let before = slot.sequence.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let tag = slot.tag.load(Ordering::Relaxed);
+            let bits = slot.value.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if slot.sequence.load(Ordering::Relaxed) == before {
+                return decode(tag, bits).map(|value| (value, before / 2));
+            }
+        }
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+}
+
+// the key of a ready slot, without its padding
+fn slot_key(slot: &Slot) -> &[u8] {
+    let len = slot
+        .key
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(slot.key.len());
+    &slot.key[..len]
+}
+
+unsafe fn map(fd: libc::c_int, size: usize, protection: libc::c_int) -> Option<*mut u8> {
+    let base = libc::mmap(
+        std::ptr::null_mut(),
+        size,
+        protection,
+        libc::MAP_SHARED,
+        fd,
+        0,
+    );
+    (base != libc::MAP_FAILED).then_some(base as *mut u8)
+}
+
+impl Drop for SharedBlackboard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base as *mut libc::c_void, self.size);
+            if self.owner {
+                libc::shm_unlink(self.name.as_ptr());
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Textual signatures of capability functions, e.g. "i32(cstr,cstr)". Providers declare them in
+// their summary and `Capability::get` compares them with the function type the caller asks for.
+use crate::bindings;
+use std::os::raw::{c_char, c_void};
+
+/// Name of a type as it appears in a capability signature.
+pub trait CType {
+    fn c_type() -> String;
+}
+
+macro_rules! c_type {
+    ($($t:ty => $name:expr),* $(,)?) => {
+        $(
+            impl CType for $t {
+                fn c_type() -> String {
+                    $name.to_string()
+                }
+            }
+        )*
+    };
+}
+
+c_type! {
+    () => "void",
+    bool => "bool",
+    c_char => "char",
+    u8 => "u8",
+    i32 => "i32",
+    u32 => "u32",
+    i64 => "i64",
+    u64 => "u64",
+    f32 => "f32",
+    f64 => "f64",
+    usize => "usize",
+    *const c_char => "cstr",
+    *mut c_char => "*mut char",
+    *const c_void => "*const void",
+    *mut c_void => "*mut void",
+    *const u8 => "*const u8",
+    *mut u8 => "*mut u8",
+    *const i32 => "*const i32",
+    *mut i32 => "*mut i32",
+    *mut u32 => "*mut u32",
+    *mut i64 => "*mut i64",
+    *mut u64 => "*mut u64",
+    *const f32 => "*const f32",
+    *mut f32 => "*mut f32",
+    *const f64 => "*const f64",
+    *mut f64 => "*mut f64",
+    *mut bool => "*mut bool",
+    &bindings::Capabilities => "caps",
+    &bindings::LegacyCapabilities => "legacy_caps",
+    *mut bindings::rt_context => "ctx",
+}
+
+/// Signature of a function type, `<return>(<arg>,<arg>,...)`.
+pub trait Signature {
+    fn signature() -> String;
+}
+
+macro_rules! fn_signature {
+    ($($arg:ident),*) => {
+        impl<R: CType, $($arg: CType),*> Signature for unsafe extern "C" fn($($arg),*) -> R {
+            fn signature() -> String {
+                let args: Vec<String> = vec![$($arg::c_type()),*];
+                format!("{}({})", R::c_type(), args.join(","))
+            }
+        }
+
+        impl<R: CType, $($arg: CType),*> Signature for extern "C" fn($($arg),*) -> R {
+            fn signature() -> String {
+                let args: Vec<String> = vec![$($arg::c_type()),*];
+                format!("{}({})", R::c_type(), args.join(","))
+            }
+        }
+    };
+}
+
+fn_signature!();
+fn_signature!(A);
+fn_signature!(A, B);
+fn_signature!(A, B, C);
+fn_signature!(A, B, C, D);
+fn_signature!(A, B, C, D, E);
+fn_signature!(A, B, C, D, E, F);
+
+/// Drops whitespace so "i32(cstr, cstr)" and "i32(cstr,cstr)" compare equal.
+pub fn normalize(signature: &str) -> String {
+    signature.chars().filter(|c| !c.is_whitespace()).collect()
+}
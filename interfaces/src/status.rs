@@ -0,0 +1,201 @@
+use crate::bindings;
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::panic::AssertUnwindSafe;
+
+/// Status returned by the capabilities of all plugins, see `RtStatus` in caps.h.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtStatus {
+    Ok = bindings::rt_status_RT_OK,
+    Error = bindings::rt_status_RT_ERROR,
+    ValueMismatch = bindings::rt_status_RT_VALUE_MISMATCH,
+    TypeMismatch = bindings::rt_status_RT_TYPE_MISMATCH,
+    Timeout = bindings::rt_status_RT_TIMEOUT,
+    NotRunning = bindings::rt_status_RT_NOT_RUNNING,
+    AlreadyRunning = bindings::rt_status_RT_ALREADY_RUNNING,
+    KeyNotFound = bindings::rt_status_RT_KEY_NOT_FOUND,
+    BufferTooSmall = bindings::rt_status_RT_BUFFER_TOO_SMALL,
+    NullArgument = bindings::rt_status_RT_NULL_ARGUMENT,
+    InvalidArgument = bindings::rt_status_RT_INVALID_ARGUMENT,
+    AccessDenied = bindings::rt_status_RT_ACCESS_DENIED,
+    ConstraintViolation = bindings::rt_status_RT_CONSTRAINT_VIOLATION,
+}
+
+impl RtStatus {
+    const ALL: [RtStatus; 13] = [
+        RtStatus::Ok,
+        RtStatus::Error,
+        RtStatus::ValueMismatch,
+        RtStatus::TypeMismatch,
+        RtStatus::Timeout,
+        RtStatus::NotRunning,
+        RtStatus::AlreadyRunning,
+        RtStatus::KeyNotFound,
+        RtStatus::BufferTooSmall,
+        RtStatus::NullArgument,
+        RtStatus::InvalidArgument,
+        RtStatus::AccessDenied,
+        RtStatus::ConstraintViolation,
+    ];
+
+    pub fn code(self) -> c_int {
+        self as c_int
+    }
+
+    /// Status of a return value, non negative values are sizes or counts and mean `Ok`. Unknown
+    /// negative values are reported as `Error`.
+    pub fn from_code(code: c_int) -> Self {
+        if code >= 0 {
+            return RtStatus::Ok;
+        }
+        RtStatus::ALL
+            .into_iter()
+            .find(|status| status.code() == code)
+            .unwrap_or(RtStatus::Error)
+    }
+}
+
+impl fmt::Display for RtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            RtStatus::Ok => "ok",
+            RtStatus::Error => "error",
+            RtStatus::ValueMismatch => "value mismatch",
+            RtStatus::TypeMismatch => "type mismatch",
+            RtStatus::Timeout => "timeout",
+            RtStatus::NotRunning => "not running",
+            RtStatus::AlreadyRunning => "already running",
+            RtStatus::KeyNotFound => "key not found",
+            RtStatus::BufferTooSmall => "buffer too small",
+            RtStatus::NullArgument => "null argument",
+            RtStatus::InvalidArgument => "invalid argument",
+            RtStatus::AccessDenied => "access denied",
+            RtStatus::ConstraintViolation => "constraint violation",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Error of a capability implementation, the status is returned to the caller and the message
+/// logged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtError {
+    pub status: RtStatus,
+    pub message: String,
+}
+
+impl RtError {
+    pub fn new(status: RtStatus, message: impl Into<String>) -> Self {
+        RtError {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn code(&self) -> c_int {
+        self.status.code()
+    }
+
+    /// Stores the message as last error of the calling thread and returns the status code.
+    pub fn record(&self) -> c_int {
+        set_last_error(&self.message);
+        self.code()
+    }
+}
+
+impl fmt::Display for RtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// errors without a more specific status
+impl From<String> for RtError {
+    fn from(message: String) -> Self {
+        RtError::new(RtStatus::Error, message)
+    }
+}
+
+impl From<RtError> for String {
+    fn from(error: RtError) -> Self {
+        error.message
+    }
+}
+
+/// Text of a panic payload, for `panic!` with a message or a format string.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Runs `call` and turns a panic into an error. A panic leaving an `extern "C"` entry of a
+/// plugin would abort the whole process.
+pub fn catch_panic<T>(call: impl FnOnce() -> Result<T, RtError>) -> Result<T, RtError> {
+    std::panic::catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|payload| {
+        Err(RtError::new(
+            RtStatus::Error,
+            format!("Panicked: {}", panic_message(payload.as_ref())),
+        ))
+    })
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Remembers `message` for `get_last_error`. Successful calls keep the previous message.
+pub fn set_last_error(message: &str) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = message.to_string());
+}
+
+pub fn last_error() -> String {
+    LAST_ERROR.with(|last| last.borrow().clone())
+}
+
+/// Implementation of `get_last_error`: returns the size of the last error of the calling thread
+/// including the null terminator. If `buffer` is not null, up to `len` bytes of it are filled
+/// with the message, truncated if needed but always null terminated.
+///
+/// # Safety
+///
+/// `buffer` must be null or valid for writes of `len` bytes.
+pub unsafe fn copy_last_error(buffer: *mut c_char, len: c_int) -> c_int {
+    copy_to_buffer(&last_error(), buffer, len)
+}
+
+/// Copies `text` like `copy_last_error`, e.g. for the status JSON of `health_status`.
+///
+/// # Safety
+///
+/// `buffer` must be null or valid for writes of `len` bytes.
+pub unsafe fn copy_to_buffer(text: &str, buffer: *mut c_char, len: c_int) -> c_int {
+    if !buffer.is_null() && len > 0 {
+        let n = text.len().min(len as usize - 1);
+        std::ptr::copy_nonoverlapping(text.as_ptr(), buffer as *mut u8, n);
+        *buffer.add(n) = 0;
+    }
+    text.len() as c_int + 1
+}
+
+/// Exports `get_last_error`, generated into every plugin by `rt_plugin`.
+#[macro_export]
+macro_rules! export_last_error {
+    () => {
+        /// Copies the message of the last failed call on this thread, see
+        /// `interfaces::status::copy_last_error`.
+        #[no_mangle]
+        #[allow(clippy::not_unsafe_ptr_arg_deref)]
+        pub extern "C" fn get_last_error(
+            buffer: *mut ::std::os::raw::c_char,
+            len: ::std::os::raw::c_int,
+        ) -> ::std::os::raw::c_int {
+            unsafe { $crate::status::copy_last_error(buffer, len) }
+        }
+    };
+}
@@ -0,0 +1,294 @@
+// Starts plugins like the loader does, for the integration tests of the plugin crates, which can
+// not link their own `cdylib`. The blackboard starts first, then the plugins under test with
+// their attributes, each given the capabilities of the blackboard, a clock in real time and the
+// `extra` ones of the test. They are loaded from `plugins/<profile>` of the workspace like the
+// loader finds them.
+use crate::bindings;
+use crate::blackboard::BlackboardEntries;
+use crate::blackboard_client::BlackboardClient;
+use crate::capabilities::{Capabilities, Capability};
+use crate::clock::{
+    CLOCK_NOW_CAPABILITY, CLOCK_NOW_SIGNATURE, CLOCK_SLEEP_UNTIL_CAPABILITY,
+    CLOCK_SLEEP_UNTIL_SIGNATURE,
+};
+use crate::context::OwnedContext;
+use crate::status::{RtError, RtStatus};
+use libloading::Library;
+use serde::Deserialize;
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+#[derive(Deserialize)]
+struct Provided {
+    capability: String,
+    entry: String,
+    signature: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Summary {
+    version: String,
+    provides: Option<Vec<Provided>>,
+    capabilities_abi: Option<u32>,
+}
+
+struct Plugin {
+    name: String,
+    library: Library,
+    summary: Summary,
+    context: OwnedContext, // passed to the entries if the plugin takes one
+    caps: Capabilities,    // handed to `start`, kept until the plugin is stopped
+    attributes: CString,
+}
+
+impl Plugin {
+    fn load(name: &str, attributes: &BlackboardEntries) -> Result<Self, RtError> {
+        let profile = if cfg!(debug_assertions) { "debug" } else { "release" };
+        let file = libloading::library_filename(name);
+        let path = plugin_dir().join(profile).join(file);
+        let library = unsafe { Library::new(&path) }
+            .map_err(|e| format!("Plugin {} can not be loaded: {}", path.display(), e))?;
+        let summary = unsafe {
+            let summary = *library
+                .get::<unsafe extern "C" fn() -> *const c_char>(b"summary")
+                .map_err(|e| e.to_string())?;
+            std::ffi::CStr::from_ptr(summary()).to_string_lossy().into_owned()
+        };
+        let summary = serde_json::from_str(&summary)
+            .map_err(|e| format!("Invalid summary of '{}': {}", name, e))?;
+        // like the loader hands over the attributes of the config
+        let attributes = serde_yml::to_string(attributes).map_err(|e| e.to_string())?;
+        let attributes = CString::new(attributes).map_err(|e| e.to_string())?;
+        Ok(Plugin {
+            name: name.to_string(),
+            library,
+            summary,
+            context: OwnedContext::new(name),
+            caps: Capabilities::new(),
+            attributes,
+        })
+    }
+
+    fn takes_context(&self) -> bool {
+        self.summary.capabilities_abi.unwrap_or(1) >= bindings::CAPABILITIES_ABI_CONTEXT
+    }
+
+    fn provided(&self) -> Result<Capabilities, RtError> {
+        let mut provided = Capabilities::new();
+        for provide in self.summary.provides.iter().flatten() {
+            let function = unsafe { self.library.get::<*mut c_void>(provide.entry.as_bytes()) }
+                .map_err(|e| format!("Capability '{}' is missing: {}", provide.capability, e))?;
+            let signature = provide.signature.as_deref().unwrap_or("");
+            let mut capability =
+                Capability::with_signature(&provide.capability, *function, signature);
+            capability.set_version(provide.version.as_ref().unwrap_or(&self.summary.version));
+            provided.add(capability)?;
+        }
+        Ok(provided)
+    }
+
+    fn start(&mut self, caps: Capabilities) -> Result<(), RtError> {
+        self.caps = caps;
+        let result = unsafe {
+            if self.takes_context() {
+                type Start = unsafe extern "C" fn(
+                    *mut bindings::rt_context,
+                    &bindings::Capabilities,
+                    *const c_char,
+                ) -> c_int;
+                let start = self.library.get::<Start>(b"start").map_err(|e| e.to_string())?;
+                start(self.context.as_ptr(), self.caps.inner(), self.attributes.as_ptr())
+            } else {
+                type Start = unsafe extern "C" fn(&bindings::Capabilities, *const c_char) -> c_int;
+                let start = self.library.get::<Start>(b"start").map_err(|e| e.to_string())?;
+                start(self.caps.inner(), self.attributes.as_ptr())
+            }
+        };
+        match result {
+            0.. => Ok(()),
+            code => Err(RtError::new(
+                RtStatus::from_code(code),
+                format!("Plugin '{}' did not start", self.name),
+            )),
+        }
+    }
+
+    // `i32()`, or `i32(ctx)` if the plugin takes a context, like `stop`
+    fn call(&self, entry: &str) -> Result<c_int, RtError> {
+        unsafe {
+            if self.takes_context() {
+                type Entry = unsafe extern "C" fn(*mut bindings::rt_context) -> c_int;
+                let entry = self.library.get::<Entry>(entry.as_bytes()).map_err(|e| e.to_string())?;
+                Ok(entry(self.context.as_ptr()))
+            } else {
+                type Entry = unsafe extern "C" fn() -> c_int;
+                let entry = self.library.get::<Entry>(entry.as_bytes()).map_err(|e| e.to_string())?;
+                Ok(entry())
+            }
+        }
+    }
+}
+
+// the clock every component gets from the loader, in real time since the first test asked
+static CLOCK_START: OnceLock<Instant> = OnceLock::new();
+
+extern "C" fn clock_now_ns() -> u64 {
+    CLOCK_START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+extern "C" fn clock_sleep_until(time: u64) -> c_int {
+    let start = *CLOCK_START.get_or_init(Instant::now);
+    std::thread::sleep(Duration::from_nanos(time).saturating_sub(start.elapsed()));
+    0
+}
+
+fn clock() -> Vec<Capability> {
+    vec![
+        Capability::with_signature(
+            CLOCK_NOW_CAPABILITY,
+            clock_now_ns as *mut c_void,
+            CLOCK_NOW_SIGNATURE,
+        ),
+        Capability::with_signature(
+            CLOCK_SLEEP_UNTIL_CAPABILITY,
+            clock_sleep_until as *mut c_void,
+            CLOCK_SLEEP_UNTIL_SIGNATURE,
+        ),
+    ]
+}
+
+// the workspace, the tests of a crate run in its directory like the loader finds its plugins
+fn plugin_dir() -> PathBuf {
+    let crate_dir = std::env::current_dir().unwrap_or_default();
+    crate_dir.parent().unwrap_or(&crate_dir).join("plugins")
+}
+
+/// The blackboard and the plugins under test, stopped in reverse order when dropped.
+pub struct Host {
+    plugins: Vec<Plugin>, // the blackboard first
+    blackboard: Capabilities,
+}
+
+impl Host {
+    /// Starts the blackboard and `plugins`, each a library name like `scheduler` with its
+    /// attributes. The blackboard may come first to get attributes too, e.g. initial keys.
+    pub fn start(plugins: &[(&str, BlackboardEntries)]) -> Result<Self, RtError> {
+        Self::start_with(plugins, Vec::new())
+    }
+
+    /// `start` giving the plugins the `extra` capabilities as well, like the `runtime_*` ones of
+    /// the loader.
+    pub fn start_with(
+        plugins: &[(&str, BlackboardEntries)],
+        extra: Vec<Capability>,
+    ) -> Result<Self, RtError> {
+        let (attributes, plugins) = match plugins.split_first() {
+            Some(((name, attributes), plugins)) if *name == "blackboard" => (attributes, plugins),
+            _ => (&Vec::new(), plugins),
+        };
+        let mut blackboard = Plugin::load("blackboard", attributes)?;
+        blackboard.start(Capabilities::new())?;
+        let mut host = Host {
+            blackboard: blackboard.provided()?,
+            plugins: vec![blackboard],
+        };
+        for (name, attributes) in plugins {
+            let mut caps = host.blackboard.clone();
+            for capability in clock().iter().chain(&extra) {
+                caps.add(Capability::from_raw(capability.inner()))?;
+            }
+            // stopped by `drop` as well if it fails to start
+            host.plugins.push(Plugin::load(name, attributes)?);
+            host.plugins.last_mut().unwrap().start(caps)?;
+        }
+        Ok(host)
+    }
+
+    /// The capabilities of the blackboard and those the plugins under test provide, like a
+    /// component requiring all of them gets them.
+    pub fn caps(&self) -> Result<Capabilities, RtError> {
+        let mut caps = self.blackboard.clone();
+        for plugin in &self.plugins[1..] {
+            for capability in plugin.provided()?.iter() {
+                caps.add(capability)?;
+            }
+        }
+        Ok(caps)
+    }
+
+    pub fn client(&self) -> BlackboardClient {
+        BlackboardClient::new(self.blackboard.clone())
+    }
+
+    fn plugin(&self, name: &str) -> Result<&Plugin, RtError> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.name == name)
+            .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("No plugin '{}'", name)))
+    }
+
+    /// Runs the skill `skill` hosted by `plugin` with its entry `run_skill`, returning what it
+    /// returns.
+    pub fn run_skill(&self, plugin: &str, skill: &str) -> Result<c_int, RtError> {
+        let plugin = self.plugin(plugin)?;
+        let skill = CString::new(skill).map_err(|e| e.to_string())?;
+        type RunSkill = unsafe extern "C" fn(*const c_char) -> c_int;
+        unsafe {
+            let run_skill = plugin.library.get::<RunSkill>(b"run_skill");
+            Ok(run_skill.map_err(|e| e.to_string())?(skill.as_ptr()))
+        }
+    }
+
+    /// The json of the entry `health_status` of `plugin`.
+    pub fn health_status(&self, plugin: &str) -> Result<serde_json::Value, RtError> {
+        let plugin = self.plugin(plugin)?;
+        let mut buffer = vec![0u8; 4096];
+        let (data, len) = (buffer.as_mut_ptr() as *mut c_char, buffer.len() as c_int);
+        let missing = |e: libloading::Error| e.to_string();
+        let written = unsafe {
+            if plugin.takes_context() {
+                type HealthStatus =
+                    unsafe extern "C" fn(*mut bindings::rt_context, *mut c_char, c_int) -> c_int;
+                let health_status =
+                    plugin.library.get::<HealthStatus>(b"health_status").map_err(missing)?;
+                health_status(plugin.context.as_ptr(), data, len)
+            } else {
+                type HealthStatus = unsafe extern "C" fn(*mut c_char, c_int) -> c_int;
+                let health_status =
+                    plugin.library.get::<HealthStatus>(b"health_status").map_err(missing)?;
+                health_status(data, len)
+            }
+        };
+        if written <= 0 || written as usize > buffer.len() {
+            return Err(RtError::new(RtStatus::from_code(written), "No health status"));
+        }
+        buffer.truncate(written as usize - 1);
+        serde_json::from_slice(&buffer).map_err(|e| RtError::from(e.to_string()))
+    }
+
+    /// Stops the plugins in reverse order, returning the names of those failing to.
+    pub fn stop(mut self) -> Vec<String> {
+        self.stop_all()
+    }
+
+    fn stop_all(&mut self) -> Vec<String> {
+        let mut failed = Vec::new();
+        while let Some(plugin) = self.plugins.pop() {
+            if !matches!(plugin.call("stop"), Ok(0..)) {
+                failed.push(plugin.name.clone());
+            }
+            // threads of a stopped plugin may still return into it, like with the loader
+            std::mem::forget(plugin.library);
+        }
+        failed
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
@@ -1,5 +1,7 @@
 use libloading::{Library, Symbol};
-use interfaces::capabilities::{Capability, Capabilities, Function};
+use interfaces::bindings;
+use interfaces::capabilities::{Capability, Capabilities, Function, Lease, SharedCapabilities};
+use interfaces::signature::{CType, Signature};
 
 const TARGET_DIR: Option<&'static str> = option_env!("CARGO_TARGET_DIR");
 const TARGET_TMPDIR: Option<&'static str> = option_env!("CARGO_TARGET_TMPDIR");
@@ -56,6 +58,12 @@ struct S {
     d: u8,
 }
 
+impl CType for S {
+    fn c_type() -> String {
+        "S".to_string()
+    }
+}
+
 #[test]
 fn test_create_capabilties() {
     make_helpers();
@@ -73,7 +81,7 @@ fn test_create_capabilties() {
 
         let mut caps = Capabilities::new();
         for cap in capabilities {
-            caps.add(cap);
+            caps.add(cap).unwrap();
         }
 
         assert_eq!(caps.len(), 2);
@@ -97,4 +105,274 @@ fn test_create_capabilties() {
         assert_eq!(caps.inner().n_capabilities, 2);
 
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_remove_capability() {
+    let mut caps = Capabilities::new();
+    for name in ["first", "second", "third"] {
+        caps.add(Capability::new(name, std::ptr::null_mut())).unwrap();
+    }
+
+    assert!(caps.remove("second"));
+    assert!(!caps.remove("second"));
+    assert_eq!(caps.len(), 2);
+    assert!(caps.get("second").is_none());
+
+    let names: Vec<String> = caps.iter().map(|cap| cap.name()).collect();
+    assert_eq!(names, vec!["first", "third"]);
+
+    // the freed slot can be used again
+    caps.add(Capability::new("fourth", std::ptr::null_mut())).unwrap();
+    assert_eq!(caps.len(), 3);
+    assert!(caps.get("fourth").is_some());
+}
+
+#[test]
+fn test_filter_and_merge_capabilities() {
+    let mut caps = Capabilities::new();
+    for name in ["blackboard_get_int", "blackboard_set_int", "blackboard_get_string"] {
+        caps.add(Capability::new(name, std::ptr::null_mut())).unwrap();
+    }
+
+    let getters = caps.filtered("blackboard_get_");
+    let names: Vec<String> = getters.iter().map(|cap| cap.name()).collect();
+    assert_eq!(names, vec!["blackboard_get_int", "blackboard_get_string"]);
+    assert_eq!(getters.inner().n_capabilities, 2);
+    assert_eq!(caps.filtered("scheduler_").len(), 0);
+
+    let mut merged = Capabilities::new();
+    merged.add(Capability::new("log_write", std::ptr::null_mut())).unwrap();
+    merged.merge(&getters).unwrap();
+    assert_eq!(merged.len(), 3);
+
+    // nothing is added if one of them is present already
+    assert!(merged.merge(&caps).is_err());
+    assert_eq!(merged.len(), 3);
+    assert!(merged.get("blackboard_set_int").is_none());
+}
+
+#[test]
+fn test_capability_leases() {
+    let lease = Lease::new();
+    let mut provided = Capabilities::new();
+    provided.add(Capability::new("blackboard_get_int", std::ptr::null_mut())).unwrap();
+    provided.hold(lease.clone());
+    provided.hold(lease.clone());
+    assert_eq!(lease.holders(), 1);
+
+    let mut caps = Capabilities::new();
+    caps.merge(&provided.filtered("blackboard_")).unwrap();
+    let copy = caps.clone();
+    drop(provided);
+    assert_eq!(lease.holders(), 2);
+    // a plugin's copy of the table holds none
+    assert!(Capabilities::from_raw(caps.inner()).leases().is_empty());
+    drop(caps);
+    drop(copy);
+    assert_eq!(lease.holders(), 0);
+}
+
+#[test]
+fn test_shared_capabilities() {
+    let mut caps = Capabilities::new();
+    caps.add(Capability::new("first", std::ptr::null_mut())).unwrap();
+    let shared = SharedCapabilities::new(caps);
+    let before = shared.snapshot();
+
+    shared
+        .update(|caps| caps.add(Capability::new("second", std::ptr::null_mut())))
+        .unwrap();
+    // a failed update keeps the table
+    assert!(shared
+        .update(|caps| caps.add(Capability::new("first", std::ptr::null_mut())))
+        .is_err());
+    assert_eq!(shared.snapshot().len(), 2);
+    // the snapshot taken before is unchanged, its C view still points at its own table
+    assert_eq!(before.len(), 1);
+    assert_eq!(before.inner().n_capabilities, 1);
+    assert!(before.get("second").is_none());
+
+    // readers on other threads see the table replaced meanwhile
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    let snapshot = shared.snapshot();
+                    assert_eq!(snapshot.inner().n_capabilities as usize, snapshot.len());
+                    assert!(snapshot.get("first").is_some());
+                }
+            })
+        })
+        .collect();
+    for i in 0..100 {
+        let mut caps = Capabilities::new();
+        caps.add(Capability::new("first", std::ptr::null_mut())).unwrap();
+        caps.add(Capability::new(&format!("cap_{}", i), std::ptr::null_mut())).unwrap();
+        shared.replace(caps);
+    }
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert!(shared.snapshot().get("cap_99").is_some());
+}
+
+#[test]
+fn test_capabilities_grow() {
+    let n = bindings::CAPABILITY_LEGACY_NUMBER_OF_CAPABILITIES as usize * 2;
+    let mut caps = Capabilities::new();
+    for i in 0..n {
+        caps.add(Capability::new(&format!("cap_{}", i), std::ptr::null_mut())).unwrap();
+    }
+    assert_eq!(caps.len(), n);
+    assert_eq!(caps.inner().n_capabilities as usize, n);
+    assert!(caps.get(&format!("cap_{}", n - 1)).is_some());
+
+    // plugins copy the table they are handed
+    let copy = Capabilities::from_raw(caps.inner());
+    assert_eq!(copy.len(), n);
+    assert_eq!(copy.get("cap_42").unwrap().name(), "cap_42");
+
+    assert!(caps.to_legacy().is_err());
+}
+
+#[test]
+fn test_legacy_capabilities() {
+    let mut caps = Capabilities::new();
+    caps.add(Capability::new("first", std::ptr::null_mut())).unwrap();
+    caps.add(Capability::new("second", std::ptr::null_mut())).unwrap();
+
+    let legacy = caps.to_legacy().unwrap();
+    assert_eq!(legacy.n_capabilities, 2);
+
+    let caps = Capabilities::from_legacy(&legacy);
+    let names: Vec<String> = caps.iter().map(|cap| cap.name()).collect();
+    assert_eq!(names, vec!["first", "second"]);
+}
+
+#[test]
+fn test_add_duplicate_capability() {
+    let mut caps = Capabilities::new();
+    caps.add(Capability::new("blackboard_get_int", std::ptr::null_mut())).unwrap();
+
+    let result = caps.add(Capability::new("blackboard_get_int", std::ptr::null_mut()));
+    assert!(result.is_err());
+    assert_eq!(caps.len(), 1);
+}
+
+#[test]
+fn test_signature() {
+    assert_eq!(<unsafe extern "C" fn(u32) -> u32>::signature(), "u32(u32)");
+    assert_eq!(
+        <extern "C" fn(*const std::ffi::c_char, *mut i32) -> i32>::signature(),
+        "i32(cstr,*mut i32)"
+    );
+    assert_eq!(<extern "C" fn()>::signature(), "void()");
+}
+
+#[test]
+fn test_capability_signature_mismatch() {
+    make_helpers();
+    unsafe {
+        let lib = Library::new(lib_path()).unwrap();
+        let f: Symbol<unsafe extern "C" fn(u32) -> u32> = lib.get(b"test_identity_u32\0").unwrap();
+
+        let cap = Capability::with_signature("test_identity_u32", f.try_as_raw_ptr().unwrap(), "u32( u32 )");
+        assert_eq!(cap.signature(), "u32(u32)");
+
+        let f2: Function<unsafe extern "C" fn(u32) -> u32> = cap.get().unwrap();
+        assert_eq!(42, f2(42));
+
+        let result = cap.get::<unsafe extern "C" fn(u64) -> u64>();
+        assert!(result.is_err());
+    }
+}
+
+// `X_async` of an `X` doubling its argument, completing on another thread
+unsafe extern "C" fn double_async(
+    value: i32,
+    completion: *mut std::ffi::c_void,
+    user_data: *mut std::ffi::c_void,
+) -> i32 {
+    use interfaces::capabilities::Completer;
+    let completer = match Completer::new(completion, user_data) {
+        Ok(completer) => completer,
+        Err(e) => return e.status.code(),
+    };
+    let handle = completer.handle();
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        // a negative value is never completed, dropping the completer reports an error
+        if value >= 0 {
+            completer.complete(value * 2);
+        }
+    });
+    handle
+}
+
+#[test]
+fn test_pending_call() {
+    use interfaces::capabilities::{async_name, PendingCall};
+    use interfaces::status::RtStatus;
+    use std::time::Duration;
+
+    assert_eq!(async_name("double"), "double_async");
+    let call = PendingCall::start(|completion, user_data| unsafe {
+        double_async(21, completion, user_data)
+    })
+    .unwrap();
+    assert!(call.handle() > 0);
+    assert_eq!(call.poll(), None);
+    assert_eq!(call.wait_timeout(Duration::from_millis(1)), None);
+    assert_eq!(call.wait(), 42);
+    assert_eq!(call.poll(), Some(42));
+
+    let dropped = PendingCall::start(|completion, user_data| unsafe {
+        double_async(-1, completion, user_data)
+    })
+    .unwrap();
+    assert_eq!(dropped.wait(), RtStatus::Error.code());
+
+    let rejected = PendingCall::start(|_, user_data| unsafe {
+        double_async(1, std::ptr::null_mut(), user_data)
+    });
+    assert_eq!(rejected.err().map(|e| e.status), Some(RtStatus::NullArgument));
+}
+
+#[test]
+fn test_capability_groups() {
+    use interfaces::capabilities::{group_library, GROUP_SIGNATURE};
+    use std::ffi::{c_char, c_int, c_void, CStr};
+
+    // a group providing `datalogger_log` only
+    extern "C" fn resolve(group: *const c_char, name: *const c_char, cap: *mut c_void) -> c_int {
+        let group = unsafe { CStr::from_ptr(group) }.to_string_lossy();
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+        if group != "datalogger.* >= 0.1" || name != "datalogger_log" {
+            return -2;
+        }
+        let resolved = Capability::with_signature("datalogger_log", resolve as *mut c_void, "");
+        unsafe { *(cap as *mut bindings::Capability) = *resolved.inner() };
+        0
+    }
+
+    assert_eq!(group_library("datalogger.* >= 0.1"), Some("datalogger"));
+    assert_eq!(group_library("datalogger.*"), Some("datalogger"));
+    assert_eq!(group_library("datalogger >= 0.1"), None);
+    assert_eq!(group_library(".*"), None);
+
+    let mut caps = Capabilities::new();
+    caps.add(Capability::new("log_write", std::ptr::null_mut())).unwrap();
+    let group =
+        Capability::with_signature("datalogger.* >= 0.1", resolve as *mut c_void, GROUP_SIGNATURE);
+    caps.add(group).unwrap();
+
+    assert_eq!(caps.get("log_write").unwrap().name(), "log_write");
+    assert_eq!(caps.get("datalogger_log").unwrap().name(), "datalogger_log");
+    assert!(caps.get("datalogger_flush").is_none());
+    // plugins resolve from their copy of the table as well
+    let copy = Capabilities::from_raw(caps.inner());
+    assert!(copy.get("datalogger_log").is_some());
+    assert_eq!(caps.len(), 2);
+}
@@ -0,0 +1,65 @@
+use interfaces::bindings;
+use interfaces::ffi;
+use interfaces::status::RtStatus;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn header_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn test_plugin_header() {
+    let header = std::fs::read_to_string(header_dir().join("rtime_plugin.h")).unwrap();
+    let version = format!("#define RTIME_PLUGIN_ABI_VERSION {}", ffi::RTIME_PLUGIN_ABI_VERSION);
+    assert!(header.contains(&version));
+    assert!(header.contains(&format!("#define RT_ABI_VERSION {}", ffi::RT_ABI_VERSION)));
+    for code in 0..=12 {
+        let status = RtStatus::from_code(-code);
+        let name = format!("RT_{}", status.to_string().to_uppercase().replace(' ', "_"));
+        assert!(header.contains(&format!("{} = {},", name, -code)), "{} is missing", name);
+    }
+}
+
+// a plugin written in C compiles against the header, with the layout of caps.h
+#[test]
+fn test_plugin_header_compiles() {
+    if Command::new("cc").arg("--version").output().is_err() {
+        return;
+    }
+    let source = format!(
+        r#"#include "rtime_plugin.h"
+_Static_assert(sizeof(Capability) == {}, "Capability");
+_Static_assert(sizeof(Capabilities) == {}, "Capabilities");
+_Static_assert(sizeof(rt_context) == {}, "rt_context");
+
+const char *summary(void) {{ return "{{\"name\": \"c_skill\", \"version\": \"0.1.0\"}}"; }}
+
+int abi_version(void) {{ return RT_ABI_VERSION; }}
+
+int run(const Capabilities *caps, const char *attributes) {{
+    (void)attributes;
+    return caps->n_capabilities > 0 ? RT_OK : RT_NOT_RUNNING;
+}}
+
+static rt_summary_fn summary_entry = summary;
+static rt_abi_version_fn abi_version_entry = abi_version;
+static rt_run_fn run_entry = run;
+"#,
+        std::mem::size_of::<bindings::Capability>(),
+        std::mem::size_of::<bindings::Capabilities>(),
+        std::mem::size_of::<bindings::rt_context>(),
+    );
+    let dir = std::env::temp_dir().join(format!("rtime-header-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("plugin.c");
+    std::fs::write(&file, source).unwrap();
+    let output = Command::new("cc")
+        .args(["-std=c11", "-Wall", "-Werror", "-fsyntax-only", "-I"])
+        .arg(header_dir())
+        .arg(&file)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,86 @@
+use interfaces::capabilities::{Capabilities, Capability};
+use interfaces::intercept::{intercept, supported, Call, Interceptor};
+use interfaces::status::RtStatus;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+extern "C" fn length(text: *const c_char, length: *mut c_int) -> c_int {
+    unsafe { *length = CStr::from_ptr(text).to_bytes().len() as c_int };
+    0
+}
+
+type Length = unsafe extern "C" fn(*const c_char, *mut c_int) -> c_int;
+
+fn call(cap: &Capability, text: &CStr) -> (c_int, c_int) {
+    let mut n = 0;
+    let result = unsafe { cap.get::<Length>().unwrap()(text.as_ptr(), &mut n) };
+    (result, n)
+}
+
+// refuses texts starting with `secret`, records what it let through
+#[derive(Default)]
+struct Guard {
+    seen: Mutex<Vec<String>>,
+    results: AtomicUsize,
+}
+
+impl Interceptor for Guard {
+    fn before(&self, call: &Call) -> Result<(), c_int> {
+        let text = call.args[0].as_str().unwrap_or_default();
+        if text.starts_with("secret") {
+            return Err(RtStatus::AccessDenied.code());
+        }
+        self.seen.lock().unwrap().push(format!("{} {}", call.capability, text));
+        Ok(())
+    }
+
+    fn after(&self, _call: &Call, result: c_int, _elapsed: Duration) {
+        if result == 0 {
+            self.results.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[test]
+fn test_intercept() {
+    let mut cap = Capability::with_signature("length", length as *mut c_void, "i32(cstr,*mut i32)");
+    cap.set_version("1.2.0");
+    let guard = Arc::new(Guard::default());
+    let intercepted = intercept("test_intercept", &cap, guard.clone()).unwrap();
+    assert_eq!(intercepted.name(), "length");
+    assert_eq!(intercepted.signature(), cap.signature());
+    assert_eq!(intercepted.version(), "1.2.0");
+    assert_ne!(intercepted.inner().function, cap.inner().function);
+
+    assert_eq!(call(&intercepted, c"rtime"), (0, 5));
+    assert_eq!(call(&intercepted, c"secret key"), (RtStatus::AccessDenied.code(), 0));
+    assert_eq!(*guard.seen.lock().unwrap(), vec!["length rtime"]);
+    assert_eq!(guard.results.load(Ordering::SeqCst), 1);
+
+    // the owner keeps its shim, with the new interceptor
+    let other = Arc::new(Guard::default());
+    let again = intercept("test_intercept", &cap, other.clone()).unwrap();
+    assert_eq!(again.inner().function, intercepted.inner().function);
+    assert_eq!(call(&intercepted, c"kiss"), (0, 4));
+    assert_eq!(other.results.load(Ordering::SeqCst), 1);
+    assert_eq!(guard.results.load(Ordering::SeqCst), 1);
+
+    // stacked interceptors need their own owner
+    assert!(intercept("test_intercept", &again, guard.clone()).is_err());
+    let stacked = intercept("test_intercept/outer", &again, guard.clone()).unwrap();
+    assert_eq!(call(&stacked, c"secret"), (RtStatus::AccessDenied.code(), 0));
+    assert_eq!(call(&stacked, c"kiss"), (0, 4));
+    assert_eq!(other.results.load(Ordering::SeqCst), 2);
+
+    // callbacks can not be intercepted
+    let signature = "i32(cstr,cstr,*mut void,*mut void)";
+    assert!(!supported(signature));
+    let subscribe = Capability::with_signature("subscribe", length as *mut c_void, signature);
+    assert!(intercept("test_intercept", &subscribe, guard).is_err());
+
+    let mut caps = Capabilities::new();
+    caps.add(stacked).unwrap();
+    assert_eq!(call(&caps.get("length").unwrap(), c"abc"), (0, 3));
+}
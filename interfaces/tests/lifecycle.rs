@@ -0,0 +1,31 @@
+use interfaces::lifecycle::{Lifecycle, PluginState};
+use interfaces::status::{RtError, RtStatus};
+
+#[test]
+fn test_lifecycle() {
+    let lifecycle = Lifecycle::new();
+    assert_eq!(lifecycle.state(), PluginState::Created);
+    assert!(lifecycle.change(PluginState::Stopped).is_err());
+
+    assert!(lifecycle.started(Ok(())).is_ok());
+    assert_eq!(lifecycle.code(), PluginState::Started.code());
+    // starting again keeps the started plugin running
+    let running = RtError::new(RtStatus::AlreadyRunning, "running");
+    assert!(lifecycle.started::<()>(Err(running)).is_err());
+    assert_eq!(lifecycle.state(), PluginState::Started);
+
+    assert!(lifecycle.stopped(Ok(())).is_ok());
+    assert_eq!(lifecycle.state(), PluginState::Stopped);
+    let stopped = RtError::new(RtStatus::NotRunning, "stopped");
+    assert!(lifecycle.stopped::<()>(Err(stopped)).is_err());
+    assert_eq!(lifecycle.state(), PluginState::Stopped);
+
+    assert!(lifecycle.started::<()>(Err("no port".to_string().into())).is_err());
+    assert_eq!(lifecycle.state(), PluginState::Failed);
+    assert!(lifecycle.started(Ok(())).is_ok());
+    assert_eq!(lifecycle.state(), PluginState::Started);
+
+    assert_eq!(PluginState::from_code(3), Some(PluginState::Failed));
+    assert_eq!(PluginState::from_code(7), None);
+    assert_eq!(PluginState::Created.to_string(), "created");
+}
@@ -0,0 +1,38 @@
+#![cfg(all(unix, feature = "shm"))]
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::shared_memory::SharedBlackboard;
+
+#[test]
+fn test_shared_memory() {
+    let name = format!("/rtime-test-{}", std::process::id());
+    let writer = SharedBlackboard::create(&name, 4).unwrap();
+    let reader = SharedBlackboard::open(&name).unwrap();
+    assert_eq!(reader.slots(), 4);
+    assert!(reader.read("speed").is_none());
+
+    assert!(writer.write("speed", &TypedBlackboardValue::Double(2.5)));
+    assert_eq!(reader.read("speed"), Some((TypedBlackboardValue::Double(2.5), 1)));
+    assert!(writer.write("speed", &TypedBlackboardValue::Int(-3)));
+    assert_eq!(reader.read("speed"), Some((TypedBlackboardValue::Int(-3), 2)));
+    // readers can not write
+    assert!(!reader.write("speed", &TypedBlackboardValue::Int(1)));
+
+    // values other than numbers clear the key
+    assert!(writer.write("mode", &TypedBlackboardValue::Bool(true)));
+    assert!(writer.write("mode", &TypedBlackboardValue::String("auto".to_string())));
+    assert!(reader.read("mode").is_none());
+    writer.remove("speed");
+    assert!(reader.read("speed").is_none());
+
+    // keys keep their slot, so two more fit
+    assert!(!writer.write(&"k".repeat(40), &TypedBlackboardValue::Int(1)));
+    assert!(writer.write("a", &TypedBlackboardValue::Int64(1 << 40)));
+    assert!(writer.write("b", &TypedBlackboardValue::Timestamp(7)));
+    assert!(!writer.write("c", &TypedBlackboardValue::Int(1)));
+    assert_eq!(reader.read("a").unwrap().0, TypedBlackboardValue::Int64(1 << 40));
+    writer.clear();
+    assert!(reader.read("b").is_none());
+
+    drop(writer);
+    assert!(SharedBlackboard::open(&name).is_err());
+}
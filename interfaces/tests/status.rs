@@ -0,0 +1,27 @@
+use interfaces::bindings;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+
+#[test]
+fn test_status_codes() {
+    assert_eq!(RtStatus::Ok.code(), 0);
+    assert_eq!(RtStatus::KeyNotFound.code(), bindings::rt_status_RT_KEY_NOT_FOUND);
+
+    assert_eq!(RtStatus::from_code(12), RtStatus::Ok);
+    assert_eq!(RtStatus::from_code(-3), RtStatus::TypeMismatch);
+    assert_eq!(RtStatus::from_code(-1000), RtStatus::Error);
+}
+
+#[test]
+fn test_error_from_string() {
+    let error: RtError = "something failed".to_string().into();
+    assert_eq!(error.code(), RtStatus::Error.code());
+    assert_eq!(error.to_string(), "something failed");
+}
+
+#[test]
+fn test_catch_panic() {
+    assert_eq!(catch_panic(|| Ok(3)), Ok(3));
+    let error = catch_panic::<()>(|| panic!("start failed: {}", 42)).unwrap_err();
+    assert_eq!(error.status, RtStatus::Error);
+    assert_eq!(error.message, "Panicked: start failed: 42");
+}
@@ -0,0 +1,197 @@
+// Usage of the capabilities by the components. With `trace_capabilities: true` in the config
+// every capability handed to a component is intercepted, see `interfaces::intercept`, counting
+// the calls and their durations per component and capability. The matrix is logged at shutdown
+// and returned by the `trace` command of the control socket.
+//
+// Only capabilities of a signature `interfaces::intercept` supports are traced, the others are
+// handed over as they are.
+use interfaces::capabilities::Capabilities;
+use interfaces::intercept::{self, Call, Interceptor};
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::ffi::c_int;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// what the calls of a capability by a component recorded
+#[derive(Default)]
+struct Traced {
+    calls: AtomicU64,
+    nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl Interceptor for Traced {
+    fn after(&self, _call: &Call, _result: c_int, elapsed: Duration) {
+        let nanos = elapsed.as_nanos() as u64;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+}
+
+// (component, capability) -> usage, kept when the component gets its capabilities again
+static TRACED: Mutex<BTreeMap<(String, String), Arc<Traced>>> = Mutex::new(BTreeMap::new());
+
+/// Traces the capabilities handed to components from now on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// The capabilities of component `caller`, traced if tracing is enabled.
+pub fn traced(caller: &str, caps: Capabilities) -> Capabilities {
+    if enabled() {
+        trace(caller, &caps)
+    } else {
+        caps
+    }
+}
+
+// `caps` with the capabilities of a supported signature intercepted
+fn trace(caller: &str, caps: &Capabilities) -> Capabilities {
+    let owner = format!("trace/{}", caller);
+    let mut traced = Capabilities::new();
+    for cap in caps.iter() {
+        let key = (caller.to_string(), cap.name());
+        if !intercept::supported(&cap.signature()) {
+            debug!(
+                component = caller, capability = key.1.as_str();
+                "Capability '{}' of '{}' is not traced", key.1, caller
+            );
+            let _ = traced.add(cap);
+            continue;
+        }
+        let usage = TRACED.lock().unwrap().get(&key).cloned().unwrap_or_default();
+        match intercept::intercept(&owner, &cap, usage.clone()) {
+            Ok(shim) => {
+                TRACED.lock().unwrap().insert(key, usage);
+                let _ = traced.add(shim);
+            }
+            Err(e) => {
+                warn!(
+                    component = caller, capability = key.1.as_str();
+                    "Capability '{}' of '{}' is not traced: {}", key.1, caller, e
+                );
+                let _ = traced.add(cap);
+            }
+        }
+    }
+    traced.hold_leases(caps);
+    traced
+}
+
+/// Calls of a capability by a component.
+#[derive(Debug)]
+pub struct Usage {
+    pub caller: String,
+    pub capability: String,
+    pub calls: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+/// The usage of every traced capability, ordered by component and capability.
+pub fn usage() -> Vec<Usage> {
+    TRACED
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((caller, capability), traced)| Usage {
+            caller: caller.clone(),
+            capability: capability.clone(),
+            calls: traced.calls.load(Ordering::Relaxed),
+            total: Duration::from_nanos(traced.nanos.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(traced.max_nanos.load(Ordering::Relaxed)),
+        })
+        .collect()
+}
+
+/// The usage as one line per called capability, e.g.
+/// `webinterface blackboard_get_int: 12 calls, mean 3.1µs, max 9.8µs, total 37.2µs`.
+pub fn report() -> String {
+    let lines: Vec<String> = usage()
+        .iter()
+        .filter(|usage| usage.calls > 0)
+        .map(|usage| {
+            format!(
+                "{} {}: {} calls, mean {:?}, max {:?}, total {:?}",
+                usage.caller,
+                usage.capability,
+                usage.calls,
+                usage.total / usage.calls as u32,
+                usage.max,
+                usage.total
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interfaces::capabilities::Capability;
+    use std::ffi::{c_char, c_void, CStr};
+
+    extern "C" fn length(text: *const c_char, length: *mut c_int) -> c_int {
+        unsafe { *length = CStr::from_ptr(text).to_bytes().len() as c_int };
+        0
+    }
+
+    #[test_log::test]
+    fn test_trace() {
+        let mut caps = Capabilities::new();
+        let function = length as *mut c_void;
+        caps.add(Capability::with_signature(
+            "length",
+            function,
+            "i32(cstr,*mut i32)",
+        ))
+        .unwrap();
+        caps.add(Capability::with_signature(
+            "subscribe",
+            function,
+            "i32(cstr,cstr,*mut void,*mut void)",
+        ))
+        .unwrap();
+
+        type Length = unsafe extern "C" fn(*const c_char, *mut c_int) -> c_int;
+        let call = |caps: &Capabilities, text: &CStr| unsafe {
+            let length = caps.get("length").unwrap().get::<Length>().unwrap();
+            let mut n = 0;
+            assert_eq!(length(text.as_ptr(), &mut n), 0);
+            n
+        };
+        let traced = trace("audit_test", &caps);
+        // callbacks are handed over untraced
+        assert_eq!(traced.len(), 2);
+        assert_eq!(traced.get("subscribe").unwrap().inner().function, function);
+        assert_ne!(traced.get("length").unwrap().inner().function, function);
+        assert_eq!(call(&traced, c"rtime"), 5);
+
+        // the pair keeps its shim when the capabilities are created again
+        let again = trace("audit_test", &caps);
+        assert_eq!(
+            again.get("length").unwrap().inner().function,
+            traced.get("length").unwrap().inner().function
+        );
+        assert_eq!(call(&again, c"kiss"), 4);
+
+        let usage: Vec<Usage> = usage()
+            .into_iter()
+            .filter(|usage| usage.caller == "audit_test")
+            .collect();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].capability, "length");
+        assert_eq!(usage[0].calls, 2);
+        assert!(usage[0].max <= usage[0].total);
+        assert!(report().contains("audit_test length: 2 calls"));
+    }
+}
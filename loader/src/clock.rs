@@ -0,0 +1,231 @@
+// Clock of the loader, its time is the time since the loader started. With `run --sim-time` it is
+// simulated: it stands still until `advance` moves it, e.g. by `clock advance <ms>` of the
+// control socket. The loops of the loader tick with `Interval` on it, and the components read
+// and sleep on it with `clock_now_ns` and `clock_sleep_until`, see `interfaces::clock`.
+use interfaces::capabilities::Capability;
+use interfaces::clock::{
+    CLOCK_NOW_CAPABILITY, CLOCK_NOW_SIGNATURE, CLOCK_SLEEP_UNTIL_CAPABILITY,
+    CLOCK_SLEEP_UNTIL_SIGNATURE,
+};
+use log::info;
+use std::ffi::{c_int, c_void};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+static CLOCK: OnceLock<Clock> = OnceLock::new();
+
+/// Simulates the clock of the loader. Fails once the clock was read, the time may not jump back.
+pub fn simulate() -> Result<(), String> {
+    CLOCK
+        .set(Clock::simulated())
+        .map_err(|_| "The clock is already running in real time".to_string())?;
+    info!("Running on simulated time, advance it with 'clock advance <ms>'");
+    Ok(())
+}
+
+/// The clock of the loader, running in real time unless `simulate` was called first.
+pub fn clock() -> &'static Clock {
+    CLOCK.get_or_init(Clock::real)
+}
+
+/// `clock_now_ns` and `clock_sleep_until` of every component, see `interfaces::clock`.
+pub fn capabilities() -> Vec<Capability> {
+    vec![
+        Capability::with_signature(
+            CLOCK_NOW_CAPABILITY,
+            clock_now_ns as *mut c_void,
+            CLOCK_NOW_SIGNATURE,
+        ),
+        Capability::with_signature(
+            CLOCK_SLEEP_UNTIL_CAPABILITY,
+            clock_sleep_until as *mut c_void,
+            CLOCK_SLEEP_UNTIL_SIGNATURE,
+        ),
+    ]
+}
+
+extern "C" fn clock_now_ns() -> u64 {
+    clock().now().as_nanos() as u64
+}
+
+extern "C" fn clock_sleep_until(time: u64) -> c_int {
+    clock().sleep_until(Duration::from_nanos(time));
+    0
+}
+
+// the time of a simulated clock, waited on by threads and by tasks
+struct Simulated {
+    now: Mutex<Duration>,
+    advanced: Condvar,
+    watch: watch::Sender<Duration>,
+}
+
+pub struct Clock {
+    start: Instant,
+    simulated: Option<Simulated>,
+}
+
+impl Clock {
+    pub fn real() -> Self {
+        Clock {
+            start: Instant::now(),
+            simulated: None,
+        }
+    }
+
+    pub fn simulated() -> Self {
+        Clock {
+            start: Instant::now(),
+            simulated: Some(Simulated {
+                now: Mutex::new(Duration::ZERO),
+                advanced: Condvar::new(),
+                watch: watch::Sender::new(Duration::ZERO),
+            }),
+        }
+    }
+
+    /// Time since the clock started.
+    pub fn now(&self) -> Duration {
+        match &self.simulated {
+            Some(simulated) => *simulated.now.lock().unwrap(),
+            None => self.start.elapsed(),
+        }
+    }
+
+    /// `now` as an instant, for the deadlines of the loader like the restart backoff.
+    pub fn instant(&self) -> Instant {
+        self.start + self.now()
+    }
+
+    /// Blocks until the clock reaches `time`.
+    pub fn sleep_until(&self, time: Duration) {
+        match &self.simulated {
+            Some(simulated) => {
+                let now = simulated.now.lock().unwrap();
+                drop(simulated.advanced.wait_while(now, |now| *now < time).unwrap());
+            }
+            None => std::thread::sleep(time.saturating_sub(self.start.elapsed())),
+        }
+    }
+
+    /// `sleep_until`, returning after `max` of real time at the latest.
+    pub fn sleep_until_within(&self, time: Duration, max: Duration) {
+        match &self.simulated {
+            Some(simulated) => {
+                let now = simulated.now.lock().unwrap();
+                drop(simulated.advanced.wait_timeout_while(now, max, |now| *now < time).unwrap());
+            }
+            None => std::thread::sleep(time.saturating_sub(self.start.elapsed()).min(max)),
+        }
+    }
+
+    /// Moves a simulated clock forward and wakes everything waiting up to the new time. Returns
+    /// the new time, fails for a clock running in real time.
+    pub fn advance(&self, by: Duration) -> Result<Duration, String> {
+        let simulated = self
+            .simulated
+            .as_ref()
+            .ok_or("The clock runs in real time, start the loader with --sim-time")?;
+        let mut now = simulated.now.lock().unwrap();
+        *now += by;
+        simulated.advanced.notify_all();
+        simulated.watch.send_replace(*now);
+        Ok(*now)
+    }
+
+    /// Ticks every `period` of the clock, the first tick is immediate like `tokio::time::interval`.
+    pub fn interval(&'static self, period: Duration) -> Interval {
+        let ticker = match &self.simulated {
+            Some(simulated) => Ticker::Simulated(simulated.watch.subscribe()),
+            None => Ticker::Real(tokio::time::interval(period)),
+        };
+        Interval {
+            ticker,
+            period,
+            next: self.now(),
+        }
+    }
+}
+
+enum Ticker {
+    Real(tokio::time::Interval),
+    Simulated(watch::Receiver<Duration>),
+}
+
+pub struct Interval {
+    ticker: Ticker,
+    period: Duration,
+    next: Duration, // the time of the next tick of a simulated clock
+}
+
+impl Interval {
+    /// Waits for the next tick. After a large step of a simulated clock the missed ticks follow
+    /// each other immediately, one per period, so the loops run as often as in real time.
+    pub async fn tick(&mut self) {
+        match &mut self.ticker {
+            Ticker::Real(interval) => {
+                interval.tick().await;
+            }
+            Ticker::Simulated(receiver) => {
+                let next = self.next;
+                // the clock lives as long as the loader, its sender is never dropped
+                let _ = receiver.wait_for(|now| *now >= next).await;
+                self.next += self.period;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_simulated() {
+        let clock = Arc::new(Clock::simulated());
+        assert_eq!(clock.now(), Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(clock.now(), Duration::ZERO);
+
+        let sleeper = {
+            let clock = clock.clone();
+            std::thread::spawn(move || {
+                clock.sleep_until(Duration::from_secs(2));
+                clock.now()
+            })
+        };
+        assert_eq!(clock.advance(Duration::from_secs(1)), Ok(Duration::from_secs(1)));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!sleeper.is_finished());
+        clock.advance(Duration::from_millis(1500)).unwrap();
+        assert_eq!(sleeper.join().unwrap(), Duration::from_millis(2500));
+        assert_eq!(clock.instant() - clock.start, Duration::from_millis(2500));
+
+        assert!(Clock::real().advance(Duration::from_secs(1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_interval() {
+        let clock: &'static Clock = Box::leak(Box::new(Clock::simulated()));
+        let mut interval = clock.interval(Duration::from_millis(100));
+        interval.tick().await;
+
+        let ticks = Arc::new(Mutex::new(0));
+        let counter = ticks.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                *counter.lock().unwrap() += 1;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*ticks.lock().unwrap(), 0);
+
+        clock.advance(Duration::from_millis(350)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*ticks.lock().unwrap(), 3);
+        task.abort();
+    }
+}
@@ -1,8 +1,21 @@
+use super::helper::{copy_for_reload, guarded, load_library};
+use super::progress::{Progress, Stage};
 use super::rtlibrary;
 use libloading::Symbol;
 use log::{error, info, trace, warn};
+use super::config::{Isolation, RestartPolicy};
 use rtlibrary::{RTLibrary, RTLibraryType};
-use std::ffi::{c_char, c_int, c_void};
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::capabilities::{group_library, Cancel, Lease, PendingCall, GROUP_SUFFIX};
+use interfaces::lifecycle::PluginState;
+use interfaces::status::RtStatus;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use interfaces::bindings::rt_context;
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub trait Component {
     fn run(
@@ -10,34 +23,120 @@ pub trait Component {
         function: &str,
         caps: &interfaces::capabilities::Capabilities,
     ) -> Result<i32, String> {
+        guarded(self.library().name(), function, self.entry_call(function, caps)?)
+    }
+
+    /// Call of the entry `function` with `caps` and the attributes. It owns its arguments, so it
+    /// may run on another thread and outlive the caller.
+    fn entry_call(
+        &self,
+        function: &str,
+        caps: &interfaces::capabilities::Capabilities,
+    ) -> Result<EntryCall, String> {
+        self.entry_call_with(function, caps, self.attributes())
+    }
+
+    /// `entry_call` with other `attributes` than the configured ones.
+    fn entry_call_with(
+        &self,
+        function: &str,
+        caps: &interfaces::capabilities::Capabilities,
+        attributes: &str,
+    ) -> Result<EntryCall, String> {
         let library = &self.library().library;
-        let attr = self.attributes();
-        let result = unsafe {
-            library.get(function.as_bytes()).map(
-                |f: Symbol<
-                    unsafe extern "C" fn(
+        // plugins read the attributes as a null terminated string
+        let attr = CString::new(attributes).map_err(|e| e.to_string())?;
+        let missing =
+            |e: libloading::Error| format!("Function '{}' can not be called. Reason: {}", function, e);
+        unsafe {
+            if self.library().takes_context() {
+                let caps = interfaces::capabilities::Capabilities::from_raw(caps.inner());
+                let context = self.library().context.clone();
+                let f = *library
+                    .get::<unsafe extern "C" fn(
+                        *mut rt_context,
                         &interfaces::bindings::Capabilities,
                         *const c_char,
-                    ) -> c_int,
-                >| { f(caps.inner(), attr.as_ptr() as *const c_char) },
-            )
+                    ) -> c_int>(function.as_bytes())
+                    .map_err(missing)?;
+                Ok(Box::new(move || f(context.as_ptr(), caps.inner(), attr.as_ptr())))
+            } else if self.library().is_legacy() {
+                let legacy_caps = caps.to_legacy()?;
+                let f = *library
+                    .get::<unsafe extern "C" fn(
+                        &interfaces::bindings::LegacyCapabilities,
+                        *const c_char,
+                    ) -> c_int>(function.as_bytes())
+                    .map_err(missing)?;
+                Ok(Box::new(move || f(&legacy_caps, attr.as_ptr())))
+            } else {
+                let caps = interfaces::capabilities::Capabilities::from_raw(caps.inner());
+                let f = *library
+                    .get::<unsafe extern "C" fn(
+                        &interfaces::bindings::Capabilities,
+                        *const c_char,
+                    ) -> c_int>(function.as_bytes())
+                    .map_err(missing)?;
+                Ok(Box::new(move || f(caps.inner(), attr.as_ptr())))
+            }
+        }
+    }
+    /// Hands new `attributes` to the optional entry `reconfigure`, which applies them without a
+    /// restart. Fails with the status and last error of the library if it returns an error.
+    fn reconfigure(&self, attributes: &str) -> Result<(), String> {
+        let library = self.library();
+        let attr = CString::new(attributes).map_err(|e| e.to_string())?;
+        let missing = |_| "it has no entry 'reconfigure'".to_string();
+        let result = if library.takes_context() {
+            let reconfigure = unsafe {
+                *library
+                    .library
+                    .get::<unsafe extern "C" fn(*mut rt_context, *const c_char) -> c_int>(
+                        b"reconfigure",
+                    )
+                    .map_err(missing)?
+            };
+            guarded(library.name(), "reconfigure", || unsafe {
+                reconfigure(library.context.as_ptr(), attr.as_ptr())
+            })?
+        } else {
+            let reconfigure = unsafe {
+                *library
+                    .library
+                    .get::<unsafe extern "C" fn(*const c_char) -> c_int>(b"reconfigure")
+                    .map_err(missing)?
+            };
+            guarded(library.name(), "reconfigure", || unsafe { reconfigure(attr.as_ptr()) })?
         };
-        match result {
-            Ok(r) => Ok(r),
-            Err(e) => Err(format!(
-                "Function '{}' can not be called. Reason: {}",
-                function, e
-            )),
+        if result < 0 {
+            return Err(format!(
+                "reconfigure returned {} ({}): {}",
+                result,
+                RtStatus::from_code(result),
+                library
+                    .last_error()
+                    .unwrap_or_else(|| "no error message".to_string())
+            ));
         }
+        Ok(())
     }
+
     fn attributes(&self) -> &str;
     fn library(&self) -> &RTLibrary;
     fn requires(&self) -> &Vec<String>;
 }
 
+/// Prepared call of a plugin entry, see `Component::entry_call`.
+pub type EntryCall = Box<dyn FnOnce() -> c_int + Send>;
+
+// `run_async` and `run_cancel` of a skill with its context, if it takes one
+type RunAsyncCall<'a> = Box<dyn Fn(*mut c_void, *mut c_void) -> c_int + 'a>;
+type CancelCall = Box<dyn Fn(c_int) -> c_int>;
+
+#[allow(clippy::large_enum_variant)] // a few components, kept in place
 pub enum ComponentsType {
     Service(Service),
-    Skill(Skill),
+    Skill(Arc<Skill>), // shared with its runs, see `SkillRun`
 }
 
 pub type ComponentsVec = Vec<ComponentsType>;
@@ -50,6 +149,50 @@ pub struct Skill {
 pub struct Service {
     pub library: RTLibrary,
     pub requires: Vec<String>,
+    running: AtomicBool,
+    busy: Arc<AtomicBool>, // a call of `start` or `stop` has not returned yet
+    panicked: Arc<AtomicBool>, // a call into the plugin panicked since its last start
+    supervision: Mutex<Supervision>,
+    progress: Mutex<Progress>,
+    leases: Mutex<Vec<Lease>>, // of the capabilities it got in `start`, kept until it stops
+}
+
+/// Health of a service as published by the supervisor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Health {
+    Running,
+    Stopped,    // ended on its own or was never started
+    Failed,     // crashed or failed to start
+    Restarting, // waiting for the backoff to pass
+}
+
+impl std::fmt::Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Health::Running => "running",
+            Health::Stopped => "stopped",
+            Health::Failed => "failed",
+            Health::Restarting => "restarting",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// How a requirement of a component is resolved, see `Components::resolve`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Resolution {
+    pub component: String,
+    pub require: String,
+    pub provider: Option<String>, // name and version of the providing library
+    pub capabilities: Vec<String>,
+    pub error: Option<String>, // why the component can not be started with it
+}
+
+// state of the supervisor for one service
+struct Supervision {
+    health: Health,
+    restarts: u32,
+    retry_at: Option<Instant>,
 }
 
 impl Component for Skill {
@@ -88,35 +231,1023 @@ impl Component for Service {
     }
 }
 
+// state of a service while ordering the startup
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
 impl Components {
     pub fn new(mut libraries: Vec<RTLibrary>) -> Self {
         let mut inner: ComponentsVec = Vec::new();
         while let Some(lib) = libraries.pop() {
-            let library_type = lib.summary.library_type.clone();
+            inner.push(ComponentsType::new(lib).unwrap());
+        }
+        Self {
+            inner,
+            started: Mutex::new(Vec::new()),
+        }
+    }
 
-            let component: ComponentsType = match library_type {
-                RTLibraryType::Service => ComponentsType::Service(Service::new(lib).unwrap()),
-                RTLibraryType::Skill => ComponentsType::Skill(Skill::new(lib).unwrap()),
+    /// Starts the services so that each one starts after the services it requires, services
+    /// of the same level in parallel. A service with a restart policy is started again after a
+    /// failed start until it runs or has used up its restarts.
+    pub fn start_services(&self) -> Result<(), String> {
+        for level in self.start_levels()? {
+            for index in &level {
+                if let ComponentsType::Service(service) = &self.inner[*index] {
+                    service.enter(Stage::Starting, None);
+                }
+            }
+            self.publish_progress();
+            let results: Vec<Result<(), String>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = level
+                    .iter()
+                    .map(|index| scope.spawn(move || self.start_at(*index)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|_| Err("Starting a service panicked".to_string()))
+                    })
+                    .collect()
+            });
+            results.into_iter().collect::<Result<Vec<()>, String>>()?;
+        }
+        Ok(())
+    }
+
+    fn start_at(&self, index: usize) -> Result<(), String> {
+        let ComponentsType::Service(service) = &self.inner[index] else {
+            return Ok(());
+        };
+        let result = self.start_service(service);
+        self.publish_progress();
+        result.map_err(|e| {
+            format!(
+                "Service '{}' can not be started. Reason: {}",
+                service.library.summary.name, e
+            )
+        })?;
+        self.started.lock().unwrap().push(index);
+        Ok(())
+    }
+
+    /// Services grouped by start level: level 0 requires no other service, every later level
+    /// only services of the levels before. Services of one level can start in parallel.
+    pub fn start_levels(&self) -> Result<Vec<Vec<usize>>, String> {
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        let mut level_of = vec![0; self.inner.len()];
+        for index in self.start_order()? {
+            let level = self
+                .dependencies(index)?
+                .iter()
+                .map(|dependency| level_of[*dependency] + 1)
+                .max()
+                .unwrap_or(0);
+            level_of[index] = level;
+            if levels.len() <= level {
+                levels.resize_with(level + 1, Vec::new);
+            }
+            levels[level].push(index);
+        }
+        Ok(levels)
+    }
+
+    // indices of the loaded services the component at `index` requires
+    fn dependencies(&self, index: usize) -> Result<Vec<usize>, String> {
+        let mut dependencies = Vec::new();
+        for require in self.inner[index].requires() {
+            let (name, _) = parse_requirement(require)?;
+            dependencies.extend(self.service_index(name));
+        }
+        Ok(dependencies)
+    }
+
+    /// Fails unless every loaded service `component` requires is started, as reported by its
+    /// `state` entry. Groups like `datalogger.*` are not required to run.
+    pub fn require_started(&self, component: &dyn Component) -> Result<(), String> {
+        for require in component.requires() {
+            let (name, _) = parse_requirement(require)?;
+            // a group works without its library
+            if group_library(require).is_some() {
+                continue;
+            }
+            let Some(ComponentsType::Service(service)) =
+                self.service_index(name).map(|index| &self.inner[index])
+            else {
+                continue;
             };
+            let state = service.state();
+            if state != PluginState::Started {
+                return Err(format!("'{}' is {}", name, state));
+            }
+        }
+        Ok(())
+    }
 
-            inner.push(component);
+    /// Indices of the component at `index` and of all services that require it, directly or
+    /// through other services. These services hold capabilities of the component.
+    pub fn dependents(&self, index: usize) -> Vec<usize> {
+        let mut dependents = vec![index];
+        let mut next = 0;
+        while next < dependents.len() {
+            let name = self.inner[dependents[next]].name();
+            for (other, component) in self.inner.iter().enumerate() {
+                let ComponentsType::Service(service) = component else {
+                    continue;
+                };
+                let requires = service.requires().iter().any(|require| {
+                    parse_requirement(require).is_ok_and(|(required, _)| required == name)
+                });
+                if requires && !dependents.contains(&other) {
+                    dependents.push(other);
+                }
+            }
+            next += 1;
         }
-        Self { inner }
+        dependents
+    }
+
+    /// Names of the services requiring the service `name`, directly or through other services.
+    /// These are stopped and started again with it by `restart_named` and the supervisor.
+    pub fn dependents_named(&self, name: &str) -> Result<Vec<String>, String> {
+        let index = self
+            .service_index(name)
+            .ok_or_else(|| format!("Service '{}' is not loaded", name))?;
+        Ok(self
+            .dependents(index)
+            .into_iter()
+            .skip(1)
+            .map(|dependent| self.inner[dependent].name().to_string())
+            .collect())
     }
 
-    pub fn start_services(&self) {
-        for component in self.inner.iter().rev() {
-            if let ComponentsType::Service(service) = component {
-                service
-                    .start(&create_caps(&service.requires(), &self.inner))
-                    .map_err(|e| {
-                        warn!(
-                            "Service '{}' can not be started. Reason: {}",
+    /// Starts the stopped service `name` once the services it requires run.
+    pub fn start_named(&self, name: &str) -> Result<(), String> {
+        let index = self
+            .service_index(name)
+            .ok_or_else(|| format!("Service '{}' is not loaded", name))?;
+        if self.started.lock().unwrap().contains(&index) {
+            return Err(format!("Service '{}' is running already", name));
+        }
+        let started = self.started.lock().unwrap().clone();
+        if let Some(missing) = self
+            .dependencies(index)?
+            .into_iter()
+            .find(|dependency| !started.contains(dependency))
+        {
+            return Err(format!(
+                "Service '{}' requires '{}', which is stopped",
+                name,
+                self.inner[missing].name()
+            ));
+        }
+        self.start_at(index)
+    }
+
+    /// Stops the service `name`, unless a running service requires it. The supervisor does not
+    /// restart it.
+    pub fn stop_named(&self, name: &str) -> Result<(), String> {
+        let index = self
+            .service_index(name)
+            .ok_or_else(|| format!("Service '{}' is not loaded", name))?;
+        let mut started = self.started.lock().unwrap();
+        if !started.contains(&index) {
+            return Err(format!("Service '{}' is not running", name));
+        }
+        if let Some(dependent) = self
+            .dependents(index)
+            .into_iter()
+            .find(|dependent| *dependent != index && started.contains(dependent))
+        {
+            return Err(format!(
+                "Service '{}' is required by '{}', which is running",
+                name,
+                self.inner[dependent].name()
+            ));
+        }
+        let ComponentsType::Service(service) = &self.inner[index] else {
+            unreachable!("only services are started");
+        };
+        started.retain(|started| *started != index);
+        drop(started);
+        *service.supervision.lock().unwrap() = Supervision {
+            health: Health::Stopped,
+            restarts: 0,
+            retry_at: None,
+        };
+        service
+            .stop_within(service.library.stop_timeout)
+            .map_err(|e| format!("Service '{}' can not be stopped. Reason: {}", name, e))
+    }
+
+    /// Replaces the attributes of the library `name`. A running service gets them through its
+    /// entry `reconfigure` and keeps the old ones if that fails, the others get them at their
+    /// next start or run.
+    pub fn reconfigure(&mut self, name: &str, attributes: Option<String>) -> Result<(), String> {
+        let index = self
+            .inner
+            .iter()
+            .position(|component| component.name() == name)
+            .ok_or_else(|| format!("Library '{}' is not loaded", name))?;
+        let running = self.started.lock().unwrap().contains(&index);
+        let library = match &mut self.inner[index] {
+            ComponentsType::Service(service) => {
+                if running {
+                    service
+                        .reconfigure(attributes.as_deref().unwrap_or(""))
+                        .map_err(|e| {
+                            format!("Service '{}' can not be reconfigured. Reason: {}", name, e)
+                        })?;
+                }
+                &mut service.library
+            }
+            ComponentsType::Skill(skill) => {
+                let running = || format!("Skill '{}' can not be reconfigured while it runs", name);
+                &mut Arc::get_mut(skill).ok_or_else(running)?.library
+            }
+        };
+        library.config_attr_str = attributes;
+        info!("Reconfigured '{}'", name);
+        Ok(())
+    }
+
+    /// Replaces the library `name` by loading its file again. The started services holding its
+    /// capabilities are stopped before and started with new capabilities after the swap.
+    /// Returns the names of the restarted services in start order. Fails without stopping any
+    /// while capabilities of the library are held elsewhere, e.g. by a blackboard client of the
+    /// loader or a running skill, see `Lease`.
+    ///
+    /// Libraries are never unloaded, so the file is loaded from a temporary copy to pick up
+    /// its new code. Nothing is stopped if the file can not be loaded.
+    pub fn reload(&mut self, name: &str) -> Result<Vec<String>, String> {
+        let index = self
+            .inner
+            .iter()
+            .position(|component| component.name() == name)
+            .ok_or_else(|| format!("Library '{}' is not loaded", name))?;
+        // every run holds the skill, see `SkillRun`
+        if let ComponentsType::Skill(skill) = &self.inner[index] {
+            if Arc::strong_count(skill) > 1 {
+                return Err(format!("Skill '{}' can not be reloaded while it runs", name));
+            }
+        }
+        let old = self.inner[index].library();
+        let path = old
+            .path
+            .clone()
+            .ok_or_else(|| format!("Library '{}' was not loaded from a file", name))?;
+
+        let component = copy_for_reload(&path)
+            .and_then(|copy| {
+                let library = load_library(&copy);
+                let _ = std::fs::remove_file(&copy);
+                library
+            })
+            .and_then(|library| RTLibrary::new(library, None))
+            .and_then(|mut library| {
+                let expected = old.instance_of.as_deref().unwrap_or(name);
+                if library.summary.name != expected {
+                    return Err(format!("The file provides '{}' now", library.summary.name));
+                }
+                if old.instance_of.is_some() {
+                    library.rename(name);
+                }
+                library.config_attr_str = old.config_attr_str.clone();
+                library.path = Some(path.clone());
+                library.restart = old.restart;
+                library.start_timeout = old.start_timeout;
+                library.stop_timeout = old.stop_timeout;
+                library.isolation = old.isolation;
+                library.limits = old.limits;
+                library.wait_for = old.wait_for.clone();
+                library.wait_timeout = old.wait_timeout;
+                library.tick = old.tick;
+                ComponentsType::new(library)
+            })
+            .map_err(|e| {
+                format!(
+                    "Library '{}' can not be reloaded from {}. Reason: {}",
+                    name,
+                    path.display(),
+                    e
+                )
+            })?;
+
+        let dependents = self.dependents(index);
+        let restart: Vec<usize> = self
+            .started
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .filter(|started| dependents.contains(started))
+            .collect();
+
+        // the loader and running skills hold capabilities as well, they must not dangle
+        let lease = &self.inner[index].library().lease;
+        let released = restart
+            .iter()
+            .filter(|started| match &self.inner[**started] {
+                ComponentsType::Service(service) => service.holds(lease),
+                ComponentsType::Skill(_) => false,
+            })
+            .count()
+            + super::groups::holders(lease);
+        let holders = lease.holders().saturating_sub(released);
+        if holders > 0 {
+            return Err(format!(
+                "Library '{}' can not be reloaded while its capabilities are held elsewhere \
+                 (holders: {})",
+                name, holders
+            ));
+        }
+
+        for started in restart.iter().rev() {
+            if let ComponentsType::Service(service) = &self.inner[*started] {
+                service.stop();
+            }
+        }
+
+        self.revoke_subscriptions(name, self.inner[index].library());
+        self.inner[index] = component;
+        info!("Library '{}' reloaded from {}", name, path.display());
+
+        self.start_again(&restart).map_err(|(service, e)| {
+            format!(
+                "Service '{}' can not be restarted after reloading '{}'. Reason: {}",
+                service, name, e
+            )
+        })
+    }
+
+    /// Revokes the blackboard subscriptions the library `name` left, before it is unloaded and
+    /// its callbacks become dangling. The blackboard knows them by the library of the callback,
+    /// not by the component they were made for.
+    fn revoke_subscriptions(&self, name: &str, library: &RTLibrary) {
+        if name == "blackboard" || self.service_running("blackboard") != Some(true) {
+            return;
+        }
+        let Some(address) = library.address() else {
+            return error!("Subscriptions of '{}' can not be revoked. Reason: no summary", name);
+        };
+        let requires = vec!["blackboard".to_string()];
+        let revoked = create_caps(&requires, &self.inner)
+            .map(BlackboardClient::new)
+            .and_then(|client| client.revoke_subscriptions(address).map_err(|e| e.to_string()));
+        match revoked {
+            Ok(0) => {}
+            Ok(revoked) => {
+                warn!("Revoked {} blackboard subscriptions left by '{}'", revoked, name)
+            }
+            Err(e) => error!("Subscriptions of '{}' can not be revoked. Reason: {}", name, e),
+        }
+    }
+
+    /// Writes the lifecycle progress of the services that changed since, once the blackboard
+    /// runs. Progress made before, like the start of the blackboard itself, follows with the
+    /// first call after it started.
+    pub fn publish_progress(&self) {
+        if self.service_running("blackboard") != Some(true) {
+            return;
+        }
+        let requires = vec!["blackboard".to_string()];
+        let client = match create_caps(&requires, &self.inner).map(BlackboardClient::new) {
+            Ok(client) => client,
+            Err(e) => return error!("Progress can not be published. Reason: {}", e),
+        };
+        for component in &self.inner {
+            let ComponentsType::Service(service) = component else {
+                continue;
+            };
+            let name = service.library.name();
+            if let Err(e) = service.progress.lock().unwrap().publish(&client, name) {
+                error!("Progress of '{}' can not be published. Reason: {}", name, e);
+            }
+        }
+    }
+
+    /// Stops the running service `name` and the running services requiring it, then starts
+    /// them again with new capabilities. Returns the names of the restarted services in start
+    /// order.
+    pub fn restart_named(&self, name: &str) -> Result<Vec<String>, String> {
+        let index = self
+            .service_index(name)
+            .ok_or_else(|| format!("Service '{}' is not loaded", name))?;
+        let dependents = self.dependents(index);
+        let restart: Vec<usize> = self
+            .started
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .filter(|started| dependents.contains(started))
+            .collect();
+        if !restart.contains(&index) {
+            return Err(format!("Service '{}' is not running", name));
+        }
+        for started in restart.iter().rev() {
+            if let ComponentsType::Service(service) = &self.inner[*started] {
+                service.stop();
+            }
+        }
+
+        self.start_again(&restart).map_err(|(service, e)| {
+            format!("Service '{}' can not be restarted. Reason: {}", service, e)
+        })
+    }
+
+    // starts the stopped services of `restart` in order, the ones not started any more are
+    // removed from `started`. Fails with the name of the service that did not start.
+    fn start_again(&self, restart: &[usize]) -> Result<Vec<String>, (String, String)> {
+        let mut started = self.started.lock().unwrap();
+        let mut restarted = Vec::new();
+        for (position, index) in restart.iter().enumerate() {
+            let ComponentsType::Service(service) = &self.inner[*index] else {
+                continue;
+            };
+            let result = component_caps(service.library.name(), service.requires(), &self.inner)
+                .and_then(|caps| service.start(&caps));
+            if let Err(e) = result {
+                started.retain(|started| !restart[position..].contains(started));
+                return Err((service.library.summary.name.clone(), e));
+            }
+            restarted.push(service.library.summary.name.clone());
+        }
+        Ok(restarted)
+    }
+
+    /// The running service hosting the skill `name`, see `RTLibrary::hosted_skills`.
+    pub fn skill_host(&self, name: &str) -> Option<&Service> {
+        let started = self.started.lock().unwrap().clone();
+        started.into_iter().find_map(|index| match &self.inner[index] {
+            ComponentsType::Service(service)
+                if service.is_running()
+                    && service.library.hosted_skills().iter().any(|skill| skill == name) =>
+            {
+                Some(service)
+            }
+            _ => None,
+        })
+    }
+
+    /// Whether the service `name` runs, None if there is no such service.
+    pub fn service_running(&self, name: &str) -> Option<bool> {
+        match &self.inner[self.service_index(name)?] {
+            ComponentsType::Service(service) => Some(service.is_running()),
+            ComponentsType::Skill(_) => None,
+        }
+    }
+
+    /// Lifecycle state of the service `name` as reported by its `state` entry, None if there is
+    /// no such service.
+    pub fn service_state(&self, name: &str) -> Option<PluginState> {
+        match &self.inner[self.service_index(name)?] {
+            ComponentsType::Service(service) => Some(service.state()),
+            ComponentsType::Skill(_) => None,
+        }
+    }
+
+    /// State of every loaded component, e.g.
+    /// `[{"name": "web", "type": "service", "version": "0.1.0", "running": true, "health":
+    /// "running", "state": "started"}]`, the health as last seen by the supervisor and the
+    /// lifecycle state as reported by the service. Skills have none of them.
+    pub fn states(&self) -> serde_json::Value {
+        let states: Vec<serde_json::Value> = self
+            .inner
+            .iter()
+            .map(|component| match component {
+                ComponentsType::Service(service) => serde_json::json!({
+                    "name": service.library.summary.name,
+                    "type": "service",
+                    "version": service.library.summary.version,
+                    "running": service.is_running(),
+                    "health": service.supervision.lock().unwrap().health.to_string(),
+                    "state": service.state().to_string(),
+                }),
+                ComponentsType::Skill(skill) => serde_json::json!({
+                    "name": skill.library.summary.name,
+                    "type": "skill",
+                    "version": skill.library.summary.version,
+                }),
+            })
+            .collect();
+        states.into()
+    }
+
+    /// What is loaded, e.g. `[{"name": "web", "type": "service", "version": "0.1.0", "provides":
+    /// [{"capability": "web_reload", "signature": "i32()", "version": "0.1.0"}], "requires":
+    /// ["blackboard >= 0.1"], "running": true, "health": "running"}]`. Skills have neither
+    /// `running` nor `health`.
+    pub fn inventory(&self) -> serde_json::Value {
+        let inventory: Vec<serde_json::Value> = self
+            .inner
+            .iter()
+            .map(|component| {
+                let summary = &component.library().summary;
+                let provides: Vec<serde_json::Value> = summary
+                    .provides
+                    .iter()
+                    .flatten()
+                    .map(|capability| {
+                        serde_json::json!({
+                            "capability": capability.capability,
+                            "signature": capability.signature,
+                            "version": capability.version.as_ref().unwrap_or(&summary.version),
+                        })
+                    })
+                    .collect();
+                let mut entry = serde_json::json!({
+                    "name": summary.name,
+                    "version": summary.version,
+                    "provides": provides,
+                    "requires": component.requires(),
+                });
+                match component {
+                    ComponentsType::Service(service) => {
+                        entry["type"] = "service".into();
+                        entry["running"] = service.is_running().into();
+                        entry["health"] =
+                            service.supervision.lock().unwrap().health.to_string().into();
+                    }
+                    ComponentsType::Skill(_) => entry["type"] = "skill".into(),
+                }
+                entry
+            })
+            .collect();
+        inventory.into()
+    }
+
+    fn start_service(&self, service: &Service) -> Result<i32, String> {
+        let restart = service.library.restart;
+        loop {
+            let result = component_caps(service.library.name(), service.requires(), &self.inner)
+                .and_then(|caps| service.start(&caps));
+            let mut supervision = service.supervision.lock().unwrap();
+            match result {
+                Err(e) if restart.policy != RestartPolicy::Never
+                    && supervision.restarts < restart.max_restarts =>
+                {
+                    let backoff = restart.backoff(supervision.restarts);
+                    supervision.restarts += 1;
+                    warn!(
+                        "Service '{}' failed to start, restarting in {:?}. Reason: {}",
+                        service.library.summary.name, backoff, e
+                    );
+                    drop(supervision);
+                    std::thread::sleep(backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Checks the health of the started services and restarts them according to their restart
+    /// policy, called periodically by the supervisor. Returns the services whose health changed.
+    ///
+    /// The running services requiring a service that is stopped are stopped before it. They wait
+    /// as `restarting` and are started again with new capabilities once it runs again, or are
+    /// `stopped` if it is not restarted.
+    pub fn supervise(&self, now: Instant) -> Vec<(String, Health)> {
+        let started = self.started.lock().unwrap().clone();
+        let mut changes = Vec::new();
+        for &index in &started {
+            let ComponentsType::Service(service) = &self.inner[index] else {
+                continue;
+            };
+            let name = &service.library.summary.name;
+            let restart = service.library.restart;
+            let mut supervision = service.supervision.lock().unwrap();
+
+            let health = if service.running.load(Ordering::SeqCst) {
+                match service.health() {
+                    Health::Running => Health::Running,
+                    health => {
+                        warn!(component = name.as_str(); "Service '{}' is {}", name, health);
+                        let dependents = self.stop_dependents(index, &started, &mut changes);
+                        service.stop();
+                        let restarts = match restart.policy {
+                            RestartPolicy::Always => true,
+                            RestartPolicy::OnFailure => health == Health::Failed,
+                            RestartPolicy::Never => false,
+                        };
+                        if restarts && supervision.restarts < restart.max_restarts {
+                            supervision.retry_at = Some(now + restart.backoff(supervision.restarts));
+                            Health::Restarting
+                        } else {
+                            self.release_dependents(&dependents, &mut changes);
+                            health
+                        }
+                    }
+                }
+            } else {
+                match supervision.retry_at {
+                    // a hanging `start` or `stop` is waited for without using up a restart
+                    Some(retry_at) if now >= retry_at && !service.busy.load(Ordering::SeqCst) => {
+                        supervision.restarts += 1;
+                        supervision.retry_at = None;
+                        info!(
+                            component = name.as_str();
+                            "Restarting service '{}' ({}. restart)", name, supervision.restarts
+                        );
+                        let result =
+                            component_caps(service.library.name(), service.requires(), &self.inner)
+                                .and_then(|caps| service.start(&caps));
+                        let dependents = self.waiting_dependents(index, &started);
+                        match result {
+                            Ok(_) => {
+                                self.resume_dependents(&dependents, &mut changes);
+                                Health::Running
+                            }
+                            Err(e) => {
+                                error!(
+                                    component = name.as_str();
+                                    "Service '{}' can not be restarted. Reason: {}", name, e
+                                );
+                                if supervision.restarts < restart.max_restarts {
+                                    supervision.retry_at =
+                                        Some(now + restart.backoff(supervision.restarts));
+                                    Health::Restarting
+                                } else {
+                                    self.release_dependents(&dependents, &mut changes);
+                                    Health::Failed
+                                }
+                            }
+                        }
+                    }
+                    _ => supervision.health,
+                }
+            };
+
+            if health != supervision.health {
+                supervision.health = health;
+                changes.push((name.clone(), health));
+            }
+        }
+        changes
+    }
+
+    // stops the running services requiring the service at `index` in reverse start order, they
+    // wait for it as restarting. Returns them in start order.
+    fn stop_dependents(
+        &self,
+        index: usize,
+        started: &[usize],
+        changes: &mut Vec<(String, Health)>,
+    ) -> Vec<usize> {
+        let dependents = self.dependents(index);
+        let stopped: Vec<usize> = started
+            .iter()
+            .copied()
+            .filter(|started| *started != index && dependents.contains(started))
+            .filter(|started| match &self.inner[*started] {
+                ComponentsType::Service(service) => service.running.load(Ordering::SeqCst),
+                ComponentsType::Skill(_) => false,
+            })
+            .collect();
+        for dependent in stopped.iter().rev() {
+            let ComponentsType::Service(service) = &self.inner[*dependent] else {
+                continue;
+            };
+            info!(
+                "Stopping service '{}', it requires '{}'",
+                service.library.summary.name,
+                self.inner[index].name()
+            );
+            service.stop();
+            let mut supervision = service.supervision.lock().unwrap();
+            supervision.retry_at = None;
+            if supervision.health != Health::Restarting {
+                supervision.health = Health::Restarting;
+                changes.push((service.library.summary.name.clone(), Health::Restarting));
+            }
+        }
+        stopped
+    }
+
+    // the services stopped by `stop_dependents` for the service at `index`, in start order
+    fn waiting_dependents(&self, index: usize, started: &[usize]) -> Vec<usize> {
+        let dependents = self.dependents(index);
+        started
+            .iter()
+            .copied()
+            .filter(|started| *started != index && dependents.contains(started))
+            .filter(|started| match &self.inner[*started] {
+                ComponentsType::Service(service) => {
+                    let supervision = service.supervision.lock().unwrap();
+                    !service.running.load(Ordering::SeqCst)
+                        && supervision.health == Health::Restarting
+                        && supervision.retry_at.is_none()
+                }
+                ComponentsType::Skill(_) => false,
+            })
+            .collect()
+    }
+
+    // starts the services in `dependents` again with new capabilities, in order
+    fn resume_dependents(&self, dependents: &[usize], changes: &mut Vec<(String, Health)>) {
+        for dependent in dependents {
+            let ComponentsType::Service(service) = &self.inner[*dependent] else {
+                continue;
+            };
+            let name = &service.library.summary.name;
+            let result = component_caps(service.library.name(), service.requires(), &self.inner)
+                .and_then(|caps| service.start(&caps));
+            let health = match result {
+                Ok(_) => {
+                    info!(component = name.as_str(); "Service '{}' started again", name);
+                    Health::Running
+                }
+                Err(e) => {
+                    error!(
+                        component = name.as_str();
+                        "Service '{}' can not be started again. Reason: {}", name, e
+                    );
+                    Health::Failed
+                }
+            };
+            service.supervision.lock().unwrap().health = health;
+            changes.push((name.clone(), health));
+        }
+    }
+
+    // the services in `dependents` stay stopped, the service they require is not restarted
+    fn release_dependents(&self, dependents: &[usize], changes: &mut Vec<(String, Health)>) {
+        for dependent in dependents {
+            if let ComponentsType::Service(service) = &self.inner[*dependent] {
+                service.supervision.lock().unwrap().health = Health::Stopped;
+                changes.push((service.library.summary.name.clone(), Health::Stopped));
+            }
+        }
+    }
+
+    /// Status details reported by the `health_status` entry of every running service.
+    pub fn health_details(&self) -> Vec<(String, serde_json::Value)> {
+        let started = self.started.lock().unwrap().clone();
+        started
+            .into_iter()
+            .filter_map(|index| match &self.inner[index] {
+                ComponentsType::Service(service) if service.running.load(Ordering::SeqCst) => {
+                    let status = service.library.health_status()?;
+                    Some((service.library.summary.name.clone(), status))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Health of all started services as JSON, e.g.
+    /// `{"status": "degraded", "degraded": ["web"], "services": {"web": "failed"}, "states":
+    /// {"web": "failed", "mqtt": "created"}}`. The status is `ok` while every service is running,
+    /// `states` holds the lifecycle state of every loaded service.
+    pub fn health_summary(&self) -> serde_json::Value {
+        let started = self.started.lock().unwrap().clone();
+        let mut services = serde_json::Map::new();
+        let mut degraded = Vec::new();
+        for index in started {
+            let ComponentsType::Service(service) = &self.inner[index] else {
+                continue;
+            };
+            let name = service.library.summary.name.clone();
+            let health = service.supervision.lock().unwrap().health;
+            if health != Health::Running {
+                degraded.push(name.clone());
+            }
+            services.insert(name, health.to_string().into());
+        }
+        let states: serde_json::Map<String, serde_json::Value> = self
+            .inner
+            .iter()
+            .filter_map(|component| match component {
+                ComponentsType::Service(service) => Some((
+                    service.library.summary.name.clone(),
+                    service.state().to_string().into(),
+                )),
+                ComponentsType::Skill(_) => None,
+            })
+            .collect();
+        serde_json::json!({
+            "status": if degraded.is_empty() { "ok" } else { "degraded" },
+            "degraded": degraded,
+            "services": services,
+            "states": states,
+        })
+    }
+
+    /// Stops the started services in reverse start order, waiting for each one at most its
+    /// stop timeout. Returns the services that failed to stop or did not stop in time. These
+    /// stay loaded, because their `stop` may still be running.
+    pub fn shutdown(&mut self) -> Vec<String> {
+        let started = std::mem::take(self.started.get_mut().unwrap());
+        let mut refused = Vec::new();
+        for index in started.into_iter().rev() {
+            if let ComponentsType::Service(service) = &self.inner[index] {
+                if service.is_running() {
+                    service.enter(Stage::Stopping, None);
+                    self.publish_progress();
+                }
+                let stopped = service.stop_within(service.library.stop_timeout);
+                self.publish_progress();
+                match stopped {
+                    Ok(()) => info!("Service '{}' stopped", service.library.summary.name),
+                    Err(e) => {
+                        error!(
+                            "Service '{}' refused to stop. Reason: {}",
                             service.library.summary.name, e
                         );
+                        refused.push(index);
+                    }
+                }
+            }
+        }
+
+        refused.sort_unstable();
+        refused
+            .into_iter()
+            .rev()
+            .map(|index| {
+                let component = self.inner.remove(index);
+                let name = component.name().to_string();
+                std::mem::forget(component);
+                name
+            })
+            .collect()
+    }
+
+    /// Checks that the requirements of all components are loaded and provide compatible
+    /// capabilities. Returns the problems found.
+    pub fn check_requires(&self) -> Vec<String> {
+        self.resolve()
+            .into_iter()
+            .filter_map(|resolution| {
+                let error = resolution.error?;
+                Some(format!(
+                    "'{}' requires '{}': {}",
+                    resolution.component, resolution.require, error
+                ))
+            })
+            .collect()
+    }
+
+    /// Resolves every requirement of every component like `create_caps` does when it is
+    /// started, without starting anything.
+    pub fn resolve(&self) -> Vec<Resolution> {
+        let mut resolutions = Vec::new();
+        // every component gets the capabilities of the loader, they are not resolved
+        let own: Vec<String> = create_caps(&Vec::new(), &self.inner)
+            .map(|caps| caps.iter().map(|cap| cap.name()).collect())
+            .unwrap_or_default();
+        for component in &self.inner {
+            for require in component.requires() {
+                let provider = parse_requirement(require).and_then(|(name, _)| {
+                    self.inner
+                        .iter()
+                        .map(ComponentsType::library)
+                        .find(|library| library.summary.name == name)
+                        .ok_or_else(|| "it is not loaded".to_string())
+                });
+                // a group is resolved once its library is started, if it ever is
+                let group = group_library(require).is_some();
+                if group && provider.is_err() {
+                    resolutions.push(Resolution {
+                        component: component.name().to_string(),
+                        require: require.clone(),
+                        provider: None,
+                        capabilities: Vec::new(),
+                        error: None,
+                    });
+                    continue;
+                }
+                let eager = match group {
+                    true => require.replacen(GROUP_SUFFIX, "", 1),
+                    false => require.clone(),
+                };
+                let capabilities = provider.clone().and_then(|_| {
+                    create_caps(&vec![eager], &self.inner).map(|caps| {
+                        caps.iter()
+                            .map(|cap| cap.name())
+                            .filter(|name| !own.contains(name))
+                            .collect()
                     })
-                    .unwrap();
+                });
+                resolutions.push(Resolution {
+                    component: component.name().to_string(),
+                    require: require.clone(),
+                    provider: provider.ok().map(|library| {
+                        format!("{} {}", library.summary.name, library.summary.version)
+                    }),
+                    capabilities: capabilities.as_ref().cloned().unwrap_or_default(),
+                    error: capabilities.err(),
+                });
+            }
+        }
+        resolutions
+    }
+
+    fn service_index(&self, name: &str) -> Option<usize> {
+        self.inner.iter().position(|component| match component {
+            ComponentsType::Service(service) => service.library.summary.name == name,
+            ComponentsType::Skill(_) => false,
+        })
+    }
+
+    /// Indices of the services in `inner`, every service after the services it requires and
+    /// otherwise in load order. Fails if the services depend on each other in a cycle.
+    pub fn start_order(&self) -> Result<Vec<usize>, String> {
+        let mut visits = vec![None; self.inner.len()];
+        let mut path = Vec::new();
+        let mut order = Vec::new();
+        for index in (0..self.inner.len()).rev() {
+            if let ComponentsType::Service(_) = self.inner[index] {
+                self.visit(index, &mut visits, &mut path, &mut order)?;
+            }
+        }
+        Ok(order)
+    }
+
+    fn visit(
+        &self,
+        index: usize,
+        visits: &mut Vec<Option<Visit>>,
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        let ComponentsType::Service(_) = &self.inner[index] else {
+            return Ok(());
+        };
+        match visits[index] {
+            Some(Visit::Done) => return Ok(()),
+            Some(Visit::InProgress) => {
+                let start = path.iter().position(|i| *i == index).unwrap_or(0);
+                let cycle: Vec<&str> = path[start..]
+                    .iter()
+                    .chain(std::iter::once(&index))
+                    .map(|i| self.inner[*i].name())
+                    .collect();
+                return Err(format!("Dependency cycle between services: {}", cycle.join(" -> ")));
             }
+            None => {}
+        }
+
+        visits[index] = Some(Visit::InProgress);
+        path.push(index);
+        for dependency in self.dependencies(index)? {
+            self.visit(dependency, visits, path, order)?;
+        }
+        path.pop();
+        visits[index] = Some(Visit::Done);
+        order.push(index);
+        Ok(())
+    }
+}
+
+impl Drop for Components {
+    fn drop(&mut self) {
+        // services stop when dropped, so drop them in reverse start order
+        let started = std::mem::take(self.started.get_mut().unwrap());
+        let mut components: Vec<Option<ComponentsType>> = self.inner.drain(..).map(Some).collect();
+        for index in started.into_iter().rev() {
+            drop(components[index].take());
+        }
+    }
+}
+
+impl ComponentsType {
+    fn new(library: RTLibrary) -> Result<Self, String> {
+        match library.summary.library_type {
+            RTLibraryType::Service => Service::new(library).map(ComponentsType::Service),
+            RTLibraryType::Skill => {
+                Skill::new(library).map(|skill| ComponentsType::Skill(Arc::new(skill)))
+            }
+        }
+    }
+
+    pub fn library(&self) -> &RTLibrary {
+        match self {
+            ComponentsType::Service(service) => &service.library,
+            ComponentsType::Skill(skill) => &skill.library,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.library().name()
+    }
+
+    pub fn requires(&self) -> &Vec<String> {
+        match self {
+            ComponentsType::Service(service) => service.requires(),
+            ComponentsType::Skill(skill) => skill.requires(),
+        }
+    }
+
+    pub fn component(&self) -> &dyn Component {
+        match self {
+            ComponentsType::Service(service) => service,
+            ComponentsType::Skill(skill) => skill.as_ref(),
         }
     }
 }
@@ -139,11 +1270,111 @@ impl Skill {
         })
     }
 
-    fn run(&self, caps: &interfaces::capabilities::Capabilities) -> Result<i32, String> {
-        Component::run(self, "run", caps)
+    fn call_run(&self, caps: &interfaces::capabilities::Capabilities) -> Result<i32, String> {
+        match self.library.isolation {
+            Isolation::None => Component::run(self, "run", caps),
+            #[cfg(unix)]
+            Isolation::Process => super::isolation::run(self, caps),
+            #[cfg(not(unix))]
+            Isolation::Process => Err("Skills can only run isolated on unix".to_string()),
+        }
+    }
+
+    /// Runs the skill with its `run_async` entry if it exports one, asking `stopped` while it
+    /// runs and cancelling it with `run_cancel` once that is true. Other skills are run with
+    /// `run` and can not be stopped.
+    pub fn run_cancellable(
+        &self,
+        caps: &interfaces::capabilities::Capabilities,
+        stopped: &dyn Fn() -> bool,
+    ) -> Result<i32, String> {
+        let name = self.library.name();
+        super::metrics::timed_run(name, || self.call_run_cancellable(caps, stopped))
+    }
+
+    fn call_run_cancellable(
+        &self,
+        caps: &interfaces::capabilities::Capabilities,
+        stopped: &dyn Fn() -> bool,
+    ) -> Result<i32, String> {
+        type RunAsync = unsafe extern "C" fn(
+            &interfaces::bindings::Capabilities,
+            *const c_char,
+            *mut c_void,
+            *mut c_void,
+        ) -> c_int;
+        type ContextRunAsync = unsafe extern "C" fn(
+            *mut rt_context,
+            &interfaces::bindings::Capabilities,
+            *const c_char,
+            *mut c_void,
+            *mut c_void,
+        ) -> c_int;
+        type ContextCancel = unsafe extern "C" fn(*mut rt_context, c_int) -> c_int;
+        let library = &self.library.library;
+        if self.library.is_legacy() || self.library.isolation != Isolation::None {
+            return self.call_run(caps);
+        }
+        let attr = CString::new(self.attributes()).map_err(|e| e.to_string())?;
+        let context = self.library.context.as_ptr();
+        let (run_async, cancel): (RunAsyncCall, Option<CancelCall>) = unsafe {
+            if self.library.takes_context() {
+                let Ok(run_async) = library.get::<ContextRunAsync>(b"run_async").map(|f| *f) else {
+                    return self.call_run(caps);
+                };
+                let cancel = library.get::<ContextCancel>(b"run_cancel").map(|f| *f).ok();
+                (
+                    Box::new(move |completion, user_data| {
+                        run_async(context, caps.inner(), attr.as_ptr(), completion, user_data)
+                    }),
+                    cancel.map(|cancel| -> CancelCall {
+                        Box::new(move |handle| cancel(context, handle))
+                    }),
+                )
+            } else {
+                let Ok(run_async) = library.get::<RunAsync>(b"run_async").map(|f| *f) else {
+                    return self.call_run(caps);
+                };
+                let cancel = library.get::<Cancel>(b"run_cancel").map(|f| *f).ok();
+                (
+                    Box::new(move |completion, user_data| {
+                        run_async(caps.inner(), attr.as_ptr(), completion, user_data)
+                    }),
+                    cancel.map(|cancel| -> CancelCall { Box::new(move |handle| cancel(handle)) }),
+                )
+            }
+        };
+        let name = self.library.name();
+        let call = guarded(name, "run_async", || {
+            PendingCall::start(run_async)
+        })?
+        .map_err(|e| e.to_string())?;
+
+        let mut cancelled = false;
+        loop {
+            if let Some(result) = call.wait_timeout(Duration::from_millis(100)) {
+                return Ok(result);
+            }
+            if !cancelled && stopped() {
+                cancelled = true;
+                info!("Cancelling skill '{}'", self.library.summary.name);
+                if let Some(cancel) = &cancel {
+                    let _ = guarded(name, "run_cancel", || cancel(call.handle()));
+                }
+            }
+        }
     }
 }
 
+// calls of `start` and `stop` not returned to their caller, who holds the components meanwhile
+static LIFECYCLE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether a service is in its `start` or `stop`. The components are locked meanwhile, so the
+/// service asking for them from there would wait for itself.
+pub fn in_lifecycle_call() -> bool {
+    LIFECYCLE_CALLS.load(Ordering::SeqCst) > 0
+}
+
 impl Service {
     fn new(library: RTLibrary) -> Result<Self, String> {
         Ok(Self {
@@ -153,36 +1384,226 @@ impl Service {
                 Vec::new()
             },
             library: library,
+            running: AtomicBool::new(false),
+            busy: Arc::new(AtomicBool::new(false)),
+            panicked: Arc::new(AtomicBool::new(false)),
+            supervision: Mutex::new(Supervision {
+                health: Health::Stopped,
+                restarts: 0,
+                retry_at: None,
+            }),
+            progress: Mutex::new(Progress::new()),
+            leases: Mutex::new(Vec::new()),
         })
     }
 
+    /// Fails with the status and last error of the service if its `start` returns an error or
+    /// does not return within the start timeout of the library.
+    // whether the capabilities it got in `start` hold `lease`
+    fn holds(&self, lease: &Lease) -> bool {
+        self.leases.lock().unwrap().iter().any(|held| held.same(lease))
+    }
+
     fn start(&self, caps: &interfaces::capabilities::Capabilities) -> Result<i32, String> {
-        Component::run(self, "start", caps)
+        self.enter(Stage::Starting, None);
+        *self.leases.lock().unwrap() = caps.leases().to_vec();
+        let result = self.call_start(caps);
+        match &result {
+            Ok(_) => self.enter(Stage::Started, None),
+            Err(e) => {
+                self.leases.lock().unwrap().clear();
+                self.enter(Stage::Failed, Some(e.clone()))
+            }
+        }
+        result
     }
 
-    fn stop(&self) {
-        unsafe {
-            let library = &self.library.library;
-            let result = library
-                .get("stop".as_bytes())
-                .map(|f: Symbol<unsafe extern "C" fn() -> c_int>| f());
-            match result {
-                Ok(_) => {
-                    info!("Service '{}' stopped", self.library.summary.name);
+    fn call_start(&self, caps: &interfaces::capabilities::Capabilities) -> Result<i32, String> {
+        let call = self.entry_call("start", caps)?;
+        let started = Instant::now();
+        let result = self.call_within("start", call, self.library.start_timeout)?;
+        super::metrics::record_start(&self.library.summary.name, started.elapsed());
+        if result < 0 {
+            return Err(format!(
+                "start returned {} ({}): {}",
+                result,
+                RtStatus::from_code(result),
+                self.library
+                    .last_error()
+                    .unwrap_or_else(|| "no error message".to_string())
+            ));
+        }
+        self.running.store(true, Ordering::SeqCst);
+        self.panicked.store(false, Ordering::SeqCst);
+        super::groups::register(&self.library);
+        Ok(result)
+    }
+
+    /// Runs `call` of the entry `function` on a watchdog thread and fails if it does not return
+    /// within `timeout` or panics. The service stays busy until the call returns, a `start`
+    /// returning after its deadline is undone by `stop` so the supervisor can start the service
+    /// again.
+    pub fn call_within(
+        &self,
+        function: &str,
+        call: EntryCall,
+        timeout: Duration,
+    ) -> Result<c_int, String> {
+        if self.busy.swap(true, Ordering::SeqCst) {
+            return Err(format!("{} can not be called, an earlier call hangs", function));
+        }
+        let stop = self.library.entry("stop").ok();
+        let undo = function == "start";
+        let name = self.library.summary.name.clone();
+        let entry = function.to_string();
+        let busy = self.busy.clone();
+        let panicked = self.panicked.clone();
+        // set by the caller when it stops waiting, under the lock so no result gets lost
+        let timed_out = Arc::new(Mutex::new(false));
+        let thread_timed_out = timed_out.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        LIFECYCLE_CALLS.fetch_add(1, Ordering::SeqCst);
+        std::thread::spawn(move || {
+            let result = guarded(&name, &entry, call);
+            if result.is_err() {
+                panicked.store(true, Ordering::SeqCst);
+            }
+            let timed_out = thread_timed_out.lock().unwrap();
+            if *timed_out {
+                match &result {
+                    Ok(result) => {
+                        warn!("Service '{}' returned {} after its deadline", name, result)
+                    }
+                    Err(e) => warn!("Service '{}' failed after its deadline: {}", name, e),
                 }
-                Err(e) => {
-                    warn!(
-                        "Service '{}' can not be stopped. Reason: {}",
-                        self.library.summary.name, e
-                    );
+                if let (true, Ok(0..), Some(stop)) = (undo, &result, stop) {
+                    let _ = guarded(&name, "stop", stop);
                 }
+                busy.store(false, Ordering::SeqCst);
+            } else {
+                // not busy anymore once the caller has the result
+                busy.store(false, Ordering::SeqCst);
+                let _ = sender.send(result);
+            }
+        });
+
+        let result = receiver.recv_timeout(timeout).or_else(|_| {
+            let mut timed_out = timed_out.lock().unwrap();
+            // the result may have arrived while waiting for the lock
+            receiver.try_recv().map_err(|_| {
+                *timed_out = true;
+                error!(
+                    "Service '{}' hangs in {}, it did not return within {:?}",
+                    self.library.summary.name, function, timeout
+                );
+                format!("{} did not return within {:?}", function, timeout)
+            })
+        });
+        LIFECYCLE_CALLS.fetch_sub(1, Ordering::SeqCst);
+        result?
+    }
+
+    // published by `Components::publish_progress`
+    fn enter(&self, stage: Stage, error: Option<String>) {
+        self.progress.lock().unwrap().enter(stage, error);
+    }
+
+    /// Whether `start` succeeded and the service was not stopped since.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Health reported by the `health` entry of the service. A running service without one is
+    /// considered healthy, a service that panicked is failed.
+    fn health(&self) -> Health {
+        if self.panicked.load(Ordering::SeqCst) {
+            return Health::Failed;
+        }
+        let Ok(health) = self.library.entry("health") else {
+            return Health::Running;
+        };
+        match guarded(self.library.name(), "health", health) {
+            Ok(result) => match RtStatus::from_code(result) {
+                RtStatus::Ok => Health::Running,
+                RtStatus::NotRunning => Health::Stopped,
+                _ => Health::Failed,
+            },
+            Err(_) => {
+                self.panicked.store(true, Ordering::SeqCst);
+                Health::Failed
+            }
+        }
+    }
+
+    /// Lifecycle state reported by the `state` entry of the service. Without one it follows from
+    /// whether the service runs and its health. A service that panicked is failed.
+    pub fn state(&self) -> PluginState {
+        if self.panicked.load(Ordering::SeqCst) {
+            return PluginState::Failed;
+        }
+        if let Ok(state) = self.library.entry("state") {
+            return match guarded(self.library.name(), "state", state) {
+                Ok(code) => PluginState::from_code(code).unwrap_or(PluginState::Failed),
+                Err(_) => PluginState::Failed,
+            };
+        }
+        if self.is_running() {
+            return PluginState::Started;
+        }
+        match self.supervision.lock().unwrap().health {
+            Health::Failed | Health::Restarting => PluginState::Failed,
+            Health::Running | Health::Stopped => PluginState::Stopped,
+        }
+    }
+
+    /// Calls `stop` of the service on a watchdog thread and fails if it returns an error or does
+    /// not return within `timeout`.
+    fn stop_within(&self, timeout: Duration) -> Result<(), String> {
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        super::groups::unregister(self.library.name());
+        self.enter(Stage::Stopping, None);
+        let result = self.call_stop(timeout);
+        match &result {
+            Ok(()) => {
+                // a service still running after a failed stop keeps its capabilities
+                self.leases.lock().unwrap().clear();
+                self.enter(Stage::Stopped, None)
             }
+            Err(e) => self.enter(Stage::Failed, Some(e.clone())),
+        }
+        result
+    }
+
+    fn call_stop(&self, timeout: Duration) -> Result<(), String> {
+        let stop = self.library.entry("stop").map_err(|e| e.to_string())?;
+        match self.call_within("stop", stop, timeout)? {
+            result if result < 0 => Err(format!(
+                "stop returned {} ({})",
+                result,
+                RtStatus::from_code(result)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stops the service if it was started, so stopping twice calls `stop` of the plugin once.
+    fn stop(&self) {
+        match self.stop_within(self.library.stop_timeout) {
+            Ok(_) => info!("Service '{}' stopped", self.library.summary.name),
+            Err(e) => warn!(
+                "Service '{}' can not be stopped. Reason: {}",
+                self.library.summary.name, e
+            ),
         }
     }
 }
 
 pub struct Components {
     pub inner: ComponentsVec,
+    started: Mutex<Vec<usize>>, // indices into `inner` in start order
 }
 
 fn get_capability_fn<'a>(
@@ -198,66 +1619,183 @@ fn get_capability_fn<'a>(
     }
 }
 
+// entries of a library only the loader calls, never handed to the components requiring it
+const LIFECYCLE_ENTRIES: [&str; 12] = [
+    "start",
+    "stop",
+    "health",
+    "state",
+    "health_status",
+    "reconfigure",
+    "run",
+    "run_async",
+    "run_cancel",
+    "skills",
+    "run_skill",
+    "self_test",
+];
+
+/// Splits a `requires` entry like `blackboard >= 0.2` into the library name and its version
+/// constraint. A plain name accepts every version, a group `datalogger.* >= 0.1` constrains the
+/// datalogger.
+pub fn parse_requirement(require: &str) -> Result<(&str, VersionReq), String> {
+    let require = require.trim();
+    let (name, constraint) = match group_library(require) {
+        Some(library) => (library, &require[library.len() + GROUP_SUFFIX.len()..]),
+        None => {
+            let split = require
+                .find(|c: char| c.is_whitespace() || "<>=~^*".contains(c))
+                .unwrap_or(require.len());
+            let (name, constraint) = require.split_at(split);
+            (name.split_once(':').map_or(name, |(name, _)| name), constraint)
+        }
+    };
+    if constraint.trim().is_empty() {
+        return Ok((name, VersionReq::STAR));
+    }
+    VersionReq::parse(constraint.trim())
+        .map(|req| (name, req))
+        .map_err(|e| format!("Invalid requirement '{}'. Reason: {}", require, e))
+}
+
+// the capabilities a `requires` entry like `blackboard:blackboard_get_ >= 0.1` is restricted to,
+// all of the library without a prefix
+fn requirement_prefix(require: &str) -> &str {
+    let name = require
+        .trim()
+        .split(|c: char| c.is_whitespace() || "<>=~^*".contains(c))
+        .next()
+        .unwrap_or("");
+    name.split_once(':').map_or("", |(_, prefix)| prefix)
+}
+
+/// `create_caps` for the component `name`, with the blackboard calls made in its session, see
+/// `sessions`, counted, see `metrics`, and traced if enabled, see `audit`.
+pub fn component_caps(
+    name: &str,
+    requires: &Vec<String>,
+    libraries: &ComponentsVec,
+) -> Result<interfaces::capabilities::Capabilities, String> {
+    let blackboard = match super::sessions::enabled() {
+        true => Some(create_caps(&vec!["blackboard".to_string()], libraries)?),
+        false => None,
+    };
+    create_caps(requires, libraries)
+        .map(|caps| super::sessions::bound(name, caps, blackboard.as_ref()))
+        .map(|caps| super::audit::traced(name, super::metrics::counted(caps)))
+}
+
+/// Collects the capabilities provided by the required libraries, without the entries the loader
+/// calls and restricted to the prefix of the requirement. Fails if a capability cannot be loaded,
+/// is provided twice or its version does not satisfy the requirement. A group like
+/// `datalogger.*` gets an entry resolving its capabilities on their first lookup, see `groups`.
 pub fn create_caps(
     requires: &Vec<String>,
     libraries: &ComponentsVec,
-) -> interfaces::capabilities::Capabilities {
+) -> Result<interfaces::capabilities::Capabilities, String> {
     let mut caps = interfaces::capabilities::Capabilities::new();
+    // every component logs through the loader, reads its clock and may ask it about the other
+    // components
+    caps.add(super::logging::log_write_capability())?;
+    let loader_caps = super::clock::capabilities()
+        .into_iter()
+        .chain(super::runtime::capabilities());
+    for capability in loader_caps {
+        caps.add(capability)?;
+    }
 
-    for require_lib in requires {
-        let lib = libraries.iter().find(|lib| match lib {
-            ComponentsType::Service(service) => service.library.summary.name == *require_lib,
-            ComponentsType::Skill(skill) => skill.library.summary.name == *require_lib,
-        });
+    for require in requires {
+        let (require_lib, version_req) = parse_requirement(require)?;
+        if group_library(require).is_some() {
+            caps.add(super::groups::entry(require))?;
+            continue;
+        }
 
-        let provides = match lib {
-            Some(ComponentsType::Service(service)) => &service.library.summary.provides,
-            Some(ComponentsType::Skill(skill)) => &skill.library.summary.provides,
-            None => {
-                warn!("Library '{}' not found", require_lib);
-                continue;
-            }
-        };
+        let lib = libraries
+            .iter()
+            .map(ComponentsType::library)
+            .find(|library| library.summary.name == require_lib);
 
-        let provides = provides.as_ref().unwrap();
+        let Some(library) = lib else {
+            warn!("Library '{}' not found", require_lib);
+            continue;
+        };
 
-        for capability in provides {
-            let capability_name = capability.capability.clone();
-            let capability_entry = capability.entry.clone();
+        let provided = provided_caps(library, require, &version_req)?;
+        caps.merge(&provided.filtered(requirement_prefix(require)))
+            .map_err(|e| {
+                format!(
+                    "System configuration error in '{}'. Reason: {}",
+                    require_lib, e
+                )
+            })?;
+    }
+    Ok(caps)
+}
 
-            trace!("Capability: {}", capability_name);
-            trace!("Entry: {}", capability_entry);
+/// The capabilities `library` provides for the requirement `require`, without the entries the
+/// loader calls. Fails if one cannot be loaded, is provided twice or its version does not
+/// satisfy `version_req`.
+pub fn provided_caps(
+    library: &RTLibrary,
+    require: &str,
+    version_req: &VersionReq,
+) -> Result<interfaces::capabilities::Capabilities, String> {
+    let mut provided = interfaces::capabilities::Capabilities::new();
+    let require_lib = library.name();
+    let Some(provides) = library.summary.provides.as_ref() else {
+        warn!("Library '{}' provides no capabilities", require_lib);
+        return Ok(provided);
+    };
 
-            let capability_fn = match lib {
-                Some(ComponentsType::Service(service)) => {
-                    get_capability_fn(&service.library, capability_entry.as_str())
-                }
-                Some(ComponentsType::Skill(skill)) => {
-                    get_capability_fn(&skill.library, capability_entry.as_str())
-                }
-                None => {
-                    let error_string = format!(
-                        "Capability '{}' not found in '{}'",
-                        capability_name, require_lib
-                    );
-                    error!("{}", error_string);
-                    Err(error_string)
-                }
-            };
+    for capability in provides {
+        let capability_name = capability.capability.clone();
+        let capability_entry = capability.entry.clone();
+        if LIFECYCLE_ENTRIES.contains(&capability_entry.as_str()) {
+            continue;
+        }
 
-            if capability_fn.is_err() {
-                panic!(
-                    "System configuration error. Reason: {}",
-                    capability_fn.unwrap_err()
-                );
-            }
+        trace!(capability = capability_name.as_str(); "Entry: {}", capability_entry);
 
-            let capability_fn = capability_fn.unwrap();
-            caps.add(interfaces::capabilities::Capability::new(
-                &capability_name,
-                unsafe { capability_fn.try_as_raw_ptr().unwrap() },
-            ));
+        // capabilities without a version share the one of their library
+        let version = capability
+            .version
+            .clone()
+            .unwrap_or_else(|| library.summary.version.clone());
+        let parsed = Version::parse(&version).map_err(|e| {
+            format!(
+                "Capability '{}' has an invalid version '{}'. Reason: {}",
+                capability_name, version, e
+            )
+        })?;
+        if !version_req.matches(&parsed) {
+            let error_string = format!(
+                "Incompatible capability '{}' {}, '{}' is required",
+                capability_name, version, require
+            );
+            error!(capability = capability_name.as_str(); "{}", error_string);
+            return Err(error_string);
         }
+
+        let capability_fn = get_capability_fn(library, capability_entry.as_str())
+            .map_err(|e| format!("System configuration error. Reason: {}", e))?;
+        let function = unsafe { capability_fn.try_as_raw_ptr() }.ok_or_else(|| {
+            format!("Capability '{}' has no function pointer", capability_name)
+        })?;
+        let signature = capability.signature.as_deref().unwrap_or("");
+        let mut cap = interfaces::capabilities::Capability::with_signature(
+            &capability_name,
+            function,
+            signature,
+        );
+        cap.set_version(&version);
+        provided.add(cap).map_err(|e| {
+            format!(
+                "System configuration error in '{}'. Reason: {}",
+                require_lib, e
+            )
+        })?;
     }
-    caps
+    provided.hold(library.lease.clone());
+    Ok(provided)
 }
@@ -2,16 +2,273 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use interfaces::blackboard::BlackboardEntries;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Shortest tick period, a higher `tick_rate_hz` would spin the tick scheduler.
+pub const MIN_TICK_PERIOD: std::time::Duration = std::time::Duration::from_micros(1);
+
+/// When the supervisor restarts a service.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    Always,    // also after the service stopped on its own
+    OnFailure, // after a failed start or a crash
+    #[default]
+    Never,
+}
+
+/// Where a skill runs, see `isolation`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Isolation {
+    #[default]
+    None,    // in the loader process
+    Process, // in a forked child process, a crash only ends the child
+}
+
+/// Resources a skill with `isolation: process` may use, see `limits`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    pub memory_mb: Option<u64>,   // resident memory of the child process
+    pub cpu_percent: Option<u32>, // of one core, averaged over a second
+}
+
+/// Blackboard namespaces a component may read and write, e.g. `{read: [nav, robot], write:
+/// [nav]}`. Enforced by the blackboard for calls made in a session of the component.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Access {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read: Option<Vec<String>>, // all keys if not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write: Option<Vec<String>>, // keys outside the namespaces others write if not given
+}
+
+/// When a skill is ticked, see `ticks`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TickConfig {
+    pub period: std::time::Duration,
+    pub budget: std::time::Duration, // a longer tick counts as overrun
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LibraryConfig {
     pub name: String,
+    pub instance: Option<String>, // name of the component if the library is loaded more than once
     pub path: Option<PathBuf>,
     pub attributes: Option<BlackboardEntries>,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    pub max_restarts: Option<u32>,    // in total, default 3
+    pub backoff_ms: Option<u64>,      // before the first restart, doubles with every restart
+    pub start_timeout_ms: Option<u64>, // how long `start` may take, default 10000
+    pub stop_timeout_ms: Option<u64>, // how long `stop` may take, default 5000
+    pub log_level: Option<log::LevelFilter>, // of the plugin's records, `RUST_LOG` overrides it
+    #[serde(default)]
+    pub isolation: Isolation, // only skills can run in a child process
+    pub access: Option<Access>,
+    pub limits: Option<Limits>, // only for skills running in a child process
+    #[serde(default)]
+    pub wait_for: Vec<String>, // services started and keys written before a skill runs
+    pub wait_timeout_ms: Option<u64>, // how long a skill waits for them, default 10000
+    pub tick_rate_hz: Option<f64>, // rate the `tick` entry of a skill is called at, see `ticks`
+    pub tick_budget_ms: Option<u64>, // how long a tick may take, one period by default
+    #[serde(default)]
+    pub profiles: Vec<String>, // loaded only in these profiles, in every profile if empty
+}
+
+impl LibraryConfig {
+    pub fn new(name: &str, path: Option<PathBuf>, attributes: Option<BlackboardEntries>) -> Self {
+        LibraryConfig {
+            name: name.to_string(),
+            instance: None,
+            path,
+            attributes,
+            restart: RestartPolicy::default(),
+            max_restarts: None,
+            backoff_ms: None,
+            start_timeout_ms: None,
+            stop_timeout_ms: None,
+            log_level: None,
+            isolation: Isolation::default(),
+            access: None,
+            limits: None,
+            wait_for: Vec::new(),
+            wait_timeout_ms: None,
+            tick_rate_hz: None,
+            tick_budget_ms: None,
+            profiles: Vec::new(),
+        }
+    }
+
+    /// Name of the component, the instance if given and the library otherwise.
+    pub fn component_name(&self) -> &str {
+        self.instance.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn restart_config(&self) -> RestartConfig {
+        let default = RestartConfig::default();
+        RestartConfig {
+            policy: self.restart,
+            max_restarts: self.max_restarts.unwrap_or(default.max_restarts),
+            backoff_ms: self.backoff_ms.unwrap_or(default.backoff_ms),
+        }
+    }
+
+    pub fn start_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.start_timeout_ms.unwrap_or(10000))
+    }
+
+    pub fn stop_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.stop_timeout_ms.unwrap_or(5000))
+    }
+
+    pub fn wait_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.wait_timeout_ms.unwrap_or(10000))
+    }
+
+    /// Tick settings of a skill with a positive `tick_rate_hz`, a period of at least
+    /// `MIN_TICK_PERIOD`.
+    pub fn tick(&self) -> Option<TickConfig> {
+        let rate = self.tick_rate_hz.filter(|rate| *rate > 0.0)?;
+        let period = std::time::Duration::try_from_secs_f64(1.0 / rate)
+            .ok()
+            .filter(|period| *period >= MIN_TICK_PERIOD)?;
+        Some(TickConfig {
+            period,
+            budget: self
+                .tick_budget_ms
+                .map_or(period, std::time::Duration::from_millis),
+        })
+    }
+
+    /// Whether the library is loaded with the `active` profiles.
+    pub fn in_profiles(&self, active: &[String]) -> bool {
+        self.profiles.is_empty() || self.profiles.iter().any(|profile| active.contains(profile))
+    }
+}
+
+/// Restart settings of a service, see `LibraryConfig`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartConfig {
+    pub policy: RestartPolicy,
+    pub max_restarts: u32,
+    pub backoff_ms: u64,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        RestartConfig {
+            policy: RestartPolicy::Never,
+            max_restarts: 3,
+            backoff_ms: 1000,
+        }
+    }
+}
+
+impl RestartConfig {
+    /// Delay before the restart following `restarts` earlier ones.
+    pub fn backoff(&self, restarts: u32) -> std::time::Duration {
+        std::time::Duration::from_millis(self.backoff_ms.saturating_mul(1 << restarts.min(16)))
+    }
 }
 
 pub type LibraryConfigs = Vec<LibraryConfig>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct RTConfig {
+    #[serde(default)]
+    pub include: Vec<PathBuf>, // base configs, relative to the including file
+    #[serde(default)]
     pub libraries: LibraryConfigs,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub plugin_dirs: Vec<PathBuf>, // every plugin found here is loaded as well
+    pub control_socket: Option<PathBuf>, // unix socket accepting commands, see `control`
+    pub trace_capabilities: Option<bool>, // counts the capability calls, see `audit`
+    pub strict: Option<bool>, // fails at startup on unresolved requirements
+    pub metrics_log_interval_s: Option<u64>, // logs the `metrics`, 60 by default, 0 never
+    #[serde(default)]
+    pub profiles: Vec<String>, // the profiles libraries may name, the first is the default
+    #[serde(skip)]
+    pub active_profiles: Vec<String>, // set by `select_profiles`
+}
+
+impl RTConfig {
+    /// Adds the settings of `other`, its libraries replace libraries of the same name and
+    /// profiles.
+    pub fn merge(&mut self, other: RTConfig) {
+        for library in other.libraries {
+            let name = library.component_name();
+            let existing = self
+                .libraries
+                .iter_mut()
+                .find(|l| l.component_name() == name && l.profiles == library.profiles);
+            match existing {
+                Some(existing) => *existing = library,
+                None => self.libraries.push(library),
+            }
+        }
+        for profile in other.profiles {
+            if !self.profiles.contains(&profile) {
+                self.profiles.push(profile);
+            }
+        }
+        for dir in other.plugin_dirs {
+            if !self.plugin_dirs.contains(&dir) {
+                self.plugin_dirs.push(dir);
+            }
+        }
+        if other.control_socket.is_some() {
+            self.control_socket = other.control_socket;
+        }
+        if other.trace_capabilities.is_some() {
+            self.trace_capabilities = other.trace_capabilities;
+        }
+        if other.strict.is_some() {
+            self.strict = other.strict;
+        }
+        if other.metrics_log_interval_s.is_some() {
+            self.metrics_log_interval_s = other.metrics_log_interval_s;
+        }
+    }
+
+    /// Keeps the libraries of the `active` profiles, the first profile of the config if none
+    /// are given, and sets `active_profiles`. Fails for profiles the config does not declare
+    /// and for libraries configured twice in the active profiles.
+    pub fn select_profiles(&mut self, active: &[String]) -> Result<(), String> {
+        let active = match active {
+            [] => self.profiles.iter().take(1).cloned().collect(),
+            active => active.to_vec(),
+        };
+        let declared = |profile: &String| self.profiles.contains(profile);
+        if let Some(profile) = active.iter().find(|profile| !declared(profile)) {
+            return Err(format!(
+                "Profile '{}' is not declared in the profiles of the config: [{}]",
+                profile,
+                self.profiles.join(", ")
+            ));
+        }
+        for library in &self.libraries {
+            if let Some(profile) = library.profiles.iter().find(|profile| !declared(profile)) {
+                return Err(format!(
+                    "Library '{}' names the undeclared profile '{}'",
+                    library.component_name(),
+                    profile
+                ));
+            }
+        }
+
+        self.libraries.retain(|library| library.in_profiles(&active));
+        let mut names = std::collections::HashSet::new();
+        if let Some(library) = self.libraries.iter().find(|l| !names.insert(l.component_name())) {
+            return Err(format!(
+                "Library '{}' is configured twice for the profiles [{}]",
+                library.component_name(),
+                active.join(", ")
+            ));
+        }
+        self.active_profiles = active;
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use super::components::{Components, ComponentsType};
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+
+const HELP: &str = "\
+list                  components and whether the services run
+start <service>       start a stopped service
+stop <service>        stop a service no running service requires
+restart <service>     restart a running service and the services requiring it
+dependents <service>  services requiring a service, restarted together with it
+reconfigure <library> hand the attributes of the config file to the library again
+bb get <key>          value of a blackboard key as {\"type\": ..., \"value\": ...}
+bb set <key> <value>  write a yaml value, e.g. 42, 1.5, true, hello or [1, 2]
+trace                 calls of the capabilities per component, with trace_capabilities: true
+clock                 time of the loader clock in milliseconds
+clock advance <ms>    move the clock forward, with run --sim-time
+help                  this text";
+
+/// Control socket of a running loader. Every line is a command, see `execute`. The reply ends
+/// with a line `ok` or `error: <reason>`, e.g. with `socat - UNIX-CONNECT:<path>`.
+pub async fn serve(
+    path: PathBuf,
+    config_path: PathBuf,
+    profiles: Vec<String>,
+    components: Arc<Mutex<Components>>,
+) -> Result<(), String> {
+    // left over by a loader that did not shut down
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)
+        .map_err(|e| format!("Can not bind control socket {}. Reason: {}", path.display(), e))?;
+    info!("Control socket listening on {}", path.display());
+
+    loop {
+        let (stream, _) = listener.accept().await.map_err(|e| e.to_string())?;
+        let components = components.clone();
+        let config_path = config_path.clone();
+        let profiles = profiles.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command_components = components.clone();
+                let command_config = config_path.clone();
+                let command_profiles = profiles.clone();
+                let reply = tokio::task::spawn_blocking(move || {
+                    execute(&command_components, &command_config, &command_profiles, &line)
+                })
+                .await
+                .unwrap_or_else(|e| Err(e.to_string()));
+                let reply = match reply {
+                    Ok(output) if output.is_empty() => "ok\n".to_string(),
+                    Ok(output) => format!("{}\nok\n", output),
+                    Err(e) => format!("error: {}\n", e.replace('\n', " ")),
+                };
+                if let Err(e) = writer.write_all(reply.as_bytes()).await {
+                    error!("Control connection failed: {}", e);
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Removes the socket file at shutdown.
+pub fn remove(path: &Path) {
+    let _ = std::fs::remove_file(path);
+}
+
+/// Runs one command of the control socket and returns its output. `config_path` is the config
+/// file `reconfigure` reads, for the `profiles` the loader runs with.
+pub fn execute(
+    components: &Mutex<Components>,
+    config_path: &Path,
+    profiles: &[String],
+    command: &str,
+) -> Result<String, String> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    match words.as_slice() {
+        [] => Ok(String::new()),
+        ["help"] => Ok(HELP.to_string()),
+        ["list"] => Ok(list(&components.lock().unwrap())),
+        ["start", name] => components.lock().unwrap().start_named(name).map(|_| String::new()),
+        ["stop" | "restart", "blackboard"] => Err("The loader uses the blackboard".to_string()),
+        ["stop", name] => components.lock().unwrap().stop_named(name).map(|_| String::new()),
+        ["restart", name] => components
+            .lock()
+            .unwrap()
+            .restart_named(name)
+            .map(|restarted| restarted.join("\n")),
+        ["dependents", name] => components
+            .lock()
+            .unwrap()
+            .dependents_named(name)
+            .map(|dependents| dependents.join("\n")),
+        ["reconfigure", name] => {
+            let mut components = components.lock().unwrap();
+            super::reconfigure_library(&mut components, config_path, profiles, name)
+                .map(|_| String::new())
+        }
+        ["bb", "get", key] => {
+            let client = super::create_blackboard_client(&components.lock().unwrap().inner)?;
+            let value = client.get_value(key)?;
+            serde_json::to_string(&value).map_err(|e| e.to_string())
+        }
+        ["bb", "set", key, ..] => {
+            // the value is the rest of the line, spaces included
+            let value = rest_after(command, &["bb", "set", key]);
+            if value.is_empty() {
+                return Err("bb set needs a value".to_string());
+            }
+            let value: BlackboardValue = serde_yml::from_str(value)
+                .map_err(|e| format!("Invalid value '{}': {}", value, e))?;
+            let client = super::create_blackboard_client(&components.lock().unwrap().inner)?;
+            let entry = BlackboardEntry {
+                key: key.to_string(),
+                value,
+            };
+            client.set_batch(&vec![entry]).map(|_| String::new())
+        }
+        ["trace"] if !super::audit::enabled() => {
+            Err("Capabilities are not traced, set trace_capabilities: true".to_string())
+        }
+        ["trace"] => Ok(super::audit::report()),
+        ["clock"] => Ok(super::clock::clock().now().as_millis().to_string()),
+        ["clock", "advance", millis] => {
+            let millis: u64 = millis
+                .parse()
+                .map_err(|_| format!("Invalid milliseconds '{}'", millis))?;
+            let now = super::clock::clock().advance(Duration::from_millis(millis))?;
+            Ok(now.as_millis().to_string())
+        }
+        _ => Err(format!("Unknown command '{}', try help", command.trim())),
+    }
+}
+
+fn list(components: &Components) -> String {
+    let lines: Vec<String> = components
+        .inner
+        .iter()
+        .map(|component| match component {
+            ComponentsType::Service(service) => format!(
+                "{} service {}",
+                service.library.summary.name,
+                if service.is_running() { "running" } else { "stopped" }
+            ),
+            ComponentsType::Skill(skill) => format!("{} skill", skill.library.summary.name),
+        })
+        .collect();
+    lines.join("\n")
+}
+
+// the line after the leading `words`
+fn rest_after<'a>(line: &'a str, words: &[&str]) -> &'a str {
+    words
+        .iter()
+        .fold(line.trim_start(), |rest, word| rest[word.len()..].trim_start())
+        .trim_end()
+}
@@ -0,0 +1,93 @@
+// Groups of capabilities in `requires`, like `datalogger.* >= 0.1`. Their capabilities are not
+// looked up at start but on their first lookup through the entry of the group in the table, so a
+// component uses the datalogger if it runs and works without otherwise. The components may be
+// locked while a skill runs, so the entry resolves from what the started services provide,
+// registered here when they start.
+use super::components::{parse_requirement, provided_caps};
+use super::rtlibrary::RTLibrary;
+use interfaces::bindings;
+use interfaces::capabilities::{Capabilities, Capability, Lease, GROUP_SIGNATURE};
+use interfaces::status::RtStatus;
+use log::{debug, warn};
+use semver::{Version, VersionReq};
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::sync::RwLock;
+
+// the capabilities of the started services by library name
+static PROVIDED: RwLock<BTreeMap<String, Capabilities>> = RwLock::new(BTreeMap::new());
+
+/// Offers the capabilities of a service that started to the groups.
+pub fn register(library: &RTLibrary) {
+    if library.summary.provides.is_none() {
+        return;
+    }
+    match provided_caps(library, library.name(), &VersionReq::STAR) {
+        Ok(caps) => {
+            PROVIDED.write().unwrap().insert(library.name().to_string(), caps);
+        }
+        Err(e) => warn!(
+            component = library.name();
+            "Capabilities of '{}' are not available to groups: {}", library.name(), e
+        ),
+    }
+}
+
+/// Withdraws the capabilities of a service that stops.
+pub fn unregister(name: &str) {
+    PROVIDED.write().unwrap().remove(name);
+}
+
+/// How many of the offered capabilities hold `lease`, they are withdrawn with its service.
+pub fn holders(lease: &Lease) -> usize {
+    PROVIDED
+        .read()
+        .unwrap()
+        .values()
+        .filter(|caps| caps.leases().iter().any(|held| held.same(lease)))
+        .count()
+}
+
+/// The entry of the group `require` in a capability table.
+pub fn entry(require: &str) -> Capability {
+    Capability::with_signature(require.trim(), resolve as *mut c_void, GROUP_SIGNATURE)
+}
+
+// the capability `name` of the group `require`, if its library runs in a matching version
+fn lookup(require: &str, name: &str) -> Result<Capability, RtStatus> {
+    let (library, version_req) = parse_requirement(require).map_err(|e| {
+        warn!("{}", e);
+        RtStatus::InvalidArgument
+    })?;
+    let provided = PROVIDED.read().unwrap();
+    let capability = provided
+        .get(library)
+        .and_then(|caps| caps.get(name))
+        .ok_or(RtStatus::KeyNotFound)?;
+    // capabilities of libraries are versioned, see `provided_caps`
+    match Version::parse(&capability.version()) {
+        Ok(version) if version_req.matches(&version) => Ok(capability),
+        _ => {
+            debug!(
+                capability = name;
+                "Capability '{}' {} does not satisfy '{}'", name, capability.version(), require
+            );
+            Err(RtStatus::ValueMismatch)
+        }
+    }
+}
+
+extern "C" fn resolve(group: *const c_char, name: *const c_char, capability: *mut c_void) -> c_int {
+    if group.is_null() || name.is_null() || capability.is_null() {
+        return RtStatus::NullArgument.code();
+    }
+    let group = unsafe { CStr::from_ptr(group) }.to_string_lossy();
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    match lookup(&group, &name) {
+        Ok(resolved) => {
+            unsafe { *(capability as *mut bindings::Capability) = *resolved.inner() };
+            RtStatus::Ok.code()
+        }
+        Err(status) => status.code(),
+    }
+}
@@ -1,4 +1,7 @@
 use libloading::Library;
+use log::error;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{env, env::consts::OS, path::PathBuf};
 
 pub fn plugin_dir() -> PathBuf {
@@ -23,6 +26,47 @@ pub fn create_library_name(pkg_name: &str) -> String {
     format!("{}{}.{}", lib_prefix, pkg_name, ext)
 }
 
+/// Opens a plugin. Plugins are never unloaded, their thread local destructors and threads
+/// would run in unmapped code otherwise.
+#[cfg(unix)]
+pub fn load_library(path: &PathBuf) -> Result<Library, String> {
+    let flags = libc::RTLD_LAZY | libc::RTLD_LOCAL | libc::RTLD_NODELETE;
+    unsafe {
+        libloading::os::unix::Library::open(Some(path), flags)
+            .map(Library::from)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(unix))]
 pub fn load_library(path: &PathBuf) -> Result<Library, String> {
     unsafe { Library::new(path).map_err(|e| e.to_string()) }
 }
+
+/// Copies a plugin to a new temporary file. Opening the original file again would return the
+/// library loaded already.
+pub fn copy_for_reload(path: &PathBuf) -> Result<PathBuf, String> {
+    static COPIES: AtomicUsize = AtomicUsize::new(0);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", path.display()))?;
+    let copy = env::temp_dir().join(format!(
+        "rtime-{}-{}-{}",
+        std::process::id(),
+        COPIES.fetch_add(1, Ordering::SeqCst),
+        file_name.to_string_lossy()
+    ));
+    std::fs::copy(path, &copy).map_err(|e| format!("Can not copy {}: {}", path.display(), e))?;
+    Ok(copy)
+}
+
+/// Runs `call`, the entry `function` of the plugin `name`, and turns a panic into an error
+/// instead of unwinding through the loader. A panic leaving the plugin itself aborts the
+/// process, plugins catch them with `interfaces::status::catch_panic`.
+pub fn guarded<T>(name: &str, function: &str, call: impl FnOnce() -> T) -> Result<T, String> {
+    std::panic::catch_unwind(AssertUnwindSafe(call)).map_err(|payload| {
+        let message = interfaces::status::panic_message(payload.as_ref());
+        error!("'{}' panicked in {}: {}", name, function, message);
+        format!("{} panicked: {}", function, message)
+    })
+}
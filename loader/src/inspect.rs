@@ -0,0 +1,139 @@
+use super::components::{Components, Resolution};
+use super::config::{LibraryConfigs, RTConfig};
+use super::helper::{create_library_name, load_library};
+use super::rtlibrary::{RTLibrary, RTLibrarySummary};
+use std::env::consts::DLL_EXTENSION;
+use std::path::{Path, PathBuf};
+
+/// Plugin files with their summary or why they can not be loaded.
+pub type PluginList = Vec<(PathBuf, Result<RTLibrarySummary, String>)>;
+
+/// Summaries of the plugins in `dir`.
+pub fn list_plugins(dir: &Path) -> Result<PluginList, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Can not read plugin directory {}. Reason: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == DLL_EXTENSION))
+        .collect();
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let summary = load_library(&path)
+                .and_then(|library| RTLibrary::new(library, None))
+                .map(|library| library.summary.clone());
+            (path, summary)
+        })
+        .collect())
+}
+
+/// Path of a plugin given by its name or path.
+pub fn plugin_path(plugin: &str, dir: &Path) -> PathBuf {
+    let path = PathBuf::from(plugin);
+    if path.exists() {
+        path
+    } else {
+        dir.join(create_library_name(plugin))
+    }
+}
+
+/// Version, type, requirements and capabilities of a plugin as readable text.
+pub fn describe(summary: &RTLibrarySummary) -> String {
+    let mut text = format!(
+        "{} {} ({:?}, capabilities ABI {})\n",
+        summary.name,
+        summary.version,
+        summary.library_type,
+        summary.capabilities_abi.unwrap_or(1)
+    );
+    text += "requires:\n";
+    for require in summary.requires.iter().flatten() {
+        text += &format!("  {}\n", require);
+    }
+    text += "provides:\n";
+    for capability in summary.provides.iter().flatten() {
+        text += &format!(
+            "  {} = {}: {} [{}]\n",
+            capability.capability,
+            capability.entry,
+            capability.signature.as_deref().unwrap_or("unchecked"),
+            capability.version.as_deref().unwrap_or(&summary.version)
+        );
+    }
+    text
+}
+
+/// Loads the libraries of `config` and resolves their requirements without starting anything.
+/// Returns the problems found.
+pub fn check(config: &RTConfig) -> Vec<String> {
+    let configs = match super::library_configs(config) {
+        Ok(configs) => configs,
+        Err(e) => return vec![e],
+    };
+    problems(&configs, &Components::new(super::load_libraries(&configs)))
+}
+
+/// The configured libraries `components` misses and the requirements it can not resolve.
+pub fn problems(configs: &LibraryConfigs, components: &Components) -> Vec<String> {
+    let mut problems: Vec<String> = configs
+        .iter()
+        .filter(|libconfig| !components.inner.iter().any(|c| c.name() == libconfig.name))
+        .map(|libconfig| format!("Library '{}' can not be loaded", libconfig.name))
+        .collect();
+    problems.extend(components.check_requires());
+    if let Err(e) = components.start_order() {
+        problems.push(e);
+    }
+    problems
+}
+
+/// Loads the libraries of `config` and resolves the requirements of every component without
+/// starting anything.
+pub fn resolve(config: &RTConfig) -> Result<Vec<Resolution>, String> {
+    let configs = super::library_configs(config)?;
+    Ok(Components::new(super::load_libraries(&configs)).resolve())
+}
+
+/// Resolutions as a table with one requirement per line.
+pub fn resolution_table(resolutions: &[Resolution]) -> String {
+    let rows: Vec<[String; 4]> = resolutions
+        .iter()
+        .map(|resolution| {
+            [
+                resolution.component.clone(),
+                resolution.require.clone(),
+                resolution.provider.clone().unwrap_or_else(|| "-".to_string()),
+                match &resolution.error {
+                    Some(error) => format!("MISSING: {}", error),
+                    None => resolution.capabilities.join(", "),
+                },
+            ]
+        })
+        .collect();
+    let header = ["COMPONENT", "REQUIRES", "PROVIDER", "CAPABILITIES"].map(str::to_string);
+    let mut widths = [0; 3];
+    for row in rows.iter().chain(std::iter::once(&header)) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut text = String::new();
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line = format!(
+            "{:w0$}  {:w1$}  {:w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+        text += line.trim_end();
+        text += "\n";
+    }
+    text
+}
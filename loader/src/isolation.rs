@@ -0,0 +1,887 @@
+// Runs skills configured with `isolation: process` in a child process, so a crashing skill
+// only ends the child and not the loader with the blackboard and the web server. The child is a
+// new loader process started as `rtime isolate <library>`, which inherits one end of a socket.
+// It gets proxies of its capabilities, which send every call over the socket to the loader.
+// The loader calls the real capability and sends back its result and the output buffers it
+// wrote.
+//
+// Only capabilities declaring one of the signatures of `proxies!` are proxied. The ones taking
+// callbacks, like `blackboard_subscribe`, are missing in the child.
+use super::components::{Component, EntryCall, Skill};
+use super::config::Limits;
+use super::helper::load_library;
+use super::rtlibrary::RTLibrary;
+use interfaces::capabilities::{Capabilities, Capability};
+use interfaces::signature::{self, Signature};
+use interfaces::status::RtStatus;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+// proxies per signature, further capabilities of the same signature are left out
+const SLOTS: usize = 32;
+// size of an output buffer passed without its length, e.g. the value of `blackboard_get_string`
+const UNSIZED_OUTPUT: usize = 1 << 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Arg {
+    Null, // a null pointer
+    Str(String),
+    Value(serde_json::Value),
+    Input(Vec<u8>),
+    Output(Option<usize>), // elements of the buffer, if the next argument tells
+}
+
+// a capability of the loader the child calls through the proxy of `slot`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Forwarded {
+    name: String,
+    signature: String,
+    slot: usize,
+    version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Message {
+    Start {
+        name: String,
+        attributes: String,
+        caps: Vec<Forwarded>,
+    },
+    Call {
+        signature: String,
+        slot: usize,
+        args: Vec<Arg>,
+    },
+    Return {
+        result: c_int,
+        outputs: Vec<(usize, Vec<u8>)>, // bytes written to the buffer of each output argument
+    },
+    Exit(c_int),
+}
+
+// an argument as the skill passed it to a proxy
+enum Raw {
+    Str(*const c_char),
+    Int(c_int), // also the length of a buffer before it
+    Value(serde_json::Value),
+    Output(*mut u8),
+    Input(*const u8, usize), // with the size of its elements
+}
+
+// an output buffer the loader passed to a capability
+struct Output {
+    index: usize,
+    buffer: *const u8,
+    bytes: usize,
+    element: usize,
+    sized: bool, // the capability got its length
+    text: bool,  // written up to its null terminator
+}
+
+// arguments of a capability called by the loader, alive until the call returned
+#[derive(Default)]
+struct Held {
+    index: usize,
+    strings: Vec<CString>,
+    buffers: Vec<Vec<u64>>, // u64 keeps every element type aligned
+    outputs: Vec<Output>,
+}
+
+impl Held {
+    fn next<T: Param>(&mut self, args: &mut std::slice::Iter<Arg>) -> Result<T, String> {
+        let arg = args.next().ok_or("Missing argument")?;
+        let native = T::native(arg, self);
+        self.index += 1;
+        native
+    }
+
+    fn string(&mut self, arg: &Arg) -> Result<*const c_char, String> {
+        match arg {
+            Arg::Null => Ok(std::ptr::null()),
+            Arg::Str(text) => {
+                let text = CString::new(text.as_str()).map_err(|e| e.to_string())?;
+                self.strings.push(text);
+                Ok(self.strings.last().unwrap().as_ptr())
+            }
+            arg => Err(format!("{:?} is no string", arg)),
+        }
+    }
+
+    fn buffer(&mut self, bytes: usize) -> *mut u8 {
+        self.buffers.push(vec![0; bytes.div_ceil(8).max(1)]);
+        self.buffers.last_mut().unwrap().as_mut_ptr() as *mut u8
+    }
+
+    fn input(&mut self, arg: &Arg) -> Result<*const u8, String> {
+        match arg {
+            Arg::Null => Ok(std::ptr::null()),
+            Arg::Input(bytes) => {
+                let buffer = self.buffer(bytes.len());
+                unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer, bytes.len()) };
+                Ok(buffer)
+            }
+            arg => Err(format!("{:?} is no input buffer", arg)),
+        }
+    }
+
+    fn output(&mut self, arg: &Arg, element: usize, text: bool) -> Result<*mut u8, String> {
+        match arg {
+            Arg::Null => Ok(std::ptr::null_mut()),
+            Arg::Output(elements) => {
+                let bytes = elements.map_or(UNSIZED_OUTPUT, |elements| elements * element);
+                let buffer = self.buffer(bytes);
+                self.outputs.push(Output {
+                    index: self.index,
+                    buffer,
+                    bytes,
+                    element,
+                    sized: elements.is_some(),
+                    text,
+                });
+                Ok(buffer)
+            }
+            arg => Err(format!("{:?} is no output buffer", arg)),
+        }
+    }
+
+    // the written part of every output buffer. Texts end at their null terminator, other
+    // buffers hold as many elements as the result tells, one without a length, none on errors.
+    // A truncated text is still passed on, with the size its capability reported.
+    fn written(&self, result: c_int) -> Vec<(usize, Vec<u8>)> {
+        let truncated = result == RtStatus::BufferTooSmall.code();
+        if result < 0 && !truncated {
+            return Vec::new();
+        }
+        self.outputs
+            .iter()
+            .map(|output| {
+                let buffer = unsafe { std::slice::from_raw_parts(output.buffer, output.bytes) };
+                let bytes = if output.text {
+                    buffer
+                        .iter()
+                        .position(|byte| *byte == 0)
+                        .map_or(output.bytes, |end| end + 1)
+                } else {
+                    let elements = match result {
+                        _ if truncated => !output.sized as usize,
+                        0 if output.sized => 0,
+                        0 => 1,
+                        result => result as usize,
+                    };
+                    (elements * output.element).min(output.bytes)
+                };
+                (output.index, buffer[..bytes].to_vec())
+            })
+            .collect()
+    }
+}
+
+// an argument type of the proxied signatures
+trait Param: Sized {
+    fn raw(self) -> Raw;
+    fn native(arg: &Arg, held: &mut Held) -> Result<Self, String>;
+}
+
+fn value(arg: &Arg) -> Result<&serde_json::Value, String> {
+    match arg {
+        Arg::Value(value) => Ok(value),
+        arg => Err(format!("{:?} is no value", arg)),
+    }
+}
+
+impl Param for c_int {
+    fn raw(self) -> Raw {
+        Raw::Int(self)
+    }
+
+    fn native(arg: &Arg, _: &mut Held) -> Result<Self, String> {
+        let value = value(arg)?;
+        value
+            .as_i64()
+            .map(|value| value as c_int)
+            .ok_or_else(|| format!("{} is no i32", value))
+    }
+}
+
+impl Param for bool {
+    fn raw(self) -> Raw {
+        Raw::Value(self.into())
+    }
+
+    fn native(arg: &Arg, _: &mut Held) -> Result<Self, String> {
+        let value = value(arg)?;
+        value
+            .as_bool()
+            .ok_or_else(|| format!("{} is no bool", value))
+    }
+}
+
+macro_rules! value_param {
+    ($($t:ty => $read:ident),* $(,)?) => {
+        $(
+            impl Param for $t {
+                fn raw(self) -> Raw {
+                    Raw::Value(serde_json::json!(self))
+                }
+
+                fn native(arg: &Arg, _: &mut Held) -> Result<Self, String> {
+                    let value = value(arg)?;
+                    value
+                        .$read()
+                        .map(|value| value as $t)
+                        .ok_or_else(|| format!("{} is no {}", value, stringify!($t)))
+                }
+            }
+        )*
+    };
+}
+
+value_param!(i64 => as_i64, u64 => as_u64, f32 => as_f64, f64 => as_f64);
+
+impl Param for *const c_char {
+    fn raw(self) -> Raw {
+        Raw::Str(self)
+    }
+
+    fn native(arg: &Arg, held: &mut Held) -> Result<Self, String> {
+        held.string(arg)
+    }
+}
+
+macro_rules! buffer_param {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl Param for *mut $t {
+                fn raw(self) -> Raw {
+                    Raw::Output(self as *mut u8)
+                }
+
+                fn native(arg: &Arg, held: &mut Held) -> Result<Self, String> {
+                    held.output(arg, std::mem::size_of::<$t>(), false).map(|buffer| buffer as *mut $t)
+                }
+            }
+
+            impl Param for *const $t {
+                fn raw(self) -> Raw {
+                    Raw::Input(self as *const u8, std::mem::size_of::<$t>())
+                }
+
+                fn native(arg: &Arg, held: &mut Held) -> Result<Self, String> {
+                    held.input(arg).map(|buffer| buffer as *const $t)
+                }
+            }
+        )*
+    };
+}
+
+buffer_param!(u8, c_int, i64, u64, f32, f64, bool);
+
+impl Param for *mut c_char {
+    fn raw(self) -> Raw {
+        Raw::Output(self as *mut u8)
+    }
+
+    fn native(arg: &Arg, held: &mut Held) -> Result<Self, String> {
+        held.output(arg, 1, true)
+            .map(|buffer| buffer as *mut c_char)
+    }
+}
+
+// result and written outputs of a capability called by the loader
+type Returned = (c_int, Vec<(usize, Vec<u8>)>);
+// calls a capability function with the arguments of a child
+type Call = fn(*mut c_void, &[Arg]) -> Result<Returned, String>;
+
+struct Proxy {
+    signature: String, // normalized
+    slots: [*mut c_void; SLOTS],
+    call: Call,
+}
+
+macro_rules! slots {
+    ($name:ident $types:tt) => {
+        slots!(@ $name $types 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25
+            26 27 28 29 30 31)
+    };
+    (@ $name:ident $types:tt $($slot:literal)*) => {
+        [$($name::<$slot> as extern "C" fn $types -> c_int as *mut c_void),*]
+    };
+}
+
+// a proxy function per signature and slot, forwarding to the capability of the slot
+macro_rules! proxies {
+    ($($name:ident($($arg:ident: $t:ty),*);)*) => {
+        $(
+            extern "C" fn $name<const SLOT: usize>($($arg: $t),*) -> c_int {
+                let signature = <unsafe extern "C" fn($($t),*) -> c_int as Signature>::signature();
+                forward(signature::normalize(&signature), SLOT, vec![$($arg.raw()),*])
+            }
+        )*
+
+        fn proxies() -> Vec<Proxy> {
+            vec![$(
+                Proxy {
+                    signature: signature::normalize(
+                        &<unsafe extern "C" fn($($t),*) -> c_int as Signature>::signature(),
+                    ),
+                    slots: slots!($name ($($t),*)),
+                    call: {
+                        #[allow(unused_mut, unused_variables)]
+                        fn call(function: *mut c_void, args: &[Arg]) -> Result<Returned, String> {
+                            let function = unsafe {
+                                std::mem::transmute::<*mut c_void, unsafe extern "C" fn($($t),*) -> c_int>(
+                                    function,
+                                )
+                            };
+                            let mut held = Held::default();
+                            let mut args = args.iter();
+                            $(let $arg = held.next::<$t>(&mut args)?;)*
+                            let result = unsafe { function($($arg),*) };
+                            Ok((result, held.written(result)))
+                        }
+                        call
+                    },
+                },
+            )*]
+        }
+    };
+}
+
+proxies! {
+    proxy_none();
+    proxy_str(a: *const c_char);
+    proxy_str_str(a: *const c_char, b: *const c_char);
+    proxy_str_str_str(a: *const c_char, b: *const c_char, c: *const c_char);
+    proxy_str_i32(a: *const c_char, b: c_int);
+    proxy_str_i32_i32(a: *const c_char, b: c_int, c: c_int);
+    proxy_str_i64(a: *const c_char, b: i64);
+    proxy_str_u64(a: *const c_char, b: u64);
+    proxy_str_f32(a: *const c_char, b: f32);
+    proxy_str_f64(a: *const c_char, b: f64);
+    proxy_str_bool(a: *const c_char, b: bool);
+    proxy_text(a: *mut c_char);
+    proxy_text_len(a: *mut c_char, b: c_int);
+    proxy_str_text(a: *const c_char, b: *mut c_char);
+    proxy_str_i32_text(a: *const c_char, b: c_int, c: *mut c_char);
+    proxy_str_text_len_out_i32(a: *const c_char, b: *mut c_char, c: c_int, d: *mut c_int);
+    proxy_str_out_i32(a: *const c_char, b: *mut c_int);
+    proxy_str_out_i64(a: *const c_char, b: *mut i64);
+    proxy_str_out_u64(a: *const c_char, b: *mut u64);
+    proxy_str_out_f32(a: *const c_char, b: *mut f32);
+    proxy_str_out_f64(a: *const c_char, b: *mut f64);
+    proxy_str_out_bool(a: *const c_char, b: *mut bool);
+    proxy_str_out_u8_len(a: *const c_char, b: *mut u8, c: c_int);
+    proxy_str_in_u8_len(a: *const c_char, b: *const u8, c: c_int);
+    proxy_str_in_i32_len(a: *const c_char, b: *const c_int, c: c_int);
+    proxy_str_in_f64_len(a: *const c_char, b: *const f64, c: c_int);
+    proxy_str_i32_str_str(a: *const c_char, b: c_int, c: *const c_char, d: *const c_char);
+}
+
+// environment variable with the file descriptor of the socket a child inherits from the loader
+const SOCKET: &str = "RTIME_ISOLATE_SOCKET";
+
+// the socket of a child to the loader, only set in the child
+static LOADER: Mutex<Option<(BufReader<UnixStream>, UnixStream)>> = Mutex::new(None);
+
+fn send(stream: &mut UnixStream, message: &Message) -> Result<(), String> {
+    let mut line = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    line.push(b'\n');
+    stream.write_all(&line).map_err(|e| e.to_string())
+}
+
+// None once the other side closed the socket, also before reading all that was sent
+fn receive(reader: &mut BufReader<UnixStream>) -> Result<Option<Message>, String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => return Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+        Ok(_) => {}
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+// called by the proxies in the child
+fn forward(signature: String, slot: usize, raws: Vec<Raw>) -> c_int {
+    let mut args = Vec::with_capacity(raws.len());
+    let mut outputs = Vec::new();
+    for (index, raw) in raws.iter().enumerate() {
+        // a buffer may be followed by its length in elements
+        let length = match raws.get(index + 1) {
+            Some(Raw::Int(length)) => Some((*length).max(0) as usize),
+            _ => None,
+        };
+        args.push(match raw {
+            Raw::Str(text) if text.is_null() => Arg::Null,
+            Raw::Str(text) => Arg::Str(unsafe { CStr::from_ptr(*text) }.to_string_lossy().into()),
+            Raw::Int(value) => Arg::Value((*value).into()),
+            Raw::Value(value) => Arg::Value(value.clone()),
+            Raw::Output(buffer) if buffer.is_null() => Arg::Null,
+            Raw::Output(buffer) => {
+                outputs.push((index, *buffer));
+                Arg::Output(length)
+            }
+            Raw::Input(buffer, _) if buffer.is_null() => Arg::Null,
+            Raw::Input(buffer, element) => Arg::Input(
+                unsafe { std::slice::from_raw_parts(*buffer, length.unwrap_or(1) * element) }
+                    .to_vec(),
+            ),
+        });
+    }
+
+    let mut loader = LOADER.lock().unwrap();
+    let Some((reader, writer)) = loader.as_mut() else {
+        return RtStatus::NotRunning.code();
+    };
+    let call = Message::Call {
+        signature,
+        slot,
+        args,
+    };
+    match send(writer, &call).and_then(|_| receive(reader)) {
+        Ok(Some(Message::Return {
+            result,
+            outputs: written,
+        })) => {
+            for (index, bytes) in written {
+                if let Some((_, buffer)) = outputs.iter().find(|(output, _)| *output == index) {
+                    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), *buffer, bytes.len()) };
+                }
+            }
+            result
+        }
+        _ => RtStatus::Error.code(),
+    }
+}
+
+/// Capabilities for a child process, see `proxy`.
+struct Proxied {
+    caps: Vec<Forwarded>,
+    functions: HashMap<(String, usize), (*mut c_void, Call)>,
+}
+
+// the proxies of `caps`, the capabilities they forward to by signature and slot
+fn proxy(caps: &Capabilities) -> Proxied {
+    let proxies = proxies();
+    let mut used: HashMap<String, usize> = HashMap::new();
+    let mut proxied = Proxied {
+        caps: Vec::new(),
+        functions: HashMap::new(),
+    };
+    for cap in caps.iter() {
+        let signature = cap.signature();
+        let Some(proxy) = proxies.iter().find(|proxy| proxy.signature == signature) else {
+            debug!(
+                "Capability '{}' of signature '{}' is not available in a child process",
+                cap.name(),
+                signature
+            );
+            continue;
+        };
+        let name = cap.name();
+        if proxied.caps.iter().any(|forwarded| forwarded.name == name) {
+            continue;
+        }
+        let slot = used.entry(signature.clone()).or_default();
+        if *slot == SLOTS {
+            warn!(
+                "Capability '{}' is not available in a child process, all proxies of '{}' are used",
+                name,
+                signature
+            );
+            continue;
+        }
+        proxied
+            .functions
+            .insert((signature.clone(), *slot), (cap.inner().function, proxy.call));
+        proxied.caps.push(Forwarded {
+            name,
+            signature,
+            slot: *slot,
+            version: cap.version(),
+        });
+        *slot += 1;
+    }
+    proxied
+}
+
+// the capabilities of a child, the proxies of the slots the loader forwards
+fn forwarded(caps: &[Forwarded]) -> Capabilities {
+    let proxies = proxies();
+    let mut forwarded = Capabilities::new();
+    for cap in caps {
+        let Some(proxy) = proxies.iter().find(|proxy| proxy.signature == cap.signature) else {
+            continue;
+        };
+        let Some(function) = proxy.slots.get(cap.slot) else {
+            continue;
+        };
+        let mut copy = Capability::with_signature(&cap.name, *function, &cap.signature);
+        copy.set_version(&cap.version);
+        let _ = forwarded.add(copy);
+    }
+    forwarded
+}
+
+/// Runs the entry `run` of `skill` in a child process with proxies of `caps`. Fails if the child
+/// ends without returning, e.g. because it crashed or exceeded the limits of the skill.
+pub fn run(skill: &Skill, caps: &Capabilities) -> Result<i32, String> {
+    let name = skill.library.name();
+    let path = skill
+        .library
+        .path
+        .as_ref()
+        .ok_or_else(|| format!("Skill '{}' has no file to run in a child process", name))?;
+    let loader = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut command = Command::new(loader);
+    command.arg("isolate").arg(path);
+    let proxied = proxy(caps);
+    run_command(command, name, skill.attributes(), &proxied, skill.library.limits)
+}
+
+// spawns `command`, a loader running `child`, and sends it the proxied caps and `attributes`
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn run_command(
+    mut command: Command,
+    name: &str,
+    attributes: &str,
+    proxied: &Proxied,
+    limits: Option<Limits>,
+) -> Result<i32, String> {
+    let (mut loader, child) =
+        UnixStream::pair().map_err(|e| format!("Can not connect to a child process: {}", e))?;
+    let fd = child.as_raw_fd();
+    command.env(SOCKET, fd.to_string()).stdin(Stdio::null());
+    // only the spawned child inherits its end of the socket, not other children spawned meanwhile
+    unsafe {
+        command.pre_exec(move || match libc::fcntl(fd, libc::F_SETFD, 0) {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+    let process = command
+        .spawn()
+        .map_err(|e| format!("Skill '{}' can not be started in a child process: {}", name, e))?;
+    drop(child);
+    let pid = process.id() as libc::pid_t;
+
+    let start = Message::Start {
+        name: name.to_string(),
+        attributes: attributes.to_string(),
+        caps: proxied.caps.clone(),
+    };
+    if let Err(e) = send(&mut loader, &start) {
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+        wait(pid);
+        return Err(format!("Skill '{}' can not be started: {}", name, e));
+    }
+    #[cfg(target_os = "linux")]
+    let watchdog = limits.map(|limits| super::limits::watch(name, pid, limits));
+    let served = serve(pid, loader, proxied);
+    #[cfg(target_os = "linux")]
+    if let Some(limit) = watchdog.and_then(|watchdog| watchdog.finish()) {
+        return Err(format!("Skill '{}' exceeded {} and was killed", name, limit));
+    }
+    served.map_err(|e| format!("Skill '{}' {}", name, e))
+}
+
+/// The child process of `run`, started as `rtime isolate <library>`: runs the entry `run` of
+/// `library` with the capabilities and attributes the loader sends and returns its result.
+pub fn isolate(library: &Path) -> Result<(), String> {
+    child(|name, attributes, caps| {
+        let mut rtlibrary = RTLibrary::new(load_library(&library.to_path_buf())?, None)?;
+        if rtlibrary.name() != name {
+            rtlibrary.rename(name);
+        }
+        rtlibrary.config_attr_str = Some(attributes.to_string());
+        let skill = Skill {
+            library: rtlibrary,
+            requires: Vec::new(),
+        };
+        skill.entry_call("run", caps)
+    })
+}
+
+// connects to the loader, prepares the `entry` with the forwarded capabilities and calls it
+fn child(
+    entry: impl FnOnce(&str, &str, &Capabilities) -> Result<EntryCall, String>,
+) -> Result<(), String> {
+    let fd: RawFd = std::env::var(SOCKET)
+        .ok()
+        .and_then(|fd| fd.parse().ok())
+        .ok_or_else(|| format!("{} names no socket to the loader", SOCKET))?;
+    let writer = unsafe { UnixStream::from_raw_fd(fd) };
+    let mut reader = writer
+        .try_clone()
+        .map(BufReader::new)
+        .map_err(|e| e.to_string())?;
+    let (name, attributes, caps) = match receive(&mut reader)? {
+        Some(Message::Start {
+            name,
+            attributes,
+            caps,
+        }) => (name, attributes, forwarded(&caps)),
+        message => return Err(format!("Expected the start of the skill, got {:?}", message)),
+    };
+    let call = entry(&name, &attributes, &caps)?;
+    *LOADER.lock().unwrap() = Some((reader, writer));
+    let result = call();
+    let mut loader = LOADER.lock().unwrap();
+    let (_, writer) = loader.as_mut().ok_or("The socket to the loader is closed")?;
+    send(writer, &Message::Exit(result))
+}
+
+// calls the capabilities for the child `pid` until it returns
+fn serve(pid: libc::pid_t, mut writer: UnixStream, proxied: &Proxied) -> Result<i32, String> {
+    let mut reader = writer
+        .try_clone()
+        .map(BufReader::new)
+        .map_err(|e| format!("can not be served: {}", e))?;
+    let returned = loop {
+        match receive(&mut reader) {
+            Ok(Some(Message::Call {
+                signature,
+                slot,
+                args,
+            })) => {
+                let reply = match proxied.functions.get(&(signature, slot)) {
+                    Some((function, call)) => call(*function, &args),
+                    None => Err(format!("called the unknown proxy {}", slot)),
+                };
+                let reply = reply.unwrap_or_else(|e| {
+                    warn!("Call of a child process failed: {}", e);
+                    (RtStatus::InvalidArgument.code(), Vec::new())
+                });
+                let (result, outputs) = reply;
+                if let Err(e) = send(&mut writer, &Message::Return { result, outputs }) {
+                    break Err(format!("can not be answered: {}", e));
+                }
+            }
+            Ok(Some(Message::Exit(result))) => break Ok(result),
+            Ok(Some(message)) => break Err(format!("sent the unexpected {:?}", message)),
+            Ok(None) => break Err(String::new()),
+            Err(e) => break Err(format!("sent an invalid message: {}", e)),
+        }
+    };
+    if returned.is_err() {
+        unsafe { libc::kill(pid, libc::SIGKILL) };
+    }
+    let ended = wait(pid);
+    returned.map_err(|e| match e.is_empty() {
+        true => ended,
+        false => e,
+    })
+}
+
+// how the child `pid` ended
+fn wait(pid: libc::pid_t) -> String {
+    let mut status = 0;
+    if unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+        return format!("ended unnoticed: {}", std::io::Error::last_os_error());
+    }
+    if libc::WIFSIGNALED(status) {
+        format!("was killed by signal {}", libc::WTERMSIG(status))
+    } else {
+        format!(
+            "exited with {} without returning",
+            libc::WEXITSTATUS(status)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn length(text: *const c_char, length: *mut c_int) -> c_int {
+        unsafe { *length = CStr::from_ptr(text).to_bytes().len() as c_int };
+        0
+    }
+
+    extern "C" fn greeting(buffer: *mut c_char, len: c_int) -> c_int {
+        unsafe { interfaces::status::copy_to_buffer("hello", buffer, len) }
+    }
+
+    fn caps() -> Capabilities {
+        let mut caps = Capabilities::new();
+        let length = length as *mut c_void;
+        let greeting = greeting as *mut c_void;
+        caps.add(Capability::with_signature(
+            "length",
+            length,
+            "i32(cstr,*mut i32)",
+        ))
+        .unwrap();
+        caps.add(Capability::with_signature(
+            "greeting",
+            greeting,
+            "i32(*mut char,i32)",
+        ))
+        .unwrap();
+        caps.add(Capability::with_signature(
+            "subscribe",
+            length,
+            "i32(cstr,cstr,*mut void,*mut void)",
+        ))
+        .unwrap();
+        caps
+    }
+
+    // the entries of the skills the tests run in a child, by their name
+    fn entry(name: &str, attributes: &str, caps: &Capabilities) -> EntryCall {
+        let caps = Capabilities::from_raw(caps.inner());
+        let attributes = attributes.len() as c_int;
+        match name {
+            "test" => Box::new(move || unsafe {
+                type Length = unsafe extern "C" fn(*const c_char, *mut c_int) -> c_int;
+                type Greeting = unsafe extern "C" fn(*mut c_char, c_int) -> c_int;
+                let length = caps.get("length").unwrap().get::<Length>().unwrap();
+                let greeting = caps.get("greeting").unwrap().get::<Greeting>().unwrap();
+                let mut n = 0;
+                length(c"rtime".as_ptr(), &mut n);
+                let mut buffer = [0 as c_char; 16];
+                let size = greeting(buffer.as_mut_ptr(), buffer.len() as c_int);
+                match CStr::from_ptr(buffer.as_ptr()).to_str() {
+                    Ok("hello") => n * 10 + size,
+                    _ => -1,
+                }
+            }),
+            "truncated" => Box::new(move || unsafe {
+                let text = caps.get("text").unwrap().get::<Text>().unwrap();
+                let (mut buffer, mut required) = ([0 as c_char; 4], 0);
+                let result = text(c"key".as_ptr(), buffer.as_mut_ptr(), 4, &mut required);
+                match CStr::from_ptr(buffer.as_ptr()).to_str() {
+                    Ok("hel") if result == RtStatus::BufferTooSmall.code() => required,
+                    _ => -1,
+                }
+            }),
+            "crashing" => Box::new(|| std::process::abort()),
+            "hungry" => Box::new(|| vec![1u8; 256 << 20].iter().map(|&b| b as i32).sum()),
+            "busy" => Box::new(|| loop {
+                std::hint::spin_loop();
+            }),
+            "attributes" => Box::new(move || attributes),
+            _ => Box::new(|| 7),
+        }
+    }
+
+    // the child of the tests, the test binary running only this test
+    #[test]
+    fn isolated() {
+        if std::env::var(SOCKET).is_ok() {
+            let result = child(|name, attributes, caps| Ok(entry(name, attributes, caps)));
+            std::process::exit(result.is_err() as i32);
+        }
+    }
+
+    fn run_test(
+        name: &str,
+        attributes: &str,
+        caps: &Capabilities,
+        limits: Option<Limits>,
+    ) -> Result<i32, String> {
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        command.args(["--exact", "isolation::tests::isolated", "--nocapture"]);
+        run_command(command, name, attributes, &proxy(caps), limits)
+    }
+
+    #[test_log::test]
+    fn test_run() {
+        // callbacks can not be proxied
+        assert_eq!(proxy(&caps()).caps.len(), 2);
+        assert_eq!(run_test("test", "", &caps(), None), Ok(56));
+        assert_eq!(run_test("attributes", "{speed: 2}", &caps(), None), Ok(10));
+    }
+
+    type Text = unsafe extern "C" fn(*const c_char, *mut c_char, c_int, *mut c_int) -> c_int;
+
+    extern "C" fn text(
+        _: *const c_char,
+        buffer: *mut c_char,
+        len: c_int,
+        required: *mut c_int,
+    ) -> c_int {
+        let size = unsafe { interfaces::status::copy_to_buffer("hello", buffer, len) };
+        unsafe { *required = size };
+        if size > len {
+            return RtStatus::BufferTooSmall.code();
+        }
+        0
+    }
+
+    #[test_log::test]
+    fn test_truncated_text() {
+        let mut caps = Capabilities::new();
+        let signature = "i32(cstr,*mut char,i32,*mut i32)";
+        caps.add(Capability::with_signature("text", text as *mut c_void, signature)).unwrap();
+        assert_eq!(run_test("truncated", "", &caps, None), Ok(6));
+    }
+
+    #[test_log::test]
+    fn test_crash() {
+        let result = run_test("crashing", "", &caps(), None);
+        assert_eq!(
+            result,
+            Err("Skill 'crashing' was killed by signal 6".to_string())
+        );
+    }
+
+    #[test_log::test]
+    fn test_child_without_skill() {
+        // runs no test, so the child never connects
+        let mut command = Command::new(std::env::current_exe().unwrap());
+        command.args(["--exact", "isolation::tests::none"]);
+        let result = run_command(command, "none", "", &proxy(&caps()), None);
+        assert_eq!(
+            result,
+            Err("Skill 'none' exited with 0 without returning".to_string())
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[serial_test::serial]
+    #[test_log::test]
+    fn test_limits() {
+        let memory = Limits {
+            memory_mb: Some(32),
+            cpu_percent: None,
+        };
+        let result = run_test("hungry", "", &caps(), Some(memory));
+        assert_eq!(
+            result,
+            Err("Skill 'hungry' exceeded its limit memory_mb of 32 and was killed".to_string())
+        );
+
+        let cpu = Limits {
+            memory_mb: None,
+            cpu_percent: Some(20),
+        };
+        let result = run_test("busy", "", &caps(), Some(cpu));
+        assert_eq!(
+            result,
+            Err("Skill 'busy' exceeded its limit cpu_percent of 20 and was killed".to_string())
+        );
+        let exceeded = crate::limits::take_exceeded();
+        let limits: Vec<_> = exceeded
+            .iter()
+            .map(|(name, event)| (name.as_str(), event["limit"].as_str().unwrap()))
+            .collect();
+        assert_eq!(limits, [("hungry", "memory_mb"), ("busy", "cpu_percent")]);
+
+        // within its limits a skill runs as before
+        assert_eq!(run_test("modest", "", &caps(), Some(memory)), Ok(7));
+    }
+}
@@ -0,0 +1,191 @@
+// Resource limits of skills running with `isolation: process`, configured as
+// `limits: {memory_mb: 256, cpu_percent: 50}`. A watchdog thread samples the child process
+// while the skill runs and kills it once it uses more memory than `memory_mb` or, averaged over
+// a second, more CPU than `cpu_percent` of one core. The skill then fails and the supervisor
+// reports the event in `limits/<name>` on the blackboard. The next run starts a fresh child.
+//
+// With cgroup v2 and a cgroup the loader may create children in, the child also gets a cgroup
+// with `memory.max`, so the kernel stops it before the watchdog notices. Otherwise the limits
+// rely on the watchdog alone, which reads the usage from `/proc`.
+use super::config::Limits;
+use log::{debug, warn};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const INTERVAL: Duration = Duration::from_millis(20);
+const CPU_WINDOW: Duration = Duration::from_secs(1);
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+// limits exceeded since the supervisor published them last, (component, event)
+static EXCEEDED: Mutex<Vec<(String, serde_json::Value)>> = Mutex::new(Vec::new());
+
+/// The limits exceeded since the last call, as json events per component.
+pub fn take_exceeded() -> Vec<(String, serde_json::Value)> {
+    std::mem::take(&mut *EXCEEDED.lock().unwrap())
+}
+
+/// Resident memory in bytes and used CPU time of process `pid`.
+pub fn usage(pid: libc::pid_t) -> Option<(u64, Duration)> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let rss_kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    Some((rss_kb * 1024, cpu_time(&stat)?))
+}
+
+/// User and system CPU time of a `/proc/<pid>/stat` or `/proc/<pid>/task/<tid>/stat`.
+pub fn cpu_time(stat: &str) -> Option<Duration> {
+    // the fields after the command, which may contain spaces, starting with the state
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let ticks: u64 = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    let per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+    Some(Duration::from_secs_f64(ticks as f64 / per_second as f64))
+}
+
+// a cgroup v2 of the child below the one of the loader, removed when dropped
+struct Cgroup(PathBuf);
+
+impl Cgroup {
+    fn create(name: &str, pid: libc::pid_t, memory: u64) -> Result<Self, String> {
+        let own = std::fs::read_to_string("/proc/self/cgroup").map_err(|e| e.to_string())?;
+        let own = own
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or("no cgroup v2")?;
+        let path = PathBuf::from(CGROUP_ROOT)
+            .join(own.trim_start_matches('/'))
+            .join(format!("rtime-{}-{}", name, pid));
+        std::fs::create_dir(&path).map_err(|e| e.to_string())?;
+        let cgroup = Cgroup(path);
+        cgroup.write("memory.max", &memory.to_string())?;
+        let _ = cgroup.write("memory.swap.max", "0");
+        cgroup.write("cgroup.procs", &pid.to_string())?;
+        Ok(cgroup)
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<(), String> {
+        std::fs::write(self.0.join(file), value).map_err(|e| format!("{}: {}", file, e))
+    }
+
+    // whether the kernel killed a process of the cgroup for lack of memory
+    fn oom_killed(&self) -> bool {
+        std::fs::read_to_string(self.0.join("memory.events")).is_ok_and(|events| {
+            events
+                .lines()
+                .any(|line| line.strip_prefix("oom_kill ").is_some_and(|count| count != "0"))
+        })
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir(&self.0) {
+            warn!("Can not remove cgroup {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Watches a child process running a skill, see `watch`.
+pub struct Watchdog {
+    name: String,
+    limits: Limits,
+    stopped: Arc<AtomicBool>,
+    thread: JoinHandle<Option<serde_json::Value>>,
+    cgroup: Option<Cgroup>,
+}
+
+/// Starts to watch the child `pid` running the skill `name` within `limits`.
+pub fn watch(name: &str, pid: libc::pid_t, limits: Limits) -> Watchdog {
+    let memory = limits.memory_mb.map(|memory_mb| memory_mb << 20);
+    let cgroup = memory.and_then(|memory| match Cgroup::create(name, pid, memory) {
+        Ok(cgroup) => Some(cgroup),
+        Err(e) => {
+            debug!("Skill '{}' runs without a cgroup: {}", name, e);
+            None
+        }
+    });
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let stopped = stopped.clone();
+        std::thread::spawn(move || {
+            let mut window = (Instant::now(), Duration::ZERO);
+            while !stopped.load(Ordering::SeqCst) {
+                let (rss, cpu) = usage(pid)?;
+                let mut exceeded = None;
+                if let Some(memory) = memory.filter(|memory| rss > *memory) {
+                    exceeded = Some(serde_json::json!({
+                        "limit": "memory_mb",
+                        "allowed": memory >> 20,
+                        "used": rss >> 20,
+                    }));
+                }
+                let elapsed = window.0.elapsed();
+                if elapsed >= CPU_WINDOW {
+                    let percent = (cpu - window.1).as_secs_f64() * 100.0 / elapsed.as_secs_f64();
+                    match limits.cpu_percent {
+                        Some(allowed) if percent > allowed as f64 && exceeded.is_none() => {
+                            exceeded = Some(serde_json::json!({
+                                "limit": "cpu_percent",
+                                "allowed": allowed,
+                                "used": percent.round() as u64,
+                            }))
+                        }
+                        _ => {}
+                    }
+                    window = (Instant::now(), cpu);
+                }
+                if exceeded.is_some() {
+                    unsafe { libc::kill(pid, libc::SIGKILL) };
+                    return exceeded;
+                }
+                std::thread::sleep(INTERVAL);
+            }
+            None
+        })
+    };
+    Watchdog {
+        name: name.to_string(),
+        limits,
+        stopped,
+        thread,
+        cgroup,
+    }
+}
+
+impl Watchdog {
+    /// Stops watching the child once it ended. Returns the limit it exceeded and reports it to
+    /// the supervisor.
+    pub fn finish(self) -> Option<String> {
+        self.stopped.store(true, Ordering::SeqCst);
+        let mut exceeded = self.thread.join().ok().flatten();
+        if exceeded.is_none() && self.cgroup.as_ref().is_some_and(Cgroup::oom_killed) {
+            exceeded = Some(serde_json::json!({
+                "limit": "memory_mb",
+                "allowed": self.limits.memory_mb,
+            }));
+        }
+        let mut exceeded = exceeded?;
+        let limit = format!(
+            "its limit {} of {}",
+            exceeded["limit"].as_str().unwrap_or_default(),
+            exceeded["allowed"]
+        );
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        exceeded["action"] = "killed".into();
+        exceeded["at_ms"] = (at.as_millis() as u64).into();
+        warn!("Skill '{}' exceeded {} and was killed", self.name, limit);
+        EXCEEDED.lock().unwrap().push((self.name, exceeded));
+        Some(limit)
+    }
+}
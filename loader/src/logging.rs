@@ -0,0 +1,133 @@
+use super::config::LibraryConfig;
+use interfaces::capabilities::Capability;
+use interfaces::logging::{LOG_WRITE_CAPABILITY, LOG_WRITE_SIGNATURE};
+use log::kv::{Error, Key, Value, VisitSource};
+use log::{Level, LevelFilter, Metadata, Record};
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// records of plugins keep their target as a field instead of a tag in the message
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// How the records are written to stderr, see `--log-format`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Lines of env_logger
+    #[default]
+    Text,
+    /// One json object per line with time, level, component, target, message and the fields of
+    /// the record, like `capability` and `key`
+    Json,
+}
+
+/// Sets up the one sink of the loader and all plugins. `RUST_LOG` overrides the `log_level` of
+/// the libraries, e.g. `RUST_LOG=webinterface=trace`.
+pub fn init(libraries: &[LibraryConfig], format: LogFormat) {
+    let mut builder = env_logger::Builder::new();
+    for library in libraries {
+        if let Some(level) = library.log_level {
+            builder.filter_module(&library.name, level);
+        }
+    }
+    builder.parse_default_env();
+    if format == LogFormat::Json {
+        JSON.store(true, Ordering::SeqCst);
+        builder.format(|buf, record| {
+            let time = buf.timestamp_millis().to_string();
+            writeln!(buf, "{}", json_record(record, &time))
+        });
+    }
+    builder.init();
+}
+
+// the fields of a record: its component, the key-values given to the log macros, e.g.
+// `warn!(key = "speed"; ...)`, and the target. The component is the crate of the target unless
+// a `component` field names it.
+pub(crate) fn json_record(record: &Record, time: &str) -> serde_json::Value {
+    let mut fields = Fields(serde_json::Map::new());
+    let _ = record.key_values().visit(&mut fields);
+    let mut fields = fields.0;
+    let target = record.target();
+    let component = target.split("::").next().unwrap_or(target);
+    fields.entry("component").or_insert(component.into());
+    fields.entry("target").or_insert(target.into());
+    fields.insert("time".to_string(), time.into());
+    fields.insert("level".to_string(), record.level().as_str().into());
+    fields.insert("message".to_string(), record.args().to_string().into());
+    serde_json::Value::Object(fields)
+}
+
+struct Fields(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), Error> {
+        let value = if let Some(value) = value.to_bool() {
+            value.into()
+        } else if let Some(value) = value.to_i64() {
+            value.into()
+        } else if let Some(value) = value.to_f64() {
+            value.into()
+        } else {
+            value.to_string().into()
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+/// `log_write` of every component, see `interfaces::logging`.
+pub fn log_write_capability() -> Capability {
+    Capability::with_signature(
+        LOG_WRITE_CAPABILITY,
+        log_write as *mut c_void,
+        LOG_WRITE_SIGNATURE,
+    )
+}
+
+// records are logged with the component as target, so they are filtered per component
+extern "C" fn log_write(
+    component: *const c_char,
+    level: c_int,
+    target: *const c_char,
+    message: *const c_char,
+) -> c_int {
+    if component.is_null() {
+        return 0;
+    }
+    let component = unsafe { CStr::from_ptr(component) }.to_string_lossy();
+    let level_enabled = |level: Level| {
+        log::logger().enabled(&Metadata::builder().level(level).target(&component).build())
+    };
+
+    let level = Level::iter().find(|l| *l as c_int == level);
+    if let (Some(level), false, false) = (level, target.is_null(), message.is_null()) {
+        if level_enabled(level) {
+            let target = unsafe { CStr::from_ptr(target) }.to_string_lossy();
+            let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+            // the crate of the plugin needs no tag, e.g. records of its dependencies do
+            let tag = if target == component
+                || target.starts_with(&format!("{}::", component))
+                || JSON.load(Ordering::SeqCst)
+            {
+                String::new()
+            } else {
+                format!("[{}] ", target)
+            };
+            let fields = [("component", &*component), ("target", &*target)];
+            log::logger().log(
+                &Record::builder()
+                    .level(level)
+                    .target(&component)
+                    .args(format_args!("{}{}", tag, message))
+                    .key_values(&fields)
+                    .build(),
+            );
+        }
+    }
+
+    Level::iter()
+        .filter(|level| level_enabled(*level))
+        .last()
+        .map_or(LevelFilter::Off, |level| level.to_level_filter()) as c_int
+}
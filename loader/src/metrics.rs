@@ -0,0 +1,229 @@
+// Metrics the loader keeps about itself and its components: CPU and memory of the process and
+// its threads, sampled by the supervisor every second, how long the services took to start, the
+// runs of the skills and the calls of the blackboard capabilities. They are served by the
+// `runtime_metrics` capability, e.g. to `/metrics` of the webinterface, and logged every
+// `metrics_log_interval_s`.
+//
+// The blackboard capabilities handed to components are intercepted to count their calls, see
+// `interfaces::intercept`, ones of an unsupported signature are not counted.
+use interfaces::capabilities::Capabilities;
+use interfaces::intercept::{self, Call, Interceptor};
+use interfaces::runtime::{Metrics, ProcessMetrics, SkillMetrics, ThreadMetrics};
+use log::debug;
+use std::collections::BTreeMap;
+use std::ffi::c_int;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// the owner of the counting shims, shared by all components
+const OWNER: &str = "metrics";
+
+static STARTS: Mutex<BTreeMap<String, f64>> = Mutex::new(BTreeMap::new());
+static SKILLS: Mutex<BTreeMap<String, SkillMetrics>> = Mutex::new(BTreeMap::new());
+static BLACKBOARD: Mutex<BTreeMap<String, Arc<Counter>>> = Mutex::new(BTreeMap::new());
+
+// the last sample, with the CPU times to tell the usage since
+struct Sample {
+    time: Instant,
+    process: ProcessMetrics,
+    threads: Vec<ThreadMetrics>,
+}
+
+static SAMPLE: Mutex<Option<Sample>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Counter {
+    calls: AtomicU64,
+}
+
+impl Interceptor for Counter {
+    fn after(&self, _call: &Call, _result: c_int, _elapsed: Duration) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records how long the `start` of service `name` took.
+pub fn record_start(name: &str, duration: Duration) {
+    STARTS.lock().unwrap().insert(name.to_string(), duration.as_secs_f64());
+}
+
+/// Runs skill `name` with `run`, recording its duration and whether it failed.
+pub fn timed_run(name: &str, run: impl FnOnce() -> Result<i32, String>) -> Result<i32, String> {
+    let started = Instant::now();
+    let result = run();
+    let seconds = started.elapsed().as_secs_f64();
+    let mut skills = SKILLS.lock().unwrap();
+    let skill = skills.entry(name.to_string()).or_default();
+    skill.runs += 1;
+    if !matches!(result, Ok(code) if code >= 0) {
+        skill.failures += 1;
+    }
+    skill.total_seconds += seconds;
+    skill.max_seconds = skill.max_seconds.max(seconds);
+    result
+}
+
+/// `caps` with the calls of the blackboard capabilities counted.
+pub fn counted(caps: Capabilities) -> Capabilities {
+    let mut counted = Capabilities::new();
+    for cap in caps.iter() {
+        let name = cap.name();
+        if !name.starts_with("blackboard_") || !intercept::supported(&cap.signature()) {
+            let _ = counted.add(cap);
+            continue;
+        }
+        let counter = BLACKBOARD.lock().unwrap().get(&name).cloned().unwrap_or_default();
+        match intercept::intercept(OWNER, &cap, counter.clone()) {
+            Ok(shim) => {
+                BLACKBOARD.lock().unwrap().insert(name, counter);
+                let _ = counted.add(shim);
+            }
+            Err(e) => {
+                debug!("Calls of '{}' are not counted: {}", name, e);
+                let _ = counted.add(cap);
+            }
+        }
+    }
+    counted.hold_leases(&caps);
+    counted
+}
+
+// CPU used between two samples in percent of one core
+fn percent(cpu: f64, before: Option<f64>, elapsed: Option<Duration>) -> f64 {
+    match (before, elapsed) {
+        (Some(before), Some(elapsed)) if !elapsed.is_zero() => {
+            (cpu - before).max(0.0) / elapsed.as_secs_f64() * 100.0
+        }
+        _ => 0.0,
+    }
+}
+
+/// Samples CPU and memory of the process and its threads from `/proc`.
+#[cfg(target_os = "linux")]
+pub fn sample() {
+    let time = Instant::now();
+    let Some((resident_bytes, cpu)) = super::limits::usage(std::process::id() as libc::pid_t)
+    else {
+        return;
+    };
+    let mut threads = Vec::new();
+    for task in std::fs::read_dir("/proc/self/task").into_iter().flatten().flatten() {
+        let Ok(id) = task.file_name().to_string_lossy().parse::<u32>() else {
+            continue;
+        };
+        let Ok(stat) = std::fs::read_to_string(task.path().join("stat")) else {
+            continue; // the thread ended meanwhile
+        };
+        let name = stat
+            .split_once('(')
+            .and_then(|(_, rest)| rest.rsplit_once(')'))
+            .map_or("", |(name, _)| name);
+        if let Some(cpu) = super::limits::cpu_time(&stat) {
+            threads.push(ThreadMetrics {
+                id,
+                name: name.to_string(),
+                cpu_percent: 0.0,
+                cpu_seconds: cpu.as_secs_f64(),
+            });
+        }
+    }
+
+    let mut sample = SAMPLE.lock().unwrap();
+    let elapsed = sample.as_ref().map(|before| time - before.time);
+    let before = |id: u32| {
+        let before = sample.as_ref()?.threads.iter().find(|thread| thread.id == id)?;
+        Some(before.cpu_seconds)
+    };
+    for thread in threads.iter_mut() {
+        thread.cpu_percent = percent(thread.cpu_seconds, before(thread.id), elapsed);
+    }
+    let cpu_seconds = cpu.as_secs_f64();
+    let process = ProcessMetrics {
+        cpu_percent: percent(
+            cpu_seconds,
+            sample.as_ref().map(|before| before.process.cpu_seconds),
+            elapsed,
+        ),
+        cpu_seconds,
+        resident_bytes,
+    };
+    *sample = Some(Sample {
+        time,
+        process,
+        threads,
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sample() {}
+
+/// The metrics as of the last sample.
+pub fn metrics() -> Metrics {
+    let (process, threads) = match &*SAMPLE.lock().unwrap() {
+        Some(sample) => (sample.process.clone(), sample.threads.clone()),
+        None => Default::default(),
+    };
+    Metrics {
+        process,
+        threads,
+        starts: STARTS.lock().unwrap().clone(),
+        skills: SKILLS.lock().unwrap().clone(),
+        blackboard: BLACKBOARD
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, counter)| (name.clone(), counter.calls.load(Ordering::Relaxed)))
+            .collect(),
+    }
+}
+
+/// The metrics as one line for the log, e.g.
+/// `cpu 2.5%, rss 48.2 MiB, 14 threads, 3 skill runs (1 failed), 1250 blackboard calls`.
+pub fn summary() -> String {
+    let metrics = metrics();
+    let skills = metrics.skills.values();
+    let runs: u64 = skills.clone().map(|skill| skill.runs).sum();
+    let failures: u64 = skills.map(|skill| skill.failures).sum();
+    format!(
+        "cpu {:.1}%, rss {:.1} MiB, {} threads, {} skill runs ({} failed), {} blackboard calls",
+        metrics.process.cpu_percent,
+        metrics.process.resident_bytes as f64 / (1024.0 * 1024.0),
+        metrics.threads.len(),
+        runs,
+        failures,
+        metrics.blackboard.values().sum::<u64>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        record_start("metrics_service", Duration::from_millis(1500));
+        assert_eq!(timed_run("metrics_skill", || Ok(0)), Ok(0));
+        assert_eq!(timed_run("metrics_skill", || Ok(-1)), Ok(-1));
+        assert!(timed_run("metrics_skill", || Err("failed".to_string())).is_err());
+
+        let metrics = metrics();
+        assert_eq!(metrics.starts["metrics_service"], 1.5);
+        let skill = &metrics.skills["metrics_skill"];
+        assert_eq!((skill.runs, skill.failures), (3, 2));
+        assert!(skill.max_seconds <= skill.total_seconds);
+
+        assert_eq!(percent(3.0, Some(2.0), Some(Duration::from_secs(2))), 50.0);
+        assert_eq!(percent(3.0, None, Some(Duration::from_secs(2))), 0.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sample() {
+        sample();
+        sample();
+        let metrics = metrics();
+        assert!(metrics.process.resident_bytes > 0);
+        assert!(metrics.threads.iter().any(|thread| thread.id == std::process::id()));
+    }
+}
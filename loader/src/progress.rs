@@ -0,0 +1,133 @@
+// Lifecycle progress of the services, published to the blackboard while they are started and
+// stopped, so a UI can show the boot sequence live and which service stalls it. Progress made
+// before the blackboard runs, like its own start, is published once it does.
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::status::RtError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// Prefix of the keys holding the progress of every service, e.g.
+/// `runtime/components/webinterface/state` holds `started`, the json
+/// `runtime/components/webinterface/progress` when it entered each stage and the last error.
+pub const PROGRESS_KEY_PREFIX: &str = "runtime/components/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Loaded,
+    Starting,
+    Started,
+    Stopping,
+    Stopped,
+    Failed, // to start or to stop, see `error`
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Stage::Loaded => "loaded",
+            Stage::Starting => "starting",
+            Stage::Started => "started",
+            Stage::Stopping => "stopping",
+            Stage::Stopped => "stopped",
+            Stage::Failed => "failed",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Progress of a service, e.g.
+/// `{"state": "failed", "since": 1714557600250, "times": {"loaded": ..., "starting": ...},
+/// "error": "start did not return within 10s"}` with milliseconds since the unix epoch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    pub state: Stage,
+    pub since: u64,
+    pub times: BTreeMap<Stage, u64>, // when each stage was entered last
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    published: bool,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        let since = now_ms();
+        Progress {
+            state: Stage::Loaded,
+            since,
+            times: BTreeMap::from([(Stage::Loaded, since)]),
+            error: None,
+            published: false,
+        }
+    }
+
+    /// Enters `stage`, entering the current stage again changes nothing. The error of a failure
+    /// is kept while starting or stopping again.
+    pub fn enter(&mut self, stage: Stage, error: Option<String>) {
+        if stage == self.state && error.is_none() {
+            return;
+        }
+        self.since = now_ms();
+        self.times.insert(stage, self.since);
+        self.error = match stage {
+            Stage::Starting | Stage::Stopping => error.or_else(|| self.error.take()),
+            _ => error,
+        };
+        self.state = stage;
+        self.published = false;
+    }
+
+    /// Writes the progress of service `name`, unless it did not change since.
+    pub fn publish(&mut self, client: &BlackboardClient, name: &str) -> Result<(), RtError> {
+        if self.published {
+            return Ok(());
+        }
+        let key = format!("{}{}", PROGRESS_KEY_PREFIX, name);
+        let progress = serde_json::to_value(&*self).map_err(|e| e.to_string())?;
+        client.set_value(&format!("{}/progress", key), &TypedBlackboardValue::Json(progress))?;
+        client.set_value(
+            &format!("{}/state", key),
+            &TypedBlackboardValue::String(self.state.to_string()),
+        )?;
+        self.published = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enter() {
+        let mut progress = Progress::new();
+        assert_eq!(progress.state, Stage::Loaded);
+        progress.enter(Stage::Starting, None);
+        progress.enter(Stage::Failed, Some("start returned -1".to_string()));
+        progress.enter(Stage::Starting, None);
+        let since = progress.since;
+        progress.enter(Stage::Starting, None);
+        assert_eq!(progress.since, since);
+        assert_eq!(progress.error.as_deref(), Some("start returned -1"));
+        progress.enter(Stage::Started, None);
+        assert_eq!(progress.error, None);
+        assert_eq!(progress.since, progress.times[&Stage::Started]);
+        assert!(progress.times[&Stage::Loaded] <= progress.times[&Stage::Started]);
+
+        let json = serde_json::to_value(&progress).unwrap();
+        assert_eq!(json["state"], "started");
+        assert_eq!(json["times"].as_object().unwrap().len(), 4);
+        assert!(json.get("error").is_none());
+        assert!(json.get("published").is_none());
+    }
+}
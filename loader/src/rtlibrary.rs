@@ -1,8 +1,17 @@
 use serde::{Deserialize, Serialize};
 
+use super::config::{Isolation, Limits, RestartConfig, TickConfig};
+use super::helper::guarded;
+use interfaces::bindings::rt_context;
 use interfaces::blackboard::BlackboardEntries;
+use interfaces::capabilities::Lease;
+use interfaces::context::OwnedContext;
 use libloading::{Library, Symbol};
-use std::ffi::CStr;
+use log::warn;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum RTLibraryType {
@@ -10,6 +19,16 @@ pub enum RTLibraryType {
     Skill,
 }
 
+impl RTLibraryType {
+    /// Entries a library of this type has to export besides `summary` and `abi_version`.
+    pub fn mandatory_entries(&self) -> &'static [&'static str] {
+        match self {
+            RTLibraryType::Service => &["start", "stop"],
+            RTLibraryType::Skill => &["run"],
+        }
+    }
+}
+
 impl Default for RTLibraryType {
     fn default() -> Self {
         RTLibraryType::Skill
@@ -20,20 +39,34 @@ impl Default for RTLibraryType {
 pub struct RTCapabilityInfo {
     pub capability: String,
     pub entry: String,
+    pub signature: Option<String>, // e.g. "i32(cstr,cstr)", missing means unchecked
+    pub version: Option<String>,   // semver, missing means the version of the library
 }
 
 impl RTCapabilityInfo {
-    pub fn new(capability: &str, entry: &str) -> Self {
+    pub fn new(
+        capability: &str,
+        entry: &str,
+        signature: &Option<String>,
+        version: &Option<String>,
+    ) -> Self {
         Self {
             capability: capability.to_string(),
             entry: entry.to_string(),
+            signature: signature.clone(),
+            version: version.clone(),
         }
     }
 }
 
 impl Clone for RTCapabilityInfo {
     fn clone(&self) -> Self {
-        return RTCapabilityInfo::new(&self.capability, &self.entry);
+        RTCapabilityInfo::new(
+            &self.capability,
+            &self.entry,
+            &self.signature,
+            &self.version,
+        )
     }
 }
 
@@ -44,6 +77,7 @@ pub struct RTLibrarySummary {
     pub version: String,
     pub provides: Option<Vec<RTCapabilityInfo>>,
     pub requires: Option<Vec<String>>,
+    pub capabilities_abi: Option<u32>, // layout of the capability table, missing means legacy
 }
 
 impl RTLibrarySummary {
@@ -53,6 +87,7 @@ impl RTLibrarySummary {
         version: &str,
         provides: &Option<Vec<RTCapabilityInfo>>,
         requires: &Option<Vec<String>>,
+        capabilities_abi: Option<u32>,
     ) -> Self {
         RTLibrarySummary {
             name: name.to_string(),
@@ -60,6 +95,7 @@ impl RTLibrarySummary {
             version: version.to_string(),
             provides: provides.clone(),
             requires: requires.clone(),
+            capabilities_abi,
         }
     }
 }
@@ -72,6 +108,7 @@ impl Clone for RTLibrarySummary {
             &self.version,
             &self.provides,
             &self.requires,
+            self.capabilities_abi,
         );
     }
 }
@@ -81,11 +118,52 @@ pub struct RTLibrary {
     pub library: Library,
     pub summary: RTLibrarySummary,
     pub config_attr_str: Option<String>,
+    pub path: Option<PathBuf>, // file the library was loaded from, needed to reload it
+    pub restart: RestartConfig,
+    pub start_timeout: Duration,
+    pub stop_timeout: Duration,
+    pub isolation: Isolation,
+    pub limits: Option<Limits>, // of the child process if isolated
+    pub wait_for: Vec<String>, // services and keys a skill waits for, see `runtime::wait_ready`
+    pub wait_timeout: Duration,
+    pub tick: Option<TickConfig>, // of a skill ticked by the loader, see `ticks`
+    pub instance_of: Option<String>, // name of the library this is an instance of, see `rename`
+    pub context: Arc<OwnedContext>, // passed to the entries if `takes_context`
+    pub lease: Lease, // held by the capabilities it provides, see `Components::reload`
+}
+
+/// Entry without arguments like `stop`, see `RTLibrary::entry`.
+pub type Entry = Box<dyn Fn() -> c_int + Send + Sync>;
+
+// the names of `entries` the library does not export
+fn missing_exports(library: &Library, entries: &[&str]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|entry| unsafe { library.get::<*const ()>(entry.as_bytes()) }.is_err())
+        .map(|entry| entry.to_string())
+        .collect()
 }
 
 impl RTLibrary {
+    /// Reads the summary of `library`. Fails if the library is built for another
+    /// `RT_ABI_VERSION` or misses one of the exports of its type, before any entry is called.
     pub fn new(library: Library, config: Option<BlackboardEntries>) -> Result<Self, String> {
+        let missing = missing_exports(&library, &["summary", "abi_version"]);
+        if !missing.is_empty() {
+            return Err(format!("Library misses the exports: {}", missing.join(", ")));
+        }
         unsafe {
+            let abi_version: Symbol<unsafe extern "C" fn() -> c_int> =
+                library.get(b"abi_version").map_err(|e| e.to_string())?;
+            let abi_version = abi_version();
+            if abi_version != interfaces::bindings::RT_ABI_VERSION as c_int {
+                return Err(format!(
+                    "Library is built for ABI version {}, the loader has version {}",
+                    abi_version,
+                    interfaces::bindings::RT_ABI_VERSION
+                ));
+            }
+
             let symbol: Symbol<unsafe extern "C" fn() -> *const ::std::os::raw::c_char> = library
                 .get(b"summary")
                 .map_err(|_e| "summary symbol not found".to_string())?;
@@ -105,22 +183,183 @@ impl RTLibrary {
                 )
             })?;
 
+            let missing = missing_exports(&library, summary.library_type.mandatory_entries());
+            if !missing.is_empty() {
+                return Err(format!(
+                    "Library '{}' misses the exports: {}",
+                    summary.name,
+                    missing.join(", ")
+                ));
+            }
+
             let config_attr_str = match config {
                 Some(config) => Some(serde_yml::to_string(&config).unwrap()), // Error handling should not be needed
                 None => None,
             };
 
             Ok(Self {
-                summary: summary,
-                config_attr_str: config_attr_str,
-                library: library,
+                config_attr_str,
+                library,
+                path: None,
+                restart: RestartConfig::default(),
+                start_timeout: Duration::from_secs(10),
+                stop_timeout: Duration::from_secs(5),
+                isolation: Isolation::None,
+                limits: None,
+                wait_for: Vec::new(),
+                wait_timeout: Duration::from_secs(10),
+                tick: None,
+                instance_of: None,
+                context: Arc::new(OwnedContext::new(&summary.name)),
+                lease: Lease::new(),
+                summary,
             })
         }
     }
 
+    /// Whether the library exports the optional `entry`, e.g. `tick`.
+    pub fn exports(&self, entry: &str) -> bool {
+        missing_exports(&self.library, &[entry]).is_empty()
+    }
+
     pub fn name(&self) -> &str {
         &self.summary.name
     }
+
+    /// Address of the `summary` symbol, it identifies the loaded library to the blackboard when
+    /// revoking the subscriptions left in it.
+    pub fn address(&self) -> Option<*const c_void> {
+        unsafe { self.library.get::<*const c_void>(b"summary") }.ok().map(|symbol| *symbol)
+    }
+
+    /// Makes the library the instance `instance` of itself: the component is named after the
+    /// instance and so are the capabilities named after the library, e.g. `serialport_write`
+    /// becomes `left_write`.
+    pub fn rename(&mut self, instance: &str) {
+        let prefix = format!("{}_", self.summary.name);
+        for capability in self.summary.provides.iter_mut().flatten() {
+            if let Some(rest) = capability.capability.strip_prefix(&prefix) {
+                capability.capability = format!("{}_{}", instance, rest);
+            }
+        }
+        let library = std::mem::replace(&mut self.summary.name, instance.to_string());
+        self.instance_of = Some(library);
+        self.context = Arc::new(OwnedContext::new(instance));
+    }
+
+    /// Whether the library is passed the legacy capability table.
+    pub fn is_legacy(&self) -> bool {
+        self.summary.capabilities_abi.unwrap_or(1) < interfaces::bindings::CAPABILITIES_ABI_TABLE
+    }
+
+    /// Whether the entries of the library take its `context` as their first argument.
+    pub fn takes_context(&self) -> bool {
+        self.summary.capabilities_abi.unwrap_or(1) >= interfaces::bindings::CAPABILITIES_ABI_CONTEXT
+    }
+
+    /// Entry `name` of type `i32()`, or `i32(ctx)` if the library takes a context, like `stop`.
+    pub fn entry(&self, name: &str) -> Result<Entry, libloading::Error> {
+        unsafe {
+            if self.takes_context() {
+                let entry = *self
+                    .library
+                    .get::<unsafe extern "C" fn(*mut rt_context) -> c_int>(name.as_bytes())?;
+                let context = self.context.clone();
+                Ok(Box::new(move || entry(context.as_ptr())))
+            } else {
+                let entry = *self.library.get::<unsafe extern "C" fn() -> c_int>(name.as_bytes())?;
+                Ok(Box::new(move || entry()))
+            }
+        }
+    }
+
+    /// Message of the last failed call into the library on this thread, if it exports
+    /// `get_last_error`.
+    pub fn last_error(&self) -> Option<String> {
+        self.read_buffer(b"get_last_error", false)
+    }
+
+    /// Status details of the optional `health_status` entry, a JSON object.
+    pub fn health_status(&self) -> Option<serde_json::Value> {
+        let status = self.read_buffer(b"health_status", self.takes_context())?;
+        serde_json::from_str(&status)
+            .map_err(|e| warn!("Invalid health status of '{}': {}", self.summary.name, e))
+            .ok()
+    }
+
+    /// Names of the skills a service hosts, listed as a JSON array by its optional `skills`
+    /// entry, like the script functions of the pyskill service.
+    pub fn hosted_skills(&self) -> Vec<String> {
+        let Some(skills) = self.read_buffer(b"skills", self.takes_context()) else {
+            return Vec::new();
+        };
+        serde_json::from_str(&skills)
+            .map_err(|e| warn!("Invalid skills of '{}': {}", self.summary.name, e))
+            .unwrap_or_default()
+    }
+
+    /// The call of the entry `run_skill` running the hosted skill `name`. It holds a lease on
+    /// the library, so the library is not reloaded while the call may run.
+    pub fn skill_call(&self, name: &str) -> Result<Entry, String> {
+        let cname = std::ffi::CString::new(name).map_err(|e| e.to_string())?;
+        let missing = |e: libloading::Error| format!("It has no entry 'run_skill': {}", e);
+        let lease = self.lease.clone();
+        unsafe {
+            if self.takes_context() {
+                type RunSkill = unsafe extern "C" fn(*mut rt_context, *const c_char) -> c_int;
+                let run_skill = *self.library.get::<RunSkill>(b"run_skill").map_err(missing)?;
+                let context = self.context.clone();
+                Ok(Box::new(move || {
+                    let _held = &lease;
+                    run_skill(context.as_ptr(), cname.as_ptr())
+                }))
+            } else {
+                type RunSkill = unsafe extern "C" fn(*const c_char) -> c_int;
+                let run_skill = *self.library.get::<RunSkill>(b"run_skill").map_err(missing)?;
+                Ok(Box::new(move || {
+                    let _held = &lease;
+                    run_skill(cname.as_ptr())
+                }))
+            }
+        }
+    }
+
+    // calls an entry `fn(buffer, len) -> size` like `get_last_error`, None for empty text. With
+    // `context` the entry takes the context first.
+    fn read_buffer(&self, entry: &[u8], context: bool) -> Option<String> {
+        let function = String::from_utf8_lossy(entry);
+        guarded(&self.summary.name, &function, || unsafe {
+            let read: Box<dyn Fn(*mut c_char, c_int) -> c_int> = if context {
+                type Read = unsafe extern "C" fn(*mut rt_context, *mut c_char, c_int) -> c_int;
+                let read: Symbol<Read> = self.library.get(entry).ok()?;
+                let (read, context) = (*read, self.context.as_ptr());
+                Box::new(move |buffer, len| read(context, buffer, len))
+            } else {
+                let read: Symbol<unsafe extern "C" fn(*mut c_char, c_int) -> c_int> =
+                    self.library.get(entry).ok()?;
+                let read = *read;
+                Box::new(move |buffer, len| read(buffer, len))
+            };
+            let size = read(std::ptr::null_mut(), 0);
+            if size <= 1 {
+                return None;
+            }
+            let mut buffer = vec![0u8; size as usize];
+            read(buffer.as_mut_ptr() as *mut c_char, size);
+            CStr::from_bytes_until_nul(&buffer)
+                .ok()
+                .map(|text| text.to_string_lossy().into_owned())
+        })
+        .ok()
+        .flatten()
+    }
+}
+
+// the plugin releases its state with its own code, before the library is unloaded
+impl Drop for RTLibrary {
+    fn drop(&mut self) {
+        self.context.release();
+    }
 }
 
 #[cfg(test)]
@@ -179,6 +418,25 @@ mod tests {
         assert_eq!(skill.summary.name, "blackboard");
     }
 
+    #[rstest]
+    #[serial]
+    #[test_log::test]
+    fn test_load_rtlibrary_abi(blackboard_plugin_path: PathBuf) {
+        let library = load_library(&blackboard_plugin_path).unwrap();
+        let abi_version = unsafe { library.get::<unsafe extern "C" fn() -> c_int>(b"abi_version") };
+        let abi_version = unsafe { abi_version.unwrap()() };
+        assert_eq!(abi_version, interfaces::bindings::RT_ABI_VERSION as c_int);
+        assert!(missing_exports(&library, RTLibraryType::Service.mandatory_entries()).is_empty());
+        assert_eq!(missing_exports(&library, &["run", "stop"]), vec!["run"]);
+
+        // a shared library which is no plugin is refused before anything is called
+        if cfg!(target_os = "linux") {
+            let library = load_library(&PathBuf::from("libm.so.6")).unwrap();
+            let error = RTLibrary::new(library, None).unwrap_err();
+            assert_eq!(error, "Library misses the exports: summary, abi_version");
+        }
+    }
+
     // #[rstest]
     // #[test_log::test]
     // fn test_create_capabilties(blackboard_plugin_path: PathBuf) {
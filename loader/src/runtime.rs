@@ -0,0 +1,314 @@
+use super::components::{in_lifecycle_call, provided_caps, Components, ComponentsType};
+use super::skill_runner::{find_skill, SkillRun};
+use interfaces::bindings;
+use interfaces::capabilities::Capability;
+use interfaces::lifecycle::PluginState;
+use interfaces::runtime::{
+    RUNTIME_CAPABILITY_CAPABILITY, RUNTIME_CAPABILITY_SIGNATURE, RUNTIME_COMPONENTS_CAPABILITY,
+    RUNTIME_COMPONENTS_SIGNATURE, RUNTIME_METRICS_CAPABILITY, RUNTIME_METRICS_SIGNATURE,
+    RUNTIME_READY_CAPABILITY, RUNTIME_READY_SIGNATURE, RUNTIME_RESTART_CAPABILITY,
+    RUNTIME_RESTART_SIGNATURE, RUNTIME_RUN_SKILL_CAPABILITY, RUNTIME_RUN_SKILL_SIGNATURE,
+    RUNTIME_STATUS_CAPABILITY, RUNTIME_STATUS_SIGNATURE,
+};
+use interfaces::status::{RtError, RtStatus};
+use log::{debug, error, info};
+use semver::VersionReq;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+
+// the components of the running loader, weak so they are dropped at shutdown
+static COMPONENTS: Mutex<Option<Weak<Mutex<Components>>>> = Mutex::new(None);
+// between two checks of the `wait_for` of a skill
+const READY_POLL: Duration = Duration::from_millis(50);
+
+/// Makes `components` available to the runtime capabilities. Without, they fail with
+/// `RT_NOT_RUNNING`.
+pub fn attach(components: &Arc<Mutex<Components>>) {
+    *COMPONENTS.lock().unwrap() = Some(Arc::downgrade(components));
+}
+
+pub fn detach() {
+    *COMPONENTS.lock().unwrap() = None;
+}
+
+/// `runtime_status`, `runtime_components`, `runtime_restart`, `runtime_run_skill`,
+/// `runtime_ready`, `runtime_metrics` and `runtime_capability` of every component, see
+/// `interfaces::runtime`.
+pub fn capabilities() -> Vec<Capability> {
+    vec![
+        Capability::with_signature(
+            RUNTIME_STATUS_CAPABILITY,
+            runtime_status as *mut c_void,
+            RUNTIME_STATUS_SIGNATURE,
+        ),
+        Capability::with_signature(
+            RUNTIME_COMPONENTS_CAPABILITY,
+            runtime_components as *mut c_void,
+            RUNTIME_COMPONENTS_SIGNATURE,
+        ),
+        Capability::with_signature(
+            RUNTIME_RESTART_CAPABILITY,
+            runtime_restart as *mut c_void,
+            RUNTIME_RESTART_SIGNATURE,
+        ),
+        Capability::with_signature(
+            RUNTIME_RUN_SKILL_CAPABILITY,
+            runtime_run_skill as *mut c_void,
+            RUNTIME_RUN_SKILL_SIGNATURE,
+        ),
+        Capability::with_signature(
+            RUNTIME_READY_CAPABILITY,
+            runtime_ready as *mut c_void,
+            RUNTIME_READY_SIGNATURE,
+        ),
+        Capability::with_signature(
+            RUNTIME_METRICS_CAPABILITY,
+            runtime_metrics as *mut c_void,
+            RUNTIME_METRICS_SIGNATURE,
+        ),
+        Capability::with_signature(
+            RUNTIME_CAPABILITY_CAPABILITY,
+            runtime_capability as *mut c_void,
+            RUNTIME_CAPABILITY_SIGNATURE,
+        ),
+    ]
+}
+
+fn attached() -> Result<Arc<Mutex<Components>>, RtStatus> {
+    COMPONENTS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(Weak::upgrade)
+        .ok_or(RtStatus::NotRunning)
+}
+
+// a service asking from its `start` or `stop` would wait for itself, see `in_lifecycle_call`
+fn lock(components: &Mutex<Components>) -> Result<MutexGuard<'_, Components>, RtStatus> {
+    if in_lifecycle_call() {
+        return Err(RtStatus::Timeout);
+    }
+    Ok(components.lock().unwrap())
+}
+
+// the entries of `wait_for` not met yet: services that are not started and keys without a value
+fn pending(components: &Components, wait_for: &[String]) -> Vec<String> {
+    // keys are only read from a loaded blackboard
+    let client = wait_for
+        .iter()
+        .any(|entry| components.service_state(entry).is_none())
+        .then(|| components.service_state("blackboard"))
+        .flatten()
+        .and_then(|_| super::create_blackboard_client(&components.inner).ok());
+    wait_for
+        .iter()
+        .filter(|entry| match components.service_state(entry) {
+            Some(state) => state != PluginState::Started,
+            None => client.as_ref().is_none_or(|client| client.get_value(entry).is_err()),
+        })
+        .cloned()
+        .collect()
+}
+
+/// Waits until the services in the `wait_for` of the skill `name` are started and its keys hold
+/// a value, for `timeout` or the `wait_timeout_ms` of the skill. The components are only locked
+/// while they are checked, so services start meanwhile.
+pub fn wait_ready(
+    components: &Mutex<Components>,
+    name: &str,
+    timeout: Option<Duration>,
+) -> Result<(), RtError> {
+    let started = Instant::now();
+    let mut deadline = timeout.map(|timeout| started + timeout);
+    loop {
+        let locked = lock(components)
+            .map_err(|status| RtError::new(status, "The loader is busy"))?;
+        let pending = match find_skill(&locked, name) {
+            Ok(skill) => {
+                deadline.get_or_insert(started + skill.library.wait_timeout);
+                pending(&locked, &skill.library.wait_for)
+            }
+            // skills of a service are run by the service, which is started already
+            Err(_) if locked.skill_host(name).is_some() => return Ok(()),
+            Err(e) => return Err(RtError::new(RtStatus::KeyNotFound, e)),
+        };
+        drop(locked);
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(RtError::new(
+                RtStatus::Timeout,
+                format!("Skill '{}' is still waiting for {}", name, pending.join(", ")),
+            ));
+        }
+        std::thread::sleep(READY_POLL);
+    }
+}
+
+// writes what `report` tells about the components as json, like `get_last_error`
+fn write_report(
+    buffer: *mut c_char,
+    len: c_int,
+    report: fn(&Components) -> serde_json::Value,
+) -> c_int {
+    let json = attached().and_then(|components| {
+        let components = lock(&components)?;
+        Ok(report(&components).to_string())
+    });
+    match json {
+        Ok(json) => unsafe { interfaces::status::copy_to_buffer(&json, buffer, len) },
+        Err(status) => status.code(),
+    }
+}
+
+extern "C" fn runtime_status(buffer: *mut c_char, len: c_int) -> c_int {
+    write_report(buffer, len, Components::states)
+}
+
+extern "C" fn runtime_components(buffer: *mut c_char, len: c_int) -> c_int {
+    write_report(buffer, len, Components::inventory)
+}
+
+// the metrics do not need the components, so they are written even while the loader is busy
+extern "C" fn runtime_metrics(buffer: *mut c_char, len: c_int) -> c_int {
+    let json = serde_json::to_string(&super::metrics::metrics()).unwrap_or_default();
+    unsafe { interfaces::status::copy_to_buffer(&json, buffer, len) }
+}
+
+extern "C" fn runtime_restart(name: *const c_char) -> c_int {
+    if name.is_null() {
+        return RtStatus::NullArgument.code();
+    }
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    if name == "blackboard" {
+        error!("The blackboard can not be restarted, the loader uses it");
+        return RtStatus::AccessDenied.code();
+    }
+    let checked = attached().and_then(|components| {
+        let locked = lock(&components)?;
+        match locked.service_running(&name) {
+            None => Err(RtStatus::KeyNotFound),
+            Some(false) => Err(RtStatus::NotRunning),
+            Some(true) => Ok(()),
+        }?;
+        drop(locked);
+        Ok(components)
+    });
+    let components = match checked {
+        Ok(components) => components,
+        Err(status) => return status.code(),
+    };
+
+    // the caller may be one of the restarted services, so it must not wait for the restart
+    std::thread::spawn(
+        move || match components.lock().unwrap().restart_named(&name) {
+            Ok(restarted) => info!("Restarted {:?}", restarted),
+            Err(e) => error!("{}", e),
+        },
+    );
+    RtStatus::Ok.code()
+}
+
+extern "C" fn runtime_run_skill(name: *const c_char) -> c_int {
+    if name.is_null() {
+        return RtStatus::NullArgument.code();
+    }
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    let components = match attached() {
+        Ok(components) => components,
+        Err(status) => return status.code(),
+    };
+    if let Err(e) = wait_ready(&components, &name, None) {
+        error!(component = name.as_str(); "Skill '{}' can not be run. Reason: {}", name, e);
+        return e.status.code();
+    }
+    let prepared = match lock(&components) {
+        Ok(locked) => SkillRun::prepare(&locked, &name),
+        Err(status) => return status.code(),
+    };
+    let run = match prepared {
+        Ok(run) => run,
+        Err(e) if e.status == RtStatus::KeyNotFound => return e.status.code(),
+        Err(e) => {
+            error!(component = name.as_str(); "Skill '{}' can not be run. Reason: {}", name, e);
+            return e.status.code();
+        }
+    };
+    // the caller waits for the skill, there is nothing to cancel it
+    match run.run(&|| false) {
+        Ok(result) => result,
+        Err(e) => {
+            error!(component = name.as_str(); "Skill '{}' can not be run. Reason: {}", name, e);
+            RtStatus::Error.code()
+        }
+    }
+}
+
+// the capability `name` of a running service or a loaded skill
+fn find_capability(components: &Components, name: &str) -> Result<Capability, RtStatus> {
+    let provides = |library: &super::rtlibrary::RTLibrary| {
+        let provides = library.summary.provides.as_deref().unwrap_or_default();
+        provides.iter().any(|capability| capability.capability == name)
+    };
+    let library = components
+        .inner
+        .iter()
+        .find_map(|component| match component {
+            ComponentsType::Service(service) if provides(&service.library) => {
+                Some(service.is_running().then_some(&service.library))
+            }
+            ComponentsType::Skill(skill) if provides(&skill.library) => Some(Some(&skill.library)),
+            _ => None,
+        })
+        .ok_or(RtStatus::KeyNotFound)?
+        .ok_or(RtStatus::NotRunning)?;
+    let caps = provided_caps(library, library.name(), &VersionReq::STAR).map_err(|e| {
+        error!(capability = name; "{}", e);
+        RtStatus::Error
+    })?;
+    // lifecycle entries are not provided to others
+    caps.get(name).ok_or(RtStatus::KeyNotFound)
+}
+
+extern "C" fn runtime_capability(name: *const c_char, capability: *mut c_void) -> c_int {
+    if name.is_null() || capability.is_null() {
+        return RtStatus::NullArgument.code();
+    }
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    let found = attached().and_then(|components| {
+        let components = lock(&components)?;
+        find_capability(&components, &name)
+    });
+    match found {
+        Ok(found) => {
+            unsafe { *(capability as *mut bindings::Capability) = *found.inner() };
+            RtStatus::Ok.code()
+        }
+        Err(status) => status.code(),
+    }
+}
+
+extern "C" fn runtime_ready(name: *const c_char, timeout_ms: c_int) -> c_int {
+    if name.is_null() {
+        return RtStatus::NullArgument.code();
+    }
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    let timeout = (timeout_ms >= 0).then(|| Duration::from_millis(timeout_ms as u64));
+    let components = match attached() {
+        Ok(components) => components,
+        Err(status) => return status.code(),
+    };
+    match wait_ready(&components, &name, timeout) {
+        Ok(()) => RtStatus::Ok.code(),
+        Err(e) => {
+            debug!(component = name.as_ref(); "{}", e);
+            e.status.code()
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// Boot-time self test of the loaded plugins, `rtime run --self-test`. A plugin may export
+// `self_test` with the arguments of `start`, returning `RT_OK` if it passes and an `RtStatus`
+// with its last error set if not. Each one runs in isolation on a synthetic blackboard, a fresh
+// instance started without the configured attributes, so it persists nothing and no test sees
+// the keys of another. Nothing else is started.
+use super::components::{create_caps, Components, ComponentsType};
+use super::helper::guarded;
+use interfaces::status::RtStatus;
+use log::{info, warn};
+
+const SELF_TEST_ENTRY: &str = "self_test";
+const BLACKBOARD: &str = "blackboard";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    Missing, // the plugin exports no `self_test`
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTest {
+    pub component: String,
+    pub outcome: Outcome,
+}
+
+/// Runs the self test of every component in the order of `components`.
+pub fn run(components: &Components) -> Vec<SelfTest> {
+    components
+        .inner
+        .iter()
+        .map(|component| SelfTest {
+            component: component.name().to_string(),
+            outcome: test(components, component),
+        })
+        .collect()
+}
+
+/// Whether any of `tests` failed.
+pub fn failed(tests: &[SelfTest]) -> bool {
+    tests
+        .iter()
+        .any(|test| matches!(test.outcome, Outcome::Failed(_)))
+}
+
+/// The outcome of `tests` as a table with one component per line.
+pub fn report(tests: &[SelfTest]) -> String {
+    let width = tests
+        .iter()
+        .map(|test| test.component.len())
+        .chain(std::iter::once("COMPONENT".len()))
+        .max()
+        .unwrap_or(0);
+    let mut text = format!("{:width$}  RESULT\n", "COMPONENT");
+    for test in tests {
+        let outcome = match &test.outcome {
+            Outcome::Passed => "passed".to_string(),
+            Outcome::Failed(reason) => format!("FAILED: {}", reason),
+            Outcome::Missing => "no self test".to_string(),
+        };
+        text += &format!("{:width$}  {}\n", test.component, outcome);
+    }
+    let passed = tests.iter().filter(|test| test.outcome == Outcome::Passed).count();
+    let failed = tests
+        .iter()
+        .filter(|test| matches!(test.outcome, Outcome::Failed(_)))
+        .count();
+    text += &format!("{} passed, {} failed\n", passed, failed);
+    text
+}
+
+fn test(components: &Components, component: &ComponentsType) -> Outcome {
+    let library = component.library();
+    if unsafe { library.library.get::<*const ()>(SELF_TEST_ENTRY.as_bytes()) }.is_err() {
+        return Outcome::Missing;
+    }
+    let blackboard = components
+        .inner
+        .iter()
+        .find(|component| component.name() == BLACKBOARD);
+    // the blackboard is the only component the test may call
+    let requires: Vec<String> = blackboard.iter().map(|_| BLACKBOARD.to_string()).collect();
+    let caps = match create_caps(&requires, &components.inner) {
+        Ok(caps) => caps,
+        Err(e) => return Outcome::Failed(e),
+    };
+    if let Some(blackboard) = blackboard {
+        if let Err(e) = start_synthetic(components, blackboard) {
+            return Outcome::Failed(format!("Synthetic blackboard can not be started: {}", e));
+        }
+    }
+
+    info!("Self test of '{}'", library.name());
+    let outcome = match component.component().run(SELF_TEST_ENTRY, &caps) {
+        Ok(result) if result >= 0 => Outcome::Passed,
+        Ok(result) => Outcome::Failed(format!(
+            "self_test returned {} ({}): {}",
+            result,
+            RtStatus::from_code(result),
+            library
+                .last_error()
+                .unwrap_or_else(|| "no error message".to_string())
+        )),
+        Err(e) => Outcome::Failed(e),
+    };
+    if let Some(blackboard) = blackboard {
+        stop_synthetic(blackboard);
+    }
+    outcome
+}
+
+// starts the blackboard without its attributes
+fn start_synthetic(components: &Components, blackboard: &ComponentsType) -> Result<(), String> {
+    let caps = create_caps(blackboard.requires(), &components.inner)?;
+    let start = blackboard.component().entry_call_with("start", &caps, "")?;
+    match guarded(BLACKBOARD, "start", start)? {
+        result if result < 0 => Err(format!(
+            "start returned {} ({})",
+            result,
+            RtStatus::from_code(result)
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn stop_synthetic(blackboard: &ComponentsType) {
+    let stopped = blackboard
+        .library()
+        .entry("stop")
+        .map_err(|e| e.to_string())
+        .and_then(|stop| guarded(BLACKBOARD, "stop", stop));
+    if let Err(e) = stopped {
+        warn!("Synthetic blackboard can not be stopped: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let tests = vec![
+            SelfTest {
+                component: "blackboard".to_string(),
+                outcome: Outcome::Passed,
+            },
+            SelfTest {
+                component: "arm".to_string(),
+                outcome: Outcome::Failed("no axis".to_string()),
+            },
+            SelfTest {
+                component: "webinterface".to_string(),
+                outcome: Outcome::Missing,
+            },
+        ];
+        assert!(failed(&tests));
+        assert!(!failed(&tests[..1]));
+        let report = report(&tests);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "COMPONENT     RESULT");
+        assert_eq!(lines[1], "blackboard    passed");
+        assert_eq!(lines[2], "arm           FAILED: no axis");
+        assert_eq!(lines[3], "webinterface  no self test");
+        assert_eq!(lines[4], "1 passed, 1 failed");
+    }
+}
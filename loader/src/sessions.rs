@@ -0,0 +1,135 @@
+// Blackboard sessions of the components. With access rules in the config the loader opens a
+// session for every component and binds it to the blackboard capabilities the component gets:
+// they are intercepted, see `interfaces::intercept`, entering the session around every call,
+// and the blackboard checks the calls against the rules of the component. No component gets the
+// capabilities to open, close or enter sessions, so none can act for another one.
+//
+// Blackboard capabilities of a signature `interfaces::intercept` does not support, like the
+// ones taking callbacks, are handed over as they are and called without a session.
+use interfaces::capabilities::Capabilities;
+use interfaces::intercept::{self, Call, Interceptor};
+use interfaces::status::RtStatus;
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_int, CString};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// the capabilities only the loader calls
+const SESSION_CAPS: [&str; 3] = [
+    "blackboard_open_session",
+    "blackboard_close_session",
+    "blackboard_enter_session",
+];
+
+type OpenSessionFn = unsafe extern "C" fn(*const c_char) -> c_int;
+type EnterSessionFn = unsafe extern "C" fn(c_int) -> c_int;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// the session token of every component, kept when the component gets its capabilities again
+static SESSIONS: Mutex<BTreeMap<String, c_int>> = Mutex::new(BTreeMap::new());
+
+// enters the session of a component around its calls
+struct Session {
+    enter: EnterSessionFn,
+    token: c_int,
+}
+
+impl Interceptor for Session {
+    fn before(&self, _call: &Call) -> Result<(), c_int> {
+        match unsafe { (self.enter)(self.token) } {
+            0 => Ok(()),
+            error => Err(error),
+        }
+    }
+
+    fn after(&self, _call: &Call, _result: c_int, _elapsed: Duration) {
+        unsafe { (self.enter)(0) };
+    }
+}
+
+/// Binds the sessions of the components to the capabilities they get from now on, for the
+/// access rules of the blackboard.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// The capabilities of component `name` without the ones of the sessions. With the
+/// capabilities of the `blackboard`, its blackboard calls are made in its session.
+pub fn bound(name: &str, caps: Capabilities, blackboard: Option<&Capabilities>) -> Capabilities {
+    let session = blackboard.and_then(|blackboard| session(name, blackboard));
+    let owner = format!("session/{}", name);
+    let mut bound = Capabilities::new();
+    for cap in caps.iter() {
+        let capability = cap.name();
+        if SESSION_CAPS.contains(&capability.as_str()) {
+            continue;
+        }
+        let Some(session) = session.clone().filter(|_| capability.starts_with("blackboard_"))
+        else {
+            let _ = bound.add(cap);
+            continue;
+        };
+        if !intercept::supported(&cap.signature()) {
+            debug!("'{}' of '{}' is called without its session", capability, name);
+            let _ = bound.add(cap);
+            continue;
+        }
+        match intercept::intercept(&owner, &cap, session) {
+            Ok(shim) => {
+                let _ = bound.add(shim);
+            }
+            Err(e) => {
+                warn!("'{}' of '{}' is called without its session: {}", capability, name, e);
+                let _ = bound.add(cap);
+            }
+        }
+    }
+    bound.hold_leases(&caps);
+    bound
+}
+
+// the session of component `name`, the one it had if the blackboard still knows it
+fn session(name: &str, blackboard: &Capabilities) -> Option<Arc<Session>> {
+    let open = blackboard
+        .get(SESSION_CAPS[0])
+        .and_then(|cap| unsafe { cap.get::<OpenSessionFn>() }.ok());
+    let enter = blackboard
+        .get(SESSION_CAPS[2])
+        .and_then(|cap| unsafe { cap.get::<EnterSessionFn>() }.ok());
+    let (Some(open), Some(enter)) = (open, enter) else {
+        warn!("No blackboard sessions, '{}' calls the blackboard without one", name);
+        return None;
+    };
+    let (open, enter) = (*open, *enter);
+    let mut sessions = SESSIONS.lock().unwrap();
+    let known = sessions
+        .get(name)
+        .copied()
+        .filter(|token| unsafe { enter(*token) } == 0);
+    unsafe { enter(0) };
+    let token = match known {
+        Some(token) => token,
+        None => {
+            let component = CString::new(name).ok()?;
+            let token = unsafe { open(component.as_ptr()) };
+            if token <= 0 {
+                warn!(
+                    "No blackboard session for '{}': {}",
+                    name,
+                    RtStatus::from_code(token)
+                );
+                return None;
+            }
+            sessions.insert(name.to_string(), token);
+            token
+        }
+    };
+    Some(Arc::new(Session { enter, token }))
+}
@@ -0,0 +1,154 @@
+use super::components::{component_caps, Component, Components, ComponentsType, Skill};
+use super::helper::guarded;
+use super::rtlibrary::Entry;
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::capabilities::Capabilities;
+use interfaces::status::{RtError, RtStatus};
+use log::{error, info};
+use std::sync::{Arc, Mutex};
+
+pub use interfaces::project::{
+    Project, PROJECT_ASSETS_KEY, PROJECT_ERROR_KEY, PROJECT_PROGRESS_KEY, PROJECT_RESULT_PREFIX,
+    PROJECT_STATUS_KEY, START_PROJECT_KEY, STOP_PROJECT_KEY,
+};
+
+/// Runs the skills of the project on the blackboard whenever `start_project` changes.
+pub struct SkillRunner {
+    components: Arc<Mutex<Components>>,
+}
+
+impl SkillRunner {
+    pub fn new(components: Arc<Mutex<Components>>) -> Self {
+        SkillRunner { components }
+    }
+
+    /// Runs the current project on a blocking task, its progress is published on the blackboard.
+    pub fn start(&self) -> tokio::task::JoinHandle<Result<(), String>> {
+        let components = self.components.clone();
+        tokio::task::spawn_blocking(move || run_project(&components))
+    }
+}
+
+/// Reads the project from the blackboard, runs its skills and publishes the status, progress
+/// and results.
+pub fn run_project(components: &Mutex<Components>) -> Result<(), String> {
+    let client = super::create_blackboard_client(&components.lock().unwrap().inner)?;
+
+    let result = run_skills(&client, components);
+    match &result {
+        Ok((project, status)) => {
+            info!("Project '{}' {}", project.name, status);
+            client.set_string(PROJECT_STATUS_KEY, status)?;
+        }
+        Err(e) => {
+            error!("Project failed: {}", e);
+            client.set_string(PROJECT_ERROR_KEY, e)?;
+            client.set_string(PROJECT_STATUS_KEY, "failed")?;
+        }
+    }
+    result.map(|_| ())
+}
+
+// the project and its final status, `finished` or `stopped`
+fn run_skills(
+    client: &BlackboardClient,
+    components: &Mutex<Components>,
+) -> Result<(Project, &'static str), String> {
+    let project = Project::parse(&client.get_string(START_PROJECT_KEY)?)?;
+    info!("Project '{}' started", project.name);
+    client.set_value(STOP_PROJECT_KEY, &TypedBlackboardValue::Bool(false))?;
+    client.set_string(PROJECT_STATUS_KEY, "running")?;
+    client.set_i32(PROJECT_PROGRESS_KEY, 0)?;
+    let assets = serde_json::to_value(&project.assets).map_err(|e| e.to_string())?;
+    client.set_value(PROJECT_ASSETS_KEY, &TypedBlackboardValue::Json(assets))?;
+
+    for (finished, name) in project.skills.iter().enumerate() {
+        if client.get_value(STOP_PROJECT_KEY)? == TypedBlackboardValue::Bool(true) {
+            return Ok((project, "stopped"));
+        }
+        match super::runtime::wait_ready(components, name, None) {
+            // a skill that is not loaded is reported below
+            Err(e) if e.status != RtStatus::KeyNotFound => {
+                return Err(format!("Skill '{}' can not be run. Reason: {}", name, e))
+            }
+            _ => {}
+        }
+        let run = match SkillRun::prepare(&components.lock().unwrap(), name) {
+            Ok(run) => run,
+            Err(e) if e.status == RtStatus::KeyNotFound => return Err(e.message),
+            Err(e) => {
+                return Err(format!("Skill '{}' can not be run. Reason: {}", name, e.message))
+            }
+        };
+        // a skill running asynchronously is cancelled once the project is stopped
+        let stopped =
+            || client.get_value(STOP_PROJECT_KEY) == Ok(TypedBlackboardValue::Bool(true));
+        let result = run
+            .run(&stopped)
+            .map_err(|e| format!("Skill '{}' can not be run. Reason: {}", name, e))?;
+
+        client.set_i32(&format!("{}{}", PROJECT_RESULT_PREFIX, name), result)?;
+        client.set_i32(PROJECT_PROGRESS_KEY, finished as i32 + 1)?;
+        if result < 0 {
+            return Err(format!(
+                "Skill '{}' returned {} ({})",
+                name,
+                result,
+                RtStatus::from_code(result)
+            ));
+        }
+    }
+    Ok((project, "finished"))
+}
+
+/// A run of a skill prepared while the components are locked and made once they are unlocked,
+/// so a long running skill does not stall the supervisor or the control socket. It holds the
+/// skill, or a lease on the service hosting it, which keeps either from being reloaded
+/// meanwhile, see `Components::reload`.
+pub enum SkillRun {
+    Loaded(Arc<Skill>, Capabilities),
+    Hosted(String, Entry), // the host and its `run_skill` call
+}
+
+impl SkillRun {
+    /// Fails with `RtStatus::KeyNotFound` if the skill `name` is neither loaded nor hosted by a
+    /// running service and with `RtStatus::NotRunning` if a service it requires is not started.
+    pub fn prepare(components: &Components, name: &str) -> Result<Self, RtError> {
+        let skill = match find_skill(components, name) {
+            Ok(skill) => skill,
+            Err(e) => {
+                let host = components
+                    .skill_host(name)
+                    .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, e))?;
+                let call = host.library.skill_call(name)?;
+                return Ok(SkillRun::Hosted(host.library.name().to_string(), call));
+            }
+        };
+        components
+            .require_started(skill.as_ref())
+            .map_err(|e| RtError::new(RtStatus::NotRunning, e))?;
+        let caps = component_caps(name, skill.requires(), &components.inner)?;
+        Ok(SkillRun::Loaded(skill.clone(), caps))
+    }
+
+    /// Runs the skill, cancelling it once `stopped` is true if it runs asynchronously. A skill
+    /// hosted by a service runs to its end.
+    pub fn run(self, stopped: &dyn Fn() -> bool) -> Result<i32, String> {
+        match self {
+            SkillRun::Loaded(skill, caps) => skill.run_cancellable(&caps, stopped),
+            SkillRun::Hosted(host, call) => guarded(&host, "run_skill", call),
+        }
+    }
+}
+
+pub fn find_skill<'a>(components: &'a Components, name: &str) -> Result<&'a Arc<Skill>, String> {
+    components
+        .inner
+        .iter()
+        .find_map(|component| match component {
+            ComponentsType::Skill(skill) if skill.library.name() == name => Some(skill),
+            _ => None,
+        })
+        .ok_or_else(|| format!("Skill '{}' is not loaded", name))
+}
@@ -0,0 +1,354 @@
+// Cooperative scheduler of the skills with a `tick_rate_hz`. Their `tick` entry, taking the same
+// arguments as `run`, is called on a thread of the loader at that rate, one skill after the
+// other in the order they fall due. Nothing interrupts a tick: one taking longer than the
+// `tick_budget_ms` of its skill counts as overrun and delays the others, and ticks missed
+// meanwhile are skipped instead of caught up. The schedule follows the clock of the loader, so
+// it stands still on simulated time until the clock is advanced.
+//
+// A tick returns 0 to be ticked again, 1 to end ticking and a negative `RtStatus` on failure.
+// After `MAX_FAILURES` failing ticks in a row a skill is not ticked anymore. The statistics of
+// every skill are published as json to `ticks/<name>` every second.
+use super::clock;
+use super::components::{component_caps, Component, Components};
+use super::config::TickConfig;
+use super::skill_runner::find_skill;
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::status::RtStatus;
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Prefix of the keys holding the statistics of the ticked skills, e.g. `ticks/control`.
+pub const TICKS_KEY_PREFIX: &str = "ticks/";
+// failing ticks in a row after which a skill is not ticked anymore
+const MAX_FAILURES: u32 = 10;
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+// how long the scheduler sleeps at most before checking whether it has to stop
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+/// Statistics of a ticked skill, the durations in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TickStats {
+    pub rate_hz: f64,
+    pub budget_ms: f64,
+    pub ticks: u64,
+    pub overruns: u64, // ticks taking longer than the budget
+    pub skipped: u64,  // ticks missed while the scheduler was late
+    pub failures: u64,
+    pub last_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+    pub active: bool, // false once the skill ended ticking or failed too often
+}
+
+struct Ticked {
+    name: String,
+    period: Duration,
+    budget: Duration,
+    next: Duration, // clock time the skill falls due
+    failures: u32,  // in a row
+    stats: TickStats,
+}
+
+impl Ticked {
+    fn new(name: &str, tick: TickConfig, now: Duration) -> Self {
+        Ticked {
+            name: name.to_string(),
+            period: tick.period,
+            budget: tick.budget,
+            next: now,
+            failures: 0,
+            stats: TickStats {
+                rate_hz: 1.0 / tick.period.as_secs_f64(),
+                budget_ms: tick.budget.as_secs_f64() * 1000.0,
+                active: true,
+                ..TickStats::default()
+            },
+        }
+    }
+
+    fn record(&mut self, result: Result<i32, String>, took: Duration) {
+        let stats = &mut self.stats;
+        let ms = took.as_secs_f64() * 1000.0;
+        stats.ticks += 1;
+        stats.last_ms = ms;
+        stats.mean_ms += (ms - stats.mean_ms) / stats.ticks as f64;
+        stats.max_ms = stats.max_ms.max(ms);
+        if took > self.budget {
+            stats.overruns += 1;
+            debug!("Tick of skill '{}' took {:.3} ms of {} ms", self.name, ms, stats.budget_ms);
+        }
+
+        let reason = match result {
+            Ok(1) => {
+                info!("Skill '{}' ended ticking", self.name);
+                stats.active = false;
+                return;
+            }
+            Ok(code) if code >= 0 => {
+                self.failures = 0;
+                return;
+            }
+            Ok(code) => format!("returned {} ({})", code, RtStatus::from_code(code)),
+            Err(e) => e,
+        };
+        stats.failures += 1;
+        self.failures += 1;
+        if self.failures >= MAX_FAILURES {
+            error!(
+                "Skill '{}' is not ticked anymore after {} failures in a row, the last {}",
+                self.name, self.failures, reason
+            );
+            stats.active = false;
+        } else if self.failures == 1 {
+            warn!("Tick of skill '{}' failed: {}", self.name, reason);
+        }
+    }
+
+    // the next tick after the one due at `now`, skipping the ones already missed
+    fn advance(&mut self, now: Duration) {
+        self.next += self.period;
+        if self.next <= now {
+            let missed = ((now - self.next).as_nanos() / self.period.as_nanos()) as u32 + 1;
+            self.next += self.period * missed;
+            self.stats.skipped += missed as u64;
+        }
+    }
+}
+
+pub struct Scheduler {
+    skills: Vec<Ticked>,
+}
+
+impl Scheduler {
+    /// Schedules the skills with a tick config, all due at `now` of the clock.
+    pub fn new(skills: Vec<(String, TickConfig)>, now: Duration) -> Self {
+        Scheduler {
+            skills: skills
+                .into_iter()
+                .map(|(name, tick)| Ticked::new(&name, tick, now))
+                .collect(),
+        }
+    }
+
+    /// Schedules the skills of `components` with a `tick_rate_hz`.
+    pub fn of(components: &Components, now: Duration) -> Self {
+        let skills = components
+            .inner
+            .iter()
+            .filter_map(|component| {
+                let library = component.library();
+                Some((library.name().to_string(), library.tick?))
+            })
+            .collect();
+        Self::new(skills, now)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.skills.is_empty()
+    }
+
+    /// When the next skill falls due, None once no skill is ticked anymore.
+    pub fn next_due(&self) -> Option<Duration> {
+        self.skills
+            .iter()
+            .filter(|skill| skill.stats.active)
+            .map(|skill| skill.next)
+            .min()
+    }
+
+    /// Ticks the skill due first if it is due at `now`, `tick` calls it and returns its result
+    /// and how long it took. Returns false if no skill is due.
+    pub fn tick_next<F>(&mut self, now: Duration, tick: F) -> bool
+    where
+        F: FnOnce(&str) -> (Result<i32, String>, Duration),
+    {
+        let due = self
+            .skills
+            .iter_mut()
+            .filter(|skill| skill.stats.active && skill.next <= now)
+            .min_by_key(|skill| skill.next);
+        let Some(skill) = due else {
+            return false;
+        };
+        let (result, took) = tick(&skill.name);
+        skill.record(result, took);
+        skill.advance(now);
+        true
+    }
+
+    pub fn stats(&self) -> Vec<(&str, &TickStats)> {
+        self.skills
+            .iter()
+            .map(|skill| (skill.name.as_str(), &skill.stats))
+            .collect()
+    }
+}
+
+// ticks skill `name`, prepared with the components locked like a `SkillRun`: the tick holds the
+// skill, which keeps it from being reloaded, but not the components. Waiting for them counts to
+// the duration of the tick, so a tick delayed by the lock is an overrun as well.
+fn tick(components: &Mutex<Components>, name: &str) -> (Result<i32, String>, Duration) {
+    let started = Instant::now();
+    let prepared = {
+        let components = components.lock().unwrap();
+        find_skill(&components, name).and_then(|skill| {
+            components.require_started(skill.as_ref())?;
+            Ok((skill.clone(), component_caps(name, skill.requires(), &components.inner)?))
+        })
+    };
+    let result = prepared.and_then(|(skill, caps)| Component::run(skill.as_ref(), "tick", &caps));
+    (result, started.elapsed())
+}
+
+fn publish(client: &BlackboardClient, scheduler: &Scheduler) {
+    for (name, stats) in scheduler.stats() {
+        let key = format!("{}{}", TICKS_KEY_PREFIX, name);
+        let result = serde_json::to_value(stats)
+            .map_err(|e| e.to_string())
+            .and_then(|stats| {
+                client
+                    .set_value(&key, &TypedBlackboardValue::Json(stats))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            error!(key = key.as_str(); "Failed to publish {}: {}", key, e);
+        }
+    }
+}
+
+/// Ticks the skills of `components` with a `tick_rate_hz` on a thread of their own until
+/// `stop` is set or no skill is ticked anymore. None if there is no such skill.
+pub fn spawn(components: Arc<Mutex<Components>>, stop: Arc<AtomicBool>) -> Option<JoinHandle<()>> {
+    let clock = clock::clock();
+    let mut scheduler = Scheduler::of(&components.lock().unwrap(), clock.now());
+    if scheduler.is_empty() {
+        return None;
+    }
+    let names: Vec<&str> = scheduler.stats().into_iter().map(|(name, _)| name).collect();
+    info!("Ticking skills: {}", names.join(", "));
+
+    let thread = std::thread::Builder::new()
+        .name("ticks".to_string())
+        .spawn(move || {
+            let client = super::create_blackboard_client(&components.lock().unwrap().inner)
+                .map_err(|e| warn!("Tick statistics are not published: {}", e))
+                .ok();
+            let mut published = clock.now();
+            while !stop.load(Ordering::SeqCst) {
+                let now = clock.now();
+                if !scheduler.tick_next(now, |name| tick(&components, name)) {
+                    let Some(due) = scheduler.next_due() else {
+                        break;
+                    };
+                    clock.sleep_until_within(due, STOP_POLL);
+                }
+                match client.as_ref() {
+                    Some(client) if now >= published + PUBLISH_INTERVAL => {
+                        publish(client, &scheduler);
+                        published = now;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(client) = client.as_ref() {
+                publish(client, &scheduler);
+            }
+            debug!("Tick scheduler stopped");
+        });
+    match thread {
+        Ok(thread) => Some(thread),
+        Err(e) => {
+            error!("Failed to spawn the tick scheduler: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn config(period_ms: u64, budget_ms: u64) -> TickConfig {
+        TickConfig {
+            period: MS * period_ms as u32,
+            budget: MS * budget_ms as u32,
+        }
+    }
+
+    // the name of the skill ticked at `now`, if any, taking `took`
+    fn tick_at(scheduler: &mut Scheduler, now: Duration, code: i32, took: Duration) -> String {
+        let mut ticked = String::new();
+        scheduler.tick_next(now, |name| {
+            ticked = name.to_string();
+            (Ok(code), took)
+        });
+        ticked
+    }
+
+    #[test]
+    fn test_schedule() {
+        let skills = vec![
+            ("fast".to_string(), config(10, 2)),
+            ("slow".to_string(), config(50, 50)),
+        ];
+        let mut scheduler = Scheduler::new(skills, Duration::ZERO);
+        assert_eq!(scheduler.next_due(), Some(Duration::ZERO));
+        assert_eq!(tick_at(&mut scheduler, Duration::ZERO, 0, MS), "fast");
+        assert_eq!(tick_at(&mut scheduler, Duration::ZERO, 0, MS), "slow");
+        assert_eq!(tick_at(&mut scheduler, Duration::ZERO, 0, MS), "");
+        assert_eq!(scheduler.next_due(), Some(MS * 10));
+
+        // an overrun delays the others, their missed ticks are skipped
+        assert_eq!(tick_at(&mut scheduler, MS * 10, 0, MS * 35), "fast");
+        assert_eq!(tick_at(&mut scheduler, MS * 45, 0, MS), "fast");
+        assert_eq!(scheduler.next_due(), Some(MS * 50));
+        let stats = scheduler.stats();
+        assert_eq!(stats[0].1.ticks, 3);
+        assert_eq!(stats[0].1.overruns, 1);
+        assert_eq!(stats[0].1.skipped, 2);
+        assert_eq!(stats[0].1.max_ms, 35.0);
+        assert_eq!(stats[0].1.last_ms, 1.0);
+        assert_eq!(stats[0].1.rate_hz, 100.0);
+        assert_eq!(stats[1].1.overruns, 0);
+
+        // skills due at the same time are ticked in the order of the config
+        assert_eq!(tick_at(&mut scheduler, MS * 60, 0, MS), "fast");
+        assert_eq!(tick_at(&mut scheduler, MS * 60, 0, MS), "slow");
+    }
+
+    #[test]
+    fn test_end_and_failures() {
+        let skills = vec![
+            ("done".to_string(), config(10, 10)),
+            ("broken".to_string(), config(10, 10)),
+        ];
+        let mut scheduler = Scheduler::new(skills, Duration::ZERO);
+        assert_eq!(tick_at(&mut scheduler, Duration::ZERO, 1, MS), "done");
+        assert!(!scheduler.stats()[0].1.active);
+
+        for tick in 0..MAX_FAILURES {
+            let now = MS * 10 * tick;
+            let code = RtStatus::Error.code();
+            assert_eq!(tick_at(&mut scheduler, now, code, MS), "broken");
+        }
+        let stats = scheduler.stats();
+        assert_eq!(stats[1].1.failures, MAX_FAILURES as u64);
+        assert!(!stats[1].1.active);
+        assert_eq!(scheduler.next_due(), None);
+
+        // a success in between starts counting over
+        let mut scheduler = Scheduler::new(vec![("flaky".to_string(), config(10, 10))], MS);
+        for tick in 1..=MAX_FAILURES * 2 {
+            let code = if tick % 2 == 0 { 0 } else { RtStatus::Error.code() };
+            assert_eq!(tick_at(&mut scheduler, MS * 10 * tick, code, MS), "flaky");
+        }
+        assert!(scheduler.stats()[0].1.active);
+    }
+}
@@ -0,0 +1,262 @@
+use super::config::{RTConfig, MIN_TICK_PERIOD};
+use std::collections::HashSet;
+use std::fmt;
+
+/// Problem found in a config file, `line` and `column` start at 1.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// JSON Schema of the config file, e.g. for editors. `validate` checks the same rules.
+pub fn config_schema() -> serde_json::Value {
+    serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "rtime config",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "include": {"type": "array", "items": {"type": "string"}},
+            "plugin_dirs": {"type": "array", "items": {"type": "string"}},
+            "control_socket": {"type": "string"},
+            "trace_capabilities": {"type": "boolean"},
+            "strict": {"type": "boolean"},
+            "metrics_log_interval_s": {"type": "integer", "minimum": 0},
+            "profiles": {"type": "array", "items": {"type": "string"}},
+            "libraries": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["name"],
+                    "additionalProperties": false,
+                    "properties": {
+                        "name": {"type": "string", "pattern": "^[A-Za-z0-9_-]+$"},
+                        "instance": {"type": "string", "pattern": "^[A-Za-z0-9_-]+$"},
+                        "path": {"type": "string"},
+                        "attributes": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["key", "value"],
+                                "properties": {
+                                    "key": {"type": "string", "minLength": 1},
+                                    "value": {}
+                                }
+                            }
+                        },
+                        "restart": {"enum": ["always", "on-failure", "never"]},
+                        "max_restarts": {"type": "integer", "minimum": 0},
+                        "backoff_ms": {"type": "integer", "minimum": 0},
+                        "start_timeout_ms": {"type": "integer", "minimum": 0},
+                        "stop_timeout_ms": {"type": "integer", "minimum": 0},
+                        "log_level": {"enum": ["off", "error", "warn", "info", "debug", "trace"]},
+                        "isolation": {"enum": ["none", "process"]},
+                        "limits": {
+                            "type": "object",
+                            "additionalProperties": false,
+                            "properties": {
+                                "memory_mb": {"type": "integer", "minimum": 1},
+                                "cpu_percent": {"type": "integer", "minimum": 1}
+                            }
+                        },
+                        "wait_for": {"type": "array", "items": {"type": "string"}},
+                        "wait_timeout_ms": {"type": "integer", "minimum": 0},
+                        "tick_rate_hz": {"type": "number", "exclusiveMinimum": 0},
+                        "tick_budget_ms": {"type": "integer", "minimum": 1},
+                        "profiles": {"type": "array", "items": {"type": "string"}},
+                        "access": {
+                            "type": "object",
+                            "additionalProperties": false,
+                            "properties": {
+                                "read": {"type": "array", "items": {"type": "string"}},
+                                "write": {"type": "array", "items": {"type": "string"}}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Replaces `${NAME}` with the value of `lookup(NAME)` and `${NAME:-default}` with the
+/// default if there is none. `$${` stays a literal `${`.
+pub fn expand_env<F>(text: &str, lookup: F) -> Result<String, Vec<Diagnostic>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(text.len());
+    let mut diagnostics = Vec::new();
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let mut rest = line;
+        while let Some(start) = rest.find("${") {
+            if rest[..start].ends_with('$') {
+                expanded.push_str(&rest[..start - 1]);
+                expanded.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            expanded.push_str(&rest[..start]);
+            let column = line.len() - rest.len() + start + 1;
+            let Some(end) = rest[start..].find('}') else {
+                diagnostics.push(Diagnostic {
+                    line: index + 1,
+                    column,
+                    message: "unterminated '${'".to_string(),
+                });
+                rest = "";
+                break;
+            };
+            let variable = &rest[start + 2..start + end];
+            let (name, default) = match variable.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (variable, None),
+            };
+            match lookup(name).or(default.map(str::to_string)) {
+                Some(value) => expanded.push_str(&value),
+                None => diagnostics.push(Diagnostic {
+                    line: index + 1,
+                    column,
+                    message: format!("environment variable '{}' is not set", name),
+                }),
+            }
+            rest = &rest[start + end + 1..];
+        }
+        expanded.push_str(rest);
+    }
+
+    if diagnostics.is_empty() {
+        Ok(expanded)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+/// Parses and checks a config file. Reports every problem with its position instead of the
+/// first serde error.
+pub fn validate(text: &str) -> Result<RTConfig, Vec<Diagnostic>> {
+    let config: RTConfig = serde_yml::from_str(text).map_err(|e| vec![parse_error(&e)])?;
+
+    let mut diagnostics = Vec::new();
+    let mut names = HashSet::new();
+    let mut occurrences = Occurrences::new(text);
+    for library in &config.libraries {
+        let (line, column) = occurrences.next(&library.name);
+        let mut report = |message: String| {
+            diagnostics.push(Diagnostic {
+                line,
+                column,
+                message,
+            })
+        };
+        if !valid_name(&library.name) {
+            report(format!(
+                "invalid library name '{}', use letters, digits, '_' and '-'",
+                library.name
+            ));
+        }
+        if let Some(instance) = library.instance.as_deref().filter(|name| !valid_name(name)) {
+            report(format!(
+                "invalid instance name '{}', use letters, digits, '_' and '-'",
+                instance
+            ));
+        }
+        if library.tick_rate_hz.is_some() && library.tick().is_none() {
+            report(format!(
+                "tick_rate_hz of library '{}' must be positive and at most {} Hz",
+                library.name,
+                1.0 / MIN_TICK_PERIOD.as_secs_f64()
+            ));
+        }
+        if library.tick_budget_ms.is_some() && library.tick_rate_hz.is_none() {
+            report(format!("tick_budget_ms of library '{}' needs a tick_rate_hz", library.name));
+        }
+        // a library may be configured once per set of profiles, see `select_profiles`
+        if !names.insert((library.component_name(), &library.profiles)) {
+            report(format!("library '{}' is configured twice", library.component_name()));
+        }
+
+        let mut keys = HashSet::new();
+        for entry in library.attributes.iter().flatten() {
+            if entry.key.is_empty() {
+                report(format!("empty attribute key in library '{}'", library.name));
+            } else if !keys.insert(entry.key.as_str()) {
+                report(format!(
+                    "attribute '{}' is set twice in library '{}'",
+                    entry.key, library.name
+                ));
+            }
+        }
+    }
+
+    if diagnostics.is_empty() {
+        Ok(config)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// serde_yml appends the position to the message and writes paths like `libraries.\[0\]`
+fn parse_error(error: &serde_yml::Error) -> Diagnostic {
+    let (line, column) = error
+        .location()
+        .map(|location| (location.line(), location.column()))
+        .unwrap_or((1, 1));
+    let message = error.to_string();
+    let suffix = format!(" at line {} column {}", line, column);
+    let message = message.strip_suffix(&suffix).unwrap_or(&message);
+    Diagnostic {
+        line,
+        column,
+        message: message.replace("\\[", "[").replace("\\]", "]").replace(".[", "["),
+    }
+}
+
+// positions of the library names, found by searching the text as the parsed config has none
+struct Occurrences<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Occurrences<'a> {
+    fn new(text: &'a str) -> Self {
+        Occurrences { text, offset: 0 }
+    }
+
+    fn next(&mut self, name: &str) -> (usize, usize) {
+        let found = self.text[self.offset..]
+            .match_indices("name")
+            .map(|(index, _)| self.offset + index)
+            .find(|index| {
+                self.text[index + 4..]
+                    .trim_start_matches(['"', '\'', ':', ' '])
+                    .strip_prefix(name)
+                    .is_some_and(|rest| {
+                        rest.is_empty() || rest.starts_with(['"', '\'', ',', '}', ' ', '\r', '\n'])
+                    })
+            });
+        let Some(index) = found else {
+            return (1, 1);
+        };
+        self.offset = index + 4;
+        let before = &self.text[..index];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |n| n + 1) + 1;
+        (line, column)
+    }
+}
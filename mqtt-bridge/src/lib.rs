@@ -0,0 +1,463 @@
+// Connects blackboard keys to an MQTT broker. Changes of the configured keys are published to
+// `<publish_prefix><key>`, messages on `<subscribe_prefix><key>` are written to the key. Payloads
+// are typed values like `{"type": "int", "value": 42}`, an empty payload removes the key.
+use interfaces::blackboard::{
+    BlackboardEntry, BlackboardValue, TypedBlackboardValue, key_matches, parse_attributes,
+};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{debug, error, info, warn};
+use rumqttc::{Client, Connection, Event, MqttOptions, Packet, QoS};
+use std::collections::HashMap;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// between two attempts to reach the broker, also bounds how long stopping waits for one
+const RECONNECT: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT_SECS: u64 = 2;
+
+struct Config {
+    broker: String, // `mqtt://host:port`, the port defaults to 1883
+    client_id: String,
+    username: Option<String>,
+    password: Option<String>,
+    qos: QoS,
+    retain: bool,             // of the published changes
+    keys: Vec<String>,        // connected keys, or prefixes ending with `*`
+    publish_prefix: String,   // topics the changes are published to
+    subscribe_prefix: String, // topics written to the keys
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            broker: "mqtt://localhost:1883".to_string(),
+            client_id: format!("rtime-{}", std::process::id()),
+            username: None,
+            password: None,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            keys: Vec::new(),
+            publish_prefix: "rtime/".to_string(),
+            subscribe_prefix: "rtime/set/".to_string(),
+        }
+    }
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("broker", BlackboardValue::String(value)) => config.broker = value.clone(),
+                ("client_id", BlackboardValue::String(value)) => config.client_id = value.clone(),
+                ("username", BlackboardValue::String(value)) => {
+                    config.username = Some(value.clone())
+                }
+                ("password", BlackboardValue::String(value)) => {
+                    config.password = Some(value.clone())
+                }
+                ("qos", BlackboardValue::Int(value)) => {
+                    config.qos = match value {
+                        0 => QoS::AtMostOnce,
+                        1 => QoS::AtLeastOnce,
+                        2 => QoS::ExactlyOnce,
+                        _ => {
+                            return Err(RtError::new(
+                                RtStatus::InvalidArgument,
+                                format!("qos {} is not 0, 1 or 2", value),
+                            ))
+                        }
+                    }
+                }
+                ("retain", BlackboardValue::Bool(value)) => config.retain = *value,
+                ("publish_prefix", BlackboardValue::String(value)) => {
+                    config.publish_prefix = value.clone()
+                }
+                ("subscribe_prefix", BlackboardValue::String(value)) => {
+                    config.subscribe_prefix = value.clone()
+                }
+                ("keys", BlackboardValue::String(key)) => config.keys = vec![key.clone()],
+                ("keys", BlackboardValue::Array(keys)) => {
+                    config.keys = keys
+                        .iter()
+                        .filter_map(|key| match key {
+                            BlackboardValue::String(key) => Some(key.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    // host and port of `broker`, only plain tcp is supported
+    fn address(&self) -> Result<(String, u16), RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let address = match self.broker.split_once("://") {
+            Some(("mqtt" | "tcp", address)) => address,
+            Some((scheme, _)) => {
+                return Err(invalid(format!("Unsupported broker scheme {}", scheme)))
+            }
+            None => self.broker.as_str(),
+        };
+        match address.rsplit_once(':') {
+            Some((host, port)) => port
+                .parse()
+                .map(|port| (host.to_string(), port))
+                .map_err(|_| invalid(format!("Invalid broker port {}", port))),
+            None => Ok((address.to_string(), 1883)),
+        }
+    }
+}
+
+fn payload(value: Option<&TypedBlackboardValue>) -> Vec<u8> {
+    value
+        .and_then(|value| serde_json::to_vec(value).ok())
+        .unwrap_or_default()
+}
+
+// the value of a message, None for an empty payload
+fn parse_payload(payload: &[u8]) -> Result<Option<TypedBlackboardValue>, String> {
+    if payload.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_slice(payload)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+struct Bridge {
+    client: BlackboardClient,
+    mqtt: Client,
+    keys: Vec<String>,
+    qos: QoS,
+    retain: bool,
+    publish_prefix: String,
+    subscribe_prefix: String,
+    // values written from messages, not published again if both prefixes are the same
+    written: Mutex<HashMap<String, Option<TypedBlackboardValue>>>,
+    connected: AtomicBool,
+    running: AtomicBool,
+}
+
+impl Bridge {
+    fn connects(&self, key: &str) -> bool {
+        self.keys.iter().any(|filter| key_matches(filter, key))
+    }
+
+    fn echoes(&self) -> bool {
+        self.publish_prefix == self.subscribe_prefix
+    }
+
+    // publishes the current value of a changed key
+    fn publish(&self, key: &str) {
+        let value = match self.client.get_value(key) {
+            Ok(value) => Some(value),
+            Err(e) if e.status == RtStatus::KeyNotFound => None,
+            Err(e) => return warn!("Can not read changed key {}: {}", key, e),
+        };
+        if self.echoes() && self.written.lock().unwrap().remove(key) == Some(value.clone()) {
+            return;
+        }
+        let topic = format!("{}{}", self.publish_prefix, key);
+        // changes are dropped while the broker is not reachable for long
+        if let Err(e) =
+            self.mqtt
+                .try_publish(&topic, self.qos, self.retain, payload(value.as_ref()))
+        {
+            warn!("Can not publish {}: {}", topic, e);
+        }
+    }
+
+    // writes a message to its key
+    fn receive(&self, topic: &str, payload: &[u8]) {
+        let Some(key) = topic.strip_prefix(&self.subscribe_prefix) else {
+            return;
+        };
+        if !self.connects(key) {
+            return debug!("Ignoring {}, {} is not connected", topic, key);
+        }
+        let value = match parse_payload(payload) {
+            Ok(value) => value,
+            Err(e) => return warn!("Invalid payload on {}: {}", topic, e),
+        };
+        if self.echoes() {
+            self.written
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.clone());
+        }
+        let written = match &value {
+            Some(value) => self.client.set_value(key, value),
+            None => match self.client.delete(key) {
+                Err(e) if e.status == RtStatus::KeyNotFound => Ok(()),
+                result => result,
+            },
+        };
+        if let Err(e) = written {
+            warn!("Can not write {} from {}: {}", key, topic, e);
+        }
+    }
+
+    // drives the connection until the bridge stops, it reconnects after errors
+    fn run(&self, mut connection: Connection) {
+        while self.running.load(Ordering::SeqCst) {
+            let Ok(notification) = connection.recv() else {
+                break;
+            };
+            if !self.running.load(Ordering::SeqCst) {
+                break;
+            }
+            match notification {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!("Connected to the broker");
+                    self.connected.store(true, Ordering::SeqCst);
+                    // the broker forgets the subscriptions of a clean session
+                    let topics = format!("{}#", self.subscribe_prefix);
+                    if let Err(e) = self.mqtt.try_subscribe(&topics, self.qos) {
+                        warn!("Can not subscribe {}: {}", topics, e);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    self.receive(&publish.topic, &publish.payload)
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    if self.connected.swap(false, Ordering::SeqCst) {
+                        warn!("Lost the broker: {}", e);
+                    } else {
+                        debug!("Can not reach the broker: {}", e);
+                    }
+                    let retry = Instant::now() + RECONNECT;
+                    while self.running.load(Ordering::SeqCst) && Instant::now() < retry {
+                        std::thread::sleep(Duration::from_millis(50));
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct BridgeState {
+    bridge: Arc<Bridge>,
+    broker: String,
+    subscriptions: Vec<Subscription>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+static BRIDGE_STATE: Mutex<Option<BridgeState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "mqtt_bridge",
+    summary = "connects blackboard keys to MQTT topics",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        mqtt_bridge_start = start: "i32(caps,cstr)",
+        mqtt_bridge_stop = stop: "i32()",
+        mqtt_bridge_health = health: "i32()",
+        mqtt_bridge_state = state: "i32()",
+        mqtt_bridge_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_bridge(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = BRIDGE_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Bridge is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    if config.keys.is_empty() {
+        return Err(RtError::new(
+            RtStatus::InvalidArgument,
+            "No keys to connect",
+        ));
+    }
+    let (host, port) = config.address()?;
+    let mut options = MqttOptions::new(&config.client_id, host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+    let (mqtt, mut connection) = Client::new(options, 64);
+    let mut network = connection.eventloop.network_options();
+    network.set_connection_timeout(CONNECT_TIMEOUT_SECS);
+    connection.eventloop.set_network_options(network);
+
+    let bridge = Arc::new(Bridge {
+        client: BlackboardClient::new(interfaces::capabilities::Capabilities::from_raw(caps)),
+        mqtt,
+        keys: config.keys,
+        qos: config.qos,
+        retain: config.retain,
+        publish_prefix: config.publish_prefix,
+        subscribe_prefix: config.subscribe_prefix,
+        written: Mutex::new(HashMap::new()),
+        connected: AtomicBool::new(false),
+        running: AtomicBool::new(true),
+    });
+
+    let (subscriptions, changes) =
+        bridge.client.subscribe_forwarding(&bridge.keys, "mqtt_bridge")?;
+
+    let publishing = bridge.clone();
+    let receiving = bridge.clone();
+    let threads = vec![
+        // ends once the subscriptions are dropped
+        std::thread::spawn(move || {
+            for key in changes {
+                publishing.publish(&key);
+            }
+        }),
+        std::thread::spawn(move || receiving.run(connection)),
+    ];
+
+    info!("Connecting {:?} to {}", bridge.keys, config.broker);
+    *state = Some(BridgeState {
+        bridge,
+        broker: config.broker,
+        subscriptions,
+        threads,
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "mqtt_bridge");
+    match LIFECYCLE.started(catch_panic(|| start_bridge(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting bridge: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_bridge() -> Result<(), RtError> {
+    let state = BRIDGE_STATE.lock().unwrap().take();
+    let state = state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Bridge is not running"))?;
+    state.bridge.running.store(false, Ordering::SeqCst);
+    drop(state.subscriptions);
+    // wakes the connection, it waits for the broker otherwise
+    let _ = state.bridge.mqtt.try_disconnect();
+    for thread in state.threads {
+        let _ = thread.join();
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_bridge)) {
+        Ok(()) => {
+            info!("Bridge stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping bridge: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_OK` while the bridge runs, `RT_NOT_RUNNING` once it is stopped. A missing broker is not
+/// an error, it is connected again.
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match BRIDGE_STATE.lock().unwrap().as_ref() {
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the bridge, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the broker and whether it is connected as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match BRIDGE_STATE.lock().unwrap().as_ref() {
+        Some(state) => serde_json::json!({
+            "broker": state.broker,
+            "connected": state.bridge.connected.load(Ordering::SeqCst),
+        }),
+        None => serde_json::json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address() {
+        let mut config = Config::default();
+        assert_eq!(config.address().unwrap(), ("localhost".to_string(), 1883));
+        config.broker = "broker.local".to_string();
+        assert_eq!(
+            config.address().unwrap(),
+            ("broker.local".to_string(), 1883)
+        );
+        config.broker = "tcp://10.0.0.2:1884".to_string();
+        assert_eq!(config.address().unwrap(), ("10.0.0.2".to_string(), 1884));
+        config.broker = "mqtts://broker.local:8883".to_string();
+        assert_eq!(
+            config.address().unwrap_err().status,
+            RtStatus::InvalidArgument
+        );
+        config.broker = "mqtt://broker.local:port".to_string();
+        assert!(config.address().is_err());
+    }
+
+    #[test]
+    fn test_payload() {
+        let value = TypedBlackboardValue::Int(42);
+        assert_eq!(payload(Some(&value)), br#"{"type":"int","value":42}"#);
+        assert_eq!(parse_payload(&payload(Some(&value))).unwrap(), Some(value));
+        assert_eq!(payload(None), b"");
+        assert_eq!(parse_payload(b"").unwrap(), None);
+        assert!(parse_payload(b"42").is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let entries: Vec<BlackboardEntry> = serde_yml::from_str(
+            "[{key: qos, value: 1}, {key: keys, value: [robot/*]}, {key: retain, value: true}]",
+        )
+        .unwrap();
+        let config = Config::new(&entries).unwrap();
+        assert_eq!(config.qos, QoS::AtLeastOnce);
+        assert_eq!(config.keys, vec!["robot/*"]);
+        assert!(config.retain);
+
+        let entries: Vec<BlackboardEntry> = serde_yml::from_str("[{key: qos, value: 3}]").unwrap();
+        assert!(Config::new(&entries).is_err());
+    }
+}
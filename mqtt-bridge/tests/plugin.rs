@@ -0,0 +1,86 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::testing::Host;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+// type and body of the next MQTT packet
+fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut byte = [0u8; 1];
+    stream.read_exact(&mut byte).unwrap();
+    let packet_type = byte[0] >> 4;
+    let (mut len, mut shift) = (0usize, 0);
+    loop {
+        stream.read_exact(&mut byte).unwrap();
+        len |= ((byte[0] & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).unwrap();
+    (packet_type, body)
+}
+
+// topic and payload of a publish packet with qos 0
+fn publish_packet(body: &[u8]) -> (String, String) {
+    let len = u16::from_be_bytes([body[0], body[1]]) as usize;
+    (
+        String::from_utf8(body[2..2 + len].to_vec()).unwrap(),
+        String::from_utf8(body[2 + len..].to_vec()).unwrap(),
+    )
+}
+
+#[test]
+fn test_mqtt_bridge() {
+    // plays the broker
+    let broker = TcpListener::bind(("127.0.0.1", 18801)).unwrap();
+    let attributes = vec![
+        BlackboardEntry {
+            key: "broker".to_string(),
+            value: BlackboardValue::String("mqtt://127.0.0.1:18801".to_string()),
+        },
+        BlackboardEntry {
+            key: "keys".to_string(),
+            value: BlackboardValue::Array(vec![BlackboardValue::String("robot/*".to_string())]),
+        },
+    ];
+    let host = Host::start(&[("mqtt_bridge", attributes)]).unwrap();
+    let client = host.client();
+
+    let (mut stream, _) = broker.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(read_packet(&mut stream).0, 1);
+    stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+    let (packet_type, subscribe) = read_packet(&mut stream);
+    assert_eq!(packet_type, 8);
+    assert_eq!(&subscribe[4..subscribe.len() - 1], b"rtime/set/#");
+    stream.write_all(&[0x90, 0x03, subscribe[0], subscribe[1], 0x00]).unwrap();
+
+    client.set_i32("other", 1).unwrap();
+    client.set_i32("robot/speed", 3).unwrap();
+    let (packet_type, publish) = read_packet(&mut stream);
+    assert_eq!(packet_type, 3);
+    assert_eq!(
+        publish_packet(&publish),
+        ("rtime/robot/speed".to_string(), r#"{"type":"int","value":3}"#.to_string())
+    );
+
+    // messages are written to the key, which publishes its state
+    let topic = b"rtime/set/robot/target";
+    let payload = br#"{"type":"string","value":"dock"}"#;
+    let mut message = vec![0x30, (2 + topic.len() + payload.len()) as u8, 0, topic.len() as u8];
+    message.extend_from_slice(topic);
+    message.extend_from_slice(payload);
+    stream.write_all(&message).unwrap();
+    let (packet_type, publish) = read_packet(&mut stream);
+    assert_eq!(packet_type, 3);
+    assert_eq!(
+        publish_packet(&publish),
+        ("rtime/robot/target".to_string(), String::from_utf8(payload.to_vec()).unwrap())
+    );
+    assert_eq!(client.get_string("robot/target").unwrap(), "dock");
+
+    assert!(host.stop().is_empty());
+}
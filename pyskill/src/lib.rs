@@ -0,0 +1,463 @@
+// Runs skills written in Python. The service embeds an interpreter, loads the scripts of its
+// attributes as modules and hosts their functions as skills: the loader lists them with the
+// entry `skills` and runs them with `run_skill`, so projects, behavior trees and state machines
+// run them like skill libraries. The scripts use the blackboard with the module `rtime`.
+//
+//     - name: pyskill
+//       attributes:
+//         - {key: scripts, value: [skills/greet.py, skills/dock.py]}
+//         - {key: skills, value: {dock: dock.approach, undock: dock.leave}}
+//
+// Without `skills` every script with a function `run` is a skill named like the script. A skill
+// returns None or an int, None and True are 0, False and exceptions are `RT_ERROR`.
+mod rtime;
+
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue, parse_attributes};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::capabilities::Capabilities;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{error, info};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyModule};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+#[derive(Default)]
+struct Config {
+    scripts: Vec<PathBuf>,
+    skills: BTreeMap<String, String>, // skill -> `module.function`
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("scripts", BlackboardValue::String(script)) => {
+                    config.scripts = vec![PathBuf::from(script)]
+                }
+                ("scripts", BlackboardValue::Array(scripts)) => {
+                    config.scripts = scripts
+                        .iter()
+                        .map(|script| match script {
+                            BlackboardValue::String(script) => Ok(PathBuf::from(script)),
+                            other => Err(invalid(format!("Invalid script {:?}", other))),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                ("skills", BlackboardValue::Json(serde_json::Value::Object(skills))) => {
+                    for (skill, function) in skills {
+                        let function = function.as_str().ok_or_else(|| {
+                            invalid(format!("Skill {} needs a function like module.run", skill))
+                        })?;
+                        config.skills.insert(skill.clone(), function.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        if config.scripts.is_empty() {
+            return Err(invalid("No scripts to load".to_string()));
+        }
+        Ok(config)
+    }
+}
+
+struct Host {
+    client: BlackboardClient,
+    scripts: Vec<PathBuf>,
+    skills: OnceLock<BTreeMap<String, PyObject>>, // set once the scripts are loaded
+    subscriptions: Mutex<Vec<Subscription>>,       // made by the scripts with `rtime.subscribe`
+    runs: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Host {
+    fn skill_names(&self) -> Vec<&String> {
+        self.skills.get().map(|skills| skills.keys().collect()).unwrap_or_default()
+    }
+}
+
+static HOST: Mutex<Option<Arc<Host>>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+// the running host, for the functions of `rtime`
+fn host() -> PyResult<Arc<Host>> {
+    HOST.lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| PyRuntimeError::new_err("pyskill is not running"))
+}
+
+// an exception with its traceback
+fn describe(py: Python<'_>, e: &PyErr) -> String {
+    let traceback = e.traceback(py).and_then(|traceback| traceback.format().ok());
+    format!("{}{}", traceback.unwrap_or_default(), e)
+}
+
+#[rt_plugin(
+    name = "pyskill",
+    summary = "runs skills written in Python",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        pyskill_start = start: "i32(caps,cstr)",
+        pyskill_stop = stop: "i32()",
+        pyskill_health = health: "i32()",
+        pyskill_state = state: "i32()",
+        pyskill_health_status = health_status: "i32(*mut char,i32)",
+        pyskill_skills = skills: "i32(*mut char,i32)",
+        pyskill_run_skill = run_skill: "i32(cstr)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+// loads the scripts as modules and returns the functions of the skills
+fn load(py: Python<'_>, config: &Config) -> Result<BTreeMap<String, PyObject>, String> {
+    let python = |e: PyErr| describe(py, &e);
+    rtime::install(py).map_err(python)?;
+    let path = py.import("sys").and_then(|sys| sys.getattr("path")).map_err(python)?;
+
+    let mut modules = BTreeMap::new();
+    for script in &config.scripts {
+        let name = script
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("Invalid script {}", script.display()))?;
+        // scripts import the modules next to them
+        if let Some(directory) = script.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            let directory = directory.display().to_string();
+            if !path.contains(&directory).map_err(python)? {
+                path.call_method1("insert", (0, directory)).map_err(python)?;
+            }
+        }
+        let code = std::fs::read_to_string(script)
+            .map_err(|e| format!("Can not read {}: {}", script.display(), e))?;
+        let cstring = |text: String| CString::new(text).map_err(|e| e.to_string());
+        let module = PyModule::from_code(
+            py,
+            &cstring(code)?,
+            &cstring(script.display().to_string())?,
+            &cstring(name.to_string())?,
+        )
+        .map_err(|e| format!("Can not load {}: {}", script.display(), describe(py, &e)))?;
+        modules.insert(name.to_string(), module);
+    }
+
+    let mut skills = BTreeMap::new();
+    if config.skills.is_empty() {
+        for (name, module) in &modules {
+            if let Some(run) = module.getattr("run").ok().filter(|run| run.is_callable()) {
+                skills.insert(name.clone(), run.unbind());
+            }
+        }
+    }
+    for (skill, target) in &config.skills {
+        let function = target
+            .rsplit_once('.')
+            .and_then(|(module, function)| modules.get(module)?.getattr(function).ok())
+            .filter(|function| function.is_callable())
+            .ok_or_else(|| format!("Skill {}: {} is no function of the scripts", skill, target))?;
+        skills.insert(skill.clone(), function.unbind());
+    }
+    Ok(skills)
+}
+
+fn start_host(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    let host = Arc::new(Host {
+        client: BlackboardClient::new(Capabilities::from_raw(caps)),
+        scripts: config.scripts.clone(),
+        skills: OnceLock::new(),
+        subscriptions: Mutex::new(Vec::new()),
+        runs: AtomicU64::new(0),
+        failures: AtomicU64::new(0),
+    });
+    {
+        let mut state = HOST.lock().unwrap();
+        if state.is_some() {
+            return Err(RtError::new(RtStatus::AlreadyRunning, "pyskill is already running"));
+        }
+        *state = Some(host.clone());
+    }
+
+    // the scripts may use `rtime` while they are loaded
+    match Python::with_gil(|py| load(py, &config)) {
+        Ok(skills) => {
+            info!("Loaded {:?} with the skills {:?}", config.scripts, skills.keys());
+            let _ = host.skills.set(skills);
+            Ok(())
+        }
+        Err(e) => {
+            HOST.lock().unwrap().take();
+            host.subscriptions.lock().unwrap().clear();
+            Err(RtError::new(RtStatus::InvalidArgument, e))
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "pyskill");
+    match LIFECYCLE.started(catch_panic(|| start_host(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting pyskill: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_host() -> Result<(), RtError> {
+    let host = HOST
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "pyskill is not running"))?;
+    // the interpreter stays, it can not be started twice in a process
+    host.subscriptions.lock().unwrap().clear();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_host)) {
+        Ok(()) => {
+            info!("pyskill stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping pyskill: {}", e);
+            e.record()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match HOST.lock().unwrap().as_ref() {
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of pyskill, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the scripts, the skills and how often they ran and failed as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match HOST.lock().unwrap().as_ref() {
+        Some(host) => json!({
+            "scripts": host.scripts,
+            "skills": host.skill_names(),
+            "runs": host.runs.load(Ordering::SeqCst),
+            "failures": host.failures.load(Ordering::SeqCst),
+        }),
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+/// Writes the names of the skills as a json array, empty while pyskill is stopped.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn skills(buffer: *mut c_char, len: c_int) -> c_int {
+    let names = match HOST.lock().unwrap().as_ref() {
+        Some(host) => json!(host.skill_names()),
+        None => json!([]),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&names.to_string(), buffer, len) }
+}
+
+// calls the function of a skill and maps what it returns to a status, see the top of the file
+fn call_skill(py: Python<'_>, function: &PyObject) -> Result<c_int, String> {
+    let returned = function.call0(py).map_err(|e| describe(py, &e))?;
+    let returned = returned.bind(py);
+    if returned.is_none() {
+        Ok(RtStatus::Ok.code())
+    } else if returned.is_instance_of::<PyBool>() {
+        Ok(match returned.is_truthy() {
+            Ok(true) => RtStatus::Ok.code(),
+            _ => RtStatus::Error.code(),
+        })
+    } else {
+        returned
+            .extract::<c_int>()
+            .map_err(|_| format!("It returned {}, not an int", returned))
+    }
+}
+
+fn run_skill_intern(name: *const c_char) -> Result<c_int, RtError> {
+    if name.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Skill name is null pointer"));
+    }
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+    let host = HOST
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "pyskill is not running"))?;
+    let function = host
+        .skills
+        .get()
+        .and_then(|skills| skills.get(&name))
+        .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("No skill {}", name)))?;
+
+    host.runs.fetch_add(1, Ordering::SeqCst);
+    let result = Python::with_gil(|py| call_skill(py, function));
+    if !matches!(result, Ok(code) if code >= 0) {
+        host.failures.fetch_add(1, Ordering::SeqCst);
+    }
+    result.map_err(|e| RtError::from(format!("Skill {} failed: {}", name, e)))
+}
+
+/// Runs the skill `name` and returns what it returns, `RT_KEY_NOT_FOUND` for unknown skills.
+#[no_mangle]
+pub extern "C" fn run_skill(name: *const c_char) -> c_int {
+    match catch_panic(|| run_skill_intern(name)) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("{}", e);
+            e.record()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(attributes: &CStr) -> Result<Config, RtError> {
+        Config::new(&unsafe { parse_attributes(attributes.as_ptr()) }?)
+    }
+
+    // writes `scripts` of (file, code) to a directory of their own
+    fn scripts(test: &str, scripts: &[(&str, &str)]) -> PathBuf {
+        let dir = format!("rtime-pyskill-{}-{}", test, std::process::id());
+        let dir = std::env::temp_dir().join(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, code) in scripts {
+            std::fs::write(dir.join(file), code).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_config() {
+        let config = parse(c"[{key: scripts, value: skills/dock.py}]").unwrap();
+        assert_eq!(config.scripts, [PathBuf::from("skills/dock.py")]);
+        assert!(config.skills.is_empty());
+
+        let config = parse(
+            c"[{key: scripts, value: [a.py, b.py]}, {key: skills, value: {dock: a.approach}}]",
+        )
+        .unwrap();
+        assert_eq!(config.scripts, [PathBuf::from("a.py"), PathBuf::from("b.py")]);
+        assert_eq!(config.skills["dock"], "a.approach");
+
+        let invalid = |attributes: &CStr| parse(attributes).err().map(|e| e.status);
+        assert_eq!(invalid(c"[]"), Some(RtStatus::InvalidArgument));
+        assert_eq!(invalid(c"[{key: scripts, value: [1]}]"), Some(RtStatus::InvalidArgument));
+        let skills = c"[{key: scripts, value: a.py}, {key: skills, value: {dock: 1}}]";
+        assert_eq!(invalid(skills), Some(RtStatus::InvalidArgument));
+        assert_eq!(invalid(c"[{key: scripts"), Some(RtStatus::InvalidArgument));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let dir = scripts(
+            "load",
+            &[
+                ("broken.py", "def run(:\n    pass\n"),
+                ("tool.py", "def helper():\n    return 1\n"),
+            ],
+        );
+        let load = |scripts: &[&str], skills: &[(&str, &str)]| {
+            let config = Config {
+                scripts: scripts.iter().map(|script| dir.join(script)).collect(),
+                skills: skills.iter().map(|(s, f)| (s.to_string(), f.to_string())).collect(),
+            };
+            Python::with_gil(|py| load(py, &config).map(|skills| skills.into_keys().collect()))
+        };
+
+        let error: String = load(&["missing.py"], &[]).unwrap_err();
+        assert!(error.starts_with("Can not read"), "{}", error);
+        let error = load(&["broken.py"], &[]).unwrap_err();
+        assert!(error.contains("SyntaxError"), "{}", error);
+        let error = load(&["tool.py"], &[("dock", "tool.approach")]).unwrap_err();
+        assert_eq!(error, "Skill dock: tool.approach is no function of the scripts");
+        let error = load(&["tool.py"], &[("dock", "other.helper")]).unwrap_err();
+        assert_eq!(error, "Skill dock: other.helper is no function of the scripts");
+
+        // without `skills` only the scripts with a function `run` are skills
+        let skills: Vec<String> = load(&["tool.py"], &[]).unwrap();
+        assert!(skills.is_empty());
+        let skills: Vec<String> = load(&["tool.py"], &[("help", "tool.helper")]).unwrap();
+        assert_eq!(skills, ["help"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_results() {
+        let dir = scripts(
+            "results",
+            &[(
+                "results.py",
+                "def none():\n    pass\n\
+                 def true():\n    return True\n\
+                 def false():\n    return False\n\
+                 def number():\n    return 7\n\
+                 def negative():\n    return -5\n\
+                 def text():\n    return 'done'\n\
+                 def huge():\n    return 2 ** 40\n\
+                 def fail():\n    raise ValueError('broken')\n",
+            )],
+        );
+        let functions = ["none", "true", "false", "number", "negative", "text", "huge", "fail"];
+        let config = Config {
+            scripts: vec![dir.join("results.py")],
+            skills: functions
+                .iter()
+                .map(|function| (function.to_string(), format!("results.{}", function)))
+                .collect(),
+        };
+        Python::with_gil(|py| {
+            let skills = load(py, &config).unwrap();
+            let call = |skill: &str| call_skill(py, &skills[skill]);
+            assert_eq!(call("none"), Ok(0));
+            assert_eq!(call("true"), Ok(0));
+            assert_eq!(call("false"), Ok(RtStatus::Error.code()));
+            assert_eq!(call("number"), Ok(7));
+            assert_eq!(call("negative"), Ok(-5));
+            assert_eq!(call("text"), Err("It returned done, not an int".to_string()));
+            assert!(call("huge").is_err());
+            // exceptions fail with their traceback
+            let error = call("fail").unwrap_err();
+            assert!(error.contains("ValueError: broken"), "{}", error);
+            assert!(error.contains("results.py"), "{}", error);
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
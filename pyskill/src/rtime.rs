@@ -0,0 +1,112 @@
+// The module `rtime` the scripts import to use the blackboard:
+//
+//     import rtime
+//     speed = rtime.get("robot/speed", 0)
+//     rtime.set("robot/speed", speed + 1)
+//     rtime.delete("robot/goal")
+//     rtime.subscribe("robot/goal", lambda key: print(key, rtime.get(key)))
+//
+// Values are plain Python values: ints, floats, bools, strings and lists or dicts for the
+// arrays and json values of the blackboard, bytes are passed as base64 text. The calls release
+// the interpreter while the blackboard works, so subscribers may run meanwhile.
+use interfaces::blackboard::{BlackboardValue, TypedBlackboardValue};
+use interfaces::status::RtStatus;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyFloat, PyInt, PyString};
+
+/// A fresh module `rtime`, replacing the one of a former start in `sys.modules`.
+pub fn install(py: Python<'_>) -> PyResult<()> {
+    let module = PyModule::new(py, "rtime")?;
+    module.add_function(wrap_pyfunction!(get, &module)?)?;
+    module.add_function(wrap_pyfunction!(set, &module)?)?;
+    module.add_function(wrap_pyfunction!(delete, &module)?)?;
+    module.add_function(wrap_pyfunction!(subscribe, &module)?)?;
+    py.import("sys")?.getattr("modules")?.set_item("rtime", module)
+}
+
+fn to_python(py: Python<'_>, value: &TypedBlackboardValue) -> PyResult<PyObject> {
+    let typed = serde_json::to_value(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let json = typed["value"].to_string();
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+fn from_python(value: &Bound<'_, PyAny>) -> PyResult<TypedBlackboardValue> {
+    // bool is a subclass of int in Python
+    if value.is_instance_of::<PyBool>() {
+        return Ok(TypedBlackboardValue::Bool(value.extract()?));
+    }
+    if value.is_instance_of::<PyInt>() {
+        let value: i64 = value.extract()?;
+        return Ok(match i32::try_from(value) {
+            Ok(value) => TypedBlackboardValue::Int(value),
+            Err(_) => TypedBlackboardValue::Int64(value),
+        });
+    }
+    if value.is_instance_of::<PyFloat>() {
+        return Ok(TypedBlackboardValue::Double(value.extract()?));
+    }
+    if value.is_instance_of::<PyString>() {
+        return Ok(TypedBlackboardValue::String(value.extract()?));
+    }
+    let json: String = value.py().import("json")?.call_method1("dumps", (value,))?.extract()?;
+    let value: BlackboardValue =
+        serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    TypedBlackboardValue::try_from(value).map_err(PyValueError::new_err)
+}
+
+/// Value of `key`, `default` if there is no such key.
+#[pyfunction]
+#[pyo3(signature = (key, default=None))]
+fn get(py: Python<'_>, key: &str, default: Option<PyObject>) -> PyResult<PyObject> {
+    let host = super::host()?;
+    match py.allow_threads(|| host.client.get_value(key)) {
+        Ok(value) => to_python(py, &value),
+        Err(e) if e.status == RtStatus::KeyNotFound => Ok(default.unwrap_or_else(|| py.None())),
+        Err(e) => Err(PyRuntimeError::new_err(e.to_string())),
+    }
+}
+
+/// Writes `value` to `key`, the blackboard type follows the Python type.
+#[pyfunction]
+fn set(py: Python<'_>, key: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+    let host = super::host()?;
+    let value = from_python(value)?;
+    py.allow_threads(|| host.client.set_value(key, &value))
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
+
+/// Removes `key`, a missing key is no error.
+#[pyfunction]
+fn delete(py: Python<'_>, key: &str) -> PyResult<()> {
+    let host = super::host()?;
+    match py.allow_threads(|| host.client.delete(key)) {
+        Err(e) if e.status != RtStatus::KeyNotFound => {
+            Err(PyRuntimeError::new_err(e.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Calls `callback(key)` after every change of `key`, which may end with `*`, until the service
+/// stops.
+#[pyfunction]
+fn subscribe(py: Python<'_>, key: &str, callback: PyObject) -> PyResult<()> {
+    if !callback.bind(py).is_callable() {
+        return Err(PyValueError::new_err("The callback is not callable"));
+    }
+    let host = super::host()?;
+    let subscription = py
+        .allow_threads(|| {
+            host.client.subscribe(key, "pyskill", move |changed| {
+                Python::with_gil(|py| {
+                    if let Err(e) = callback.call1(py, (changed,)) {
+                        log::warn!("Subscriber of {} failed: {}", changed, super::describe(py, &e));
+                    }
+                })
+            })
+        })
+        .map_err(PyRuntimeError::new_err)?;
+    host.subscriptions.lock().unwrap().push(subscription);
+    Ok(())
+}
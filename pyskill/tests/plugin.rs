@@ -0,0 +1,55 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::status::RtStatus;
+use interfaces::testing::Host;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_pyskill() {
+    let dir = std::env::temp_dir().join(format!("rtime-pyskill-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("greet.py");
+    std::fs::write(
+        &script,
+        "import rtime\n\
+         rtime.subscribe('py/in', lambda key: rtime.set('py/out', rtime.get(key) * 2))\n\
+         def run():\n    rtime.set('py/greeting', 'hello ' + rtime.get('name', 'nobody'))\n\
+         def fail():\n    raise ValueError('broken')\n",
+    )
+    .unwrap();
+    let attributes = vec![
+        BlackboardEntry {
+            key: "scripts".to_string(),
+            value: BlackboardValue::String(script.display().to_string()),
+        },
+        BlackboardEntry {
+            key: "skills".to_string(),
+            value: BlackboardValue::Json(
+                serde_json::json!({"greet": "greet.run", "fail": "greet.fail"}),
+            ),
+        },
+    ];
+    let host = Host::start(&[("pyskill", attributes)]).unwrap();
+    let client = host.client();
+
+    // the functions run as skills, which the loader hosts like skill libraries
+    assert_eq!(host.run_skill("pyskill", "greet"), Ok(0));
+    assert_eq!(client.get_string("py/greeting").unwrap(), "hello nobody");
+    client.set_string("name", "rtime").unwrap();
+    assert_eq!(host.run_skill("pyskill", "greet"), Ok(0));
+    assert_eq!(client.get_string("py/greeting").unwrap(), "hello rtime");
+    assert_eq!(host.run_skill("pyskill", "fail"), Ok(RtStatus::Error.code()));
+    assert_eq!(host.run_skill("pyskill", "run"), Ok(RtStatus::KeyNotFound.code()));
+
+    client.set_i32("py/in", 21).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while client.get_i32("py/out") != Ok(42) {
+        assert!(Instant::now() < deadline, "py/out was not written");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let status = host.health_status("pyskill").unwrap();
+    assert_eq!(status["skills"], serde_json::json!(["fail", "greet"]));
+    assert_eq!(status["failures"], 1);
+
+    assert!(host.stop().is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
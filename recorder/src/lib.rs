@@ -0,0 +1,407 @@
+// Records the changes of blackboard keys with their time and plays them back, so an incident in
+// the field can be replayed against the same skills on a developer machine. Times are taken from
+// the clock of the loader, see `interfaces::clock`, so a playback under `run --sim-time` steps
+// with the simulated time.
+mod recording;
+
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue, parse_attributes};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::capabilities::Capabilities;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{error, info, warn};
+use recording::{Change, Writer};
+use serde_json::json;
+use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// how often a waiting playback looks at the clock, a simulated clock does not wake it
+const POLL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Record,
+    Playback,
+}
+
+struct Config {
+    mode: Mode,
+    file: PathBuf,
+    keys: Vec<String>, // recorded keys, or prefixes ending with `*`
+    speed: f64,        // of the playback, 2 plays twice as fast as recorded
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mode: Mode::Record,
+            file: PathBuf::from("recording.jsonl"),
+            keys: vec!["*".to_string()],
+            speed: 1.0,
+        }
+    }
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("mode", BlackboardValue::String(value)) => {
+                    config.mode = match value.as_str() {
+                        "record" => Mode::Record,
+                        "playback" => Mode::Playback,
+                        _ => {
+                            return Err(invalid(format!(
+                                "Unknown mode {}, use record or playback",
+                                value
+                            )))
+                        }
+                    }
+                }
+                ("file", BlackboardValue::String(value)) => config.file = PathBuf::from(value),
+                ("keys", BlackboardValue::String(key)) => config.keys = vec![key.clone()],
+                ("keys", BlackboardValue::Array(keys)) => {
+                    config.keys = keys
+                        .iter()
+                        .filter_map(|key| match key {
+                            BlackboardValue::String(key) => Some(key.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                }
+                ("speed", BlackboardValue::Double(value)) => config.speed = *value,
+                ("speed", BlackboardValue::Int(value)) => config.speed = *value as f64,
+                _ => {}
+            }
+        }
+        if !(config.speed > 0.0 && config.speed.is_finite()) {
+            return Err(invalid(format!("Invalid speed {}", config.speed)));
+        }
+        Ok(config)
+    }
+}
+
+// time of the loader clock, which every component is given
+fn now(caps: &Capabilities) -> Duration {
+    interfaces::clock::now(caps).unwrap_or_default()
+}
+
+struct Recorder {
+    file: PathBuf,
+    changes: AtomicU64,
+    failing: AtomicBool, // the last write failed
+}
+
+impl Recorder {
+    // writes the queued changes in batches until the subscriptions are dropped
+    fn run(&self, mut writer: Writer, changes: mpsc::Receiver<Change>) {
+        while let Ok(change) = changes.recv() {
+            let mut batch = vec![change];
+            batch.extend(changes.try_iter());
+            match writer.write(&batch) {
+                Ok(()) => {
+                    self.changes.fetch_add(batch.len() as u64, Ordering::SeqCst);
+                    self.failing.store(false, Ordering::SeqCst);
+                }
+                Err(e) => {
+                    if !self.failing.swap(true, Ordering::SeqCst) {
+                        warn!("Can not write {}: {}", self.file.display(), e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Player {
+    file: PathBuf,
+    total: u64,
+    played: AtomicU64,
+    finished: AtomicBool,
+    stopping: Mutex<bool>,
+    stopped: Condvar,
+}
+
+impl Player {
+    // waits until the clock reaches `due`, false once the player stops
+    fn wait_until(&self, caps: &Capabilities, due: Duration) -> bool {
+        let mut stopping = self.stopping.lock().unwrap();
+        loop {
+            if *stopping {
+                return false;
+            }
+            let now = now(caps);
+            if now >= due {
+                return true;
+            }
+            let wait = (due - now).min(POLL);
+            stopping = self.stopped.wait_timeout(stopping, wait).unwrap().0;
+        }
+    }
+
+    // writes the changes at their time, scaled by `speed`, from the start of the playback
+    fn run(&self, client: &BlackboardClient, changes: Vec<Change>, speed: f64) {
+        let start = now(&client.caps());
+        for change in changes {
+            let due = start + Duration::from_millis(change.time).div_f64(speed);
+            if !self.wait_until(&client.caps(), due) {
+                return;
+            }
+            let result = match &change.value {
+                Some(value) => client.set_value(&change.key, value),
+                None => match client.delete(&change.key) {
+                    Err(e) if e.status == RtStatus::KeyNotFound => Ok(()),
+                    result => result,
+                },
+            };
+            if let Err(e) = result {
+                warn!("Can not play back {}: {}", change.key, e);
+            }
+            self.played.fetch_add(1, Ordering::SeqCst);
+        }
+        self.finished.store(true, Ordering::SeqCst);
+        info!("Played back {}", self.file.display());
+    }
+
+    fn stop(&self) {
+        *self.stopping.lock().unwrap() = true;
+        self.stopped.notify_all();
+    }
+}
+
+enum RecorderState {
+    Recording {
+        recorder: Arc<Recorder>,
+        subscriptions: Vec<Subscription>,
+        writer: JoinHandle<()>,
+    },
+    Playing {
+        player: Arc<Player>,
+        thread: JoinHandle<()>,
+    },
+}
+
+static RECORDER_STATE: Mutex<Option<RecorderState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "recorder",
+    summary = "records blackboard changes and plays them back",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        recorder_start = start: "i32(caps,cstr)",
+        recorder_stop = stop: "i32()",
+        recorder_health = health: "i32()",
+        recorder_state = state: "i32()",
+        recorder_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_recording(
+    client: Arc<BlackboardClient>,
+    config: &Config,
+) -> Result<RecorderState, RtError> {
+    if config.keys.is_empty() {
+        return Err(RtError::new(RtStatus::InvalidArgument, "No keys to record"));
+    }
+    let writer = Writer::create(&config.file)?;
+    let started = now(&client.caps());
+
+    // values are read in the notification, later changes would overwrite them
+    let (sender, changes) = mpsc::channel::<Change>();
+    let mut keys = config.keys.clone();
+    keys.sort();
+    keys.dedup();
+    let subscriptions = keys
+        .iter()
+        .map(|key| {
+            let sender = sender.clone();
+            let reader = client.clone();
+            client.subscribe(key, "recorder", move |changed| {
+                let value = match reader.get_value(changed) {
+                    Ok(value) => Some(value),
+                    Err(e) if e.status == RtStatus::KeyNotFound => None,
+                    Err(e) => {
+                        warn!("Can not read changed key {}: {}", changed, e);
+                        return;
+                    }
+                };
+                let time = now(&reader.caps()).saturating_sub(started);
+                let _ = sender.send(Change {
+                    time: time.as_millis() as u64,
+                    key: changed.to_string(),
+                    value,
+                });
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    drop(sender);
+
+    let recorder = Arc::new(Recorder {
+        file: writer.path().to_path_buf(),
+        changes: AtomicU64::new(0),
+        failing: AtomicBool::new(false),
+    });
+    let writing = recorder.clone();
+    info!("Recording {:?} to {}", keys, recorder.file.display());
+    Ok(RecorderState::Recording {
+        recorder,
+        subscriptions,
+        writer: std::thread::spawn(move || writing.run(writer, changes)),
+    })
+}
+
+fn start_playback(
+    client: Arc<BlackboardClient>,
+    config: &Config,
+) -> Result<RecorderState, RtError> {
+    let changes = recording::read(&config.file)?;
+    let player = Arc::new(Player {
+        file: config.file.clone(),
+        total: changes.len() as u64,
+        played: AtomicU64::new(0),
+        finished: AtomicBool::new(false),
+        stopping: Mutex::new(false),
+        stopped: Condvar::new(),
+    });
+    let playing = player.clone();
+    let speed = config.speed;
+    info!(
+        "Playing back {} changes of {} at {}x",
+        changes.len(),
+        config.file.display(),
+        speed
+    );
+    Ok(RecorderState::Playing {
+        player,
+        thread: std::thread::spawn(move || playing.run(&client, changes, speed)),
+    })
+}
+
+fn start_recorder(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = RECORDER_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Recorder is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    let client = Arc::new(BlackboardClient::new(Capabilities::from_raw(caps)));
+    interfaces::clock::now(&client.caps())?;
+    *state = Some(match config.mode {
+        Mode::Record => start_recording(client, &config)?,
+        Mode::Playback => start_playback(client, &config)?,
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "recorder");
+    match LIFECYCLE.started(catch_panic(|| start_recorder(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting recorder: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_recorder() -> Result<(), RtError> {
+    let state = RECORDER_STATE.lock().unwrap().take();
+    match state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Recorder is not running"))? {
+        RecorderState::Recording {
+            subscriptions,
+            writer,
+            ..
+        } => {
+            // the writer ends after the queued changes
+            drop(subscriptions);
+            let _ = writer.join();
+        }
+        RecorderState::Playing { player, thread } => {
+            player.stop();
+            let _ = thread.join();
+        }
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_recorder)) {
+        Ok(()) => {
+            info!("Recorder stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping recorder: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_ERROR` while the changes can not be recorded, like on a full disk.
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match RECORDER_STATE.lock().unwrap().as_ref() {
+        Some(RecorderState::Recording { recorder, .. })
+            if recorder.failing.load(Ordering::SeqCst) =>
+        {
+            RtError::new(
+                RtStatus::Error,
+                format!("Can not write {}", recorder.file.display()),
+            )
+            .record()
+        }
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the recorder, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the mode, the file and the progress as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match RECORDER_STATE.lock().unwrap().as_ref() {
+        Some(RecorderState::Recording { recorder, .. }) => json!({
+            "mode": "record",
+            "file": recorder.file.display().to_string(),
+            "changes": recorder.changes.load(Ordering::SeqCst),
+            "failing": recorder.failing.load(Ordering::SeqCst),
+        }),
+        Some(RecorderState::Playing { player, .. }) => json!({
+            "mode": "playback",
+            "file": player.file.display().to_string(),
+            "played": player.played.load(Ordering::SeqCst),
+            "total": player.total,
+            "finished": player.finished.load(Ordering::SeqCst),
+        }),
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
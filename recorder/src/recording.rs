@@ -0,0 +1,122 @@
+// Recordings of the recorder, one json line per change with the milliseconds since the recording
+// started, the key and the typed value. A removed key has no value:
+//
+//     {"t":1520,"k":"robot/speed","v":{"type":"int","value":3}}
+//     {"t":1610,"k":"robot/speed"}
+use interfaces::blackboard::TypedBlackboardValue;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    #[serde(rename = "t")]
+    pub time: u64,
+    #[serde(rename = "k")]
+    pub key: String,
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<TypedBlackboardValue>,
+}
+
+pub struct Writer {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl Writer {
+    /// Starts a new recording, a former recording at `path` is replaced.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("Can not create {}: {}", path.display(), e);
+        if let Some(directory) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(directory).map_err(error)?;
+        }
+        Ok(Writer {
+            path: path.to_path_buf(),
+            file: BufWriter::new(File::create(path).map_err(error)?),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, changes: &[Change]) -> Result<(), String> {
+        for change in changes {
+            let line = serde_json::to_string(change).map_err(|e| e.to_string())?;
+            writeln!(self.file, "{}", line).map_err(|e| e.to_string())?;
+        }
+        self.file.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// Reads a whole recording, sorted by time.
+pub fn read(path: &Path) -> Result<Vec<Change>, String> {
+    let file = File::open(path).map_err(|e| format!("Can not open {}: {}", path.display(), e))?;
+    let mut changes = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Can not read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let change: Change = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+        changes.push(change);
+    }
+    // the changes of concurrent writers may be queued slightly out of order
+    changes.sort_by_key(|change| change.time);
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording() {
+        let directory =
+            std::env::temp_dir().join(format!("rtime-recording-{}", std::process::id()));
+        let path = directory.join("run.jsonl");
+        let changes = vec![
+            Change {
+                time: 20,
+                key: "robot/speed".to_string(),
+                value: Some(TypedBlackboardValue::Int(3)),
+            },
+            Change {
+                time: 10,
+                key: "robot/name".to_string(),
+                value: Some(TypedBlackboardValue::String("r2".to_string())),
+            },
+            Change {
+                time: 30,
+                key: "robot/speed".to_string(),
+                value: None,
+            },
+        ];
+        let mut writer = Writer::create(&path).unwrap();
+        writer.write(&changes).unwrap();
+        drop(writer);
+
+        let lines = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            lines.lines().next().unwrap(),
+            r#"{"t":20,"k":"robot/speed","v":{"type":"int","value":3}}"#
+        );
+        assert_eq!(lines.lines().last().unwrap(), r#"{"t":30,"k":"robot/speed"}"#);
+        let recorded = read(&path).unwrap();
+        assert_eq!(recorded.iter().map(|change| change.time).collect::<Vec<_>>(), [10, 20, 30]);
+        assert_eq!(recorded[1], changes[0]);
+
+        // a new recording replaces the former one
+        Writer::create(&path).unwrap();
+        assert!(read(&path).unwrap().is_empty());
+
+        fs::write(&path, "{\"t\":1}\n").unwrap();
+        assert!(read(&path).unwrap_err().contains("run.jsonl:1"));
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
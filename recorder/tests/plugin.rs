@@ -0,0 +1,55 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::testing::Host;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_recorder() {
+    let dir = std::env::temp_dir().join(format!("rtime-recorder-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let file = dir.join("incident.jsonl");
+    let attributes = |mode: &str| {
+        vec![
+            BlackboardEntry {
+                key: "mode".to_string(),
+                value: BlackboardValue::String(mode.to_string()),
+            },
+            BlackboardEntry {
+                key: "file".to_string(),
+                value: BlackboardValue::String(file.display().to_string()),
+            },
+            BlackboardEntry {
+                key: "keys".to_string(),
+                value: BlackboardValue::String("robot/*".to_string()),
+            },
+            BlackboardEntry {
+                key: "speed".to_string(),
+                value: BlackboardValue::Double(10.0),
+            },
+        ]
+    };
+
+    let host = Host::start(&[("recorder", attributes("record"))]).unwrap();
+    let client = host.client();
+    client.set_i32("robot/speed", 3).unwrap();
+    client.set_i32("other", 1).unwrap();
+    client.set_string("robot/state", "docking").unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    client.set_i32("robot/speed", 0).unwrap();
+    assert!(host.stop().is_empty());
+
+    // a fresh blackboard gets the recorded changes again
+    let host = Host::start(&[("recorder", attributes("playback"))]).unwrap();
+    let client = host.client();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while client.get_string("robot/state").is_err() {
+        assert!(Instant::now() < deadline, "robot/state was not played back");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    while client.get_i32("robot/speed") != Ok(0) {
+        assert!(Instant::now() < deadline, "robot/speed was not played back");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert!(client.get_i32("other").is_err());
+    assert!(host.stop().is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,480 @@
+// Connects blackboard keys to a ROS 2 stack through rosbridge (`rosbridge_server`), so no ROS
+// installation is needed next to rtime. Keys are published to topics and set as parameters,
+// messages of subscribed topics are written to keys.
+mod messages;
+
+use interfaces::blackboard::{
+    BlackboardEntry, BlackboardValue, TypedBlackboardValue, parse_attributes,
+};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{debug, error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tungstenite::{Message, WebSocket};
+
+// between two attempts to reach rosbridge
+const RECONNECT: Duration = Duration::from_secs(1);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+// how long reading blocks the socket for writes
+const POLL: Duration = Duration::from_millis(100);
+
+/// A key and the topic it is published to or written from.
+#[derive(Debug, Clone, Deserialize)]
+struct Topic {
+    key: String,
+    topic: String,
+    #[serde(rename = "type")]
+    message_type: String, // like `std_msgs/msg/Int32`
+}
+
+/// A key set as parameter `name` of a node, like `/controller:max_speed`.
+#[derive(Debug, Clone, Deserialize)]
+struct Parameter {
+    key: String,
+    name: String,
+}
+
+struct Config {
+    url: String, // of rosbridge, `ws://host:port`
+    publish: Vec<Topic>,
+    subscribe: Vec<Topic>,
+    parameters: Vec<Parameter>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            url: "ws://localhost:9090".to_string(),
+            publish: Vec::new(),
+            subscribe: Vec::new(),
+            parameters: Vec::new(),
+        }
+    }
+}
+
+// a list of mappings, given as json or yaml
+fn mappings<T: DeserializeOwned>(key: &str, value: &BlackboardValue) -> Result<Vec<T>, RtError> {
+    serde_json::to_value(value)
+        .and_then(serde_json::from_value)
+        .map_err(|e| RtError::new(RtStatus::InvalidArgument, format!("Invalid {}: {}", key, e)))
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("url", BlackboardValue::String(value)) => config.url = value.clone(),
+                ("publish", value) => config.publish = mappings(&entry.key, value)?,
+                ("subscribe", value) => config.subscribe = mappings(&entry.key, value)?,
+                ("parameters", value) => config.parameters = mappings(&entry.key, value)?,
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    // host and port of `url`, only plain websockets are supported
+    fn address(&self) -> Result<String, RtError> {
+        let address = self.url.strip_prefix("ws://").ok_or_else(|| {
+            RtError::new(
+                RtStatus::InvalidArgument,
+                format!("Unsupported rosbridge url {}", self.url),
+            )
+        })?;
+        let address = address.split('/').next().unwrap_or_default();
+        Ok(match address.contains(':') {
+            true => address.to_string(),
+            false => format!("{}:9090", address),
+        })
+    }
+
+    // keys sent to ROS
+    fn outgoing(&self) -> impl Iterator<Item = &String> {
+        let topics = self.publish.iter().map(|topic| &topic.key);
+        topics.chain(self.parameters.iter().map(|parameter| &parameter.key))
+    }
+}
+
+struct Bridge {
+    client: BlackboardClient,
+    config: Config,
+    socket: Mutex<Option<WebSocket<TcpStream>>>,
+    // values of messages written to outgoing keys, not sent back
+    written: Mutex<HashMap<String, TypedBlackboardValue>>,
+    running: AtomicBool,
+}
+
+impl Bridge {
+    // sends one rosbridge operation, the connection is dropped if it fails
+    fn send(&self, operation: Value) {
+        let mut socket = self.socket.lock().unwrap();
+        if let Some(websocket) = socket.as_mut() {
+            if let Err(e) = websocket.send(Message::Text(operation.to_string())) {
+                warn!("Lost rosbridge: {}", e);
+                *socket = None;
+            }
+        }
+    }
+
+    // sends the current value of an outgoing key
+    fn local_change(&self, key: &str) {
+        let value = match self.client.get_value(key) {
+            Ok(value) => value,
+            // topics and parameters keep their last value
+            Err(e) if e.status == RtStatus::KeyNotFound => return,
+            Err(e) => return warn!("Can not read changed key {}: {}", key, e),
+        };
+        if self.written.lock().unwrap().remove(key).as_ref() == Some(&value) {
+            return;
+        }
+        self.send_value(key, &value);
+    }
+
+    fn send_value(&self, key: &str, value: &TypedBlackboardValue) {
+        for topic in self.config.publish.iter().filter(|topic| topic.key == key) {
+            match messages::to_message(&topic.message_type, value) {
+                Ok(message) => self.send(json!({
+                    "op": "publish",
+                    "topic": topic.topic,
+                    "msg": message,
+                })),
+                Err(e) => warn!("Can not publish {} to {}: {}", key, topic.topic, e),
+            }
+        }
+        for parameter in self
+            .config
+            .parameters
+            .iter()
+            .filter(|parameter| parameter.key == key)
+        {
+            // rosapi takes the value as yaml, json is a subset
+            let plain = serde_json::to_value(value).map(|typed| typed["value"].to_string());
+            match plain {
+                Ok(plain) => self.send(json!({
+                    "op": "call_service",
+                    "service": "/rosapi/set_param",
+                    "args": {"name": parameter.name, "value": plain},
+                })),
+                Err(e) => warn!("Can not set {} from {}: {}", parameter.name, key, e),
+            }
+        }
+    }
+
+    // writes the messages of subscribed topics to their keys
+    fn receive(&self, text: &str) {
+        let operation: Value = match serde_json::from_str(text) {
+            Ok(operation) => operation,
+            Err(e) => return warn!("Invalid message of rosbridge: {}", e),
+        };
+        match operation["op"].as_str() {
+            Some("publish") => {}
+            Some("status") => return warn!("rosbridge: {}", operation["msg"]),
+            _ => return debug!("Ignoring {}", text),
+        }
+        let topics = self
+            .config
+            .subscribe
+            .iter()
+            .filter(|topic| operation["topic"] == topic.topic.as_str());
+        for topic in topics {
+            let value = match messages::from_message(&topic.message_type, &operation["msg"]) {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!("Can not write {} from {}: {}", topic.key, topic.topic, e);
+                    continue;
+                }
+            };
+            if self.config.outgoing().any(|key| *key == topic.key) {
+                self.written
+                    .lock()
+                    .unwrap()
+                    .insert(topic.key.clone(), value.clone());
+            }
+            if let Err(e) = self.client.set_value(&topic.key, &value) {
+                warn!("Can not write {} from {}: {}", topic.key, topic.topic, e);
+            }
+        }
+    }
+
+    fn connect(&self) -> Result<WebSocket<TcpStream>, String> {
+        let address = self.config.address().map_err(String::from)?;
+        let peer = address
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("No address for {}", address))?;
+        let stream =
+            TcpStream::connect_timeout(&peer, CONNECT_TIMEOUT).map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(CONNECT_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        let (mut websocket, _) =
+            tungstenite::client(self.config.url.as_str(), stream).map_err(|e| e.to_string())?;
+        websocket
+            .get_mut()
+            .set_read_timeout(Some(POLL))
+            .map_err(|e| e.to_string())?;
+        Ok(websocket)
+    }
+
+    // announces the topics and sends the current values
+    fn connected(&self, websocket: WebSocket<TcpStream>) {
+        *self.socket.lock().unwrap() = Some(websocket);
+        for topic in &self.config.publish {
+            self.send(json!({"op": "advertise", "topic": topic.topic, "type": topic.message_type}));
+        }
+        for topic in &self.config.subscribe {
+            self.send(json!({"op": "subscribe", "topic": topic.topic, "type": topic.message_type}));
+        }
+        let mut keys: Vec<&String> = self.config.outgoing().collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            if let Ok(value) = self.client.get_value(key) {
+                self.send_value(key, &value);
+            }
+        }
+    }
+
+    // reads from rosbridge until the connection is lost or the bridge stops
+    fn read(&self) {
+        while self.running.load(Ordering::SeqCst) {
+            let message = match self.socket.lock().unwrap().as_mut() {
+                Some(websocket) => websocket.read(),
+                None => return,
+            };
+            match message {
+                Ok(Message::Text(text)) => self.receive(&text),
+                Ok(Message::Close(_)) => {
+                    warn!("rosbridge closed the connection");
+                    break;
+                }
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => {
+                    warn!("Lost rosbridge: {}", e);
+                    break;
+                }
+            }
+        }
+        if let Some(mut websocket) = self.socket.lock().unwrap().take() {
+            let _ = websocket.close(None);
+            let _ = websocket.flush();
+        }
+    }
+
+    // connects again whenever the connection is lost
+    fn run(&self) {
+        while self.running.load(Ordering::SeqCst) {
+            match self.connect() {
+                Ok(websocket) => {
+                    info!("Connected to rosbridge at {}", self.config.url);
+                    self.connected(websocket);
+                    self.read();
+                }
+                Err(e) => debug!("Can not connect to {}: {}", self.config.url, e),
+            }
+            let retry = Instant::now() + RECONNECT;
+            while self.running.load(Ordering::SeqCst) && Instant::now() < retry {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+    }
+}
+
+struct BridgeState {
+    bridge: Arc<Bridge>,
+    subscriptions: Vec<Subscription>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+static BRIDGE_STATE: Mutex<Option<BridgeState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "ros2_bridge",
+    summary = "connects blackboard keys to ROS 2 topics and parameters",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        ros2_bridge_start = start: "i32(caps,cstr)",
+        ros2_bridge_stop = stop: "i32()",
+        ros2_bridge_health = health: "i32()",
+        ros2_bridge_state = state: "i32()",
+        ros2_bridge_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_bridge(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = BRIDGE_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Bridge is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    config.address()?;
+    if config.publish.is_empty() && config.subscribe.is_empty() && config.parameters.is_empty() {
+        return Err(RtError::new(
+            RtStatus::InvalidArgument,
+            "No topics or parameters to connect",
+        ));
+    }
+
+    let bridge = Arc::new(Bridge {
+        client: BlackboardClient::new(interfaces::capabilities::Capabilities::from_raw(caps)),
+        config,
+        socket: Mutex::new(None),
+        written: Mutex::new(HashMap::new()),
+        running: AtomicBool::new(true),
+    });
+
+    let mut keys: Vec<&String> = bridge.config.outgoing().collect();
+    keys.sort();
+    keys.dedup();
+    let (subscriptions, changes) = bridge.client.subscribe_forwarding(keys, "ros2_bridge")?;
+
+    let sending = bridge.clone();
+    let receiving = bridge.clone();
+    let threads = vec![
+        // ends once the subscriptions are dropped
+        std::thread::spawn(move || {
+            for key in changes {
+                sending.local_change(&key);
+            }
+        }),
+        std::thread::spawn(move || receiving.run()),
+    ];
+
+    info!("Connecting to rosbridge at {}", bridge.config.url);
+    *state = Some(BridgeState {
+        bridge,
+        subscriptions,
+        threads,
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "ros2_bridge");
+    match LIFECYCLE.started(catch_panic(|| start_bridge(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting bridge: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_bridge() -> Result<(), RtError> {
+    let state = BRIDGE_STATE.lock().unwrap().take();
+    let state = state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Bridge is not running"))?;
+    state.bridge.running.store(false, Ordering::SeqCst);
+    drop(state.subscriptions);
+    for thread in state.threads {
+        let _ = thread.join();
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_bridge)) {
+        Ok(()) => {
+            info!("Bridge stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping bridge: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_OK` while the bridge runs, `RT_NOT_RUNNING` once it is stopped. A missing rosbridge is
+/// not an error, it is connected again.
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match BRIDGE_STATE.lock().unwrap().as_ref() {
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the bridge, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the url of rosbridge and whether it is connected as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match BRIDGE_STATE.lock().unwrap().as_ref() {
+        Some(state) => json!({
+            "url": state.bridge.config.url,
+            "connected": state.bridge.socket.lock().unwrap().is_some(),
+        }),
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let entries: Vec<BlackboardEntry> = serde_yml::from_str(concat!(
+            "- {key: url, value: 'ws://robot'}\n",
+            "- {key: publish, value: [{key: speed, topic: /speed, type: std_msgs/msg/Float64}]}\n",
+            "- {key: parameters, value: [{key: limit, name: '/controller:max_speed'}]}\n",
+        ))
+        .unwrap();
+        let config = Config::new(&entries).unwrap();
+        assert_eq!(config.address().unwrap(), "robot:9090");
+        assert_eq!(config.publish[0].message_type, "std_msgs/msg/Float64");
+        assert_eq!(
+            config.outgoing().collect::<Vec<_>>(),
+            vec!["speed", "limit"]
+        );
+
+        let entries: Vec<BlackboardEntry> =
+            serde_yml::from_str("[{key: subscribe, value: [{key: speed}]}]").unwrap();
+        assert!(matches!(Config::new(&entries), Err(e) if e.status == RtStatus::InvalidArgument));
+        let config = Config {
+            url: "wss://robot:9090".to_string(),
+            ..Config::default()
+        };
+        assert!(config.address().is_err());
+    }
+}
@@ -0,0 +1,126 @@
+// Conversion between blackboard values and ROS 2 messages as rosbridge writes them in json.
+// The `std_msgs` types carrying one `data` field map to the matching blackboard types, every
+// other message is kept as json.
+use interfaces::blackboard::TypedBlackboardValue;
+use serde_json::{json, Value};
+
+// `std_msgs/msg/Int32` and `std_msgs/Int32` name the same type
+fn short_name(message_type: &str) -> &str {
+    match message_type.strip_prefix("std_msgs/") {
+        Some(name) => name.strip_prefix("msg/").unwrap_or(name),
+        None => "",
+    }
+}
+
+/// The message of `message_type` carrying `value`.
+pub fn to_message(message_type: &str, value: &TypedBlackboardValue) -> Result<Value, String> {
+    let data = match (short_name(message_type), value) {
+        ("", TypedBlackboardValue::Json(message)) => return Ok(message.clone()),
+        ("Int32" | "Int64", TypedBlackboardValue::Int(value)) => json!(value),
+        ("Int64", TypedBlackboardValue::Int64(value)) => json!(value),
+        ("Int64", TypedBlackboardValue::Timestamp(value)) => json!(value),
+        ("Float32" | "Float64", TypedBlackboardValue::Float(value)) => json!(value),
+        ("Float64", TypedBlackboardValue::Double(value)) => json!(value),
+        ("Bool", TypedBlackboardValue::Bool(value)) => json!(value),
+        ("String", TypedBlackboardValue::String(value)) => json!(value),
+        ("Int32MultiArray", TypedBlackboardValue::IntArray(values)) => json!(values),
+        ("Float64MultiArray", TypedBlackboardValue::DoubleArray(values)) => json!(values),
+        _ => {
+            return Err(format!(
+                "A {} value can not be sent as {}",
+                value.type_name(),
+                message_type
+            ))
+        }
+    };
+    // rosbridge fills in the fields left out, like the layout of the arrays
+    Ok(json!({ "data": data }))
+}
+
+/// The value a message of `message_type` is written to the blackboard as.
+pub fn from_message(message_type: &str, message: &Value) -> Result<TypedBlackboardValue, String> {
+    let name = short_name(message_type);
+    if name.is_empty() {
+        return Ok(TypedBlackboardValue::Json(message.clone()));
+    }
+    let data = &message["data"];
+    let invalid = || format!("Invalid {} message {}", message_type, message);
+    Ok(match name {
+        "Int32" => TypedBlackboardValue::Int(
+            data.as_i64()
+                .and_then(|value| i32::try_from(value).ok())
+                .ok_or_else(invalid)?,
+        ),
+        "Int64" => TypedBlackboardValue::Int64(data.as_i64().ok_or_else(invalid)?),
+        "Float32" => TypedBlackboardValue::Float(data.as_f64().ok_or_else(invalid)? as f32),
+        "Float64" => TypedBlackboardValue::Double(data.as_f64().ok_or_else(invalid)?),
+        "Bool" => TypedBlackboardValue::Bool(data.as_bool().ok_or_else(invalid)?),
+        "String" => TypedBlackboardValue::String(data.as_str().ok_or_else(invalid)?.to_string()),
+        "Int32MultiArray" => TypedBlackboardValue::IntArray(
+            serde_json::from_value(data.clone()).map_err(|_| invalid())?,
+        ),
+        "Float64MultiArray" => TypedBlackboardValue::DoubleArray(
+            serde_json::from_value(data.clone()).map_err(|_| invalid())?,
+        ),
+        _ => return Err(format!("{} is not supported, use its fields", message_type)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_message() {
+        assert_eq!(
+            to_message("std_msgs/msg/Int32", &TypedBlackboardValue::Int(3)).unwrap(),
+            json!({"data": 3})
+        );
+        assert_eq!(
+            to_message("std_msgs/Float64", &TypedBlackboardValue::Double(0.5)).unwrap(),
+            json!({"data": 0.5})
+        );
+        assert_eq!(
+            to_message(
+                "std_msgs/msg/Float64MultiArray",
+                &TypedBlackboardValue::DoubleArray(vec![1.0])
+            )
+            .unwrap(),
+            json!({"data": [1.0]})
+        );
+        let twist = json!({"linear": {"x": 1.0}});
+        assert_eq!(
+            to_message(
+                "geometry_msgs/msg/Twist",
+                &TypedBlackboardValue::Json(twist.clone())
+            )
+            .unwrap(),
+            twist
+        );
+        assert!(to_message("std_msgs/msg/Bool", &TypedBlackboardValue::Int(1)).is_err());
+        assert!(to_message("geometry_msgs/msg/Twist", &TypedBlackboardValue::Int(1)).is_err());
+    }
+
+    #[test]
+    fn test_from_message() {
+        assert_eq!(
+            from_message("std_msgs/msg/String", &json!({"data": "dock"})).unwrap(),
+            TypedBlackboardValue::String("dock".to_string())
+        );
+        assert_eq!(
+            from_message(
+                "std_msgs/msg/Int32MultiArray",
+                &json!({"layout": {}, "data": [1, 2]})
+            )
+            .unwrap(),
+            TypedBlackboardValue::IntArray(vec![1, 2])
+        );
+        assert!(from_message("std_msgs/msg/Int32", &json!({"data": 1u64 << 40})).is_err());
+        assert!(from_message("std_msgs/msg/Header", &json!({})).is_err());
+        let pose = json!({"position": {"x": 1.0}});
+        assert_eq!(
+            from_message("geometry_msgs/msg/Pose", &pose).unwrap(),
+            TypedBlackboardValue::Json(pose)
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::testing::Host;
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_ros2_bridge() {
+    // plays rosbridge
+    let rosbridge = TcpListener::bind(("127.0.0.1", 18802)).unwrap();
+    let attributes = vec![
+        BlackboardEntry {
+            key: "url".to_string(),
+            value: BlackboardValue::String("ws://127.0.0.1:18802".to_string()),
+        },
+        BlackboardEntry {
+            key: "publish".to_string(),
+            value: BlackboardValue::Json(serde_json::json!([
+                {"key": "robot/speed", "topic": "/speed", "type": "std_msgs/msg/Int32"}
+            ])),
+        },
+        BlackboardEntry {
+            key: "subscribe".to_string(),
+            value: BlackboardValue::Json(serde_json::json!([
+                {"key": "robot/target", "topic": "/target", "type": "std_msgs/msg/String"}
+            ])),
+        },
+    ];
+    let host = Host::start(&[("ros2_bridge", attributes)]).unwrap();
+    let client = host.client();
+
+    let (stream, _) = rosbridge.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut websocket = tungstenite::accept(stream).unwrap();
+    let mut read = || -> serde_json::Value {
+        serde_json::from_str(websocket.read().unwrap().to_text().unwrap()).unwrap()
+    };
+    assert_eq!(
+        read(),
+        serde_json::json!({"op": "advertise", "topic": "/speed", "type": "std_msgs/msg/Int32"})
+    );
+    assert_eq!(
+        read(),
+        serde_json::json!({"op": "subscribe", "topic": "/target", "type": "std_msgs/msg/String"})
+    );
+    client.set_i32("robot/speed", 3).unwrap();
+    assert_eq!(
+        read(),
+        serde_json::json!({"op": "publish", "topic": "/speed", "msg": {"data": 3}})
+    );
+
+    let message = serde_json::json!({"op": "publish", "topic": "/target", "msg": {"data": "dock"}});
+    websocket.send(tungstenite::Message::Text(message.to_string())).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while client.get_string("robot/target").is_err() {
+        assert!(Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(client.get_string("robot/target").unwrap(), "dock");
+
+    assert!(host.stop().is_empty());
+}
@@ -0,0 +1,200 @@
+// Minimal HTTP/1.1 client for the api of the webinterface, one connection per request. Https is
+// not supported, use the control socket or a tunnel for remote loaders.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+pub struct Client {
+    address: String, // host:port
+    prefix: String,  // path in front of the api, without a trailing slash
+    token: Option<String>,
+}
+
+/// Status line, headers and the body reader of a response.
+pub struct Response {
+    pub status: u16,
+    pub reason: String,
+    body: Box<dyn BufRead>,
+}
+
+impl Response {
+    /// Reads the whole body.
+    pub fn text(mut self) -> Result<String, String> {
+        let mut text = String::new();
+        self.body
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Can not read the response: {}", e))?;
+        Ok(text)
+    }
+
+    /// Calls `on_line` with every line of the body until the server closes the connection or
+    /// `on_line` returns false.
+    pub fn lines(self, mut on_line: impl FnMut(&str) -> bool) -> Result<(), String> {
+        for line in self.body.lines() {
+            let line = line.map_err(|e| format!("Can not read the response: {}", e))?;
+            if !on_line(&line) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Client of a webinterface at a url like `http://localhost:8080`.
+    pub fn new(url: &str, token: Option<String>) -> Result<Self, String> {
+        let rest = match url.split_once("://") {
+            Some(("http", rest)) => rest,
+            Some((scheme, _)) => return Err(format!("Unsupported scheme '{}', use http", scheme)),
+            None => url,
+        };
+        let (address, prefix) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        if address.is_empty() {
+            return Err(format!("No host in url '{}'", url));
+        }
+        let address = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:80", address)
+        };
+        Ok(Client {
+            address,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    /// Sends a request and returns the body, fails for a status of 400 and above.
+    pub fn call(&self, method: &str, path: &str, body: Option<&str>) -> Result<String, String> {
+        let response = self.send(method, path, body)?;
+        if response.status >= 400 {
+            let (status, reason) = (response.status, response.reason.clone());
+            let text = response.text().unwrap_or_default();
+            return Err(format!("{} {}: {}", status, reason, text.trim()));
+        }
+        response.text()
+    }
+
+    /// Sends a request with a json body, if any.
+    pub fn send(&self, method: &str, path: &str, body: Option<&str>) -> Result<Response, String> {
+        let mut stream = TcpStream::connect(&self.address)
+            .map_err(|e| format!("Can not connect to {}: {}", self.address, e))?;
+        let mut request = format!(
+            "{} {}{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nAccept: */*\r\n",
+            method, self.prefix, path, self.address
+        );
+        if let Some(token) = &self.token {
+            request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+        }
+        let body = body.unwrap_or_default();
+        if !body.is_empty() {
+            request.push_str("Content-Type: application/json\r\n");
+        }
+        request.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("Can not send the request: {}", e))?;
+        read_response(BufReader::new(stream))
+    }
+}
+
+fn read_response<R: BufRead + 'static>(mut reader: R) -> Result<Response, String> {
+    let read_line = |reader: &mut R| -> Result<String, String> {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Can not read the response: {}", e))?;
+        Ok(line.trim_end().to_string())
+    };
+    let status_line = read_line(&mut reader)?;
+    let mut parts = status_line.splitn(3, ' ');
+    let status = match (parts.next(), parts.next()) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/") => status.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Invalid response '{}'", status_line))?;
+    let reason = parts.next().unwrap_or_default().to_string();
+
+    let mut chunked = false;
+    let mut length = None;
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+                "content-length" => length = value.parse::<u64>().ok(),
+                _ => {}
+            }
+        }
+    }
+    let body: Box<dyn BufRead> = match (chunked, length) {
+        (true, _) => Box::new(BufReader::new(Chunked::new(reader))),
+        (false, Some(length)) => Box::new(reader.take(length)),
+        (false, None) => Box::new(reader),
+    };
+    Ok(Response {
+        status,
+        reason,
+        body,
+    })
+}
+
+/// Body of a response with `Transfer-Encoding: chunked`, streamed responses like `/events`.
+pub struct Chunked<R: BufRead> {
+    inner: R,
+    remaining: usize, // bytes left in the current chunk
+    done: bool,
+}
+
+impl<R: BufRead> Chunked<R> {
+    pub fn new(inner: R) -> Self {
+        Chunked {
+            inner,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    fn next_chunk(&mut self) -> std::io::Result<()> {
+        let mut line = String::new();
+        self.inner.read_line(&mut line)?;
+        // the line ending of the previous chunk
+        if line.trim().is_empty() {
+            line.clear();
+            self.inner.read_line(&mut line)?;
+        }
+        let size = line.trim().split(';').next().unwrap_or_default();
+        self.remaining = usize::from_str_radix(size, 16).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid chunk size '{}'", line.trim()),
+            )
+        })?;
+        self.done = self.remaining == 0;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 && !self.done {
+            self.next_chunk()?;
+        }
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.remaining);
+        let read = self.inner.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read;
+        Ok(read)
+    }
+}
@@ -0,0 +1,513 @@
+// Command line client of a running rtime, talking to the webinterface or to the control socket
+// of the loader.
+mod http;
+mod socket;
+
+use clap::{Parser, Subcommand};
+use http::Client;
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::project::{Project, START_PROJECT_KEY};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "rtimectl",
+    version = "0.1.0",
+    about = "Controls a running rtime"
+)]
+struct Args {
+    /// Url of the webinterface
+    #[arg(long, default_value = "http://localhost:8080")]
+    url: String,
+    /// Token of the webinterface, sent as `Authorization: Bearer <token>`
+    #[arg(long)]
+    token: Option<String>,
+    /// Control socket of the loader, used instead of the webinterface
+    #[arg(long)]
+    socket: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Read and write the blackboard
+    #[command(subcommand)]
+    Bb(BbCommand),
+    /// Print the loaded libraries and whether their services run
+    Components,
+    /// Restart a running service and the services requiring it
+    Restart { service: String },
+    /// Run projects
+    #[command(subcommand)]
+    Project(ProjectCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum BbCommand {
+    /// Print the value of a key as {"type": ..., "value": ...}
+    Get { key: String },
+    /// Write a yaml value, e.g. 42, 1.5, true, hello or [1, 2]
+    Set {
+        key: String,
+        value: String,
+        /// Blackboard type of the value, e.g. int64 or json, guessed from the value otherwise
+        #[arg(long = "type")]
+        value_type: Option<String>,
+    },
+    /// Print the changes of some keys, or of all, until interrupted
+    Watch { keys: Vec<String> },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProjectCommand {
+    /// Start the project described by a yaml or json file
+    Start { file: PathBuf },
+}
+
+enum Target {
+    Http(Client),
+    Socket(PathBuf),
+}
+
+fn execute(target: &Target, command: &Command, out: &mut dyn Write) -> Result<(), String> {
+    let output = match (target, command) {
+        (Target::Http(client), Command::Bb(BbCommand::Get { key })) => {
+            client.call("GET", &format!("/api/blackboard/{}", encode(key)), None)?
+        }
+        (Target::Socket(path), Command::Bb(BbCommand::Get { key })) => {
+            socket::command(path, &format!("bb get {}", key))?
+        }
+        (
+            Target::Http(client),
+            Command::Bb(BbCommand::Set {
+                key,
+                value,
+                value_type,
+            }),
+        ) => {
+            let value = typed_value(value, value_type.as_deref())?;
+            let body = serde_json::to_string(&value).map_err(|e| e.to_string())?;
+            client.call(
+                "PUT",
+                &format!("/api/blackboard/{}", encode(key)),
+                Some(&body),
+            )?
+        }
+        (
+            Target::Socket(_),
+            Command::Bb(BbCommand::Set {
+                value_type: Some(_),
+                ..
+            }),
+        ) => return Err("--type needs the webinterface".to_string()),
+        (Target::Socket(path), Command::Bb(BbCommand::Set { key, value, .. })) => {
+            socket::command(path, &format!("bb set {} {}", key, value))?
+        }
+        (Target::Http(client), Command::Bb(BbCommand::Watch { keys })) => {
+            return watch(client, keys, out)
+        }
+        (Target::Socket(_), Command::Bb(BbCommand::Watch { .. })) => {
+            return Err("bb watch needs the webinterface".to_string())
+        }
+        (Target::Http(client), Command::Components) => {
+            let components = client.call("GET", "/api/components", None)?;
+            match serde_json::from_str::<Value>(&components) {
+                Ok(components) => {
+                    serde_json::to_string_pretty(&components).unwrap_or(components.to_string())
+                }
+                Err(_) => components,
+            }
+        }
+        (Target::Socket(path), Command::Components) => socket::command(path, "list")?,
+        (Target::Http(client), Command::Restart { service }) => client.call(
+            "POST",
+            &format!("/api/runtime/restart/{}", encode(service)),
+            None,
+        )?,
+        (Target::Socket(path), Command::Restart { service }) => {
+            socket::command(path, &format!("restart {}", service))?
+        }
+        (target, Command::Project(ProjectCommand::Start { file })) => {
+            let description = std::fs::read_to_string(file)
+                .map_err(|e| format!("Can not read {}: {}", file.display(), e))?;
+            let project = Project::parse(&description)?;
+            let description = serde_json::to_string(&project).map_err(|e| e.to_string())?;
+            match target {
+                Target::Http(client) => {
+                    let value = TypedBlackboardValue::String(description);
+                    let body = serde_json::to_string(&value).map_err(|e| e.to_string())?;
+                    let path = format!("/api/blackboard/{}", START_PROJECT_KEY);
+                    client.call("PUT", &path, Some(&body))?
+                }
+                // a json string is a quoted yaml string
+                Target::Socket(path) => {
+                    let value = Value::String(description).to_string();
+                    socket::command(path, &format!("bb set {} {}", START_PROJECT_KEY, value))?
+                }
+            }
+        }
+    };
+    if !output.is_empty() {
+        writeln!(out, "{}", output.trim_end()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// prints the data of the server-sent events, one change per line
+fn watch(client: &Client, keys: &[String], out: &mut dyn Write) -> Result<(), String> {
+    let path = if keys.is_empty() {
+        "/events".to_string()
+    } else {
+        let keys: Vec<String> = keys.iter().map(|key| encode(key)).collect();
+        format!("/events?keys={}", keys.join(","))
+    };
+    let response = client.send("GET", &path, None)?;
+    if response.status >= 400 {
+        return Err(format!("{} {}", response.status, response.reason));
+    }
+    let mut result = Ok(());
+    response.lines(|line| match line.strip_prefix("data:") {
+        Some(data) => {
+            result = writeln!(out, "{}", data.trim()).and_then(|_| out.flush());
+            result.is_ok()
+        }
+        None => true,
+    })?;
+    result.map_err(|e| e.to_string())
+}
+
+/// Value of `bb set`: a yaml value of the given type, or an int, int64, double, bool,
+/// int_array, double_array, string or json guessed from the value.
+fn typed_value(value: &str, value_type: Option<&str>) -> Result<TypedBlackboardValue, String> {
+    if value_type == Some("string") {
+        return Ok(TypedBlackboardValue::String(value.to_string()));
+    }
+    let parsed: Value =
+        serde_yml::from_str(value).map_err(|e| format!("Invalid value '{}': {}", value, e))?;
+    if let Some(value_type) = value_type {
+        return serde_json::from_value(json!({"type": value_type, "value": parsed}))
+            .map_err(|e| format!("Invalid {} '{}': {}", value_type, value, e));
+    }
+    let as_int = |value: &Value| value.as_i64().and_then(|v| i32::try_from(v).ok());
+    Ok(match parsed {
+        Value::Number(number) => match number.as_i64() {
+            Some(v) => i32::try_from(v)
+                .map(TypedBlackboardValue::Int)
+                .unwrap_or(TypedBlackboardValue::Int64(v)),
+            None => TypedBlackboardValue::Double(number.as_f64().unwrap_or_default()),
+        },
+        Value::Bool(flag) => TypedBlackboardValue::Bool(flag),
+        Value::String(text) => TypedBlackboardValue::String(text),
+        Value::Array(items)
+            if !items.is_empty() && items.iter().all(|item| as_int(item).is_some()) =>
+        {
+            TypedBlackboardValue::IntArray(items.iter().filter_map(as_int).collect())
+        }
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_number) => {
+            TypedBlackboardValue::DoubleArray(items.iter().filter_map(Value::as_f64).collect())
+        }
+        Value::Null => TypedBlackboardValue::String(value.to_string()),
+        other => TypedBlackboardValue::Json(other),
+    })
+}
+
+// percent-encodes a path segment or query value
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'*' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let target = match args.socket {
+        Some(path) => Target::Socket(path),
+        None => match Client::new(&args.url, args.token) {
+            Ok(client) => Target::Http(client),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    match execute(&target, &args.command, &mut std::io::stdout()) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::os::unix::net::UnixListener;
+    use std::thread::JoinHandle;
+
+    // answers one request with `response` and returns the request
+    fn serve_http(response: String) -> (Client, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8(body).unwrap());
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request
+        });
+        (
+            Client::new(&url, Some("secret".to_string())).unwrap(),
+            handle,
+        )
+    }
+
+    // response with a chunked body, one chunk per part
+    fn chunked(headers: &str, parts: &[&str]) -> String {
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\n{}Transfer-Encoding: chunked\r\n\r\n",
+            headers
+        );
+        for part in parts {
+            response.push_str(&format!("{:x}\r\n{}\r\n", part.len(), part));
+        }
+        response + "0\r\n\r\n"
+    }
+
+    // answers the commands of one connection with `replies` and returns the commands
+    fn serve_socket(
+        name: &str,
+        replies: &'static [&'static str],
+    ) -> (PathBuf, JoinHandle<Vec<String>>) {
+        let path =
+            std::env::temp_dir().join(format!("rtimectl-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut commands = Vec::new();
+            for (line, reply) in BufReader::new(stream).lines().zip(replies) {
+                commands.push(line.unwrap());
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+            commands
+        });
+        (path, handle)
+    }
+
+    fn run(target: &Target, command: Command) -> Result<String, String> {
+        let mut out = Vec::new();
+        execute(target, &command, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_client_url() {
+        assert!(Client::new("http://localhost:8080", None).is_ok());
+        assert!(Client::new("localhost", None).is_ok());
+        assert_eq!(
+            Client::new("https://localhost", None).err(),
+            Some("Unsupported scheme 'https', use http".to_string())
+        );
+        assert!(Client::new("http://", None).is_err());
+    }
+
+    #[test]
+    fn test_typed_value() {
+        assert_eq!(typed_value("42", None), Ok(TypedBlackboardValue::Int(42)));
+        assert_eq!(
+            typed_value("5000000000", None),
+            Ok(TypedBlackboardValue::Int64(5000000000))
+        );
+        assert_eq!(
+            typed_value("1.5", None),
+            Ok(TypedBlackboardValue::Double(1.5))
+        );
+        assert_eq!(
+            typed_value("true", None),
+            Ok(TypedBlackboardValue::Bool(true))
+        );
+        assert_eq!(
+            typed_value("hello", None),
+            Ok(TypedBlackboardValue::String("hello".to_string()))
+        );
+        assert_eq!(
+            typed_value("[1, 2]", None),
+            Ok(TypedBlackboardValue::IntArray(vec![1, 2]))
+        );
+        assert_eq!(
+            typed_value("[1, 2.5]", None),
+            Ok(TypedBlackboardValue::DoubleArray(vec![1.0, 2.5]))
+        );
+        assert_eq!(
+            typed_value("{a: 1}", None),
+            Ok(TypedBlackboardValue::Json(json!({"a": 1})))
+        );
+        assert_eq!(
+            typed_value("42", Some("string")),
+            Ok(TypedBlackboardValue::String("42".to_string()))
+        );
+        assert_eq!(
+            typed_value("42", Some("int64")),
+            Ok(TypedBlackboardValue::Int64(42))
+        );
+        assert!(typed_value("hello", Some("int")).is_err());
+    }
+
+    #[test]
+    fn test_http() {
+        let (client, server) =
+            serve_http(chunked("", &["{\"type\": \"int", "\", \"value", "\": 42}"]));
+        let target = Target::Http(client);
+        let output = run(
+            &target,
+            Command::Bb(BbCommand::Get {
+                key: "answer".to_string(),
+            }),
+        );
+        assert_eq!(
+            output,
+            Ok("{\"type\": \"int\", \"value\": 42}\n".to_string())
+        );
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /api/blackboard/answer HTTP/1.1\r\n"));
+        assert!(request.contains("Authorization: Bearer secret\r\n"));
+
+        let (client, server) =
+            serve_http("HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nnull".to_string());
+        let target = Target::Http(client);
+        let set = BbCommand::Set {
+            key: "answer".to_string(),
+            value: "42".to_string(),
+            value_type: None,
+        };
+        assert_eq!(run(&target, Command::Bb(set)), Ok("null\n".to_string()));
+        let request = server.join().unwrap();
+        assert!(request.starts_with("PUT /api/blackboard/answer HTTP/1.1\r\n"));
+        assert!(request.ends_with("\r\n\r\n{\"type\":\"int\",\"value\":42}"));
+
+        let (client, server) = serve_http(
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 27\r\n\r\nUnknown service 'unknown'\r\n"
+                .to_string(),
+        );
+        let target = Target::Http(client);
+        let restart = Command::Restart {
+            service: "unknown".to_string(),
+        };
+        assert_eq!(
+            run(&target, restart),
+            Err("404 Not Found: Unknown service 'unknown'".to_string())
+        );
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("POST /api/runtime/restart/unknown HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_watch() {
+        let (client, server) = serve_http(chunked(
+            "Content-Type: text/event-stream\r\n",
+            &[
+                "id: 1\nevent: change\ndata: {\"key\":\"answer\",\"value\":{\"type\":\"int\",\"value\":42}}\n\n",
+                ": heartbeat\n",
+                "id: 2\nevent: change\ndata: {\"key\":\"answer\",\"value\":null}\n\n",
+            ],
+        ));
+        let target = Target::Http(client);
+        let watch = BbCommand::Watch {
+            keys: vec!["answer".to_string(), "robot/*".to_string()],
+        };
+        let output = run(&target, Command::Bb(watch)).unwrap();
+        assert_eq!(
+            output,
+            "{\"key\":\"answer\",\"value\":{\"type\":\"int\",\"value\":42}}\n\
+             {\"key\":\"answer\",\"value\":null}\n"
+        );
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("GET /events?keys=answer,robot%2F* HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_socket() {
+        let (path, server) = serve_socket(
+            "commands",
+            &["webinterface\nok\n", "error: Unknown service 'x'\n"],
+        );
+        let target = Target::Socket(path.clone());
+        let restart = Command::Restart {
+            service: "webinterface".to_string(),
+        };
+        assert_eq!(run(&target, restart), Ok("webinterface\n".to_string()));
+        let restart = Command::Restart {
+            service: "x".to_string(),
+        };
+        // every command has its own connection, the server answers the first only
+        assert!(run(&target, restart).is_err());
+        assert_eq!(
+            server.join().unwrap(),
+            vec!["restart webinterface".to_string()]
+        );
+
+        let watch = Command::Bb(BbCommand::Watch { keys: Vec::new() });
+        assert_eq!(
+            run(&target, watch),
+            Err("bb watch needs the webinterface".to_string())
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_project_start() {
+        let file =
+            std::env::temp_dir().join(format!("rtimectl-project-{}.yaml", std::process::id()));
+        std::fs::write(&file, "name: demo\nskills: [greet]\n").unwrap();
+        let (path, server) = serve_socket("project", &["ok\n"]);
+        let target = Target::Socket(path.clone());
+        let start = Command::Project(ProjectCommand::Start { file: file.clone() });
+        assert_eq!(run(&target, start), Ok(String::new()));
+        assert_eq!(
+            server.join().unwrap(),
+            vec![
+                r#"bb set start_project "{\"name\":\"demo\",\"skills\":[\"greet\"]}""#.to_string()
+            ]
+        );
+
+        std::fs::write(&file, "skills: 42\n").unwrap();
+        let start = Command::Project(ProjectCommand::Start { file: file.clone() });
+        assert!(run(&target, start)
+            .unwrap_err()
+            .starts_with("Invalid project"));
+        let _ = std::fs::remove_file(file);
+        let _ = std::fs::remove_file(path);
+    }
+}
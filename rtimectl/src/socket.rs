@@ -0,0 +1,26 @@
+// Client of the control socket of a loader, see `loader/src/control.rs`.
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Sends one command and returns the lines of the reply in front of its final `ok`, or the
+/// reason of its `error: <reason>` line.
+pub fn command(path: &Path, command: &str) -> Result<String, String> {
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| format!("Can not connect to {}: {}", path.display(), e))?;
+    stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .map_err(|e| format!("Can not send the command: {}", e))?;
+    let mut output: Vec<String> = Vec::new();
+    for line in BufReader::new(stream).lines() {
+        let line = line.map_err(|e| format!("Can not read the reply: {}", e))?;
+        if line == "ok" {
+            return Ok(output.join("\n"));
+        }
+        if let Some(reason) = line.strip_prefix("error: ") {
+            return Err(reason.to_string());
+        }
+        output.push(line);
+    }
+    Err("The loader closed the connection without a reply".to_string())
+}
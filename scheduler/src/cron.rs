@@ -0,0 +1,169 @@
+// Cron expressions with the fields `minute hour day-of-month month day-of-week`. A field is `*`,
+// a number, a range `1-5` or a list `1,3`, each with an optional step like `*/15`. Sunday is 0
+// or 7. As in cron, a day matches if day of month or day of week match, when both are given.
+
+// the next match is searched up to a leap year ahead
+const SEARCHED_MINUTES: i64 = 366 * 24 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+// the bits of the values a field allows
+fn field(text: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for item in text.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid step in '{}'", item))?,
+            ),
+            None => (item, 1),
+        };
+        let number = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .ok_or_else(|| format!("'{}' is not within {}-{}", value, min, max))
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (number(first)?, number(last)?),
+            // `5/10` runs from 5 to the end
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if first > last {
+            return Err(format!("Empty range '{}'", item));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("'{}' does not have 5 fields", expression));
+        };
+        let mut weekday_bits = field(weekdays, 0, 7)?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Cron {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            // like cron, `*/2` counts as unrestricted
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+
+    fn matches(&self, time: &libc::tm) -> bool {
+        let set = |bits: u64, value: i32| bits & (1 << value) != 0;
+        let day = set(self.days, time.tm_mday);
+        let weekday = set(self.weekdays, time.tm_wday);
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        day && set(self.minutes, time.tm_min)
+            && set(self.hours, time.tm_hour)
+            && set(self.months, time.tm_mon + 1)
+    }
+
+    /// The first matching minute after `after`, both as seconds since the unix epoch. None if
+    /// there is none within a year, like for `0 0 30 2 *`.
+    pub fn next_after(&self, after: i64) -> Option<i64> {
+        let first = (after / 60 + 1) * 60;
+        (0..SEARCHED_MINUTES)
+            .map(|minute| first + minute * 60)
+            .find(|time| local_time(*time).is_some_and(|tm| self.matches(&tm)))
+    }
+}
+
+fn local_time(time: i64) -> Option<libc::tm> {
+    let time = time as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let converted = unsafe { libc::localtime_r(&time, &mut tm) };
+    (!converted.is_null()).then_some(tm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tm(minute: i32, hour: i32, day: i32, month: i32, weekday: i32) -> libc::tm {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        (tm.tm_min, tm.tm_hour, tm.tm_mday, tm.tm_mon, tm.tm_wday) =
+            (minute, hour, day, month - 1, weekday);
+        tm
+    }
+
+    #[test]
+    fn test_parse() {
+        let cron = Cron::parse("*/15 8-17 * * 1-5").unwrap();
+        assert!(cron.matches(&tm(45, 8, 3, 6, 1)));
+        assert!(!cron.matches(&tm(50, 8, 3, 6, 1)));
+        assert!(!cron.matches(&tm(0, 18, 3, 6, 1)));
+        assert!(!cron.matches(&tm(0, 8, 3, 6, 0)));
+
+        // day of month or Sunday
+        let cron = Cron::parse("0 0 1 * 7").unwrap();
+        assert!(cron.matches(&tm(0, 0, 1, 2, 3)));
+        assert!(cron.matches(&tm(0, 0, 9, 2, 0)));
+        assert!(!cron.matches(&tm(0, 0, 9, 2, 1)));
+
+        assert_eq!(
+            Cron::parse("@daily").unwrap(),
+            Cron::parse("0 0 * * *").unwrap()
+        );
+        assert_eq!(
+            Cron::parse("5/20 * * * *").unwrap().minutes,
+            1 << 5 | 1 << 25 | 1 << 45
+        );
+
+        for invalid in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(Cron::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_next_after() {
+        let every_minute = Cron::parse("* * * * *").unwrap();
+        assert_eq!(every_minute.next_after(120), Some(180));
+        assert_eq!(every_minute.next_after(150), Some(180));
+        assert_eq!(Cron::parse("0 0 30 2 *").unwrap().next_after(0), None);
+    }
+}
@@ -0,0 +1,377 @@
+// Fires blackboard keys periodically or at cron times, see `interfaces::scheduler`. Every time a
+// key fires, the number of times it fired is written to it.
+mod cron;
+
+use cron::Cron;
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{debug, error, info, warn};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// how long the timer sleeps without schedules
+const IDLE: Duration = Duration::from_secs(60);
+
+enum Trigger {
+    Periodic(Duration),
+    Cron(Cron, i64), // and the minute it fires next, in seconds since the unix epoch
+}
+
+struct Job {
+    trigger: Trigger,
+    due: Instant,
+    ticks: i32,
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64)
+}
+
+// the instant of a time given in seconds since the unix epoch
+fn instant_of(time: i64) -> Instant {
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let at = Duration::from_secs(time.max(0) as u64);
+    Instant::now() + at.saturating_sub(since)
+}
+
+impl Job {
+    fn periodic(period: Duration) -> Self {
+        Job {
+            trigger: Trigger::Periodic(period),
+            due: Instant::now() + period,
+            ticks: 0,
+        }
+    }
+
+    fn cron(expression: &str) -> Result<Self, RtError> {
+        let cron =
+            Cron::parse(expression).map_err(|e| RtError::new(RtStatus::InvalidArgument, e))?;
+        let next = cron.next_after(unix_now()).ok_or_else(|| {
+            RtError::new(
+                RtStatus::InvalidArgument,
+                format!("'{}' does not fire within a year", expression),
+            )
+        })?;
+        Ok(Job {
+            trigger: Trigger::Cron(cron, next),
+            due: instant_of(next),
+            ticks: 0,
+        })
+    }
+
+    // moves the job to its next time, false if there is none
+    fn advance(&mut self, now: Instant) -> bool {
+        match &mut self.trigger {
+            // a late timer skips the missed periods instead of catching up
+            Trigger::Periodic(period) => {
+                self.due += *period;
+                if self.due <= now {
+                    self.due = now + *period;
+                }
+                true
+            }
+            Trigger::Cron(cron, next) => match cron.next_after(*next) {
+                Some(after) => {
+                    *next = after;
+                    self.due = instant_of(after);
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+struct Scheduler {
+    client: BlackboardClient,
+    jobs: Mutex<HashMap<String, Job>>,
+    changed: Condvar,
+    running: AtomicBool,
+}
+
+impl Scheduler {
+    fn schedule(&self, key: &str, job: Job) {
+        self.jobs.lock().unwrap().insert(key.to_string(), job);
+        self.changed.notify_all();
+    }
+
+    fn cancel(&self, key: &str) -> Result<(), RtError> {
+        self.jobs.lock().unwrap().remove(key).ok_or_else(|| {
+            RtError::new(RtStatus::KeyNotFound, format!("{} is not scheduled", key))
+        })?;
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    // fires the due keys until the scheduler stops
+    fn run(&self) {
+        let mut jobs = self.jobs.lock().unwrap();
+        while self.running.load(Ordering::SeqCst) {
+            let now = Instant::now();
+            let mut fired = Vec::new();
+            jobs.retain(|key, job| {
+                if job.due > now {
+                    return true;
+                }
+                job.ticks = job.ticks.wrapping_add(1);
+                fired.push((key.clone(), job.ticks));
+                job.advance(now)
+            });
+            if !fired.is_empty() {
+                // written unlocked, a subscriber may schedule from its callback
+                drop(jobs);
+                for (key, ticks) in fired {
+                    if let Err(e) = self.client.set_i32(&key, ticks) {
+                        warn!("Can not fire {}: {}", key, e);
+                    }
+                }
+                jobs = self.jobs.lock().unwrap();
+                continue;
+            }
+            let wait = jobs
+                .values()
+                .map(|job| job.due.saturating_duration_since(now))
+                .min()
+                .unwrap_or(IDLE);
+            jobs = self.changed.wait_timeout(jobs, wait).unwrap().0;
+        }
+    }
+}
+
+struct SchedulerState {
+    scheduler: Arc<Scheduler>,
+    timer: JoinHandle<()>,
+}
+
+static SCHEDULER_STATE: Mutex<Option<SchedulerState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "scheduler",
+    summary = "fires blackboard keys periodically or at cron times",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        scheduler_start = start: "i32(caps,cstr)",
+        scheduler_stop = stop: "i32()",
+        scheduler_health = health: "i32()",
+        scheduler_state = state: "i32()",
+        scheduler_health_status = health_status: "i32(*mut char,i32)",
+        schedule_periodic = schedule_periodic: "i32(cstr,i32)",
+        schedule_cron = schedule_cron: "i32(cstr,cstr)",
+        schedule_cancel = schedule_cancel: "i32(cstr)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+// the schedules of the attributes `periodic: {key: period_ms}` and `cron: {key: expression}`
+fn configured_jobs(attributes: *const c_char) -> Result<Vec<(String, Job)>, RtError> {
+    if attributes.is_null() {
+        return Ok(Vec::new());
+    }
+    let attributes = unsafe { CStr::from_ptr(attributes) }
+        .to_str()
+        .map_err(|e| format!("Cannot convert incoming attributes to string: {}", e))?;
+    let entries: Vec<BlackboardEntry> = serde_yml::from_str(attributes)
+        .map_err(|e| RtError::new(RtStatus::InvalidArgument, e.to_string()))?;
+    let invalid = |key: &str, e: String| {
+        RtError::new(
+            RtStatus::InvalidArgument,
+            format!("Invalid schedule {}: {}", key, e),
+        )
+    };
+    let mut jobs = Vec::new();
+    for entry in entries {
+        let BlackboardValue::Json(serde_json::Value::Object(schedules)) = &entry.value else {
+            continue;
+        };
+        for (key, schedule) in schedules {
+            let job = match (entry.key.as_str(), schedule) {
+                ("periodic", schedule) => match schedule.as_u64() {
+                    Some(period) if period > 0 => Job::periodic(Duration::from_millis(period)),
+                    _ => return Err(invalid(key, format!("{} is no period", schedule))),
+                },
+                ("cron", serde_json::Value::String(expression)) => {
+                    Job::cron(expression).map_err(|e| invalid(key, e.message))?
+                }
+                _ => return Err(invalid(key, format!("{} is no schedule", schedule))),
+            };
+            jobs.push((key.clone(), job));
+        }
+    }
+    Ok(jobs)
+}
+
+fn start_scheduler(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = SCHEDULER_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "Scheduler is already running",
+        ));
+    }
+    let scheduler = Arc::new(Scheduler {
+        client: BlackboardClient::new(interfaces::capabilities::Capabilities::from_raw(caps)),
+        jobs: Mutex::new(configured_jobs(attributes)?.into_iter().collect()),
+        changed: Condvar::new(),
+        running: AtomicBool::new(true),
+    });
+    let timer = scheduler.clone();
+    *state = Some(SchedulerState {
+        timer: std::thread::spawn(move || timer.run()),
+        scheduler,
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "scheduler");
+    match LIFECYCLE.started(catch_panic(|| start_scheduler(caps, attributes))) {
+        Ok(()) => {
+            info!("Scheduler started");
+            0
+        }
+        Err(e) => {
+            error!("Error starting scheduler: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_scheduler() -> Result<(), RtError> {
+    let state = SCHEDULER_STATE.lock().unwrap().take();
+    let state =
+        state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "Scheduler is not running"))?;
+    state.scheduler.running.store(false, Ordering::SeqCst);
+    // taken while the timer waits, so the notification is not lost
+    drop(state.scheduler.jobs.lock().unwrap());
+    state.scheduler.changed.notify_all();
+    let _ = state.timer.join();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_scheduler)) {
+        Ok(()) => {
+            info!("Scheduler stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping scheduler: {}", e);
+            e.record()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match SCHEDULER_STATE.lock().unwrap().as_ref() {
+        Some(state) if state.timer.is_finished() => {
+            RtError::new(RtStatus::Error, "Timer ended unexpectedly").record()
+        }
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the scheduler, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the scheduled keys with the times they fired as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match SCHEDULER_STATE.lock().unwrap().as_ref() {
+        Some(state) => {
+            let jobs = state.scheduler.jobs.lock().unwrap();
+            let ticks: serde_json::Map<String, serde_json::Value> = jobs
+                .iter()
+                .map(|(key, job)| (key.clone(), job.ticks.into()))
+                .collect();
+            serde_json::json!({ "scheduled": ticks })
+        }
+        None => serde_json::json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+// runs `schedule` with the running scheduler and the key, for the capabilities
+fn with_scheduler(
+    key: *const c_char,
+    schedule: impl FnOnce(&Scheduler, &str) -> Result<(), RtError>,
+) -> c_int {
+    if key.is_null() {
+        return RtStatus::NullArgument.code();
+    }
+    let key = unsafe { CStr::from_ptr(key) }.to_string_lossy();
+    let scheduler = match SCHEDULER_STATE.lock().unwrap().as_ref() {
+        Some(state) => state.scheduler.clone(),
+        None => return RtError::new(RtStatus::NotRunning, "Scheduler is not running").record(),
+    };
+    match schedule(&scheduler, &key) {
+        Ok(()) => RtStatus::Ok.code(),
+        Err(e) => {
+            debug!("{}", e);
+            e.record()
+        }
+    }
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn schedule_periodic(key: *const c_char, period_ms: c_int) -> c_int {
+    with_scheduler(key, |scheduler, key| {
+        let period = u64::try_from(period_ms)
+            .ok()
+            .filter(|period| *period > 0)
+            .ok_or_else(|| {
+                RtError::new(RtStatus::InvalidArgument, "The period must be positive")
+            })?;
+        scheduler.schedule(key, Job::periodic(Duration::from_millis(period)));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn schedule_cron(key: *const c_char, expression: *const c_char) -> c_int {
+    if expression.is_null() {
+        return RtStatus::NullArgument.code();
+    }
+    let expression = unsafe { CStr::from_ptr(expression) }.to_string_lossy();
+    with_scheduler(key, |scheduler, key| {
+        scheduler.schedule(key, Job::cron(&expression)?);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn schedule_cancel(key: *const c_char) -> c_int {
+    with_scheduler(key, |scheduler, key| scheduler.cancel(key))
+}
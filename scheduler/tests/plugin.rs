@@ -0,0 +1,39 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::status::RtStatus;
+use interfaces::testing::Host;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_scheduler() {
+    let attributes = vec![BlackboardEntry {
+        key: "periodic".to_string(),
+        value: BlackboardValue::Json(serde_json::json!({"timer/fast": 20})),
+    }];
+    let host = Host::start(&[("scheduler", attributes)]).unwrap();
+    let client = host.client();
+    let wait_for = |key: &str, ticks: i32| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.get_i32(key).map_or(true, |value| value < ticks) {
+            assert!(Instant::now() < deadline, "{} did not fire", key);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+    wait_for("timer/fast", 2);
+
+    let caps = host.caps().unwrap();
+    interfaces::scheduler::schedule_periodic(&caps, "timer/slow", Duration::from_millis(30))
+        .unwrap();
+    wait_for("timer/slow", 1);
+    interfaces::scheduler::schedule_cron(&caps, "timer/hourly", "@hourly").unwrap();
+    let invalid = interfaces::scheduler::schedule_cron(&caps, "timer/never", "0 0 30 2 *");
+    assert!(matches!(invalid, Err(e) if e.status == RtStatus::InvalidArgument));
+
+    interfaces::scheduler::cancel(&caps, "timer/fast").unwrap();
+    let fired = client.get_i32("timer/fast").unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(client.get_i32("timer/fast").unwrap(), fired);
+    let cancelled = interfaces::scheduler::cancel(&caps, "timer/fast");
+    assert!(matches!(cancelled, Err(e) if e.status == RtStatus::KeyNotFound));
+
+    assert!(host.stop().is_empty());
+}
@@ -0,0 +1,248 @@
+// Runs a state machine, see `machine` for its description in the `machine` attribute. The
+// conditions are checked again whenever one of their keys changes, the current state is
+// published to `state_key`.
+mod machine;
+
+use interfaces::blackboard::{
+    BlackboardEntry, BlackboardValue, TypedBlackboardValue, parse_attributes,
+};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use interfaces::capabilities::Capabilities;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{error, info};
+use machine::{Context, Machine, Spec};
+use serde_json::json;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+
+struct Config {
+    machine: Option<Spec>,
+    state_key: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            machine: None,
+            state_key: "statemachine/state".to_string(),
+        }
+    }
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("machine", BlackboardValue::String(text)) => {
+                    config.machine = Some(Spec::parse(text).map_err(invalid)?)
+                }
+                // written as yaml right in the attributes
+                ("machine", BlackboardValue::Json(machine)) => {
+                    config.machine = Some(Spec::from_json(machine.clone()).map_err(invalid)?)
+                }
+                ("state_key", BlackboardValue::String(key)) => config.state_key = key.clone(),
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+// the blackboard and the loader
+struct Runtime {
+    client: BlackboardClient,
+    caps: Capabilities,
+}
+
+impl Context for Runtime {
+    fn read(&self, key: &str) -> Option<TypedBlackboardValue> {
+        self.client.get_value(key).ok()
+    }
+
+    fn write(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), String> {
+        self.client.set_value(key, value).map_err(|e| e.to_string())
+    }
+
+    fn run_skill(&self, name: &str) -> Result<i32, String> {
+        interfaces::runtime::run_skill(&self.caps, name).map_err(|e| e.to_string())
+    }
+
+    fn restart(&self, service: &str) -> Result<(), String> {
+        interfaces::runtime::restart(&self.caps, service).map_err(|e| e.to_string())
+    }
+}
+
+struct Orchestrator {
+    runtime: Runtime,
+    state: Mutex<Option<String>>,
+    transitions: AtomicU64,
+    failing: AtomicBool, // the machine cycled at the last step
+}
+
+impl Orchestrator {
+    fn step(&self, machine: &mut Machine) {
+        let result = machine.step(&self.runtime);
+        if let Err(e) = &result {
+            error!("{}", e);
+        }
+        self.failing.store(result.is_err(), Ordering::SeqCst);
+        *self.state.lock().unwrap() = machine.current().map(str::to_string);
+        self.transitions
+            .store(machine.transitions(), Ordering::SeqCst);
+    }
+
+    // steps at the start and on every change until the subscriptions are dropped
+    fn run(&self, mut machine: Machine, changes: mpsc::Receiver<String>) {
+        self.step(&mut machine);
+        while changes.recv().is_ok() {
+            // one step checks all conditions, the changes queued meanwhile are covered
+            changes.try_iter().for_each(drop);
+            self.step(&mut machine);
+        }
+    }
+}
+
+struct MachineState {
+    orchestrator: Arc<Orchestrator>,
+    subscriptions: Vec<Subscription>,
+    worker: JoinHandle<()>,
+}
+
+static MACHINE_STATE: Mutex<Option<MachineState>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "statemachine",
+    summary = "runs a state machine guarded by blackboard conditions",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        statemachine_start = start: "i32(caps,cstr)",
+        statemachine_stop = stop: "i32()",
+        statemachine_health = health: "i32()",
+        statemachine_state = state: "i32()",
+        statemachine_health_status = health_status: "i32(*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_machine(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = MACHINE_STATE.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(
+            RtStatus::AlreadyRunning,
+            "State machine is already running",
+        ));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    let spec = config
+        .machine
+        .ok_or_else(|| RtError::new(RtStatus::InvalidArgument, "No machine is given"))?;
+    let machine = Machine::new(spec, &config.state_key)
+        .map_err(|e| RtError::new(RtStatus::InvalidArgument, e))?;
+    let orchestrator = Arc::new(Orchestrator {
+        runtime: Runtime {
+            client: BlackboardClient::new(Capabilities::from_raw(caps)),
+            caps: Capabilities::from_raw(caps),
+        },
+        state: Mutex::new(None),
+        transitions: AtomicU64::new(0),
+        failing: AtomicBool::new(false),
+    });
+
+    let (subscriptions, changes) =
+        orchestrator.runtime.client.subscribe_forwarding(machine.keys(), "statemachine")?;
+
+    let running = orchestrator.clone();
+    *state = Some(MachineState {
+        orchestrator,
+        subscriptions,
+        worker: std::thread::spawn(move || running.run(machine, changes)),
+    });
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "statemachine");
+    match LIFECYCLE.started(catch_panic(|| start_machine(caps, attributes))) {
+        Ok(()) => {
+            info!("State machine started");
+            0
+        }
+        Err(e) => {
+            error!("Error starting state machine: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_machine() -> Result<(), RtError> {
+    let state = MACHINE_STATE.lock().unwrap().take();
+    let state =
+        state.ok_or_else(|| RtError::new(RtStatus::NotRunning, "State machine is not running"))?;
+    // the worker ends after the running actions
+    drop(state.subscriptions);
+    let _ = state.worker.join();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_machine)) {
+        Ok(()) => {
+            info!("State machine stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping state machine: {}", e);
+            e.record()
+        }
+    }
+}
+
+/// `RT_ERROR` while the machine cycles between states without end.
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match MACHINE_STATE.lock().unwrap().as_ref() {
+        Some(state) if state.orchestrator.failing.load(Ordering::SeqCst) => {
+            RtError::new(RtStatus::Error, "The state machine is cycling").record()
+        }
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of the state machine, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the current state and the number of transitions taken as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match MACHINE_STATE.lock().unwrap().as_ref() {
+        Some(state) => json!({
+            "state": *state.orchestrator.state.lock().unwrap(),
+            "transitions": state.orchestrator.transitions.load(Ordering::SeqCst),
+        }),
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
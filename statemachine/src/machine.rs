@@ -0,0 +1,345 @@
+// State machines of the statemachine plugin. A state has entry and exit actions and
+// transitions guarded by conditions on keys, see `interfaces::predicate`:
+//
+//     initial: idle
+//     states:
+//       idle:
+//         transitions: [{to: charging, when: "battery/level < 20"}]
+//       charging:
+//         entry: [{skill: dock}, {set: {key: robot/led, value: yellow}}]
+//         exit: [{set: {key: robot/led, value: green}}]
+//         transitions: [{to: idle, when: "battery/level >= 95"}]
+//
+// The first transition of the current state whose condition holds is taken, a transition
+// without `when` is taken right after the entry actions.
+use interfaces::blackboard::{BlackboardValue, TypedBlackboardValue};
+use interfaces::predicate::Predicate;
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// taken in one step at most, more means the machine is cycling
+const MAX_TRANSITIONS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ActionSpec {
+    Set { key: String, value: BlackboardValue },
+    Skill(String),
+    Restart(String), // a service, with the services requiring it
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransitionSpec {
+    to: String,
+    when: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StateSpec {
+    entry: Vec<ActionSpec>,
+    exit: Vec<ActionSpec>,
+    transitions: Vec<TransitionSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    initial: String,
+    states: HashMap<String, StateSpec>,
+}
+
+impl Spec {
+    /// Reads the machine from yaml or json.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // serde_yml only reads enums from yaml tags, json takes the maps with one key
+        serde_yml::from_str(text)
+            .map_err(|e| e.to_string())
+            .and_then(Spec::from_json)
+    }
+
+    pub fn from_json(machine: serde_json::Value) -> Result<Self, String> {
+        serde_json::from_value(machine).map_err(|e| format!("Invalid state machine: {}", e))
+    }
+}
+
+/// What the actions act on, the blackboard and the loader.
+pub trait Context {
+    fn read(&self, key: &str) -> Option<TypedBlackboardValue>;
+    fn write(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), String>;
+    fn run_skill(&self, name: &str) -> Result<i32, String>;
+    fn restart(&self, service: &str) -> Result<(), String>;
+}
+
+enum Action {
+    Set {
+        key: String,
+        value: TypedBlackboardValue,
+    },
+    Skill(String),
+    Restart(String),
+}
+
+impl Action {
+    fn new(spec: ActionSpec) -> Result<Self, String> {
+        Ok(match spec {
+            ActionSpec::Set { key, value } => Action::Set {
+                key,
+                value: value.try_into()?,
+            },
+            ActionSpec::Skill(name) => Action::Skill(name),
+            ActionSpec::Restart(service) => Action::Restart(service),
+        })
+    }
+
+    // a failed action is logged, the machine goes on
+    fn run(&self, context: &dyn Context) {
+        let result = match self {
+            Action::Set { key, value } => context.write(key, value),
+            Action::Skill(name) => context.run_skill(name).map(|_| ()),
+            Action::Restart(service) => context.restart(service),
+        };
+        if let Err(e) = result {
+            warn!("{}", e);
+        }
+    }
+}
+
+struct Transition {
+    to: String,
+    when: Option<Predicate>,
+}
+
+struct State {
+    entry: Vec<Action>,
+    exit: Vec<Action>,
+    transitions: Vec<Transition>,
+}
+
+pub struct Machine {
+    states: HashMap<String, State>,
+    initial: String,
+    current: Option<String>,
+    state_key: String,
+    transitions: u64,
+}
+
+impl Machine {
+    /// Fails for unknown states and invalid conditions or values.
+    pub fn new(spec: Spec, state_key: &str) -> Result<Self, String> {
+        let actions = |specs: Vec<ActionSpec>| {
+            specs
+                .into_iter()
+                .map(Action::new)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let mut states = HashMap::new();
+        for (name, state) in spec.states {
+            let transitions = state
+                .transitions
+                .into_iter()
+                .map(|transition| {
+                    Ok(Transition {
+                        when: transition
+                            .when
+                            .as_deref()
+                            .map(Predicate::parse)
+                            .transpose()?,
+                        to: transition.to,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let state = State {
+                entry: actions(state.entry)?,
+                exit: actions(state.exit)?,
+                transitions,
+            };
+            states.insert(name, state);
+        }
+        let unknown = std::iter::once(&spec.initial)
+            .chain(
+                states
+                    .values()
+                    .flat_map(|state| state.transitions.iter().map(|transition| &transition.to)),
+            )
+            .find(|name| !states.contains_key(*name));
+        if let Some(name) = unknown {
+            return Err(format!("Unknown state {}", name));
+        }
+        Ok(Machine {
+            states,
+            initial: spec.initial,
+            current: None,
+            state_key: state_key.to_string(),
+            transitions: 0,
+        })
+    }
+
+    /// The keys the conditions read.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self
+            .states
+            .values()
+            .flat_map(|state| state.transitions.iter())
+            .filter_map(|transition| transition.when.as_ref().map(Predicate::key))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    pub fn transitions(&self) -> u64 {
+        self.transitions
+    }
+
+    fn enter(&mut self, name: &str, context: &dyn Context) {
+        info!("Entering state {}", name);
+        self.current = Some(name.to_string());
+        let state = TypedBlackboardValue::String(name.to_string());
+        if let Err(e) = context.write(&self.state_key, &state) {
+            warn!("Can not publish the state: {}", e);
+        }
+        self.states[name]
+            .entry
+            .iter()
+            .for_each(|action| action.run(context));
+    }
+
+    /// Enters the initial state at the first step, then takes the transitions whose conditions
+    /// hold until none does.
+    pub fn step(&mut self, context: &dyn Context) -> Result<(), String> {
+        if self.current.is_none() {
+            let initial = self.initial.clone();
+            self.enter(&initial, context);
+        }
+        for _ in 0..MAX_TRANSITIONS {
+            let Some(current) = self.current.as_deref() else {
+                return Ok(());
+            };
+            let state = &self.states[current];
+            let next = state
+                .transitions
+                .iter()
+                .find(|transition| match &transition.when {
+                    Some(when) => when.holds(context.read(when.key()).as_ref()),
+                    None => true,
+                });
+            let Some(next) = next.map(|transition| transition.to.clone()) else {
+                return Ok(());
+            };
+            state.exit.iter().for_each(|action| action.run(context));
+            self.transitions += 1;
+            self.enter(&next, context);
+        }
+        Err(format!(
+            "More than {} transitions at once, the machine is cycling",
+            MAX_TRANSITIONS
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Board {
+        values: Mutex<HashMap<String, TypedBlackboardValue>>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Context for Board {
+        fn read(&self, key: &str) -> Option<TypedBlackboardValue> {
+            self.values.lock().unwrap().get(key).cloned()
+        }
+
+        fn write(&self, key: &str, value: &TypedBlackboardValue) -> Result<(), String> {
+            self.values
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.clone());
+            Ok(())
+        }
+
+        fn run_skill(&self, name: &str) -> Result<i32, String> {
+            self.calls.lock().unwrap().push(format!("skill {}", name));
+            Ok(0)
+        }
+
+        fn restart(&self, service: &str) -> Result<(), String> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("restart {}", service));
+            Err(format!("Service '{}' can not be restarted", service))
+        }
+    }
+
+    const MACHINE: &str = "
+        initial: idle
+        states:
+          idle:
+            transitions: [{to: charging, when: 'battery/level < 20'}]
+          charging:
+            entry: [{skill: dock}, {set: {key: robot/led, value: yellow}}]
+            exit: [{restart: camera}]
+            transitions: [{to: idle, when: 'battery/level >= 95'}, {to: idle, when: abort}]";
+
+    #[test]
+    fn test_step() {
+        let board = Board::default();
+        let mut machine = Machine::new(Spec::parse(MACHINE).unwrap(), "fsm/state").unwrap();
+        assert_eq!(machine.keys(), vec!["abort", "battery/level"]);
+
+        machine.step(&board).unwrap();
+        assert_eq!(machine.current(), Some("idle"));
+        assert_eq!(
+            board.read("fsm/state"),
+            Some(TypedBlackboardValue::String("idle".to_string()))
+        );
+
+        board
+            .write("battery/level", &TypedBlackboardValue::Double(12.0))
+            .unwrap();
+        machine.step(&board).unwrap();
+        assert_eq!(machine.current(), Some("charging"));
+        assert_eq!(
+            board.read("robot/led"),
+            Some(TypedBlackboardValue::String("yellow".to_string()))
+        );
+        assert_eq!(*board.calls.lock().unwrap(), vec!["skill dock"]);
+
+        // a failed exit action does not stop the transition
+        board
+            .write("battery/level", &TypedBlackboardValue::Int(95))
+            .unwrap();
+        machine.step(&board).unwrap();
+        assert_eq!(machine.current(), Some("idle"));
+        assert_eq!(
+            *board.calls.lock().unwrap(),
+            vec!["skill dock", "restart camera"]
+        );
+        assert_eq!(machine.transitions(), 2);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let machine = |text: &str| Spec::parse(text).and_then(|spec| Machine::new(spec, "state"));
+        assert!(machine("initial: a\nstates: {b: {}}").is_err());
+        assert!(machine("initial: a\nstates: {a: {transitions: [{to: b}]}}").is_err());
+        assert!(machine("initial: a\nstates: {a: {transitions: [{to: a, when: '<'}]}}").is_err());
+        assert!(machine("initial: a\nstates: {a: {entry: [{call: x}]}}").is_err());
+
+        // a transition without condition back to the same state cycles
+        let board = Board::default();
+        let mut cycling = machine("initial: a\nstates: {a: {transitions: [{to: a}]}}").unwrap();
+        assert!(cycling.step(&board).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue, TypedBlackboardValue};
+use interfaces::testing::Host;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_statemachine() {
+    let machine = serde_json::json!({
+        "initial": "idle",
+        "states": {
+            "idle": {"transitions": [{"to": "charging", "when": "battery/level < 20"}]},
+            "charging": {
+                "entry": [{"set": {"key": "robot/led", "value": "yellow"}}],
+                "exit": [{"set": {"key": "robot/led", "value": "green"}}],
+                "transitions": [{"to": "idle", "when": "battery/level >= 95"}]
+            }
+        }
+    });
+    let attributes = vec![BlackboardEntry {
+        key: "machine".to_string(),
+        value: BlackboardValue::Json(machine),
+    }];
+    let host = Host::start(&[("statemachine", attributes)]).unwrap();
+    let client = host.client();
+    let wait_for = |key: &str, value: &str| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.get_string(key).ok().as_deref() != Some(value) {
+            assert!(Instant::now() < deadline, "{} is not {}", key, value);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+    wait_for("statemachine/state", "idle");
+
+    client.set_value("battery/level", &TypedBlackboardValue::Double(15.0)).unwrap();
+    wait_for("statemachine/state", "charging");
+    assert_eq!(client.get_string("robot/led").unwrap(), "yellow");
+    client.set_i32("battery/level", 50).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(client.get_string("statemachine/state").unwrap(), "charging");
+    client.set_i32("battery/level", 100).unwrap();
+    wait_for("statemachine/state", "idle");
+    assert_eq!(client.get_string("robot/led").unwrap(), "green");
+
+    assert!(host.stop().is_empty());
+}
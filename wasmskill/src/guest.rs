@@ -0,0 +1,137 @@
+// A run of a skill in a fresh instance of its module. The instance gets the functions of the
+// import module `rtime` and nothing else, strings are passed as pointer and length into the
+// memory the module exports as `memory`:
+//
+//     log(level, message, message_len)                  1 error .. 5 trace
+//     get(key, key_len, buffer, buffer_len) -> size      the value as {"type": ..., "value": ...}
+//     set(key, key_len, value, value_len) -> status      the value as {"type": ..., "value": ...}
+//     delete(key, key_len) -> status
+//     now_ns() -> i64                                    time of the loader clock
+//
+// `get` writes the value only if it fits into the buffer and returns its size either way.
+// Errors of the blackboard are returned as negative status codes, invalid pointers trap.
+use super::Host;
+use interfaces::blackboard::TypedBlackboardValue;
+use std::sync::Arc;
+use wasmtime::{Caller, Extern, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+struct Guest {
+    host: Arc<Host>,
+    skill: String,
+    limits: StoreLimits,
+}
+
+fn slice(caller: &mut Caller<'_, Guest>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let memory = match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => memory,
+        _ => return Err(wasmtime::Error::msg("The module exports no memory")),
+    };
+    let start = ptr as u32 as usize;
+    memory
+        .data(&caller)
+        .get(start..start + len as u32 as usize)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| wasmtime::Error::msg("Pointer out of memory"))
+}
+
+fn text(caller: &mut Caller<'_, Guest>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    String::from_utf8(slice(caller, ptr, len)?).map_err(wasmtime::Error::msg)
+}
+
+fn write(caller: &mut Caller<'_, Guest>, ptr: i32, data: &[u8]) -> wasmtime::Result<()> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        return Err(wasmtime::Error::msg("The module exports no memory"));
+    };
+    memory.write(caller, ptr as u32 as usize, data).map_err(wasmtime::Error::msg)
+}
+
+fn linker(module: &Module) -> wasmtime::Result<Linker<Guest>> {
+    let mut linker = Linker::new(module.engine());
+    linker.func_wrap(
+        "rtime",
+        "log",
+        |mut caller: Caller<'_, Guest>, level: i32, ptr: i32, len: i32| {
+            let message = text(&mut caller, ptr, len)?;
+            let level = match level {
+                1 => log::Level::Error,
+                2 => log::Level::Warn,
+                3 => log::Level::Info,
+                4 => log::Level::Debug,
+                _ => log::Level::Trace,
+            };
+            log::log!(level, "{}: {}", caller.data().skill, message);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "rtime",
+        "get",
+        |mut caller: Caller<'_, Guest>, key: i32, key_len: i32, buffer: i32, buffer_len: i32| {
+            let key = text(&mut caller, key, key_len)?;
+            let value = match caller.data().host.client.get_value(&key) {
+                Ok(value) => value,
+                Err(e) => return Ok(e.status.code()),
+            };
+            let value = serde_json::to_vec(&value)?;
+            if value.len() <= buffer_len.max(0) as usize {
+                write(&mut caller, buffer, &value)?;
+            }
+            Ok(value.len() as i32)
+        },
+    )?;
+    linker.func_wrap(
+        "rtime",
+        "set",
+        |mut caller: Caller<'_, Guest>, key: i32, key_len: i32, value: i32, value_len: i32| {
+            let key = text(&mut caller, key, key_len)?;
+            let value = slice(&mut caller, value, value_len)?;
+            let value: TypedBlackboardValue = match serde_json::from_slice(&value) {
+                Ok(value) => value,
+                Err(e) => {
+                    log::warn!("{}: invalid value of {}: {}", caller.data().skill, key, e);
+                    return Ok(interfaces::status::RtStatus::InvalidArgument.code());
+                }
+            };
+            Ok(match caller.data().host.client.set_value(&key, &value) {
+                Ok(()) => 0,
+                Err(e) => e.status.code(),
+            })
+        },
+    )?;
+    linker.func_wrap(
+        "rtime",
+        "delete",
+        |mut caller: Caller<'_, Guest>, key: i32, key_len: i32| {
+            let key = text(&mut caller, key, key_len)?;
+            Ok(match caller.data().host.client.delete(&key) {
+                Ok(()) => 0,
+                Err(e) => e.status.code(),
+            })
+        },
+    )?;
+    linker.func_wrap("rtime", "now_ns", |caller: Caller<'_, Guest>| {
+        let now = interfaces::clock::now(&caller.data().host.client.caps()).unwrap_or_default();
+        now.as_nanos() as i64
+    })?;
+    Ok(linker)
+}
+
+/// Runs the function `function() -> i32` of `module` for `skill` within the fuel and memory of
+/// the host. A trap, like running out of fuel, is an error.
+pub fn run(host: &Arc<Host>, skill: &str, module: &Module, function: &str) -> Result<i32, String> {
+    let guest = Guest {
+        host: host.clone(),
+        skill: skill.to_string(),
+        limits: StoreLimitsBuilder::new().memory_size(host.memory).build(),
+    };
+    let mut store = Store::new(module.engine(), guest);
+    store.limiter(|guest| &mut guest.limits);
+    store.set_fuel(host.fuel).map_err(|e| e.to_string())?;
+    let instance = linker(module)
+        .and_then(|linker| linker.instantiate(&mut store, module))
+        .map_err(|e| format!("Can not instantiate: {:#}", e))?;
+    let function = instance
+        .get_typed_func::<(), i32>(&mut store, function)
+        .map_err(|e| format!("No function {}: {:#}", function, e))?;
+    function.call(&mut store, ()).map_err(|e| format!("{:#}", e))
+}
@@ -0,0 +1,506 @@
+// Runs skills compiled to WebAssembly in a sandbox. The service compiles the modules of its
+// attributes and hosts their functions as skills like pyskill: the loader lists them with the
+// entry `skills` and runs them with `run_skill`. Every run gets a fresh instance limited in fuel
+// and memory, which reaches the blackboard and the log only through the functions of
+// `guest`. A module whose file changed is compiled again before its next run.
+//
+//     - name: wasmskill
+//       access: {read: [robot], write: [robot/arm]}
+//       attributes:
+//         - {key: modules, value: [skills/grip.wasm]}
+//         - {key: skills, value: {open: grip.open, close: grip.close}}
+//         - {key: fuel, value: 100000000}
+//         - {key: memory_mb, value: 16}
+//
+// Without `skills` every module exporting `run` is a skill named like its file. The blackboard
+// calls are made in the session of `wasmskill`, so its `access` rules apply to all modules.
+mod guest;
+
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue, parse_attributes};
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::capabilities::Capabilities;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
+use log::{error, info};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use wasmtime::{Engine, ExternType, Module};
+
+struct Config {
+    modules: Vec<PathBuf>,
+    skills: BTreeMap<String, String>, // skill -> `module.function`
+    fuel: u64,                        // per run, roughly the number of instructions
+    memory_mb: usize,                 // per instance
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            modules: Vec::new(),
+            skills: BTreeMap::new(),
+            fuel: 1_000_000_000,
+            memory_mb: 64,
+        }
+    }
+}
+
+impl Config {
+    fn new(key_values: &Vec<BlackboardEntry>) -> Result<Self, RtError> {
+        let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+        let mut config = Self::default();
+        for entry in key_values {
+            match (entry.key.as_str(), &entry.value) {
+                ("modules", BlackboardValue::String(module)) => {
+                    config.modules = vec![PathBuf::from(module)]
+                }
+                ("modules", BlackboardValue::Array(modules)) => {
+                    config.modules = modules
+                        .iter()
+                        .map(|module| match module {
+                            BlackboardValue::String(module) => Ok(PathBuf::from(module)),
+                            other => Err(invalid(format!("Invalid module {:?}", other))),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                ("skills", BlackboardValue::Json(serde_json::Value::Object(skills))) => {
+                    for (skill, function) in skills {
+                        let function = function.as_str().ok_or_else(|| {
+                            invalid(format!("Skill {} needs a function like module.run", skill))
+                        })?;
+                        config.skills.insert(skill.clone(), function.to_string());
+                    }
+                }
+                ("fuel", BlackboardValue::Int(fuel)) if *fuel > 0 => config.fuel = *fuel as u64,
+                ("fuel", BlackboardValue::Int64(fuel)) if *fuel > 0 => config.fuel = *fuel as u64,
+                ("memory_mb", BlackboardValue::Int(memory)) if *memory > 0 => {
+                    config.memory_mb = *memory as usize
+                }
+                ("fuel" | "memory_mb", value) => {
+                    return Err(invalid(format!("Invalid {} {:?}", entry.key, value)))
+                }
+                _ => {}
+            }
+        }
+        if config.modules.is_empty() {
+            return Err(invalid("No modules to load".to_string()));
+        }
+        Ok(config)
+    }
+}
+
+// a compiled module and the modification time of its file
+struct Compiled {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    module: Module,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// compiles a module from its binary or text format
+fn compile(engine: &Engine, path: &Path) -> Result<Compiled, String> {
+    Ok(Compiled {
+        path: path.to_path_buf(),
+        modified: modified(path),
+        module: Module::from_file(engine, path)
+            .map_err(|e| format!("Can not compile {}: {:#}", path.display(), e))?,
+    })
+}
+
+fn exports_function(module: &Module, function: &str) -> bool {
+    module
+        .exports()
+        .any(|export| export.name() == function && matches!(export.ty(), ExternType::Func(_)))
+}
+
+struct Host {
+    client: BlackboardClient,
+    engine: Engine,
+    fuel: u64,
+    memory: usize, // bytes
+    modules: Mutex<BTreeMap<String, Compiled>>,
+    skills: BTreeMap<String, (String, String)>, // skill -> (module, function)
+    runs: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Host {
+    // the module `name`, compiled again if its file changed
+    fn module(&self, name: &str) -> Result<Module, String> {
+        let mut modules = self.modules.lock().unwrap();
+        let compiled = modules
+            .get_mut(name)
+            .ok_or_else(|| format!("No module {}", name))?;
+        if modified(&compiled.path) != compiled.modified {
+            *compiled = compile(&self.engine, &compiled.path)?;
+            info!("Compiled {} again", compiled.path.display());
+        }
+        Ok(compiled.module.clone())
+    }
+}
+
+static HOST: Mutex<Option<Arc<Host>>> = Mutex::new(None);
+static LIFECYCLE: Lifecycle = Lifecycle::new();
+
+#[rt_plugin(
+    name = "wasmskill",
+    summary = "runs skills compiled to WebAssembly in a sandbox",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 2,
+    provides(
+        wasmskill_start = start: "i32(caps,cstr)",
+        wasmskill_stop = stop: "i32()",
+        wasmskill_health = health: "i32()",
+        wasmskill_state = state: "i32()",
+        wasmskill_health_status = health_status: "i32(*mut char,i32)",
+        wasmskill_skills = skills: "i32(*mut char,i32)",
+        wasmskill_run_skill = run_skill: "i32(cstr)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
+fn start_host(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> Result<(), RtError> {
+    let mut state = HOST.lock().unwrap();
+    if state.is_some() {
+        return Err(RtError::new(RtStatus::AlreadyRunning, "wasmskill is already running"));
+    }
+    let config = Config::new(&unsafe { parse_attributes(attributes) }?)?;
+    let invalid = |message: String| RtError::new(RtStatus::InvalidArgument, message);
+
+    let mut engine_config = wasmtime::Config::new();
+    engine_config.consume_fuel(true);
+    let engine = Engine::new(&engine_config).map_err(|e| e.to_string())?;
+    let mut modules = BTreeMap::new();
+    for path in &config.modules {
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| invalid(format!("Invalid module {}", path.display())))?;
+        modules.insert(name.to_string(), compile(&engine, path).map_err(invalid)?);
+    }
+
+    let mut skills = BTreeMap::new();
+    if config.skills.is_empty() {
+        for (name, compiled) in &modules {
+            if exports_function(&compiled.module, "run") {
+                skills.insert(name.clone(), (name.clone(), "run".to_string()));
+            }
+        }
+    }
+    for (skill, target) in &config.skills {
+        let (module, function) = target
+            .rsplit_once('.')
+            .filter(|(module, function)| {
+                modules
+                    .get(*module)
+                    .is_some_and(|compiled| exports_function(&compiled.module, function))
+            })
+            .ok_or_else(|| {
+                invalid(format!("Skill {}: {} is no function of the modules", skill, target))
+            })?;
+        skills.insert(skill.clone(), (module.to_string(), function.to_string()));
+    }
+
+    let client = BlackboardClient::new(Capabilities::from_raw(caps));
+    info!("Loaded {:?} with the skills {:?}", config.modules, skills.keys());
+    *state = Some(Arc::new(Host {
+        client,
+        engine,
+        fuel: config.fuel,
+        memory: config.memory_mb << 20,
+        modules: Mutex::new(modules),
+        skills,
+        runs: AtomicU64::new(0),
+        failures: AtomicU64::new(0),
+    }));
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn start(
+    caps: &interfaces::bindings::Capabilities,
+    attributes: *const c_char,
+) -> i32 {
+    interfaces::logging::init_with_fallback(caps, "wasmskill");
+    match LIFECYCLE.started(catch_panic(|| start_host(caps, attributes))) {
+        Ok(()) => 0,
+        Err(e) => {
+            error!("Error starting wasmskill: {}", e);
+            e.record()
+        }
+    }
+}
+
+fn stop_host() -> Result<(), RtError> {
+    HOST.lock()
+        .unwrap()
+        .take()
+        .map(|_| ())
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "wasmskill is not running"))
+}
+
+#[no_mangle]
+pub extern "C" fn stop() -> i32 {
+    match LIFECYCLE.stopped(catch_panic(stop_host)) {
+        Ok(()) => {
+            info!("wasmskill stopped");
+            0
+        }
+        Err(e) => {
+            error!("Error stopping wasmskill: {}", e);
+            e.record()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn health() -> i32 {
+    match HOST.lock().unwrap().as_ref() {
+        Some(_) => RtStatus::Ok.code(),
+        None => RtStatus::NotRunning.code(),
+    }
+}
+
+/// Lifecycle state of wasmskill, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state() -> i32 {
+    LIFECYCLE.code()
+}
+
+/// Writes the modules, the skills and how often they ran and failed as json.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(buffer: *mut c_char, len: c_int) -> c_int {
+    let status = match HOST.lock().unwrap().as_ref() {
+        Some(host) => {
+            let modules: Vec<PathBuf> = host
+                .modules
+                .lock()
+                .unwrap()
+                .values()
+                .map(|compiled| compiled.path.clone())
+                .collect();
+            json!({
+                "modules": modules,
+                "skills": host.skills.keys().collect::<Vec<_>>(),
+                "runs": host.runs.load(Ordering::SeqCst),
+                "failures": host.failures.load(Ordering::SeqCst),
+            })
+        }
+        None => json!({}),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status.to_string(), buffer, len) }
+}
+
+/// Writes the names of the skills as a json array, empty while wasmskill is stopped.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn skills(buffer: *mut c_char, len: c_int) -> c_int {
+    let names = match HOST.lock().unwrap().as_ref() {
+        Some(host) => json!(host.skills.keys().collect::<Vec<_>>()),
+        None => json!([]),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&names.to_string(), buffer, len) }
+}
+
+fn run_skill_intern(name: *const c_char) -> Result<c_int, RtError> {
+    if name.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Skill name is null pointer"));
+    }
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+    let host = HOST
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "wasmskill is not running"))?;
+    let (module, function) = host
+        .skills
+        .get(&name)
+        .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, format!("No skill {}", name)))?;
+
+    host.runs.fetch_add(1, Ordering::SeqCst);
+    let result = host
+        .module(module)
+        .and_then(|module| guest::run(&host, &name, &module, function));
+    if !matches!(result, Ok(code) if code >= 0) {
+        host.failures.fetch_add(1, Ordering::SeqCst);
+    }
+    result.map_err(|e| RtError::from(format!("Skill {} failed: {}", name, e)))
+}
+
+/// Runs the skill `name` and returns what it returns, `RT_KEY_NOT_FOUND` for unknown skills.
+#[no_mangle]
+pub extern "C" fn run_skill(name: *const c_char) -> c_int {
+    match catch_panic(|| run_skill_intern(name)) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("{}", e);
+            e.record()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(fuel: u64, memory_mb: usize) -> Arc<Host> {
+        Arc::new(new_host(fuel, memory_mb))
+    }
+
+    fn new_host(fuel: u64, memory_mb: usize) -> Host {
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        Host {
+            client: BlackboardClient::new(Capabilities::new()),
+            engine: Engine::new(&engine_config).unwrap(),
+            fuel,
+            memory: memory_mb << 20,
+            modules: Mutex::new(BTreeMap::new()),
+            skills: BTreeMap::new(),
+            runs: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+
+    // runs `function` of the module `wat`
+    fn run(host: &Arc<Host>, wat: &str, function: &str) -> Result<i32, String> {
+        let module = Module::new(&host.engine, wat).map_err(|e| e.to_string())?;
+        guest::run(host, "test", &module, function)
+    }
+
+    #[test]
+    fn test_config() {
+        let config = |attributes: &CStr| {
+            Config::new(&unsafe { parse_attributes(attributes.as_ptr()) }.unwrap())
+        };
+        let parsed = config(c"[{key: modules, value: grip.wasm}, {key: fuel, value: 500}]");
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.modules, [PathBuf::from("grip.wasm")]);
+        assert_eq!((parsed.fuel, parsed.memory_mb), (500, 64));
+        let invalid = |attributes: &CStr| config(attributes).err().map(|e| e.status);
+        assert_eq!(invalid(c"[]"), Some(RtStatus::InvalidArgument));
+        let fuel = c"[{key: modules, value: a.wasm}, {key: fuel, value: 0}]";
+        assert_eq!(invalid(fuel), Some(RtStatus::InvalidArgument));
+        let memory = c"[{key: modules, value: a.wasm}, {key: memory_mb, value: many}]";
+        assert_eq!(invalid(memory), Some(RtStatus::InvalidArgument));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let host = host(1000, 1);
+        let dir = std::env::temp_dir().join(format!("rtime-wasmskill-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let error = compile(&host.engine, &dir.join("missing.wasm")).err().unwrap();
+        assert!(error.starts_with("Can not compile"), "{}", error);
+        let broken = dir.join("broken.wat");
+        std::fs::write(&broken, "(module (func (export \"run\") (result i32)").unwrap();
+        let error = compile(&host.engine, &broken).err().unwrap();
+        assert!(error.contains("broken.wat"), "{}", error);
+        let garbage = dir.join("garbage.wasm");
+        std::fs::write(&garbage, [0, 97, 115, 109, 9, 9, 9, 9]).unwrap();
+        assert!(compile(&host.engine, &garbage).is_err());
+
+        let valid = dir.join("valid.wat");
+        std::fs::write(&valid, "(module (func (export \"run\") (result i32) (i32.const 0)))")
+            .unwrap();
+        let compiled = compile(&host.engine, &valid).unwrap();
+        assert!(exports_function(&compiled.module, "run"));
+        assert!(!exports_function(&compiled.module, "stop"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unresolved_imports() {
+        let host = host(1000, 1);
+        // only the functions of `rtime` are provided, with their signatures
+        let unknown = r#"(module (import "rtime" "open" (func (param i32)))
+            (func (export "run") (result i32) (i32.const 0)))"#;
+        let error = run(&host, unknown, "run").unwrap_err();
+        assert!(error.starts_with("Can not instantiate"), "{}", error);
+        assert!(error.contains("open"), "{}", error);
+        let other = r#"(module (import "wasi_snapshot_preview1" "fd_write"
+                (func (param i32 i32 i32 i32) (result i32)))
+            (func (export "run") (result i32) (i32.const 0)))"#;
+        assert!(run(&host, other, "run").unwrap_err().starts_with("Can not instantiate"));
+        let mismatched = r#"(module (import "rtime" "delete" (func (param i32) (result i32)))
+            (func (export "run") (result i32) (i32.const 0)))"#;
+        assert!(run(&host, mismatched, "run").unwrap_err().starts_with("Can not instantiate"));
+        // more memory than allowed
+        let hungry = r#"(module (memory (export "memory") 32)
+            (func (export "run") (result i32) (i32.const 0)))"#;
+        assert!(run(&host, hungry, "run").unwrap_err().starts_with("Can not instantiate"));
+    }
+
+    #[test]
+    fn test_results() {
+        let host = host(10_000, 1);
+        let wat = r#"(module
+            (import "rtime" "get" (func $get (param i32 i32 i32 i32) (result i32)))
+            (func (export "done") (result i32) (i32.const 7))
+            (func (export "failed") (result i32) (i32.const -3))
+            (func (export "trap") (result i32) (unreachable))
+            (func (export "spin") (result i32) (loop $again (br $again)) (i32.const 0))
+            (func (export "get") (result i32)
+              (call $get (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 0)))
+            (func (export "typed") (param i32) (result i32) (local.get 0)))"#;
+        // what the function returns is the result of the skill, traps are errors
+        assert_eq!(run(&host, wat, "done"), Ok(7));
+        assert_eq!(run(&host, wat, "failed"), Ok(-3));
+        let error = run(&host, wat, "trap").unwrap_err();
+        assert!(error.contains("unreachable"), "{}", error);
+        let error = run(&host, wat, "spin").unwrap_err();
+        assert!(error.contains("fuel"), "{}", error);
+        // the host functions need the memory of the module
+        let error = run(&host, wat, "get").unwrap_err();
+        assert!(error.contains("The module exports no memory"), "{}", error);
+        assert!(run(&host, wat, "typed").unwrap_err().starts_with("No function typed"));
+        assert!(run(&host, wat, "missing").unwrap_err().starts_with("No function missing"));
+    }
+
+    #[test]
+    fn test_run_skill() {
+        let mut host = new_host(10_000, 1);
+        let wat = r#"(module
+            (func (export "done") (result i32) (i32.const 7))
+            (func (export "failed") (result i32) (i32.const -3))
+            (func (export "trap") (result i32) (unreachable)))"#;
+        let compiled = Compiled {
+            path: PathBuf::from("skills.wat"),
+            modified: None,
+            module: Module::new(&host.engine, wat).unwrap(),
+        };
+        host.modules.get_mut().unwrap().insert("skills".to_string(), compiled);
+        for skill in ["done", "failed", "trap"] {
+            let target = ("skills".to_string(), skill.to_string());
+            host.skills.insert(skill.to_string(), target);
+        }
+        *HOST.lock().unwrap() = Some(Arc::new(host));
+
+        assert_eq!(run_skill(c"done".as_ptr()), 7);
+        assert_eq!(run_skill(c"failed".as_ptr()), -3);
+        assert_eq!(run_skill(c"trap".as_ptr()), RtStatus::Error.code());
+        assert_eq!(run_skill(c"other".as_ptr()), RtStatus::KeyNotFound.code());
+        assert_eq!(run_skill(std::ptr::null()), RtStatus::NullArgument.code());
+        // negative results count as failures like traps
+        let host = HOST.lock().unwrap().take().unwrap();
+        assert_eq!(host.runs.load(Ordering::SeqCst), 3);
+        assert_eq!(host.failures.load(Ordering::SeqCst), 2);
+        assert_eq!(run_skill(c"done".as_ptr()), RtStatus::NotRunning.code());
+    }
+}
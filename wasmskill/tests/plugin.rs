@@ -0,0 +1,66 @@
+use interfaces::blackboard::{BlackboardEntry, BlackboardValue};
+use interfaces::status::RtStatus;
+use interfaces::testing::Host;
+
+#[test]
+fn test_wasmskill() {
+    let dir = std::env::temp_dir().join(format!("rtime-wasmskill-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let module = dir.join("hello.wat");
+    std::fs::write(
+        &module,
+        r#"(module
+             (import "rtime" "get" (func $get (param i32 i32 i32 i32) (result i32)))
+             (import "rtime" "set" (func $set (param i32 i32 i32 i32) (result i32)))
+             (import "rtime" "log" (func $log (param i32 i32 i32)))
+             (memory (export "memory") 1)
+             (data (i32.const 0) "wasm/greeting")
+             (data (i32.const 16) "{\"type\":\"string\",\"value\":\"hello\"}")
+             (data (i32.const 64) "name")
+             (func (export "run") (result i32)
+               (call $log (i32.const 3) (i32.const 0) (i32.const 13))
+               (call $set (i32.const 0) (i32.const 13) (i32.const 16) (i32.const 33)))
+             (func (export "size") (result i32)
+               (call $get (i32.const 64) (i32.const 4) (i32.const 128) (i32.const 256)))
+             (func (export "spin") (result i32)
+               (loop $again (br $again))
+               (i32.const 0)))"#,
+    )
+    .unwrap();
+    let attributes = vec![
+        BlackboardEntry {
+            key: "modules".to_string(),
+            value: BlackboardValue::String(module.display().to_string()),
+        },
+        BlackboardEntry {
+            key: "skills".to_string(),
+            value: BlackboardValue::Json(serde_json::json!(
+                {"greet": "hello.run", "size": "hello.size", "spin": "hello.spin"}
+            )),
+        },
+        BlackboardEntry {
+            key: "fuel".to_string(),
+            value: BlackboardValue::Int(100_000),
+        },
+    ];
+    let host = Host::start(&[("wasmskill", attributes)]).unwrap();
+    let client = host.client();
+
+    assert_eq!(host.run_skill("wasmskill", "greet"), Ok(0));
+    assert_eq!(client.get_string("wasm/greeting").unwrap(), "hello");
+    // `get` returns the size of the typed value, or the status of the blackboard
+    assert_eq!(host.run_skill("wasmskill", "size"), Ok(RtStatus::KeyNotFound.code()));
+    client.set_string("name", "rtime").unwrap();
+    let size = serde_json::to_vec(&client.get_value("name").unwrap()).unwrap().len();
+    assert_eq!(host.run_skill("wasmskill", "size"), Ok(size as i32));
+    // an endless loop runs out of fuel
+    assert_eq!(host.run_skill("wasmskill", "spin"), Ok(RtStatus::Error.code()));
+
+    let status = host.health_status("wasmskill").unwrap();
+    assert_eq!(status["skills"], serde_json::json!(["greet", "size", "spin"]));
+    assert_eq!(status["runs"], 4);
+    assert_eq!(status["failures"], 2);
+
+    assert!(host.stop().is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,59 @@
+// Alerts raised by the `alerts` plugin, read from their status keys `alerts/<name>`.
+// Acknowledging one writes `true` to `alerts/<name>/ack`, which the plugin watches.
+use super::{blackboard_call, AppData};
+use actix_web::{get, post, web, Responder};
+use interfaces::alerts::{ack_key, alert_key, AlertState, AlertStatus, ALERT_PREFIX};
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::status::{RtError, RtStatus};
+
+fn status(client: &BlackboardClient, name: &str) -> Result<AlertStatus, RtError> {
+    let unknown = || {
+        RtError::new(RtStatus::KeyNotFound, format!("Alert '{}' does not exist", name))
+    };
+    match client.get_value(&alert_key(name)) {
+        Ok(TypedBlackboardValue::Json(status)) => {
+            serde_json::from_value(status).map_err(|_| unknown())
+        }
+        Ok(_) => Err(unknown()),
+        Err(e) if e.status == RtStatus::KeyNotFound => Err(unknown()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Status of every alert, e.g. `[{"name": "overheat", "state": "firing", ...}]`.
+#[get("/api/alerts")]
+async fn list_alerts(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| {
+        let names: Vec<String> = client
+            .keys()?
+            .into_iter()
+            .filter_map(|info| info.key.strip_prefix(ALERT_PREFIX).map(str::to_string))
+            .filter(|name| !name.contains('/'))
+            .collect();
+        Ok(names
+            .iter()
+            .filter_map(|name| status(client, name).ok())
+            .collect::<Vec<_>>())
+    })
+    .await
+}
+
+/// Acknowledges a firing alert, a conflict if it does not fire.
+#[post("/api/alerts/{name}/ack")]
+async fn acknowledge(data: web::Data<AppData>, name: web::Path<String>) -> impl Responder {
+    blackboard_call(data, move |client| {
+        let alert = status(client, &name)?;
+        if alert.state != AlertState::Firing {
+            let message = format!("Alert '{}' is not firing", name);
+            return Err(RtError::new(RtStatus::ValueMismatch, message));
+        }
+        client.set_value(&ack_key(&name), &TypedBlackboardValue::Bool(true))
+    })
+    .await
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_alerts);
+    cfg.service(acknowledge);
+}
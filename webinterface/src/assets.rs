@@ -0,0 +1,408 @@
+// Files uploaded for projects and skills, like mission files, maps and calibration data.
+// `POST /api/assets` stores every file of a multipart form in `asset_dir` under its id, the
+// sha256 of its content, next to `<id>.json` with its name, size and content type. Projects name
+// the assets they use in `assets`, skills get the path of an asset from the capability
+// `webinterface_asset_path`.
+use super::{error_response, ApiError, AppData};
+use actix_multipart::Multipart;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use futures::StreamExt;
+use interfaces::status::{RtError, RtStatus};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Default of `asset_max_size`, the size of one file in bytes.
+pub const DEFAULT_MAX_SIZE: u64 = 64 << 20;
+
+/// An uploaded file, described by `<id>.json` in the asset directory.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Asset {
+    pub id: String,
+    pub name: String, // file name of the upload
+    pub size: u64,
+    pub content_type: Option<String>,
+}
+
+// asset directories of the running instances, searched by `asset_path`
+static DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+// names the files being uploaded
+static UPLOADS: AtomicUsize = AtomicUsize::new(0);
+const UPLOAD_PREFIX: &str = ".upload-";
+
+fn valid_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn not_found(id: &str) -> RtError {
+    RtError::new(RtStatus::KeyNotFound, format!("Asset '{}' does not exist", id))
+}
+
+/// The path of the asset `id` in the asset directory of any running instance.
+pub fn path(id: &str) -> Option<PathBuf> {
+    if !valid_id(id) {
+        return None;
+    }
+    DIRS.lock()
+        .unwrap()
+        .iter()
+        .map(|dir| dir.join(id))
+        .find(|path| path.is_file())
+}
+
+// the temporary file of an upload, removed unless `Store::add` moved it to its id, also when
+// the client aborts and the handler is dropped in the middle of the upload
+struct Upload {
+    path: PathBuf,
+    kept: bool,
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        if !self.kept {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The assets of an instance, registered for `asset_path` while it exists.
+pub struct Store {
+    dir: PathBuf,
+    max_size: u64,          // of one file
+    max_total: Option<u64>, // of all files
+    adding: Mutex<()>,      // checks `max_total` and stores an upload at once
+}
+
+impl Store {
+    /// Removes the uploads a previous instance left unfinished.
+    pub fn open(dir: &Path, max_size: u64, max_total: Option<u64>) -> Result<Self, RtError> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Can not create asset_dir {}: {}", dir.display(), e))?;
+        let entries = std::fs::read_dir(dir).map_err(|e| e.to_string())?;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if entry.file_name().to_string_lossy().starts_with(UPLOAD_PREFIX) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+        DIRS.lock().unwrap().push(dir.to_path_buf());
+        Ok(Store {
+            dir: dir.to_path_buf(),
+            max_size,
+            max_total,
+            adding: Mutex::new(()),
+        })
+    }
+
+    fn file(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn description(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn get(&self, id: &str) -> Result<Asset, RtError> {
+        if !valid_id(id) {
+            return Err(not_found(id));
+        }
+        let description =
+            std::fs::read_to_string(self.description(id)).map_err(|_| not_found(id))?;
+        serde_json::from_str(&description).map_err(|e| RtError::from(e.to_string()))
+    }
+
+    /// All assets, ordered by id.
+    pub fn list(&self) -> Result<Vec<Asset>, RtError> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| e.to_string())?;
+        let mut ids: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| name.strip_suffix(".json").map(str::to_string))
+            .filter(|id| valid_id(id))
+            .collect();
+        ids.sort();
+        ids.iter().map(|id| self.get(id)).collect()
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), RtError> {
+        self.get(id)?;
+        std::fs::remove_file(self.description(id)).map_err(|e| e.to_string())?;
+        let _ = std::fs::remove_file(self.file(id));
+        Ok(())
+    }
+
+    // moves the uploaded file to its id, unless that exceeds the size of all files
+    fn add(&self, mut upload: Upload, asset: Asset) -> Result<Asset, Refused> {
+        let _adding = self.adding.lock().unwrap();
+        if let Ok(existing) = self.get(&asset.id) {
+            return Ok(existing);
+        }
+        if let Some(max_total) = self.max_total {
+            let used: u64 = self.list()?.iter().map(|a| a.size).sum();
+            if used + asset.size > max_total {
+                return Err(Refused::TooLarge(format!(
+                    "{} exceeds the {} bytes of all assets",
+                    asset.name, max_total
+                )));
+            }
+        }
+        let description = serde_json::to_string(&asset).map_err(|e| e.to_string())?;
+        std::fs::rename(&upload.path, self.file(&asset.id))
+            .map_err(|e| format!("Can not store {}: {}", asset.name, e))?;
+        upload.kept = true;
+        std::fs::write(self.description(&asset.id), description)
+            .map_err(|e| format!("Can not store {}: {}", asset.name, e))?;
+        Ok(asset)
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        let mut dirs = DIRS.lock().unwrap();
+        if let Some(index) = dirs.iter().position(|dir| *dir == self.dir) {
+            dirs.remove(index);
+        }
+    }
+}
+
+// why an upload is not stored
+enum Refused {
+    TooLarge(String),
+    Failed(RtError),
+}
+
+impl From<RtError> for Refused {
+    fn from(e: RtError) -> Self {
+        Refused::Failed(e)
+    }
+}
+
+impl From<String> for Refused {
+    fn from(e: String) -> Self {
+        Refused::Failed(RtError::from(e))
+    }
+}
+
+impl Refused {
+    fn response(self) -> HttpResponse {
+        match self {
+            Refused::TooLarge(error) => HttpResponse::PayloadTooLarge().json(ApiError { error }),
+            Refused::Failed(e) => error_response(e),
+        }
+    }
+}
+
+fn store(data: &AppData) -> Result<&Store, RtError> {
+    data.assets
+        .as_ref()
+        .ok_or_else(|| RtError::new(RtStatus::NotRunning, "No asset_dir is configured"))
+}
+
+// the file system calls may block, so they run on the blocking pool like the blackboard calls
+async fn blocking<T, F>(call: F) -> Result<T, Refused>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Refused> + Send + 'static,
+{
+    web::block(call)
+        .await
+        .map_err(|e| Refused::Failed(RtError::from(e.to_string())))?
+}
+
+// writes one file of the form to a temporary file in the asset directory
+async fn receive(
+    data: &web::Data<AppData>,
+    field: &mut actix_multipart::Field,
+    name: &str,
+) -> Result<Asset, Refused> {
+    let (dir, max_size) = store(data).map(|store| (store.dir.clone(), store.max_size))?;
+    let upload = Upload {
+        path: dir.join(format!("{}{}", UPLOAD_PREFIX, UPLOADS.fetch_add(1, Ordering::SeqCst))),
+        kept: false,
+    };
+    let (path, file_name) = (upload.path.clone(), name.to_string());
+    let mut file = blocking(move || {
+        std::fs::File::create(&path)
+            .map_err(|e| format!("Can not store {}: {}", file_name, e).into())
+    })
+    .await?;
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    while let Some(chunk) = field.next().await {
+        let chunk = match chunk {
+            Ok(chunk) if size + chunk.len() as u64 > max_size => {
+                return Err(Refused::TooLarge(format!(
+                    "{} exceeds the {} bytes of an asset",
+                    name, max_size
+                )))
+            }
+            Ok(chunk) => chunk,
+            Err(e) => return Err(RtError::new(RtStatus::InvalidArgument, e.to_string()).into()),
+        };
+        size += chunk.len() as u64;
+        hasher.update(&chunk);
+        let file_name = name.to_string();
+        file = blocking(move || match file.write_all(&chunk) {
+            Ok(()) => Ok(file),
+            Err(e) => Err(format!("Can not store {}: {}", file_name, e).into()),
+        })
+        .await?;
+    }
+    drop(file);
+    let id = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let asset = Asset {
+        id,
+        name: name.to_string(),
+        size,
+        content_type: field.content_type().map(|mime| mime.to_string()),
+    };
+    let data = data.clone();
+    blocking(move || store(&data)?.add(upload, asset)).await
+}
+
+/// Stores the files of a multipart form and returns them as
+/// `[{"id": "9f86...", "name": "map.pgm", "size": 1024, "content_type": "image/png"}]`. A file
+/// uploaded before keeps its id.
+#[post("/api/assets")]
+async fn upload_assets(data: web::Data<AppData>, mut form: Multipart) -> HttpResponse {
+    if let Err(e) = store(&data) {
+        return error_response(e);
+    }
+    let mut assets = Vec::new();
+    while let Some(field) = form.next().await {
+        let mut field = match field {
+            Ok(field) => field,
+            Err(e) => {
+                return error_response(RtError::new(RtStatus::InvalidArgument, e.to_string()))
+            }
+        };
+        // other fields of the form are ignored
+        let Some(name) = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        match receive(&data, &mut field, &name).await {
+            Ok(asset) => assets.push(asset),
+            Err(refused) => return refused.response(),
+        }
+    }
+    if assets.is_empty() {
+        return error_response(RtError::new(RtStatus::InvalidArgument, "No file in the form"));
+    }
+    HttpResponse::Ok().json(assets)
+}
+
+#[get("/api/assets")]
+async fn list_assets(data: web::Data<AppData>) -> HttpResponse {
+    match store(&data).and_then(|store| store.list()) {
+        Ok(assets) => HttpResponse::Ok().json(assets),
+        Err(e) => error_response(e),
+    }
+}
+
+/// The content of an asset, with its name as file name.
+#[get("/api/assets/{id}")]
+async fn get_asset(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    id: web::Path<String>,
+) -> HttpResponse {
+    let (store, asset) = match store(&data).and_then(|store| Ok((store, store.get(&id)?))) {
+        Ok(found) => found,
+        Err(e) => return error_response(e),
+    };
+    match actix_files::NamedFile::open(store.file(&asset.id)) {
+        Ok(file) => file
+            .set_content_disposition(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(asset.name)],
+            })
+            .respond_to(&req)
+            .map_into_boxed_body(),
+        Err(e) => error_response(RtError::from(e.to_string())),
+    }
+}
+
+#[delete("/api/assets/{id}")]
+async fn delete_asset(data: web::Data<AppData>, id: web::Path<String>) -> HttpResponse {
+    match store(&data).and_then(|store| store.remove(&id)) {
+        Ok(()) => HttpResponse::Ok().json(()),
+        Err(e) => error_response(e),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(upload_assets);
+    cfg.service(list_assets);
+    cfg.service(get_asset);
+    cfg.service(delete_asset);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn upload(dir: &Path, name: &str, content: &str) -> Upload {
+        let path = dir.join(format!("{}{}", UPLOAD_PREFIX, name));
+        std::fs::write(&path, content).unwrap();
+        Upload { path, kept: false }
+    }
+
+    fn asset(content: &str) -> Asset {
+        Asset {
+            id: format!("{:x}", Sha256::digest(content)),
+            name: format!("{}.txt", content),
+            size: content.len() as u64,
+            content_type: None,
+        }
+    }
+
+    #[test]
+    fn test_uploads() {
+        let dir = std::env::temp_dir().join(format!("rtime-uploads-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // left by an aborted upload before
+        let left = upload(&dir, "left", "partial");
+        std::mem::forget(left);
+        let store = Store::open(&dir, DEFAULT_MAX_SIZE, Some(8)).unwrap();
+        assert!(!dir.join(".upload-left").exists());
+
+        // an upload dropped before it is added is removed
+        drop(upload(&dir, "aborted", "partial"));
+        assert!(!dir.join(".upload-aborted").exists());
+
+        // concurrent uploads together stay within the size of all assets
+        let store = Arc::new(store);
+        let added: Vec<bool> = ["hello", "world"]
+            .map(|content| {
+                let store = store.clone();
+                let upload = upload(&dir, content, content);
+                std::thread::spawn(move || store.add(upload, asset(content)).is_ok())
+            })
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect();
+        assert_eq!(added.iter().filter(|added| **added).count(), 1);
+        assert_eq!(store.list().unwrap().len(), 1);
+        let mut files: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.retain(|name| name.starts_with(UPLOAD_PREFIX));
+        assert!(files.is_empty(), "{:?}", files);
+
+        drop(store);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,103 @@
+// Optional authentication of the endpoints changing state. Reads, the dashboard and the
+// websocket stay open, writing the blackboard and reloading libraries need a bearer token or a
+// configured user.
+use super::ApiError;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::collections::HashMap;
+
+/// Credentials accepted for writes, set by the `auth_token` and `users` attributes. Without any
+/// everybody may write.
+#[derive(Debug, Default, Clone)]
+pub struct Auth {
+    pub token: Option<String>,          // `Authorization: Bearer <token>`
+    pub users: HashMap<String, String>, // basic auth, password of every user
+}
+
+impl Auth {
+    pub fn enabled(&self) -> bool {
+        self.token.is_some() || !self.users.is_empty()
+    }
+
+    /// Whether the value of an `Authorization` header matches the token or a user.
+    pub fn authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(authorization) = authorization else {
+            return false;
+        };
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return self
+                .token
+                .as_ref()
+                .is_some_and(|expected| same(expected, token.trim()));
+        }
+        let Some(encoded) = authorization.strip_prefix("Basic ") else {
+            return false;
+        };
+        let Some(credentials) = STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+        else {
+            return false;
+        };
+        let Some((user, password)) = credentials.split_once(':') else {
+            return false;
+        };
+        self.users
+            .get(user)
+            .is_some_and(|expected| same(expected, password))
+    }
+
+    fn challenge(&self) -> &'static str {
+        if self.users.is_empty() {
+            "Bearer"
+        } else {
+            "Basic realm=\"rtime\""
+        }
+    }
+}
+
+// compares every byte, so the time taken tells nothing about how much of a secret matched
+fn same(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn changes_state(request: &ServiceRequest) -> bool {
+    !matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Middleware answering requests changing state with 401 unless they carry valid credentials.
+/// Reads the `Auth` from the app data.
+pub async fn require_auth(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let auth = request.app_data::<web::Data<Auth>>().cloned();
+    if let Some(auth) = auth.filter(|auth| auth.enabled() && changes_state(&request)) {
+        let authorization = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        if !auth.authorized(authorization) {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, auth.challenge()))
+                .json(ApiError {
+                    error: "Authentication required".to_string(),
+                });
+            return Ok(request.into_response(response).map_into_right_body());
+        }
+    }
+    next.call(request)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,225 @@
+// Calls of capabilities from the api, e.g. to home an axis while commissioning. `POST
+// /api/capabilities/arm_home` with `{"args": [2]}` calls `arm_home` of the running library
+// providing it and answers `{"result": 0}`. Only capabilities listed in `capabilities` of the
+// config are called, and only ones returning `i32` with no or one argument of a primitive type,
+// checked against their declared signature.
+use super::{error_response, AppData};
+use actix_web::{post, web, HttpResponse, Responder};
+use interfaces::capabilities::Capability;
+use interfaces::signature;
+use interfaces::status::{RtError, RtStatus};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ffi::{c_char, c_int, CString};
+
+/// The body of a call, the arguments in the order of the signature.
+#[derive(Debug, Default, Deserialize)]
+pub struct Call {
+    #[serde(default)]
+    pub args: Vec<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct Answer {
+    capability: String,
+    signature: String,
+    result: c_int,
+    status: Option<String>, // of a negative result
+}
+
+/// Whether `name` is in `allowed`, by name or by a prefix like `arm_*`.
+pub fn allowed(allowed: &[String], name: &str) -> bool {
+    allowed.iter().any(|entry| match entry.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => entry == name,
+    })
+}
+
+fn invalid(message: String) -> RtError {
+    RtError::new(RtStatus::InvalidArgument, message)
+}
+
+fn integer<T: TryFrom<i64> + TryFrom<u64>>(value: &Value) -> Result<T, RtError> {
+    let converted = match (value.as_i64(), value.as_u64()) {
+        (_, Some(value)) => T::try_from(value).ok(),
+        (Some(value), _) => T::try_from(value).ok(),
+        _ => None,
+    };
+    converted.ok_or_else(|| invalid(format!("{} does not fit the integer of the signature", value)))
+}
+
+fn float(value: &Value) -> Result<f64, RtError> {
+    value
+        .as_f64()
+        .ok_or_else(|| invalid(format!("{} is not a number", value)))
+}
+
+fn boolean(value: &Value) -> Result<bool, RtError> {
+    value
+        .as_bool()
+        .ok_or_else(|| invalid(format!("{} is not a boolean", value)))
+}
+
+fn cstring(value: &Value) -> Result<CString, RtError> {
+    let string = value
+        .as_str()
+        .ok_or_else(|| invalid(format!("{} is not a string", value)))?;
+    CString::new(string).map_err(|e| invalid(e.to_string()))
+}
+
+// calls the function of `capability` as `F` with `args`
+macro_rules! call {
+    ($capability:expr, $fn:ty $(, $arg:expr)*) => {{
+        let function = unsafe { $capability.get::<$fn>() }.map_err(RtError::from)?;
+        Ok(unsafe { function($($arg),*) })
+    }};
+}
+
+/// Calls `capability` with `args`, which have to match its signature.
+pub fn invoke(capability: &Capability, args: &[Value]) -> Result<c_int, RtError> {
+    let declared = signature::normalize(&capability.signature());
+    let arity = match declared.as_str() {
+        "i32()" => 0,
+        "i32(i32)" | "i32(u32)" | "i32(i64)" | "i32(u64)" | "i32(f32)" | "i32(f64)"
+        | "i32(bool)" | "i32(cstr)" => 1,
+        _ => {
+            return Err(invalid(format!(
+                "Capability '{}' of signature '{}' can not be called from the api",
+                capability.name(),
+                declared
+            )))
+        }
+    };
+    if args.len() != arity {
+        return Err(invalid(format!(
+            "{} takes {} arguments, {} given",
+            declared,
+            arity,
+            args.len()
+        )));
+    }
+    match declared.as_str() {
+        "i32()" => call!(capability, unsafe extern "C" fn() -> c_int),
+        "i32(i32)" => call!(capability, unsafe extern "C" fn(i32) -> c_int, integer(&args[0])?),
+        "i32(u32)" => call!(capability, unsafe extern "C" fn(u32) -> c_int, integer(&args[0])?),
+        "i32(i64)" => call!(capability, unsafe extern "C" fn(i64) -> c_int, integer(&args[0])?),
+        "i32(u64)" => call!(capability, unsafe extern "C" fn(u64) -> c_int, integer(&args[0])?),
+        "i32(f32)" => {
+            call!(capability, unsafe extern "C" fn(f32) -> c_int, float(&args[0])? as f32)
+        }
+        "i32(f64)" => call!(capability, unsafe extern "C" fn(f64) -> c_int, float(&args[0])?),
+        "i32(bool)" => call!(capability, unsafe extern "C" fn(bool) -> c_int, boolean(&args[0])?),
+        "i32(cstr)" => {
+            let string = cstring(&args[0])?;
+            call!(capability, unsafe extern "C" fn(*const c_char) -> c_int, string.as_ptr())
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Calls the capability `name` with the `args` of the body, e.g. `{"args": [2]}`.
+#[post("/api/capabilities/{name}")]
+async fn call_capability(
+    data: web::Data<AppData>,
+    name: web::Path<String>,
+    body: web::Bytes,
+) -> impl Responder {
+    let name = name.into_inner();
+    if !allowed(&data.capabilities, &name) {
+        let error = format!("Capability '{}' is not listed in `capabilities`", name);
+        return error_response(RtError::new(RtStatus::AccessDenied, error));
+    }
+    // a call without arguments needs no body
+    let call = match body.is_empty() {
+        true => Ok(Call::default()),
+        false => serde_json::from_slice::<Call>(&body),
+    };
+    let args = match call {
+        Ok(call) => call.args,
+        Err(e) => return error_response(invalid(format!("Invalid call: {}", e))),
+    };
+    // the capability may block, like the blackboard calls
+    let answer = web::block(move || {
+        let capability = interfaces::runtime::capability(&data.client.caps(), &name)?;
+        let result = invoke(&capability, &args)?;
+        Ok::<_, RtError>(Answer {
+            capability: name,
+            signature: signature::normalize(&capability.signature()),
+            result,
+            status: (result < 0).then(|| RtStatus::from_code(result).to_string()),
+        })
+    })
+    .await;
+    match answer {
+        Ok(Ok(answer)) => HttpResponse::Ok().json(answer),
+        Ok(Err(e)) => error_response(e),
+        Err(e) => error_response(RtError::from(e.to_string())),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(call_capability);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::ffi::{c_void, CStr};
+
+    extern "C" fn home() -> c_int {
+        7
+    }
+
+    extern "C" fn double(value: i32) -> c_int {
+        value * 2
+    }
+
+    extern "C" fn scale(value: f64) -> c_int {
+        (value * 10.0) as c_int
+    }
+
+    extern "C" fn length(text: *const c_char) -> c_int {
+        unsafe { CStr::from_ptr(text) }.to_bytes().len() as c_int
+    }
+
+    extern "C" fn pair(_: i32, _: i32) -> c_int {
+        0
+    }
+
+    fn capability(function: *mut c_void, signature: &str) -> Capability {
+        Capability::with_signature("test", function, signature)
+    }
+
+    #[test]
+    fn test_invoke() {
+        let home = capability(home as *mut c_void, "i32()");
+        assert_eq!(invoke(&home, &[]).unwrap(), 7);
+        let double = capability(double as *mut c_void, "i32(i32)");
+        assert_eq!(invoke(&double, &[json!(21)]).unwrap(), 42);
+        let scale = capability(scale as *mut c_void, "i32(f64)");
+        assert_eq!(invoke(&scale, &[json!(1.5)]).unwrap(), 15);
+        // integers are numbers as well
+        assert_eq!(invoke(&scale, &[json!(2)]).unwrap(), 20);
+        let length = capability(length as *mut c_void, "i32( cstr )");
+        assert_eq!(invoke(&length, &[json!("rtime")]).unwrap(), 5);
+
+        let status = |result: Result<c_int, RtError>| result.unwrap_err().status;
+        assert_eq!(status(invoke(&home, &[json!(1)])), RtStatus::InvalidArgument);
+        assert_eq!(status(invoke(&double, &[])), RtStatus::InvalidArgument);
+        assert_eq!(status(invoke(&double, &[json!("21")])), RtStatus::InvalidArgument);
+        assert_eq!(status(invoke(&double, &[json!(1u64 << 40)])), RtStatus::InvalidArgument);
+        assert_eq!(status(invoke(&length, &[json!(5)])), RtStatus::InvalidArgument);
+        let pair = capability(pair as *mut c_void, "i32(i32,i32)");
+        assert_eq!(status(invoke(&pair, &[json!(1), json!(2)])), RtStatus::InvalidArgument);
+    }
+
+    #[test]
+    fn test_allowed() {
+        let list = vec!["arm_home".to_string(), "gripper_*".to_string()];
+        assert!(allowed(&list, "arm_home"));
+        assert!(allowed(&list, "gripper_open"));
+        assert!(!allowed(&list, "arm_home_all"));
+        assert!(!allowed(&[], "arm_home"));
+    }
+}
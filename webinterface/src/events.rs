@@ -0,0 +1,155 @@
+// Server-sent events of the blackboard changes, for clients that can not keep a websocket open,
+// e.g. behind proxies. One subscription to all keys feeds a log of the recent changes, so a
+// client reconnecting with `Last-Event-ID` gets the changes it missed.
+use super::{key_filters, AppData, ChangeEvent};
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use interfaces::blackboard::key_matches;
+use interfaces::blackboard_client::Subscription;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+// changes kept for clients resuming
+const RETAINED: usize = 1000;
+// comment sent to idle clients, so proxies keep the connection
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+struct Event {
+    id: u64,
+    key: String,
+    data: String,
+}
+
+/// The last changes of the blackboard, numbered from 1 since the server started.
+pub struct EventLog {
+    events: Mutex<VecDeque<Event>>,
+    latest: watch::Sender<u64>, // id of the last change
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog {
+            events: Mutex::new(VecDeque::new()),
+            latest: watch::channel(0).0,
+        }
+    }
+}
+
+impl EventLog {
+    fn push(&self, event: &ChangeEvent) {
+        let mut events = self.events.lock().unwrap();
+        let id = *self.latest.borrow() + 1;
+        events.push_back(Event {
+            id,
+            key: event.key.clone(),
+            data: serde_json::to_string(event).unwrap_or_default(),
+        });
+        if events.len() > RETAINED {
+            events.pop_front();
+        }
+        self.latest.send_replace(id);
+    }
+
+    // frames of the changes after `id` matching `filters` and the id of the last change
+    fn since(&self, id: u64, filters: &[String]) -> (String, u64) {
+        let events = self.events.lock().unwrap();
+        let mut frames = String::new();
+        for event in events.iter().filter(|event| event.id > id) {
+            if filters.iter().any(|filter| key_matches(filter, &event.key)) {
+                frames.push_str(&format!("id: {}\nevent: change\ndata: {}\n\n", event.id, event.data));
+            }
+        }
+        (frames, events.back().map_or(id, |event| event.id.max(id)))
+    }
+}
+
+/// Subscribes the log of `data` to every key of the blackboard, until the subscription is
+/// dropped.
+pub fn feed(data: web::Data<AppData>) -> Result<Subscription, String> {
+    let feed_data = data.clone();
+    data.client.subscribe("*", "webinterface/events", move |key| {
+        if let Some(event) = ChangeEvent::read(&feed_data.client, key) {
+            feed_data.events.push(&event);
+        }
+    })
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    keys: Option<String>,
+    last_event_id: Option<u64>, // for the first request, browsers only send the header on reconnects
+}
+
+// state of one connection
+struct Connection {
+    data: web::Data<AppData>,
+    filters: Vec<String>,
+    last_id: u64,
+    latest: watch::Receiver<u64>,
+    closing: watch::Receiver<bool>,
+}
+
+/// Streams the changes of the blackboard as server-sent events with the data of
+/// `/ws/blackboard` and the same `keys` filter. `Last-Event-ID` resumes after that change as
+/// long as it is one of the last 1000, without it the stream starts with the next change.
+#[get("/events")]
+async fn blackboard_events(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    query: web::Query<EventsQuery>,
+) -> HttpResponse {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(query.last_event_id);
+    let latest = data.events.latest.subscribe();
+    let current = *latest.borrow();
+    let last_id = match last_event_id {
+        Some(id) if id <= current => id,
+        // ids start again with a restarted server
+        Some(_) => 0,
+        None => current,
+    };
+    let connection = Connection {
+        filters: key_filters(query.keys.as_deref()),
+        closing: data.closing.clone(),
+        data,
+        last_id,
+        latest,
+    };
+
+    let stream = futures::stream::unfold(connection, |mut connection| async move {
+        loop {
+            let (frames, last_id) = connection
+                .data
+                .events
+                .since(connection.last_id, &connection.filters);
+            connection.last_id = last_id;
+            if !frames.is_empty() {
+                return Some((Ok::<_, actix_web::Error>(web::Bytes::from(frames)), connection));
+            }
+            tokio::select! {
+                changed = connection.latest.changed() => {
+                    if changed.is_err() {
+                        return None;
+                    }
+                }
+                // the server only stops once all connections are closed
+                _ = connection.closing.changed() => return None,
+                _ = tokio::time::sleep(HEARTBEAT) => {
+                    return Some((Ok(web::Bytes::from_static(b": heartbeat\n\n")), connection));
+                }
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(stream)
+}
@@ -0,0 +1,157 @@
+// Generic settings forms. The schema of the blackboard is extended by what a form needs to
+// edit a key, an update of several keys is checked against it before anything is written.
+use super::{blackboard_call, error_response, AppData};
+use actix_web::{get, patch, post, web, HttpResponse, Responder};
+use interfaces::blackboard::{BlackboardKeyInfo, BlackboardOperation, TypedBlackboardValue};
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::status::{RtError, RtStatus};
+use serde_json::{json, Map, Value};
+
+// `nav` covers the key `nav` and every key below `nav/`, `*` covers all keys
+pub fn in_namespace(key: &str, namespaces: &[String]) -> bool {
+    namespaces.iter().any(|namespace| {
+        namespace == "*"
+            || key
+                .strip_prefix(namespace.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+// keeps a bound the blackboard declares for the key, see the constraints of the blackboard
+fn bound(property: &mut Value, name: &str, value: Value) {
+    if property[name].is_null() {
+        property[name] = value;
+    }
+}
+
+// adds the blackboard type as `x-rt-type`, `readOnly` and the range of the type to every key
+pub fn form_schema(mut schema: Value, keys: &[BlackboardKeyInfo], read_only: &[String]) -> Value {
+    let Some(properties) = schema["properties"].as_object_mut() else {
+        return schema;
+    };
+    for (key, property) in properties.iter_mut() {
+        // a key written between reading the schema and the types can not be edited safely
+        let value_type = keys.iter().find(|info| info.key == *key).map(|info| &info.value_type);
+        property["readOnly"] = (value_type.is_none() || in_namespace(key, read_only)).into();
+        let Some(value_type) = value_type else {
+            continue;
+        };
+        property["x-rt-type"] = value_type.as_str().into();
+        match value_type.as_str() {
+            "int" => {
+                bound(property, "minimum", i32::MIN.into());
+                bound(property, "maximum", i32::MAX.into());
+            }
+            "int64" => {
+                bound(property, "minimum", i64::MIN.into());
+                bound(property, "maximum", i64::MAX.into());
+            }
+            "timestamp" => bound(property, "minimum", 0.into()),
+            "int_array" => {
+                bound(&mut property["items"], "minimum", i32::MIN.into());
+                bound(&mut property["items"], "maximum", i32::MAX.into());
+            }
+            _ => {}
+        }
+    }
+    schema
+}
+
+/// JSON schema of the blackboard like `/api/schema`, every key is extended by its blackboard
+/// type in `x-rt-type`, `readOnly` and the range of integers. A key named `schema` is answered
+/// like `/api/blackboard/{key}` instead.
+#[get("/api/blackboard/schema")]
+async fn get_schema(data: web::Data<AppData>) -> impl Responder {
+    let read_only = data.read_only.clone();
+    blackboard_call(data, move |client| {
+        let keys = client.keys()?;
+        if keys.iter().any(|info| info.key == "schema") {
+            let value = client.get_value("schema")?;
+            return serde_json::to_value(value).map_err(|e| RtError::from(e.to_string()));
+        }
+        Ok(form_schema(client.schema()?, &keys, &read_only))
+    })
+    .await
+}
+
+// the typed value of every update, fails for unknown, read only and mistyped keys
+fn typed_updates(
+    client: &BlackboardClient,
+    updates: Map<String, Value>,
+    read_only: &[String],
+) -> Result<Vec<(String, TypedBlackboardValue)>, RtError> {
+    let keys = client.keys()?;
+    updates
+        .into_iter()
+        .map(|(key, value)| {
+            let info = keys.iter().find(|info| info.key == key).ok_or_else(|| {
+                RtError::new(RtStatus::KeyNotFound, format!("Key not found: {}", key))
+            })?;
+            if in_namespace(&key, read_only) {
+                return Err(RtError::new(
+                    RtStatus::AccessDenied,
+                    format!("Key {} is read only", key),
+                ));
+            }
+            let value = serde_json::from_value(json!({"type": info.value_type, "value": value}))
+                .map_err(|e| {
+                    RtError::new(
+                        RtStatus::InvalidArgument,
+                        format!("Invalid {} for key {}: {}", info.value_type, key, e),
+                    )
+                })?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// Writes the keys of a json object like `{"speed": 2.5, "mode": "auto"}`, each keeping its
+/// type. Nothing is written if one of them does not match the form schema. Returns the keys
+/// written.
+#[patch("/api/blackboard")]
+async fn patch_keys(data: web::Data<AppData>, updates: web::Json<Map<String, Value>>) -> impl Responder {
+    let read_only = data.read_only.clone();
+    blackboard_call(data, move |client| {
+        let updates = typed_updates(client, updates.into_inner(), &read_only)?;
+        for (key, value) in &updates {
+            client.set_value(key, value)?;
+        }
+        Ok(updates.into_iter().map(|(key, _)| key).collect::<Vec<_>>())
+    })
+    .await
+}
+
+/// Applies a json array of operations like
+/// `{"op": "set", "key": "speed", "value": {"type": "double", "value": 2.5}}` and
+/// `{"op": "delete", "key": "mode"}`, all or none. Subscribers are notified once per key.
+/// Returns the result of every operation, with 409 if they were not applied.
+#[post("/api/blackboard/batch")]
+async fn batch(
+    data: web::Data<AppData>,
+    operations: web::Json<Vec<BlackboardOperation>>,
+) -> HttpResponse {
+    let operations = operations.into_inner();
+    if let Some(operation) = operations
+        .iter()
+        .find(|operation| in_namespace(operation.key(), &data.read_only))
+    {
+        let message = format!("Key {} is read only", operation.key());
+        return error_response(RtError::new(RtStatus::AccessDenied, message));
+    }
+    match web::block(move || data.client.transaction(&operations)).await {
+        Ok(Ok(results)) if results.iter().all(|result| result.applied) => {
+            HttpResponse::Ok().json(results)
+        }
+        Ok(Ok(results)) => HttpResponse::Conflict().json(results),
+        Ok(Err(e)) => error_response(e),
+        Err(e) => error_response(RtError::from(e.to_string())),
+    }
+}
+
+// registered in front of `/api/blackboard/{key}`, only the schema shares its method, see
+// `get_schema`
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_schema);
+    cfg.service(patch_keys);
+    cfg.service(batch);
+}
@@ -1,31 +1,57 @@
-use actix_web::{get, web, App, HttpServer, Responder};
+mod alerts;
+mod assets;
+mod auth;
+mod capabilities;
+mod events;
+mod forms;
+mod metrics;
+mod nodes;
+mod openapi;
+mod projects;
+mod sandbox;
+
+use actix_web::http::StatusCode;
+use actix_web::{
+    delete, get, middleware, post, put, web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use auth::Auth;
+use interfaces::blackboard::{BlackboardValue, TypedBlackboardValue};
+use interfaces::blackboard_client::{BlackboardClient, Subscription};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::os::raw::{c_char, c_int};
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::runtime::Runtime;
+use tokio::sync::{mpsc, watch};
 
+use interfaces::bindings::rt_context;
+use interfaces::context::Context;
+use interfaces::lifecycle::Lifecycle;
+use interfaces::status::{catch_panic, RtError, RtStatus};
+use interfaces_macros::rt_plugin;
 use log::{debug, error, info, warn};
 
-static SUMMARY_MESSAGE: &str = "{
-    \"name\": \"webinterface\",
-    \"summary\": \"web backend\",
-    \"library_type\": \"Service\",
-    \"version\": \"0.1.0\",
-    \"provides\": [
-        {
-            \"capability\": \"webinterface_start\",
-            \"entry\": \"start\"
-        },
-        {
-            \"capability\": \"webinterface_stop\",
-            \"entry\": \"stop\"
-        }
-    ],
-    \"requires\": [\"blackboard\"]
-}\0";
+// served at `/` unless `static_dir` is configured
+const DASHBOARD: &str = include_str!("dashboard.html");
 
 struct Config {
     hostname: String,
     port: u16,
+    static_dir: Option<PathBuf>, // served at `/` instead of the bundled dashboard
+    auth: Auth,
+    tls_cert: Option<PathBuf>, // pem files, the server speaks https if both are set
+    tls_key: Option<PathBuf>,
+    cors_origins: Option<Vec<String>>, // origins of browser frontends, `*` allows any
+    body_limit: Option<usize>,         // of request bodies in bytes, actix' defaults otherwise
+    request_log: bool,                 // logs every request
+    read_only: Vec<String>,            // blackboard namespaces forms may not write, e.g. `health`
+    asset_dir: Option<PathBuf>,        // uploads of `/api/assets`, disabled without
+    asset_max_size: u64,               // of one asset in bytes
+    asset_quota: Option<u64>,          // of all assets in bytes
+    nodes: BTreeMap<String, nodes::Node>, // served at `/api/nodes/<name>/`
+    capabilities: Vec<String>, // callable by `/api/capabilities/<name>`, e.g. `[arm_home, arm_*]`
 }
 
 impl Default for Config {
@@ -33,6 +59,19 @@ impl Default for Config {
         Config {
             hostname: "127.0.0.1".to_string(),
             port: 8080,
+            static_dir: None,
+            auth: Auth::default(),
+            tls_cert: None,
+            tls_key: None,
+            cors_origins: None,
+            body_limit: None,
+            request_log: false,
+            read_only: Vec::new(),
+            asset_dir: None,
+            asset_max_size: assets::DEFAULT_MAX_SIZE,
+            asset_quota: None,
+            nodes: BTreeMap::new(),
+            capabilities: Vec::new(),
         }
     }
 }
@@ -52,6 +91,123 @@ impl Config {
                         config.port = value.clone() as u16;
                     }
                 }
+                "static_dir" => {
+                    if let interfaces::blackboard::BlackboardValue::String(value) = &entry.value {
+                        config.static_dir = Some(PathBuf::from(value));
+                    }
+                }
+                "tls_cert" => {
+                    if let interfaces::blackboard::BlackboardValue::String(value) = &entry.value {
+                        config.tls_cert = Some(PathBuf::from(value));
+                    }
+                }
+                "tls_key" => {
+                    if let interfaces::blackboard::BlackboardValue::String(value) = &entry.value {
+                        config.tls_key = Some(PathBuf::from(value));
+                    }
+                }
+                "cors_origins" => match &entry.value {
+                    interfaces::blackboard::BlackboardValue::String(origin) => {
+                        config.cors_origins = Some(vec![origin.clone()]);
+                    }
+                    interfaces::blackboard::BlackboardValue::Array(origins) => {
+                        config.cors_origins = Some(
+                            origins
+                                .iter()
+                                .filter_map(|origin| match origin {
+                                    interfaces::blackboard::BlackboardValue::String(origin) => {
+                                        Some(origin.clone())
+                                    }
+                                    _ => None,
+                                })
+                                .collect(),
+                        );
+                    }
+                    _ => {}
+                },
+                "read_only" => {
+                    if let interfaces::blackboard::BlackboardValue::Array(namespaces) = &entry.value {
+                        config.read_only = namespaces
+                            .iter()
+                            .filter_map(|namespace| match namespace {
+                                interfaces::blackboard::BlackboardValue::String(namespace) => {
+                                    Some(namespace.clone())
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                    }
+                }
+                "capabilities" => {
+                    if let interfaces::blackboard::BlackboardValue::Array(names) = &entry.value {
+                        config.capabilities = names
+                            .iter()
+                            .filter_map(|name| match name {
+                                interfaces::blackboard::BlackboardValue::String(name) => {
+                                    Some(name.clone())
+                                }
+                                _ => None,
+                            })
+                            .collect();
+                    }
+                }
+                "body_limit" => {
+                    if let interfaces::blackboard::BlackboardValue::Int(value) = &entry.value {
+                        config.body_limit = usize::try_from(*value).ok();
+                    }
+                }
+                "asset_dir" => {
+                    if let interfaces::blackboard::BlackboardValue::String(value) = &entry.value {
+                        config.asset_dir = Some(PathBuf::from(value));
+                    }
+                }
+                "asset_max_size" | "asset_quota" => {
+                    let size = match &entry.value {
+                        BlackboardValue::Int(value) => u64::try_from(*value),
+                        BlackboardValue::Int64(value) => u64::try_from(*value),
+                        _ => continue,
+                    };
+                    match (entry.key.as_str(), size) {
+                        ("asset_max_size", Ok(size)) => config.asset_max_size = size,
+                        (_, Ok(size)) => config.asset_quota = Some(size),
+                        (key, Err(_)) => warn!("{} must not be negative", key),
+                    }
+                }
+                "request_log" => {
+                    if let interfaces::blackboard::BlackboardValue::Bool(value) = &entry.value {
+                        config.request_log = *value;
+                    }
+                }
+                "auth_token" => {
+                    if let interfaces::blackboard::BlackboardValue::String(value) = &entry.value {
+                        config.auth.token = Some(value.clone());
+                    }
+                }
+                // `{robot1: "http://10.0.0.5:8080", robot2: {url: ..., token: ...}}`
+                "nodes" => {
+                    if let interfaces::blackboard::BlackboardValue::Json(nodes) = &entry.value {
+                        match nodes::parse(nodes) {
+                            Ok(nodes) => config.nodes = nodes,
+                            Err(e) => warn!("{}", e),
+                        }
+                    }
+                }
+                // `{alice: secret, bob: other}`
+                "users" => {
+                    if let interfaces::blackboard::BlackboardValue::Json(
+                        serde_json::Value::Object(users),
+                    ) = &entry.value
+                    {
+                        for (user, password) in users {
+                            match password.as_str() {
+                                Some(password) => {
+                                    config.auth.users.insert(user.clone(), password.to_string());
+                                }
+                                None => warn!("Password of user {} is not a string", user),
+                            }
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -59,66 +215,414 @@ impl Config {
     }
 }
 
-#[get("/startproject")]
-async fn start_project(data: web::Data<AppData>) -> impl Responder {
-
-    web::block(
-        move || {
-            data.caps
-                .get("blackboard_set_string")
-                .map(|cap| {
-                    unsafe {
-                        let f: interfaces::capabilities::Function<
-                            unsafe extern "C" fn(*const c_char, *const c_char) -> c_int,
-                        > = cap.get().unwrap();
-                        let result = f(
-                            "start_project\0".as_ptr() as *const c_char,
-                            "{\"value\": \"Hello World\"}\0".as_ptr() as *const c_char,
-                        );
+// reads the certificate chain and private key, both pem encoded
+fn tls_config(cert: &Path, key: &Path) -> Result<rustls::ServerConfig, RtError> {
+    let open = |path: &Path| {
+        std::fs::File::open(path)
+            .map(std::io::BufReader::new)
+            .map_err(|e| format!("Can not read {}: {}", path.display(), e))
+    };
+    let certs = rustls_pemfile::certs(&mut open(cert)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate {}: {}", cert.display(), e))?;
+    let key = rustls_pemfile::private_key(&mut open(key)?)
+        .map_err(|e| format!("Invalid private key {}: {}", key.display(), e))?
+        .ok_or_else(|| format!("No private key in {}", key.display()))?;
 
-                        debug!("Start server project: {}", result);
-                        format!("Start project: {}", result)
-                    }
+    // the provider is given explicitly, so it does not depend on the features of other crates
+    rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| RtError::new(RtStatus::InvalidArgument, format!("Invalid tls setup: {}", e)))
+}
+
+// allows the origins to call the api from a browser, `*` allows all
+fn cors(origins: &[String]) -> actix_cors::Cors {
+    let cors = actix_cors::Cors::default()
+        .allowed_methods(["GET", "PUT", "PATCH", "POST", "DELETE"])
+        .allowed_headers([
+            actix_web::http::header::AUTHORIZATION,
+            actix_web::http::header::CONTENT_TYPE,
+        ])
+        .max_age(3600);
+    if origins.iter().any(|origin| origin == "*") {
+        return cors.allow_any_origin();
+    }
+    origins
+        .iter()
+        .fold(cors, |cors, origin| cors.allowed_origin(origin))
+}
+
+// maps the status of a failed blackboard call to the http status
+fn error_response(error: RtError) -> HttpResponse {
+    let status = match error.status {
+        RtStatus::KeyNotFound => StatusCode::NOT_FOUND,
+        RtStatus::TypeMismatch | RtStatus::ValueMismatch | RtStatus::AlreadyRunning => {
+            StatusCode::CONFLICT
+        }
+        RtStatus::InvalidArgument | RtStatus::NullArgument => StatusCode::BAD_REQUEST,
+        RtStatus::ConstraintViolation => StatusCode::UNPROCESSABLE_ENTITY,
+        RtStatus::NotRunning => StatusCode::SERVICE_UNAVAILABLE,
+        RtStatus::AccessDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    HttpResponse::build(status).json(ApiError {
+        error: error.message,
+    })
+}
+
+#[derive(Serialize)]
+struct ApiError {
+    error: String,
+}
+
+// the blackboard calls may block, so they run on the blocking pool
+async fn blackboard_call<T, F>(data: web::Data<AppData>, call: F) -> HttpResponse
+where
+    T: Serialize + Send + 'static,
+    F: FnOnce(&BlackboardClient) -> Result<T, RtError> + Send + 'static,
+{
+    match web::block(move || call(&data.client)).await {
+        Ok(Ok(value)) => HttpResponse::Ok().json(value),
+        Ok(Err(e)) => error_response(e),
+        Err(e) => error_response(RtError::from(e.to_string())),
+    }
+}
+
+/// Keys of the blackboard with their types, e.g. `[{"key": "answer", "type": "int"}]`.
+#[get("/api/blackboard")]
+async fn list_keys(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| client.keys()).await
+}
+
+/// Value of a key as `{"type": "int", "value": 42}`.
+#[get("/api/blackboard/{key:.*}")]
+async fn get_key(data: web::Data<AppData>, key: web::Path<String>) -> impl Responder {
+    blackboard_call(data, move |client| client.get_value(&key)).await
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    since: Option<u64>, // milliseconds since the unix epoch, all kept values without
+}
+
+/// Values of a key listed in the `history` of the blackboard, oldest first, e.g.
+/// `[{"time": 1714557600000, "value": {"type": "double", "value": 0.5}}]` for time-series plots.
+#[get("/api/blackboard/{key:.*}/history")]
+async fn key_history(
+    data: web::Data<AppData>,
+    key: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+) -> impl Responder {
+    let since = query.since.unwrap_or_default();
+    blackboard_call(data, move |client| client.history(&key, since)).await
+}
+
+/// Writes a value given as `{"type": "int", "value": 42}`, keeping its type.
+#[put("/api/blackboard/{key:.*}")]
+async fn put_key(
+    data: web::Data<AppData>,
+    key: web::Path<String>,
+    value: web::Json<TypedBlackboardValue>,
+) -> impl Responder {
+    blackboard_call(data, move |client| client.set_value(&key, &value)).await
+}
+
+#[delete("/api/blackboard/{key:.*}")]
+async fn delete_key(data: web::Data<AppData>, key: web::Path<String>) -> impl Responder {
+    blackboard_call(data, move |client| client.delete(&key)).await
+}
+
+/// JSON schema of the blackboard with the current values, see `as_json_schema`.
+#[get("/api/schema")]
+async fn blackboard_schema(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| client.schema()).await
+}
+
+// every connection subscribes as its own component, so they unsubscribe independently
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    keys: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ChangeEvent {
+    key: String,
+    value: Option<TypedBlackboardValue>,
+}
+
+impl ChangeEvent {
+    // reads the changed key, a removed key has no value
+    fn read(client: &BlackboardClient, key: &str) -> Option<Self> {
+        let value = match client.get_value(key) {
+            Ok(value) => Some(value),
+            Err(e) if e.status == RtStatus::KeyNotFound => None,
+            Err(e) => {
+                warn!("Can not read changed key {}: {}", key, e);
+                return None;
+            }
+        };
+        Some(ChangeEvent {
+            key: key.to_string(),
+            value,
+        })
+    }
+}
+
+// the `keys` parameter of the change streams, comma separated keys or prefixes ending with `*`
+fn key_filters(keys: Option<&str>) -> Vec<String> {
+    let mut keys: Vec<String> = keys
+        .unwrap_or("*")
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Streams the changes of the blackboard as text frames like
+/// `{"key": "answer", "value": {"type": "int", "value": 42}}`, `value` is null once the key is
+/// removed. `keys` filters them, e.g. `/ws/blackboard?keys=health,robot/*`, default is all keys.
+#[get("/ws/blackboard")]
+async fn blackboard_changes(
+    req: HttpRequest,
+    body: web::Payload,
+    data: web::Data<AppData>,
+    query: web::Query<ChangesQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let keys = key_filters(query.keys.as_deref());
+
+    let component = format!("webinterface/ws/{}", CONNECTIONS.fetch_add(1, Ordering::SeqCst));
+    let (sender, changes) = mpsc::unbounded_channel();
+    let subscribe_data = data.clone();
+    let subscriptions = web::block(move || {
+        keys.iter()
+            .map(|key| {
+                let sender = sender.clone();
+                subscribe_data.client.subscribe(key, &component, move |changed| {
+                    let _ = sender.send(changed.to_string());
                 })
-                .unwrap_or_else(|| "Capability not found".to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()
+    })
+    .await?;
+    let subscriptions = match subscriptions {
+        Ok(subscriptions) => subscriptions,
+        Err(e) => return Ok(error_response(RtError::from(e))),
+    };
+
+    match actix_ws::handle(&req, body) {
+        Ok((response, session, stream)) => {
+            actix_web::rt::spawn(stream_changes(data, session, stream, changes, subscriptions));
+            Ok(response)
         }
-    ).await.unwrap_or_else(|e| format!("Error: {:?}", e))
-    
+        Err(e) => {
+            // unsubscribing waits for the notification thread of the blackboard
+            let _ = web::block(move || drop(subscriptions)).await;
+            Err(e)
+        }
+    }
+}
 
+async fn stream_changes(
+    data: web::Data<AppData>,
+    mut session: actix_ws::Session,
+    mut stream: actix_ws::MessageStream,
+    mut changes: mpsc::UnboundedReceiver<String>,
+    subscriptions: Vec<Subscription>,
+) {
+    let mut closing = data.closing.clone();
+    data.metrics.websocket_opened();
+    loop {
+        tokio::select! {
+            message = stream.recv() => match message {
+                Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            Some(key) = changes.recv() => {
+                let read_data = data.clone();
+                let event = match web::block(move || ChangeEvent::read(&read_data.client, &key)).await {
+                    Ok(Some(event)) => event,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        error!("Can not read changed key: {}", e);
+                        continue;
+                    }
+                };
+                let frame = serde_json::to_string(&event).unwrap_or_default();
+                if session.text(frame).await.is_err() {
+                    break;
+                }
+            }
+            // the server only stops once all connections are closed
+            _ = closing.changed() => break,
+        }
+    }
+    data.metrics.websocket_closed();
+    let _ = web::block(move || drop(subscriptions)).await;
+    let _ = session.close(None).await;
+}
+
+/// State of all components as reported by the loader, see `interfaces::runtime`.
+#[get("/api/runtime/status")]
+async fn runtime_status(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| interfaces::runtime::status(&client.caps())).await
+}
+
+/// Every loaded library with its version, type, capabilities, requirements and state, see
+/// `interfaces::runtime::components`.
+#[get("/api/components")]
+async fn components(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| interfaces::runtime::components(&client.caps())).await
+}
+
+/// Restarts a running service and the services requiring it, the webinterface itself included.
+#[post("/api/runtime/restart/{component}")]
+async fn runtime_restart(data: web::Data<AppData>, component: web::Path<String>) -> impl Responder {
+    blackboard_call(data, move |client| {
+        interfaces::runtime::restart(&client.caps(), &component)
+    })
+    .await
+}
+
+// the loader watches `reload_library` and reloads the named library
+#[post("/reload/{name}")]
+async fn reload_library(data: web::Data<AppData>, name: web::Path<String>) -> impl Responder {
+    let name = name.into_inner();
+    web::block(move || match data.client.set_string("reload_library", &name) {
+        Ok(_) => format!("Reload library '{}' requested", name),
+        Err(e) => format!("Reload library '{}': {}", name, e),
+    })
+    .await
+    .unwrap_or_else(|e| format!("Error: {:?}", e))
+}
+
+// aggregate health of all services, published by the loader
+#[get("/health")]
+async fn health_summary(data: web::Data<AppData>) -> impl Responder {
+    web::block(move || match data.client.get_string("health") {
+        Ok(summary) => summary,
+        Err(e) => format!("Health: {}", e),
+    })
+    .await
+    .unwrap_or_else(|e| format!("Error: {:?}", e))
 }
 
 fn config_app(cfg: &mut web::ServiceConfig) {
-    cfg.service(start_project);
+    cfg.configure(forms::config);
+    cfg.service(list_keys);
+    // keys may contain `/`, a key ending with `/history` is read by its own route
+    cfg.service(key_history);
+    cfg.service(get_key);
+    cfg.service(put_key);
+    cfg.service(delete_key);
+    cfg.service(blackboard_changes);
+    cfg.service(events::blackboard_events);
+    cfg.service(reload_library);
+    cfg.service(health_summary);
+    cfg.service(blackboard_schema);
+    cfg.service(metrics::metrics);
+    cfg.service(runtime_status);
+    cfg.service(components);
+    cfg.service(runtime_restart);
+    cfg.configure(capabilities::config);
+    cfg.configure(alerts::config);
+    cfg.configure(projects::config);
+    cfg.configure(assets::config);
+    cfg.configure(nodes::config);
+    cfg.configure(sandbox::config);
+    cfg.service(openapi::openapi);
+    cfg.service(openapi::docs);
+}
+
+// registered last, so the routes above take precedence over files of the same name
+fn config_static(cfg: &mut web::ServiceConfig, static_dir: &Option<PathBuf>) {
+    match static_dir {
+        Some(dir) => {
+            cfg.service(actix_files::Files::new("/", dir).index_file("index.html"));
+        }
+        None => {
+            cfg.route(
+                "/",
+                web::get().to(|| async {
+                    HttpResponse::Ok()
+                        .content_type("text/html; charset=utf-8")
+                        .body(DASHBOARD)
+                }),
+            );
+        }
+    }
 }
 
 // Shared state to hold the server handle and shutdown signal
 struct ServerState {
+    address: String,
     server_task: tokio::task::JoinHandle<()>,
     server_handle: actix_web::dev::ServerHandle,
+    closing: watch::Sender<bool>,
+    events: Option<Subscription>, // feeds the log of `/events`
     rt: Runtime,
 }
 
 struct AppData {
-    caps: interfaces::capabilities::Capabilities,
+    client: BlackboardClient,
+    metrics: Arc<metrics::Metrics>,
+    events: events::EventLog,
+    closing: watch::Receiver<bool>, // tells the websocket and event connections to close
+    read_only: Vec<String>,
+    assets: Option<assets::Store>,
+    nodes: nodes::Nodes,
+    capabilities: Vec<String>, // see `capabilities::allowed`
+    sandboxes: sandbox::Sandboxes,
 }
 
-lazy_static::lazy_static! {
-    static ref SERVER_STATE: Mutex<Option<ServerState>> = Mutex::new(None);
+/// State of an instance of the webinterface, kept in the context the loader passes.
+#[derive(Default)]
+struct Instance {
+    lifecycle: Lifecycle,
+    server: Mutex<Option<ServerState>>,
 }
 
-#[no_mangle]
-pub extern "C" fn summary() -> *const c_char {
-    // summary message + null terminator
-    SUMMARY_MESSAGE.as_ptr() as *const c_char
+fn instance<'a>(context: *mut rt_context) -> Result<&'a Instance, RtError> {
+    unsafe { Context::from_raw(context) }?.state::<Instance>()
 }
 
+#[rt_plugin(
+    name = "webinterface",
+    summary = "web backend",
+    version = "0.1.0",
+    library_type = "Service",
+    capabilities_abi = 3,
+    provides(
+        webinterface_start = start: "i32(ctx,caps,cstr)",
+        webinterface_stop = stop: "i32(ctx)",
+        webinterface_health = health: "i32(ctx)",
+        webinterface_state = state: "i32(ctx)",
+        webinterface_health_status = health_status: "i32(ctx,*mut char,i32)",
+        webinterface_asset_path = asset_path: "i32(cstr,*mut char,i32)",
+    ),
+    requires("blackboard >= 0.1"),
+)]
+pub extern "C" fn summary() -> *const c_char;
+
 fn start_server(
+    instance: &Instance,
     caps: &interfaces::bindings::Capabilities,
     attributes: *const c_char,
-) -> Result<(), String> {
-    let mut state = SERVER_STATE.lock().unwrap();
+) -> Result<(), RtError> {
+    let mut state = instance.server.lock().unwrap();
     if state.is_some() {
-        return Err("Server is already running.".to_string());
+        return Err(RtError::new(RtStatus::AlreadyRunning, "Server is already running."));
     }
     let config = |attr: *const c_char| -> Result<*const c_char, String> {
         if !attr.is_null() {
@@ -145,16 +649,82 @@ fn start_server(
 
     info!("Starting server....");
 
+    let assets = config
+        .asset_dir
+        .as_deref()
+        .map(|dir| assets::Store::open(dir, config.asset_max_size, config.asset_quota))
+        .transpose()?;
+    let (closing, closing_receiver) = watch::channel(false);
+    let metrics = Arc::new(metrics::Metrics::default());
+    let timer = metrics.clone();
     let data = web::Data::new(AppData {
-        caps: interfaces::capabilities::Capabilities::from_raw(caps),
+        client: BlackboardClient::new(
+            interfaces::capabilities::Capabilities::from_raw(caps),
+        )
+        .with_timer(Arc::new(move |operation, duration| timer.observe_call(operation, duration))),
+        metrics,
+        events: events::EventLog::default(),
+        closing: closing_receiver,
+        read_only: config.read_only.clone(),
+        assets,
+        nodes: nodes::Nodes::new(config.nodes.clone())?,
+        capabilities: config.capabilities.clone(),
+        sandboxes: sandbox::Sandboxes::default(),
     });
 
     let rt = Runtime::new().map_err(|e| format!("Error starting async runtime\n Reason: {}", e))?;
-    let bind_server = HttpServer::new( move || App::new().configure(config_app)
-        .app_data(data.clone())
-)
-        .bind((config.hostname, config.port as u16))
-        .map_err(|e| format!("Error binding server\n Reason: {}", e))?;
+    let scheme = if config.tls_cert.is_some() { "https" } else { "http" };
+    let address = format!("{}://{}:{}", scheme, config.hostname, config.port);
+    if let Some(dir) = &config.static_dir {
+        if !dir.is_dir() {
+            return Err(RtError::new(
+                RtStatus::InvalidArgument,
+                format!("static_dir {} is not a directory", dir.display()),
+            ));
+        }
+    }
+    if !config.auth.enabled() && !["127.0.0.1", "localhost", "::1"].contains(&config.hostname.as_str()) {
+        warn!("No auth_token or users configured, everybody reaching {} may write", address);
+    }
+    let static_dir = config.static_dir.clone();
+    let feed_data = data.clone();
+    let auth = web::Data::new(config.auth);
+    let (cors_origins, body_limit, request_log) =
+        (config.cors_origins, config.body_limit, config.request_log);
+    // the last middleware wrapped runs first, cors answers preflight requests before the others
+    let bind_server = HttpServer::new(move || {
+        let mut app = App::new()
+            .configure(config_app)
+            .configure(|cfg| config_static(cfg, &static_dir))
+            .app_data(data.clone())
+            .app_data(auth.clone());
+        if let Some(limit) = body_limit {
+            app = app
+                .app_data(web::PayloadConfig::new(limit))
+                .app_data(web::JsonConfig::default().limit(limit));
+        }
+        app.wrap(middleware::from_fn(auth::require_auth))
+            .wrap(middleware::from_fn(metrics::count_requests))
+            .wrap(middleware::Condition::new(request_log, middleware::Logger::default()))
+            .wrap(middleware::Condition::new(
+                cors_origins.is_some(),
+                cors(cors_origins.as_deref().unwrap_or_default()),
+            ))
+    });
+    let bind_server = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            let tls = tls_config(cert, key)?;
+            bind_server.bind_rustls_0_23((config.hostname, config.port), tls)
+        }
+        (None, None) => bind_server.bind((config.hostname, config.port)),
+        _ => {
+            return Err(RtError::new(
+                RtStatus::InvalidArgument,
+                "tls_cert and tls_key have to be set together",
+            ))
+        }
+    }
+    .map_err(|e| format!("Error binding server\n Reason: {}", e))?;
     let server = bind_server.run();
     let server_handle: actix_web::dev::ServerHandle = server.handle();
 
@@ -162,9 +732,17 @@ fn start_server(
         server.await.unwrap();
     });
 
+    // the other endpoints work without
+    let events = events::feed(feed_data)
+        .map_err(|e| warn!("No blackboard events at /events: {}", e))
+        .ok();
+
     let server_state = ServerState {
+        address,
         server_task: server_task,
         server_handle: server_handle,
+        closing,
+        events,
         rt,
     };
 
@@ -176,35 +754,46 @@ fn start_server(
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn start(
+    context: *mut rt_context,
     caps: &interfaces::bindings::Capabilities,
     attributes: *const c_char,
 ) -> i32 {
-    env_logger::init();
-    match start_server(caps, attributes) {
+    let instance = match instance(context) {
+        Ok(instance) => instance,
+        Err(e) => return e.record(),
+    };
+    let name = unsafe { Context::from_raw(context) }.map_or("webinterface", |c| c.instance());
+    interfaces::logging::init_with_fallback(caps, name);
+    let result = catch_panic(|| start_server(instance, caps, attributes));
+    match instance.lifecycle.started(result) {
         Ok(_) => {
             info!("Server started");
             0
         }
         Err(e) => {
-            error!("Error starting server: {:?}", e);
-            -1
+            error!("Error starting server: {}", e);
+            e.record()
         }
     }
 }
 
-fn stop_server() -> Result<(), String> {
-    let mut state = SERVER_STATE
+fn stop_server(instance: &Instance) -> Result<(), RtError> {
+    let mut state = instance
+        .server
         .lock()
         .map_err(|e| format!("Error locking server state: {:?}", e))?;
 
     if state.is_none() {
-        return Err("Server is not running".to_string());
+        return Err(RtError::new(RtStatus::NotRunning, "Server is not running"));
     }
 
     info!("Stopping server");
     let server_state = state.take().unwrap();
     let rt = server_state.rt;
+    drop(server_state.events);
+    let _ = server_state.closing.send(true);
 
     rt.spawn(async move {
         server_state.server_handle.stop(true).await;
@@ -220,19 +809,90 @@ fn stop_server() -> Result<(), String> {
 }
 
 #[no_mangle]
-pub extern "C" fn stop() -> i32 {
-    match stop_server() {
+pub extern "C" fn stop(context: *mut rt_context) -> i32 {
+    let instance = match instance(context) {
+        Ok(instance) => instance,
+        Err(e) => return e.record(),
+    };
+    match instance.lifecycle.stopped(catch_panic(|| stop_server(instance))) {
         Ok(_) => {
             info!("Server stopped");
             0
         }
         Err(e) => {
-            error!("Error stopping server: {:?}", e);
-            -1
+            error!("Error stopping server: {}", e);
+            e.record()
         }
     }
 }
 
+/// `RT_OK` while the server runs, `RT_NOT_RUNNING` if it is stopped and `RT_ERROR` if it ended
+/// without being stopped.
+#[no_mangle]
+pub extern "C" fn health(context: *mut rt_context) -> i32 {
+    let instance = match instance(context) {
+        Ok(instance) => instance,
+        Err(e) => return e.record(),
+    };
+    let state = instance.server.lock().unwrap();
+    match state.as_ref() {
+        None => RtStatus::NotRunning.code(),
+        Some(state) if state.server_task.is_finished() => {
+            RtError::new(RtStatus::Error, "Server ended unexpectedly").record()
+        }
+        Some(_) => RtStatus::Ok.code(),
+    }
+}
+
+/// Lifecycle state of the server, see `interfaces::lifecycle`.
+#[no_mangle]
+pub extern "C" fn state(context: *mut rt_context) -> i32 {
+    match instance(context) {
+        Ok(instance) => instance.lifecycle.code(),
+        Err(e) => e.record(),
+    }
+}
+
+/// Writes the bound address as json, like `get_last_error`. Read by the supervisor of the loader.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn health_status(
+    context: *mut rt_context,
+    buffer: *mut c_char,
+    len: c_int,
+) -> c_int {
+    let instance = match instance(context) {
+        Ok(instance) => instance,
+        Err(e) => return e.record(),
+    };
+    let status = match instance.server.lock().unwrap().as_ref() {
+        Some(state) => serde_json::json!({"address": state.address}).to_string(),
+        None => "{}".to_string(),
+    };
+    unsafe { interfaces::status::copy_to_buffer(&status, buffer, len) }
+}
+
+fn asset_path_intern(id: *const c_char, buffer: *mut c_char, len: c_int) -> Result<c_int, RtError> {
+    if id.is_null() {
+        return Err(RtError::new(RtStatus::NullArgument, "Asset id is null pointer"));
+    }
+    let id = unsafe { std::ffi::CStr::from_ptr(id) }.to_string_lossy();
+    let path = assets::path(&id).ok_or_else(|| {
+        RtError::new(RtStatus::KeyNotFound, format!("Asset '{}' does not exist", id))
+    })?;
+    Ok(unsafe { interfaces::status::copy_to_buffer(&path.to_string_lossy(), buffer, len) })
+}
+
+/// Writes the path of the uploaded asset `id`, a capability for skills reading it.
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn asset_path(id: *const c_char, buffer: *mut c_char, len: c_int) -> c_int {
+    match catch_panic(|| asset_path_intern(id, buffer, len)) {
+        Ok(size) => size,
+        Err(e) => e.record(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -242,6 +902,19 @@ mod tests {
     use rstest::rstest;
     use serial_test::serial;
     use std::os::raw::c_int;
+    use std::sync::OnceLock;
+
+    // the tests share one instance, like the entries of a loaded plugin
+    fn context() -> *mut rt_context {
+        static CONTEXT: OnceLock<interfaces::context::OwnedContext> = OnceLock::new();
+        CONTEXT
+            .get_or_init(|| interfaces::context::OwnedContext::new("webinterface"))
+            .as_ptr()
+    }
+
+    fn test_instance() -> &'static Instance {
+        instance(context()).unwrap()
+    }
 
     #[test]
     #[serial]
@@ -263,20 +936,20 @@ mod tests {
     fn test_startup() {
         let caps = interfaces::capabilities::Capabilities::new();
 
-        let result = start_server(caps.inner(), std::ptr::null());
+        let result = start_server(test_instance(), caps.inner(), std::ptr::null());
         assert_eq!(result.is_ok(), true);
 
-        let result = start_server(caps.inner(), std::ptr::null());
+        let result = start_server(test_instance(), caps.inner(), std::ptr::null());
         assert_eq!(result.is_err(), true);
 
         // sleep for 1 second to allow server to start
         info!("Sleeping for 1 seconds");
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        let _ = stop();
+        let _ = stop(context());
 
-        let result = stop();
-        assert_eq!(result, -1);
+        let result = stop(context());
+        assert_eq!(result, RtStatus::NotRunning.code());
 
         let config = vec![
             interfaces::blackboard::BlackboardEntry {
@@ -290,22 +963,52 @@ mod tests {
         ]; // empty config
 
         let config = serde_yml::to_string(&config).unwrap() + "\0";
-        let result = start_server(caps.inner(), config.as_ptr() as *const c_char);
+        let result = start_server(test_instance(), caps.inner(), config.as_ptr() as *const c_char);
 
         assert_eq!(result.is_ok(), true);
 
         info!("Sleeping for 1 seconds");
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        let result = stop();
+        let result = stop(context());
         assert_eq!(result, 0);
     }
 
+    #[test_log::test]
+    #[serial]
+    fn test_tls() {
+        let _ = stop(context());
+        let caps = interfaces::capabilities::Capabilities::new();
+        let tls = concat!(env!("CARGO_MANIFEST_DIR"), "/../test_data/tls");
+        let config = format!(
+            "[{{key: port, value: 3334}}, {{key: tls_cert, value: {0}/cert.pem}}, \
+             {{key: tls_key, value: {0}/key.pem}}]\0",
+            tls
+        );
+        let config = config.as_ptr() as *const c_char;
+        assert!(start_server(test_instance(), caps.inner(), config).is_ok());
+
+        let status = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let client = reqwest::Client::builder()
+                .danger_accept_invalid_certs(true)
+                .build()
+                .unwrap();
+            client.get("https://127.0.0.1:3334/").send().await.unwrap().status()
+        });
+        assert_eq!(status, 200);
+        assert_eq!(stop(context()), 0);
+
+        // a key without a certificate is refused
+        let config = format!("[{{key: tls_key, value: {}/key.pem}}]\0", tls);
+        let result = start_server(test_instance(), caps.inner(), config.as_ptr() as *const c_char);
+        assert_eq!(result.unwrap_err().status, RtStatus::InvalidArgument);
+    }
+
     #[fixture]
     fn startup() -> c_int {
-        let _result = stop();
+        let _result = stop(context());
         let caps = interfaces::capabilities::Capabilities::new();
-        let result = start_server(caps.inner(), std::ptr::null());
+        let result = start_server(test_instance(), caps.inner(), std::ptr::null());
         return if result.is_ok() { 0 } else { -1 };
     }
 
@@ -328,13 +1031,13 @@ mod tests {
 
         {
             trace!("Spawning fetch_page");
-            let state = SERVER_STATE.lock().unwrap();
+            let state = test_instance().server.lock().unwrap();
             let rt = &state.as_ref().unwrap().rt;
             rt.spawn(fetch_page);
         }
         trace!("Sleeping for 1 seconds");
         std::thread::sleep(std::time::Duration::from_secs(3));
-        let result: i32 = stop();
+        let result: i32 = stop(context());
         assert_eq!(result, 0);
     }
 }
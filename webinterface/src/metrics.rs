@@ -0,0 +1,235 @@
+// Metrics of the webinterface in the Prometheus text format, scraped from `/metrics`.
+use super::AppData;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{get, web, HttpResponse, Responder};
+use interfaces::runtime::Metrics as LoaderMetrics;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>, // method, route and status
+    calls: Mutex<BTreeMap<String, (f64, u64)>>,           // seconds and count per operation
+    websockets: AtomicI64,
+}
+
+impl Metrics {
+    /// Records a call into the blackboard, see `BlackboardClient::with_timer`.
+    pub fn observe_call(&self, operation: &str, duration: Duration) {
+        let mut calls = self.calls.lock().unwrap();
+        let (seconds, count) = calls.entry(operation.to_string()).or_default();
+        *seconds += duration.as_secs_f64();
+        *count += 1;
+    }
+
+    pub fn websocket_opened(&self) {
+        self.websockets.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn websocket_closed(&self) {
+        self.websockets.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn observe_request(&self, method: &str, route: &str, status: u16) {
+        let key = (method.to_string(), route.to_string(), status);
+        *self.requests.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    /// All metrics, `services` is the health of every service as published by the loader and
+    /// `loader` the metrics of the loader, if it answered.
+    pub fn render(
+        &self,
+        services: &BTreeMap<String, String>,
+        loader: Option<&LoaderMetrics>,
+    ) -> String {
+        let mut text = String::new();
+        text.push_str("# HELP rtime_http_requests_total Requests by method, route and status.\n");
+        text.push_str("# TYPE rtime_http_requests_total counter\n");
+        for ((method, route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                text,
+                "rtime_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                label(method),
+                label(route),
+                status,
+                count
+            );
+        }
+
+        text.push_str("# HELP rtime_blackboard_call_seconds Duration of calls into the blackboard.\n");
+        text.push_str("# TYPE rtime_blackboard_call_seconds summary\n");
+        for (operation, (seconds, count)) in self.calls.lock().unwrap().iter() {
+            let operation = label(operation);
+            let _ = writeln!(
+                text,
+                "rtime_blackboard_call_seconds_sum{{operation=\"{}\"}} {}",
+                operation, seconds
+            );
+            let _ = writeln!(
+                text,
+                "rtime_blackboard_call_seconds_count{{operation=\"{}\"}} {}",
+                operation, count
+            );
+        }
+
+        text.push_str("# HELP rtime_websocket_connections Open connections of /ws/blackboard.\n");
+        text.push_str("# TYPE rtime_websocket_connections gauge\n");
+        let _ = writeln!(
+            text,
+            "rtime_websocket_connections {}",
+            self.websockets.load(Ordering::SeqCst)
+        );
+
+        text.push_str("# HELP rtime_service_up 1 if the service is running, 0 otherwise.\n");
+        text.push_str("# TYPE rtime_service_up gauge\n");
+        for (service, health) in services {
+            let _ = writeln!(
+                text,
+                "rtime_service_up{{service=\"{}\",health=\"{}\"}} {}",
+                label(service),
+                label(health),
+                (health == "running") as u8
+            );
+        }
+        if let Some(loader) = loader {
+            render_loader(&mut text, loader);
+        }
+        text
+    }
+}
+
+// the metrics of the loader, see `interfaces::runtime::metrics`
+fn render_loader(text: &mut String, loader: &LoaderMetrics) {
+    text.push_str("# HELP rtime_process_cpu_percent CPU used by the loader, of one core.\n");
+    text.push_str("# TYPE rtime_process_cpu_percent gauge\n");
+    let _ = writeln!(text, "rtime_process_cpu_percent {}", loader.process.cpu_percent);
+    text.push_str("# HELP rtime_process_resident_bytes Resident memory of the loader.\n");
+    text.push_str("# TYPE rtime_process_resident_bytes gauge\n");
+    let _ = writeln!(text, "rtime_process_resident_bytes {}", loader.process.resident_bytes);
+
+    text.push_str("# HELP rtime_thread_cpu_percent CPU used by a thread of the loader.\n");
+    text.push_str("# TYPE rtime_thread_cpu_percent gauge\n");
+    for thread in &loader.threads {
+        let _ = writeln!(
+            text,
+            "rtime_thread_cpu_percent{{thread=\"{}\",id=\"{}\"}} {}",
+            label(&thread.name),
+            thread.id,
+            thread.cpu_percent
+        );
+    }
+
+    text.push_str("# HELP rtime_component_start_seconds Duration of the last service start.\n");
+    text.push_str("# TYPE rtime_component_start_seconds gauge\n");
+    for (component, seconds) in &loader.starts {
+        let _ = writeln!(
+            text,
+            "rtime_component_start_seconds{{component=\"{}\"}} {}",
+            label(component),
+            seconds
+        );
+    }
+
+    text.push_str("# HELP rtime_skill_run_seconds Duration of the runs of a skill.\n");
+    text.push_str("# TYPE rtime_skill_run_seconds summary\n");
+    for (skill, runs) in &loader.skills {
+        let skill = label(skill);
+        let _ = writeln!(
+            text,
+            "rtime_skill_run_seconds_sum{{skill=\"{}\"}} {}",
+            skill, runs.total_seconds
+        );
+        let _ = writeln!(
+            text,
+            "rtime_skill_run_seconds_count{{skill=\"{}\"}} {}",
+            skill, runs.runs
+        );
+    }
+    text.push_str("# HELP rtime_skill_run_seconds_max Longest run of a skill.\n");
+    text.push_str("# TYPE rtime_skill_run_seconds_max gauge\n");
+    for (skill, runs) in &loader.skills {
+        let _ = writeln!(
+            text,
+            "rtime_skill_run_seconds_max{{skill=\"{}\"}} {}",
+            label(skill),
+            runs.max_seconds
+        );
+    }
+    text.push_str("# HELP rtime_skill_run_failures_total Runs of a skill that failed.\n");
+    text.push_str("# TYPE rtime_skill_run_failures_total counter\n");
+    for (skill, runs) in &loader.skills {
+        let _ = writeln!(
+            text,
+            "rtime_skill_run_failures_total{{skill=\"{}\"}} {}",
+            label(skill),
+            runs.failures
+        );
+    }
+
+    text.push_str("# HELP rtime_blackboard_operations_total Calls of a blackboard capability.\n");
+    text.push_str("# TYPE rtime_blackboard_operations_total counter\n");
+    for (operation, calls) in &loader.blackboard {
+        let _ = writeln!(
+            text,
+            "rtime_blackboard_operations_total{{operation=\"{}\"}} {}",
+            label(operation),
+            calls
+        );
+    }
+}
+
+// escapes a label value
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Middleware counting the requests, by the route they matched.
+pub async fn count_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let data = request.app_data::<web::Data<AppData>>().cloned();
+    let method = request.method().to_string();
+    let response = next.call(request).await?;
+    if let Some(data) = data {
+        // the route is known once the request was routed
+        let route = response.request().match_pattern();
+        data.metrics.observe_request(
+            &method,
+            route.as_deref().unwrap_or("unmatched"),
+            response.status().as_u16(),
+        );
+    }
+    Ok(response)
+}
+
+#[get("/metrics")]
+async fn metrics(data: web::Data<AppData>) -> impl Responder {
+    let read = data.clone();
+    // without the loader there is no health to report
+    let services = web::block(move || read.client.get_string("health"))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .and_then(|health| serde_json::from_str::<serde_json::Value>(&health).ok())
+        .and_then(|health| {
+            serde_json::from_value::<BTreeMap<String, String>>(health["services"].clone()).ok()
+        })
+        .unwrap_or_default();
+    let read = data.clone();
+    let loader = web::block(move || interfaces::runtime::metrics(&read.client.caps()))
+        .await
+        .ok()
+        .and_then(Result::ok);
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(data.metrics.render(&services, loader.as_ref()))
+}
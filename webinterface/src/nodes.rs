@@ -0,0 +1,293 @@
+// Remote rtime nodes, e.g. the robots of a fleet, monitored from this webinterface. The api of a
+// node is served under `/api/nodes/<name>/`, `/api/nodes/robot1/components` answers with
+// `/api/components` of `robot1`, and `/api/nodes` lists every node with its runtime status.
+use super::{error_response, ApiError, AppData};
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::http::StatusCode;
+use actix_web::{get, route, web, HttpRequest, HttpResponse, Responder};
+use interfaces::status::{RtError, RtStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// of a request to a node, an unreachable node does not keep the dashboard waiting
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The webinterface of a node, configured as its url or as `{url: ..., token: ...}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Node {
+    pub url: String,
+    pub token: Option<String>, // sent as `Authorization: Bearer <token>`
+}
+
+impl Node {
+    fn parse(name: &str, value: &serde_json::Value) -> Result<Self, String> {
+        let node = match value {
+            serde_json::Value::String(url) => Node {
+                url: url.clone(),
+                token: None,
+            },
+            value => serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid node {}: {}", name, e))?,
+        };
+        Ok(Node {
+            url: node.url.trim_end_matches('/').to_string(),
+            ..node
+        })
+    }
+}
+
+/// The `nodes` of the config, `{robot1: "http://10.0.0.5:8080", robot2: {url: ..., token: ...}}`.
+pub fn parse(value: &serde_json::Value) -> Result<BTreeMap<String, Node>, String> {
+    let nodes = value
+        .as_object()
+        .ok_or_else(|| "nodes must map names to urls".to_string())?;
+    nodes
+        .iter()
+        .map(|(name, node)| Ok((name.clone(), Node::parse(name, node)?)))
+        .collect()
+}
+
+/// What `/api/nodes` tells about a node.
+#[derive(Debug, Serialize)]
+struct NodeStatus {
+    url: String,
+    reachable: bool,
+    status: Option<serde_json::Value>, // `/api/runtime/status` of the node
+    error: Option<String>,
+}
+
+pub struct Nodes {
+    nodes: BTreeMap<String, Node>,
+    client: reqwest::Client,
+}
+
+impl Nodes {
+    pub fn new(nodes: BTreeMap<String, Node>) -> Result<Self, RtError> {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .map_err(|e| format!("Can not create the client of the nodes: {}", e))?;
+        Ok(Nodes { nodes, client })
+    }
+
+    fn request(&self, node: &Node, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, format!("{}/api/{}", node.url, path));
+        match &node.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn status(&self, node: &Node) -> NodeStatus {
+        let request = self.request(node, reqwest::Method::GET, "runtime/status");
+        let status = async {
+            let response = request.send().await.and_then(|response| response.error_for_status());
+            let body = response.map_err(|e| e.to_string())?.bytes().await;
+            serde_json::from_slice(&body.map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+        };
+        let status: Result<serde_json::Value, String> = status.await;
+        NodeStatus {
+            url: node.url.clone(),
+            reachable: status.is_ok(),
+            error: status.as_ref().err().cloned(),
+            status: status.ok(),
+        }
+    }
+
+    /// Forwards a request to `/api/<path>` of the node `name` and answers with its response.
+    /// An unknown node answers 404, one not answering 502 and a path leaving `/api` 400.
+    async fn forward(
+        &self,
+        name: &str,
+        method: &str,
+        path: &str,
+        query: &str,
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> HttpResponse {
+        let Some(node) = self.nodes.get(name) else {
+            let error = format!("Node '{}' is not configured", name);
+            return error_response(RtError::new(RtStatus::KeyNotFound, error));
+        };
+        if path.split(['/', '\\']).any(dot_segment) {
+            let error = format!("Path '{}' leaves the api of the node", path);
+            return error_response(RtError::new(RtStatus::InvalidArgument, error));
+        }
+        let method = reqwest::Method::from_bytes(method.as_bytes()).unwrap_or_default();
+        // the query is passed on as it was received, still encoded
+        let path = match query {
+            "" => path.to_string(),
+            query => format!("{}?{}", path, query),
+        };
+        let mut request = self.request(node, method, &path).body(body);
+        if let Some(content_type) = content_type {
+            request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+        }
+        let unreachable = |e: reqwest::Error| {
+            HttpResponse::BadGateway().json(ApiError {
+                error: format!("Node '{}' is not reachable: {}", name, e),
+            })
+        };
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => return unreachable(e),
+        };
+        let status =
+            StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        match response.bytes().await {
+            Ok(body) => {
+                let mut response = HttpResponse::build(status);
+                if let Some(content_type) = content_type {
+                    response.content_type(content_type);
+                }
+                response.body(body.to_vec())
+            }
+            Err(e) => unreachable(e),
+        }
+    }
+}
+
+// `.` or `..`, also encoded like `%2e%2e`, which the url of the node would resolve
+fn dot_segment(segment: &str) -> bool {
+    matches!(segment.to_ascii_lowercase().replace("%2e", ".").as_str(), "." | "..")
+}
+
+/// Every node with its url and runtime status, e.g. `{"robot1": {"url": "http://10.0.0.5:8080",
+/// "reachable": true, "status": {...}, "error": null}}`.
+#[get("/api/nodes")]
+async fn list_nodes(data: web::Data<AppData>) -> impl Responder {
+    let nodes = &data.nodes;
+    let statuses = nodes
+        .nodes
+        .iter()
+        .map(|(name, node)| async move { (name.clone(), nodes.status(node).await) });
+    let statuses: BTreeMap<String, NodeStatus> =
+        futures::future::join_all(statuses).await.into_iter().collect();
+    HttpResponse::Ok().json(statuses)
+}
+
+/// The api of a node, `/api/nodes/robot1/blackboard/answer` is `/api/blackboard/answer` of
+/// `robot1`.
+#[route(
+    "/api/nodes/{name}/{path:.*}",
+    method = "GET",
+    method = "PUT",
+    method = "POST",
+    method = "PATCH",
+    method = "DELETE"
+)]
+async fn forward(
+    req: HttpRequest,
+    data: web::Data<AppData>,
+    path: web::Path<(String, String)>,
+    body: web::Bytes,
+) -> impl Responder {
+    let name = path.into_inner().0;
+    // still encoded, so `blackboard/robot%2Fspeed` stays one key
+    let path = req.uri().path().splitn(5, '/').nth(4).unwrap_or_default();
+    let content_type = req.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    let method = req.method().as_str();
+    data.nodes
+        .forward(&name, method, path, req.query_string(), content_type, body.to_vec())
+        .await
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_nodes);
+    cfg.service(forward);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, HttpServer};
+
+    #[test]
+    fn test_parse() {
+        let nodes = serde_json::json!({
+            "robot1": "http://10.0.0.5:8080/",
+            "robot2": {"url": "https://robot2", "token": "secret"},
+        });
+        let nodes = parse(&nodes).unwrap();
+        assert_eq!(nodes["robot1"].url, "http://10.0.0.5:8080");
+        assert_eq!(nodes["robot2"].token.as_deref(), Some("secret"));
+
+        assert!(parse(&serde_json::json!(["http://robot1"])).is_err());
+        assert!(parse(&serde_json::json!({"robot1": {"token": "secret"}})).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_forward() {
+        // a node answering with what it was asked
+        let server = HttpServer::new(|| {
+            App::new().default_service(web::to(|req: HttpRequest, body: String| async move {
+                let authorization = req.headers().get("authorization").map(|value| {
+                    value.to_str().unwrap().to_string()
+                });
+                HttpResponse::Ok().json(serde_json::json!({
+                    "method": req.method().as_str(),
+                    "uri": req.uri().to_string(),
+                    "authorization": authorization,
+                    "body": body,
+                }))
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let node = |token: Option<&str>| Node {
+            url: format!("http://{}", address),
+            token: token.map(str::to_string),
+        };
+        let nodes = Nodes::new(BTreeMap::from([
+            ("robot1".to_string(), node(Some("secret"))),
+            ("robot2".to_string(), node(None)),
+            ("gone".to_string(), Node { url: "http://127.0.0.1:1".to_string(), token: None }),
+        ]))
+        .unwrap();
+
+        let body = b"{\"type\": \"int\", \"value\": 42}".to_vec();
+        let response = nodes
+            .forward("robot1", "PUT", "blackboard/robot%2Fspeed", "a=1", None, body)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+        let answer: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(answer["method"], "PUT");
+        assert_eq!(answer["uri"], "/api/blackboard/robot%2Fspeed?a=1");
+        assert_eq!(answer["authorization"], "Bearer secret");
+        assert_eq!(answer["body"], "{\"type\": \"int\", \"value\": 42}");
+
+        let status = nodes.status(&nodes.nodes["robot2"]).await;
+        assert!(status.reachable);
+        assert_eq!(status.status.unwrap()["uri"], "/api/runtime/status");
+
+        let response = nodes.forward("gone", "GET", "components", "", None, Vec::new()).await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert!(!nodes.status(&nodes.nodes["gone"]).await.reachable);
+        let response = nodes.forward("other", "GET", "components", "", None, Vec::new()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // the token of a node is only sent to its api
+        for path in ["../secret", "%2e%2e/secret", ".%2E/secret", "blackboard/./a", "a/..\\b"] {
+            let response = nodes.forward("robot1", "GET", path, "", None, Vec::new()).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path);
+        }
+        let dots = "blackboard/..a";
+        let response = nodes.forward("robot1", "GET", dots, "", None, Vec::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        handle.stop(true).await;
+    }
+}
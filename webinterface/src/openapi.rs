@@ -0,0 +1,827 @@
+// OpenAPI 3 description of the endpoints of the webinterface, served at `/api/openapi.json` and
+// rendered by Swagger UI at `/api/docs`. It is written by hand next to the handlers, a new
+// endpoint is added to `paths` as well.
+use actix_web::{get, HttpResponse, Responder};
+use serde_json::{json, Map, Value};
+
+// loads Swagger UI from a CDN, the document is fetched relative to `/api/docs`
+const DOCS: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>rtime api</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="docs"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({url: "openapi.json", dom_id: "#docs"});</script>
+</body>
+</html>
+"##;
+
+fn schema(name: &str) -> Value {
+    json!({"$ref": format!("#/components/schemas/{}", name)})
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({"application/json": {"schema": schema}})
+}
+
+// the responses of an endpoint answering with json, failed calls answer with an `Error`
+fn responses(description: &str, body: Value) -> Value {
+    json!({
+        "200": {"description": description, "content": json_content(body)},
+        "default": {"description": "The call failed", "content": json_content(schema("Error"))},
+    })
+}
+
+fn operation(id: &str, tag: &str, summary: &str, responses: Value) -> Value {
+    json!({"operationId": id, "tags": [tag], "summary": summary, "responses": responses})
+}
+
+// a write, which needs credentials once the webinterface has an `auth_token` or `users`
+fn write(mut operation: Value) -> Value {
+    operation["security"] = json!([{"bearer": []}, {"basic": []}, {}]);
+    operation
+}
+
+fn with_body(mut operation: Value, content: Value) -> Value {
+    operation["requestBody"] = json!({"required": true, "content": content});
+    operation
+}
+
+fn path_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": {"type": "string"},
+    })
+}
+
+fn keys_parameter() -> Value {
+    json!({
+        "name": "keys",
+        "in": "query",
+        "description": "Comma separated keys or prefixes ending with `*`, all keys by default",
+        "schema": {"type": "string"},
+    })
+}
+
+fn blackboard_paths(paths: &mut Map<String, Value>) {
+    let string_list = json!({"type": "array", "items": {"type": "string"}});
+    paths.insert(
+        "/api/blackboard".to_string(),
+        json!({
+            "get": operation(
+                "listKeys",
+                "blackboard",
+                "Keys of the blackboard with their types",
+                responses("The keys", json!({"type": "array", "items": schema("KeyInfo")})),
+            ),
+            "patch": write(with_body(
+                operation(
+                    "patchKeys",
+                    "blackboard",
+                    "Writes the keys of an object, each keeping its type, all or none",
+                    responses("The keys written", string_list.clone()),
+                ),
+                json_content(json!({"type": "object", "additionalProperties": true})),
+            )),
+        }),
+    );
+    paths.insert(
+        "/api/blackboard/schema".to_string(),
+        json!({"get": operation(
+            "getFormSchema",
+            "blackboard",
+            "JSON schema of the blackboard for forms, with x-rt-type, readOnly and ranges, \
+             the value of a key named schema if it exists",
+            responses("The schema", json!({"type": "object"})),
+        )}),
+    );
+    let results = json!({"type": "array", "items": schema("OperationResult")});
+    let mut batch = write(with_body(
+        operation(
+            "batch",
+            "blackboard",
+            "Applies set and delete operations, all or none",
+            responses("All operations were applied", results.clone()),
+        ),
+        json_content(json!({"type": "array", "items": schema("Operation")})),
+    ));
+    batch["responses"]["409"] = json!({
+        "description": "No operation was applied, the results tell why",
+        "content": json_content(results),
+    });
+    paths.insert("/api/blackboard/batch".to_string(), json!({"post": batch}));
+    paths.insert(
+        "/api/blackboard/{key}".to_string(),
+        json!({
+            "parameters": [path_parameter("key", "Key of the blackboard, may contain `/`")],
+            "get": operation(
+                "getKey",
+                "blackboard",
+                "Value of a key",
+                responses("The value", schema("TypedValue")),
+            ),
+            "put": write(with_body(
+                operation(
+                    "putKey",
+                    "blackboard",
+                    "Writes a value, keeping its type",
+                    responses("The value was written", json!({"nullable": true})),
+                ),
+                json_content(schema("TypedValue")),
+            )),
+            "delete": write(operation(
+                "deleteKey",
+                "blackboard",
+                "Removes a key",
+                responses("The key was removed", json!({"nullable": true})),
+            )),
+        }),
+    );
+    paths.insert(
+        "/api/blackboard/{key}/history".to_string(),
+        json!({"get": {
+            "operationId": "getKeyHistory",
+            "tags": ["blackboard"],
+            "summary": "Values of a key kept by the history of the blackboard, oldest first",
+            "parameters": [
+                path_parameter("key", "Key listed in the history of the blackboard"),
+                {
+                    "name": "since",
+                    "in": "query",
+                    "description": "Only values written later, in milliseconds since the epoch",
+                    "schema": {"type": "integer"},
+                },
+            ],
+            "responses": responses(
+                "The values",
+                json!({"type": "array", "items": schema("HistorySample")}),
+            ),
+        }}),
+    );
+    paths.insert(
+        "/api/schema".to_string(),
+        json!({"get": operation(
+            "getSchema",
+            "blackboard",
+            "JSON schema of the blackboard with the current values",
+            responses("The schema", json!({"type": "object"})),
+        )}),
+    );
+    paths.insert(
+        "/ws/blackboard".to_string(),
+        json!({"get": {
+            "operationId": "blackboardChanges",
+            "tags": ["blackboard"],
+            "summary": "Websocket streaming the changes of the blackboard as `Change` frames",
+            "parameters": [keys_parameter()],
+            "responses": {"101": {"description": "Switching to the websocket protocol"}},
+        }}),
+    );
+    let last_event_id = json!({
+        "name": "Last-Event-ID",
+        "in": "header",
+        "description": "Resumes after this change",
+        "schema": {"type": "integer"},
+    });
+    paths.insert(
+        "/events".to_string(),
+        json!({"get": {
+            "operationId": "blackboardEvents",
+            "tags": ["blackboard"],
+            "summary": "Server-sent events of the changes of the blackboard, as `Change` data",
+            "parameters": [keys_parameter(), last_event_id],
+            "responses": {"200": {
+                "description": "The event stream",
+                "content": {"text/event-stream": {"schema": {"type": "string"}}},
+            }},
+        }}),
+    );
+}
+
+fn runtime_paths(paths: &mut Map<String, Value>) {
+    paths.insert(
+        "/api/runtime/status".to_string(),
+        json!({"get": operation(
+            "runtimeStatus",
+            "runtime",
+            "State of all components as reported by the loader",
+            responses("The states", json!({"type": "object"})),
+        )}),
+    );
+    paths.insert(
+        "/api/components".to_string(),
+        json!({"get": operation(
+            "components",
+            "runtime",
+            "Loaded libraries with their version, type, capabilities, requirements and state",
+            responses("The libraries", json!({"type": "array", "items": {"type": "object"}})),
+        )}),
+    );
+    paths.insert(
+        "/api/runtime/restart/{component}".to_string(),
+        json!({
+            "parameters": [path_parameter("component", "Name of a running service")],
+            "post": write(operation(
+                "restartComponent",
+                "runtime",
+                "Restarts a running service and the services requiring it",
+                responses("The restart is scheduled", json!({"nullable": true})),
+            )),
+        }),
+    );
+    let mut call = write(operation(
+        "callCapability",
+        "runtime",
+        "Calls a capability listed in `capabilities` of the config, returning i32 with no or one \
+         argument of a primitive type",
+        responses(
+            "The result of the capability, with its status if negative",
+            json!({
+                "type": "object",
+                "properties": {
+                    "capability": {"type": "string"},
+                    "signature": {"type": "string"},
+                    "result": {"type": "integer"},
+                    "status": {"type": "string", "nullable": true},
+                },
+            }),
+        ),
+    ));
+    call["requestBody"] = json!({
+        "required": false,
+        "content": json_content(json!({
+            "type": "object",
+            "properties": {"args": {"type": "array", "items": {}}},
+        })),
+    });
+    paths.insert(
+        "/api/capabilities/{name}".to_string(),
+        json!({
+            "parameters": [path_parameter("name", "Name of a capability")],
+            "post": call,
+        }),
+    );
+    paths.insert(
+        "/reload/{name}".to_string(),
+        json!({
+            "parameters": [path_parameter("name", "Name of a library")],
+            "post": write(operation(
+                "reloadLibrary",
+                "runtime",
+                "Asks the loader to reload a library",
+                json!({"200": {
+                    "description": "What was requested",
+                    "content": {"text/plain": {"schema": {"type": "string"}}},
+                }}),
+            )),
+        }),
+    );
+    let text = |description: &str| {
+        json!({"200": {
+            "description": description,
+            "content": {"text/plain": {"schema": {"type": "string"}}},
+        }})
+    };
+    paths.insert(
+        "/health".to_string(),
+        json!({"get": operation(
+            "health",
+            "runtime",
+            "Aggregate health of all services as json",
+            text("The health published by the loader"),
+        )}),
+    );
+    paths.insert(
+        "/metrics".to_string(),
+        json!({"get": operation(
+            "metrics",
+            "runtime",
+            "Metrics of the webinterface and the blackboard in the Prometheus text format",
+            text("The metrics"),
+        )}),
+    );
+}
+
+fn project_paths(paths: &mut Map<String, Value>) {
+    let none = || json!({"nullable": true});
+    paths.insert(
+        "/api/projects".to_string(),
+        json!({"get": operation(
+            "listProjects",
+            "projects",
+            "Names of the stored projects",
+            responses("The names", json!({"type": "array", "items": {"type": "string"}})),
+        )}),
+    );
+    paths.insert(
+        "/api/projects/{name}".to_string(),
+        json!({
+            "parameters": [path_parameter("name", "Name of the project")],
+            "get": operation(
+                "getProject",
+                "projects",
+                "Definition of a project",
+                responses("The project", schema("Project")),
+            ),
+            "put": write(with_body(
+                operation(
+                    "putProject",
+                    "projects",
+                    "Stores a project given as YAML or JSON, named by the path",
+                    responses("The stored project", schema("Project")),
+                ),
+                json!({
+                    "application/json": {"schema": schema("Project")},
+                    "application/yaml": {"schema": {"type": "string"}},
+                }),
+            )),
+            "delete": write(operation(
+                "deleteProject",
+                "projects",
+                "Removes a project",
+                responses("The project was removed", none()),
+            )),
+        }),
+    );
+    paths.insert(
+        "/api/projects/{name}/select".to_string(),
+        json!({
+            "parameters": [path_parameter("name", "Name of the project")],
+            "post": write(operation(
+                "selectProject",
+                "projects",
+                "Makes the project the one started next",
+                responses("The project is selected", none()),
+            )),
+        }),
+    );
+    paths.insert(
+        "/api/project".to_string(),
+        json!({"get": operation(
+            "projectState",
+            "projects",
+            "State of the selected and the last started project",
+            responses("The state", schema("ProjectState")),
+        )}),
+    );
+    paths.insert(
+        "/api/project/start".to_string(),
+        json!({"post": write(operation(
+            "startProject",
+            "projects",
+            "Starts the selected project, unless a project is running",
+            responses("The project is started", none()),
+        ))}),
+    );
+    paths.insert(
+        "/api/project/stop".to_string(),
+        json!({"post": write(operation(
+            "stopProject",
+            "projects",
+            "Stops the running project before its next skill",
+            responses("The project stops", none()),
+        ))}),
+    );
+}
+
+fn asset_paths(paths: &mut Map<String, Value>) {
+    let assets = json!({"type": "array", "items": schema("Asset")});
+    let form = json!({
+        "type": "object",
+        "properties": {"file": {"type": "array", "items": {"type": "string", "format": "binary"}}},
+    });
+    paths.insert(
+        "/api/assets".to_string(),
+        json!({
+            "get": operation(
+                "listAssets",
+                "assets",
+                "The uploaded assets",
+                responses("The assets", assets.clone()),
+            ),
+            "post": write(with_body(
+                operation(
+                    "uploadAssets",
+                    "assets",
+                    "Stores the files of a multipart form, a file is too large with status 413",
+                    responses("The stored assets", assets),
+                ),
+                json!({"multipart/form-data": {"schema": form}}),
+            )),
+        }),
+    );
+    paths.insert(
+        "/api/assets/{id}".to_string(),
+        json!({
+            "parameters": [path_parameter("id", "Sha256 of the content of the asset")],
+            "get": operation(
+                "getAsset",
+                "assets",
+                "Content of an asset",
+                json!({"200": {
+                    "description": "The content",
+                    "content": {"application/octet-stream": {"schema": {"type": "string"}}},
+                }}),
+            ),
+            "delete": write(operation(
+                "deleteAsset",
+                "assets",
+                "Removes an asset",
+                responses("The asset was removed", json!({"nullable": true})),
+            )),
+        }),
+    );
+}
+
+fn alert_paths(paths: &mut Map<String, Value>) {
+    paths.insert(
+        "/api/alerts".to_string(),
+        json!({"get": operation(
+            "listAlerts",
+            "alerts",
+            "Status of the alerts raised by the alerts plugin",
+            responses("The alerts", json!({"type": "array", "items": schema("Alert")})),
+        )}),
+    );
+    let mut acknowledge = write(operation(
+        "acknowledgeAlert",
+        "alerts",
+        "Acknowledges a firing alert",
+        responses("The acknowledgment is written", json!({"nullable": true})),
+    ));
+    acknowledge["responses"]["409"] = json!({
+        "description": "The alert does not fire",
+        "content": json_content(schema("Error")),
+    });
+    paths.insert(
+        "/api/alerts/{name}/ack".to_string(),
+        json!({
+            "parameters": [path_parameter("name", "Name of an alert")],
+            "post": acknowledge,
+        }),
+    );
+}
+
+fn node_paths(paths: &mut Map<String, Value>) {
+    let statuses = json!({"type": "object", "additionalProperties": schema("NodeStatus")});
+    paths.insert(
+        "/api/nodes".to_string(),
+        json!({"get": operation(
+            "listNodes",
+            "nodes",
+            "The configured nodes with their runtime status",
+            responses("The nodes by name", statuses),
+        )}),
+    );
+    // the answer of the node is passed on as it is
+    let forwarded = |id: &str, summary: &str| {
+        operation(
+            id,
+            "nodes",
+            summary,
+            json!({
+                "default": {"description": "The answer of the node"},
+                "404": {"description": "No such node", "content": json_content(schema("Error"))},
+                "502": {
+                    "description": "The node is not reachable",
+                    "content": json_content(schema("Error")),
+                },
+            }),
+        )
+    };
+    paths.insert(
+        "/api/nodes/{name}/{path}".to_string(),
+        json!({
+            "parameters": [
+                path_parameter("name", "Name of a node"),
+                path_parameter("path", "Path below `/api/` of the node, e.g. `components`"),
+            ],
+            "get": forwarded("getNodeApi", "Reads from the api of a node"),
+            "put": write(forwarded("putNodeApi", "Writes through the api of a node")),
+            "post": write(forwarded("postNodeApi", "Posts to the api of a node")),
+            "delete": write(forwarded("deleteNodeApi", "Deletes through the api of a node")),
+        }),
+    );
+}
+
+fn sandbox_paths(paths: &mut Map<String, Value>) {
+    let none = || json!({"nullable": true});
+    let id = || path_parameter("id", "Id of a sandbox");
+    let change = json!({
+        "type": "object",
+        "required": ["key"],
+        "properties": {
+            "key": {"type": "string"},
+            "value": {"allOf": [schema("TypedValue")], "nullable": true},
+            "current": {"allOf": [schema("TypedValue")], "nullable": true},
+        },
+    });
+    let created = json!({"type": "object", "properties": {"id": {"type": "string"}}});
+    paths.insert(
+        "/api/sandbox".to_string(),
+        json!({"post": write(operation(
+            "createSandbox",
+            "sandbox",
+            "Opens a sandbox staging blackboard changes of a session",
+            responses("The id of the sandbox", created),
+        ))}),
+    );
+    let preview = json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string"},
+            "changes": {"type": "array", "items": change},
+        },
+    });
+    paths.insert(
+        "/api/sandbox/{id}".to_string(),
+        json!({
+            "parameters": [id()],
+            "get": operation(
+                "previewSandbox",
+                "sandbox",
+                "The staged changes with the live values they replace",
+                responses("The changes", preview),
+            ),
+            "delete": write(operation(
+                "discardSandbox",
+                "sandbox",
+                "Discards the staged changes",
+                responses("The sandbox is closed", none()),
+            )),
+        }),
+    );
+    paths.insert(
+        "/api/sandbox/{id}/schema".to_string(),
+        json!({
+            "parameters": [id()],
+            "get": operation(
+                "sandboxSchema",
+                "sandbox",
+                "Form schema of the blackboard with the staged changes, marked by x-rt-staged",
+                responses("The schema", json!({"type": "object"})),
+            ),
+        }),
+    );
+    paths.insert(
+        "/api/sandbox/{id}/blackboard/{key}".to_string(),
+        json!({
+            "parameters": [id(), path_parameter("key", "Key of the blackboard, may contain `/`")],
+            "put": write(with_body(
+                operation(
+                    "stageSet",
+                    "sandbox",
+                    "Stages writing a value",
+                    responses("The write is staged", none()),
+                ),
+                json_content(schema("TypedValue")),
+            )),
+            "delete": write(operation(
+                "stageDelete",
+                "sandbox",
+                "Stages deleting a key",
+                responses("The delete is staged", none()),
+            )),
+        }),
+    );
+    let results = json!({"type": "array", "items": schema("OperationResult")});
+    let mut commit = write(operation(
+        "commitSandbox",
+        "sandbox",
+        "Applies the staged changes, all or none, and closes the sandbox",
+        responses("All changes were applied", results.clone()),
+    ));
+    commit["responses"]["409"] = json!({
+        "description": "No change was applied, the results tell why, the sandbox is kept",
+        "content": json_content(results),
+    });
+    paths.insert(
+        "/api/sandbox/{id}/commit".to_string(),
+        json!({"parameters": [id()], "post": commit}),
+    );
+}
+
+fn schemas() -> Value {
+    let value_types = [
+        "string",
+        "int",
+        "int64",
+        "timestamp",
+        "float",
+        "double",
+        "bool",
+        "int_array",
+        "double_array",
+        "json",
+        "bytes",
+    ];
+    json!({
+        "Error": {
+            "type": "object",
+            "required": ["error"],
+            "properties": {"error": {"type": "string"}},
+        },
+        "NodeStatus": {
+            "type": "object",
+            "required": ["url", "reachable"],
+            "properties": {
+                "url": {"type": "string"},
+                "reachable": {"type": "boolean"},
+                "status": {"type": "object", "nullable": true},
+                "error": {"type": "string", "nullable": true},
+            },
+        },
+        "TypedValue": {
+            "type": "object",
+            "required": ["type", "value"],
+            "description": "A value with its blackboard type, bytes are base64 encoded",
+            "properties": {"type": {"type": "string", "enum": value_types}, "value": {}},
+            "example": {"type": "int", "value": 42},
+        },
+        "KeyInfo": {
+            "type": "object",
+            "required": ["key", "type"],
+            "properties": {
+                "key": {"type": "string"},
+                "type": {"type": "string", "enum": value_types},
+            },
+        },
+        "HistorySample": {
+            "type": "object",
+            "required": ["time", "value"],
+            "description": "`time` of the write in milliseconds since the epoch",
+            "properties": {"time": {"type": "integer"}, "value": schema("TypedValue")},
+        },
+        "Operation": {
+            "type": "object",
+            "required": ["op", "key"],
+            "description": "`value` is required for `set`",
+            "properties": {
+                "op": {"type": "string", "enum": ["set", "delete"]},
+                "key": {"type": "string"},
+                "value": schema("TypedValue"),
+            },
+        },
+        "OperationResult": {
+            "type": "object",
+            "required": ["key", "applied"],
+            "properties": {
+                "key": {"type": "string"},
+                "applied": {"type": "boolean"},
+                "error": {"type": "string"},
+            },
+        },
+        "Change": {
+            "type": "object",
+            "required": ["key", "value"],
+            "description": "`value` is null once the key is removed",
+            "properties": {
+                "key": {"type": "string"},
+                "value": {"allOf": [schema("TypedValue")], "nullable": true},
+            },
+        },
+        "Project": {
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "skills": {"type": "array", "items": {"type": "string"}},
+                "assets": {
+                    "type": "object",
+                    "description": "Ids of the uploaded assets of the project by name",
+                    "additionalProperties": {"type": "string"},
+                },
+            },
+        },
+        "Asset": {
+            "type": "object",
+            "required": ["id", "name", "size"],
+            "properties": {
+                "id": {"type": "string", "description": "Sha256 of the content"},
+                "name": {"type": "string"},
+                "size": {"type": "integer"},
+                "content_type": {"type": "string", "nullable": true},
+            },
+        },
+        "Alert": {
+            "type": "object",
+            "required": ["name", "state", "severity", "message", "since", "fired"],
+            "properties": {
+                "name": {"type": "string"},
+                "state": {"type": "string", "enum": ["ok", "pending", "firing", "acknowledged"]},
+                "severity": {"type": "string"},
+                "message": {"type": "string"},
+                "since": {
+                    "type": "integer",
+                    "description": "Start of the state in milliseconds since the unix epoch",
+                },
+                "fired": {"type": "integer"},
+            },
+        },
+        "ProjectState": {
+            "type": "object",
+            "properties": {
+                "selected": {"type": "string", "nullable": true},
+                "started": {"type": "string", "nullable": true},
+                "status": {
+                    "type": "string",
+                    "nullable": true,
+                    "enum": ["running", "finished", "failed", "stopped", null],
+                },
+                "progress": {"type": "integer", "nullable": true},
+                "error": {"type": "string", "nullable": true},
+                "results": {"type": "object", "additionalProperties": {"type": "integer"}},
+            },
+        },
+    })
+}
+
+/// The OpenAPI document of all endpoints.
+pub fn document() -> Value {
+    let mut paths = Map::new();
+    blackboard_paths(&mut paths);
+    runtime_paths(&mut paths);
+    project_paths(&mut paths);
+    asset_paths(&mut paths);
+    alert_paths(&mut paths);
+    node_paths(&mut paths);
+    sandbox_paths(&mut paths);
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "rtime webinterface",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Blackboard, runtime and project api of the rtime loader",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearer": {"type": "http", "scheme": "bearer"},
+                "basic": {"type": "http", "scheme": "basic"},
+            },
+        },
+    })
+}
+
+#[get("/api/openapi.json")]
+async fn openapi() -> impl Responder {
+    HttpResponse::Ok().json(document())
+}
+
+/// Swagger UI of `/api/openapi.json`.
+#[get("/api/docs")]
+async fn docs() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DOCS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every `$ref` in `value`
+    fn references<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::String(reference)) = object.get("$ref") {
+                    found.push(reference);
+                }
+                object.values().for_each(|value| references(value, found));
+            }
+            Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_document() {
+        let document = document();
+        let mut found = Vec::new();
+        references(&document, &mut found);
+        for reference in found {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(document["components"]["schemas"].get(name).is_some(), "{}", reference);
+        }
+
+        let mut ids: Vec<&str> = document["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .flat_map(|path| path.as_object().unwrap().values())
+            .filter_map(|operation| operation["operationId"].as_str())
+            .collect();
+        let operations = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), operations);
+        assert_eq!(document["paths"]["/api/blackboard/{key}"]["put"]["operationId"], "putKey");
+    }
+}
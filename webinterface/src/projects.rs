@@ -0,0 +1,180 @@
+// Stored projects and the state of the running one. Definitions are kept on the blackboard
+// under `projects/<name>`, starting one writes it to `start_project` for the skill runner of
+// the loader.
+use super::{blackboard_call, AppData};
+use actix_web::{delete, get, post, put, web, Responder};
+use interfaces::blackboard::TypedBlackboardValue;
+use interfaces::blackboard_client::BlackboardClient;
+use interfaces::project::{
+    Project, PROJECT_DEFINITION_PREFIX, PROJECT_ERROR_KEY, PROJECT_PROGRESS_KEY,
+    PROJECT_RESULT_PREFIX, PROJECT_SELECTED_KEY, PROJECT_STATUS_KEY, START_PROJECT_KEY,
+    STOP_PROJECT_KEY,
+};
+use interfaces::status::{RtError, RtStatus};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// State of the selected and the last started project, read from the keys of the skill runner.
+#[derive(Serialize)]
+struct ProjectState {
+    selected: Option<String>,
+    started: Option<String>,
+    status: Option<String>, // `running`, `finished`, `failed` or `stopped`
+    progress: Option<i32>,
+    error: Option<String>,
+    results: BTreeMap<String, i32>, // of the skills of the started project that ran
+}
+
+// value of a key, None if it does not exist
+fn read(client: &BlackboardClient, key: &str) -> Result<Option<TypedBlackboardValue>, RtError> {
+    match client.get_value(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.status == RtStatus::KeyNotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_string(client: &BlackboardClient, key: &str) -> Result<Option<String>, RtError> {
+    Ok(match read(client, key)? {
+        Some(TypedBlackboardValue::String(value)) => Some(value),
+        _ => None,
+    })
+}
+
+fn definition(client: &BlackboardClient, name: &str) -> Result<Project, RtError> {
+    let key = format!("{}{}", PROJECT_DEFINITION_PREFIX, name);
+    let description = read_string(client, &key)?.ok_or_else(|| {
+        RtError::new(RtStatus::KeyNotFound, format!("Project '{}' does not exist", name))
+    })?;
+    Ok(Project::parse(&description)?)
+}
+
+/// Names of the stored projects.
+#[get("/api/projects")]
+async fn list_projects(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| {
+        Ok(client
+            .keys()?
+            .into_iter()
+            .filter_map(|info| info.key.strip_prefix(PROJECT_DEFINITION_PREFIX).map(str::to_string))
+            .collect::<Vec<_>>())
+    })
+    .await
+}
+
+/// Definition of a project as `{"name": "demo", "skills": ["greet"]}`.
+#[get("/api/projects/{name}")]
+async fn get_project(data: web::Data<AppData>, name: web::Path<String>) -> impl Responder {
+    blackboard_call(data, move |client| definition(client, &name)).await
+}
+
+/// Stores a project given as YAML or JSON, its name is the one of the path.
+#[put("/api/projects/{name}")]
+async fn put_project(
+    data: web::Data<AppData>,
+    name: web::Path<String>,
+    body: String,
+) -> impl Responder {
+    let app = data.clone();
+    blackboard_call(data, move |client| {
+        let mut project = Project::parse(&body)
+            .map_err(|e| RtError::new(RtStatus::InvalidArgument, e))?;
+        project.name = name.into_inner();
+        for (asset, id) in &project.assets {
+            let store = app.assets.as_ref().ok_or_else(|| {
+                RtError::new(RtStatus::InvalidArgument, "No asset_dir is configured")
+            })?;
+            store.get(id).map_err(|_| {
+                let e = format!("Asset '{}' of {} does not exist", id, asset);
+                RtError::new(RtStatus::InvalidArgument, e)
+            })?;
+        }
+        let description = serde_json::to_string(&project).map_err(|e| e.to_string())?;
+        client.set_string(
+            &format!("{}{}", PROJECT_DEFINITION_PREFIX, project.name),
+            &description,
+        )?;
+        Ok(project)
+    })
+    .await
+}
+
+#[delete("/api/projects/{name}")]
+async fn delete_project(data: web::Data<AppData>, name: web::Path<String>) -> impl Responder {
+    blackboard_call(data, move |client| {
+        client.delete(&format!("{}{}", PROJECT_DEFINITION_PREFIX, name))
+    })
+    .await
+}
+
+/// Makes the project the one `POST /api/project/start` starts.
+#[post("/api/projects/{name}/select")]
+async fn select_project(data: web::Data<AppData>, name: web::Path<String>) -> impl Responder {
+    blackboard_call(data, move |client| {
+        definition(client, &name)?;
+        Ok(client.set_string(PROJECT_SELECTED_KEY, &name)?)
+    })
+    .await
+}
+
+#[get("/api/project")]
+async fn project_state(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| {
+        let started = read_string(client, START_PROJECT_KEY)?.and_then(|d| Project::parse(&d).ok());
+        let mut results = BTreeMap::new();
+        for skill in started.iter().flat_map(|project| &project.skills) {
+            let key = format!("{}{}", PROJECT_RESULT_PREFIX, skill);
+            if let Some(TypedBlackboardValue::Int(result)) = read(client, &key)? {
+                results.insert(skill.clone(), result);
+            }
+        }
+        Ok(ProjectState {
+            selected: read_string(client, PROJECT_SELECTED_KEY)?,
+            started: started.map(|project| project.name),
+            status: read_string(client, PROJECT_STATUS_KEY)?,
+            progress: match read(client, PROJECT_PROGRESS_KEY)? {
+                Some(TypedBlackboardValue::Int(progress)) => Some(progress),
+                _ => None,
+            },
+            error: read_string(client, PROJECT_ERROR_KEY)?,
+            results,
+        })
+    })
+    .await
+}
+
+/// Starts the selected project, unless a project is running.
+#[post("/api/project/start")]
+async fn start_project(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| {
+        let name = read_string(client, PROJECT_SELECTED_KEY)?
+            .ok_or_else(|| RtError::new(RtStatus::KeyNotFound, "No project is selected"))?;
+        if read_string(client, PROJECT_STATUS_KEY)?.as_deref() == Some("running") {
+            return Err(RtError::new(RtStatus::AlreadyRunning, "A project is running"));
+        }
+        let project = definition(client, &name)?;
+        let description = serde_json::to_string(&project).map_err(|e| e.to_string())?;
+        Ok(client.set_string(START_PROJECT_KEY, &description)?)
+    })
+    .await
+}
+
+/// Stops the running project before its next skill, the skill running now is not interrupted.
+#[post("/api/project/stop")]
+async fn stop_project(data: web::Data<AppData>) -> impl Responder {
+    blackboard_call(data, |client| {
+        client.set_value(STOP_PROJECT_KEY, &TypedBlackboardValue::Bool(true))
+    })
+    .await
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_projects);
+    cfg.service(get_project);
+    cfg.service(put_project);
+    cfg.service(delete_project);
+    cfg.service(select_project);
+    cfg.service(project_state);
+    cfg.service(start_project);
+    cfg.service(stop_project);
+}
@@ -0,0 +1,308 @@
+// Sandboxes of a UI session. Edits of the blackboard are staged in a sandbox, previewed over the
+// live values and then committed all or none by a transaction, or discarded, so a live robot
+// never runs with half of an edit. Sandboxes are kept in memory, one untouched for
+// `IDLE_TIMEOUT` is dropped, and at most `MAX_SANDBOXES` are open.
+use super::forms::{form_schema, in_namespace};
+use super::{blackboard_call, error_response, ApiError, AppData};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use interfaces::blackboard::{BlackboardKeyInfo, BlackboardOperation, TypedBlackboardValue};
+use interfaces::status::{RtError, RtStatus};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::c_void;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+const MAX_SANDBOXES: usize = 64;
+
+struct Sandbox {
+    operations: BTreeMap<String, BlackboardOperation>, // the last staged one of every key
+    used: Instant,
+}
+
+#[derive(Default)]
+pub struct Sandboxes {
+    sandboxes: Mutex<HashMap<String, Sandbox>>,
+}
+
+impl Sandboxes {
+    // fails with `RtStatus::ConstraintViolation` once `MAX_SANDBOXES` are open
+    fn create(&self) -> Result<String, RtError> {
+        let mut sandboxes = self.sandboxes.lock().unwrap();
+        sandboxes.retain(|_, sandbox| sandbox.used.elapsed() < IDLE_TIMEOUT);
+        if sandboxes.len() >= MAX_SANDBOXES {
+            return Err(RtError::new(
+                RtStatus::ConstraintViolation,
+                format!("{} sandboxes are open, commit or discard one", sandboxes.len()),
+            ));
+        }
+        let id = loop {
+            let id = sandbox_id()?;
+            if !sandboxes.contains_key(&id) {
+                break id;
+            }
+        };
+        let sandbox = Sandbox {
+            operations: BTreeMap::new(),
+            used: Instant::now(),
+        };
+        sandboxes.insert(id.clone(), sandbox);
+        Ok(id)
+    }
+
+    // the staged operations, in the order of their keys
+    fn operations(&self, id: &str) -> Result<Vec<BlackboardOperation>, RtError> {
+        self.with(id, |sandbox| sandbox.operations.values().cloned().collect())
+    }
+
+    fn stage(&self, id: &str, operation: BlackboardOperation) -> Result<(), RtError> {
+        self.with(id, |sandbox| {
+            sandbox.operations.insert(operation.key().to_string(), operation);
+        })
+    }
+
+    fn remove(&self, id: &str) -> Result<(), RtError> {
+        match self.sandboxes.lock().unwrap().remove(id) {
+            Some(_) => Ok(()),
+            None => Err(unknown(id)),
+        }
+    }
+
+    fn with<T>(&self, id: &str, f: impl FnOnce(&mut Sandbox) -> T) -> Result<T, RtError> {
+        let mut sandboxes = self.sandboxes.lock().unwrap();
+        let sandbox = sandboxes
+            .get_mut(id)
+            .filter(|sandbox| sandbox.used.elapsed() < IDLE_TIMEOUT)
+            .ok_or_else(|| unknown(id))?;
+        sandbox.used = Instant::now();
+        Ok(f(sandbox))
+    }
+}
+
+// not guessable by another session, the id is all it takes to commit
+fn sandbox_id() -> Result<String, RtError> {
+    let mut bytes = [0u8; 16];
+    let read = unsafe { libc::getrandom(bytes.as_mut_ptr() as *mut c_void, bytes.len(), 0) };
+    if read != bytes.len() as isize {
+        return Err(RtError::new(
+            RtStatus::Error,
+            format!("No random sandbox id: {}", std::io::Error::last_os_error()),
+        ));
+    }
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn unknown(id: &str) -> RtError {
+    RtError::new(RtStatus::KeyNotFound, format!("Sandbox '{}' does not exist", id))
+}
+
+#[derive(Serialize)]
+struct Change {
+    key: String,
+    value: Option<TypedBlackboardValue>,   // staged, `None` deletes the key
+    current: Option<TypedBlackboardValue>, // live, `None` if the key does not exist
+}
+
+// the json schema type of a key set to `value`, like the blackboard writes it
+fn property(value: &TypedBlackboardValue) -> Value {
+    let items = |items: &str| json!({"type": "array", "items": {"type": items}});
+    match value {
+        TypedBlackboardValue::String(_) | TypedBlackboardValue::Bytes(_) => {
+            json!({"type": "string"})
+        }
+        TypedBlackboardValue::Int(_) | TypedBlackboardValue::Int64(_) => json!({"type": "integer"}),
+        TypedBlackboardValue::Timestamp(_) => json!({"type": "integer", "format": "monotonic-ns"}),
+        TypedBlackboardValue::Float(_) | TypedBlackboardValue::Double(_) => {
+            json!({"type": "number"})
+        }
+        TypedBlackboardValue::Bool(_) => json!({"type": "boolean"}),
+        TypedBlackboardValue::IntArray(_) => items("integer"),
+        TypedBlackboardValue::DoubleArray(_) => items("number"),
+        TypedBlackboardValue::Json(_) => json!({}),
+    }
+}
+
+/// Opens a sandbox, answers with its id like `{"id": "9f86d081884c7d65a9f86d081884c7d6"}`, or
+/// with 429 while too many are open.
+#[post("/api/sandbox")]
+async fn create(data: web::Data<AppData>) -> impl Responder {
+    match data.sandboxes.create() {
+        Ok(id) => HttpResponse::Ok().json(json!({"id": id})),
+        Err(e) if e.status == RtStatus::ConstraintViolation => {
+            HttpResponse::TooManyRequests().json(ApiError { error: e.message })
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+/// The staged changes with the live values they replace, e.g.
+/// `{"id": "...", "changes": [{"key": "speed", "value": {...}, "current": {...}}]}`.
+#[get("/api/sandbox/{id}")]
+async fn preview(data: web::Data<AppData>, id: web::Path<String>) -> HttpResponse {
+    let operations = match data.sandboxes.operations(&id) {
+        Ok(operations) => operations,
+        Err(e) => return error_response(e),
+    };
+    blackboard_call(data, move |client| {
+        let changes = operations
+            .into_iter()
+            .map(|operation| {
+                let current = match client.get_value(operation.key()) {
+                    Ok(value) => Some(value),
+                    Err(e) if e.status == RtStatus::KeyNotFound => None,
+                    Err(e) => return Err(e),
+                };
+                Ok(match operation {
+                    BlackboardOperation::Set { key, value } => Change {
+                        key,
+                        value: Some(value),
+                        current,
+                    },
+                    BlackboardOperation::Delete { key } => Change {
+                        key,
+                        value: None,
+                        current,
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, RtError>>()?;
+        Ok(json!({"id": *id, "changes": changes}))
+    })
+    .await
+}
+
+/// The form schema of `/api/blackboard/schema` as it is after a commit. Staged keys are marked by
+/// `x-rt-staged`, deleted keys are left out.
+#[get("/api/sandbox/{id}/schema")]
+async fn schema(data: web::Data<AppData>, id: web::Path<String>) -> HttpResponse {
+    let operations = match data.sandboxes.operations(&id) {
+        Ok(operations) => operations,
+        Err(e) => return error_response(e),
+    };
+    let read_only = data.read_only.clone();
+    blackboard_call(data, move |client| {
+        let mut schema = client.schema()?;
+        let mut keys = client.keys()?;
+        for operation in operations {
+            keys.retain(|info| info.key != operation.key());
+            let Some(properties) = schema["properties"].as_object_mut() else {
+                break;
+            };
+            let (key, value) = match operation {
+                BlackboardOperation::Set { key, value } => (key, value),
+                BlackboardOperation::Delete { key } => {
+                    properties.remove(&key);
+                    continue;
+                }
+            };
+            let mut staged = property(&value);
+            staged["value"] = serde_json::to_value(&value).unwrap_or_default()["value"].take();
+            staged["x-rt-staged"] = true.into();
+            keys.push(BlackboardKeyInfo {
+                key: key.clone(),
+                value_type: value.type_name().to_string(),
+            });
+            properties.insert(key, staged);
+        }
+        Ok(form_schema(schema, &keys, &read_only))
+    })
+    .await
+}
+
+fn stage(data: &AppData, id: &str, operation: BlackboardOperation) -> HttpResponse {
+    if in_namespace(operation.key(), &data.read_only) {
+        let message = format!("Key {} is read only", operation.key());
+        return error_response(RtError::new(RtStatus::AccessDenied, message));
+    }
+    match data.sandboxes.stage(id, operation) {
+        Ok(()) => HttpResponse::Ok().json(()),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Stages writing a value given as `{"type": "int", "value": 42}`.
+#[put("/api/sandbox/{id}/blackboard/{key:.*}")]
+async fn stage_set(
+    data: web::Data<AppData>,
+    path: web::Path<(String, String)>,
+    value: web::Json<TypedBlackboardValue>,
+) -> impl Responder {
+    let (id, key) = path.into_inner();
+    let value = value.into_inner();
+    stage(&data, &id, BlackboardOperation::Set { key, value })
+}
+
+/// Stages deleting a key.
+#[delete("/api/sandbox/{id}/blackboard/{key:.*}")]
+async fn stage_delete(
+    data: web::Data<AppData>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (id, key) = path.into_inner();
+    stage(&data, &id, BlackboardOperation::Delete { key })
+}
+
+/// Applies the staged changes all or none and closes the sandbox. Returns the result of every
+/// change like `/api/blackboard/batch`, with 409 if they were not applied, keeping the sandbox.
+#[post("/api/sandbox/{id}/commit")]
+async fn commit(data: web::Data<AppData>, id: web::Path<String>) -> HttpResponse {
+    let operations = match data.sandboxes.operations(&id) {
+        Ok(operations) => operations,
+        Err(e) => return error_response(e),
+    };
+    let client = data.clone();
+    match web::block(move || client.client.transaction(&operations)).await {
+        Ok(Ok(results)) if results.iter().all(|result| result.applied) => {
+            let _ = data.sandboxes.remove(&id);
+            HttpResponse::Ok().json(results)
+        }
+        Ok(Ok(results)) => HttpResponse::Conflict().json(results),
+        Ok(Err(e)) => error_response(e),
+        Err(e) => error_response(RtError::from(e.to_string())),
+    }
+}
+
+/// Discards the staged changes and closes the sandbox.
+#[delete("/api/sandbox/{id}")]
+async fn discard(data: web::Data<AppData>, id: web::Path<String>) -> impl Responder {
+    match data.sandboxes.remove(&id) {
+        Ok(()) => HttpResponse::Ok().json(()),
+        Err(e) => error_response(e),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(create);
+    cfg.service(preview);
+    cfg.service(schema);
+    cfg.service(stage_set);
+    cfg.service(stage_delete);
+    cfg.service(commit);
+    cfg.service(discard);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create() {
+        let sandboxes = Sandboxes::default();
+        let first = sandboxes.create().unwrap();
+        assert_eq!(first.len(), 32);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+
+        for _ in 1..MAX_SANDBOXES {
+            assert_ne!(sandboxes.create().unwrap(), first);
+        }
+        let full = sandboxes.create().unwrap_err();
+        assert_eq!(full.status, RtStatus::ConstraintViolation);
+
+        // a discarded one makes room
+        sandboxes.remove(&first).unwrap();
+        assert!(sandboxes.operations(&first).is_err());
+        assert_ne!(sandboxes.create().unwrap(), first);
+    }
+}